@@ -0,0 +1,199 @@
+//! Generating corpora for the benchmarks.
+//!
+//! All data is generated from a fixed seed so that runs are comparable. The
+//! shapes are modelled on what a recursive resolver or authoritative server
+//! typically sees: short names below a handful of zones, responses with a
+//! few answer records and glue, and zone files with a mix of record types.
+
+use domain::base::iana::{Class, Rcode, Rtype};
+use domain::base::{Dname, MessageBuilder, StaticCompressor, Ttl};
+use domain::rdata::{Aaaa, Mx, Ns, Txt, A};
+use std::fmt::Write;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Rng -----------------------------------------------------------
+
+/// A tiny deterministic pseudo-random number generator.
+///
+/// This is xorshift64 which is plenty for picking labels and addresses and
+/// avoids a dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Self {
+        Rng(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+//------------ Names ---------------------------------------------------------
+
+const ZONES: &[&str] = &[
+    "example.com",
+    "example.net",
+    "example.org",
+    "sub.example.com",
+    "a.long.chain.of.labels.example",
+];
+
+const LABELS: &[&str] = &[
+    "www",
+    "mail",
+    "ns1",
+    "ns2",
+    "api",
+    "cdn",
+    "static",
+    "login",
+    "m",
+    "shop",
+    "blog",
+    "dev",
+    "StagingArea",
+    "x",
+    "_tcp",
+    "_sip",
+];
+
+/// Returns `count` names below the benchmark zones.
+pub fn names(rng: &mut Rng, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let mut name = String::new();
+            for _ in 0..rng.below(3) {
+                name.push_str(LABELS[rng.below(LABELS.len())]);
+                name.push('.');
+            }
+            write!(name, "host{}.{}", i, ZONES[rng.below(ZONES.len())])
+                .unwrap();
+            name
+        })
+        .collect()
+}
+
+//------------ Messages ------------------------------------------------------
+
+/// Returns `count` response messages in wire format.
+///
+/// Each response answers an A, AAAA, MX, or TXT question with between one
+/// and eight records and adds NS records and glue. Names are compressed.
+pub fn messages(rng: &mut Rng, count: usize) -> Vec<Vec<u8>> {
+    let names = names(rng, 64);
+    let names: Vec<_> = names
+        .iter()
+        .map(|name| Dname::<Vec<u8>>::from_str(name).unwrap())
+        .collect();
+    (0..count)
+        .map(|_| {
+            let qname = &names[rng.below(names.len())];
+            let qtype =
+                [Rtype::A, Rtype::Aaaa, Rtype::Mx, Rtype::Txt][rng.below(4)];
+            let mut msg = MessageBuilder::from_target(StaticCompressor::new(
+                Vec::new(),
+            ))
+            .unwrap();
+            msg.header_mut().set_id(rng.next() as u16);
+            msg.header_mut().set_qr(true);
+            msg.header_mut().set_rcode(Rcode::NoError);
+            let mut msg = msg.question();
+            msg.push((qname, qtype, Class::In)).unwrap();
+            let mut msg = msg.answer();
+            let ttl = Ttl::from_secs(3600);
+            for i in 0..=rng.below(8) {
+                match qtype {
+                    Rtype::A => msg
+                        .push((
+                            qname,
+                            ttl,
+                            A::from_octets(192, 0, 2, i as u8),
+                        ))
+                        .unwrap(),
+                    Rtype::Aaaa => msg
+                        .push((
+                            qname,
+                            ttl,
+                            Aaaa::new(Ipv6Addr::new(
+                                0x2001, 0xdb8, 0, 0, 0, 0, 0, i as u16,
+                            )),
+                        ))
+                        .unwrap(),
+                    Rtype::Mx => msg
+                        .push((
+                            qname,
+                            ttl,
+                            Mx::new(
+                                i as u16 * 10,
+                                &names[rng.below(names.len())],
+                            ),
+                        ))
+                        .unwrap(),
+                    _ => msg
+                        .push((
+                            qname,
+                            ttl,
+                            Txt::<Vec<u8>>::build_from_slice(
+                                b"v=spf1 include:_spf.example.com ~all",
+                            )
+                            .unwrap(),
+                        ))
+                        .unwrap(),
+                }
+            }
+            let mut msg = msg.authority();
+            let ns1 = &names[rng.below(names.len())];
+            let ns2 = &names[rng.below(names.len())];
+            msg.push((qname, ttl, Ns::new(ns1))).unwrap();
+            msg.push((qname, ttl, Ns::new(ns2))).unwrap();
+            let mut msg = msg.additional();
+            msg.push((ns1, ttl, A::from_octets(198, 51, 100, 1)))
+                .unwrap();
+            msg.push((ns2, ttl, A::from_octets(198, 51, 100, 2)))
+                .unwrap();
+            msg.finish().into_target()
+        })
+        .collect()
+}
+
+//------------ Zone Files ----------------------------------------------------
+
+/// Returns a zone file with `count` records in presentation format.
+pub fn zonefile(rng: &mut Rng, count: usize) -> String {
+    let mut zone = String::from(
+        "$ORIGIN example.com.\n\
+         $TTL 3600\n\
+         @ IN SOA ns1 hostmaster (\n\
+         \t2024070101 ; serial\n\
+         \t7200 3600 1209600 3600 )\n\
+         \tIN NS ns1\n\
+         \tIN NS ns2.example.net.\n",
+    );
+    for i in 0..count {
+        let label = LABELS[rng.below(LABELS.len())];
+        match rng.below(5) {
+            0 => writeln!(zone, "{}{} IN A 192.0.2.{}", label, i, i % 256),
+            1 => writeln!(zone, "{}{} IN AAAA 2001:db8::{:x}", label, i, i),
+            2 => writeln!(zone, "{}{} 300 IN MX 10 mail{}", label, i, i),
+            3 => writeln!(
+                zone,
+                "{}{} IN TXT \"some text\" \"more text for {}\"",
+                label, i, i
+            ),
+            _ => writeln!(zone, "{}{} IN CNAME www", label, i),
+        }
+        .unwrap();
+    }
+    zone
+}
@@ -0,0 +1,286 @@
+//! Benchmarks for the parsing and composing hot paths.
+//!
+//! Run with `cargo bench --features std,zonefile`. A benchmark name given
+//! as an argument limits the run to benchmarks whose name contains it.
+//! With the `resolv` feature, the answer cache is benchmarked, too.
+//!
+//! The benchmarks use [criterion] which compares each run with the results
+//! of the previous one kept in `target/criterion`.
+//!
+//! [criterion]: https://crates.io/crates/criterion
+
+mod corpus;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use domain::base::iana::Rtype;
+use domain::base::name::ParsedDname;
+use domain::base::{
+    Dname, Message, MessageBuilder, StaticCompressor, ToDname, Ttl,
+};
+use domain::rdata::AllRecordData;
+use domain::zonefile::inplace::{Entry, Zonefile};
+use std::collections::HashMap;
+use std::str::FromStr;
+#[cfg(feature = "resolv")]
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ Benchmarks ----------------------------------------------------
+
+fn parse_messages(c: &mut Criterion) {
+    let messages = corpus::messages(&mut corpus::Rng::new(), 256);
+    let messages: Vec<_> = messages
+        .iter()
+        .map(|octets| Message::from_octets(octets.as_slice()).unwrap())
+        .collect();
+
+    c.bench_function("parse/header_counts", |b| {
+        b.iter(|| {
+            messages
+                .iter()
+                .map(|msg| msg.header_counts().ancount())
+                .sum::<u16>()
+        })
+    });
+    c.bench_function("parse/records", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for msg in &messages {
+                for item in msg.iter() {
+                    let (record, _) = item.unwrap();
+                    let record = record
+                        .into_record::<AllRecordData<_, ParsedDname<_>>>()
+                        .unwrap();
+                    count += usize::from(record.is_some());
+                }
+            }
+            count
+        })
+    });
+    c.bench_function("parse/check_strict", |b| {
+        b.iter(|| {
+            messages
+                .iter()
+                .filter(|msg| msg.check_strict().is_ok())
+                .count()
+        })
+    });
+}
+
+fn compose_messages(c: &mut Criterion) {
+    let names = corpus::names(&mut corpus::Rng::new(), 64);
+    let names: Vec<_> = names
+        .iter()
+        .map(|name| Dname::<Vec<u8>>::from_str(name).unwrap())
+        .collect();
+    let ttl = Ttl::from_secs(3600);
+
+    c.bench_function("compose/uncompressed", |b| {
+        b.iter(|| {
+            let mut msg = MessageBuilder::new_vec().answer();
+            for (i, name) in names.iter().enumerate() {
+                msg.push((name, ttl, domain::rdata::Ns::new(&names[i / 2])))
+                    .unwrap();
+            }
+            msg.finish().len()
+        })
+    });
+    c.bench_function("compose/static_compressor", |b| {
+        b.iter(|| {
+            let mut msg = MessageBuilder::from_target(StaticCompressor::new(
+                Vec::new(),
+            ))
+            .unwrap()
+            .answer();
+            for (i, name) in names.iter().enumerate() {
+                msg.push((name, ttl, domain::rdata::Ns::new(&names[i / 2])))
+                    .unwrap();
+            }
+            msg.finish().as_slice().len()
+        })
+    });
+    c.bench_function("compose/tree_compressor", |b| {
+        b.iter(|| {
+            let mut msg = MessageBuilder::from_target(
+                domain::base::TreeCompressor::new(Vec::new()),
+            )
+            .unwrap()
+            .answer();
+            for (i, name) in names.iter().enumerate() {
+                msg.push((name, ttl, domain::rdata::Ns::new(&names[i / 2])))
+                    .unwrap();
+            }
+            msg.finish().as_slice().len()
+        })
+    });
+}
+
+fn scan_zonefile(c: &mut Criterion) {
+    let zone = corpus::zonefile(&mut corpus::Rng::new(), 1000);
+
+    c.bench_function("zonefile/scan_1000", |b| {
+        b.iter(|| {
+            let mut zone = Zonefile::from(zone.as_str());
+            let mut count = 0;
+            while let Some(entry) = zone.next_entry().unwrap() {
+                if let Entry::Record(_) = entry {
+                    count += 1;
+                }
+            }
+            count
+        })
+    });
+}
+
+fn cache_lookups(c: &mut Criterion) {
+    // A cache is keyed by name and record type. Lookups are done with the
+    // name as it appears in a query which may differ in case.
+    let names = corpus::names(&mut corpus::Rng::new(), 4096);
+    let cache: HashMap<_, _> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            ((Dname::<Vec<u8>>::from_str(name).unwrap(), Rtype::A), i)
+        })
+        .collect();
+    let queries: Vec<_> = names
+        .iter()
+        .step_by(7)
+        .map(|name| Dname::<Vec<u8>>::from_str(&name.to_uppercase()).unwrap())
+        .collect();
+
+    c.bench_function("cache/lookup", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .filter(|name| {
+                    cache.contains_key(&((*name).clone(), Rtype::A))
+                })
+                .count()
+        })
+    });
+    c.bench_function("cache/name_eq", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for (query, name) in queries.iter().zip(cache.keys()) {
+                count += usize::from(query.name_eq(&name.0));
+            }
+            count
+        })
+    });
+    c.bench_function("cache/name_cmp", |b| {
+        b.iter(|| {
+            let mut sorted: Vec<_> = queries.clone();
+            sorted.sort_by(|left, right| left.name_cmp(right));
+            sorted.len()
+        })
+    });
+}
+
+#[cfg(feature = "resolv")]
+fn cache_contention(c: &mut Criterion) {
+    use domain::base::Question;
+    use domain::resolv::stub::cache::Cache;
+    use domain::resolv::stub::Answer;
+    use std::sync::Mutex;
+
+    // Answers are looked up by many tasks at once. Compare a single map
+    // behind one mutex – the design the validator used to have – with the
+    // sharded cache.
+    let answers: Vec<Answer> = corpus::messages(&mut corpus::Rng::new(), 256)
+        .into_iter()
+        .map(|octets| Message::from_octets(octets.into()).unwrap().into())
+        .collect();
+    let questions: Vec<Question<Dname<Vec<u8>>>> = answers
+        .iter()
+        .map(|answer| {
+            let question = answer.first_question().unwrap();
+            Question::new(
+                question.qname().to_vec(),
+                question.qtype(),
+                question.qclass(),
+            )
+        })
+        .collect();
+
+    let single = Mutex::new(HashMap::new());
+    let expires = Instant::now() + Duration::from_secs(3600);
+    for (question, answer) in questions.iter().zip(&answers) {
+        single
+            .lock()
+            .unwrap()
+            .insert(question.clone(), (answer.clone(), expires));
+    }
+    let sharded = Cache::new(1024);
+    for answer in &answers {
+        sharded.insert(answer.clone());
+    }
+
+    c.bench_function("cache/single_mutex", |b| {
+        b.iter(|| {
+            contend(&questions, |question| {
+                match single.lock().unwrap().get(question) {
+                    Some((answer, expires)) if *expires > Instant::now() => {
+                        Some(answer.clone())
+                    }
+                    _ => None,
+                }
+            })
+        })
+    });
+    c.bench_function("cache/sharded", |b| {
+        b.iter(|| contend(&questions, |question| sharded.get(question)))
+    });
+}
+
+/// Looks up questions from a number of threads at once.
+#[cfg(feature = "resolv")]
+fn contend<Q: Sync, A>(
+    questions: &[Q],
+    lookup: impl Fn(&Q) -> Option<A> + Sync,
+) -> usize {
+    const THREADS: usize = 4;
+    const LOOKUPS: usize = 4096;
+
+    std::thread::scope(|scope| {
+        let lookup = &lookup;
+        let threads: Vec<_> = (0..THREADS)
+            .map(|i| {
+                scope.spawn(move || {
+                    questions
+                        .iter()
+                        .cycle()
+                        .skip(i * 61)
+                        .take(LOOKUPS)
+                        .filter(|question| lookup(question).is_some())
+                        .count()
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .sum()
+    })
+}
+
+//------------ Main ----------------------------------------------------------
+
+#[cfg(not(feature = "resolv"))]
+criterion_group!(
+    benches,
+    parse_messages,
+    compose_messages,
+    scan_zonefile,
+    cache_lookups
+);
+#[cfg(feature = "resolv")]
+criterion_group!(
+    benches,
+    parse_messages,
+    compose_messages,
+    scan_zonefile,
+    cache_lookups,
+    cache_contention
+);
+criterion_main!(benches);
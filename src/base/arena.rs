@@ -0,0 +1,171 @@
+//! Allocating owned data for a message from a shared buffer.
+//!
+//! Data parsed from a message borrows from the message’s octets sequence.
+//! If it needs to outlive the message – in order to keep it in a cache or
+//! send it to another task, for instance –, it has to be converted into
+//! owned data which normally means an allocation for each name and each
+//! record.
+//!
+//! The [`Arena`] provided by this module avoids these allocations. It copies
+//! names and records into one large buffer and hands out
+//! [`Bytes`][bytes::Bytes] values referencing the part of the buffer
+//! they occupy. Once all values handed out have been dropped,
+//! [`Arena::reset`] makes the buffer available again without allocating a
+//! new one. This way, a server can handle message after message using only
+//! the arena’s buffer.
+#![cfg(feature = "bytes")]
+#![cfg_attr(docsrs, doc(cfg(feature = "bytes")))]
+
+use super::name::{Dname, ParsedDname, ToDname};
+use super::rdata::{ComposeRecordData, ParseRecordData, RecordData};
+use super::record::Record;
+use super::wire::ParseError;
+use bytes::{Bytes, BytesMut};
+use octseq::builder::infallible;
+use octseq::parse::Parser;
+
+//------------ Arena ---------------------------------------------------------
+
+/// A buffer for allocating owned data for messages.
+///
+/// The arena keeps a buffer of a given capacity. Each of the methods
+/// allocating data from it appends the data to the buffer and returns a
+/// [`Bytes`] value or a type based on it that shares the buffer. If the
+/// buffer runs out of space, a new buffer of the same capacity – or larger
+/// if the data requires it – is allocated.
+///
+/// Call [`reset`][Self::reset] after you are done with a message. If all
+/// values allocated from the arena have been dropped at that point, the
+/// buffer is reused. Otherwise, the values keep their part of the buffer
+/// alive and a new buffer is allocated.
+#[derive(Clone, Debug)]
+pub struct Arena {
+    /// The unused part of the buffer.
+    buf: BytesMut,
+
+    /// The capacity of the buffer.
+    capacity: usize,
+}
+
+impl Arena {
+    /// Creates a new arena with a buffer of the given capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Arena {
+            buf: BytesMut::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns the number of octets still available in the current buffer.
+    pub fn remaining(&self) -> usize {
+        self.buf.capacity() - self.buf.len()
+    }
+
+    /// Makes the buffer available again.
+    ///
+    /// If all values allocated from the arena have been dropped, the
+    /// current buffer is reused without allocating.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.buf.reserve(self.capacity);
+    }
+
+    /// Allocates a copy of an octets slice.
+    pub fn octets(&mut self, octets: &[u8]) -> Bytes {
+        self.reserve(octets.len());
+        self.buf.extend_from_slice(octets);
+        self.buf.split().freeze()
+    }
+
+    /// Allocates a copy of a domain name.
+    ///
+    /// The name is copied in its uncompressed form.
+    pub fn dname<N: ToDname + ?Sized>(&mut self, name: &N) -> Dname<Bytes> {
+        self.reserve(name.compose_len().into());
+        infallible(name.compose(&mut self.buf));
+        // Safety: We just composed a valid name.
+        unsafe { Dname::from_octets_unchecked(self.buf.split().freeze()) }
+    }
+
+    /// Allocates a copy of a record.
+    ///
+    /// The record is copied in wire format without name compression and
+    /// then parsed as a record with data of type `Data`. This type must be
+    /// able to parse record data of the record’s type. Typically, it will be
+    /// the same type as used for parsing the record from the message in the
+    /// first place, but based on `Bytes`, such as
+    /// `AllRecordData<Bytes, ParsedDname<Bytes>>`.
+    ///
+    /// Returns `Ok(None)` if `Data` doesn’t know the record type.
+    pub fn record<N, D, Data>(
+        &mut self,
+        record: &Record<N, D>,
+    ) -> Result<Option<Record<ParsedDname<Bytes>, Data>>, ParseError>
+    where
+        N: ToDname,
+        D: RecordData + ComposeRecordData,
+        Data: for<'a> ParseRecordData<'a, Bytes>,
+    {
+        if let Some(len) = record.compose_len() {
+            self.reserve(len);
+        }
+        infallible(record.compose(&mut self.buf));
+        let octets = self.buf.split().freeze();
+        Record::parse(&mut Parser::from_ref(&octets))
+    }
+
+    /// Makes sure the buffer has space for `len` more octets.
+    fn reserve(&mut self, len: usize) {
+        if self.remaining() < len {
+            self.buf = BytesMut::with_capacity(self.capacity.max(len));
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::record::Ttl;
+    use crate::rdata::{AllRecordData, Mx};
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn allocate() {
+        let mut arena = Arena::with_capacity(256);
+        let start = arena.buf.as_ptr();
+
+        let name = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        let owned_name = arena.dname(&name);
+        assert_eq!(owned_name, name);
+        assert_eq!(owned_name.as_slice().as_ptr(), start);
+
+        let record = Record::new(
+            &name,
+            Class::In,
+            Ttl::from_secs(3600),
+            Mx::new(10, &name),
+        );
+        let owned: Record<_, AllRecordData<Bytes, ParsedDname<Bytes>>> =
+            arena.record(&record).unwrap().unwrap();
+        assert_eq!(owned.owner().to_string(), "example.com");
+        assert_eq!(owned.data().to_string(), "10 example.com.");
+        assert_eq!(arena.remaining(), 256 - 13 - 13 - 10 - 2 - 13);
+
+        // With all values dropped, the buffer is reused.
+        drop(owned_name);
+        drop(owned);
+        arena.reset();
+        assert_eq!(arena.remaining(), 256);
+        assert_eq!(arena.octets(b"foo").as_ptr(), start);
+
+        // Data larger than the capacity goes elsewhere.
+        assert_eq!(arena.octets(&[0; 1000]).len(), 1000);
+        assert_eq!(arena.octets(b"bar").as_ref(), b"bar");
+    }
+}
@@ -0,0 +1,383 @@
+//! Diagnostic queries in the CHAOS class.
+//!
+//! Many name servers answer TXT queries in the CHAOS class for a handful of
+//! well-known names with information about themselves, such as their
+//! software version or the name of the particular server instance that
+//! answered. These queries are commonly used by diagnostic tools to find
+//! out which instance of an anycast service is being talked to.
+//!
+//! The names are defined by BIND’s conventions and, in the case of
+//! `id.server` and `version.server`, by [RFC 4892]. They are represented by
+//! the [`ChaosName`] enum.
+//!
+//! Server implementations can use [`ChaosResponder`] to answer these
+//! queries with configured strings. It requires the `std` feature. For
+//! sending these queries, the stub resolver provides
+#![cfg_attr(
+    feature = "resolv",
+    doc = "[`lookup_chaos`][crate::resolv::lookup::lookup_chaos]."
+)]
+#![cfg_attr(not(feature = "resolv"), doc = "`lookup_chaos`.")]
+//!
+//! [RFC 4892]: https://tools.ietf.org/html/rfc4892
+
+#[cfg(feature = "std")]
+use super::iana::Rcode;
+use super::iana::{Class, Rtype};
+use super::message::Message;
+#[cfg(feature = "std")]
+use super::message_builder::{AnswerBuilder, MessageBuilder, PushError};
+use super::name::{Dname, ToDname};
+use super::question::Question;
+#[cfg(feature = "std")]
+use super::wire::Composer;
+#[cfg(feature = "std")]
+use crate::rdata::Txt;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use octseq::builder::ShortBuf;
+use octseq::octets::Octets;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+//------------ ChaosName -----------------------------------------------------
+
+/// The well-known names queried in the CHAOS class.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ChaosName {
+    /// `version.bind`: the server software and its version.
+    VersionBind,
+
+    /// `hostname.bind`: the host name of the server instance.
+    HostnameBind,
+
+    /// `id.server`: an identifier of the server instance.
+    ///
+    /// This is the standardized version of `hostname.bind`.
+    IdServer,
+
+    /// `version.server`: the server software and its version.
+    ///
+    /// This is the standardized version of `version.bind`.
+    VersionServer,
+}
+
+impl ChaosName {
+    /// All the known names.
+    pub const ALL: [ChaosName; 4] = [
+        ChaosName::VersionBind,
+        ChaosName::HostnameBind,
+        ChaosName::IdServer,
+        ChaosName::VersionServer,
+    ];
+
+    /// Returns the name as a string without the trailing dot.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChaosName::VersionBind => "version.bind",
+            ChaosName::HostnameBind => "hostname.bind",
+            ChaosName::IdServer => "id.server",
+            ChaosName::VersionServer => "version.server",
+        }
+    }
+
+    /// Returns the name as a domain name.
+    pub fn to_dname(self) -> &'static Dname<[u8]> {
+        Dname::from_slice(match self {
+            ChaosName::VersionBind => b"\x07version\x04bind\x00",
+            ChaosName::HostnameBind => b"\x08hostname\x04bind\x00",
+            ChaosName::IdServer => b"\x02id\x06server\x00",
+            ChaosName::VersionServer => b"\x07version\x06server\x00",
+        })
+        .expect("bad static domain name")
+    }
+
+    /// Returns the question for querying the name.
+    ///
+    /// This is a question for the TXT record type in the CHAOS class.
+    pub fn question(self) -> Question<&'static Dname<[u8]>> {
+        Question::new(self.to_dname(), Rtype::Txt, Class::Ch)
+    }
+
+    /// Returns the name for a domain name if it is one of the known names.
+    ///
+    /// The comparison is case-insensitive.
+    pub fn from_dname(name: &impl ToDname) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|item| item.to_dname().name_eq(name))
+    }
+
+    /// Returns the name asked for if the question is a CHAOS query.
+    ///
+    /// This is the case if the question is for the TXT or ANY record types
+    /// in the CHAOS class and the name is one of the known names.
+    pub fn from_question<N: ToDname>(question: &Question<N>) -> Option<Self> {
+        if question.qclass() != Class::Ch
+            || !matches!(question.qtype(), Rtype::Txt | Rtype::Any)
+        {
+            return None;
+        }
+        Self::from_dname(question.qname())
+    }
+
+    /// Returns the name asked for if the message is a CHAOS query.
+    ///
+    /// The message needs to have exactly one question that satisfies
+    /// [`from_question`][Self::from_question].
+    pub fn from_message<Octs: Octets>(msg: &Message<Octs>) -> Option<Self> {
+        if msg.header().qr() || msg.header_counts().qdcount() != 1 {
+            return None;
+        }
+        Self::from_question(&msg.first_question()?)
+    }
+}
+
+//--- FromStr
+
+impl FromStr for ChaosName {
+    type Err = UnknownChaosName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_suffix('.').unwrap_or(s);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|item| item.as_str().eq_ignore_ascii_case(s))
+            .ok_or(UnknownChaosName)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for ChaosName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+//------------ ChaosResponder ------------------------------------------------
+
+/// Answers CHAOS queries with configured strings.
+///
+/// The responder keeps an optional string for the server’s version, its
+/// host name, and its identifier. Queries for `version.bind` and
+/// `version.server` are answered with the version, queries for
+/// `hostname.bind` with the host name, and queries for `id.server` with
+/// the identifier or, if there is none, the host name.
+///
+/// Queries for names that have no string configured are refused. This is
+/// what operators who don’t want to disclose this information expect.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct ChaosResponder {
+    /// The text for `version.bind` and `version.server`.
+    version: Option<Txt<Vec<u8>>>,
+
+    /// The text for `hostname.bind`.
+    hostname: Option<Txt<Vec<u8>>>,
+
+    /// The text for `id.server`.
+    id: Option<Txt<Vec<u8>>>,
+}
+
+#[cfg(feature = "std")]
+impl ChaosResponder {
+    /// Creates a new responder that refuses all queries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the version string.
+    ///
+    /// Returns an error if the string is too long for a TXT record.
+    pub fn set_version(
+        &mut self,
+        version: Option<&str>,
+    ) -> Result<(), ShortBuf> {
+        self.version = Self::txt(version)?;
+        Ok(())
+    }
+
+    /// Sets the host name string.
+    ///
+    /// Returns an error if the string is too long for a TXT record.
+    pub fn set_hostname(
+        &mut self,
+        hostname: Option<&str>,
+    ) -> Result<(), ShortBuf> {
+        self.hostname = Self::txt(hostname)?;
+        Ok(())
+    }
+
+    /// Sets the server identifier string.
+    ///
+    /// Returns an error if the string is too long for a TXT record.
+    pub fn set_id(&mut self, id: Option<&str>) -> Result<(), ShortBuf> {
+        self.id = Self::txt(id)?;
+        Ok(())
+    }
+
+    fn txt(text: Option<&str>) -> Result<Option<Txt<Vec<u8>>>, ShortBuf> {
+        text.map(|text| Txt::build_from_slice(text.as_bytes()))
+            .transpose()
+    }
+
+    /// Returns the text to answer a query for the given name with.
+    pub fn text(&self, name: ChaosName) -> Option<&Txt<Vec<u8>>> {
+        match name {
+            ChaosName::VersionBind | ChaosName::VersionServer => {
+                self.version.as_ref()
+            }
+            ChaosName::HostnameBind => self.hostname.as_ref(),
+            ChaosName::IdServer => {
+                self.id.as_ref().or(self.hostname.as_ref())
+            }
+        }
+    }
+
+    /// Answers a request if it is a CHAOS query.
+    ///
+    /// If `request` is a query as determined by
+    /// [`ChaosName::from_message`], the method starts an answer using
+    /// `builder` and returns it. The answer contains the configured text
+    /// or, if there is none, is refused. The returned answer builder can be
+    /// used to add further sections before finishing the message.
+    ///
+    /// Returns `Ok(None)` if `request` isn’t a CHAOS query.
+    pub fn answer<Octs: Octets, Target: Composer>(
+        &self,
+        request: &Message<Octs>,
+        builder: MessageBuilder<Target>,
+    ) -> Result<Option<AnswerBuilder<Target>>, PushError> {
+        let name = match ChaosName::from_message(request) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let text = match self.text(name) {
+            Some(text) => text,
+            None => {
+                return builder
+                    .start_answer(request, Rcode::Refused)
+                    .map(Some)
+            }
+        };
+        let mut answer = builder.start_answer(request, Rcode::NoError)?;
+        answer.header_mut().set_aa(true);
+        answer.push((name.to_dname(), Class::Ch, 0, text))?;
+        Ok(Some(answer))
+    }
+}
+
+//------------ UnknownChaosName ----------------------------------------------
+
+/// A string was not one of the known CHAOS names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnknownChaosName;
+
+impl fmt::Display for UnknownChaosName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("unknown CHAOS name")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownChaosName {}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    fn query(name: ChaosName) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push(name.question()).unwrap();
+        msg.into_message()
+    }
+
+    #[test]
+    fn names() {
+        for name in ChaosName::ALL {
+            assert_eq!(name.as_str().parse::<ChaosName>(), Ok(name));
+            assert_eq!(ChaosName::from_dname(&name.to_dname()), Some(name));
+        }
+        assert_eq!(
+            ChaosName::from_dname(
+                &Dname::<Vec<u8>>::from_str("ID.Server.").unwrap()
+            ),
+            Some(ChaosName::IdServer)
+        );
+        assert_eq!(
+            "version.bind.".parse::<ChaosName>(),
+            Ok(ChaosName::VersionBind)
+        );
+        assert!("version.example".parse::<ChaosName>().is_err());
+
+        // Only the CHAOS class counts.
+        assert_eq!(
+            ChaosName::from_question(&Question::new(
+                ChaosName::IdServer.to_dname(),
+                Rtype::Txt,
+                Class::In
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn respond() {
+        let mut responder = ChaosResponder::new();
+        responder.set_version(Some("domain 0.8")).unwrap();
+        responder.set_hostname(Some("ns1.example")).unwrap();
+
+        let answer = responder
+            .answer(
+                &query(ChaosName::VersionServer),
+                MessageBuilder::new_vec(),
+            )
+            .unwrap()
+            .unwrap()
+            .into_message();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        let record = answer
+            .answer()
+            .unwrap()
+            .limit_to::<Txt<_>>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.class(), Class::Ch);
+        assert_eq!(
+            record.data().as_flat_slice(),
+            Some(b"domain 0.8".as_ref())
+        );
+
+        // id.server falls back to the host name.
+        let answer = responder
+            .answer(&query(ChaosName::IdServer), MessageBuilder::new_vec())
+            .unwrap()
+            .unwrap()
+            .into_message();
+        assert_eq!(answer.header_counts().ancount(), 1);
+
+        // Unset strings are refused.
+        responder.set_version(None).unwrap();
+        let answer = responder
+            .answer(&query(ChaosName::VersionBind), MessageBuilder::new_vec())
+            .unwrap()
+            .unwrap()
+            .into_message();
+        assert_eq!(answer.header().rcode(), Rcode::Refused);
+
+        // Other queries are left alone.
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((Dname::<Vec<u8>>::root_vec(), Rtype::A)).unwrap();
+        assert!(responder
+            .answer(&msg.into_message(), MessageBuilder::new_vec())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -235,6 +235,15 @@ impl<Octs: AsRef<[u8]> + ?Sized> CharStr<Octs> {
             octets: self.as_slice(),
         }
     }
+
+    /// Returns an object that displays the string in quotes.
+    ///
+    /// This is how character strings appear in zone files. Unlike the
+    /// unquoted output of the `Display` implementation of the type itself,
+    /// it can be scanned again even if the string is empty.
+    pub fn display_quoted(&self) -> DisplayQuoted<'_> {
+        DisplayQuoted(self.as_slice())
+    }
 }
 
 impl CharStr<[u8]> {
@@ -321,7 +330,9 @@ where
 
 //--- AsRef and AsMut
 //
-// No Borrow as character strings compare ignoring case.
+// No Borrow as character strings compare ignoring case. AsMut only for
+// slices as mutable access to the octets sequence itself would allow
+// growing the string beyond 255 octets.
 
 impl<Octets: AsRef<U> + ?Sized, U: ?Sized> AsRef<U> for CharStr<Octets> {
     fn as_ref(&self) -> &U {
@@ -329,8 +340,8 @@ impl<Octets: AsRef<U> + ?Sized, U: ?Sized> AsRef<U> for CharStr<Octets> {
     }
 }
 
-impl<Octets: AsMut<U> + ?Sized, U: ?Sized> AsMut<U> for CharStr<Octets> {
-    fn as_mut(&mut self) -> &mut U {
+impl<Octets: AsMut<[u8]> + ?Sized> AsMut<[u8]> for CharStr<Octets> {
+    fn as_mut(&mut self) -> &mut [u8] {
         self.0.as_mut()
     }
 }
@@ -786,6 +797,24 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+//------------ DisplayQuoted -------------------------------------------------
+
+/// A character string displayed in quotes.
+///
+/// A value of this type is returned by [`CharStr::display_quoted`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayQuoted<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for DisplayQuoted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for &ch in self.0 {
+            fmt::Display::fmt(&Symbol::from_octet(ch), f)?;
+        }
+        f.write_str("\"")
+    }
+}
+
 //============ Error Types ===================================================
 
 //------------ CharStrError --------------------------------------------------
@@ -1003,6 +1032,20 @@ mod test {
         assert_eq!(o.len(), 255);
     }
 
+    #[test]
+    fn display_quoted() {
+        use std::string::{String, ToString};
+
+        fn fmt(s: &[u8]) -> String {
+            CharStr::from_slice(s).unwrap().display_quoted().to_string()
+        }
+
+        assert_eq!(fmt(b""), "\"\"");
+        assert_eq!(fmt(b"foo"), "\"foo\"");
+        assert_eq!(fmt(b"a \"b\";(c)"), "\"a\\ \\\"b\\\"\\;\\(c\\)\"");
+        assert_eq!(fmt(b"\x00\xff"), "\"\\000\\255\"");
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn ser_de() {
@@ -0,0 +1,340 @@
+//! Displaying messages in the style of dig.
+//!
+//! The output of the `dig` tool that comes with BIND has become the de-facto
+//! standard for showing the content of a DNS message to humans. This module
+//! provides the [`DisplayDig`] type which renders a message in the same
+//! layout: a header line with opcode, status, and message ID, a line with
+//! the flags and section counts, the OPT pseudosection with the EDNS details
+//! if the message has an OPT record, and finally each non-empty section with
+//! its records in presentation format.
+//!
+//! A value of this type is created via [`Message::display_dig`], so you can
+//! simply do something like this:
+//!
+//! ```
+//! use domain::base::{Dname, MessageBuilder, Rtype};
+//! use std::str::FromStr;
+//!
+//! let mut msg = MessageBuilder::new_vec().question();
+//! msg.push((Dname::<Vec<u8>>::from_str("example.com").unwrap(), Rtype::A))
+//!     .unwrap();
+//! let msg = msg.into_message();
+//! println!("{}", msg.display_dig());
+//! ```
+//!
+//! Only the parts of dig’s output that are derived from the message itself
+//! are printed, there are no comments on the query time or server.
+
+use super::iana::{OptionCode, Rtype};
+use super::message::Message;
+use super::name::ParsedDname;
+use super::opt::{AllOptData, OptData};
+use super::wire::ParseError;
+use crate::rdata::AllRecordData;
+use core::fmt;
+use octseq::octets::Octets;
+
+//------------ Extending Message ---------------------------------------------
+
+impl<Octs: Octets> Message<Octs> {
+    /// Returns a value that displays the message in the style of dig.
+    pub fn display_dig(&self) -> DisplayDig<'_, Octs> {
+        DisplayDig(self)
+    }
+}
+
+//------------ DisplayDig ----------------------------------------------------
+
+/// A helper type for displaying a message in the style of dig.
+///
+/// A value of this type can be obtained via [`Message::display_dig`]. See
+/// the [module documentation][self] for details.
+///
+/// If parts of the message cannot be parsed, the output ends with a comment
+/// line describing the error.
+pub struct DisplayDig<'a, Octs: ?Sized>(&'a Message<Octs>);
+
+impl<'a, Octs: Octets> DisplayDig<'a, Octs> {
+    /// Writes the header line and the flags line.
+    fn write_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = self.0.header();
+        let counts = self.0.header_counts();
+        let rcode = match self.0.opt() {
+            Some(opt) => opt.rcode(header),
+            None => header.rcode().into(),
+        };
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            header.opcode(),
+            rcode,
+            header.id()
+        )?;
+        f.write_str(";; flags:")?;
+        for (set, name) in [
+            (header.qr(), " qr"),
+            (header.aa(), " aa"),
+            (header.tc(), " tc"),
+            (header.rd(), " rd"),
+            (header.ra(), " ra"),
+            (header.ad(), " ad"),
+            (header.cd(), " cd"),
+        ] {
+            if set {
+                f.write_str(name)?;
+            }
+        }
+        writeln!(
+            f,
+            "; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            counts.qdcount(),
+            counts.ancount(),
+            counts.nscount(),
+            counts.arcount()
+        )
+    }
+
+    /// Writes the OPT pseudosection if there is an OPT record.
+    fn write_opt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opt = match self.0.opt() {
+            Some(opt) => opt,
+            None => return Ok(()),
+        };
+        f.write_str("\n;; OPT PSEUDOSECTION:\n")?;
+        write!(f, "; EDNS: version: {}, flags:", opt.version())?;
+        if opt.dnssec_ok() {
+            f.write_str(" do")?;
+        }
+        writeln!(f, "; udp: {}", opt.udp_payload_size())?;
+        for option in opt.opt().iter::<AllOptData<_, _>>() {
+            match option {
+                Ok(option) => write_option(f, &option)?,
+                Err(err) => return write_error(f, err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the question section.
+    fn write_question(&self, f: &mut fmt::Formatter) -> Result<(), Error> {
+        if self.0.header_counts().qdcount() == 0 {
+            return Ok(());
+        }
+        f.write_str("\n;; QUESTION SECTION:\n")?;
+        for question in self.0.question() {
+            let question = question?;
+            writeln!(
+                f,
+                ";{}.\t\t{}\t{}",
+                question.qname(),
+                question.qclass(),
+                question.qtype()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the three record sections.
+    fn write_records(&self, f: &mut fmt::Formatter) -> Result<(), Error> {
+        let counts = self.0.header_counts();
+        let arcount = counts
+            .arcount()
+            .saturating_sub(u16::from(self.0.opt().is_some()));
+        for (count, label, section) in [
+            (counts.ancount(), "ANSWER", self.0.answer()),
+            (counts.nscount(), "AUTHORITY", self.0.authority()),
+            (arcount, "ADDITIONAL", self.0.additional()),
+        ] {
+            if count == 0 {
+                continue;
+            }
+            writeln!(f, "\n;; {} SECTION:", label)?;
+            for record in section? {
+                let record = match record?
+                    .into_record::<AllRecordData<_, ParsedDname<_>>>()?
+                {
+                    Some(record) => record,
+                    None => continue,
+                };
+                if record.rtype() == Rtype::Opt {
+                    continue;
+                }
+                writeln!(
+                    f,
+                    "{}.\t{}\t{}\t{}\t{}",
+                    record.owner(),
+                    record.ttl().as_secs(),
+                    record.class(),
+                    record.rtype(),
+                    record.data()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//--- Display
+
+impl<'a, Octs: Octets> fmt::Display for DisplayDig<'a, Octs> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_header(f)?;
+        self.write_opt(f)?;
+        match self.write_question(f).and_then(|_| self.write_records(f)) {
+            Ok(()) => Ok(()),
+            Err(Error::Fmt(err)) => Err(err),
+            Err(Error::Parse(err)) => write_error(f, err),
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Writes a single EDNS option using dig’s labels.
+fn write_option<Octs: AsRef<[u8]>, Name: fmt::Display>(
+    f: &mut fmt::Formatter,
+    option: &AllOptData<Octs, Name>,
+) -> fmt::Result {
+    if let AllOptData::ExtendedError(ref ede) = *option {
+        write!(f, "; EDE: {} ({})", ede.code().to_int(), ede.code())?;
+        if let Some(Ok(text)) = ede.text() {
+            write!(f, ": ({})", text)?;
+        }
+        return f.write_str("\n");
+    }
+    let label = match option.code() {
+        OptionCode::Nsid => "NSID",
+        OptionCode::Dau => "DAU",
+        OptionCode::Dhu => "DHU",
+        OptionCode::N3u => "N3U",
+        OptionCode::ClientSubnet => "CLIENT-SUBNET",
+        OptionCode::Expire => "EXPIRE",
+        OptionCode::Cookie => "COOKIE",
+        OptionCode::TcpKeepalive => "TCP-KEEPALIVE",
+        OptionCode::Padding => "PADDING",
+        OptionCode::Chain => "CHAIN",
+        OptionCode::KeyTag => "KEY-TAG",
+        code => return writeln!(f, "; OPT={}: {}", code.to_int(), option),
+    };
+    writeln!(f, "; {}: {}", label, option)
+}
+
+/// Writes a comment line for a parse error.
+fn write_error(f: &mut fmt::Formatter, err: ParseError) -> fmt::Result {
+    writeln!(f, ";; ERROR: failed to parse message: {}", err)
+}
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened while displaying a section.
+enum Error {
+    /// The formatter failed.
+    Fmt(fmt::Error),
+
+    /// Parsing the message failed.
+    Parse(ParseError),
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Error::Fmt(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::iana::OptRcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::KeyTag;
+    use crate::rdata::{Ns, A};
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn display_dig() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((name("example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com"), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            opt.set_dnssec_ok(true);
+            opt.set_rcode(OptRcode::BadVers);
+            opt.key_tag(KeyTag::from_slice(b"\x4f\x66").unwrap())
+        })
+        .unwrap();
+        let msg = msg.into_message();
+        assert_eq!(
+            msg.display_dig().to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: BADVER, id: 4711\n\
+             ;; flags: qr rd; QUERY: 1, ANSWER: 1, AUTHORITY: 0, \
+             ADDITIONAL: 1\n\
+             \n\
+             ;; OPT PSEUDOSECTION:\n\
+             ; EDNS: version: 0, flags: do; udp: 1232\n\
+             ; KEY-TAG: 20326\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;example.com.\t\tIN\tA\n\
+             \n\
+             ;; ANSWER SECTION:\n\
+             example.com.\t3600\tIN\tA\t192.0.2.1\n"
+        );
+    }
+
+    #[test]
+    fn display_dig_without_opt() {
+        let mut msg = MessageBuilder::new_vec().authority();
+        msg.push((name("example.com"), 86400, Ns::new(name("ns.example"))))
+            .unwrap();
+        let msg = msg.into_message();
+        assert_eq!(
+            msg.display_dig().to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 0\n\
+             ;; flags:; QUERY: 0, ANSWER: 0, AUTHORITY: 1, ADDITIONAL: 0\n\
+             \n\
+             ;; AUTHORITY SECTION:\n\
+             example.com.\t86400\tIN\tNS\tns.example.\n"
+        );
+    }
+
+    #[test]
+    fn display_dig_broken() {
+        let mut msg = MessageBuilder::new_vec().answer();
+        msg.push((name("example.com"), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut octets = msg.finish();
+        octets.truncate(octets.len() - 2);
+        let msg = Message::from_octets(octets).unwrap();
+        assert_eq!(
+            msg.display_dig().to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 0\n\
+             ;; flags:; QUERY: 0, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0\n\
+             \n\
+             ;; ANSWER SECTION:\n\
+             ;; ERROR: failed to parse message: unexpected end of input\n"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Conversions from and to the types of the hickory-proto crate.
+//!
+//! Projects moving from [hickory-proto] (formerly known as trust-dns-proto)
+//! to this crate or using both of them may need to pass the same data to
+//! either. This module provides `TryFrom` conversions between the
+//! corresponding types of the two crates:
+//!
+//! * [`Message`] and `hickory_proto::op::Message`,
+//! * [`Record`] and `hickory_proto::rr::Record`, and
+//! * [`Dname`] and `hickory_proto::rr::Name`.
+//!
+//! All conversions go through the wire format. Since the two crates
+//! represent the data quite differently, this is the only way to make
+//! sure nothing is lost or altered on the way. In the direction of this
+//! crate, records are converted into [`AllRecordData`] and names and
+//! octets are owned by `Vec<u8>`s.
+//!
+//! Hickory names can be relative. Since a [`Dname`] is always absolute,
+//! converting such a name results in an error.
+//!
+//! The module is only available with the `hickory-proto` feature which
+//! also enables the `bytes` and `std` features. Note that hickory-proto
+//! requires Rust 1.71.1 or newer while the rest of this crate needs only
+//! Rust 1.65.
+//!
+//! [hickory-proto]: https://crates.io/crates/hickory-proto
+#![cfg(feature = "hickory-proto")]
+#![cfg_attr(docsrs, doc(cfg(feature = "hickory-proto")))]
+
+use super::message::Message;
+use super::name::{Dname, ParsedDname, ToDname};
+use super::rdata::ComposeRecordData;
+use super::record::{ParsedRecord, Record};
+use super::wire::ParseError;
+use crate::rdata::AllRecordData;
+use bytes::Bytes;
+use core::convert::TryFrom;
+use core::fmt;
+use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+use hickory_proto::{op, rr, ProtoError};
+use octseq::parse::Parser;
+use std::error;
+use std::vec::Vec;
+
+//------------ Type Aliases --------------------------------------------------
+
+/// A record converted from a hickory record.
+pub type OwnedRecord =
+    Record<Dname<Vec<u8>>, AllRecordData<Vec<u8>, Dname<Vec<u8>>>>;
+
+//------------ Message -------------------------------------------------------
+
+impl<Octs: AsRef<[u8]>> TryFrom<&Message<Octs>> for op::Message {
+    type Error = HickoryError;
+
+    fn try_from(msg: &Message<Octs>) -> Result<Self, Self::Error> {
+        op::Message::from_vec(msg.as_slice()).map_err(Into::into)
+    }
+}
+
+impl TryFrom<&op::Message> for Message<Vec<u8>> {
+    type Error = HickoryError;
+
+    fn try_from(msg: &op::Message) -> Result<Self, Self::Error> {
+        Message::from_octets(msg.to_vec()?)
+            .map_err(|_| ParseError::ShortInput.into())
+    }
+}
+
+//------------ Record --------------------------------------------------------
+
+impl<N, D> TryFrom<&Record<N, D>> for rr::Record
+where
+    N: ToDname,
+    D: ComposeRecordData,
+{
+    type Error = HickoryError;
+
+    fn try_from(record: &Record<N, D>) -> Result<Self, Self::Error> {
+        let mut octets = Vec::new();
+        record
+            .compose(&mut octets)
+            .map_err(|_| ParseError::form_error("record too long"))?;
+        rr::Record::from_bytes(&octets).map_err(Into::into)
+    }
+}
+
+impl TryFrom<&rr::Record> for OwnedRecord {
+    type Error = HickoryError;
+
+    fn try_from(record: &rr::Record) -> Result<Self, Self::Error> {
+        let octets = Bytes::from(record.to_bytes()?);
+        let mut parser = Parser::from_ref(&octets);
+        let record = ParsedRecord::parse(&mut parser)?
+            .into_record::<AllRecordData<_, ParsedDname<_>>>()?
+            .ok_or_else(|| ParseError::form_error("no record data"))?;
+        if parser.remaining() != 0 {
+            return Err(ParseError::form_error("trailing data").into());
+        }
+        let long = |_| ParseError::form_error("long domain name");
+        let (owner, class, ttl) =
+            (record.owner().clone(), record.class(), record.ttl());
+        Ok(Record::new(
+            owner.flatten_into().map_err(long)?,
+            class,
+            ttl,
+            record.into_data().flatten_into().map_err(long)?,
+        ))
+    }
+}
+
+//------------ Dname ---------------------------------------------------------
+
+impl<Octs: AsRef<[u8]>> TryFrom<&Dname<Octs>> for rr::Name {
+    type Error = HickoryError;
+
+    fn try_from(name: &Dname<Octs>) -> Result<Self, Self::Error> {
+        rr::Name::from_bytes(name.as_slice()).map_err(Into::into)
+    }
+}
+
+impl TryFrom<&rr::Name> for Dname<Vec<u8>> {
+    type Error = HickoryError;
+
+    fn try_from(name: &rr::Name) -> Result<Self, Self::Error> {
+        if !name.is_fqdn() {
+            return Err(ParseError::form_error("relative domain name").into());
+        }
+        Dname::from_octets(name.to_bytes()?)
+            .map_err(|_| ParseError::form_error("invalid domain name").into())
+    }
+}
+
+//------------ HickoryError --------------------------------------------------
+
+/// A value could not be converted from or into a hickory-proto type.
+#[derive(Debug)]
+pub enum HickoryError {
+    /// Hickory failed to encode or decode the value.
+    Hickory(ProtoError),
+
+    /// The wire format produced by hickory could not be parsed.
+    Parse(ParseError),
+}
+
+impl From<ProtoError> for HickoryError {
+    fn from(err: ProtoError) -> Self {
+        HickoryError::Hickory(err)
+    }
+}
+
+impl From<ParseError> for HickoryError {
+    fn from(err: ParseError) -> Self {
+        HickoryError::Parse(err)
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for HickoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HickoryError::Hickory(ref err) => err.fmt(f),
+            HickoryError::Parse(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for HickoryError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::Ttl;
+    use crate::rdata::{Mx, Txt, A};
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn message() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        msg.header_mut().set_qr(true);
+        let mut msg = msg.question();
+        msg.push((name("example.com."), Rtype::Mx)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            name("example.com."),
+            3600,
+            Mx::new(10, name("mail.example.com.")),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            name("mail.example.com."),
+            3600,
+            A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+
+        let hickory = op::Message::try_from(&msg).unwrap();
+        assert_eq!(hickory.id(), 4711);
+        assert_eq!(hickory.answers().len(), 1);
+        assert_eq!(hickory.additionals().len(), 1);
+        assert_eq!(hickory.answers()[0].name().to_ascii(), "example.com.");
+
+        let back = Message::try_from(&hickory).unwrap();
+        assert_eq!(back.header(), msg.header());
+        assert_eq!(back.header_counts(), msg.header_counts());
+        for (left, right) in back
+            .answer()
+            .unwrap()
+            .chain(back.additional().unwrap())
+            .zip(msg.answer().unwrap().chain(msg.additional().unwrap()))
+        {
+            let (left, right) = (left.unwrap(), right.unwrap());
+            assert_eq!(
+                left.to_record::<AllRecordData<_, _>>().unwrap(),
+                right.to_record::<AllRecordData<_, _>>().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn record() {
+        let record = Record::new(
+            name("example.com."),
+            Class::In,
+            Ttl::from_secs(300),
+            Txt::<Vec<u8>>::build_from_slice(b"hello").unwrap(),
+        );
+        let hickory: rr::Record = (&record).try_into().unwrap();
+        assert_eq!(hickory.ttl(), 300);
+        assert_eq!(hickory.record_type(), rr::RecordType::TXT);
+
+        let back = OwnedRecord::try_from(&hickory).unwrap();
+        assert_eq!(back.owner(), record.owner());
+        assert_eq!(back.ttl(), record.ttl());
+        match back.data() {
+            AllRecordData::Txt(txt) => {
+                assert_eq!(txt.as_flat_slice(), Some(b"hello".as_ref()))
+            }
+            _ => panic!("wrong record data"),
+        }
+    }
+
+    #[test]
+    fn dname() {
+        let hickory = rr::Name::try_from(&name("www.Example.com.")).unwrap();
+        assert_eq!(hickory.to_ascii(), "www.Example.com.");
+        assert_eq!(
+            Dname::try_from(&hickory).unwrap().as_slice(),
+            b"\x03www\x07Example\x03com\x00"
+        );
+        assert!(Dname::try_from(&rr::Name::root()).unwrap().is_root());
+        assert!(matches!(
+            Dname::try_from(&rr::Name::from_str("www.example").unwrap()),
+            Err(HickoryError::Parse(_))
+        ));
+    }
+}
@@ -35,25 +35,25 @@ int_enum! {
     /// Chaosnet (CH).
     ///
     /// A network protocol developed at MIT in the 1970s. Reused by BIND for
-    /// built-in server information zones.",
+    /// built-in server information zones.
     (Ch => 3, b"CH")
 
     /// Hesiod (HS).
     ///
-    /// A system information protocol part of MIT's Project Athena.",
+    /// A system information protocol part of MIT's Project Athena.
     (Hs => 4, b"HS")
 
     /// Query class None.
     ///
     /// Defined in RFC 2136, this class is used in UPDATE queries to
-    /// require that an RRset does not exist prior to the update.",
+    /// require that an RRset does not exist prior to the update.
     (None => 0xFE, b"NONE")
 
-    /// Query class * (ANY).
+    /// Query class ANY (*).
     ///
     /// This class can be used in a query to indicate that records for the
-    /// given name from any class are requested.",
-    (Any => 0xFF, b"*")
+    /// given name from any class are requested.
+    (Any => 0xFF, b"ANY", b"*")
 }
 
 int_enum_str_with_prefix!(Class, "CLASS", b"CLASS", u16, "unknown class");
@@ -62,6 +62,38 @@ int_enum_str_with_prefix!(Class, "CLASS", b"CLASS", u16, "unknown class");
 
 #[cfg(test)]
 mod test {
+    #[test]
+    fn from_str() {
+        use super::Class;
+        use core::str::FromStr;
+
+        assert_eq!(Class::from_str("IN").unwrap(), Class::In);
+        assert_eq!(Class::from_str("ch").unwrap(), Class::Ch);
+        assert_eq!(Class::from_str("CLASS1").unwrap(), Class::In);
+        assert_eq!(Class::from_str("class32").unwrap(), Class::Int(32));
+        assert_eq!(Class::from_bytes(b"CLASS32"), Some(Class::Int(32)));
+        assert!(Class::from_str("CLASS").is_err());
+        assert!(Class::from_str("CLASS+32").is_err());
+        assert!(Class::from_str("CLASS65536").is_err());
+        assert_eq!(Class::from_bytes(b"CLASS+32"), None);
+        assert_eq!(Class::from_str("ANY").unwrap(), Class::Any);
+        assert_eq!(Class::from_str("*").unwrap(), Class::Any);
+        assert_eq!(Class::from_str("none").unwrap(), Class::None);
+        assert_eq!(Class::from_str("HS").unwrap(), Class::Hs);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn display() {
+        use super::Class;
+        use std::string::ToString;
+
+        assert_eq!(Class::In.to_string(), "IN");
+        assert_eq!(Class::Any.to_string(), "ANY");
+        assert_eq!(Class::Int(0xFF).to_string(), "ANY");
+        assert_eq!(Class::Int(32).to_string(), "CLASS32");
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn ser_de() {
@@ -9,7 +9,7 @@ int_enum! {
     /// key digest in the record has been generated.
     ///
     /// For the currently registered values see the [IANA registration].
-    /// This type is complete as of the registry update of 2012-04-13.
+    /// This type is complete as of the registry update of 2024-04-19.
     ///
     /// [IANA registration]: https://www.iana.org/assignments/ds-rr-types/ds-rr-types.xhtml#ds-rr-types-1
     =>
@@ -40,6 +40,22 @@ int_enum! {
     ///
     /// [RFC 6605]: https://tools.ietf.org/html/rfc6605
     (Sha384 => 4, b"SHA-384")
+
+    /// Specifies that the GOST R 34.11-2012 hash function is used.
+    ///
+    /// Use of this hash function is described in [RFC 9558]. Implementing
+    /// the function is optional.
+    ///
+    /// [RFC 9558]: https://tools.ietf.org/html/rfc9558
+    (Gost12 => 5, b"GOST R 34.11-2012")
+
+    /// Specifies that the SM3 hash function is used.
+    ///
+    /// Use of this hash function is described in [RFC 9563]. Implementing
+    /// the function is optional.
+    ///
+    /// [RFC 9563]: https://tools.ietf.org/html/rfc9563
+    (Sm3 => 6, b"SM3")
 }
 
 int_enum_str_decimal!(DigestAlg, u8);
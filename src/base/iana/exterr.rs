@@ -149,6 +149,38 @@ int_enum! {
     /// is otherwise configured to support. Examples of this include
     /// its most recent zone being too old or having expired.
     (InvalidData => 24, b"Invalid Data")
+
+    /// The resolver attempted to perform DNSSEC validation, but the
+    /// signature’s expiration time lies before its inception time and
+    /// the signature was thus never valid.
+    (SignatureExpiredBeforeValid => 25, b"Signature Expired before Valid")
+
+    /// The server received a request sent in 0-RTT data of a DNS over
+    /// QUIC connection and refused to process it as it wasn’t safe to do
+    /// so.
+    ///
+    /// This code is defined in [RFC 9250].
+    ///
+    /// [RFC 9250]: https://tools.ietf.org/html/rfc9250
+    (TooEarly => 26, b"Too Early")
+
+    /// The resolver attempted to perform DNSSEC validation, but the
+    /// NSEC3 iterations value exceeded the limit it is willing to
+    /// process.
+    ///
+    /// This code is defined in [RFC 9276].
+    ///
+    /// [RFC 9276]: https://tools.ietf.org/html/rfc9276
+    (UnsupportedNsec3IterationsValue
+        => 27, b"Unsupported NSEC3 Iterations Value")
+
+    /// The server could not process the request because it could not
+    /// conform to the policy requested by the client.
+    (UnableToConformToPolicy => 28, b"Unable to conform to policy")
+
+    /// The answer was synthesized by the server, for instance from a
+    /// local configuration rather than actual data.
+    (Synthesized => 29, b"Synthesized")
 }
 
 /// Start of the private range for EDE codes.
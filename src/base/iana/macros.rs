@@ -6,11 +6,15 @@
 /// `Hash`.
 ///
 /// For `FromStr` and `Display`, see one of the other macros in this module.
+///
+/// A variant can have additional mnemonics following the first one. These
+/// are accepted by `from_mnemonic` but never produced by `to_mnemonic`.
 macro_rules! int_enum {
     ( $(#[$attr:meta])* =>
       $ianatype:ident, $inttype:path;
       $( $(#[$variant_attr:meta])* ( $variant:ident =>
-                                        $value:expr, $mnemonic:expr) )* ) => {
+                                        $value:expr, $mnemonic:expr
+                                        $(, $alias:expr)* ) )* ) => {
         $(#[$attr])*
         #[derive(Clone, Copy, Debug)]
         pub enum $ianatype {
@@ -40,7 +44,9 @@ macro_rules! int_enum {
             /// Returns a value from a well-defined mnemonic.
             pub fn from_mnemonic(m: &[u8]) -> Option<Self> {
                 $(
-                    if m.eq_ignore_ascii_case($mnemonic) {
+                    if m.eq_ignore_ascii_case($mnemonic)
+                        $( || m.eq_ignore_ascii_case($alias) )*
+                    {
                         return Some($ianatype::$variant)
                     }
                 )*
@@ -371,7 +377,8 @@ macro_rules! int_enum_str_with_decimal {
 ///
 /// For `FromStr` recognizes all defined mnemonics ignoring case. Additionally
 /// recognizes a value starting with the prefix given in the second argument
-/// (again, ignoring case) directly followed by a decimal number.
+/// (again, ignoring case) directly followed by a decimal number. This is the
+/// generic syntax for unknown classes and types defined in RFC 3597.
 ///
 /// For `Display`, values without mnemonic will be written starting with the
 /// prefix directly followed by the decimal representation of the value.
@@ -385,7 +392,9 @@ macro_rules! int_enum_str_with_prefix {
                         return None;
                     }
                     let (l, r) = bytes.split_at($u8_prefix.len());
-                    if !l.eq_ignore_ascii_case($u8_prefix) {
+                    if !l.eq_ignore_ascii_case($u8_prefix)
+                        || !r.iter().all(u8::is_ascii_digit)
+                    {
                         return None;
                     }
                     let r = match core::str::from_utf8(r) {
@@ -410,7 +419,9 @@ macro_rules! int_enum_str_with_prefix {
                             s.char_indices().nth($str_prefix.len())
                         {
                             let (l, r) = s.split_at(n);
-                            if l.eq_ignore_ascii_case($str_prefix) {
+                            if l.eq_ignore_ascii_case($str_prefix)
+                                && r.bytes().all(|ch| ch.is_ascii_digit())
+                            {
                                 let value = match r.parse() {
                                     Ok(x) => x,
                                     Err(..) => return Err(FromStrError),
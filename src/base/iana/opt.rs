@@ -1,4 +1,4 @@
-//! DNS EDNS0 pption codes.
+//! DNS EDNS0 option codes.
 
 //------------ OptionCode ----------------------------------------------------
 
@@ -10,7 +10,7 @@ int_enum! {
     /// code.*
     ///
     /// The currently assigned option codes can be found in the
-    /// [IANA registry]. The type is complete as of 2019-12-23.
+    /// [IANA registry]. The type is complete as of 2024-07-01.
     ///
     /// [OPT]: ../../opt/index.html
     /// [IANA registry]: http://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11
@@ -162,6 +162,31 @@ int_enum! {
     /// [draft-bellis-dnsop-edns-tags]: https://datatracker.ietf.org/doc/draft-bellis-dnsop-edns-tags/
     (ServerTag => 17, b"EDNS-Server-Tag")
 
+    /// Report channel (18).
+    ///
+    /// The report channel option allows a server to announce an agent
+    /// domain to which resolvers can report errors. It is defined in
+    /// [RFC 9567].
+    ///
+    /// [RFC 9567]: https://tools.ietf.org/html/rfc9567
+    (ReportChannel => 18, b"Report-Channel")
+
+    /// Zone version (19).
+    ///
+    /// The zone version option allows a client to ask for and a server to
+    /// provide an identifier for the version of the zone an answer was
+    /// taken from. It is defined in [RFC 9660].
+    ///
+    /// [RFC 9660]: https://tools.ietf.org/html/rfc9660
+    (ZoneVersion => 19, b"ZONEVERSION")
+
+    /// Umbrella Ident (20292).
+    ///
+    /// Ths option is used by the [Cisco Umbrella network device API].
+    ///
+    /// [Cisco Umbrella network device API]: https://docs.umbrella.com/developer/networkdevices-api/identifying-dns-traffic2
+    (UmbrellaIdent => 20292, b"Umbrella Ident")
+
     /// DeviceID (26946).
     ///
     /// Ths option is used by the [Cisco Umbrella network device API].
@@ -18,6 +18,7 @@
 //!
 #![allow(clippy::upper_case_acronyms)]
 
+use core::str::FromStr;
 use core::{cmp, fmt, hash};
 
 //------------ Rcode --------------------------------------------------------
@@ -37,7 +38,7 @@ use core::{cmp, fmt, hash};
 /// represented by [TsigRcode].
 ///
 /// All three codes share the same name space. Their values are defined in
-/// one registry, [IANA DNS RCODEs]. This type is complete as of 2019-01-28.
+/// one registry, [IANA DNS RCODEs]. This type is complete as of 2024-07-01.
 ///
 /// [OptRcode]: enum.OptRcode.html
 /// [TsigRcode]: enum.TsigRcode.html
@@ -160,6 +161,16 @@ pub enum Rcode {
     /// [RFC 2136]: https://tools.ietf.org/html/rfc2136
     NotZone,
 
+    /// DSO-TYPE not implemented.
+    ///
+    /// The server does not implement the type of a DNS Stateful Operations
+    /// request.
+    ///
+    /// Defined in [RFC 8490].
+    ///
+    /// [RFC 8490]: https://tools.ietf.org/html/rfc8490
+    DsoTypeNi,
+
     /// A raw, integer rcode value.
     ///
     /// When converting to an `u8`, only the lower four bits are used.
@@ -185,6 +196,7 @@ impl Rcode {
             8 => NXRRSet,
             9 => NotAuth,
             10 => NotZone,
+            11 => DsoTypeNi,
             value => Int(value),
         }
     }
@@ -205,9 +217,53 @@ impl Rcode {
             NXRRSet => 8,
             NotAuth => 9,
             NotZone => 10,
+            DsoTypeNi => 11,
             Int(value) => value & 0x0F,
         }
     }
+
+    /// Returns a value from a well-defined mnemonic.
+    ///
+    /// The mnemonic is matched ignoring case. In addition to the mnemonics
+    /// used for displaying, NOTAUTH and NOTIMPL are accepted.
+    pub fn from_mnemonic(m: &[u8]) -> Option<Self> {
+        if m.eq_ignore_ascii_case(b"NOTAUTH") {
+            return Some(Rcode::NotAuth);
+        }
+        if m.eq_ignore_ascii_case(b"NOTIMPL") {
+            return Some(Rcode::NotImp);
+        }
+        (0..0x10).map(Rcode::from_int).find(|rcode| {
+            rcode
+                .to_mnemonic()
+                .map(|value| value.eq_ignore_ascii_case(m))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Returns the mnemonic for this value if there is one.
+    ///
+    /// This will also return a mnemonic if a well-defined variant is hidden
+    /// in an `Int` variant.
+    pub fn to_mnemonic(self) -> Option<&'static [u8]> {
+        use self::Rcode::*;
+
+        match Rcode::from_int(self.to_int()) {
+            NoError => Some(b"NOERROR"),
+            FormErr => Some(b"FORMERR"),
+            ServFail => Some(b"SERVFAIL"),
+            NXDomain => Some(b"NXDOMAIN"),
+            NotImp => Some(b"NOTIMP"),
+            Refused => Some(b"REFUSED"),
+            YXDomain => Some(b"YXDOMAIN"),
+            YXRRSet => Some(b"YXRRSET"),
+            NXRRSet => Some(b"NXRRSET"),
+            NotAuth => Some(b"NOAUTH"),
+            NotZone => Some(b"NOTZONE"),
+            DsoTypeNi => Some(b"DSOTYPENI"),
+            Int(_) => None,
+        }
+    }
 }
 
 //--- From
@@ -224,28 +280,30 @@ impl From<Rcode> for u8 {
     }
 }
 
+//--- FromStr
+
+impl FromStr for Rcode {
+    type Err = FromStrError;
+
+    /// Creates an rcode from its mnemonic or its decimal value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Rcode::from_mnemonic(s.as_bytes()) {
+            Some(res) => Ok(res),
+            None => match s.parse() {
+                Ok(value) if value < 0x10 => Ok(Rcode::from_int(value)),
+                _ => Err(FromStrError),
+            },
+        }
+    }
+}
+
 //--- Display
 
 impl fmt::Display for Rcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::Rcode::*;
-
-        match *self {
-            NoError => "NOERROR".fmt(f),
-            FormErr => "FORMERR".fmt(f),
-            ServFail => "SERVFAIL".fmt(f),
-            NXDomain => "NXDOMAIN".fmt(f),
-            NotImp => "NOTIMP".fmt(f),
-            Refused => "REFUSED".fmt(f),
-            YXDomain => "YXDOMAIN".fmt(f),
-            YXRRSet => "YXRRSET".fmt(f),
-            NXRRSet => "NXRRSET".fmt(f),
-            NotAuth => "NOAUTH".fmt(f),
-            NotZone => "NOTZONE".fmt(f),
-            Int(i) => match Rcode::from_int(i) {
-                Rcode::Int(i) => i.fmt(f),
-                value => value.fmt(f),
-            },
+        match self.to_mnemonic() {
+            Some(m) => write_mnemonic(f, m),
+            None => self.to_int().fmt(f),
         }
     }
 }
@@ -345,7 +403,7 @@ impl<'de> serde::Deserialize<'de> for Rcode {
 /// Because of this, we decided to have separate types.
 ///
 /// The values for all three response code types are defined in
-/// the [IANA DNS RCODEs] registry. This type is complete as of 2019-01-28.
+/// the [IANA DNS RCODEs] registry. This type is complete as of 2024-07-01.
 ///
 /// [Rcode]: enum.Rcode.html
 /// [`TsigRcode`]: enum.TsigRcode.html
@@ -470,6 +528,16 @@ pub enum OptRcode {
     /// [RFC 2136]: https://tools.ietf.org/html/rfc2136
     NotZone,
 
+    /// DSO-TYPE not implemented.
+    ///
+    /// The server does not implement the type of a DNS Stateful Operations
+    /// request.
+    ///
+    /// Defined in [RFC 8490].
+    ///
+    /// [RFC 8490]: https://tools.ietf.org/html/rfc8490
+    DsoTypeNi,
+
     /// Bad OPT version.
     ///
     /// A name server does not implement the EDNS version requested in the
@@ -518,6 +586,7 @@ impl OptRcode {
             8 => NXRRSet,
             9 => NotAuth,
             10 => NotZone,
+            11 => DsoTypeNi,
             16 => BadVers,
             23 => BadCookie,
             value => Int(value),
@@ -540,9 +609,45 @@ impl OptRcode {
             NXRRSet => 8,
             NotAuth => 9,
             NotZone => 10,
+            DsoTypeNi => 11,
             BadVers => 16,
             BadCookie => 23,
-            Int(value) => value & 0x0F,
+            Int(value) => value & 0x0FFF,
+        }
+    }
+
+    /// Returns a value from a well-defined mnemonic.
+    ///
+    /// The mnemonic is matched ignoring case. Both BADVER and BADVERS are
+    /// accepted for [`OptRcode::BadVers`].
+    pub fn from_mnemonic(m: &[u8]) -> Option<Self> {
+        if m.eq_ignore_ascii_case(b"BADVERS") {
+            return Some(OptRcode::BadVers);
+        }
+        if let Some(rcode) = Rcode::from_mnemonic(m) {
+            return Some(rcode.into());
+        }
+        [OptRcode::BadVers, OptRcode::BadCookie]
+            .into_iter()
+            .find(|rcode| {
+                rcode
+                    .to_mnemonic()
+                    .map(|value| value.eq_ignore_ascii_case(m))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Returns the mnemonic for this value if there is one.
+    ///
+    /// This will also return a mnemonic if a well-defined variant is hidden
+    /// in an `Int` variant.
+    pub fn to_mnemonic(self) -> Option<&'static [u8]> {
+        match OptRcode::from_int(self.to_int()) {
+            OptRcode::BadVers => Some(b"BADVER"),
+            OptRcode::BadCookie => Some(b"BADCOOKIE"),
+            OptRcode::Int(_) => None,
+            rcode if rcode.ext() == 0 => rcode.rcode().to_mnemonic(),
+            _ => None,
         }
     }
 
@@ -554,7 +659,7 @@ impl OptRcode {
     /// Returns the two parts of an extended rcode value.
     pub fn to_parts(self) -> (Rcode, u8) {
         let res = self.to_int();
-        (Rcode::from_int(res as u8), (res >> 8) as u8)
+        (Rcode::from_int(res as u8), (res >> 4) as u8)
     }
 
     /// Returns the rcode part of the extended rcode.
@@ -588,34 +693,111 @@ impl From<Rcode> for OptRcode {
     }
 }
 
+//--- FromStr
+
+impl FromStr for OptRcode {
+    type Err = FromStrError;
+
+    /// Creates an rcode from its mnemonic or its decimal value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match OptRcode::from_mnemonic(s.as_bytes()) {
+            Some(res) => Ok(res),
+            None => match s.parse() {
+                Ok(value) if value < 0x1000 => Ok(OptRcode::from_int(value)),
+                _ => Err(FromStrError),
+            },
+        }
+    }
+}
+
 //--- Display
 
 impl fmt::Display for OptRcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::OptRcode::*;
-
-        match *self {
-            NoError => "NOERROR".fmt(f),
-            FormErr => "FORMERR".fmt(f),
-            ServFail => "SERVFAIL".fmt(f),
-            NXDomain => "NXDOMAIN".fmt(f),
-            NotImp => "NOTIMP".fmt(f),
-            Refused => "REFUSED".fmt(f),
-            YXDomain => "YXDOMAIN".fmt(f),
-            YXRRSet => "YXRRSET".fmt(f),
-            NXRRSet => "NXRRSET".fmt(f),
-            NotAuth => "NOAUTH".fmt(f),
-            NotZone => "NOTZONE".fmt(f),
-            BadVers => "BADVER".fmt(f),
-            BadCookie => "BADCOOKIE".fmt(f),
-            Int(i) => match OptRcode::from_int(i) {
-                Int(i) => i.fmt(f),
-                value => value.fmt(f),
-            },
+        match self.to_mnemonic() {
+            Some(m) => write_mnemonic(f, m),
+            None => self.to_int().fmt(f),
         }
     }
 }
 
+//--- PartialEq and Eq
+
+impl cmp::PartialEq for OptRcode {
+    fn eq(&self, other: &OptRcode) -> bool {
+        self.to_int() == other.to_int()
+    }
+}
+
+impl cmp::PartialEq<u16> for OptRcode {
+    fn eq(&self, other: &u16) -> bool {
+        self.to_int() == *other
+    }
+}
+
+impl cmp::PartialEq<OptRcode> for u16 {
+    fn eq(&self, other: &OptRcode) -> bool {
+        *self == other.to_int()
+    }
+}
+
+impl cmp::Eq for OptRcode {}
+
+//--- PartialOrd and Ord
+
+impl cmp::PartialOrd for OptRcode {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl cmp::PartialOrd<u16> for OptRcode {
+    fn partial_cmp(&self, other: &u16) -> Option<cmp::Ordering> {
+        self.to_int().partial_cmp(other)
+    }
+}
+
+impl cmp::PartialOrd<OptRcode> for u16 {
+    fn partial_cmp(&self, other: &OptRcode) -> Option<cmp::Ordering> {
+        self.partial_cmp(&other.to_int())
+    }
+}
+
+impl cmp::Ord for OptRcode {
+    fn cmp(&self, other: &OptRcode) -> cmp::Ordering {
+        self.to_int().cmp(&other.to_int())
+    }
+}
+
+//--- Hash
+
+impl hash::Hash for OptRcode {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.to_int().hash(state)
+    }
+}
+
+//--- Serialize and Deserialize
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for OptRcode {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.to_int().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OptRcode {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        u16::deserialize(deserializer).map(OptRcode::from_int)
+    }
+}
+
 //------------ TsigRcode ----------------------------------------------------
 
 int_enum! {
@@ -629,7 +811,7 @@ int_enum! {
     /// types.
     ///
     /// The values for all three response code types are defined in
-    /// the [IANA DNS RCODEs] registry. This type is complete as of 2019-01-28.
+    /// the [IANA DNS RCODEs] registry. This type is complete as of 2024-07-01.
     ///
     /// [`Rcode`]: enum.Rcode.html
     /// [`OptRcode`]: enum.OptRcode.html
@@ -751,6 +933,16 @@ int_enum! {
     /// [RFC 2136]: https://tools.ietf.org/html/rfc2136
     (NotZone => 10, b"NOTZONE")
 
+    /// DSO-TYPE not implemented.
+    ///
+    /// The server does not implement the type of a DNS Stateful Operations
+    /// request.
+    ///
+    /// Defined in [RFC 8490].
+    ///
+    /// [RFC 8490]: https://tools.ietf.org/html/rfc8490
+    (DsoTypeNi => 11, b"DSOTYPENI")
+
     /// TSIG signature failure.
     ///
     /// The TSIG signature fails to verify.
@@ -845,4 +1037,43 @@ impl From<OptRcode> for TsigRcode {
     }
 }
 
-int_enum_str_with_decimal!(TsigRcode, u16, "unknown TSIG error");
+int_enum_str_with_decimal!(TsigRcode, u16, "unknown response code");
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Writes an ASCII mnemonic to a formatter.
+fn write_mnemonic(f: &mut fmt::Formatter, m: &[u8]) -> fmt::Result {
+    use core::fmt::Write;
+
+    m.iter().try_for_each(|&ch| f.write_char(ch.into()))
+}
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rcode_str() {
+        assert_eq!(Rcode::from_str("nxdomain").unwrap(), Rcode::NXDomain);
+        assert_eq!(Rcode::from_str("NOTAUTH").unwrap(), Rcode::NotAuth);
+        assert_eq!(Rcode::from_str("11").unwrap(), Rcode::DsoTypeNi);
+        assert_eq!(Rcode::from_str("12").unwrap(), Rcode::Int(12));
+        assert!(Rcode::from_str("16").is_err());
+        assert!(Rcode::from_str("BADVERS").is_err());
+        assert_eq!(Rcode::Int(3).to_mnemonic(), Some(b"NXDOMAIN".as_ref()));
+        assert_eq!(Rcode::Int(12).to_mnemonic(), None);
+    }
+
+    #[test]
+    fn opt_rcode_str() {
+        assert_eq!(OptRcode::from_str("BADVERS").unwrap(), OptRcode::BadVers);
+        assert_eq!(OptRcode::from_str("badver").unwrap(), OptRcode::BadVers);
+        assert_eq!(OptRcode::from_str("refused").unwrap(), OptRcode::Refused);
+        assert_eq!(OptRcode::from_str("23").unwrap(), OptRcode::BadCookie);
+        assert!(OptRcode::from_str("4096").is_err());
+        assert_eq!(OptRcode::Int(0x123).to_int(), 0x123);
+        assert_eq!(OptRcode::Int(0x123).to_mnemonic(), None);
+    }
+}
@@ -12,7 +12,7 @@ int_enum! {
     /// questions. This type represents both these types.
     ///
     /// The currently assigned values are maintained in an [IANA registry].
-    /// This type is complete as of 2019-01-28.
+    /// This type is complete as of 2024-07-01.
     ///
     /// [IANA registry]: http://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
     ///
@@ -296,19 +296,24 @@ int_enum! {
 
     /// Message digest for DNS zone.
     ///
-    /// See draft-wessels-dns-zone-digest.
+    /// See RFC 8976.
     (Zonemd => 63, b"ZONEMD")
 
     /// General Purpose Service Endpoints.
     ///
-    /// See draft-ietf-dnsop-svcb-httpssvc
+    /// See RFC 9460.
     (Svcb => 64, b"SVCB")
 
     /// HTTPS Specific Service Endpoints.
     ///
-    /// See draft-ietf-dnsop-svcb-httpssvc
+    /// See RFC 9460.
     (Https => 65, b"HTTPS")
 
+    /// Endpoint discovery for delegation synchronization.
+    ///
+    /// See draft-ietf-dnsop-generalized-notify.
+    (Dsync => 66, b"DSYNC")
+
     /// SPF.
     ///
     /// RFC 7208.
@@ -364,6 +369,12 @@ int_enum! {
     /// See RFC 7043.
     (Eui64 => 109, b"EUI64")
 
+    /// Non-existent name.
+    ///
+    /// A meta type used with compact denial of existence. See
+    /// draft-ietf-dnsop-compact-denial-of-existence.
+    (Nxname => 128, b"NXNAME")
+
     /// Transaction key.
     ///
     /// See RFC 2930.
@@ -415,6 +426,25 @@ int_enum! {
     /// See draft-durand-doa-over-dns.
     (Doa => 259, b"DOA")
 
+    /// Automatic Multicast Tunneling Relay.
+    ///
+    /// See RFC 8777.
+    (Amtrelay => 260, b"AMTRELAY")
+
+    /// Resolver information as key/value pairs.
+    ///
+    /// See RFC 9606.
+    (Resinfo => 261, b"RESINFO")
+
+    /// Public wallet address.
+    (Wallet => 262, b"WALLET")
+
+    /// BP Convergence Layer Adapter.
+    (Cla => 263, b"CLA")
+
+    /// BP Node Number.
+    (Ipn => 264, b"IPN")
+
     /// DNSSEC trust authorities.
     (Ta => 32768, b"TA")
 
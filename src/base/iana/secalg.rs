@@ -8,6 +8,7 @@ int_enum! {
     /// These numbers are used in various security related record types.
     ///
     /// For the currently registered values see the [IANA registration].
+    /// This type is complete as of 2024-07-01.
     ///
     /// [IANA registration]: http://www.iana.org/assignments/dns-sec-alg-numbers/dns-sec-alg-numbers.xhtml#dns-sec-alg-numbers-1].
     =>
@@ -100,6 +101,18 @@ int_enum! {
     /// This algorithm is described in RFC 8080.
     (Ed448 => 16, b"ED448")
 
+    /// SM2 signing with SM3 hashing
+    ///
+    /// This algorithm is described in RFC 9563. It may be used for zone
+    /// signing only.
+    (Sm2Sm3 => 17, b"SM2SM3")
+
+    /// GOST R 34.10-2012
+    ///
+    /// This algorithm is described in RFC 9558. It may be used for zone
+    /// signing only.
+    (EccGost12 => 23, b"ECC-GOST12")
+
     /// Reserved for Indirect Keys
     ///
     /// This value is reserved by RFC 4034.
@@ -0,0 +1,554 @@
+//! The JSON format of DNS-over-HTTPS APIs.
+//!
+//! Some public resolvers offer an HTTP API that answers queries given as
+//! URL query parameters with a JSON object using the media type
+//! `application/dns-json`. This format has never been standardized and
+//! differs from the one defined in RFC 8427. It is, however, widely
+//! supported.
+//!
+//! A request is a GET request with the query name and type provided as
+//! the `name` and `type` query parameters. The query string for a request
+//! can be created via [`query_string`].
+//!
+//! The response is represented by [`DohResponse`]. It can be deserialized
+//! from the JSON returned by the server and then converted into a regular
+//! [`Message`] via [`DohResponse::to_message`]. The record data in the
+//! response is given in presentation format and is scanned into the record
+//! data types of this crate. In the other direction,
+//! [`DohResponse::from_message`] creates a value from a response message.
+//!
+//! The format doesn’t include record classes, so all records are assumed
+//! to be of class IN.
+
+use super::NameStr;
+use crate::base::iana::{Class, OptRcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{MessageBuilder, PushError};
+use crate::base::name::{Dname, FromStrError, ToDname};
+use crate::base::question::Question;
+use crate::base::record::{Record, Ttl};
+use crate::base::scan::{IterScanner, ScannerError, StrError, Symbol};
+use crate::base::wire::ParseError;
+use crate::rdata::{AllRecordData, ZoneRecordData};
+use core::fmt;
+use core::str::FromStr;
+use octseq::octets::Octets;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use std::{error, format};
+
+//------------ query_string --------------------------------------------------
+
+/// Returns the URL query string for a request.
+///
+/// The query string asks for records of type `qtype` for `qname`. If
+/// `dnssec_ok` is `true`, the server is asked to include DNSSEC records.
+/// If `checking_disabled` is `true`, the server is asked not to perform
+/// DNSSEC validation.
+///
+/// The returned string does not include the leading question mark.
+pub fn query_string(
+    qname: &impl ToDname,
+    qtype: Rtype,
+    dnssec_ok: bool,
+    checking_disabled: bool,
+) -> String {
+    let mut res = String::from("name=");
+    for ch in NameStr(qname).to_string().chars() {
+        // Escape everything that isn’t allowed in a query component
+        // unencoded.
+        if ch.is_ascii_alphanumeric() || "-._~".contains(ch) {
+            res.push(ch)
+        } else {
+            let mut buf = [0u8; 4];
+            for octet in ch.encode_utf8(&mut buf).bytes() {
+                res.push_str(&format!("%{:02X}", octet));
+            }
+        }
+    }
+    res.push_str(&format!("&type={}", qtype.to_int()));
+    if dnssec_ok {
+        res.push_str("&do=1");
+    }
+    if checking_disabled {
+        res.push_str("&cd=1");
+    }
+    res
+}
+
+//------------ DohResponse ---------------------------------------------------
+
+/// A DNS response in the JSON format of DNS-over-HTTPS APIs.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct DohResponse {
+    /// The response code of the response.
+    ///
+    /// This is the full extended response code.
+    #[serde(rename = "Status")]
+    pub status: u16,
+
+    /// Whether the response was truncated.
+    #[serde(rename = "TC", default)]
+    pub tc: bool,
+
+    /// Whether recursion was desired.
+    #[serde(rename = "RD", default)]
+    pub rd: bool,
+
+    /// Whether recursion is available.
+    #[serde(rename = "RA", default)]
+    pub ra: bool,
+
+    /// Whether the server considers the data in the response authentic.
+    #[serde(rename = "AD", default)]
+    pub ad: bool,
+
+    /// Whether DNSSEC validation was disabled by the client.
+    #[serde(rename = "CD", default)]
+    pub cd: bool,
+
+    /// The question section.
+    #[serde(
+        rename = "Question",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub question: Vec<DohQuestion>,
+
+    /// The answer section.
+    #[serde(
+        rename = "Answer",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub answer: Vec<DohRecord>,
+
+    /// The authority section.
+    #[serde(
+        rename = "Authority",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub authority: Vec<DohRecord>,
+
+    /// The additional section.
+    ///
+    /// This does not include the OPT record.
+    #[serde(
+        rename = "Additional",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub additional: Vec<DohRecord>,
+
+    /// The EDNS client subnet used by the server, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edns_client_subnet: Option<String>,
+
+    /// A free-form comment by the server.
+    #[serde(
+        rename = "Comment",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub comment: Option<String>,
+}
+
+impl DohResponse {
+    /// Creates a value from a response message.
+    ///
+    /// OPT records are skipped. Record data is converted into its
+    /// presentation format.
+    pub fn from_message<Octs: Octets>(
+        msg: &Message<Octs>,
+    ) -> Result<Self, ParseError> {
+        let header = msg.header();
+        let status = match msg.opt() {
+            Some(opt) => opt.rcode(header),
+            None => header.rcode().into(),
+        };
+        let mut res = DohResponse {
+            status: status.to_int(),
+            tc: header.tc(),
+            rd: header.rd(),
+            ra: header.ra(),
+            ad: header.ad(),
+            cd: header.cd(),
+            ..Default::default()
+        };
+        for question in msg.question() {
+            let question = question?;
+            res.question.push(DohQuestion {
+                name: NameStr(question.qname()).to_string(),
+                qtype: question.qtype().to_int(),
+            });
+        }
+        let mut section = msg.answer()?;
+        for target in
+            [&mut res.answer, &mut res.authority, &mut res.additional]
+        {
+            for record in &mut section {
+                let record = record?;
+                if record.rtype() == Rtype::Opt {
+                    continue;
+                }
+                if let Some(record) =
+                    record.into_record::<AllRecordData<_, _>>()?
+                {
+                    target.push(DohRecord::from_record(&record));
+                }
+            }
+            section = match section.next_section()? {
+                Some(section) => section,
+                None => break,
+            };
+        }
+        Ok(res)
+    }
+
+    /// Converts the value into a response message.
+    ///
+    /// The record data of all records needs to be in the presentation
+    /// format of a record type supported by the crate or in the generic
+    /// format defined in RFC 3597.
+    ///
+    /// If the status is an extended response code, an OPT record is added
+    /// to the message. The EDNS client subnet and the comment are not
+    /// included in the message.
+    pub fn to_message(&self) -> Result<Message<Vec<u8>>, DohError> {
+        let mut builder = MessageBuilder::new_vec();
+        let status = OptRcode::from_int(self.status);
+        let header = builder.header_mut();
+        header.set_qr(true);
+        header.set_rcode(status.rcode());
+        header.set_tc(self.tc);
+        header.set_rd(self.rd);
+        header.set_ra(self.ra);
+        header.set_ad(self.ad);
+        header.set_cd(self.cd);
+
+        let mut builder = builder.question();
+        for question in &self.question {
+            builder.push(question.to_question()?)?;
+        }
+        let mut builder = builder.answer();
+        for record in &self.answer {
+            builder.push(record.to_record()?)?;
+        }
+        let mut builder = builder.authority();
+        for record in &self.authority {
+            builder.push(record.to_record()?)?;
+        }
+        let mut builder = builder.additional();
+        for record in &self.additional {
+            builder.push(record.to_record()?)?;
+        }
+        if status.ext() != 0 {
+            builder.opt(|opt| {
+                opt.set_rcode(status);
+                Ok(())
+            })?;
+        }
+        Ok(builder.into_message())
+    }
+}
+
+//------------ DohQuestion ---------------------------------------------------
+
+/// A question in the JSON format of DNS-over-HTTPS APIs.
+#[derive(
+    Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct DohQuestion {
+    /// The query name in presentation format.
+    pub name: String,
+
+    /// The integer value of the query type.
+    #[serde(rename = "type")]
+    pub qtype: u16,
+}
+
+impl DohQuestion {
+    /// Converts the value into a question of class IN.
+    pub fn to_question(&self) -> Result<Question<Dname<Vec<u8>>>, DohError> {
+        Ok(Question::new(
+            parse_name(&self.name)?,
+            Rtype::from_int(self.qtype),
+            Class::In,
+        ))
+    }
+}
+
+//------------ DohRecord -----------------------------------------------------
+
+/// A resource record in the JSON format of DNS-over-HTTPS APIs.
+#[derive(
+    Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct DohRecord {
+    /// The owner name in presentation format.
+    pub name: String,
+
+    /// The integer value of the record type.
+    #[serde(rename = "type")]
+    pub rtype: u16,
+
+    /// The time-to-live of the record in seconds.
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+
+    /// The record data in presentation format.
+    pub data: String,
+}
+
+/// A record of class IN as created by [`DohRecord::to_record`].
+type VecRecord =
+    Record<Dname<Vec<u8>>, ZoneRecordData<Vec<u8>, Dname<Vec<u8>>>>;
+
+impl DohRecord {
+    /// Creates a value from a record.
+    pub fn from_record<N, O, RN>(
+        record: &Record<N, AllRecordData<O, RN>>,
+    ) -> Self
+    where
+        N: ToDname,
+        O: Octets,
+        RN: fmt::Display,
+    {
+        let data = match record.data() {
+            // The display implementation of TXT record data concatenates
+            // all character strings, so we need to quote them ourselves.
+            AllRecordData::Txt(txt) => {
+                let mut data = String::new();
+                for item in txt.iter() {
+                    if !data.is_empty() {
+                        data.push(' ');
+                    }
+                    data.push('"');
+                    for &ch in item {
+                        data.push_str(&Symbol::from_octet(ch).to_string());
+                    }
+                    data.push('"');
+                }
+                data
+            }
+            data => data.to_string(),
+        };
+        DohRecord {
+            name: NameStr(record.owner()).to_string(),
+            rtype: record.rtype().to_int(),
+            ttl: record.ttl().as_secs(),
+            data,
+        }
+    }
+
+    /// Converts the value into a record of class IN.
+    pub fn to_record(&self) -> Result<VecRecord, DohError> {
+        let mut scanner =
+            IterScanner::<_, Vec<u8>>::new(tokenize(&self.data)?);
+        let data =
+            ZoneRecordData::scan(Rtype::from_int(self.rtype), &mut scanner)
+                .map_err(DohError::Data)?;
+        if !scanner.is_exhausted() {
+            return Err(DohError::Data(StrError::custom(
+                "trailing record data",
+            )));
+        }
+        Ok(Record::new(
+            parse_name(&self.name)?,
+            Class::In,
+            Ttl::from_secs(self.ttl),
+            data,
+        ))
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses a domain name in presentation format.
+///
+/// Relative names are considered to be relative to the root.
+fn parse_name(s: &str) -> Result<Dname<Vec<u8>>, DohError> {
+    if s == "." {
+        Ok(Dname::root_vec())
+    } else {
+        Dname::from_str(s).map_err(DohError::Name)
+    }
+}
+
+/// Splits record data in presentation format into tokens.
+///
+/// Tokens are separated by white space unless enclosed in double quotes.
+/// Escape sequences are kept and processed by the scanner later.
+fn tokenize(data: &str) -> Result<Vec<String>, DohError> {
+    let mut res = Vec::new();
+    let mut token: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = data.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                let token = token.get_or_insert_with(String::new);
+                token.push(ch);
+                if let Some(ch) = chars.next() {
+                    token.push(ch)
+                }
+            }
+            '"' if quoted => {
+                quoted = false;
+                res.push(token.take().unwrap_or_default());
+            }
+            '"' => {
+                res.extend(token.take());
+                quoted = true;
+                token = Some(String::new());
+            }
+            ch if ch.is_whitespace() && !quoted => res.extend(token.take()),
+            ch => token.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    if quoted {
+        return Err(DohError::Data(StrError::custom("unterminated quote")));
+    }
+    res.extend(token);
+    Ok(res)
+}
+
+//============ Error Types ===================================================
+
+//------------ DohError ------------------------------------------------------
+
+/// A DNS-over-HTTPS JSON response could not be converted into a message.
+#[derive(Debug)]
+pub enum DohError {
+    /// A domain name was invalid.
+    Name(FromStrError),
+
+    /// The record data of a record was invalid.
+    Data(StrError),
+
+    /// The message became too large.
+    Push(PushError),
+}
+
+impl From<PushError> for DohError {
+    fn from(err: PushError) -> Self {
+        DohError::Push(err)
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for DohError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DohError::Name(ref err) => write!(f, "invalid name: {}", err),
+            DohError::Data(ref err) => {
+                write!(f, "invalid record data: {}", err)
+            }
+            DohError::Push(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for DohError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::{Mx, Txt, A};
+
+    // A response in the format used by public resolvers.
+    const RESPONSE: &str = r#"{
+        "Status": 0, "TC": false, "RD": true, "RA": true,
+        "AD": true, "CD": false,
+        "Question": [ { "name": "example.com.", "type": 1 } ],
+        "Answer": [
+            { "name": "example.com.", "type": 5, "TTL": 300,
+              "data": "www.example.com." },
+            { "name": "www.example.com.", "type": 1, "TTL": 3600,
+              "data": "192.0.2.1" },
+            { "name": "www.example.com.", "type": 16, "TTL": 3600,
+              "data": "\"v=spf1 -all\" \"more \\\"text\\\"\"" },
+            { "name": "www.example.com.", "type": 15, "TTL": 3600,
+              "data": "10 mail.example.com." }
+        ],
+        "Comment": "Response from 192.0.2.53."
+    }"#;
+
+    #[test]
+    fn to_message() {
+        let response: DohResponse = serde_yaml::from_str(RESPONSE).unwrap();
+        assert_eq!(
+            response.comment.as_deref(),
+            Some("Response from 192.0.2.53.")
+        );
+        let msg = response.to_message().unwrap();
+        let header = msg.header();
+        assert!(header.qr() && header.rd() && header.ra() && header.ad());
+        assert!(!header.tc() && !header.cd());
+        assert_eq!(msg.sole_question().unwrap().qtype(), Rtype::A);
+        assert_eq!(msg.header_counts().ancount(), 4);
+
+        let answer = msg.answer().unwrap();
+        let a = answer.limit_to::<A>().next().unwrap().unwrap();
+        assert_eq!(a.data(), &A::from_octets(192, 0, 2, 1));
+        let txt = answer.limit_to::<Txt<_>>().next().unwrap().unwrap();
+        let strings: Vec<_> = txt.data().iter().collect();
+        assert_eq!(strings, [b"v=spf1 -all".as_ref(), b"more \"text\""]);
+        let mx = answer.limit_to::<Mx<_>>().next().unwrap().unwrap();
+        assert_eq!(mx.data().preference(), 10);
+        assert_eq!(mx.ttl(), Ttl::from_secs(3600));
+
+        // Round trip through a message.
+        let mut back = DohResponse::from_message(&msg).unwrap();
+        back.comment = response.comment.clone();
+        let msg2 = back.to_message().unwrap();
+        assert_eq!(msg.as_slice(), msg2.as_slice());
+    }
+
+    #[test]
+    fn extended_rcode() {
+        let response = DohResponse {
+            status: 16,
+            ..Default::default()
+        };
+        let msg = response.to_message().unwrap();
+        assert_eq!(msg.opt().unwrap().rcode(msg.header()).to_int(), 16);
+        assert_eq!(DohResponse::from_message(&msg).unwrap(), response);
+    }
+
+    #[test]
+    fn bad_data() {
+        let record = |data: &str| DohRecord {
+            name: "example.com.".into(),
+            rtype: 1,
+            ttl: 10,
+            data: data.into(),
+        };
+        assert!(record("192.0.2.1").to_record().is_ok());
+        assert!(matches!(
+            record("192.0.2.1 192.0.2.2").to_record(),
+            Err(DohError::Data(_))
+        ));
+        assert!(matches!(record("\"").to_record(), Err(DohError::Data(_))));
+        assert!(matches!(record("foo").to_record(), Err(DohError::Data(_))));
+    }
+
+    #[test]
+    fn query() {
+        let name = Dname::<Vec<u8>>::from_str("ex\\032ample.com").unwrap();
+        assert_eq!(
+            query_string(&name, Rtype::Aaaa, true, false),
+            "name=ex%5C%20ample.com.&type=28&do=1"
+        );
+        assert_eq!(
+            query_string(&Dname::root_ref(), Rtype::Ns, false, true),
+            "name=.&type=2&cd=1"
+        );
+    }
+}
@@ -0,0 +1,695 @@
+//! DNS messages and records in JSON.
+//!
+//! [RFC 8427] defines a representation of DNS messages and resource
+//! records as JSON objects. This module allows serializing and
+//! deserializing messages, questions, and records into and from this
+//! representation through Serde. Since the format itself is defined as
+//! JSON, it is intended for use with human readable serializers.
+//!
+//! Because the RFC 8427 representation uses its own names for the various
+//! members, it can’t be the regular Serde representation of the types.
+//! Instead, it is available through the wrapper type [`Json`]: a reference
+//! to a [`Message`], [`Question`], or [`Record`] wrapped in `Json` will
+//! serialize in the RFC 8427 format. In the opposite direction, a `Json`
+//! wrapping a message, question, or record using owned octets can be
+//! deserialized from it.
+//!
+//! When serializing a record, its record data is included both as the
+//! `RDATAHEX` member, i.e., in its uncompressed wire format, and in
+//! presentation format as the member named `rdata` followed by the record
+//! type’s mnemonic, e.g., `rdataA`. When deserializing, only the
+//! `RDATAHEX` member is used and the record data becomes
+//! [`UnknownRecordData`]. It can be converted into concrete record data by
+//! parsing it.
+//!
+//! If a message’s object contains the `messageOctetsHEX` member, this
+//! member takes precedence over all the other members upon
+//! deserialization. Otherwise, the message is constructed from its header
+//! and section members. Members defined by RFC 8427 that aren’t needed to
+//! construct a message as well as unknown members are ignored.
+//!
+//! [RFC 8427]: https://tools.ietf.org/html/rfc8427
+#![cfg(all(feature = "serde", feature = "std"))]
+#![cfg_attr(docsrs, doc(cfg(all(feature = "serde", feature = "std"))))]
+
+use super::iana::{Class, Opcode, Rcode, Rtype};
+use super::message::Message;
+use super::message_builder::MessageBuilder;
+use super::name::{Dname, ToDname};
+use super::question::Question;
+use super::rdata::{ComposeRecordData, RecordData, UnknownRecordData};
+use super::record::{Record, Ttl};
+use crate::rdata::AllRecordData;
+use crate::utils::base16;
+use core::fmt;
+use core::str::FromStr;
+use octseq::builder::infallible;
+use octseq::octets::Octets;
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny};
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+pub mod doh;
+
+//------------ Json ----------------------------------------------------------
+
+/// A wrapper for serializing into and deserializing from RFC 8427 JSON.
+///
+/// See the [module documentation][self] for details.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+//--- Serialize
+
+impl<Octs: Octets> Serialize for Json<&Message<Octs>> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let msg = self.0;
+        let header = msg.header();
+        let counts = msg.header_counts();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("ID", &header.id())?;
+        map.serialize_entry("QR", &header.qr())?;
+        map.serialize_entry("Opcode", &header.opcode().to_int())?;
+        map.serialize_entry("AA", &header.aa())?;
+        map.serialize_entry("TC", &header.tc())?;
+        map.serialize_entry("RD", &header.rd())?;
+        map.serialize_entry("RA", &header.ra())?;
+        map.serialize_entry("AD", &header.ad())?;
+        map.serialize_entry("CD", &header.cd())?;
+        map.serialize_entry("RCODE", &header.rcode().to_int())?;
+        map.serialize_entry("QDCOUNT", &counts.qdcount())?;
+        map.serialize_entry("ANCOUNT", &counts.ancount())?;
+        map.serialize_entry("NSCOUNT", &counts.nscount())?;
+        map.serialize_entry("ARCOUNT", &counts.arcount())?;
+
+        let mut questions = Vec::new();
+        for question in msg.question() {
+            questions.push(question.map_err(ser::Error::custom)?);
+        }
+        if let [question] = questions.as_slice() {
+            // The common case of a single question is represented by
+            // members of the message object.
+            map.serialize_entry("QNAME", &NameStr(question.qname()))?;
+            map.serialize_entry("QTYPE", &question.qtype().to_int())?;
+            map.serialize_entry("QTYPEname", &question.qtype().to_string())?;
+            map.serialize_entry("QCLASS", &question.qclass().to_int())?;
+            map.serialize_entry(
+                "QCLASSname",
+                &question.qclass().to_string(),
+            )?;
+        } else if !questions.is_empty() {
+            let questions: Vec<_> = questions.iter().map(Json).collect();
+            map.serialize_entry("questionRRs", &questions)?;
+        }
+
+        let mut section = msg.answer().map_err(ser::Error::custom)?;
+        for key in ["answerRRs", "authorityRRs", "additionalRRs"] {
+            let mut records = Vec::new();
+            for record in &mut section {
+                let record = record.map_err(ser::Error::custom)?;
+                let record = record
+                    .into_record::<AllRecordData<_, _>>()
+                    .map_err(ser::Error::custom)?;
+                if let Some(record) = record {
+                    records.push(record)
+                }
+            }
+            if !records.is_empty() {
+                let records: Vec<_> = records.iter().map(Json).collect();
+                map.serialize_entry(key, &records)?;
+            }
+            section = match section.next_section() {
+                Ok(Some(section)) => section,
+                Ok(None) => break,
+                Err(err) => return Err(ser::Error::custom(err)),
+            };
+        }
+        map.end()
+    }
+}
+
+impl<N: ToDname> Serialize for Json<&Question<N>> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let question = self.0;
+        let mut map = serializer.serialize_map(Some(5))?;
+        map.serialize_entry("NAME", &NameStr(question.qname()))?;
+        map.serialize_entry("TYPE", &question.qtype().to_int())?;
+        map.serialize_entry("TYPEname", &question.qtype().to_string())?;
+        map.serialize_entry("CLASS", &question.qclass().to_int())?;
+        map.serialize_entry("CLASSname", &question.qclass().to_string())?;
+        map.end()
+    }
+}
+
+impl<N, D> Serialize for Json<&Record<N, D>>
+where
+    N: ToDname,
+    D: RecordData + ComposeRecordData + fmt::Display,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let record = self.0;
+        let rtype = record.rtype();
+        let mut rdata = Vec::new();
+        infallible(record.data().compose_rdata(&mut rdata));
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("NAME", &NameStr(record.owner()))?;
+        map.serialize_entry("TYPE", &rtype.to_int())?;
+        map.serialize_entry("TYPEname", &rtype.to_string())?;
+        map.serialize_entry("CLASS", &record.class().to_int())?;
+        map.serialize_entry("CLASSname", &record.class().to_string())?;
+        map.serialize_entry("TTL", &record.ttl().as_secs())?;
+        map.serialize_entry("RDLENGTH", &rdata.len())?;
+        map.serialize_entry("RDATAHEX", &base16::encode_string(&rdata))?;
+        // OPT records don’t have a presentation format.
+        if rtype != Rtype::Opt {
+            map.serialize_entry(
+                &format!("rdata{}", rtype),
+                &record.data().to_string(),
+            )?;
+        }
+        map.end()
+    }
+}
+
+//--- Deserialize
+
+impl<'de> Deserialize<'de> for Json<Message<Vec<u8>>> {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Json<Message<Vec<u8>>>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a DNS message object")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut builder = MessageBuilder::new_vec();
+                let mut octets = None;
+                let mut qname = None;
+                let mut qtype = TypeMembers::default();
+                let mut qclass = TypeMembers::default();
+                let mut questions = Vec::<Json<Question<_>>>::new();
+                let mut answer = Vec::<Json<Record<_, _>>>::new();
+                let mut authority = Vec::<Json<Record<_, _>>>::new();
+                let mut additional = Vec::<Json<Record<_, _>>>::new();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    let header = builder.header_mut();
+                    match key.as_str() {
+                        "ID" => header.set_id(map.next_value()?),
+                        "QR" => header.set_qr(map.next_value::<Flag>()?.0),
+                        "Opcode" => {
+                            header.set_opcode(Opcode::from_int(
+                                map.next_value()?,
+                            ));
+                        }
+                        "AA" => header.set_aa(map.next_value::<Flag>()?.0),
+                        "TC" => header.set_tc(map.next_value::<Flag>()?.0),
+                        "RD" => header.set_rd(map.next_value::<Flag>()?.0),
+                        "RA" => header.set_ra(map.next_value::<Flag>()?.0),
+                        "AD" => header.set_ad(map.next_value::<Flag>()?.0),
+                        "CD" => header.set_cd(map.next_value::<Flag>()?.0),
+                        "RCODE" => {
+                            header.set_rcode(Rcode::from_int(
+                                map.next_value()?,
+                            ));
+                        }
+                        "QNAME" => {
+                            qname = Some(parse_name(
+                                &map.next_value::<String>()?,
+                            )?);
+                        }
+                        "QTYPE" => qtype.int = Some(map.next_value()?),
+                        "QTYPEname" => qtype.name = Some(map.next_value()?),
+                        "QCLASS" => qclass.int = Some(map.next_value()?),
+                        "QCLASSname" => qclass.name = Some(map.next_value()?),
+                        "questionRRs" => questions = map.next_value()?,
+                        "answerRRs" => answer = map.next_value()?,
+                        "authorityRRs" => authority = map.next_value()?,
+                        "additionalRRs" => additional = map.next_value()?,
+                        "messageOctetsHEX" => {
+                            octets = Some(
+                                base16::decode_vec(
+                                    &map.next_value::<String>()?,
+                                )
+                                .map_err(de::Error::custom)?,
+                            );
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                if let Some(octets) = octets {
+                    return Message::from_octets(octets)
+                        .map(Json)
+                        .map_err(de::Error::custom);
+                }
+
+                let mut builder = builder.question();
+                if let Some(qname) = qname {
+                    builder
+                        .push(Question::new(
+                            qname,
+                            qtype.get::<Rtype, A::Error>("QTYPE")?,
+                            qclass.get::<Class, A::Error>("QCLASS")?,
+                        ))
+                        .map_err(de::Error::custom)?;
+                }
+                for question in questions {
+                    builder.push(question.0).map_err(de::Error::custom)?;
+                }
+                let mut builder = builder.answer();
+                for record in answer {
+                    builder.push(record.0).map_err(de::Error::custom)?;
+                }
+                let mut builder = builder.authority();
+                for record in authority {
+                    builder.push(record.0).map_err(de::Error::custom)?;
+                }
+                let mut builder = builder.additional();
+                for record in additional {
+                    builder.push(record.0).map_err(de::Error::custom)?;
+                }
+                Ok(Json(builder.into_message()))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Json<Question<Dname<Vec<u8>>>> {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Json<Question<Dname<Vec<u8>>>>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a DNS question object")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut name = None;
+                let mut rtype = TypeMembers::default();
+                let mut class = TypeMembers::default();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "NAME" => {
+                            name = Some(parse_name(
+                                &map.next_value::<String>()?,
+                            )?);
+                        }
+                        "TYPE" => rtype.int = Some(map.next_value()?),
+                        "TYPEname" => rtype.name = Some(map.next_value()?),
+                        "CLASS" => class.int = Some(map.next_value()?),
+                        "CLASSname" => class.name = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(Json(Question::new(
+                    name.ok_or_else(|| de::Error::missing_field("NAME"))?,
+                    rtype.get("TYPE")?,
+                    class.get("CLASS")?,
+                )))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+impl<'de> Deserialize<'de>
+    for Json<Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>>
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value =
+                Json<Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a DNS resource record object")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut name = None;
+                let mut rtype = TypeMembers::default();
+                let mut class = TypeMembers::default();
+                let mut ttl = None;
+                let mut rdata = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "NAME" => {
+                            name = Some(parse_name(
+                                &map.next_value::<String>()?,
+                            )?);
+                        }
+                        "TYPE" => rtype.int = Some(map.next_value()?),
+                        "TYPEname" => rtype.name = Some(map.next_value()?),
+                        "CLASS" => class.int = Some(map.next_value()?),
+                        "CLASSname" => class.name = Some(map.next_value()?),
+                        "TTL" => ttl = Some(map.next_value()?),
+                        "RDATAHEX" => {
+                            rdata = Some(
+                                base16::decode_vec(
+                                    &map.next_value::<String>()?,
+                                )
+                                .map_err(de::Error::custom)?,
+                            );
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                let rdata = UnknownRecordData::from_octets(
+                    rtype.get("TYPE")?,
+                    rdata.ok_or_else(|| {
+                        de::Error::missing_field("RDATAHEX")
+                    })?,
+                )
+                .map_err(de::Error::custom)?;
+                Ok(Json(Record::new(
+                    name.ok_or_else(|| de::Error::missing_field("NAME"))?,
+                    class.get("CLASS")?,
+                    Ttl::from_secs(
+                        ttl.ok_or_else(|| de::Error::missing_field("TTL"))?,
+                    ),
+                    rdata,
+                )))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+//------------ NameStr -------------------------------------------------------
+
+/// Serializes a domain name as an absolute name in presentation format.
+struct NameStr<'a, N: ?Sized>(&'a N);
+
+impl<'a, N: ToDname + ?Sized> fmt::Display for NameStr<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut empty = true;
+        for label in self.0.iter_labels() {
+            if label.is_root() {
+                if empty {
+                    f.write_str(".")?;
+                }
+            } else {
+                write!(f, "{}.", label)?;
+                empty = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, N: ToDname + ?Sized> Serialize for NameStr<'a, N> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Parses a domain name from its presentation format.
+fn parse_name<E: de::Error>(s: &str) -> Result<Dname<Vec<u8>>, E> {
+    if s == "." {
+        Ok(Dname::root_vec())
+    } else {
+        Dname::from_str(s).map_err(E::custom)
+    }
+}
+
+//------------ TypeMembers ---------------------------------------------------
+
+/// The integer and name members of a type or class.
+///
+/// RFC 8427 represents record types and classes by an integer member and
+/// optionally a member with the mnemonic. If both are present, the integer
+/// member wins.
+#[derive(Default)]
+struct TypeMembers {
+    int: Option<u16>,
+    name: Option<String>,
+}
+
+impl TypeMembers {
+    fn get<T, E>(self, member: &'static str) -> Result<T, E>
+    where
+        T: From<u16> + FromStr,
+        T::Err: fmt::Display,
+        E: de::Error,
+    {
+        match (self.int, self.name) {
+            (Some(int), _) => Ok(int.into()),
+            (None, Some(name)) => T::from_str(&name).map_err(E::custom),
+            (None, None) => Err(E::missing_field(member)),
+        }
+    }
+}
+
+//------------ Flag ----------------------------------------------------------
+
+/// A header flag.
+///
+/// RFC 8427 defines the flags as booleans but its examples use integers,
+/// so both are accepted.
+struct Flag(bool);
+
+impl<'de> Deserialize<'de> for Flag {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Flag;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a boolean or an integer")
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Flag, E> {
+                Ok(Flag(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Flag, E> {
+                Ok(Flag(v != 0))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Flag, E> {
+                Ok(Flag(v != 0))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::{Aaaa, Ns, A};
+
+    type Name = Dname<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn message() -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(32784);
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((name("example.com."), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com."), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut msg = msg.authority();
+        msg.push((name("example.com."), 3600, Ns::new(name("ns.example."))))
+            .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            name("ns.example."),
+            3600,
+            Aaaa::new("2001:db8::1".parse().unwrap()),
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    #[test]
+    fn serialize_message() {
+        let msg = message();
+        let yaml = serde_yaml::to_string(&Json(&msg)).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(value["ID"], 32784);
+        assert_eq!(value["QR"], true);
+        assert_eq!(value["AA"], false);
+        assert_eq!(value["RD"], true);
+        assert_eq!(value["ANCOUNT"], 1);
+        assert_eq!(value["QNAME"], "example.com.");
+        assert_eq!(value["QTYPE"], 1);
+        assert_eq!(value["QTYPEname"], "A");
+        assert_eq!(value["QCLASSname"], "IN");
+        let answer = &value["answerRRs"][0];
+        assert_eq!(answer["NAME"], "example.com.");
+        assert_eq!(answer["TTL"], 3600);
+        assert_eq!(answer["RDLENGTH"], 4);
+        assert_eq!(answer["RDATAHEX"], "C0000201");
+        assert_eq!(answer["rdataA"], "192.0.2.1");
+        assert_eq!(value["authorityRRs"][0]["rdataNS"], "ns.example.");
+        assert_eq!(value["additionalRRs"][0]["rdataAAAA"], "2001:db8::1");
+    }
+
+    #[test]
+    fn round_trip() {
+        let msg = message();
+        let yaml = serde_yaml::to_string(&Json(&msg)).unwrap();
+        let back: Json<Message<Vec<u8>>> =
+            serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(msg.as_slice(), back.0.as_slice());
+    }
+
+    #[test]
+    fn deserialize_rfc8427_example() {
+        // The response example from RFC 8427, section 6.2.
+        let msg: Json<Message<Vec<u8>>> = serde_yaml::from_str(
+            r#"{
+                "ID": 32784, "QR": 1, "AA": 1, "RCODE": 0,
+                "QDCOUNT": 1, "ANCOUNT": 2, "NSCOUNT": 1, "ARCOUNT": 0,
+                "QNAME": "example.com.", "QTYPE": 1, "QCLASS": 1,
+                "answerRRs": [
+                    { "NAME": "example.com.", "TYPE": 1, "CLASS": 1,
+                      "TTL": 3600, "RDATAHEX": "C0000201" },
+                    { "NAME": "example.com.", "TYPE": 1, "CLASS": 1,
+                      "TTL": 3600, "RDATAHEX": "C000AA01" }
+                ],
+                "authorityRRs": [
+                    { "NAME": "ns.example.com.", "TYPEname": "A",
+                      "CLASSname": "IN", "TTL": 28800,
+                      "RDATAHEX": "CB007181" }
+                ],
+                "dateString": "2019-02-03T12:34:56Z"
+            }"#,
+        )
+        .unwrap();
+        let msg = msg.into_inner();
+        assert_eq!(msg.header().id(), 32784);
+        assert!(msg.header().qr());
+        assert!(msg.header().aa());
+        assert!(!msg.header().rd());
+        assert_eq!(msg.header_counts().ancount(), 2);
+        assert_eq!(msg.header_counts().nscount(), 1);
+        let question = msg.sole_question().unwrap();
+        assert_eq!(question.qname().to_string(), "example.com");
+        assert_eq!(question.qtype(), Rtype::A);
+        let answer: Vec<_> = msg
+            .answer()
+            .unwrap()
+            .limit_to::<A>()
+            .map(|record| record.unwrap().data().addr().to_string())
+            .collect();
+        assert_eq!(answer, ["192.0.2.1", "192.0.170.1"]);
+        let ns = msg
+            .authority()
+            .unwrap()
+            .limit_to::<A>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ns.ttl(), Ttl::from_secs(28800));
+        assert_eq!(ns.data().addr().to_string(), "203.0.113.129");
+    }
+
+    #[test]
+    fn message_octets() {
+        let msg = message();
+        let value = format!(
+            "{{\"ID\": 1, \"messageOctetsHEX\": \"{}\"}}",
+            base16::encode_string(msg.as_slice())
+        );
+        let back: Json<Message<Vec<u8>>> =
+            serde_yaml::from_str(&value).unwrap();
+        assert_eq!(msg.as_slice(), back.0.as_slice());
+    }
+
+    #[test]
+    fn record() {
+        let record = Record::new(
+            Name::root_vec(),
+            Class::In,
+            Ttl::from_secs(60),
+            UnknownRecordData::from_octets(Rtype::Int(65280), vec![1, 2])
+                .unwrap(),
+        );
+        let yaml = serde_yaml::to_string(&Json(&record)).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(value["NAME"], ".");
+        assert_eq!(value["TYPEname"], "TYPE65280");
+        assert_eq!(value["rdataTYPE65280"], "\\# 2 01 02");
+        let back: Json<Record<Name, UnknownRecordData<Vec<u8>>>> =
+            serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(back.0, record);
+
+        assert!(serde_yaml::from_str::<
+            Json<Record<Name, UnknownRecordData<Vec<u8>>>>,
+        >(
+            r#"{ "NAME": "example.", "TYPE": 1, "CLASS": 1, "TTL": 10 }"#
+        )
+        .is_err());
+    }
+}
@@ -15,11 +15,13 @@ use super::iana::{Class, Rcode, Rtype};
 use super::message_builder::{AdditionalBuilder, AnswerBuilder, PushError};
 use super::name::ParsedDname;
 use super::opt::{Opt, OptRecord};
+use super::policy::{self, ParsePolicy, Violation};
 use super::question::Question;
-use super::rdata::ParseRecordData;
+use super::rdata::{ComposeRecordData, ParseRecordData};
 use super::record::{ComposeRecord, ParsedRecord, Record};
 use super::wire::{Composer, ParseError};
 use crate::rdata::rfc1035::Cname;
+use crate::rdata::AllRecordData;
 use core::marker::PhantomData;
 use core::{fmt, mem};
 use octseq::{Octets, OctetsFrom, Parser};
@@ -128,6 +130,14 @@ use octseq::{Octets, OctetsFrom, Parser};
 /// message is fully broken, the next iteration will return `None` to signal
 /// that.
 ///
+/// Names and record data parsed from the message use the octets sequence
+/// type that the message’s octets sequence uses for its ranges. For a
+/// message atop a `bytes::Bytes` value, they thus are `Bytes` values, too,
+/// which share ownership of the message’s buffer rather than copying it.
+/// Such records can be kept around or sent to other threads independently
+/// of the message. For a message atop a slice, they borrow from the slice
+/// instead.
+///
 /// [`additional`]: #method.additional
 /// [`answer`]: #method.answer
 /// [`authority`]: #method.authority
@@ -444,6 +454,42 @@ impl<Octs: Octets + ?Sized> Message<Octs> {
         self.question().next().unwrap()
     }
 
+    /// Returns how many questions the message has.
+    ///
+    /// While regular queries and responses have exactly one question, this
+    /// isn’t true for all messages. The zone section of UPDATE messages
+    /// takes the place of the question section and queries that only
+    /// carry a DNS cookie have no question at all. Use
+    /// [`question`][Self::question] to iterate over all questions.
+    pub fn question_count(&self) -> QuestionCount {
+        match self.header_counts().qdcount() {
+            0 => QuestionCount::Zero,
+            1 => QuestionCount::One,
+            count => QuestionCount::Multiple(count),
+        }
+    }
+
+    /// Returns the zone of an UPDATE message.
+    ///
+    /// RFC 2136 requires the zone section to contain exactly one entry of
+    /// type SOA. The method returns an error if that isn’t the case.
+    pub fn update_zone(
+        &self,
+    ) -> Result<Question<ParsedDname<Octs::Range<'_>>>, ParseError> {
+        let zone = match self.header_counts().qdcount() {
+            1 => self.question().next().unwrap()?,
+            _ => {
+                return Err(ParseError::form_error(
+                    "zone section must contain one zone",
+                ))
+            }
+        };
+        if zone.qtype() != Rtype::Soa {
+            return Err(ParseError::form_error("zone type must be SOA"));
+        }
+        Ok(zone)
+    }
+
     /// Returns the query type of the first question, if any.
     pub fn qtype(&self) -> Option<Rtype> {
         self.first_question().map(|x| x.qtype())
@@ -624,6 +670,169 @@ impl<Octs: Octets + ?Sized> Message<Octs> {
     }
 }
 
+/// # Checking the Message
+///
+/// Messages are parsed lazily: the methods above only look at those parts
+/// of a message that are actually accessed. The methods in this section
+/// walk over the complete message instead and report the first problem they
+/// encounter. The problem is classified via [`MessageErrorKind`], so these
+/// methods can be used to reject malformed messages early.
+///
+/// Both methods are guaranteed to terminate for any input. In particular,
+/// compression pointers must always point to an earlier position in the
+/// message, so a sequence of pointers can never form a loop.
+///
+impl<Octs: Octets> Message<Octs> {
+    /// Checks that the complete message can be parsed.
+    ///
+    /// The method walks over all questions and records of the message and
+    /// parses them, including the record data of all record types known to
+    /// [`AllRecordData`]. It applies the same rules as the regular parsing
+    /// methods, so if it succeeds, these methods will succeed, too. If it
+    /// fails, the returned error describes what went wrong and where in the
+    /// message it happened.
+    ///
+    /// Octets left over after the last record are ignored.
+    pub fn check(&self) -> Result<(), MessageError> {
+        self.check_parts(false, &ParsePolicy::new(), |_| ())
+    }
+
+    /// Strictly checks that the complete message is well-formed.
+    ///
+    /// In addition to the checks performed by [`check`][Self::check],
+    /// this method requires that each compression pointer in a question or
+    /// owner name points to a position before the one it was reached from,
+    /// i.e., before the start of the name for the first pointer and before
+    /// the target of the previous pointer for all others, as is the case
+    /// for all names compressed as described in RFC 1035. It also rejects
+    /// messages with octets left over after the last record.
+    ///
+    /// Names within record data are checked by the rules of the regular
+    /// parsing methods only.
+    pub fn check_strict(&self) -> Result<(), MessageError> {
+        self.check_parts(true, &ParsePolicy::new(), |_| ())
+    }
+
+    /// Checks the complete message applying a policy.
+    ///
+    /// The method performs the same checks as [`check`][Self::check]. In
+    /// addition, it looks for questionable records as described in the
+    /// [`policy`][super::policy] module and deals with them according to
+    /// `policy`: Violations the policy denies are returned as an error of
+    /// kind [`MessageErrorKind::Policy`]. Violations that should result in
+    /// a warning are handed to `warn` in the same form and checking
+    /// continues.
+    ///
+    /// Since messages don’t belong to a zone, the policy for out-of-zone
+    /// data is not used.
+    pub fn check_with_policy(
+        &self,
+        policy: &ParsePolicy,
+        warn: impl FnMut(MessageError),
+    ) -> Result<(), MessageError> {
+        self.check_parts(false, policy, warn)
+    }
+
+    /// Walks over the complete message.
+    fn check_parts(
+        &self,
+        strict: bool,
+        policy: &ParsePolicy,
+        mut warn: impl FnMut(MessageError),
+    ) -> Result<(), MessageError> {
+        let counts = self.header_counts();
+        let mut parser = Parser::from_ref(&self.octets);
+        parser.advance(mem::size_of::<HeaderSection>()).unwrap();
+
+        for index in 0..counts.qdcount() {
+            let offset = parser.pos();
+            let part = MessagePart::Question(index);
+            let fail = |kind| MessageError::from_kind(kind, offset, part);
+            if parser.remaining() == 0 {
+                return Err(fail(MessageErrorKind::CountMismatch));
+            }
+            check_name(&mut parser, strict).map_err(fail)?;
+            parser
+                .advance(4)
+                .map_err(|_| fail(MessageErrorKind::Truncated))?;
+        }
+
+        for section in
+            [Section::Answer, Section::Authority, Section::Additional]
+        {
+            for index in 0..section.count(counts) {
+                let offset = parser.pos();
+                let part = MessagePart::Record(section, index);
+                let fail = |kind| MessageError::from_kind(kind, offset, part);
+                if parser.remaining() == 0 {
+                    return Err(fail(MessageErrorKind::CountMismatch));
+                }
+                check_name(&mut parser, strict).map_err(fail)?;
+                if parser.remaining() < 10 {
+                    return Err(fail(MessageErrorKind::Truncated));
+                }
+                let rtype = Rtype::parse(&mut parser).unwrap();
+                parser.advance(6).unwrap();
+                let rdlen = usize::from(parser.parse_u16_be().unwrap());
+                let fail = |kind| fail(kind).with_rtype(rtype);
+                if rdlen > parser.remaining() {
+                    return Err(fail(MessageErrorKind::RdlenOverrun));
+                }
+
+                // The framing of the record is fine, so we can now parse it
+                // for real.
+                parser.seek(offset).unwrap();
+                let record = ParsedRecord::parse(&mut parser)
+                    .and_then(|record| {
+                        record
+                            .into_record::<AllRecordData<_, ParsedDname<_>>>()
+                    })
+                    .map_err(|err| {
+                        fail(MessageErrorKind::BadRecordData).with_error(err)
+                    })?;
+
+                // Now we can apply the policy.
+                let mut apply = |violation| match policy.check(violation) {
+                    Ok(None) => Ok(()),
+                    Ok(Some(violation)) => {
+                        warn(fail(MessageErrorKind::Policy(violation)));
+                        Ok(())
+                    }
+                    Err(violation) => {
+                        Err(fail(MessageErrorKind::Policy(violation)))
+                    }
+                };
+                if policy::is_obsolete(rtype, false) {
+                    apply(Violation::ObsoleteType(rtype))?;
+                }
+                if !policy::allows_compression(rtype) {
+                    // Any compressed name makes the record data shorter
+                    // than its uncompressed form.
+                    let compressed =
+                        record.as_ref().map_or(false, |record| {
+                            matches!(
+                                record.data().rdlen(false),
+                                Some(len) if usize::from(len) > rdlen
+                            )
+                        });
+                    if compressed {
+                        apply(Violation::ForbiddenCompression(rtype))?;
+                    }
+                }
+            }
+        }
+
+        if strict && parser.remaining() > 0 {
+            return Err(MessageError::from_kind(
+                MessageErrorKind::TrailingData,
+                parser.pos(),
+                MessagePart::End,
+            ));
+        }
+        Ok(())
+    }
+}
+
 //--- AsRef
 
 // Octs here can’t be ?Sized or it’ll conflict with AsRef<[u8]> below.
@@ -815,6 +1024,34 @@ where
     }
 }
 
+//------------ QuestionCount -------------------------------------------------
+
+/// The number of questions in a message.
+///
+/// A value of this type is returned by [`Message::question_count`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QuestionCount {
+    /// The message has no question.
+    Zero,
+
+    /// The message has exactly one question.
+    One,
+
+    /// The message has the given number of questions, at least two.
+    Multiple(u16),
+}
+
+impl QuestionCount {
+    /// Returns the number of questions.
+    pub fn count(self) -> u16 {
+        match self {
+            QuestionCount::Zero => 0,
+            QuestionCount::One => 1,
+            QuestionCount::Multiple(count) => count,
+        }
+    }
+}
+
 //------------ Section -------------------------------------------------------
 
 /// A helper type enumerating the three kinds of record sections.
@@ -1066,15 +1303,20 @@ impl<'a, Octs: Octets + ?Sized> Iterator for MessageIter<'a, Octs> {
 /// also produce errors. This case can be distinguished from an error while
 /// parsing the record data by [`next_section`] returning an error, too.
 ///
+/// The iterator can be switched to a lenient mode via [`lenient`] in which
+/// records with record data that fails to parse are skipped, too.
+///
 /// You can create a value of this type through the
 /// [`RecordSection::limit_to`] method.
 ///
+/// [`lenient`]: #method.lenient
 /// [`next_section`]: #method.next_section
 /// [`RecordSection::limit_to`]: struct.RecordSection.html#method.limit_to
 #[derive(Debug)]
 pub struct RecordIter<'a, Octs: ?Sized, Data> {
     section: RecordSection<'a, Octs>,
     in_only: bool,
+    lenient: bool,
     marker: PhantomData<Data>,
 }
 
@@ -1088,10 +1330,23 @@ where
         RecordIter {
             section,
             in_only,
+            lenient: false,
             marker: PhantomData,
         }
     }
 
+    /// Switches the iterator to lenient mode.
+    ///
+    /// In lenient mode, records are silently skipped if their record data
+    /// cannot be parsed. This is safe since the record data length in the
+    /// record header determines where the next record starts. Errors
+    /// parsing the record header still end the iteration as these leave
+    /// the rest of the section unusable.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
     /// Trades the limited iterator for the full iterator.
     ///
     /// The returned iterator will continue right after the last record
@@ -1118,6 +1373,7 @@ impl<'a, Octs: ?Sized, Data> Clone for RecordIter<'a, Octs, Data> {
         RecordIter {
             section: self.section,
             in_only: self.in_only,
+            lenient: self.lenient,
             marker: PhantomData,
         }
     }
@@ -1145,6 +1401,7 @@ where
             }
             match record.into_record() {
                 Ok(Some(record)) => return Some(Ok(record)),
+                Err(_) if self.lenient => {}
                 Err(err) => return Some(Err(err)),
                 Ok(None) => {}
             }
@@ -1152,6 +1409,67 @@ where
     }
 }
 
+//------------ Helper Functions ----------------------------------------------
+
+/// Checks the domain name at the parser’s position and moves past it.
+///
+/// Each compression pointer must point to a position before the pointer
+/// itself. If `strict` is true, the first pointer must point to a position
+/// before the start of the name and each later pointer to a position before
+/// the target of the previous pointer. Either way, the positions visited
+/// keep decreasing or the name grows, so the function always terminates.
+fn check_name<Octs: AsRef<[u8]> + ?Sized>(
+    parser: &mut Parser<Octs>,
+    strict: bool,
+) -> Result<(), MessageErrorKind> {
+    let mut tmp = *parser;
+    let mut limit = tmp.pos();
+    let mut name_len = 0;
+    let mut jumped = false;
+    loop {
+        // Running out of octets or finding garbage after following a
+        // pointer means the pointer is bad, not the message.
+        let (short, bad_label) = if jumped {
+            (
+                MessageErrorKind::BadCompressionPointer,
+                MessageErrorKind::BadCompressionPointer,
+            )
+        } else {
+            (MessageErrorKind::Truncated, MessageErrorKind::BadLabel)
+        };
+        let pos = tmp.pos();
+        match tmp.parse_u8().map_err(|_| short)? {
+            0 => {
+                if !jumped {
+                    *parser = tmp;
+                }
+                return Ok(());
+            }
+            len @ 1..=0x3F => {
+                tmp.advance(len.into()).map_err(|_| short)?;
+                name_len += usize::from(len) + 1;
+                if name_len >= 255 {
+                    return Err(MessageErrorKind::LongName);
+                }
+            }
+            ltype @ 0xC0..=0xFF => {
+                let low = tmp.parse_u8().map_err(|_| short)?;
+                let ptr = (usize::from(ltype & 0x3F) << 8) | usize::from(low);
+                if ptr >= if strict { limit } else { pos } {
+                    return Err(MessageErrorKind::BadCompressionPointer);
+                }
+                if !jumped {
+                    *parser = tmp;
+                    jumped = true;
+                }
+                limit = ptr;
+                tmp.seek(ptr).unwrap();
+            }
+            _ => return Err(bad_label),
+        }
+    }
+}
+
 //============ Error Types ===================================================
 
 //------------ ShortMessage --------------------------------------------------
@@ -1203,6 +1521,253 @@ impl fmt::Display for CopyRecordsError {
 #[cfg(feature = "std")]
 impl std::error::Error for CopyRecordsError {}
 
+//------------ MessageError --------------------------------------------------
+
+/// Parsing a part of a message failed.
+///
+/// This error is returned by [`Message::check`] and
+/// [`Message::check_strict`]. In addition to the underlying [`ParseError`],
+/// it describes the kind of problem and where in the message it happened:
+/// the offset of the question or record from the start of the message,
+/// which question or record it was, and the record type if the record
+/// header could be parsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MessageError {
+    /// The kind of problem.
+    kind: MessageErrorKind,
+
+    /// The underlying parse error.
+    error: ParseError,
+
+    /// The offset of the question or record from the start of the message.
+    offset: usize,
+
+    /// The part of the message that failed to parse.
+    part: MessagePart,
+
+    /// The record type if the record header could be parsed.
+    rtype: Option<Rtype>,
+}
+
+impl MessageError {
+    /// Creates a new error for the given kind.
+    ///
+    /// The underlying parse error is derived from the kind.
+    fn from_kind(
+        kind: MessageErrorKind,
+        offset: usize,
+        part: MessagePart,
+    ) -> Self {
+        let error = match kind {
+            MessageErrorKind::CountMismatch
+            | MessageErrorKind::Truncated
+            | MessageErrorKind::RdlenOverrun => ParseError::ShortInput,
+            MessageErrorKind::BadLabel => {
+                ParseError::form_error("invalid label type")
+            }
+            MessageErrorKind::LongName => {
+                ParseError::form_error("long domain name")
+            }
+            MessageErrorKind::BadCompressionPointer => {
+                ParseError::form_error("too many compression pointers")
+            }
+            MessageErrorKind::BadRecordData => {
+                ParseError::form_error("invalid record data")
+            }
+            MessageErrorKind::TrailingData => {
+                ParseError::form_error("trailing data")
+            }
+            MessageErrorKind::Policy(violation) => {
+                ParseError::form_error(violation.as_str())
+            }
+        };
+        MessageError {
+            kind,
+            error,
+            offset,
+            part,
+            rtype: None,
+        }
+    }
+
+    /// Replaces the underlying parse error.
+    fn with_error(mut self, error: ParseError) -> Self {
+        self.error = error;
+        self
+    }
+
+    /// Adds the record type.
+    fn with_rtype(mut self, rtype: Rtype) -> Self {
+        self.rtype = Some(rtype);
+        self
+    }
+
+    /// Returns the kind of problem.
+    pub fn kind(&self) -> MessageErrorKind {
+        self.kind
+    }
+
+    /// Returns the underlying parse error.
+    ///
+    /// This is the error the regular parsing methods would return.
+    pub fn error(&self) -> ParseError {
+        self.error
+    }
+
+    /// Returns the offset of the failed question or record.
+    ///
+    /// The offset is given in octets from the start of the message and
+    /// points to the start of the question or record. For trailing data,
+    /// it points to the first octet after the last record.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the part of the message that failed to parse.
+    pub fn part(&self) -> MessagePart {
+        self.part
+    }
+
+    /// Returns the record type of the failed record.
+    ///
+    /// This is only available if the record header could be parsed.
+    pub fn rtype(&self) -> Option<Rtype> {
+        self.rtype
+    }
+}
+
+//--- From
+
+impl From<MessageError> for ParseError {
+    fn from(err: MessageError) -> Self {
+        err.error
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.part)?;
+        if let Some(rtype) = self.rtype {
+            write!(f, " ({})", rtype)?;
+        }
+        write!(f, " at offset {}: {}", self.offset, self.kind)?;
+        if self.kind == MessageErrorKind::BadRecordData {
+            write!(f, ": {}", self.error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MessageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+//------------ MessageErrorKind ----------------------------------------------
+
+/// The kind of problem with a malformed message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MessageErrorKind {
+    /// The message ended before all questions or records were found.
+    ///
+    /// The header announces more questions or records than the message
+    /// contains.
+    CountMismatch,
+
+    /// The message ended in the middle of a question or record header.
+    Truncated,
+
+    /// A domain name contains an invalid label type.
+    BadLabel,
+
+    /// A domain name is longer than 255 octets.
+    LongName,
+
+    /// A domain name contains a bad compression pointer.
+    ///
+    /// The pointer either doesn’t point to an earlier position in the
+    /// message or points to octets that don’t contain a valid name.
+    BadCompressionPointer,
+
+    /// The record data length goes beyond the end of the message.
+    RdlenOverrun,
+
+    /// The record data could not be parsed.
+    ///
+    /// This includes record data that is shorter or longer than what the
+    /// record type calls for.
+    BadRecordData,
+
+    /// There are octets left after the last record.
+    ///
+    /// This is only checked by [`Message::check_strict`].
+    TrailingData,
+
+    /// A record violates the parse policy.
+    ///
+    /// This is only checked by [`Message::check_with_policy`].
+    Policy(Violation),
+}
+
+impl fmt::Display for MessageErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            MessageErrorKind::CountMismatch => "fewer entries than announced",
+            MessageErrorKind::Truncated => "truncated",
+            MessageErrorKind::BadLabel => "invalid label type",
+            MessageErrorKind::LongName => "long domain name",
+            MessageErrorKind::BadCompressionPointer => {
+                "bad compression pointer"
+            }
+            MessageErrorKind::RdlenOverrun => {
+                "record data beyond end of message"
+            }
+            MessageErrorKind::BadRecordData => "bad record data",
+            MessageErrorKind::TrailingData => "trailing data",
+            MessageErrorKind::Policy(violation) => return violation.fmt(f),
+        })
+    }
+}
+
+//------------ MessagePart ---------------------------------------------------
+
+/// A question or record of a message.
+///
+/// Questions and records are identified by their index within their
+/// section, starting at zero.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MessagePart {
+    /// A question of the question section.
+    Question(u16),
+
+    /// A record of one of the three record sections.
+    Record(Section, u16),
+
+    /// The octets after the last record.
+    End,
+}
+
+impl fmt::Display for MessagePart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MessagePart::Question(index) => write!(f, "question {}", index),
+            MessagePart::Record(section, index) => {
+                let section = match section {
+                    Section::Answer => "answer",
+                    Section::Authority => "authority",
+                    Section::Additional => "additional",
+                };
+                write!(f, "{} record {}", section, index)
+            }
+            MessagePart::End => f.write_str("end of message"),
+        }
+    }
+}
+
 //============ Testing =======================================================
 
 #[cfg(test)]
@@ -1213,7 +1778,7 @@ mod test {
     #[cfg(feature = "std")]
     use crate::base::name::Dname;
     #[cfg(feature = "std")]
-    use crate::rdata::{AllRecordData, Ns};
+    use crate::rdata::{Ns, A};
     #[cfg(feature = "std")]
     use std::vec::Vec;
 
@@ -1238,6 +1803,56 @@ mod test {
         msg.into_message()
     }
 
+    #[test]
+    #[cfg(all(feature = "serde", feature = "std"))]
+    fn serialize_records() {
+        type Owned =
+            Record<Dname<Vec<u8>>, AllRecordData<Vec<u8>, Dname<Vec<u8>>>>;
+
+        let msg = get_test_message();
+        for record in msg.answer().unwrap() {
+            let record = record
+                .unwrap()
+                .into_record::<AllRecordData<_, _>>()
+                .unwrap()
+                .unwrap();
+            let yaml = serde_yaml::to_string(&record).unwrap();
+            let owned: Owned = serde_yaml::from_str(&yaml).unwrap();
+            assert_eq!(owned, record);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn question_count() {
+        let name =
+            Dname::<Vec<u8>>::from_chars("example.com".chars()).unwrap();
+
+        let msg = MessageBuilder::new_vec().into_message();
+        assert_eq!(msg.question_count(), QuestionCount::Zero);
+        assert!(msg.first_question().is_none());
+        assert!(msg.sole_question().is_err());
+        assert!(msg.update_zone().is_err());
+
+        let msg = MessageBuilder::new_vec()
+            .start_update(&name, Class::In)
+            .unwrap()
+            .into_message();
+        assert_eq!(msg.question_count(), QuestionCount::One);
+        assert_eq!(msg.update_zone().unwrap().qname(), &name);
+
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((&name, Rtype::A)).unwrap();
+        msg.push((&name, Rtype::Aaaa)).unwrap();
+        let msg = msg.into_message();
+        assert_eq!(msg.question_count(), QuestionCount::Multiple(2));
+        assert_eq!(msg.question_count().count(), 2);
+        assert_eq!(msg.question().count(), 2);
+        assert!(msg.sole_question().is_err());
+        assert!(msg.update_zone().is_err());
+        assert_eq!(msg.first_question().unwrap().qtype(), Rtype::A);
+    }
+
     #[test]
     fn short_message() {
         assert!(Message::from_octets(&[0u8; 11]).is_err());
@@ -1302,6 +1917,33 @@ mod test {
         assert!(msg.as_message().canonical_name().is_none());
     }
 
+    #[test]
+    #[cfg(all(feature = "std", feature = "bytes"))]
+    fn shared_octets() {
+        use crate::base::name::ParsedDname;
+        use bytes::Bytes;
+        use std::string::ToString;
+
+        let msg = get_test_message().into_octets();
+        let msg = Message::from_octets(Bytes::from(msg)).unwrap();
+        let records: Vec<Record<ParsedDname<Bytes>, Ns<ParsedDname<Bytes>>>> =
+            msg.authority()
+                .unwrap()
+                .limit_to::<Ns<_>>()
+                .map(Result::unwrap)
+                .collect();
+        drop(msg);
+        let names = std::thread::spawn(move || {
+            records
+                .iter()
+                .map(|record| record.data().nsdname().to_string())
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(names, ["baz.example.com"]);
+    }
+
     #[test]
     #[cfg(feature = "std")]
     fn message_iterator() {
@@ -1340,4 +1982,266 @@ mod test {
             assert_eq!(0, msg.header_counts().arcount());
         }
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn check() {
+        use std::string::ToString;
+
+        assert_eq!(get_test_message().check(), Ok(()));
+
+        // An A record with only three octets of record data.
+        let mut msg = MessageBuilder::new_vec().answer();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            3600,
+            A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        let mut octets = msg.finish();
+        octets[34] = 3;
+        octets.truncate(octets.len() - 1);
+        let err = Message::from_octets(octets).unwrap().check().unwrap_err();
+        assert_eq!(err.kind(), MessageErrorKind::BadRecordData);
+        assert_eq!(err.error(), ParseError::ShortInput);
+        assert_eq!(err.offset(), 12);
+        assert_eq!(err.part(), MessagePart::Record(Section::Answer, 0));
+        assert_eq!(err.rtype(), Some(Rtype::A));
+        assert_eq!(
+            err.to_string(),
+            "answer record 0 (A) at offset 12: \
+             bad record data: unexpected end of input"
+        );
+
+        // The second question is missing.
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((Dname::vec_from_str("example.com").unwrap(), Rtype::A))
+            .unwrap();
+        let mut octets = msg.finish();
+        octets[5] = 2;
+        let err = Message::from_octets(octets).unwrap().check().unwrap_err();
+        assert_eq!(err.kind(), MessageErrorKind::CountMismatch);
+        assert_eq!(err.offset(), 29);
+        assert_eq!(err.part(), MessagePart::Question(1));
+        assert_eq!(err.rtype(), None);
+    }
+
+    #[cfg(feature = "std")]
+    fn a_message() -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec().answer();
+        for addr in [1, 2] {
+            msg.push((
+                Dname::vec_from_str("example.com").unwrap(),
+                3600,
+                A::from_octets(192, 0, 2, addr),
+            ))
+            .unwrap();
+        }
+        msg.finish()
+    }
+
+    #[cfg(feature = "std")]
+    fn check_kind(octets: Vec<u8>) -> (MessageErrorKind, MessageErrorKind) {
+        let msg = Message::from_octets(octets).unwrap();
+        (
+            msg.check().unwrap_err().kind(),
+            msg.check_strict().unwrap_err().kind(),
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn check_strict() {
+        let mut octets = a_message();
+        assert!(Message::from_octets(octets.as_slice())
+            .unwrap()
+            .check()
+            .is_ok());
+        assert!(Message::from_octets(octets.as_slice())
+            .unwrap()
+            .check_strict()
+            .is_ok());
+
+        // Trailing data is only rejected in strict mode.
+        octets.push(0);
+        let msg = Message::from_octets(octets.as_slice()).unwrap();
+        assert!(msg.check().is_ok());
+        let err = msg.check_strict().unwrap_err();
+        assert_eq!(err.kind(), MessageErrorKind::TrailingData);
+        assert_eq!(err.offset(), octets.len() - 1);
+        assert_eq!(err.part(), MessagePart::End);
+
+        // Record data length of the second record goes beyond the end.
+        let mut octets = a_message();
+        octets[61] = 5;
+        let err = Message::from_octets(octets).unwrap().check().unwrap_err();
+        assert_eq!(err.kind(), MessageErrorKind::RdlenOverrun);
+        assert_eq!(err.offset(), 39);
+        assert_eq!(err.part(), MessagePart::Record(Section::Answer, 1));
+        assert_eq!(err.rtype(), Some(Rtype::A));
+
+        // Message ends within the second record header.
+        let mut octets = a_message();
+        octets.truncate(50);
+        assert_eq!(
+            check_kind(octets),
+            (MessageErrorKind::Truncated, MessageErrorKind::Truncated)
+        );
+
+        // Header claims a third record.
+        let mut octets = a_message();
+        octets[7] = 3;
+        assert_eq!(
+            check_kind(octets),
+            (
+                MessageErrorKind::CountMismatch,
+                MessageErrorKind::CountMismatch
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn check_compression() {
+        let question = |name: &[u8]| {
+            let mut octets = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+            octets.extend_from_slice(name);
+            octets.extend_from_slice(&[0, 1, 0, 1]);
+            octets
+        };
+
+        // Pointer to itself.
+        assert_eq!(
+            check_kind(question(b"\xc0\x0c")),
+            (
+                MessageErrorKind::BadCompressionPointer,
+                MessageErrorKind::BadCompressionPointer
+            )
+        );
+
+        // Pointer forward.
+        assert_eq!(
+            check_kind(question(b"\x01a\xc0\x10\x00")),
+            (
+                MessageErrorKind::BadCompressionPointer,
+                MessageErrorKind::BadCompressionPointer
+            )
+        );
+
+        // Pointer back to the start of the name, creating a loop. This is
+        // only caught by the name length in lenient mode.
+        assert_eq!(
+            check_kind(question(b"\x01a\xc0\x0c")),
+            (
+                MessageErrorKind::LongName,
+                MessageErrorKind::BadCompressionPointer
+            )
+        );
+
+        // Invalid label type.
+        assert_eq!(
+            check_kind(question(b"\x40")),
+            (MessageErrorKind::BadLabel, MessageErrorKind::BadLabel)
+        );
+
+        // Compression pointer to an earlier name.
+        let mut octets = a_message();
+        octets.splice(39..52, *b"\xc0\x0c");
+        assert!(Message::from_octets(octets.as_slice())
+            .unwrap()
+            .check_strict()
+            .is_ok());
+        octets[40] = 0x0d;
+        let err = Message::from_octets(octets).unwrap().check().unwrap_err();
+        assert_eq!(err.kind(), MessageErrorKind::BadCompressionPointer);
+        assert_eq!(err.offset(), 39);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn check_with_policy() {
+        use crate::base::policy::Level;
+        use std::string::ToString;
+
+        let record = |rtype: u16, rdata: &[u8]| {
+            let mut octets = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+            octets.extend_from_slice(b"\x07example\x03com\x00");
+            octets.extend_from_slice(&rtype.to_be_bytes());
+            octets.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+            octets.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            octets.extend_from_slice(rdata);
+            Message::from_octets(octets).unwrap()
+        };
+        let srv = record(33, b"\0\0\0\0\0\x35\xc0\x0c");
+        let srv_plain = record(33, b"\0\0\0\0\0\x35\x01a\0");
+        let mx = record(15, b"\0\x0a\xc0\x0c");
+        let md = record(3, b"\xc0\x0c");
+
+        let mut policy = ParsePolicy::new();
+        for msg in [&srv, &srv_plain, &mx, &md] {
+            assert!(msg.check_with_policy(&policy, |_| panic!()).is_ok());
+        }
+
+        policy.set_forbidden_compression(Level::Deny);
+        let err = srv.check_with_policy(&policy, |_| panic!()).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            MessageErrorKind::Policy(Violation::ForbiddenCompression(
+                Rtype::Srv
+            ))
+        );
+        assert_eq!(err.offset(), 12);
+        assert_eq!(err.rtype(), Some(Rtype::Srv));
+        assert!(srv_plain.check_with_policy(&policy, |_| panic!()).is_ok());
+        assert!(mx.check_with_policy(&policy, |_| panic!()).is_ok());
+
+        policy.set_obsolete_types(Level::Warn);
+        let mut warnings = Vec::new();
+        assert!(md
+            .check_with_policy(&policy, |err| warnings.push(err))
+            .is_ok());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].kind(),
+            MessageErrorKind::Policy(Violation::ObsoleteType(Rtype::Md))
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "answer record 0 (MD) at offset 12: obsolete record type MD"
+        );
+
+        // The regular checks ignore the policy.
+        assert!(srv.check().is_ok());
+        assert!(md.check_strict().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn lenient_records() {
+        use std::string::ToString;
+
+        // Turn the first record into a AAAA record with four octets.
+        let mut octets = a_message();
+        octets[26] = 28;
+        let msg = Message::from_octets(octets).unwrap();
+
+        let mut iter = msg
+            .answer()
+            .unwrap()
+            .limit_to::<AllRecordData<_, ParsedDname<_>>>();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().is_none());
+
+        let mut iter = msg
+            .answer()
+            .unwrap()
+            .limit_to::<AllRecordData<_, ParsedDname<_>>>()
+            .lenient();
+        assert_eq!(
+            iter.next().unwrap().unwrap().data().to_string(),
+            "192.0.2.2"
+        );
+        assert!(iter.next().is_none());
+    }
 }
@@ -62,6 +62,10 @@
 //! with lots of different names. However, 24 should be good enough for most
 //! normal messages.
 //!
+//! Finally, the [`SliceTarget`] builds a message directly into a buffer
+//! provided by the caller, such as a socket buffer, without needing an
+//! allocator. The buffer may be uninitialized.
+//!
 //! # Example
 //!
 //! The following example builds a message with both name compression and
@@ -122,6 +126,7 @@
 //! [`AdditionalBuilder::opt`]: struct.AdditionalBuilder.html#method.opt
 //! [`OptBuilder`]: struct.OptBuilder.html
 //! [`RecordSectionBuilder`]: trait.RecordSectionBuilder.html
+//! [`SliceTarget`]: struct.SliceTarget.html
 //! [`StaticCompressor`]: struct.StaticCompressor.html
 //! [`StreamTarget`]: struct.StreamTarget.html
 //! [`TreeCompressor`]: struct.TreeCompressor.html
@@ -130,9 +135,8 @@
 //! [octets builder]: ../octets/trait.OctetsBuilder.html
 
 use super::header::{CountOverflow, Header, HeaderCounts, HeaderSection};
-#[cfg(feature = "rand")]
 use super::iana::Rtype;
-use super::iana::{OptRcode, OptionCode, Rcode};
+use super::iana::{Class, Opcode, OptRcode, OptionCode, Rcode};
 use super::message::Message;
 use super::name::{Label, ToDname};
 use super::opt::{ComposeOptData, OptHeader};
@@ -143,8 +147,9 @@ use super::wire::{Compose, Composer};
 use bytes::BytesMut;
 #[cfg(feature = "std")]
 use core::convert::TryInto;
+use core::mem::MaybeUninit;
 use core::ops::{Deref, DerefMut};
-use core::{fmt, mem};
+use core::{fmt, mem, slice};
 #[cfg(feature = "std")]
 use octseq::array::Array;
 #[cfg(any(feature = "std", feature = "bytes"))]
@@ -268,6 +273,23 @@ impl<Target: Composer> MessageBuilder<Target> {
         builder.push((apex, Rtype::Axfr))?;
         Ok(builder.answer())
     }
+
+    /// Starts creating an UPDATE message for the given zone.
+    ///
+    /// Sets the opcode to UPDATE, pushes the zone into the zone section,
+    /// and converts the builder into a builder for the prerequisite
+    /// section. Use [`AnswerBuilder::update`] to proceed to the update
+    /// section. As required by RFC 2136, there can only be one zone.
+    pub fn start_update<N: ToDname>(
+        mut self,
+        zone: N,
+        class: Class,
+    ) -> Result<AnswerBuilder<Target>, PushError> {
+        self.header_mut().set_opcode(Opcode::Update);
+        let mut builder = self.zone();
+        builder.push_zone(zone, class)?;
+        Ok(builder.prerequisite())
+    }
 }
 
 /// # Access to the Message Header
@@ -311,6 +333,14 @@ impl<Target: Composer> MessageBuilder<Target> {
         QuestionBuilder::new(self)
     }
 
+    /// Converts the message builder into a builder for the zone section.
+    ///
+    /// This is identical to `self.question()` and is here for building
+    /// UPDATE messages.
+    pub fn zone(self) -> QuestionBuilder<Target> {
+        self.question()
+    }
+
     /// Converts the message builder into an answer builder.
     ///
     /// This will leave the question section empty.
@@ -318,6 +348,14 @@ impl<Target: Composer> MessageBuilder<Target> {
         self.question().answer()
     }
 
+    /// Converts the message builder into a prerequisite builder.
+    ///
+    /// This is identical to `self.answer()` and is here for building
+    /// UPDATE messages.
+    pub fn prerequisite(self) -> AnswerBuilder<Target> {
+        self.answer()
+    }
+
     /// Converts the message builder into an authority builder.
     ///
     /// This will leave the question and answer sections empty.
@@ -325,6 +363,14 @@ impl<Target: Composer> MessageBuilder<Target> {
         self.question().answer().authority()
     }
 
+    /// Converts the message builder into an update builder.
+    ///
+    /// This is identical to `self.authority()` and is here for building
+    /// UPDATE messages.
+    pub fn update(self) -> AuthorityBuilder<Target> {
+        self.authority()
+    }
+
     /// Converts the message builder into an additional builder.
     ///
     /// This will leave the question, answer, and authority sections empty.
@@ -528,6 +574,17 @@ impl<Target: Composer> QuestionBuilder<Target> {
             |counts| counts.inc_qdcount(),
         )
     }
+
+    /// Appends a zone to the zone section of an UPDATE message.
+    ///
+    /// This pushes a question for the SOA record of the zone.
+    pub fn push_zone(
+        &mut self,
+        zone: impl ToDname,
+        class: Class,
+    ) -> Result<(), PushError> {
+        self.push((zone, Rtype::Soa, class))
+    }
 }
 
 /// # Conversions
@@ -565,6 +622,13 @@ impl<Target: Composer> QuestionBuilder<Target> {
         AnswerBuilder::new(self.builder)
     }
 
+    /// Converts the question builder into a prerequisite builder.
+    ///
+    /// This is identical to `self.answer()`.
+    pub fn prerequisite(self) -> AnswerBuilder<Target> {
+        self.answer()
+    }
+
     /// Converts the question builder into an authority builder.
     ///
     /// This will leave the answer section empty.
@@ -816,6 +880,13 @@ impl<Target: Composer> AnswerBuilder<Target> {
         AuthorityBuilder::new(self)
     }
 
+    /// Converts the answer builder into an update builder.
+    ///
+    /// This is identical to `self.authority()`.
+    pub fn update(self) -> AuthorityBuilder<Target> {
+        self.authority()
+    }
+
     /// Converts the answer builder into an additional builder.
     ///
     /// This will leave the authority section empty.
@@ -1572,6 +1643,14 @@ impl<'a, Target: Composer + ?Sized> OptBuilder<'a, Target> {
         op(self.target)
     }
 
+    /// Returns a reference to the underlying target.
+    ///
+    /// The target contains the complete message assembled so far including
+    /// the OPT record currently being built.
+    pub fn as_target(&self) -> &Target {
+        self.target
+    }
+
     /// Returns the current UDP payload size field of the OPT record.
     ///
     /// This field contains the largest UDP datagram the sender can accept.
@@ -1797,6 +1876,210 @@ where
     }
 }
 
+//------------ StreamWriter --------------------------------------------------
+
+/// An adapter writing messages for stream transports to a writer.
+///
+/// Where [`StreamTarget`] adds the 16 bit length value while building a
+/// message, this type adds it when writing an already assembled message to
+/// an [`io::Write`][std::io::Write] such as a TCP socket. This allows
+/// building messages atop any octets builder, including `BytesMut` and
+/// `SmallVec`, and framing them only when sending.
+///
+/// Each message is written via two writes, so wrapping an unbuffered
+/// writer into a [`BufWriter`][std::io::BufWriter] may be advisable.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct StreamWriter<W> {
+    /// The underlying writer.
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> StreamWriter<W> {
+    /// Creates a new stream writer wrapping a writer.
+    pub fn new(writer: W) -> Self {
+        StreamWriter { writer }
+    }
+
+    /// Writes a message preceded by its length.
+    ///
+    /// The message is given as the octets of the message itself without
+    /// a length value, for instance a [`Message`] or a message builder.
+    /// Returns an error of kind `InvalidInput` if the message is longer
+    /// than 65535 octets.
+    pub fn write_message(
+        &mut self,
+        msg: &impl AsRef<[u8]>,
+    ) -> Result<(), std::io::Error> {
+        let msg = msg.as_ref();
+        let len = u16::try_from(msg.len()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "message too long for stream transport",
+            )
+        })?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(msg)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Converts the stream writer into the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+//------------ SliceTarget ---------------------------------------------------
+
+/// A builder target atop a buffer provided by the caller.
+///
+/// This type allows building a message directly into memory owned by
+/// someone else, for instance a socket buffer, without the need for an
+/// allocator. The buffer can be given either as an octets slice via
+/// [`new`][Self::new] or as a slice of possibly uninitialized octets via
+/// [`from_uninit`][Self::from_uninit]. Only the part of the buffer that has
+/// been written to is ever exposed.
+///
+/// Appending fails with [`ShortBuf`] if the buffer is full. Once done, the
+/// number of octets written is available via [`len`][Self::len] and the
+/// written part of the buffer via [`into_slice`][Self::into_slice].
+pub struct SliceTarget<'a> {
+    /// The underlying buffer.
+    ///
+    /// The first `len` octets are initialized.
+    buf: &'a mut [MaybeUninit<u8>],
+
+    /// The number of octets written so far.
+    len: usize,
+}
+
+impl<'a> SliceTarget<'a> {
+    /// Creates a new, empty target atop an octets slice.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        // Safety: u8 and MaybeUninit<u8> have the same layout. Since we
+        //         only ever write initialized octets into the buffer, the
+        //         caller will find it still initialized afterwards.
+        let buf =
+            unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        Self::from_uninit(buf)
+    }
+
+    /// Creates a new, empty target atop a slice of uninitialized octets.
+    pub fn from_uninit(buf: &'a mut [MaybeUninit<u8>]) -> Self {
+        SliceTarget { buf, len: 0 }
+    }
+
+    /// Returns the number of octets written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether nothing has been written so far.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the size of the underlying buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns a reference to the octets written so far.
+    pub fn as_slice(&self) -> &[u8] {
+        // Safety: The first self.len octets are initialized.
+        unsafe {
+            slice::from_raw_parts(self.buf.as_ptr() as *const u8, self.len)
+        }
+    }
+
+    /// Returns a mutable reference to the octets written so far.
+    pub fn as_slice_mut(&mut self) -> &mut [u8] {
+        // Safety: The first self.len octets are initialized.
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.buf.as_mut_ptr() as *mut u8,
+                self.len,
+            )
+        }
+    }
+
+    /// Converts the target into the part of the buffer written to.
+    pub fn into_slice(self) -> &'a mut [u8] {
+        // Safety: The first self.len octets are initialized.
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.buf.as_mut_ptr() as *mut u8,
+                self.len,
+            )
+        }
+    }
+}
+
+//--- AsRef, AsMut
+
+impl<'a> AsRef<[u8]> for SliceTarget<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl<'a> AsMut<[u8]> for SliceTarget<'a> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_slice_mut()
+    }
+}
+
+//--- OctetsBuilder, Truncate, Composer
+
+impl<'a> OctetsBuilder for SliceTarget<'a> {
+    type AppendError = ShortBuf;
+
+    fn append_slice(
+        &mut self,
+        slice: &[u8],
+    ) -> Result<(), Self::AppendError> {
+        let end = self.len.checked_add(slice.len()).ok_or(ShortBuf)?;
+        let buf = self.buf.get_mut(self.len..end).ok_or(ShortBuf)?;
+        for (dst, src) in buf.iter_mut().zip(slice) {
+            *dst = MaybeUninit::new(*src);
+        }
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl<'a> Truncate for SliceTarget<'a> {
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len)
+    }
+}
+
+impl<'a> Composer for SliceTarget<'a> {}
+
+//--- Debug
+
+impl<'a> fmt::Debug for SliceTarget<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SliceTarget")
+            .field("buf", &self.as_slice())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
 //------------ StaticCompressor ----------------------------------------------
 
 /// A domain name compressor that doesn’t require an allocator.
@@ -2324,6 +2607,26 @@ mod test {
         assert_eq!(opts.next(), Some(Ok(nsid)));
     }
 
+    #[test]
+    fn stream_writer() {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((Dname::root_slice(), Rtype::A)).unwrap();
+        let msg = msg.into_message();
+
+        let mut writer = StreamWriter::new(Vec::new());
+        writer.write_message(&msg).unwrap();
+        writer.write_message(&msg).unwrap();
+        let written = writer.into_inner();
+        let len = msg.as_slice().len();
+        assert_eq!(written.len(), 2 * (len + 2));
+        assert_eq!(&written[..2], &(len as u16).to_be_bytes());
+        assert_eq!(&written[2..len + 2], msg.as_slice());
+
+        let mut writer = StreamWriter::new(Vec::new());
+        assert!(writer.write_message(&vec![0u8; 0x10000]).is_err());
+        assert!(writer.get_ref().is_empty());
+    }
+
     fn create_compressed<T: Composer>(target: T) -> T
     where
         T::AppendError: fmt::Debug,
@@ -2380,4 +2683,34 @@ mod test {
         let msg = create_compressed(TreeCompressor::new(Vec::new()));
         assert_eq!(&expect[..], msg.as_ref());
     }
+
+    #[test]
+    fn slice_target() {
+        let expect = create_compressed(StaticCompressor::new(Vec::new()));
+        let expect = expect.as_slice();
+
+        let mut buf = [0u8; 512];
+        let target = SliceTarget::new(&mut buf);
+        let msg = create_compressed(StaticCompressor::new(target));
+        let len = msg.as_target().len();
+        assert_eq!(msg.into_target().into_slice(), expect);
+        assert_eq!(&buf[..len], expect);
+
+        let mut buf = [MaybeUninit::uninit(); 512];
+        let target = SliceTarget::from_uninit(&mut buf);
+        let msg = create_compressed(StaticCompressor::new(target));
+        assert_eq!(msg.as_slice(), expect);
+
+        // A buffer that is too short results in an error and leaves the
+        // target unchanged.
+        let mut buf = [0u8; 14];
+        let mut target = SliceTarget::new(&mut buf);
+        target.append_slice(b"0123456789").unwrap();
+        assert_eq!(target.append_slice(b"01234"), Err(ShortBuf));
+        assert_eq!(target.as_slice(), b"0123456789");
+        target.append_slice(b"0123").unwrap();
+        assert_eq!(target.len(), 14);
+        target.truncate(2);
+        assert_eq!(target.as_slice(), b"01");
+    }
 }
@@ -58,6 +58,7 @@
 //! * [header](header/index.html) for the header of DNS messages,
 //! * [name](name/index.html) for domain names,
 //! * [opt](opt/index.html) for the record data of OPT records used in EDNS,
+//! * [policy](policy/index.html) for dealing with questionable data,
 //! * [question](question/index.html) for questions,
 //! * [serial](serial/index.html) for serial numbers of zones, and
 //! * [record](record/index.html) for DNS resource records including record
@@ -97,12 +98,15 @@ pub use self::charstr::CharStr;
 pub use self::cmp::CanonicalOrd;
 pub use self::header::{Header, HeaderCounts, HeaderSection};
 pub use self::iana::Rtype;
-pub use self::message::{Message, QuestionSection, RecordSection};
-#[cfg(feature = "std")]
-pub use self::message_builder::TreeCompressor;
+pub use self::message::{
+    Message, QuestionCount, QuestionSection, RecordSection,
+};
 pub use self::message_builder::{
-    MessageBuilder, RecordSectionBuilder, StaticCompressor, StreamTarget,
+    MessageBuilder, RecordSectionBuilder, SliceTarget, StaticCompressor,
+    StreamTarget,
 };
+#[cfg(feature = "std")]
+pub use self::message_builder::{StreamWriter, TreeCompressor};
 pub use self::name::{
     Dname, DnameBuilder, ParsedDname, RelativeDname, ToDname, ToRelativeDname,
 };
@@ -113,15 +117,21 @@ pub use self::serial::Serial;
 
 //--- Modules
 
+pub mod arena;
+pub mod chaos;
 pub mod charstr;
 pub mod cmp;
+pub mod dig;
 pub mod header;
+pub mod hickory;
 pub mod iana;
+pub mod json;
 pub mod message;
 pub mod message_builder;
 pub mod name;
 pub mod net;
 pub mod opt;
+pub mod policy;
 pub mod question;
 pub mod rdata;
 pub mod record;
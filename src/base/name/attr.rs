@@ -0,0 +1,261 @@
+//! Building names of attribute leaves.
+//!
+//! This is a private module. Its public types are re-exported by the parent
+//! module.
+//!
+//! A number of protocols store information in records at names that start
+//! with one or more labels with a leading underscore. RFC 8552 calls these
+//! attribute leaves. Examples are `_imap._tcp.example.com` for SRV
+//! records, `_25._tcp.mail.example.com` for TLSA records, or
+//! `selector._domainkey.example.com` for DKIM keys. This module adds
+//! methods to [`DnameBuilder`] and [`Dname`] that create such names from
+//! their typed components.
+
+use super::builder::{DnameBuilder, PushError};
+use super::dname::Dname;
+use super::label::Label;
+use super::traits::{ToDname, ToRelativeDname};
+use core::fmt;
+use octseq::builder::OctetsBuilder;
+use octseq::builder::{EmptyBuilder, FreezeBuilder, FromBuilder};
+
+//------------ ServiceProto --------------------------------------------------
+
+/// The transport protocol of a service.
+///
+/// This is used as the second label of SRV and TLSA names.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ServiceProto {
+    /// The service runs over TCP.
+    Tcp,
+
+    /// The service runs over UDP.
+    Udp,
+
+    /// The service runs over SCTP.
+    Sctp,
+}
+
+impl ServiceProto {
+    /// Returns the label for the protocol, including the underscore.
+    pub fn label(self) -> &'static Label {
+        match self {
+            ServiceProto::Tcp => Label::TCP,
+            ServiceProto::Udp => Label::UDP,
+            ServiceProto::Sctp => Label::from_static(b"_sctp"),
+        }
+    }
+}
+
+impl fmt::Display for ServiceProto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.label().fmt(f)
+    }
+}
+
+//------------ DnameBuilder --------------------------------------------------
+
+impl<Builder> DnameBuilder<Builder>
+where
+    Builder: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Appends a label with a leading underscore.
+    ///
+    /// The `label` is given without the underscore. Returns an error if it
+    /// is longer than 62 octets or if the name would become too long.
+    pub fn append_underscore_label(
+        &mut self,
+        label: &[u8],
+    ) -> Result<(), PushError> {
+        let mut buf = [0u8; Label::MAX_LEN];
+        let buf = match buf.get_mut(..label.len() + 1) {
+            Some(buf) => buf,
+            None => return Err(PushError::LongLabel),
+        };
+        buf[0] = b'_';
+        buf[1..].copy_from_slice(label);
+        self.append_label(buf)
+    }
+
+    /// Appends the labels for a service and its protocol.
+    ///
+    /// The `service` is the service name without the leading underscore,
+    /// e.g., `b"imap"`. This appends the labels `_imap._tcp` for a
+    /// `proto` of [`ServiceProto::Tcp`].
+    pub fn append_service(
+        &mut self,
+        service: &[u8],
+        proto: ServiceProto,
+    ) -> Result<(), PushError> {
+        self.append_underscore_label(service)?;
+        self.append_label(proto.label().as_slice())
+    }
+
+    /// Appends the labels for a port and its protocol.
+    ///
+    /// This appends the labels `_25._tcp` for port 25 and a `proto` of
+    /// [`ServiceProto::Tcp`] as used by TLSA records.
+    pub fn append_port(
+        &mut self,
+        port: u16,
+        proto: ServiceProto,
+    ) -> Result<(), PushError> {
+        let mut buf = [0u8; 5];
+        let mut start = buf.len();
+        let mut port = port;
+        loop {
+            start -= 1;
+            buf[start] = b'0' + (port % 10) as u8;
+            port /= 10;
+            if port == 0 {
+                break;
+            }
+        }
+        self.append_underscore_label(&buf[start..])?;
+        self.append_label(proto.label().as_slice())
+    }
+}
+
+//------------ Dname ---------------------------------------------------------
+
+impl<Octs> Dname<Octs>
+where
+    Octs: FromBuilder,
+    <Octs as FromBuilder>::Builder: EmptyBuilder
+        + FreezeBuilder<Octets = Octs>
+        + AsRef<[u8]>
+        + AsMut<[u8]>,
+{
+    /// Creates the name of the SRV records of a service.
+    ///
+    /// The name is `_service._proto.domain`, with `service` given without
+    /// the leading underscore.
+    pub fn srv_name(
+        service: &[u8],
+        proto: ServiceProto,
+        domain: &impl ToDname,
+    ) -> Result<Self, PushError> {
+        let mut builder = DnameBuilder::<Octs::Builder>::new();
+        builder.append_service(service, proto)?;
+        builder.append_origin(domain).map_err(Into::into)
+    }
+
+    /// Creates the name of the TLSA records for a port of a host.
+    ///
+    /// The name is `_port._proto.domain` as defined in RFC 6698.
+    pub fn tlsa_name(
+        port: u16,
+        proto: ServiceProto,
+        domain: &impl ToDname,
+    ) -> Result<Self, PushError> {
+        let mut builder = DnameBuilder::<Octs::Builder>::new();
+        builder.append_port(port, proto)?;
+        builder.append_origin(domain).map_err(Into::into)
+    }
+
+    /// Creates the name of a DKIM key record.
+    ///
+    /// The name is `selector._domainkey.domain` as defined in RFC 6376.
+    /// As the selector may consist of multiple labels, it is given as a
+    /// relative name.
+    pub fn dkim_name(
+        selector: &impl ToRelativeDname,
+        domain: &impl ToDname,
+    ) -> Result<Self, PushError> {
+        let mut builder = DnameBuilder::<Octs::Builder>::new();
+        builder.append_name(selector)?;
+        builder.append_label(Label::DOMAINKEY.as_slice())?;
+        builder.append_origin(domain).map_err(Into::into)
+    }
+
+    /// Creates the name of the DMARC policy record of a domain.
+    ///
+    /// The name is `_dmarc.domain` as defined in RFC 7489.
+    pub fn dmarc_name(domain: &impl ToDname) -> Result<Self, PushError> {
+        let mut builder = DnameBuilder::<Octs::Builder>::new();
+        builder.append_label(Label::DMARC.as_slice())?;
+        builder.append_origin(domain).map_err(Into::into)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::name::RelativeDname;
+    use core::str::FromStr;
+    use std::vec::Vec;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn build_names() {
+        let domain = name("example.com");
+        assert_eq!(
+            Name::srv_name(b"imap", ServiceProto::Tcp, &domain).unwrap(),
+            name("_imap._tcp.example.com")
+        );
+        assert_eq!(
+            Name::tlsa_name(25, ServiceProto::Tcp, &name("mail.example.com"))
+                .unwrap(),
+            name("_25._tcp.mail.example.com")
+        );
+        assert_eq!(
+            Name::tlsa_name(0, ServiceProto::Udp, &domain).unwrap(),
+            name("_0._udp.example.com")
+        );
+        assert_eq!(
+            Name::tlsa_name(65535, ServiceProto::Sctp, &domain).unwrap(),
+            name("_65535._sctp.example.com")
+        );
+        assert_eq!(
+            Name::dkim_name(
+                &RelativeDname::<Vec<u8>>::from_str("2024.sel").unwrap(),
+                &domain
+            )
+            .unwrap(),
+            name("2024.sel._domainkey.example.com")
+        );
+        assert_eq!(
+            Name::dmarc_name(&domain).unwrap(),
+            name("_dmarc.example.com")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let domain = name("example.com");
+        assert_eq!(
+            Name::srv_name(&[b'a'; 62], ServiceProto::Tcp, &domain)
+                .unwrap()
+                .first()
+                .len(),
+            63
+        );
+        assert_eq!(
+            Name::srv_name(&[b'a'; 63], ServiceProto::Tcp, &domain),
+            Err(PushError::LongLabel)
+        );
+        let long = name(&format!(
+            "{a}.{a}.{a}.{b}",
+            a = "a".repeat(63),
+            b = "b".repeat(58)
+        ));
+        assert_eq!(Name::dmarc_name(&long), Err(PushError::LongName));
+    }
+
+    #[test]
+    fn underscore_label_rollback() {
+        let mut builder = DnameBuilder::new_vec();
+        builder.append_label(b"www").unwrap();
+        assert!(builder.append_underscore_label(&[b'a'; 63]).is_err());
+        builder.append_label(b"example").unwrap();
+        assert_eq!(builder.into_dname().unwrap(), name("www.example"));
+    }
+}
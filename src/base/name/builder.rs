@@ -482,6 +482,15 @@ impl From<ShortBuf> for PushError {
     }
 }
 
+impl From<PushNameError> for PushError {
+    fn from(err: PushNameError) -> PushError {
+        match err {
+            PushNameError::LongName => PushError::LongName,
+            PushNameError::ShortBuf => PushError::ShortBuf,
+        }
+    }
+}
+
 //--- Display and Error
 
 impl fmt::Display for PushError {
@@ -114,7 +114,11 @@ impl<L, R> Chain<L, R> {
 //--- ToLabelIter, ToRelativeDname, ToDname
 
 impl<L: ToRelativeDname, R: ToLabelIter> ToLabelIter for Chain<L, R> {
-    type LabelIter<'a> = ChainIter<'a, L, R> where L: 'a, R: 'a;
+    type LabelIter<'a>
+        = ChainIter<'a, L, R>
+    where
+        L: 'a,
+        R: 'a;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         ChainIter(self.left.iter_labels().chain(self.right.iter_labels()))
@@ -133,8 +137,11 @@ where
     Octs: AsRef<[u8]>,
     R: ToDname,
 {
-    type LabelIter<'a> = UncertainChainIter<'a, Octs, R>
-        where Octs: 'a, R: 'a;
+    type LabelIter<'a>
+        = UncertainChainIter<'a, Octs, R>
+    where
+        Octs: 'a,
+        R: 'a;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         match self.left {
@@ -173,9 +180,28 @@ where
 
 //--- Display
 
-impl<L: fmt::Display, R: fmt::Display> fmt::Display for Chain<L, R> {
+impl<L, R> fmt::Display for Chain<L, R>
+where
+    Self: ToLabelIter,
+{
+    /// Formats the domain name.
+    ///
+    /// Like for the other domain name types, an absolute name is printed
+    /// without the trailing dot. Empty names on either side of the chain
+    /// don’t result in extra dots.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{}", self.left, self.right)
+        let mut first = true;
+        for label in self.iter_labels() {
+            if label.is_root() {
+                continue;
+            }
+            if !first {
+                f.write_str(".")?;
+            }
+            first = false;
+            label.fmt(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -489,4 +515,44 @@ mod test {
         );
         assert_eq!(buf, b"\x07example\x03com\x00");
     }
+
+    /// Tests that displaying doesn’t add dots for empty names.
+    #[test]
+    #[cfg(feature = "std")]
+    fn display() {
+        use std::string::ToString;
+
+        let w = RelativeDname::from_octets(b"\x03www".as_ref()).unwrap();
+        let ec = RelativeDname::from_octets(b"\x07example\x03com".as_ref())
+            .unwrap();
+        let ecr =
+            Dname::from_octets(b"\x07example\x03com\x00".as_ref()).unwrap();
+
+        assert_eq!(
+            w.clone().chain(ec.clone()).unwrap().to_string(),
+            "www.example.com"
+        );
+        assert_eq!(
+            w.clone().chain(ecr.clone()).unwrap().to_string(),
+            "www.example.com"
+        );
+        assert_eq!(
+            RelativeDname::empty_ref().chain(ecr).unwrap().to_string(),
+            "example.com"
+        );
+        assert_eq!(
+            RelativeDname::empty_ref()
+                .chain(Dname::root_ref())
+                .unwrap()
+                .to_string(),
+            ""
+        );
+        assert_eq!(
+            UncertainDname::from(w)
+                .chain(Dname::root_ref())
+                .unwrap()
+                .to_string(),
+            "www"
+        );
+    }
 }
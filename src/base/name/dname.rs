@@ -777,7 +777,10 @@ impl<Octs> ToLabelIter for Dname<Octs>
 where
     Octs: AsRef<[u8]> + ?Sized,
 {
-    type LabelIter<'a> = DnameIter<'a> where Octs: 'a;
+    type LabelIter<'a>
+        = DnameIter<'a>
+    where
+        Octs: 'a;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         self.iter()
@@ -42,15 +42,53 @@ impl Label {
     /// Domain name labels have a maximum length of 63 octets.
     pub const MAX_LEN: usize = 63;
 
+    /// The root label.
+    pub const ROOT: &'static Self = Self::from_static(b"");
+
+    /// The wildcard label `*`.
+    pub const WILDCARD: &'static Self = Self::from_static(b"*");
+
+    /// The label `_tcp` used for services over TCP.
+    pub const TCP: &'static Self = Self::from_static(b"_tcp");
+
+    /// The label `_udp` used for services over UDP.
+    pub const UDP: &'static Self = Self::from_static(b"_udp");
+
+    /// The label `_tls` used for services over TLS.
+    pub const TLS: &'static Self = Self::from_static(b"_tls");
+
+    /// The label `_dmarc` used for DMARC policies.
+    pub const DMARC: &'static Self = Self::from_static(b"_dmarc");
+
+    /// The label `_domainkey` used for DKIM public keys.
+    pub const DOMAINKEY: &'static Self = Self::from_static(b"_domainkey");
+
+    /// The label `_mta-sts` used for MTA-STS policies.
+    pub const MTA_STS: &'static Self = Self::from_static(b"_mta-sts");
+
+    /// The label `_acme-challenge` used for ACME DNS challenges.
+    pub const ACME_CHALLENGE: &'static Self =
+        Self::from_static(b"_acme-challenge");
+
     /// Creates a label from the underlying slice without any checking.
     ///
     /// # Safety
     ///
     /// The `slice` must be at most 63 octets long.
-    pub(super) unsafe fn from_slice_unchecked(slice: &[u8]) -> &Self {
+    pub(super) const unsafe fn from_slice_unchecked(slice: &[u8]) -> &Self {
         &*(slice as *const [u8] as *const Self)
     }
 
+    /// Creates a label from a static octets slice.
+    ///
+    /// The function can be used in constant expressions. It is intended
+    /// for labels known at compile time, so it panics if the slice is
+    /// longer than 63 octets.
+    pub const fn from_static(slice: &'static [u8]) -> &'static Self {
+        assert!(slice.len() <= Label::MAX_LEN, "long label");
+        unsafe { Self::from_slice_unchecked(slice) }
+    }
+
     /// Creates a mutable label from the underlying slice without checking.
     ///
     /// # Safety
@@ -66,12 +104,12 @@ impl Label {
     ///
     /// The root label is an empty label.
     pub fn root() -> &'static Self {
-        unsafe { Self::from_slice_unchecked(b"") }
+        Self::ROOT
     }
 
     /// Returns a static reference to the wildcard label `"*"`.
     pub fn wildcard() -> &'static Self {
-        unsafe { Self::from_slice_unchecked(b"*") }
+        Self::WILDCARD
     }
 
     /// Converts an octets slice into a label.
@@ -285,11 +323,10 @@ impl Label {
         &self,
         target: &mut Builder,
     ) -> Result<(), Builder::AppendError> {
-        target.append_slice(&[self.len() as u8])?;
-        for ch in self.into_iter() {
-            target.append_slice(&[ch.to_ascii_lowercase()])?;
-        }
-        Ok(())
+        let mut buf = [0u8; 64];
+        buf[0] = self.len() as u8;
+        lowercase_into(self.as_slice(), &mut buf[1..]);
+        target.append_slice(&buf[..self.len() + 1])
     }
 }
 
@@ -319,6 +356,14 @@ impl Label {
         self.0.len() == 1 && self.0[0] == b'*'
     }
 
+    /// Returns whether the label starts with an underscore.
+    ///
+    /// Such labels are used for attribute leaves as described in RFC 8552,
+    /// e.g., for services or mail authentication policies.
+    pub fn is_underscore(&self) -> bool {
+        self.0.first() == Some(&b'_')
+    }
+
     /// Returns the length of the composed version of the label.
     ///
     /// This length is one more than the length of the label as their is a
@@ -344,8 +389,8 @@ impl AsMut<[u8]> for Label {
 
 //--- ToOwned
 
-#[cfg(feature = "std")]
-impl std::borrow::ToOwned for Label {
+#[cfg(feature = "alloc")]
+impl alloc::borrow::ToOwned for Label {
     type Owned = OwnedLabel;
 
     fn to_owned(&self) -> Self::Owned {
@@ -357,7 +402,7 @@ impl std::borrow::ToOwned for Label {
 
 impl<T: AsRef<[u8]> + ?Sized> PartialEq<T> for Label {
     fn eq(&self, other: &T) -> bool {
-        self.as_slice().eq_ignore_ascii_case(other.as_ref())
+        eq_ignore_case(self.as_slice(), other.as_ref())
     }
 }
 
@@ -376,19 +421,13 @@ impl PartialOrd for Label {
     ///
     /// [RFC 4034]: https://tools.ietf.org/html/rfc4034
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        self.as_slice()
-            .iter()
-            .map(u8::to_ascii_lowercase)
-            .partial_cmp(other.as_slice().iter().map(u8::to_ascii_lowercase))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Label {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.as_slice()
-            .iter()
-            .map(u8::to_ascii_lowercase)
-            .cmp(other.as_slice().iter().map(u8::to_ascii_lowercase))
+        cmp_ignore_case(self.as_slice(), other.as_slice())
     }
 }
 
@@ -398,10 +437,10 @@ impl hash::Hash for Label {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         // Include the length in the hash so we can simply hash over the
         // labels when building a name’s hash.
-        (self.len() as u8).hash(state);
-        for c in self.iter() {
-            c.to_ascii_lowercase().hash(state)
-        }
+        let mut buf = [0u8; 64];
+        buf[0] = self.len() as u8;
+        lowercase_into(self.as_slice(), &mut buf[1..]);
+        state.write(&buf[..self.len() + 1])
     }
 }
 
@@ -421,7 +460,10 @@ impl<'a> IntoIterator for &'a Label {
 impl fmt::Display for Label {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for ch in self.iter() {
-            if ch == b' ' || ch == b'.' || ch == b'\\' {
+            if matches!(
+                ch,
+                b' ' | b'.' | b'\\' | b'"' | b';' | b'(' | b')' | b'@' | b'$'
+            ) {
                 write!(f, "\\{}", ch as char)?;
             } else if !(0x20..0x7F).contains(&ch) {
                 write!(f, "\\{:03}", ch)?;
@@ -761,6 +803,92 @@ impl<'a> Iterator for SliceLabelsIter<'a> {
     }
 }
 
+//============ Helper Functions ==============================================
+//
+// The case-insensitive comparisons are at the heart of looking up names,
+// so instead of going octet by octet, they process eight octets at a time
+// packed into a `u64`. This only uses portable integer operations and thus
+// doesn’t require any detection of CPU features.
+
+/// A `u64` with all octets set to one.
+const LSB: u64 = 0x0101_0101_0101_0101;
+
+/// Converts the ASCII letters among eight octets packed into a `u64` to
+/// lowercase.
+fn lowercase_word(word: u64) -> u64 {
+    // Adding to the lower seven bits of each octet sets the top bit of the
+    // octet if it is above `Z` or at least `A`, respectively, without
+    // carrying over into the next octet.
+    let heptets = word & (0x7F * LSB);
+    let above_z = heptets + (0x7F - u64::from(b'Z')) * LSB;
+    let from_a = heptets + (0x80 - u64::from(b'A')) * LSB;
+
+    // Only octets with the top bit clear can be ASCII letters.
+    let upper = (from_a ^ above_z) & !word & (0x80 * LSB);
+    word | (upper >> 2)
+}
+
+/// Loads eight octets into a `u64`.
+///
+/// The octets are loaded in big-endian order so comparing the resulting
+/// integers compares the octets lexicographically.
+fn load_word(octets: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(octets);
+    u64::from_be_bytes(buf)
+}
+
+/// Writes the lowercase version of `src` to the start of `dst`.
+///
+/// Panics if `dst` is shorter than `src`.
+fn lowercase_into(src: &[u8], dst: &mut [u8]) {
+    let mut dst = dst[..src.len()].chunks_exact_mut(8);
+    let mut src = src.chunks_exact(8);
+    for (src, dst) in (&mut src).zip(&mut dst) {
+        dst.copy_from_slice(&lowercase_word(load_word(src)).to_be_bytes());
+    }
+    for (src, dst) in src.remainder().iter().zip(dst.into_remainder()) {
+        *dst = src.to_ascii_lowercase()
+    }
+}
+
+/// Returns whether two octets slices are equal ignoring ASCII case.
+pub(super) fn eq_ignore_case(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    let mut left = left.chunks_exact(8);
+    let mut right = right.chunks_exact(8);
+    for (left, right) in (&mut left).zip(&mut right) {
+        if lowercase_word(load_word(left)) != lowercase_word(load_word(right))
+        {
+            return false;
+        }
+    }
+    left.remainder().eq_ignore_ascii_case(right.remainder())
+}
+
+/// Compares two octets slices ignoring ASCII case.
+fn cmp_ignore_case(left: &[u8], right: &[u8]) -> cmp::Ordering {
+    let mut left_words = left.chunks_exact(8);
+    let mut right_words = right.chunks_exact(8);
+    for (left, right) in (&mut left_words).zip(&mut right_words) {
+        match lowercase_word(load_word(left))
+            .cmp(&lowercase_word(load_word(right)))
+        {
+            cmp::Ordering::Equal => {}
+            other => return other,
+        }
+    }
+
+    // At least one of the slices has less than eight octets left.
+    let offset = left.len().min(right.len()) & !7;
+    left[offset..]
+        .iter()
+        .map(u8::to_ascii_lowercase)
+        .cmp(right[offset..].iter().map(u8::to_ascii_lowercase))
+}
+
 //============ Error Types ===================================================
 
 //------------ LabelTypeError ------------------------------------------------
@@ -881,6 +1009,23 @@ mod test {
         assert!(Label::from_slice(&x[..]).is_err());
     }
 
+    #[test]
+    fn constants() {
+        assert!(Label::ROOT.is_root());
+        assert!(Label::WILDCARD.is_wildcard());
+        assert_eq!(Label::TCP.as_slice(), b"_tcp");
+        assert_eq!(Label::DMARC, Label::from_slice(b"_DMARC").unwrap());
+        assert!(Label::DOMAINKEY.is_underscore());
+        assert!(!Label::wildcard().is_underscore());
+        assert!(!Label::root().is_underscore());
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_static_long() {
+        let _ = Label::from_static(&[b'a'; 64]);
+    }
+
     #[test]
     fn split_from() {
         // regular label
@@ -997,6 +1142,59 @@ mod test {
         assert_eq!(s1.finish(), s2.finish());
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn ignore_case() {
+        // Every octet value in every position of a word.
+        for pos in 0..8 {
+            for ch in 0..=255u8 {
+                let mut octets = *b"aBcDeFgH";
+                octets[pos] = ch;
+                let mut lower = [0u8; 8];
+                lowercase_into(&octets, &mut lower);
+                assert_eq!(lower[..], octets.to_ascii_lowercase());
+            }
+        }
+
+        let samples: &[&[u8]] = &[
+            b"",
+            b"a",
+            b"A",
+            b"abcdefgh",
+            b"ABCDEFGH",
+            b"abcdefg\x00",
+            b"abcdefghi",
+            b"abcdefghI",
+            b"abcdefgh\x00",
+            b"abcdefgh\xFF",
+            b"abcdefgh_",
+            b"ABCDEFGH[",
+            b"\x80\xC1abc",
+            b"\xE1\xC1abc",
+            b"www-example-with-a-longer-label",
+            b"WWW-Example-with-a-longer-label",
+        ];
+        for left in samples {
+            for right in samples {
+                assert_eq!(
+                    eq_ignore_case(left, right),
+                    left.eq_ignore_ascii_case(right),
+                    "{:?} == {:?}",
+                    left,
+                    right
+                );
+                assert_eq!(
+                    cmp_ignore_case(left, right),
+                    left.to_ascii_lowercase()
+                        .cmp(&right.to_ascii_lowercase()),
+                    "{:?} cmp {:?}",
+                    left,
+                    right
+                );
+            }
+        }
+    }
+
     // XXX OwnedLabel::from_str
 
     #[cfg(feature = "serde")]
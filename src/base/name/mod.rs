@@ -86,8 +86,10 @@
 //! You can create a domain name value from its presentation format using
 //! the `FromStr` trait. Alternatively, the [`DnameBuilder`] type allows you
 //! to construct a name from scratch by appending octets, slices, or complete
-//! labels.
+//! labels. Names of attribute leaves such as `_imap._tcp.example.com` can
+//! be created via [`Dname::srv_name`] and friends.
 
+pub use self::attr::ServiceProto;
 pub use self::builder::{
     DnameBuilder, FromStrError, PushError, PushNameError,
 };
@@ -105,6 +107,7 @@ pub use self::relative::{
 pub use self::traits::{ToDname, ToLabelIter, ToRelativeDname};
 pub use self::uncertain::UncertainDname;
 
+mod attr;
 mod builder;
 mod chain;
 mod dname;
@@ -456,7 +456,10 @@ impl<Octs: AsRef<[u8]>> hash::Hash for ParsedDname<Octs> {
 //--- ToLabelIter and ToDname
 
 impl<Octs: AsRef<[u8]>> ToLabelIter for ParsedDname<Octs> {
-    type LabelIter<'s> = ParsedDnameIter<'s> where Octs: 's;
+    type LabelIter<'s>
+        = ParsedDnameIter<'s>
+    where
+        Octs: 's;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         self.iter()
@@ -519,6 +522,48 @@ impl<Octs: AsRef<[u8]>> fmt::Debug for ParsedDname<Octs> {
     }
 }
 
+//--- Serialize
+
+#[cfg(feature = "serde")]
+impl<Octs: AsRef<[u8]>> serde::Serialize for ParsedDname<Octs> {
+    /// Serializes the name.
+    ///
+    /// The name is serialized the same way as a [`Dname`], so it can be
+    /// deserialized into one.
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use octseq::serde::SerializeOctets;
+
+        if serializer.is_human_readable() {
+            serializer
+                .serialize_newtype_struct("Dname", &format_args!("{}", self))
+        } else if let Some(slice) = self.as_flat_slice() {
+            serializer.serialize_newtype_struct(
+                "Dname",
+                &slice.as_serialized_octets(),
+            )
+        } else {
+            // A name is at most 255 octets long, so we can uncompress it
+            // on the stack.
+            let mut buf = [0u8; 255];
+            let mut len = 0;
+            for label in self.iter() {
+                buf[len] = label.len() as u8;
+                buf[len + 1..len + 1 + label.len()]
+                    .copy_from_slice(label.as_slice());
+                len += 1 + label.len();
+            }
+            let slice: &[u8] = &buf[..len];
+            serializer.serialize_newtype_struct(
+                "Dname",
+                &slice.as_serialized_octets(),
+            )
+        }
+    }
+}
+
 //------------ ParsedDnameIter -----------------------------------------------
 
 /// An iterator over the labels in a parsed domain name.
@@ -1195,5 +1240,25 @@ mod test {
         ne_step(Dname::from_slice(b"\x03ww4\x07EXAMPLE\x03com\x00").unwrap());
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ser() {
+        use serde_test::{assert_ser_tokens, Configure, Token};
+
+        for name in [name!(flat), name!(once), name!(twice)] {
+            assert_ser_tokens(
+                &name.compact(),
+                &[Token::NewtypeStruct { name: "Dname" }, Token::Bytes(WECR)],
+            );
+            assert_ser_tokens(
+                &name.readable(),
+                &[
+                    Token::NewtypeStruct { name: "Dname" },
+                    Token::Str("www.example.com"),
+                ],
+            );
+        }
+    }
+
     // XXX TODO Test for cmp and hash.
 }
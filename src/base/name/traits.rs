@@ -5,7 +5,7 @@
 use super::builder::PushError;
 use super::chain::{Chain, LongChainError};
 use super::dname::Dname;
-use super::label::Label;
+use super::label::{eq_ignore_case, Label};
 use super::relative::RelativeDname;
 #[cfg(feature = "bytes")]
 use bytes::Bytes;
@@ -84,7 +84,11 @@ pub trait ToLabelIter {
 }
 
 impl<'r, N: ToLabelIter + ?Sized> ToLabelIter for &'r N {
-    type LabelIter<'a> = N::LabelIter<'a> where 'r: 'a, N: 'a;
+    type LabelIter<'a>
+        = N::LabelIter<'a>
+    where
+        'r: 'a,
+        N: 'a;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         (*self).iter_labels()
@@ -225,7 +229,7 @@ pub trait ToDname: ToLabelIter {
         {
             // We can do this because the length octets of each label are in
             // the ranged 0..64 which is before all ASCII letters.
-            left.eq_ignore_ascii_case(right)
+            eq_ignore_case(left, right)
         } else {
             self.iter_labels().eq(other.iter_labels())
         }
@@ -486,7 +490,7 @@ pub trait ToRelativeDname: ToLabelIter {
         if let (Some(left), Some(right)) =
             (self.as_flat_slice(), other.as_flat_slice())
         {
-            left.eq_ignore_ascii_case(right)
+            eq_ignore_case(left, right)
         } else {
             self.iter_labels().eq(other.iter_labels())
         }
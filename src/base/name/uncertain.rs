@@ -375,7 +375,10 @@ impl<Octets: AsRef<[u8]>> hash::Hash for UncertainDname<Octets> {
 //--- ToLabelIter
 
 impl<Octs: AsRef<[u8]>> ToLabelIter for UncertainDname<Octs> {
-    type LabelIter<'a> = DnameIter<'a> where Octs: 'a;
+    type LabelIter<'a>
+        = DnameIter<'a>
+    where
+        Octs: 'a;
 
     fn iter_labels(&self) -> Self::LabelIter<'_> {
         match *self {
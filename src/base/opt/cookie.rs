@@ -605,6 +605,149 @@ impl fmt::Display for StandardServerCookie {
 }
 
 
+//------------ ServerSecrets -------------------------------------------------
+
+/// The secrets a server uses for creating and checking server cookies.
+///
+/// Section 5 of [RFC 9018] recommends that servers change their secret
+/// regularly. In order not to invalidate all server cookies handed out
+/// with the old secret at once, there is a transition period during which
+/// cookies created with the previous secret are still accepted while new
+/// cookies are created with the new secret only.
+///
+/// A value of this type keeps the current and, optionally, the previous
+/// secret. Use [`rotate`][Self::rotate] to switch to a new secret and
+/// [`retire_previous`][Self::retire_previous] to end the transition period.
+///
+/// The type also enforces the timestamp rules of section 4.3 of RFC 9018:
+/// a server cookie is only accepted if its timestamp is at most an hour in
+/// the past and at most five minutes in the future. If a received cookie is
+/// older than half an hour, a fresh one should be returned, which can be
+/// determined via [`needs_refresh`][Self::needs_refresh].
+///
+/// This type is only available if the `siphasher` feature is enabled.
+///
+/// [RFC 9018]: https://tools.ietf.org/html/rfc9018
+#[cfg(feature = "siphasher")]
+#[derive(Clone)]
+pub struct ServerSecrets {
+    /// The secret used for creating new server cookies.
+    current: [u8; 16],
+
+    /// The previous secret still accepted for checking server cookies.
+    previous: Option<[u8; 16]>,
+}
+
+#[cfg(feature = "siphasher")]
+impl ServerSecrets {
+    /// The maximum age of an acceptable server cookie in seconds.
+    const MAX_AGE: u32 = 3600;
+
+    /// How far a server cookie may be in the future in seconds.
+    const MAX_FUTURE: u32 = 300;
+
+    /// The age in seconds after which a server cookie should be renewed.
+    const REFRESH_AGE: u32 = 1800;
+
+    /// Creates a new value using the given secret.
+    pub fn new(secret: [u8; 16]) -> Self {
+        ServerSecrets { current: secret, previous: None }
+    }
+
+    /// Creates a new value with a random secret.
+    #[cfg(feature = "rand")]
+    pub fn new_random() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// Returns the current secret.
+    pub fn current(&self) -> &[u8; 16] {
+        &self.current
+    }
+
+    /// Returns the previous secret if there still is one.
+    pub fn previous(&self) -> Option<&[u8; 16]> {
+        self.previous.as_ref()
+    }
+
+    /// Switches to a new secret.
+    ///
+    /// The current secret becomes the previous secret and replaces any
+    /// previous secret still present.
+    pub fn rotate(&mut self, secret: [u8; 16]) {
+        self.previous = Some(core::mem::replace(&mut self.current, secret));
+    }
+
+    /// Switches to a new random secret.
+    #[cfg(feature = "rand")]
+    pub fn rotate_random(&mut self) {
+        self.rotate(rand::random())
+    }
+
+    /// Stops accepting server cookies created with the previous secret.
+    pub fn retire_previous(&mut self) {
+        self.previous = None
+    }
+
+    /// Returns whether the cookie contains a valid server cookie.
+    ///
+    /// The cookie is valid if it contains a standard server cookie with an
+    /// acceptable timestamp relative to `now` and a hash created for
+    /// `client_ip` with either the current or the previous secret.
+    ///
+    /// If this returns `false`, the server should proceed as if the request
+    /// contained only a client cookie.
+    pub fn check(
+        &self,
+        cookie: &Cookie,
+        client_ip: crate::base::net::IpAddr,
+        now: Serial,
+    ) -> bool {
+        let timestamp_ok = |ts: Serial| {
+            ts <= now.add(Self::MAX_FUTURE) && ts.add(Self::MAX_AGE) >= now
+        };
+        cookie.check_server_hash(client_ip, &self.current, timestamp_ok)
+            || self.previous.as_ref().map(|secret| {
+                cookie.check_server_hash(client_ip, secret, timestamp_ok)
+            }).unwrap_or(false)
+    }
+
+    /// Returns whether a fresh server cookie should be sent.
+    ///
+    /// This is the case if the cookie doesn’t contain a standard server
+    /// cookie created during the last half hour.
+    pub fn needs_refresh(&self, cookie: &Cookie, now: Serial) -> bool {
+        match cookie.server().and_then(ServerCookie::try_to_standard) {
+            Some(server) => server.timestamp().add(Self::REFRESH_AGE) < now,
+            None => true,
+        }
+    }
+
+    /// Creates the cookie to be included in a response.
+    ///
+    /// The server cookie is calculated using the current secret and `now`
+    /// as the timestamp.
+    pub fn respond(
+        &self,
+        cookie: &Cookie,
+        client_ip: crate::base::net::IpAddr,
+        now: Serial,
+    ) -> Cookie {
+        cookie.create_response(now, client_ip, &self.current)
+    }
+}
+
+#[cfg(feature = "siphasher")]
+impl fmt::Debug for ServerSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Don’t leak the secrets into logs.
+        f.debug_struct("ServerSecrets")
+            .field("previous", &self.previous.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+
 //============ Tests =========================================================
 
 #[cfg(test)]
@@ -755,6 +898,49 @@ mod test {
                 ).unwrap()
             );
         }
+
+        /// A.4 again, but using the rotating secrets.
+        #[test]
+        fn server_secrets() {
+            let mut secrets = ServerSecrets::new([
+                0xdd, 0x3b, 0xdf, 0x93, 0x44, 0xb6, 0x78, 0xb1,
+                0x85, 0xa6, 0xf5, 0xcb, 0x60, 0xfc, 0xa7, 0x15,
+            ]);
+            let request = parse_slice(
+                &base16::decode_vec(
+                    "22681ab97d52c298010000005cf7c57926556bd0934c72f8"
+                ).unwrap(),
+                Cookie::parse
+            ).unwrap();
+            let now = Serial(1559741961);
+            assert!(secrets.check(&request, CLIENT_6, now));
+            assert!(!secrets.needs_refresh(&request, now));
+
+            secrets.rotate([
+                0x44, 0x55, 0x36, 0xbc, 0xd2, 0x51, 0x32, 0x98,
+                0x07, 0x5a, 0x5d, 0x37, 0x96, 0x63, 0xc9, 0x62,
+            ]);
+            assert!(secrets.check(&request, CLIENT_6, now));
+            assert!(!secrets.check(&request, CLIENT_1, now));
+            assert_eq!(
+                compose_vec(|vec| {
+                    secrets.respond(
+                        &request, CLIENT_6, now
+                    ).compose_option(vec)
+                }),
+                base16::decode_vec(
+                    "22681ab97d52c298010000005cf7c609a6bb79d16625507a"
+                ).unwrap()
+            );
+
+            // Timestamps too far in the past or future.
+            assert!(!secrets.check(&request, CLIENT_6, now.add(3600)));
+            assert!(secrets.needs_refresh(&request, now.add(1801)));
+            assert!(!secrets.check(&request, CLIENT_6, Serial(1559741000)));
+
+            secrets.retire_previous();
+            assert!(!secrets.check(&request, CLIENT_6, now));
+        }
     }
 }
 
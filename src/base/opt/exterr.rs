@@ -6,7 +6,7 @@
 //! The option is defined in [RFC 8914](https://tools.ietf.org/html/rfc8914).
 
 use super::super::iana::exterr::{ExtendedErrorCode, EDE_PRIVATE_RANGE_BEGIN};
-use super::super::iana::OptionCode;
+use super::super::iana::{OptRcode, OptionCode};
 use super::super::message_builder::OptBuilder;
 use super::super::wire::ParseError;
 use super::super::wire::{Compose, Composer};
@@ -280,6 +280,16 @@ impl<Octs: Octets> Opt<Octs> {
     pub fn extended_error(&self) -> Option<ExtendedError<Octs::Range<'_>>> {
         self.first()
     }
+
+    /// Returns an iterator over all extended DNS error options.
+    ///
+    /// A response may contain more than one extended DNS error option.
+    /// Options that cannot be parsed are skipped.
+    pub fn extended_errors(
+        &self
+    ) -> impl Iterator<Item = ExtendedError<Octs::Range<'_>>> + '_ {
+        self.iter().filter_map(Result::ok)
+    }
 }
 
 impl<'a, Target: Composer> OptBuilder<'a, Target> {
@@ -304,6 +314,18 @@ impl<'a, Target: Composer> OptBuilder<'a, Target> {
         )?;
         Ok(())
     }
+
+    /// Sets the extended rcode and appends an extended DNS error option.
+    ///
+    /// This is a shortcut for servers that want to explain why they are
+    /// answering with an error such as SERVFAIL or NXDOMAIN. The rcode is
+    /// set both in the OPT header and the message header.
+    pub fn rcode_with_error<Octs: AsRef<[u8]>>(
+        &mut self, rcode: OptRcode, error: &ExtendedError<Octs>
+    ) -> Result<(), Target::AppendError> {
+        self.set_rcode(rcode);
+        self.push(error)
+    }
 }
 
 //============ Tests =========================================================
@@ -333,4 +355,32 @@ mod tests {
         let ede: ExtendedError<&[u8]> = EDE_PRIVATE_RANGE_BEGIN.into();
         assert!(ede.is_private());
     }
+
+    #[test]
+    fn error_response() {
+        use crate::base::iana::Rcode;
+        use crate::base::message::Message;
+        use crate::base::message_builder::MessageBuilder;
+        use std::vec::Vec;
+
+        let mut msg = MessageBuilder::new_vec().additional();
+        msg.opt(|opt| {
+            opt.rcode_with_error(
+                OptRcode::ServFail,
+                &ExtendedError::<&[u8]>::from(ExtendedErrorCode::NotReady)
+            )?;
+            opt.push(&ExtendedError::new(
+                ExtendedErrorCode::Other,
+                Some(Str::from_string("still loading".into()))
+            ).unwrap())
+        }).unwrap();
+        let msg = Message::from_octets(msg.finish()).unwrap();
+        assert_eq!(msg.header().rcode(), Rcode::ServFail);
+
+        let opt = msg.opt().unwrap();
+        let errors = opt.opt().extended_errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].code(), ExtendedErrorCode::NotReady);
+        assert_eq!(errors[1].text_slice(), Some(b"still loading".as_ref()));
+    }
 }
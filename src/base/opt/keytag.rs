@@ -230,12 +230,12 @@ impl<Octets: AsRef<[u8]> + ?Sized> fmt::Display  for KeyTag<Octets> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut first = true;
         
-        for v in self.octets.as_ref() {
+        for v in self.iter() {
             if first {
-                write!(f, "{:X}", ((*v as u16) << 8) | *v as u16)?;
+                write!(f, "{}", v)?;
                 first = false;
             } else {
-                write!(f, ", {:X}", ((*v as u16) << 8) | *v as u16)?;
+                write!(f, ", {}", v)?;
             }
         }
 
@@ -355,5 +355,13 @@ mod test {
             |parser| KeyTag::parse(parser)
         );
     }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            format!("{}", KeyTag::from_octets(b"\x4f\x66\x97\x28").unwrap()),
+            "20326, 38696"
+        );
+    }
 }
 
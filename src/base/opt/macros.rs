@@ -101,5 +101,21 @@ macro_rules! opt_types {
                 }
             }
         }
+
+        //--- Display
+
+        impl<Octs, Name> core::fmt::Display for AllOptData<Octs, Name>
+        where Octs: AsRef<[u8]>, Name: core::fmt::Display {
+            fn fmt(
+                &self, f: &mut core::fmt::Formatter
+            ) -> core::fmt::Result {
+                match *self {
+                    $( $(
+                        AllOptData::$opt(ref inner) => inner.fmt(f),
+                    )* )*
+                    AllOptData::Other(ref inner) => inner.fmt(f),
+                }
+            }
+        }
     }
 }
@@ -277,6 +277,73 @@ impl<Octs: AsRef<[u8]> + ?Sized> fmt::Debug for Opt<Octs> {
     }
 }
 
+//--- Serialize and Deserialize
+
+#[cfg(feature = "serde")]
+impl<Octs> serde::Serialize for Opt<Octs>
+where
+    Octs: AsRef<[u8]> + octseq::serde::SerializeOctets + ?Sized,
+{
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_newtype_struct(
+                "Opt",
+                &format_args!("{}", base16::encode_display(&self.octets)),
+            )
+        } else {
+            serializer.serialize_newtype_struct(
+                "Opt",
+                &self.octets.as_serialized_octets(),
+            )
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Octs> serde::Deserialize<'de> for Opt<Octs>
+where
+    Octs: AsRef<[u8]>
+        + octseq::builder::FromBuilder
+        + octseq::serde::DeserializeOctets<'de>,
+    <Octs as octseq::builder::FromBuilder>::Builder:
+        octseq::builder::EmptyBuilder,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct NewtypeVisitor<Octs>(PhantomData<Octs>);
+
+        impl<'de, Octs> serde::de::Visitor<'de> for NewtypeVisitor<Octs>
+        where
+            Octs: AsRef<[u8]>
+                + octseq::builder::FromBuilder
+                + octseq::serde::DeserializeOctets<'de>,
+            <Octs as octseq::builder::FromBuilder>::Builder:
+                octseq::builder::EmptyBuilder,
+        {
+            type Value = Opt<Octs>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("OPT record data")
+            }
+
+            fn visit_newtype_struct<D: serde::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                Opt::from_octets(base16::serde::deserialize(deserializer)?)
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer
+            .deserialize_newtype_struct("Opt", NewtypeVisitor(PhantomData))
+    }
+}
+
 //------------ OptHeader -----------------------------------------------------
 
 /// The header of an OPT record.
@@ -905,6 +972,34 @@ pub(super) mod test {
         test_compose_parse(&rdata, |parser| Opt::parse(parser));
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn opt_ser_de() {
+        use serde_test::{assert_de_tokens_error, assert_tokens};
+        use serde_test::{Configure, Token};
+
+        let rdata =
+            Opt::from_octets(Vec::from(b"\0\x03\0\x01x".as_ref())).unwrap();
+        assert_tokens(
+            &rdata.clone().compact(),
+            &[
+                Token::NewtypeStruct { name: "Opt" },
+                Token::ByteBuf(b"\0\x03\0\x01x"),
+            ],
+        );
+        assert_tokens(
+            &rdata.readable(),
+            &[
+                Token::NewtypeStruct { name: "Opt" },
+                Token::Str("0003000178"),
+            ],
+        );
+        assert_de_tokens_error::<serde_test::Readable<Opt<Vec<u8>>>>(
+            &[Token::NewtypeStruct { name: "Opt" }, Token::Str("000300")],
+            "unexpected end of input",
+        );
+    }
+
     #[test]
     fn opt_record_header() {
         let mut header = OptHeader::default();
@@ -118,6 +118,18 @@ impl<Octs: ?Sized> Nsid<Octs> {
         self.octets.as_ref()
     }
 
+    /// Returns a value displaying the identifier as text.
+    ///
+    /// Since the identifier is an arbitrary sequence of octets, it is
+    /// interpreted as UTF-8 with invalid sequences replaced by the
+    /// replacement character and control characters replaced by a dot.
+    pub fn display_text(&self) -> NsidText<'_>
+    where
+        Octs: AsRef<[u8]>,
+    {
+        NsidText(self.as_slice())
+    }
+
     /// Returns a value over an octets slice.
     pub fn for_slice(&self) -> &Nsid<[u8]>
     where
@@ -183,11 +195,62 @@ impl<Octs: AsRef<[u8]> + ?Sized> fmt::Display for Nsid<Octs> {
         // | User interfaces MUST read and write the contents of the NSID
         // | option as a sequence of hexadecimal digits, two digits per
         // | payload octet.
-        for v in self.octets.as_ref() {
-            write!(f, "{:X} ", *v)?;
+        //
+        // We follow dig and add the content as text in parentheses.
+        let mut octets = self.octets.as_ref().iter();
+        if let Some(v) = octets.next() {
+            write!(f, "{:02x}", *v)?;
+        }
+        for v in octets {
+            write!(f, " {:02x}", *v)?;
         }
-        if let Ok(s) = str::from_utf8(self.octets.as_ref()) {
-            write!(f, "({})", s)?;
+        if !self.octets.as_ref().is_empty() {
+            write!(f, " (\"{}\")", self.display_text())?;
+        }
+        Ok(())
+    }
+}
+
+//------------ NsidText ------------------------------------------------------
+
+/// A helper type for displaying the name server identifier as text.
+///
+/// A value of this type can be obtained via [`Nsid::display_text`].
+#[derive(Clone, Copy, Debug)]
+pub struct NsidText<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for NsidText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use core::fmt::Write;
+
+        let mut octets = self.0;
+        while !octets.is_empty() {
+            let (valid, rest) = match str::from_utf8(octets) {
+                Ok(valid) => (valid, None),
+                Err(err) => {
+                    let (valid, rest) = octets.split_at(err.valid_up_to());
+                    let skip = err.error_len().unwrap_or(rest.len());
+                    (
+                        unsafe { str::from_utf8_unchecked(valid) },
+                        Some(&rest[skip..])
+                    )
+                }
+            };
+            for ch in valid.chars() {
+                if ch.is_control() {
+                    f.write_char('.')?;
+                }
+                else {
+                    f.write_char(ch)?;
+                }
+            }
+            match rest {
+                Some(rest) => {
+                    f.write_char(char::REPLACEMENT_CHARACTER)?;
+                    octets = rest;
+                }
+                None => break
+            }
         }
         Ok(())
     }
@@ -277,6 +340,24 @@ impl<'a, Target: Composer> OptBuilder<'a, Target> {
     pub fn client_nsid(&mut self) -> Result<(), Target::AppendError> {
         self.push(Nsid::empty())
     }
+
+    /// Appends an NSID option if the request asked for it.
+    ///
+    /// A server should include its identifier only if the OPT record of the
+    /// request, given via `request`, contains an NSID option. This method
+    /// appends `nsid` in this case and does nothing otherwise.
+    pub fn nsid_if_requested<Octs: Octets>(
+        &mut self,
+        request: &Opt<Octs>,
+        nsid: &Nsid<impl AsRef<[u8]> + ?Sized>,
+    ) -> Result<(), Target::AppendError> {
+        if request.nsid().is_some() {
+            self.push(nsid)
+        }
+        else {
+            Ok(())
+        }
+    }
 }
 
 //============ Testing ======================================================
@@ -294,4 +375,17 @@ mod test {
             |parser| Nsid::parse(parser)
         );
     }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            format!("{}", Nsid::from_slice(b"gpdns-fra").unwrap()),
+            "67 70 64 6e 73 2d 66 72 61 (\"gpdns-fra\")"
+        );
+        assert_eq!(
+            format!("{}", Nsid::from_slice(b"a\x0a\xffb").unwrap()),
+            "61 0a ff 62 (\"a.\u{FFFD}b\")"
+        );
+        assert_eq!(format!("{}", Nsid::empty()), "");
+    }
 }
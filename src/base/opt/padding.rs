@@ -7,6 +7,13 @@
 //! Since this option does not have any meaning for the receiver of a message,
 //! you should generally just use the [`OptBuilder::padding`] and
 //! [`OptBuilder::random_padding`] methods when constructing a message.
+//!
+//! How much padding to add is decided by a [`PaddingPolicy`] which can be
+//! applied via [`OptBuilder::pad_with`]. The module provides
+//! [`BlockPadding`], the block-length padding strategy recommended by
+//! [RFC 8467] for encrypted transports such as DNS over TLS and HTTPS.
+//!
+//! [RFC 8467]: https://tools.ietf.org/html/rfc8467
 
 use core::{borrow, fmt, str};
 use super::super::iana::OptionCode;
@@ -190,5 +197,136 @@ impl<'a, Target: Composer> OptBuilder<'a, Target> {
             }
         )
     }
+
+    /// Appends a padding option as determined by the given policy.
+    ///
+    /// The policy is asked for the padding length based on the length of
+    /// the message assembled so far. Because of this, the padding option
+    /// should be the last option added to the OPT record and the OPT
+    /// record should be the last record of the message except for a
+    /// TSIG record.
+    ///
+    /// If the policy decides not to pad, nothing is appended.
+    pub fn pad_with<P: PaddingPolicy + ?Sized>(
+        &mut self, policy: &P
+    ) -> Result<(), Target::AppendError> {
+        match policy.padding_len(self.as_target().as_ref().len()) {
+            Some(len) => self.padding(len),
+            None => Ok(())
+        }
+    }
+}
+
+
+//------------ PaddingPolicy -------------------------------------------------
+
+/// A policy deciding how much padding to add to a message.
+///
+/// The policy is used by [`OptBuilder::pad_with`].
+pub trait PaddingPolicy {
+    /// Returns the length of the padding option data to add.
+    ///
+    /// The `msg_len` is the length of the message before the padding
+    /// option is added. It does not include the length of the four octet
+    /// option header which needs to be taken into account by the policy.
+    ///
+    /// If the method returns `None`, no padding option is added at all.
+    fn padding_len(&self, msg_len: usize) -> Option<u16>;
+}
+
+impl<P: PaddingPolicy + ?Sized> PaddingPolicy for &P {
+    fn padding_len(&self, msg_len: usize) -> Option<u16> {
+        (*self).padding_len(msg_len)
+    }
+}
+
+
+//------------ BlockPadding --------------------------------------------------
+
+/// Block-length padding.
+///
+/// With this policy, the message is padded so that its length is a
+/// multiple of the block size. [RFC 8467] recommends this policy with a
+/// block size of 128 octets for queries and 468 octets for responses. These
+/// can be created via [`query`][Self::query] and
+/// [`response`][Self::response], respectively.
+///
+/// [RFC 8467]: https://tools.ietf.org/html/rfc8467
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct BlockPadding {
+    /// The block size.
+    block_size: u16,
+}
+
+impl BlockPadding {
+    /// The block size recommended for queries.
+    pub const QUERY_BLOCK_SIZE: u16 = 128;
+
+    /// The block size recommended for responses.
+    pub const RESPONSE_BLOCK_SIZE: u16 = 468;
+
+    /// Creates a new policy for the given block size.
+    ///
+    /// # Panics
+    ///
+    /// The function panics if `block_size` is zero.
+    pub fn new(block_size: u16) -> Self {
+        assert!(block_size > 0, "zero padding block size");
+        BlockPadding { block_size }
+    }
+
+    /// Creates the policy recommended for queries.
+    pub fn query() -> Self {
+        Self::new(Self::QUERY_BLOCK_SIZE)
+    }
+
+    /// Creates the policy recommended for responses.
+    pub fn response() -> Self {
+        Self::new(Self::RESPONSE_BLOCK_SIZE)
+    }
+
+    /// Returns the block size.
+    pub fn block_size(self) -> u16 {
+        self.block_size
+    }
+}
+
+impl PaddingPolicy for BlockPadding {
+    fn padding_len(&self, msg_len: usize) -> Option<u16> {
+        let block = usize::from(self.block_size);
+        let len = msg_len + 4;
+        u16::try_from((block - len % block) % block).ok()
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature="std", feature = "bytes"))]
+mod tests {
+    use super::*;
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::iana::Rtype;
+    use core::str::FromStr;
+    use std::vec::Vec;
+
+    #[test]
+    fn block_padding() {
+        for name in ["a.example", "a-much-longer-name.example.com"] {
+            let mut msg = MessageBuilder::new_vec().question();
+            msg.push((
+                Dname::<Vec<u8>>::from_str(name).unwrap(), Rtype::A
+            )).unwrap();
+            let mut msg = msg.additional();
+            msg.opt(|opt| opt.pad_with(&BlockPadding::query())).unwrap();
+            let msg = msg.finish();
+            assert_eq!(msg.len(), 128);
+            let msg = Message::from_octets(msg).unwrap();
+            let opt = msg.opt().unwrap();
+            assert!(opt.opt().iter::<Padding<_>>().next().is_some());
+        }
+    }
 }
 
@@ -37,6 +37,22 @@ use octseq::parse::Parser;
 ///
 /// The option is defined in [RFC 7871](https://tools.ietf.org/html/rfc7871)
 /// which also includes some guidance on its use.
+///
+/// # Privacy
+///
+/// Because the option reveals information about the client, section 11.1
+/// of RFC 7871 recommends to truncate client addresses to at most 24 bits
+/// for IPv4 and 56 bits for IPv6. The [`from_client`][Self::from_client]
+/// function does so by default. A client that doesn’t want its network to
+/// be revealed at all can send an option with a source prefix length of
+/// zero which can be created via [`opt_out`][Self::opt_out].
+///
+/// # Caching
+///
+/// The scope prefix length of a response determines for which clients the
+/// answer is valid and thus should be part of the cache key. The
+/// [`scope`][Self::scope] method returns this covering prefix as a
+/// [`SubnetScope`].
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ClientSubnet {
     /// The source prefix length.
@@ -50,6 +66,12 @@ pub struct ClientSubnet {
 }
 
 impl ClientSubnet {
+    /// The default source prefix length for IPv4 client addresses.
+    pub const DEFAULT_IPV4_PREFIX_LEN: u8 = 24;
+
+    /// The default source prefix length for IPv6 client addresses.
+    pub const DEFAULT_IPV6_PREFIX_LEN: u8 = 56;
+
     /// Creates a new client subnet value.
     ///
     /// The function is very forgiving regarding the arguments and corrects
@@ -72,6 +94,67 @@ impl ClientSubnet {
         }
     }
 
+    /// Creates a value for a query on behalf of the given client.
+    ///
+    /// The client address is truncated to the default prefix lengths
+    /// recommended by RFC 7871, i.e., to
+    /// [`DEFAULT_IPV4_PREFIX_LEN`][Self::DEFAULT_IPV4_PREFIX_LEN] or
+    /// [`DEFAULT_IPV6_PREFIX_LEN`][Self::DEFAULT_IPV6_PREFIX_LEN] bits.
+    pub fn from_client(addr: IpAddr) -> ClientSubnet {
+        Self::from_client_with_len(
+            addr,
+            Self::DEFAULT_IPV4_PREFIX_LEN,
+            Self::DEFAULT_IPV6_PREFIX_LEN,
+        )
+    }
+
+    /// Creates a value for a query using the given source prefix lengths.
+    ///
+    /// Depending on the address family of `addr`, either `ipv4_len` or
+    /// `ipv6_len` is used as the source prefix length.
+    pub fn from_client_with_len(
+        addr: IpAddr,
+        ipv4_len: u8,
+        ipv6_len: u8,
+    ) -> ClientSubnet {
+        let len = match addr {
+            IpAddr::V4(_) => ipv4_len,
+            IpAddr::V6(_) => ipv6_len,
+        };
+        Self::new(len, 0, addr)
+    }
+
+    /// Creates a value that asks the server not to use the client subnet.
+    ///
+    /// The value has a source prefix length of zero. As described in
+    /// section 7.1.2 of RFC 7871, this prohibits any upstream resolver from
+    /// adding client subnet information of its own. The address family is
+    /// taken from `addr`.
+    pub fn opt_out(addr: IpAddr) -> ClientSubnet {
+        Self::new(0, 0, addr)
+    }
+
+    /// Returns whether this value opts out of client subnet information.
+    pub fn is_opt_out(&self) -> bool {
+        self.source_prefix_len == 0
+    }
+
+    /// Returns the prefix an answer with this option is valid for.
+    ///
+    /// The prefix uses the scope prefix length returned by the server but
+    /// never more bits than the source prefix length, as section 7.3.1 of
+    /// RFC 7871 forbids caching with a longer prefix than initially
+    /// provided.
+    ///
+    /// The returned value should be used as part of the key when caching
+    /// the answer.
+    pub fn scope(&self) -> SubnetScope {
+        SubnetScope::new(
+            self.addr,
+            core::cmp::min(self.scope_prefix_len, self.source_prefix_len),
+        )
+    }
+
     /// Returns the source prefix length.
     ///
     /// The source prefix length is the prefix length as specified by the
@@ -261,6 +344,9 @@ impl<Octs: Octets> Opt<Octs> {
 }
 
 impl<'a, Target: Composer> OptBuilder<'a, Target> {
+    /// Appends a client subnet option.
+    ///
+    /// See [`ClientSubnet::new`] for how the arguments are treated.
     pub fn client_subnet(
         &mut self,
         source_prefix_len: u8,
@@ -273,6 +359,72 @@ impl<'a, Target: Composer> OptBuilder<'a, Target> {
     }
 }
 
+//------------ SubnetScope ---------------------------------------------------
+
+/// The address prefix a client subnet answer is valid for.
+///
+/// A value of this type is created from a client subnet option of a
+/// response via [`ClientSubnet::scope`]. A caching resolver can use it as
+/// part of the cache key and check via [`covers`][Self::covers] whether a
+/// cached answer can be used for a given client.
+///
+/// A prefix length of zero means that the answer is valid for all clients
+/// of the address family.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SubnetScope {
+    /// The address with all bits beyond the prefix length set to zero.
+    addr: IpAddr,
+
+    /// The prefix length.
+    prefix_len: u8,
+}
+
+impl SubnetScope {
+    /// Creates a new scope from an address and a prefix length.
+    ///
+    /// The prefix length is limited to the length of the address and all
+    /// bits of the address not covered by it are set to zero.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        let prefix_len = normalize_prefix_len(addr, prefix_len);
+        let (addr, _) = addr_apply_mask(addr, prefix_len);
+        SubnetScope { addr, prefix_len }
+    }
+
+    /// Returns the address of the prefix.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the prefix length.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether the prefix covers the given client address.
+    ///
+    /// Addresses of a different address family are never covered.
+    pub fn covers(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(_), IpAddr::V4(_))
+            | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                addr_apply_mask(addr, self.prefix_len).0 == self.addr
+            }
+            _ => false,
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for SubnetScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.addr {
+            IpAddr::V4(addr) => write!(f, "{}/{}", addr, self.prefix_len),
+            IpAddr::V6(addr) => write!(f, "{}/{}", addr, self.prefix_len),
+        }
+    }
+}
+
 //------------ Helper Functions ----------------------------------------------
 
 /// Returns the number of bytes needed for a prefix of a given length
@@ -383,6 +535,53 @@ mod tests {
     check!(prefix_max, "192.0.2.0", 32, "192.0.2.0", true);
     check!(prefix_too_long, "192.0.2.0", 100, "192.0.2.0", false);
     
+    #[test]
+    fn privacy_defaults() {
+        let opt = ClientSubnet::from_client(
+            IpAddr::from_str("192.0.2.77").unwrap()
+        );
+        assert_eq!(opt.source_prefix_len(), 24);
+        assert_eq!(opt.addr(), IpAddr::from_str("192.0.2.0").unwrap());
+
+        let opt = ClientSubnet::from_client(
+            IpAddr::from_str("2001:db8:1:2:3::1").unwrap()
+        );
+        assert_eq!(opt.source_prefix_len(), 56);
+        assert_eq!(opt.addr(), IpAddr::from_str("2001:db8:1::").unwrap());
+
+        let opt = ClientSubnet::opt_out(
+            IpAddr::from_str("192.0.2.77").unwrap()
+        );
+        assert!(opt.is_opt_out());
+        assert_eq!(opt.addr(), IpAddr::from_str("0.0.0.0").unwrap());
+    }
+
+    #[test]
+    fn scope() {
+        // Scope shorter than source.
+        let scope = ClientSubnet::new(
+            24, 16, IpAddr::from_str("192.0.2.0").unwrap()
+        ).scope();
+        assert_eq!(scope.prefix_len(), 16);
+        assert_eq!(scope.addr(), IpAddr::from_str("192.0.0.0").unwrap());
+        assert!(scope.covers(IpAddr::from_str("192.0.200.1").unwrap()));
+        assert!(!scope.covers(IpAddr::from_str("192.1.2.1").unwrap()));
+        assert!(!scope.covers(IpAddr::from_str("c000::").unwrap()));
+
+        // Scope longer than source is limited to source.
+        let scope = ClientSubnet::new(
+            24, 32, IpAddr::from_str("192.0.2.0").unwrap()
+        ).scope();
+        assert_eq!(scope.prefix_len(), 24);
+        assert!(scope.covers(IpAddr::from_str("192.0.2.99").unwrap()));
+
+        // Scope zero covers everything.
+        let scope = ClientSubnet::new(
+            24, 0, IpAddr::from_str("192.0.2.0").unwrap()
+        ).scope();
+        assert!(scope.covers(IpAddr::from_str("198.51.100.1").unwrap()));
+    }
+
     #[test]
     fn client_subnet_compose_parse() {
         test_option_compose_parse(
@@ -0,0 +1,251 @@
+//! Policies for dealing with questionable data.
+//!
+//! Some data is well-formed but still questionable. Record types may have
+//! been declared obsolete, a zonefile may contain records that don’t belong
+//! to the zone, or a message may use name compression in the record data of
+//! a record type that isn’t allowed to use it. Whether such data should be
+//! rejected, reported, or simply accepted depends on the application, so
+//! it can be configured via a [`ParsePolicy`].
+//!
+//! A policy assigns a [`Level`] to each kind of [`Violation`]. It can be
+//! given to the zonefile scanner via `Zonefile::set_policy` and to
+//! [`Message::check_with_policy`] for checking a message. Both apply the
+//! same rules:
+//!
+//! * The record types MD and MF have been obsoleted by [RFC 973]. Records
+//!   of these types are [`Violation::ObsoleteType`]. The same is true for
+//!   NULL records in zonefiles since [RFC 1035] doesn’t allow them there.
+//! * Records in a zonefile whose owner is not at or below the owner of the
+//!   first SOA record are [`Violation::OutOfZone`]. This only applies to
+//!   zonefiles.
+//! * Name compression in record data is only allowed for the record types
+//!   defined in [RFC 1035]. For all other types, compressed names are
+//!   [`Violation::ForbiddenCompression`] as required by [RFC 3597]. Since
+//!   zonefiles don’t use compression, this only applies to messages.
+//!
+//! The default policy accepts all these violations, which is how data was
+//! treated before policies existed.
+//!
+//! [`Message::check_with_policy`]: super::message::Message::check_with_policy
+//! [RFC 973]: https://tools.ietf.org/html/rfc973
+//! [RFC 1035]: https://tools.ietf.org/html/rfc1035
+//! [RFC 3597]: https://tools.ietf.org/html/rfc3597
+
+use super::iana::Rtype;
+use core::fmt;
+
+//------------ ParsePolicy ---------------------------------------------------
+
+/// The policy for dealing with questionable data.
+///
+/// See the [module documentation][self] for details.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ParsePolicy {
+    /// The level for records of obsolete types.
+    obsolete_types: Level,
+
+    /// The level for records outside the zone.
+    out_of_zone: Level,
+
+    /// The level for compressed names where compression isn’t allowed.
+    forbidden_compression: Level,
+}
+
+impl ParsePolicy {
+    /// Creates a new policy that accepts all data.
+    pub const fn new() -> Self {
+        ParsePolicy {
+            obsolete_types: Level::Allow,
+            out_of_zone: Level::Allow,
+            forbidden_compression: Level::Allow,
+        }
+    }
+
+    /// Creates a new policy that rejects all questionable data.
+    pub const fn strict() -> Self {
+        ParsePolicy {
+            obsolete_types: Level::Deny,
+            out_of_zone: Level::Deny,
+            forbidden_compression: Level::Deny,
+        }
+    }
+
+    /// Returns the level for records of obsolete types.
+    pub fn obsolete_types(&self) -> Level {
+        self.obsolete_types
+    }
+
+    /// Sets the level for records of obsolete types.
+    pub fn set_obsolete_types(&mut self, level: Level) {
+        self.obsolete_types = level
+    }
+
+    /// Returns the level for records outside the zone.
+    pub fn out_of_zone(&self) -> Level {
+        self.out_of_zone
+    }
+
+    /// Sets the level for records outside the zone.
+    pub fn set_out_of_zone(&mut self, level: Level) {
+        self.out_of_zone = level
+    }
+
+    /// Returns the level for compressed names where they aren’t allowed.
+    pub fn forbidden_compression(&self) -> Level {
+        self.forbidden_compression
+    }
+
+    /// Sets the level for compressed names where they aren’t allowed.
+    pub fn set_forbidden_compression(&mut self, level: Level) {
+        self.forbidden_compression = level
+    }
+
+    /// Returns the level for the given violation.
+    pub fn level(&self, violation: Violation) -> Level {
+        match violation {
+            Violation::ObsoleteType(_) => self.obsolete_types,
+            Violation::OutOfZone => self.out_of_zone,
+            Violation::ForbiddenCompression(_) => self.forbidden_compression,
+        }
+    }
+
+    /// Applies the policy to a violation.
+    ///
+    /// Returns an error if the violation is to be rejected, the violation
+    /// if it is to be reported as a warning, and `None` if it is to be
+    /// accepted silently.
+    pub fn check(
+        &self,
+        violation: Violation,
+    ) -> Result<Option<Violation>, Violation> {
+        match self.level(violation) {
+            Level::Allow => Ok(None),
+            Level::Warn => Ok(Some(violation)),
+            Level::Deny => Err(violation),
+        }
+    }
+}
+
+//------------ Level ---------------------------------------------------------
+
+/// How to deal with a violation.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Level {
+    /// The violation is accepted silently.
+    #[default]
+    Allow,
+
+    /// The violation is accepted but reported as a warning.
+    Warn,
+
+    /// The violation is an error.
+    Deny,
+}
+
+//------------ Violation -----------------------------------------------------
+
+/// A kind of questionable data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Violation {
+    /// A record of an obsolete record type.
+    ObsoleteType(Rtype),
+
+    /// A record in a zonefile outside of the zone.
+    OutOfZone,
+
+    /// A compressed name in record data that doesn’t allow compression.
+    ForbiddenCompression(Rtype),
+}
+
+impl Violation {
+    /// Returns a static description of the violation.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Violation::ObsoleteType(_) => "obsolete record type",
+            Violation::OutOfZone => "record outside of zone",
+            Violation::ForbiddenCompression(_) => {
+                "compressed name in record data"
+            }
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::ObsoleteType(rtype)
+            | Violation::ForbiddenCompression(rtype) => {
+                write!(f, "{} {}", self.as_str(), rtype)
+            }
+            Violation::OutOfZone => f.write_str(self.as_str()),
+        }
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Returns whether a record type is obsolete.
+///
+/// If `zonefile` is `true`, also considers record types that must not
+/// appear in zonefiles.
+pub fn is_obsolete(rtype: Rtype, zonefile: bool) -> bool {
+    match rtype {
+        Rtype::Md | Rtype::Mf => true,
+        Rtype::Null => zonefile,
+        _ => false,
+    }
+}
+
+/// Returns whether names in the record data of a type may be compressed.
+///
+/// This is only the case for the record types defined in RFC 1035.
+pub fn allows_compression(rtype: Rtype) -> bool {
+    matches!(
+        rtype,
+        Rtype::Cname
+            | Rtype::Mb
+            | Rtype::Md
+            | Rtype::Mf
+            | Rtype::Mg
+            | Rtype::Minfo
+            | Rtype::Mr
+            | Rtype::Mx
+            | Rtype::Ns
+            | Rtype::Ptr
+            | Rtype::Soa
+    )
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let mut policy = ParsePolicy::new();
+        let md = Violation::ObsoleteType(Rtype::Md);
+        assert_eq!(policy.check(md), Ok(None));
+        policy.set_obsolete_types(Level::Warn);
+        assert_eq!(policy.check(md), Ok(Some(md)));
+        assert_eq!(policy.check(Violation::OutOfZone), Ok(None));
+        assert_eq!(
+            ParsePolicy::strict().check(Violation::OutOfZone),
+            Err(Violation::OutOfZone)
+        );
+    }
+
+    #[test]
+    fn rules() {
+        assert!(is_obsolete(Rtype::Mf, false));
+        assert!(!is_obsolete(Rtype::Null, false));
+        assert!(is_obsolete(Rtype::Null, true));
+        assert!(!is_obsolete(Rtype::Minfo, true));
+        assert!(allows_compression(Rtype::Mx));
+        assert!(!allows_compression(Rtype::Srv));
+        assert!(!allows_compression(Rtype::Rrsig));
+    }
+}
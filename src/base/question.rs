@@ -101,6 +101,18 @@ impl<Octs> Question<ParsedDname<Octs>> {
 }
 
 impl<N: ToDname> Question<N> {
+    /// Returns the length of the question’s uncompressed wire format.
+    ///
+    /// This is the maximum number of octets [`compose`][Self::compose]
+    /// will append.
+    pub fn compose_len(&self) -> usize {
+        usize::from(
+            self.qname.compose_len()
+                + Rtype::COMPOSE_LEN
+                + Class::COMPOSE_LEN,
+        )
+    }
+
     pub fn compose<Target: Composer + ?Sized>(
         &self,
         target: &mut Target,
@@ -263,6 +275,13 @@ pub trait ComposeQuestion {
         &self,
         target: &mut Target,
     ) -> Result<(), Target::AppendError>;
+
+    /// Returns the maximum length of the question’s wire format if known.
+    ///
+    /// The default implementation returns `None`.
+    fn compose_len(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl<'a, Q: ComposeQuestion> ComposeQuestion for &'a Q {
@@ -272,6 +291,10 @@ impl<'a, Q: ComposeQuestion> ComposeQuestion for &'a Q {
     ) -> Result<(), Target::AppendError> {
         (*self).compose_question(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        (*self).compose_len()
+    }
 }
 
 impl<Name: ToDname> ComposeQuestion for Question<Name> {
@@ -281,6 +304,10 @@ impl<Name: ToDname> ComposeQuestion for Question<Name> {
     ) -> Result<(), Target::AppendError> {
         self.compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Some(Question::compose_len(self))
+    }
 }
 
 impl<Name: ToDname> ComposeQuestion for (Name, Rtype, Class) {
@@ -290,6 +317,10 @@ impl<Name: ToDname> ComposeQuestion for (Name, Rtype, Class) {
     ) -> Result<(), Target::AppendError> {
         Question::new(&self.0, self.1, self.2).compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Some(Question::new(&self.0, self.1, self.2).compose_len())
+    }
 }
 
 impl<Name: ToDname> ComposeQuestion for (Name, Rtype) {
@@ -299,4 +330,8 @@ impl<Name: ToDname> ComposeQuestion for (Name, Rtype) {
     ) -> Result<(), Target::AppendError> {
         Question::new(&self.0, self.1, Class::In).compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Some(Question::new(&self.0, self.1, Class::In).compose_len())
+    }
 }
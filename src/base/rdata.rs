@@ -16,6 +16,10 @@
 //! to deal with record types whose specification is not known (or has not
 //! been implemented yet).
 //!
+//! Record types whose data consists of a sequence of fixed-size fields,
+//! such as integers or addresses, can be defined with little effort
+//! using the [`rdata_type!`][crate::rdata_type] macro.
+//!
 //! [`RecordData`]: trait.RecordData.html
 //! [`ParseRecordData`]: trait.ParseRecordData.html
 //! [`RtypeRecordData`]: trait.RtypeRecordData.html
@@ -29,6 +33,7 @@ use super::wire::{Compose, Composer, ParseError};
 use crate::utils::base16;
 use core::cmp::Ordering;
 use core::fmt;
+use octseq::builder::FreezeBuilder;
 use octseq::octets::{Octets, OctetsFrom};
 use octseq::parse::Parser;
 
@@ -294,8 +299,13 @@ impl<Octs> UnknownRecordData<Octs> {
         // Second token is the rdata length.
         let len = u16::scan(scanner)?;
 
-        // The rest is the actual data.
-        let data = scanner.convert_entry(base16::SymbolConverter::new())?;
+        // The rest is the actual data. It is missing entirely if the
+        // length is zero.
+        let data = if len == 0 && !scanner.continues() {
+            scanner.octets_builder()?.freeze()
+        } else {
+            scanner.convert_entry(base16::SymbolConverter::new())?
+        };
 
         if data.as_ref().len() != usize::from(len) {
             return Err(S::Error::custom(
@@ -470,17 +480,337 @@ impl fmt::Display for LongRecordData {
 #[cfg(feature = "std")]
 impl std::error::Error for LongRecordData {}
 
+//------------ rdata_type! ---------------------------------------------------
+
+/// Defines a record data type consisting of fixed-size fields.
+///
+/// The macro takes a struct definition with named fields and the record
+/// type given in parentheses after the name of the struct. It defines the
+/// struct and provides all the implementations necessary to use it as
+/// record data:
+///
+/// * an associated constant `RTYPE` with the record type,
+/// * a function `new` taking all the fields in order,
+/// * the functions `parse` and `scan` for parsing the wire format and
+///   scanning the presentation format, both reading the fields in order,
+/// * the traits [`RecordData`], [`ParseRecordData`], and
+///   [`ComposeRecordData`],
+/// * the trait [`Compose`][crate::base::wire::Compose] for the wire format
+///   which is the fields’ wire formats concatenated,
+/// * `Display` for the presentation format which is the fields’
+///   presentation formats separated by a space,
+/// * `Clone`, `Debug`, `Hash`, `PartialEq`, and `Eq` via derive, and
+/// * `PartialOrd`, `Ord`, and [`CanonicalOrd`] via comparing the wire
+///   format as required for canonical ordering.
+///
+/// Each field’s type must implement [`Compose`][crate::base::wire::Compose],
+/// [`Parse`][crate::base::wire::Parse], [`Scan`], and `Display`. Within
+/// this crate, that is true for the unsigned integers and IP addresses.
+/// There must be at least one field. Additional attributes, including
+/// further derives, can be given for the struct and for each field.
+///
+/// Since record data defined this way isn’t part of
+/// [`AllRecordData`][crate::rdata::AllRecordData], records of the new
+/// type are parsed into the type directly by using it as the record data
+/// type, e.g., via
+/// [`Message::answer`][crate::base::Message::answer] and
+/// [`RecordSection::limit_to`][crate::base::message::RecordSection::limit_to].
+///
+/// # Example
+///
+/// ```
+/// use domain::base::iana::Rtype;
+/// use domain::base::net::Ipv4Addr;
+///
+/// domain::rdata_type! {
+///     /// A private record type with a preference and an address.
+///     pub struct Pref(Rtype::Int(65280)) {
+///         pub preference: u16,
+///         pub addr: Ipv4Addr,
+///     }
+/// }
+///
+/// let pref = Pref::new(10, Ipv4Addr::new(192, 0, 2, 1));
+/// assert_eq!(pref.to_string(), "10 192.0.2.1");
+/// ```
+#[macro_export]
+macro_rules! rdata_type {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident($rtype:expr) {
+            $(
+                $(#[$fattr:meta])*
+                $fvis:vis $field:ident: $ty:ty
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        $vis struct $name {
+            $(
+                $(#[$fattr])*
+                $fvis $field: $ty,
+            )+
+        }
+
+        impl $name {
+            /// The record type of the record data.
+            pub const RTYPE: $crate::base::iana::Rtype = $rtype;
+
+            /// Creates new record data from its fields.
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($( $field: $ty ),+) -> Self {
+                $name { $( $field ),+ }
+            }
+
+            /// Parses the record data from its wire format.
+            pub fn parse<Octs: AsRef<[u8]> + ?Sized>(
+                parser: &mut $crate::base::rdata::__macro::Parser<Octs>,
+            ) -> Result<Self, $crate::base::wire::ParseError>
+            where
+                $( $ty: for<'a> $crate::base::wire::Parse<'a, Octs>, )+
+            {
+                Ok($name {
+                    $(
+                        $field: <$ty as $crate::base::wire::Parse<
+                            '_, Octs
+                        >>::parse(parser)?,
+                    )+
+                })
+            }
+
+            /// Scans the record data from its presentation format.
+            pub fn scan<S: $crate::base::scan::Scanner>(
+                scanner: &mut S,
+            ) -> Result<Self, S::Error>
+            where
+                $( $ty: $crate::base::scan::Scan<S>, )+
+            {
+                Ok($name {
+                    $(
+                        $field: <$ty as $crate::base::scan::Scan<S>>::scan(
+                            scanner
+                        )?,
+                    )+
+                })
+            }
+        }
+
+        impl $crate::base::wire::Compose for $name {
+            const COMPOSE_LEN: u16 = 0 $(
+                + <$ty as $crate::base::wire::Compose>::COMPOSE_LEN
+            )+;
+
+            fn compose<Target>(
+                &self,
+                target: &mut Target,
+            ) -> Result<(), Target::AppendError>
+            where
+                Target: $crate::base::rdata::__macro::OctetsBuilder + ?Sized,
+            {
+                $(
+                    $crate::base::wire::Compose::compose(
+                        &self.$field, target
+                    )?;
+                )+
+                Ok(())
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(
+                &self,
+                other: &Self,
+            ) -> Option<$crate::base::rdata::__macro::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(
+                &self,
+                other: &Self,
+            ) -> $crate::base::rdata::__macro::Ordering {
+                $crate::base::rdata::__macro::cmp_composed(self, other)
+            }
+        }
+
+        impl $crate::base::cmp::CanonicalOrd for $name {
+            fn canonical_cmp(
+                &self,
+                other: &Self,
+            ) -> $crate::base::rdata::__macro::Ordering {
+                self.cmp(other)
+            }
+        }
+
+        impl $crate::base::rdata::RecordData for $name {
+            fn rtype(&self) -> $crate::base::iana::Rtype {
+                Self::RTYPE
+            }
+        }
+
+        impl<'a, Octs> $crate::base::rdata::ParseRecordData<'a, Octs>
+        for $name
+        where
+            Octs: AsRef<[u8]> + ?Sized,
+            $( $ty: for<'x> $crate::base::wire::Parse<'x, Octs>, )+
+        {
+            fn parse_rdata(
+                rtype: $crate::base::iana::Rtype,
+                parser: &mut $crate::base::rdata::__macro::Parser<'a, Octs>,
+            ) -> Result<Option<Self>, $crate::base::wire::ParseError> {
+                if rtype == Self::RTYPE {
+                    Self::parse(parser).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+
+        impl $crate::base::rdata::ComposeRecordData for $name {
+            fn rdlen(&self, _compress: bool) -> Option<u16> {
+                Some(<Self as $crate::base::wire::Compose>::COMPOSE_LEN)
+            }
+
+            fn compose_rdata<Target>(
+                &self,
+                target: &mut Target,
+            ) -> Result<(), Target::AppendError>
+            where
+                Target: $crate::base::wire::Composer + ?Sized,
+            {
+                $crate::base::wire::Compose::compose(self, target)
+            }
+
+            fn compose_canonical_rdata<Target>(
+                &self,
+                target: &mut Target,
+            ) -> Result<(), Target::AppendError>
+            where
+                Target: $crate::base::wire::Composer + ?Sized,
+            {
+                self.compose_rdata(target)
+            }
+        }
+
+        impl $crate::base::rdata::__macro::fmt::Display for $name {
+            #[allow(unused_assignments)]
+            fn fmt(
+                &self,
+                f: &mut $crate::base::rdata::__macro::fmt::Formatter,
+            ) -> $crate::base::rdata::__macro::fmt::Result {
+                let mut sep = "";
+                $(
+                    write!(f, "{}{}", sep, self.$field)?;
+                    sep = " ";
+                )+
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Items used by the `rdata_type!` macro.
+///
+/// This is not part of the public API and may change at any time.
+#[doc(hidden)]
+pub mod __macro {
+    use crate::base::wire::Compose;
+    pub use core::cmp::Ordering;
+    use core::convert::Infallible;
+    pub use core::fmt;
+    use octseq::builder::infallible;
+    pub use octseq::builder::OctetsBuilder;
+    pub use octseq::parse::Parser;
+
+    /// The size of the window used when comparing wire formats.
+    const WINDOW: usize = 64;
+
+    /// Compares the wire formats of two values.
+    ///
+    /// Since there may not be an allocator, the values are composed
+    /// repeatedly, each time keeping only a window of their wire formats.
+    pub fn cmp_composed<T: Compose + ?Sized>(
+        left: &T,
+        right: &T,
+    ) -> Ordering {
+        let mut start = 0;
+        loop {
+            let left = Window::compose(left, start);
+            let right = Window::compose(right, start);
+            match left.as_slice().cmp(right.as_slice()) {
+                Ordering::Equal => {}
+                res => return res,
+            }
+            if left.len < WINDOW {
+                return Ordering::Equal;
+            }
+            start += WINDOW;
+        }
+    }
+
+    /// An octets builder keeping only a part of the data appended to it.
+    struct Window {
+        /// The position of the first octet to keep.
+        start: usize,
+
+        /// The number of octets appended so far.
+        pos: usize,
+
+        /// The octets kept.
+        buf: [u8; WINDOW],
+
+        /// The number of octets kept.
+        len: usize,
+    }
+
+    impl Window {
+        fn compose<T: Compose + ?Sized>(value: &T, start: usize) -> Self {
+            let mut res = Window {
+                start,
+                pos: 0,
+                buf: [0; WINDOW],
+                len: 0,
+            };
+            infallible(value.compose(&mut res));
+            res
+        }
+
+        fn as_slice(&self) -> &[u8] {
+            &self.buf[..self.len]
+        }
+    }
+
+    impl OctetsBuilder for Window {
+        type AppendError = Infallible;
+
+        fn append_slice(&mut self, slice: &[u8]) -> Result<(), Infallible> {
+            let end = self.pos + slice.len();
+            let from = self.start.max(self.pos);
+            let to = (self.start + WINDOW).min(end);
+            if from < to {
+                self.buf[from - self.start..to - self.start]
+                    .copy_from_slice(&slice[from - self.pos..to - self.pos]);
+                self.len = to - self.start;
+            }
+            self.pos = end;
+            Ok(())
+        }
+    }
+}
+
 //============ Testing ======================================================
 
 #[cfg(test)]
 #[cfg(all(feature = "std", feature = "bytes"))]
 pub(crate) mod test {
     use super::super::scan::{IterScanner, Scanner};
-    use super::super::wire::ParseError;
+    use super::super::wire::{Compose, ParseError};
     use super::*;
     use bytes::{Bytes, BytesMut};
     use core::fmt::Debug;
-    use octseq::builder::infallible;
+    use octseq::builder::{infallible, OctetsBuilder};
     use std::vec::Vec;
 
     /// Check that `rdlen` produces the correct length.
@@ -537,4 +867,70 @@ pub(crate) mod test {
         assert_eq!(*expected, scan(&mut scanner).unwrap(),);
         assert!(scanner.is_exhausted());
     }
+
+    crate::rdata_type! {
+        /// A record type for testing the macro.
+        #[derive(Copy)]
+        struct Sample(Rtype::Int(65280)) {
+            preference: u16,
+            addr: crate::base::net::Ipv4Addr,
+            flags: u8,
+        }
+    }
+
+    #[test]
+    fn rdata_type_macro() {
+        use crate::base::net::Ipv4Addr;
+
+        let rdata = Sample::new(10, Ipv4Addr::new(192, 0, 2, 1), 3);
+        assert_eq!(rdata.rtype(), Rtype::Int(65280));
+        assert_eq!(rdata.rdlen(false), Some(7));
+        test_rdlen(rdata);
+        test_compose_parse(&rdata, Sample::parse);
+        test_scan(&["10", "192.0.2.1", "3"], Sample::scan, &rdata);
+        assert_eq!(format!("{}", rdata), "10 192.0.2.1 3");
+
+        let mut buf = Vec::new();
+        infallible(rdata.compose_rdata(&mut buf));
+        let mut parser = Parser::from_ref(buf.as_slice());
+        assert_eq!(Sample::parse_rdata(Rtype::A, &mut parser).unwrap(), None);
+        assert_eq!(
+            Sample::parse_rdata(Sample::RTYPE, &mut parser).unwrap(),
+            Some(rdata)
+        );
+        assert!(Sample::parse(&mut Parser::from_ref(&buf[..6])).is_err());
+
+        // Ordering follows the wire format.
+        let other = Sample::new(10, Ipv4Addr::new(192, 0, 2, 2), 0);
+        assert_eq!(rdata.canonical_cmp(&other), Ordering::Less);
+        assert_eq!(other.cmp(&rdata), Ordering::Greater);
+        assert_eq!(rdata.canonical_cmp(&rdata), Ordering::Equal);
+        assert!(Sample::new(256, other.addr, 0) > other);
+    }
+
+    #[test]
+    fn cmp_composed_windows() {
+        // Values longer than the comparison window.
+        struct Long([u128; 6]);
+
+        impl Compose for Long {
+            fn compose<Target: OctetsBuilder + ?Sized>(
+                &self,
+                target: &mut Target,
+            ) -> Result<(), Target::AppendError> {
+                self.0.iter().try_for_each(|item| item.compose(target))
+            }
+        }
+
+        let left = [u128::MAX, 0, 0, 0, 0, 1];
+        let right = [u128::MAX, 0, 0, 0, 0, 2];
+        assert_eq!(
+            __macro::cmp_composed(&Long(left), &Long(right)),
+            Ordering::Less
+        );
+        assert_eq!(
+            __macro::cmp_composed(&Long(right), &Long(right)),
+            Ordering::Equal
+        );
+    }
 }
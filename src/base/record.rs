@@ -199,6 +199,27 @@ impl<Octs, Data> Record<ParsedDname<Octs>, Data> {
 }
 
 impl<N: ToDname, D: RecordData + ComposeRecordData> Record<N, D> {
+    /// Returns the length of the record’s wire format.
+    ///
+    /// The length is that of the record without name compression. Since
+    /// compression can only make a record shorter, this is the maximum
+    /// number of octets [`compose`][Self::compose] will append and exactly
+    /// what [`compose_canonical`][Self::compose_canonical] will append.
+    ///
+    /// Returns `None` if the record data doesn’t know its length in
+    /// advance.
+    pub fn compose_len(&self) -> Option<usize> {
+        self.data.rdlen(false).map(|rdlen| {
+            usize::from(
+                self.owner.compose_len()
+                    + Rtype::COMPOSE_LEN
+                    + Class::COMPOSE_LEN
+                    + Ttl::COMPOSE_LEN
+                    + u16::COMPOSE_LEN,
+            ) + usize::from(rdlen)
+        })
+    }
+
     pub fn compose<Target: Composer + ?Sized>(
         &self,
         target: &mut Target,
@@ -422,6 +443,15 @@ pub trait ComposeRecord {
         &self,
         target: &mut Target,
     ) -> Result<(), Target::AppendError>;
+
+    /// Returns the maximum length of the record’s wire format if known.
+    ///
+    /// This allows deciding whether a record will fit into a message before
+    /// adding it. See [`Record::compose_len`] for details. The default
+    /// implementation returns `None`.
+    fn compose_len(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl<'a, T: ComposeRecord> ComposeRecord for &'a T {
@@ -431,6 +461,10 @@ impl<'a, T: ComposeRecord> ComposeRecord for &'a T {
     ) -> Result<(), Target::AppendError> {
         (*self).compose_record(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        (*self).compose_len()
+    }
 }
 
 impl<Name, Data> ComposeRecord for Record<Name, Data>
@@ -444,6 +478,10 @@ where
     ) -> Result<(), Target::AppendError> {
         self.compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Record::compose_len(self)
+    }
 }
 
 impl<Name, Data> ComposeRecord for (Name, Class, u32, Data)
@@ -458,6 +496,11 @@ where
         Record::new(&self.0, self.1, Ttl::from_secs(self.2), &self.3)
             .compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Record::new(&self.0, self.1, Ttl::from_secs(self.2), &self.3)
+            .compose_len()
+    }
 }
 
 impl<Name, Data> ComposeRecord for (Name, Class, Ttl, Data)
@@ -471,6 +514,10 @@ where
     ) -> Result<(), Target::AppendError> {
         Record::new(&self.0, self.1, self.2, &self.3).compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Record::new(&self.0, self.1, self.2, &self.3).compose_len()
+    }
 }
 
 impl<Name, Data> ComposeRecord for (Name, u32, Data)
@@ -485,6 +532,11 @@ where
         Record::new(&self.0, Class::In, Ttl::from_secs(self.1), &self.2)
             .compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Record::new(&self.0, Class::In, Ttl::from_secs(self.1), &self.2)
+            .compose_len()
+    }
 }
 
 impl<Name, Data> ComposeRecord for (Name, Ttl, Data)
@@ -498,6 +550,10 @@ where
     ) -> Result<(), Target::AppendError> {
         Record::new(&self.0, Class::In, self.1, &self.2).compose(target)
     }
+
+    fn compose_len(&self) -> Option<usize> {
+        Record::new(&self.0, Class::In, self.1, &self.2).compose_len()
+    }
 }
 
 //------------ RecordHeader --------------------------------------------------
@@ -601,7 +657,7 @@ impl<'a, Octs: AsRef<[u8]> + ?Sized> RecordHeader<ParsedDname<&'a Octs>> {
             ParsedDname::parse_ref(parser)?,
             Rtype::parse(parser)?,
             Class::parse(parser)?,
-            Ttl::parse(parser)?,
+            Ttl::parse_header(parser)?,
             parser.parse_u16_be()?,
         ))
     }
@@ -1022,6 +1078,12 @@ impl Ttl {
     /// The practical maximum time to live as recommended by [RFC 8767](https://datatracker.ietf.org/doc/html/rfc8767#section-4).
     pub const CAP: Ttl = Ttl::from_secs(604_800);
 
+    /// The largest TTL allowed on the wire.
+    ///
+    /// Larger record TTLs are treated as zero when parsed as required by
+    /// RFC 2181.
+    pub const MAX_WIRE: Ttl = Ttl::from_secs(i32::MAX as u32);
+
     /// The maximum number of minutes that a `Ttl` can represent.
     pub const MAX_MINUTES: u32 = 71582788;
 
@@ -1390,6 +1452,27 @@ impl Ttl {
             .map(Ttl::from_secs)
             .map_err(Into::into)
     }
+
+    /// Parses the TTL of a record header from its wire format.
+    ///
+    /// [RFC 2181] limits record TTLs to 2^31 - 1 seconds and requires
+    /// values with the most significant bit set to be treated as zero.
+    /// Other fields using the TTL type, such as the original TTL of an
+    /// RRSIG record or the SOA timers, should be parsed with
+    /// [`parse`][Self::parse] instead which keeps the value as is.
+    ///
+    /// [RFC 2181]: https://tools.ietf.org/html/rfc2181#section-8
+    pub fn parse_header<Octs: AsRef<[u8]> + ?Sized>(
+        parser: &mut Parser<'_, Octs>,
+    ) -> Result<Self, ParseError> {
+        Self::parse(parser).map(|ttl| {
+            if ttl > Self::MAX_WIRE {
+                Ttl::ZERO
+            } else {
+                ttl
+            }
+        })
+    }
 }
 
 impl core::ops::Add for Ttl {
@@ -1509,4 +1592,69 @@ mod test {
         assert_eq!(ds.owner(), ds_bytes.owner());
         asswer_eq!(ds.data().digest(), ds_bytes.data().digest());
     }
+
+    #[test]
+    fn parse_ttl() {
+        use super::*;
+
+        fn parse(secs: u32) -> Ttl {
+            let bytes = secs.to_be_bytes();
+            Ttl::parse_header(&mut Parser::from_ref(bytes.as_slice()))
+                .unwrap()
+        }
+
+        assert_eq!(parse(3600), Ttl::HOUR);
+        assert_eq!(parse(i32::MAX as u32), Ttl::MAX_WIRE);
+        assert_eq!(parse(i32::MAX as u32 + 1), Ttl::ZERO);
+        assert_eq!(parse(u32::MAX), Ttl::ZERO);
+
+        // Plain parsing keeps the value.
+        let bytes = u32::MAX.to_be_bytes();
+        assert_eq!(
+            Ttl::parse(&mut Parser::from_ref(bytes.as_slice())).unwrap(),
+            Ttl::MAX
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn compose_len() {
+        use super::*;
+        use crate::base::message_builder::MessageBuilder;
+        use crate::base::name::Dname;
+        use crate::rdata::{Mx, Txt, A};
+        use core::str::FromStr;
+        use std::vec::Vec;
+
+        let name = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        let mx = Mx::new(
+            10,
+            Dname::<Vec<u8>>::from_str("mail.example.com").unwrap(),
+        );
+        let a = (&name, 3600, A::from_octets(192, 0, 2, 1));
+        let mx = (&name, 3600, mx);
+        let txt = (
+            &name,
+            3600,
+            Txt::<Vec<u8>>::build_from_slice(b"hello").unwrap(),
+        );
+        assert_eq!(a.compose_len(), Some(13 + 10 + 4));
+        assert_eq!(mx.compose_len(), Some(13 + 10 + 2 + 18));
+        assert_eq!(txt.compose_len(), Some(13 + 10 + 6));
+
+        // Without compression, the length is exact.
+        let mut target = Vec::new();
+        a.compose_record(&mut target).unwrap();
+        assert_eq!(Some(target.len()), a.compose_len());
+        let mut target = Vec::new();
+        txt.compose_record(&mut target).unwrap();
+        assert_eq!(Some(target.len()), txt.compose_len());
+
+        // With compression, it is an upper bound.
+        let mut msg = MessageBuilder::new_vec().answer();
+        msg.push(&a).unwrap();
+        let len = msg.as_slice().len();
+        msg.push(&mx).unwrap();
+        assert!(msg.as_slice().len() - len <= mx.compose_len().unwrap());
+    }
 }
@@ -23,10 +23,12 @@
 
 use crate::base::charstr::{CharStr, CharStrBuilder};
 use crate::base::name::{Dname, ToDname};
+use crate::base::net::{Ipv4Addr, Ipv6Addr};
 use crate::base::wire::{Compose, Composer};
 use core::convert::{TryFrom, TryInto};
 use core::iter::Peekable;
 use core::marker::PhantomData;
+use core::str::FromStr;
 use core::{fmt, str};
 use octseq::str::Str;
 use octseq::{
@@ -109,6 +111,26 @@ impl<S: Scanner> Scan<S> for Ttl {
     }
 }
 
+impl<S: Scanner> Scan<S> for Ipv4Addr {
+    fn scan(scanner: &mut S) -> Result<Self, S::Error> {
+        let token = scanner.scan_octets()?;
+        str::from_utf8(token.as_ref())
+            .ok()
+            .and_then(|token| Ipv4Addr::from_str(token).ok())
+            .ok_or_else(|| S::Error::custom("expected IPv4 address"))
+    }
+}
+
+impl<S: Scanner> Scan<S> for Ipv6Addr {
+    fn scan(scanner: &mut S) -> Result<Self, S::Error> {
+        let token = scanner.scan_octets()?;
+        str::from_utf8(token.as_ref())
+            .ok()
+            .and_then(|token| Ipv6Addr::from_str(token).ok())
+            .ok_or_else(|| S::Error::custom("expected IPv6 address"))
+    }
+}
+
 //------------ Scanner -------------------------------------------------------
 
 /// A type that can produce tokens of data in presentation format.
@@ -574,11 +596,18 @@ impl Symbol {
     /// Provides the best symbol for an octet.
     ///
     /// The function will use the simple escape sequence for octet values that
-    /// represent ASCII spaces, quotes, backslashes, and semicolons and the
-    /// plain ASCII value for all other printable ASCII characters. Any other
-    /// value is escaped using the decimal escape sequence.
+    /// represent ASCII spaces, quotes, backslashes, semicolons, and
+    /// parentheses and the plain ASCII value for all other printable ASCII
+    /// characters. Any other value is escaped using the decimal escape
+    /// sequence.
     pub fn from_octet(ch: u8) -> Self {
-        if ch == b' ' || ch == b'"' || ch == b'\\' || ch == b';' {
+        if ch == b' '
+            || ch == b'"'
+            || ch == b'\\'
+            || ch == b';'
+            || ch == b'('
+            || ch == b')'
+        {
             Symbol::SimpleEscape(ch)
         } else if !(0x20..0x7F).contains(&ch) {
             Symbol::DecimalEscape(ch)
@@ -696,7 +725,7 @@ impl fmt::Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Symbol::Char(ch) => write!(f, "{}", ch),
-            Symbol::SimpleEscape(ch) => write!(f, "\\{}", ch),
+            Symbol::SimpleEscape(ch) => write!(f, "\\{}", char::from(ch)),
             Symbol::DecimalEscape(ch) => write!(f, "\\{:03}", ch),
         }
     }
@@ -1182,4 +1211,15 @@ mod test {
             );
         }
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn symbol_display() {
+        use std::string::ToString;
+
+        assert_eq!(Symbol::from_octet(b'a').to_string(), "a");
+        assert_eq!(Symbol::from_octet(b' ').to_string(), "\\ ");
+        assert_eq!(Symbol::from_octet(b'"').to_string(), "\\\"");
+        assert_eq!(Symbol::from_octet(0).to_string(), "\\000");
+    }
 }
@@ -193,6 +193,55 @@ impl Serial {
     }
 }
 
+/// # Serial Number Schemes
+///
+/// RFC 1982 arithmetic only requires that a new version of a zone has a
+/// greater serial number than the previous one. In practice, zones pick
+/// their serial numbers following one of a few schemes. The methods in this
+/// section return the serial number for the next version of a zone under
+/// these schemes given the serial number of the current version in `self`.
+///
+/// If the serial number of the scheme isn’t greater than `self`, these
+/// methods fall back to incrementing `self` by one. This happens if there
+/// already was a change with the same scheme value or if the serial number
+/// has moved past the scheme at some point. The result is thus always
+/// greater than `self`.
+///
+impl Serial {
+    /// Returns the next serial number using the date scheme.
+    ///
+    /// In this scheme, the serial number is the date of the change in the
+    /// form `YYYYMMDDnn` with the last two digits counting the changes made
+    /// on that date, starting at zero.
+    pub fn next_date(self, date: Date) -> Self {
+        let value = u64::try_from(date.year())
+            .ok()
+            .map(|year| {
+                year * 1_000_000
+                    + u64::from(u8::from(date.month())) * 10_000
+                    + u64::from(date.day()) * 100
+            })
+            .and_then(|value| u32::try_from(value).ok());
+        match value {
+            Some(value) if Serial(value) > self => Serial(value),
+            _ => self.add(1),
+        }
+    }
+
+    /// Returns the next serial number using the Unix time scheme.
+    ///
+    /// In this scheme, the serial number is the time of the change as the
+    /// number of seconds since the Unix epoch. The current time is
+    /// available via [`Serial::now`].
+    pub fn next_unix_time(self, time: Serial) -> Self {
+        if time > self {
+            time
+        } else {
+            self.add(1)
+        }
+    }
+}
+
 /// # Parsing and Composing
 ///
 impl Serial {
@@ -331,6 +380,33 @@ mod test {
         );
     }
 
+    #[test]
+    fn next_date() {
+        let date =
+            Date::from_calendar_date(2024, Month::February, 29).unwrap();
+        assert_eq!(Serial(2024022805).next_date(date), Serial(2024022900));
+        assert_eq!(Serial(2024022900).next_date(date), Serial(2024022901));
+        assert_eq!(Serial(2024030100).next_date(date), Serial(2024030101));
+        assert_eq!(Serial(1).next_date(date), Serial(2024022900));
+
+        // The serial number has moved past the scheme.
+        assert_eq!(
+            Serial(4_000_000_000).next_date(date),
+            Serial(4_000_000_001)
+        );
+
+        // The scheme value doesn’t fit into a serial number.
+        let date = Date::from_calendar_date(9999, Month::May, 1).unwrap();
+        assert_eq!(Serial(7).next_date(date), Serial(8));
+    }
+
+    #[test]
+    fn next_unix_time() {
+        assert_eq!(Serial(1000).next_unix_time(Serial(2000)), Serial(2000));
+        assert_eq!(Serial(2000).next_unix_time(Serial(2000)), Serial(2001));
+        assert_eq!(Serial(0xFFFF_FFF0).next_unix_time(Serial(5)), Serial(5));
+    }
+
     #[test]
     #[should_panic]
     fn bad_addition() {
@@ -0,0 +1,222 @@
+//! A small dig-like tool built atop the stub resolver.
+//!
+//! The tool sends a single query through the stub resolver and prints the
+//! response in the style of dig, followed by the time it took to get it.
+//! It is built with the `cli` feature and serves as an example of how to
+//! use the resolver and its options.
+//!
+//! ```text
+//! domain-dig [@server] [-p port] [+option ...] name [type]
+//! ```
+//!
+//! Without a server, the system’s resolver configuration is used. The
+//! supported options are `+tcp`, `+[no]recurse`, `+dnssec`, `+cd`, `+nsid`,
+//! `+nocookie`, `+timeout=<seconds>`, and `+tries=<number>`.
+//!
+//! The crate provides neither TLS nor HTTP clients, so only UDP and TCP
+//! are available. DNS-over-HTTPS needs a `DohTransport` with an HTTP
+//! client of your choice.
+use domain::base::iana::Rtype;
+use domain::base::name::Dname;
+use domain::resolv::stub::conf::{ResolvConf, ServerConf, Transport};
+use domain::resolv::StubResolver;
+use std::net::{IpAddr, SocketAddr};
+use std::process::exit;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::{env, fmt};
+
+//------------ Args ----------------------------------------------------------
+
+/// The command line arguments.
+struct Args {
+    /// The query name.
+    qname: Dname<Vec<u8>>,
+
+    /// The query type.
+    qtype: Rtype,
+
+    /// The server to send the query to if not the system’s.
+    server: Option<IpAddr>,
+
+    /// The port to send the query to.
+    port: u16,
+
+    /// Whether to use TCP instead of UDP.
+    tcp: bool,
+
+    /// The resolver configuration.
+    conf: ResolvConf,
+}
+
+impl Args {
+    /// Parses the command line arguments.
+    fn from_env() -> Result<Self, ArgsError> {
+        let mut qname = None;
+        let mut qtype = None;
+        let mut server = None;
+        let mut port = 53;
+        let mut tcp = false;
+        let mut conf = ResolvConf::default();
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if let Some(addr) = arg.strip_prefix('@') {
+                server = Some(IpAddr::from_str(addr).map_err(|_| {
+                    ArgsError::new("invalid server address", &arg)
+                })?);
+            } else if arg == "-p" {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ArgsError::new("missing port", &arg))?;
+                port = u16::from_str(&value)
+                    .map_err(|_| ArgsError::new("invalid port", &value))?;
+            } else if let Some(option) = arg.strip_prefix('+') {
+                Self::apply_option(option, &mut tcp, &mut conf)
+                    .map_err(|msg| ArgsError::new(msg, &arg))?;
+            } else if qname.is_none() {
+                qname = Some(
+                    Dname::from_str(&arg)
+                        .map_err(|_| ArgsError::new("invalid name", &arg))?,
+                );
+            } else if qtype.is_none() {
+                qtype = Some(
+                    Rtype::from_str(&arg)
+                        .map_err(|_| ArgsError::new("invalid type", &arg))?,
+                );
+            } else {
+                return Err(ArgsError::new("unexpected argument", &arg));
+            }
+        }
+
+        Ok(Args {
+            qname: qname.ok_or(ArgsError::usage())?,
+            qtype: qtype.unwrap_or(Rtype::A),
+            server,
+            port,
+            tcp,
+            conf,
+        })
+    }
+
+    /// Applies a `+` option.
+    fn apply_option(
+        option: &str,
+        tcp: &mut bool,
+        conf: &mut ResolvConf,
+    ) -> Result<(), &'static str> {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+        let options = &mut conf.options;
+        match (name, value) {
+            ("tcp", None) => *tcp = true,
+            ("recurse", None) => options.recurse = true,
+            ("norecurse", None) => options.recurse = false,
+            ("dnssec", None) => options.dnssec_ok = true,
+            ("cd", None) => options.checking_disabled = true,
+            ("nsid", None) => options.nsid = true,
+            ("nocookie", None) => options.cookies = false,
+            ("timeout", Some(value)) => {
+                options.timeout = Duration::from_secs(
+                    u64::from_str(value).map_err(|_| "invalid timeout")?,
+                );
+            }
+            ("tries", Some(value)) => {
+                options.attempts =
+                    usize::from_str(value).map_err(|_| "invalid tries")?;
+            }
+            _ => return Err("unknown option"),
+        }
+        Ok(())
+    }
+
+    /// Converts the arguments into resolver configuration and question.
+    fn into_conf(self) -> (ResolvConf, Dname<Vec<u8>>, Rtype) {
+        let mut conf = self.conf;
+        if let Some(addr) = self.server {
+            // As with the system configuration, TCP is only used if the
+            // UDP response is truncated.
+            let addr = SocketAddr::new(addr, self.port);
+            conf.servers = vec![
+                ServerConf::new(addr, Transport::Udp),
+                ServerConf::new(addr, Transport::Tcp),
+            ];
+        } else {
+            for server in &mut conf.servers {
+                server.addr.set_port(self.port);
+            }
+        }
+        if self.tcp {
+            conf.servers
+                .retain(|server| server.transport == Transport::Tcp);
+        }
+        conf.finalize();
+        (conf, self.qname, self.qtype)
+    }
+}
+
+//------------ ArgsError -----------------------------------------------------
+
+/// An error happened while parsing the command line.
+struct ArgsError(String);
+
+impl ArgsError {
+    fn new(msg: &str, arg: &str) -> Self {
+        ArgsError(format!("{}: {}", msg, arg))
+    }
+
+    fn usage() -> Self {
+        ArgsError(
+            "Usage: domain-dig [@server] [-p port] [+option ...] \
+             name [type]"
+                .into(),
+        )
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+//------------ main ----------------------------------------------------------
+
+fn main() {
+    let args = match Args::from_env() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    let (conf, qname, qtype) = args.into_conf();
+    let servers = conf
+        .servers
+        .iter()
+        .map(|server| format!("{}({:?})", server.addr, server.transport))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let (answer, rtt) =
+        StubResolver::run_with_conf(conf, move |stub| async move {
+            let start = Instant::now();
+            let answer = stub.query((qname, qtype)).await;
+            (answer, start.elapsed())
+        });
+    let answer = match answer {
+        Ok(answer) => answer,
+        Err(err) => {
+            eprintln!(";; query failed: {}", err);
+            exit(1);
+        }
+    };
+
+    print!("{}", answer.display_dig());
+    println!();
+    println!(";; Query time: {} msec", rtt.as_millis());
+    println!(";; SERVERS: {}", servers);
+    println!(";; MSG SIZE  rcvd: {}", answer.as_slice().len());
+}
@@ -0,0 +1,755 @@
+//! Cryptographic backends for DNSSEC.
+//!
+//! **This module is experimental and likely to change significantly.**
+//!
+//! Both the [validate][crate::validate] and [sign][crate::sign] modules
+//! only deal with the DNS side of things: they find the records, construct
+//! the signed data, and check that keys and signatures belong together.
+//! The actual cryptographic operations are delegated to a type
+//! implementing the [`Backend`] trait for verifying signatures and
+//! calculating digests and, for signing, to the key types implementing
+//! `sign::key::SigningKey`.
+//!
+//! The crate provides two backends: `RingBackend` uses the [ring] crate and
+//! is available with the `ring` feature, `OpensslBackend` uses the
+//! [openssl] crate and is available with the `openssl` feature. The
+//! OpenSSL backend additionally supports Ed448 and can make use of a
+//! FIPS-validated OpenSSL installation. Users that need yet another crypto
+//! library can provide their own backend by implementing these traits
+//! without having to change any of the DNSSEC code.
+//!
+//! Hashed owner names for NSEC3 are calculated by [`Nsec3Hasher`] which is
+//! used for both validation and signing. The function [`nsec3_hash`]
+//! provides a shortcut for getting the hashed owner label of a name.
+//!
+//! [ring]: https://github.com/briansmith/ring
+//! [openssl]: https://github.com/sfackler/rust-openssl
+#![cfg(any(feature = "sign", feature = "validate"))]
+#![cfg_attr(docsrs, doc(cfg(any(feature = "sign", feature = "validate"))))]
+
+use crate::base::iana::{DigestAlg, Nsec3HashAlg, SecAlg};
+use crate::base::name::ToDname;
+use crate::utils::base32;
+use octseq::builder::infallible;
+#[cfg(feature = "openssl")]
+use openssl::bn::{BigNum, BigNumContext};
+#[cfg(feature = "openssl")]
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+#[cfg(feature = "openssl")]
+use openssl::ecdsa::EcdsaSig;
+#[cfg(feature = "openssl")]
+use openssl::hash::{hash, MessageDigest};
+#[cfg(feature = "openssl")]
+use openssl::nid::Nid;
+#[cfg(feature = "openssl")]
+use openssl::pkey::{Id, PKey};
+#[cfg(feature = "openssl")]
+use openssl::rsa::Rsa;
+#[cfg(feature = "openssl")]
+use openssl::sign::Verifier;
+#[cfg(feature = "ring")]
+use ring::{digest, signature};
+use std::string::String;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Backend -------------------------------------------------------
+
+/// A provider of the cryptographic operations needed for DNSSEC.
+pub trait Backend {
+    /// Returns whether the backend supports a signature algorithm.
+    fn supports_algorithm(&self, algorithm: SecAlg) -> bool;
+
+    /// Returns whether the backend supports a digest algorithm.
+    fn supports_digest(&self, algorithm: DigestAlg) -> bool;
+
+    /// Verifies a signature.
+    ///
+    /// The `public_key` is given in the format of the public key field of
+    /// the DNSKEY record and the `signature` in the format of the signature
+    /// field of the RRSIG record for the given algorithm.
+    ///
+    /// Returns [`AlgorithmError::Unsupported`] if the algorithm or key size
+    /// isn’t supported, [`AlgorithmError::InvalidData`] if the key is
+    /// malformed, and [`AlgorithmError::BadSig`] if the signature doesn’t
+    /// verify.
+    fn verify(
+        &self,
+        algorithm: SecAlg,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), AlgorithmError>;
+
+    /// Calculates the digest of some data.
+    fn digest(
+        &self,
+        algorithm: DigestAlg,
+        data: &[u8],
+    ) -> Result<Vec<u8>, AlgorithmError>;
+}
+
+impl<B: Backend + ?Sized> Backend for &B {
+    fn supports_algorithm(&self, algorithm: SecAlg) -> bool {
+        (*self).supports_algorithm(algorithm)
+    }
+
+    fn supports_digest(&self, algorithm: DigestAlg) -> bool {
+        (*self).supports_digest(algorithm)
+    }
+
+    fn verify(
+        &self,
+        algorithm: SecAlg,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), AlgorithmError> {
+        (*self).verify(algorithm, public_key, signed_data, signature)
+    }
+
+    fn digest(
+        &self,
+        algorithm: DigestAlg,
+        data: &[u8],
+    ) -> Result<Vec<u8>, AlgorithmError> {
+        (*self).digest(algorithm, data)
+    }
+}
+
+//------------ RingBackend ---------------------------------------------------
+
+/// A backend using the ring crate.
+///
+/// The backend supports the RSA algorithms with SHA-1, SHA-256, and SHA-512
+/// for keys of at least 1024 bits, ECDSA with P-256 and P-384, and Ed25519.
+/// It supports the SHA-1, SHA-256, and SHA-384 digests.
+#[cfg(feature = "ring")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RingBackend;
+
+#[cfg(feature = "ring")]
+impl RingBackend {
+    /// The minimum supported size of an RSA modulus in octets.
+    const RSA_MIN_BYTES: usize = 1024 / 8;
+
+    fn digest_algorithm(
+        algorithm: DigestAlg,
+    ) -> Option<&'static digest::Algorithm> {
+        match algorithm {
+            DigestAlg::Sha1 => Some(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+            DigestAlg::Sha256 => Some(&digest::SHA256),
+            DigestAlg::Sha384 => Some(&digest::SHA384),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "ring")]
+impl Backend for RingBackend {
+    fn supports_algorithm(&self, algorithm: SecAlg) -> bool {
+        matches!(
+            SecAlg::from_int(algorithm.to_int()),
+            SecAlg::RsaSha1
+                | SecAlg::RsaSha1Nsec3Sha1
+                | SecAlg::RsaSha256
+                | SecAlg::RsaSha512
+                | SecAlg::EcdsaP256Sha256
+                | SecAlg::EcdsaP384Sha384
+                | SecAlg::Ed25519
+        )
+    }
+
+    fn supports_digest(&self, algorithm: DigestAlg) -> bool {
+        Self::digest_algorithm(DigestAlg::from_int(algorithm.to_int()))
+            .is_some()
+    }
+
+    fn verify(
+        &self,
+        algorithm: SecAlg,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), AlgorithmError> {
+        // Note: Canonicalize the algorithm, otherwise matching named variants against Int(_) is not going to work
+        let sec_alg = SecAlg::from_int(algorithm.to_int());
+        match sec_alg {
+            SecAlg::RsaSha1
+            | SecAlg::RsaSha1Nsec3Sha1
+            | SecAlg::RsaSha256
+            | SecAlg::RsaSha512 => {
+                let algorithm = match sec_alg {
+                    SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => {
+                        &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY
+                    }
+                    SecAlg::RsaSha256 => {
+                        &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY
+                    }
+                    SecAlg::RsaSha512 => {
+                        &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY
+                    }
+                    _ => unreachable!(),
+                };
+
+                // The key isn't available in either PEM or DER, so use the
+                // direct RSA verifier.
+                let (e, n) =
+                    rsa_exponent_modulus(public_key, Self::RSA_MIN_BYTES)?;
+                let public_key =
+                    signature::RsaPublicKeyComponents { n: &n, e: &e };
+                public_key
+                    .verify(algorithm, signed_data, signature)
+                    .map_err(|_| AlgorithmError::BadSig)
+            }
+            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+                let algorithm = match sec_alg {
+                    SecAlg::EcdsaP256Sha256 => {
+                        &signature::ECDSA_P256_SHA256_FIXED
+                    }
+                    SecAlg::EcdsaP384Sha384 => {
+                        &signature::ECDSA_P384_SHA384_FIXED
+                    }
+                    _ => unreachable!(),
+                };
+
+                // Add 0x4 identifier to the ECDSA pubkey as expected by ring.
+                let mut key = Vec::with_capacity(public_key.len() + 1);
+                key.push(0x4);
+                key.extend_from_slice(public_key);
+
+                signature::UnparsedPublicKey::new(algorithm, &key)
+                    .verify(signed_data, signature)
+                    .map_err(|_| AlgorithmError::BadSig)
+            }
+            SecAlg::Ed25519 => signature::UnparsedPublicKey::new(
+                &signature::ED25519,
+                public_key,
+            )
+            .verify(signed_data, signature)
+            .map_err(|_| AlgorithmError::BadSig),
+            _ => Err(AlgorithmError::Unsupported),
+        }
+    }
+
+    fn digest(
+        &self,
+        algorithm: DigestAlg,
+        data: &[u8],
+    ) -> Result<Vec<u8>, AlgorithmError> {
+        let algorithm =
+            Self::digest_algorithm(DigestAlg::from_int(algorithm.to_int()))
+                .ok_or(AlgorithmError::Unsupported)?;
+        Ok(digest::digest(algorithm, data).as_ref().into())
+    }
+}
+
+//------------ OpensslBackend ------------------------------------------------
+
+/// A backend using the openssl crate.
+///
+/// The backend supports the RSA algorithms with SHA-1, SHA-256, and SHA-512
+/// for keys of at least 1024 bits, ECDSA with P-256 and P-384, Ed25519, and
+/// Ed448. It supports the SHA-1, SHA-256, and SHA-384 digests. Whether the
+/// SHA-1 based algorithms are actually available depends on the
+/// configuration of the OpenSSL library.
+#[cfg(feature = "openssl")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpensslBackend;
+
+#[cfg(feature = "openssl")]
+impl OpensslBackend {
+    /// The minimum supported size of an RSA modulus in octets.
+    const RSA_MIN_BYTES: usize = 1024 / 8;
+
+    fn digest_algorithm(algorithm: DigestAlg) -> Option<MessageDigest> {
+        match algorithm {
+            DigestAlg::Sha1 => Some(MessageDigest::sha1()),
+            DigestAlg::Sha256 => Some(MessageDigest::sha256()),
+            DigestAlg::Sha384 => Some(MessageDigest::sha384()),
+            _ => None,
+        }
+    }
+
+    /// Verifies an RSA signature.
+    fn verify_rsa(
+        digest: MessageDigest,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, AlgorithmError> {
+        let (e, n) = rsa_exponent_modulus(public_key, Self::RSA_MIN_BYTES)?;
+        let key = BigNum::from_slice(n)
+            .and_then(|n| Ok((n, BigNum::from_slice(e)?)))
+            .and_then(|(n, e)| Rsa::from_public_components(n, e))
+            .and_then(PKey::from_rsa)
+            .map_err(|_| AlgorithmError::InvalidData)?;
+        Verifier::new(digest, &key)
+            .and_then(|mut verifier| {
+                verifier.verify_oneshot(signature, signed_data)
+            })
+            .map_err(|_| AlgorithmError::BadSig)
+    }
+
+    /// Verifies an ECDSA signature.
+    ///
+    /// DNSSEC uses the concatenated integers r and s of equal length as
+    /// the signature and the uncompressed point sans its leading 0x04 as
+    /// the public key.
+    fn verify_ecdsa(
+        curve: Nid,
+        digest: MessageDigest,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, AlgorithmError> {
+        let group = EcGroup::from_curve_name(curve)
+            .map_err(|_| AlgorithmError::Unsupported)?;
+        let mut point = Vec::with_capacity(public_key.len() + 1);
+        point.push(0x4);
+        point.extend_from_slice(public_key);
+        let key = BigNumContext::new()
+            .and_then(|mut ctx| EcPoint::from_bytes(&group, &point, &mut ctx))
+            .and_then(|point| EcKey::from_public_key(&group, &point))
+            .map_err(|_| AlgorithmError::InvalidData)?;
+        if signature.len() % 2 != 0 {
+            return Ok(false);
+        }
+        let (r, s) = signature.split_at(signature.len() / 2);
+        let sig = BigNum::from_slice(r)
+            .and_then(|r| Ok((r, BigNum::from_slice(s)?)))
+            .and_then(|(r, s)| EcdsaSig::from_private_components(r, s))
+            .map_err(|_| AlgorithmError::BadSig)?;
+        hash(digest, signed_data)
+            .and_then(|digest| sig.verify(&digest, &key))
+            .map_err(|_| AlgorithmError::BadSig)
+    }
+
+    /// Verifies an EdDSA signature.
+    fn verify_eddsa(
+        id: Id,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, AlgorithmError> {
+        let key = PKey::public_key_from_raw_bytes(public_key, id)
+            .map_err(|_| AlgorithmError::InvalidData)?;
+        Verifier::new_without_digest(&key)
+            .and_then(|mut verifier| {
+                verifier.verify_oneshot(signature, signed_data)
+            })
+            .map_err(|_| AlgorithmError::BadSig)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Backend for OpensslBackend {
+    fn supports_algorithm(&self, algorithm: SecAlg) -> bool {
+        matches!(
+            SecAlg::from_int(algorithm.to_int()),
+            SecAlg::RsaSha1
+                | SecAlg::RsaSha1Nsec3Sha1
+                | SecAlg::RsaSha256
+                | SecAlg::RsaSha512
+                | SecAlg::EcdsaP256Sha256
+                | SecAlg::EcdsaP384Sha384
+                | SecAlg::Ed25519
+                | SecAlg::Ed448
+        )
+    }
+
+    fn supports_digest(&self, algorithm: DigestAlg) -> bool {
+        Self::digest_algorithm(DigestAlg::from_int(algorithm.to_int()))
+            .is_some()
+    }
+
+    fn verify(
+        &self,
+        algorithm: SecAlg,
+        public_key: &[u8],
+        signed_data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), AlgorithmError> {
+        let verified = match SecAlg::from_int(algorithm.to_int()) {
+            SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => Self::verify_rsa(
+                MessageDigest::sha1(),
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::RsaSha256 => Self::verify_rsa(
+                MessageDigest::sha256(),
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::RsaSha512 => Self::verify_rsa(
+                MessageDigest::sha512(),
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::EcdsaP256Sha256 => Self::verify_ecdsa(
+                Nid::X9_62_PRIME256V1,
+                MessageDigest::sha256(),
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::EcdsaP384Sha384 => Self::verify_ecdsa(
+                Nid::SECP384R1,
+                MessageDigest::sha384(),
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::Ed25519 => Self::verify_eddsa(
+                Id::ED25519,
+                public_key,
+                signed_data,
+                signature,
+            ),
+            SecAlg::Ed448 => Self::verify_eddsa(
+                Id::ED448,
+                public_key,
+                signed_data,
+                signature,
+            ),
+            _ => Err(AlgorithmError::Unsupported),
+        }?;
+        if verified {
+            Ok(())
+        } else {
+            Err(AlgorithmError::BadSig)
+        }
+    }
+
+    fn digest(
+        &self,
+        algorithm: DigestAlg,
+        data: &[u8],
+    ) -> Result<Vec<u8>, AlgorithmError> {
+        let algorithm =
+            Self::digest_algorithm(DigestAlg::from_int(algorithm.to_int()))
+                .ok_or(AlgorithmError::Unsupported)?;
+        hash(algorithm, data)
+            .map(|digest| digest.as_ref().into())
+            .map_err(|_| AlgorithmError::Unsupported)
+    }
+}
+
+/// Return the RSA exponent and modulus components from DNSKEY record data.
+#[cfg(any(feature = "ring", feature = "openssl"))]
+fn rsa_exponent_modulus(
+    public_key: &[u8],
+    min_len: usize,
+) -> Result<(&[u8], &[u8]), AlgorithmError> {
+    if public_key.len() <= 3 {
+        return Err(AlgorithmError::InvalidData);
+    }
+
+    let (pos, exp_len) = match public_key[0] {
+        0 => (
+            3,
+            (usize::from(public_key[1]) << 8) | usize::from(public_key[2]),
+        ),
+        len => (1, usize::from(len)),
+    };
+
+    // Check if there's enough space for exponent and modulus.
+    if public_key[pos..].len() < pos + exp_len {
+        return Err(AlgorithmError::InvalidData);
+    };
+
+    // Check for minimum supported key size
+    if public_key[pos..].len() < min_len {
+        return Err(AlgorithmError::Unsupported);
+    }
+
+    Ok(public_key[pos..].split_at(exp_len))
+}
+
+//------------ Nsec3Hasher ---------------------------------------------------
+
+/// The default maximum number of additional NSEC3 hash iterations.
+///
+/// Since every iteration adds to the cost of checking a negative answer,
+/// [RFC 9276] recommends that validators treat NSEC3 records with a high
+/// number of iterations as insecure. It also notes that validator
+/// implementations have settled on a limit of 150 iterations.
+///
+/// [RFC 9276]: https://tools.ietf.org/html/rfc9276
+pub const NSEC3_MAX_ITERATIONS: u16 = 150;
+
+/// Calculates hashed owner names for NSEC3 as described in [RFC 5155].
+///
+/// The hasher uses a crypto backend for calculating the digests and
+/// refuses to hash a name with more additional iterations than its
+/// configured maximum which defaults to [`NSEC3_MAX_ITERATIONS`].
+///
+/// [RFC 5155]: https://tools.ietf.org/html/rfc5155#section-5
+#[derive(Clone, Copy, Debug)]
+pub struct Nsec3Hasher<B> {
+    /// The backend for calculating digests.
+    backend: B,
+
+    /// The maximum number of additional iterations.
+    max_iterations: u16,
+}
+
+#[cfg(feature = "ring")]
+impl Nsec3Hasher<RingBackend> {
+    /// Creates a new hasher using the [`RingBackend`].
+    pub fn new() -> Self {
+        Self::with_backend(RingBackend)
+    }
+}
+
+#[cfg(feature = "ring")]
+impl Default for Nsec3Hasher<RingBackend> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B> Nsec3Hasher<B> {
+    /// Creates a new hasher using the given backend.
+    pub fn with_backend(backend: B) -> Self {
+        Nsec3Hasher {
+            backend,
+            max_iterations: NSEC3_MAX_ITERATIONS,
+        }
+    }
+
+    /// Returns the maximum number of additional iterations.
+    pub fn max_iterations(&self) -> u16 {
+        self.max_iterations
+    }
+
+    /// Sets the maximum number of additional iterations.
+    pub fn set_max_iterations(&mut self, max_iterations: u16) {
+        self.max_iterations = max_iterations
+    }
+
+    /// Returns a reference to the backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+}
+
+impl<B: Backend> Nsec3Hasher<B> {
+    /// Calculates the hash of `name`.
+    ///
+    /// The hash is calculated over the canonical form of `name`, i.e., with
+    /// all ASCII letters in lowercase, followed by `salt` and then rehashed
+    /// together with `salt` `iterations` times.
+    pub fn hash<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+        algorithm: Nsec3HashAlg,
+        iterations: u16,
+        salt: &[u8],
+    ) -> Result<Vec<u8>, Nsec3HashError> {
+        if algorithm != Nsec3HashAlg::Sha1 {
+            return Err(Nsec3HashError::UnsupportedAlgorithm);
+        }
+        if iterations > self.max_iterations {
+            return Err(Nsec3HashError::TooManyIterations);
+        }
+        let mut buf = Vec::new();
+        infallible(name.compose_canonical(&mut buf));
+        buf.extend_from_slice(salt);
+        let mut hash = self.digest(&buf)?;
+        for _ in 0..iterations {
+            buf.clear();
+            buf.extend_from_slice(&hash);
+            buf.extend_from_slice(salt);
+            hash = self.digest(&buf)?;
+        }
+        Ok(hash)
+    }
+
+    /// Calculates the hashed owner label of `name`.
+    ///
+    /// This is the hash encoded in lowercase Base 32 with the extended hex
+    /// alphabet, ready to be prepended to the zone apex.
+    pub fn hash_label<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+        algorithm: Nsec3HashAlg,
+        iterations: u16,
+        salt: &[u8],
+    ) -> Result<String, Nsec3HashError> {
+        self.hash(name, algorithm, iterations, salt).map(|hash| {
+            let mut label = base32::encode_string_hex(&hash);
+            label.make_ascii_lowercase();
+            label
+        })
+    }
+
+    fn digest(&self, data: &[u8]) -> Result<Vec<u8>, Nsec3HashError> {
+        self.backend
+            .digest(DigestAlg::Sha1, data)
+            .map_err(|_| Nsec3HashError::UnsupportedAlgorithm)
+    }
+}
+
+/// Returns the hashed owner label of `name` for NSEC3.
+///
+/// This uses an [`Nsec3Hasher`] with the [`RingBackend`] and the default
+/// maximum number of iterations.
+#[cfg(feature = "ring")]
+pub fn nsec3_hash<N: ToDname + ?Sized>(
+    name: &N,
+    algorithm: Nsec3HashAlg,
+    iterations: u16,
+    salt: &[u8],
+) -> Result<String, Nsec3HashError> {
+    Nsec3Hasher::new().hash_label(name, algorithm, iterations, salt)
+}
+
+//============ Error Types ===================================================
+
+//------------ AlgorithmError ------------------------------------------------
+
+/// An error happened in a cryptographic operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AlgorithmError {
+    Unsupported,
+    BadSig,
+    InvalidData,
+}
+
+//--- Display and Error
+
+impl fmt::Display for AlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlgorithmError::Unsupported => {
+                f.write_str("unsupported algorithm")
+            }
+            AlgorithmError::BadSig => f.write_str("bad signature"),
+            AlgorithmError::InvalidData => f.write_str("invalid data"),
+        }
+    }
+}
+
+impl error::Error for AlgorithmError {}
+
+//------------ Nsec3HashError ------------------------------------------------
+
+/// An NSEC3 hash could not be calculated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Nsec3HashError {
+    /// The hash algorithm isn’t supported.
+    UnsupportedAlgorithm,
+
+    /// The number of iterations exceeds the configured maximum.
+    TooManyIterations,
+}
+
+//--- Display and Error
+
+impl fmt::Display for Nsec3HashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nsec3HashError::UnsupportedAlgorithm => {
+                f.write_str("unsupported NSEC3 hash algorithm")
+            }
+            Nsec3HashError::TooManyIterations => {
+                f.write_str("too many NSEC3 iterations")
+            }
+        }
+    }
+}
+
+impl error::Error for Nsec3HashError {}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ring_support() {
+        assert!(RingBackend.supports_algorithm(SecAlg::RsaSha256));
+        assert!(RingBackend.supports_algorithm(SecAlg::from_int(15)));
+        assert!(!RingBackend.supports_algorithm(SecAlg::Dsa));
+        assert!(RingBackend.supports_digest(DigestAlg::Sha256));
+        assert!(!RingBackend.supports_digest(DigestAlg::Gost));
+        assert_eq!(
+            RingBackend.digest(DigestAlg::Sha1, b"abc").unwrap(),
+            b"\xa9\x99\x3e\x36\x47\x06\x81\x6a\xba\x3e\
+              \x25\x71\x78\x50\xc2\x6c\x9c\xd0\xd8\x9d"
+        );
+        assert_eq!(
+            RingBackend.verify(SecAlg::Dsa, b"", b"", b""),
+            Err(AlgorithmError::Unsupported)
+        );
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn openssl_support() {
+        assert!(OpensslBackend.supports_algorithm(SecAlg::RsaSha256));
+        assert!(OpensslBackend.supports_algorithm(SecAlg::from_int(16)));
+        assert!(!OpensslBackend.supports_algorithm(SecAlg::Dsa));
+        assert!(!OpensslBackend.supports_digest(DigestAlg::Gost));
+        for alg in [DigestAlg::Sha1, DigestAlg::Sha256, DigestAlg::Sha384] {
+            assert_eq!(
+                OpensslBackend.digest(alg, b"abc").unwrap(),
+                RingBackend.digest(alg, b"abc").unwrap()
+            );
+        }
+        assert_eq!(
+            OpensslBackend.verify(SecAlg::Dsa, b"", b"", b""),
+            Err(AlgorithmError::Unsupported)
+        );
+
+        // Nsec3Hasher works with any backend.
+        let name = crate::base::name::Dname::root_vec();
+        assert_eq!(
+            Nsec3Hasher::with_backend(OpensslBackend)
+                .hash(&name, Nsec3HashAlg::Sha1, 1, b"ab")
+                .unwrap(),
+            Nsec3Hasher::new()
+                .hash(&name, Nsec3HashAlg::Sha1, 1, b"ab")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn nsec3_hashes() {
+        use crate::base::name::Dname;
+        use core::str::FromStr;
+
+        // The hashes from RFC 5155, appendix A.
+        let salt = b"\xaa\xbb\xcc\xdd";
+        for (name, hash) in [
+            ("example.", "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"),
+            ("a.example.", "35mthgpgcu1qg68fab165klnsnk3dpvl"),
+            ("ns1.Example.", "2t7b4g4vsa5smi47k61mv5bv1a22bojr"),
+            ("*.w.example.", "r53bq7cc2uvmubfu5ocmm6pers9tk9en"),
+        ] {
+            let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+            assert_eq!(
+                nsec3_hash(&name, Nsec3HashAlg::Sha1, 12, salt).unwrap(),
+                hash
+            );
+        }
+
+        let name = Dname::<Vec<u8>>::from_str("example.").unwrap();
+        assert_eq!(
+            nsec3_hash(&name, Nsec3HashAlg::Int(2), 0, b""),
+            Err(Nsec3HashError::UnsupportedAlgorithm)
+        );
+        assert_eq!(
+            nsec3_hash(&name, Nsec3HashAlg::Sha1, 151, b""),
+            Err(Nsec3HashError::TooManyIterations)
+        );
+        let mut hasher = Nsec3Hasher::new();
+        hasher.set_max_iterations(500);
+        assert!(hasher.hash(&name, Nsec3HashAlg::Sha1, 151, b"").is_ok());
+    }
+}
@@ -0,0 +1,396 @@
+//! The Frame Streams protocol.
+//!
+//! [Frame Streams] is a simple protocol for transporting a stream of
+//! opaque data frames. Each frame is preceded by its length as a 32 bit
+//! integer. Control frames, distinguished by a length of zero, mark the
+//! start and end of the stream and state the content type of the frames.
+//!
+//! In its unidirectional form, used for writing files, the stream starts
+//! with a START and ends with a STOP control frame. A [`Writer`] created
+//! via [`Writer::new`] writes such a stream and a [`Reader`] created via
+//! [`Reader::new`] reads it.
+//!
+//! When sending frames over a socket to a collector, the bidirectional
+//! form is used. Before the stream starts, the writer offers the content
+//! type via a READY frame and the reader accepts it with an ACCEPT frame.
+//! After the writer has sent STOP, the reader confirms with a FINISH
+//! frame. These handshakes are performed by [`Writer::connect`] and
+//! [`Writer::close`] on the writing side and by [`Reader::accept`] and
+//! [`Reader::finish`] on the reading side.
+//!
+//! [Frame Streams]: https://github.com/farsightsec/fstrm
+
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+//------------ Constants -----------------------------------------------------
+
+/// The maximum length of a control frame.
+const MAX_CONTROL_LEN: usize = 512;
+
+/// The control frame type ACCEPT.
+const CONTROL_ACCEPT: u32 = 0x01;
+
+/// The control frame type START.
+const CONTROL_START: u32 = 0x02;
+
+/// The control frame type STOP.
+const CONTROL_STOP: u32 = 0x03;
+
+/// The control frame type READY.
+const CONTROL_READY: u32 = 0x04;
+
+/// The control frame type FINISH.
+const CONTROL_FINISH: u32 = 0x05;
+
+/// The control field type for the content type.
+const FIELD_CONTENT_TYPE: u32 = 0x01;
+
+//------------ Writer --------------------------------------------------------
+
+/// Writes frames to a Frame Streams stream.
+///
+/// See the [module documentation][self] for details.
+#[derive(Debug)]
+pub struct Writer<W> {
+    /// The target of the stream.
+    target: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts a unidirectional stream of the given content type.
+    pub fn new(mut target: W, content_type: &[u8]) -> io::Result<Self> {
+        write_control(&mut target, CONTROL_START, Some(content_type))?;
+        Ok(Writer { target })
+    }
+
+    /// Writes a data frame.
+    ///
+    /// Empty frames can’t be written since they would be mistaken for a
+    /// control frame and are silently skipped.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let len = u32::try_from(data.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "frame too long")
+        })?;
+        self.target.write_all(&len.to_be_bytes())?;
+        self.target.write_all(data)
+    }
+
+    /// Flushes the target.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.target.flush()
+    }
+
+    /// Ends a unidirectional stream.
+    ///
+    /// Writes the STOP frame, flushes the target, and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        write_control(&mut self.target, CONTROL_STOP, None)?;
+        self.target.flush()?;
+        Ok(self.target)
+    }
+}
+
+impl<W: Read + Write> Writer<W> {
+    /// Starts a bidirectional stream of the given content type.
+    ///
+    /// Offers the content type to the reader and waits for it to accept
+    /// it before starting the stream. Such a stream has to be ended via
+    /// [`close`][Self::close].
+    pub fn connect(mut stream: W, content_type: &[u8]) -> io::Result<Self> {
+        write_control(&mut stream, CONTROL_READY, Some(content_type))?;
+        stream.flush()?;
+        let accept = read_control(&mut stream)?;
+        if accept.kind != CONTROL_ACCEPT {
+            return Err(unexpected_frame());
+        }
+        if !accept.content_types.iter().any(|item| item == content_type) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "content type not accepted",
+            ));
+        }
+        Self::new(stream, content_type)
+    }
+
+    /// Ends a bidirectional stream.
+    ///
+    /// Writes the STOP frame and waits for the reader to confirm it.
+    pub fn close(self) -> io::Result<W> {
+        let mut stream = self.finish()?;
+        if read_control(&mut stream)?.kind != CONTROL_FINISH {
+            return Err(unexpected_frame());
+        }
+        Ok(stream)
+    }
+}
+
+//------------ Reader --------------------------------------------------------
+
+/// Reads frames from a Frame Streams stream.
+///
+/// See the [module documentation][self] for details.
+#[derive(Debug)]
+pub struct Reader<R> {
+    /// The source of the stream.
+    source: R,
+
+    /// Whether the STOP frame has been read.
+    stopped: bool,
+}
+
+impl<R: Read> Reader<R> {
+    /// Starts reading a unidirectional stream of the given content type.
+    ///
+    /// Returns an error if the stream doesn’t start with a START frame or
+    /// states a different content type.
+    pub fn new(mut source: R, content_type: &[u8]) -> io::Result<Self> {
+        let start = read_control(&mut source)?;
+        if start.kind != CONTROL_START {
+            return Err(unexpected_frame());
+        }
+        match start.content_types.first() {
+            Some(item) if item != content_type => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected content type",
+                ))
+            }
+            _ => {}
+        }
+        Ok(Reader {
+            source,
+            stopped: false,
+        })
+    }
+
+    /// Reads the next data frame.
+    ///
+    /// Returns `Ok(None)` once the stream has ended with a STOP frame.
+    pub fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.stopped {
+            return Ok(None);
+        }
+        let len = read_u32(&mut self.source)?;
+        if len == 0 {
+            let control = read_control_frame(&mut self.source)?;
+            if control.kind != CONTROL_STOP {
+                return Err(unexpected_frame());
+            }
+            self.stopped = true;
+            return Ok(None);
+        }
+        let mut data = vec![0; len as usize];
+        self.source.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Returns the source of the stream.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read + Write> Reader<R> {
+    /// Accepts a bidirectional stream of the given content type.
+    ///
+    /// Waits for the writer to offer the content type, accepts it, and
+    /// starts reading the stream. Once [`read`][Self::read] has returned
+    /// `Ok(None)`, the stream should be ended via [`finish`][Self::finish].
+    pub fn accept(mut stream: R, content_type: &[u8]) -> io::Result<Self> {
+        let ready = read_control(&mut stream)?;
+        if ready.kind != CONTROL_READY {
+            return Err(unexpected_frame());
+        }
+        if !ready.content_types.iter().any(|item| item == content_type) {
+            write_control(&mut stream, CONTROL_ACCEPT, None)?;
+            stream.flush()?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "content type not offered",
+            ));
+        }
+        write_control(&mut stream, CONTROL_ACCEPT, Some(content_type))?;
+        stream.flush()?;
+        Self::new(stream, content_type)
+    }
+
+    /// Ends a bidirectional stream.
+    ///
+    /// Confirms the end of the stream to the writer and returns the
+    /// stream. Returns an error if the stream hasn’t been stopped yet.
+    pub fn finish(mut self) -> io::Result<R> {
+        if !self.stopped {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "stream not stopped",
+            ));
+        }
+        write_control(&mut self.source, CONTROL_FINISH, None)?;
+        self.source.flush()?;
+        Ok(self.source)
+    }
+}
+
+//------------ Control -------------------------------------------------------
+
+/// The content of a control frame.
+struct Control {
+    /// The control frame type.
+    kind: u32,
+
+    /// The content types listed in the frame.
+    content_types: Vec<Vec<u8>>,
+}
+
+//============ Helper Functions ==============================================
+
+fn write_control(
+    target: &mut impl Write,
+    kind: u32,
+    content_type: Option<&[u8]>,
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(20);
+    frame.extend_from_slice(&0u32.to_be_bytes());
+    frame.extend_from_slice(&0u32.to_be_bytes());
+    frame.extend_from_slice(&kind.to_be_bytes());
+    if let Some(content_type) = content_type {
+        if content_type.len() > MAX_CONTROL_LEN - 12 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "content type too long",
+            ));
+        }
+        frame.extend_from_slice(&FIELD_CONTENT_TYPE.to_be_bytes());
+        frame.extend_from_slice(&(content_type.len() as u32).to_be_bytes());
+        frame.extend_from_slice(content_type);
+    }
+    let len = (frame.len() - 8) as u32;
+    frame[4..8].copy_from_slice(&len.to_be_bytes());
+    target.write_all(&frame)
+}
+
+/// Reads a control frame including its escape sequence.
+fn read_control(source: &mut impl Read) -> io::Result<Control> {
+    if read_u32(source)? != 0 {
+        return Err(unexpected_frame());
+    }
+    read_control_frame(source)
+}
+
+/// Reads a control frame after its escape sequence.
+fn read_control_frame(source: &mut impl Read) -> io::Result<Control> {
+    let len = read_u32(source)? as usize;
+    if !(4..=MAX_CONTROL_LEN).contains(&len) {
+        return Err(bad_control());
+    }
+    let mut frame = [0; MAX_CONTROL_LEN];
+    let frame = &mut frame[..len];
+    source.read_exact(frame)?;
+    let (kind, mut fields) = split_u32(frame)?;
+    let mut content_types = Vec::new();
+    while !fields.is_empty() {
+        let (field, rest) = split_u32(fields)?;
+        let (len, rest) = split_u32(rest)?;
+        let len = len as usize;
+        if rest.len() < len {
+            return Err(bad_control());
+        }
+        if field == FIELD_CONTENT_TYPE {
+            content_types.push(rest[..len].into());
+        }
+        fields = &rest[len..];
+    }
+    Ok(Control {
+        kind,
+        content_types,
+    })
+}
+
+fn read_u32(source: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    source.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn split_u32(data: &[u8]) -> io::Result<(u32, &[u8])> {
+    if data.len() < 4 {
+        return Err(bad_control());
+    }
+    let (head, tail) = data.split_at(4);
+    Ok((
+        u32::from_be_bytes([head[0], head[1], head[2], head[3]]),
+        tail,
+    ))
+}
+
+fn bad_control() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed control frame")
+}
+
+fn unexpected_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "unexpected control frame")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unidirectional() {
+        let mut writer = Writer::new(Vec::new(), b"test").unwrap();
+        writer.write(b"one").unwrap();
+        writer.write(b"").unwrap();
+        writer.write(b"two").unwrap();
+        let stream = writer.finish().unwrap();
+        assert_eq!(
+            stream,
+            b"\0\0\0\0\0\0\0\x10\0\0\0\x02\0\0\0\x01\0\0\0\x04test\
+              \0\0\0\x03one\
+              \0\0\0\x03two\
+              \0\0\0\0\0\0\0\x04\0\0\0\x03"
+        );
+
+        let mut reader = Reader::new(stream.as_slice(), b"test").unwrap();
+        assert_eq!(reader.read().unwrap().unwrap(), b"one");
+        assert_eq!(reader.read().unwrap().unwrap(), b"two");
+        assert!(reader.read().unwrap().is_none());
+        assert!(reader.read().unwrap().is_none());
+
+        assert!(Reader::new(stream.as_slice(), b"other").is_err());
+        assert!(Reader::new(&stream[24..], b"test").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bidirectional() {
+        use std::os::unix::net::UnixStream;
+
+        let (left, right) = UnixStream::pair().unwrap();
+        let collector = std::thread::spawn(move || {
+            let mut reader = Reader::accept(right, b"test").unwrap();
+            let mut frames = Vec::new();
+            while let Some(frame) = reader.read().unwrap() {
+                frames.push(frame);
+            }
+            reader.finish().unwrap();
+            frames
+        });
+        let mut writer = Writer::connect(left, b"test").unwrap();
+        writer.write(b"one").unwrap();
+        writer.write(b"two").unwrap();
+        writer.close().unwrap();
+        assert_eq!(collector.join().unwrap(), [b"one", b"two"]);
+
+        let (left, right) = UnixStream::pair().unwrap();
+        let collector =
+            std::thread::spawn(move || Reader::accept(right, b"test"));
+        assert!(Writer::connect(left, b"other").is_err());
+        assert!(collector.join().unwrap().is_err());
+    }
+}
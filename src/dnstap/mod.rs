@@ -0,0 +1,792 @@
+//! Logging DNS traffic in the dnstap format.
+//!
+//! **This module is experimental and likely to change significantly.**
+//!
+//! [dnstap] is a format for logging DNS messages together with
+//! information on who exchanged them, when, and over which transport. It
+//! is supported by a number of name servers and the tools analysing their
+//! logs. The format is defined as a Protocol Buffers schema. This module
+//! implements the encoding of that schema directly, so no protobuf
+//! library is needed.
+//!
+//! Each log entry is a [`Dnstap`] value wrapping a [`Message`]. It is
+//! encoded via [`Dnstap::to_vec`] and decoded via [`Dnstap::from_slice`].
+//!
+//! Log entries are transported in the frames of the
+//! [Frame Streams][frame] protocol, either written to a file or sent
+//! over a socket to a collector. The content type of these streams is
+//! [`CONTENT_TYPE`].
+//!
+//! # Example
+//!
+//! Logging a query received by a server into a file:
+//!
+//! ```no_run
+//! use bytes::Bytes;
+//! use domain::dnstap::{
+//!     Dnstap, Message, MessageType, SocketProtocol, CONTENT_TYPE,
+//! };
+//! use domain::dnstap::frame::Writer;
+//! use std::fs::File;
+//! use std::time::SystemTime;
+//!
+//! # fn log(request: Bytes) -> std::io::Result<()> {
+//! let file = File::create("dnstap.log")?;
+//! let mut writer = Writer::new(file, CONTENT_TYPE)?;
+//! let mut message = Message::new(MessageType::AuthQuery);
+//! message.socket_protocol = Some(SocketProtocol::Udp);
+//! message.query_address = Some("192.0.2.1:4711".parse().unwrap());
+//! message.response_address = Some("198.51.100.53:53".parse().unwrap());
+//! message.query_time = Some(SystemTime::now());
+//! message.query_message = Some(request);
+//! writer.write(&Dnstap::new(message).to_vec())?;
+//! writer.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [dnstap]: https://dnstap.info/
+#![cfg(feature = "dnstap")]
+#![cfg_attr(docsrs, doc(cfg(feature = "dnstap")))]
+
+use crate::base::name::Dname;
+use bytes::Bytes;
+use core::fmt;
+use std::error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+//------------ Sub-modules ---------------------------------------------------
+
+pub mod frame;
+
+//------------ Constants -----------------------------------------------------
+
+/// The Frame Streams content type of dnstap.
+pub const CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+
+//------------ Dnstap --------------------------------------------------------
+
+/// A dnstap log entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dnstap {
+    /// The identity of the server that created the entry.
+    pub identity: Option<Bytes>,
+
+    /// The version of the server that created the entry.
+    pub version: Option<Bytes>,
+
+    /// Additional, implementation specific data.
+    pub extra: Option<Bytes>,
+
+    /// The logged message.
+    pub message: Message,
+}
+
+impl Dnstap {
+    /// Creates a new entry for a message.
+    pub fn new(message: Message) -> Self {
+        Dnstap {
+            identity: None,
+            version: None,
+            extra: None,
+            message,
+        }
+    }
+
+    /// Creates a new entry with identity and version.
+    pub fn with_identity(
+        message: Message,
+        identity: impl Into<Bytes>,
+        version: impl Into<Bytes>,
+    ) -> Self {
+        Dnstap {
+            identity: Some(identity.into()),
+            version: Some(version.into()),
+            extra: None,
+            message,
+        }
+    }
+
+    /// Decodes an entry from its protobuf encoding.
+    pub fn from_slice(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut res = Dnstap::new(Message::new(MessageType::ToolQuery));
+        let mut kind = None;
+        let mut message = None;
+        for field in Fields::new(data) {
+            match field? {
+                (1, Value::Bytes(data)) => {
+                    res.identity = Some(Bytes::copy_from_slice(data))
+                }
+                (2, Value::Bytes(data)) => {
+                    res.version = Some(Bytes::copy_from_slice(data))
+                }
+                (3, Value::Bytes(data)) => {
+                    res.extra = Some(Bytes::copy_from_slice(data))
+                }
+                (14, Value::Bytes(data)) => {
+                    message = Some(Message::from_slice(data)?)
+                }
+                (15, Value::Varint(value)) => kind = Some(value),
+                (1..=3 | 14 | 15, _) => {
+                    return Err(DecodeError::BadValue("Dnstap"))
+                }
+                _ => {}
+            }
+        }
+        match kind {
+            Some(DNSTAP_MESSAGE) => {}
+            Some(_) => return Err(DecodeError::BadValue("Dnstap.type")),
+            None => return Err(DecodeError::MissingField("Dnstap.type")),
+        }
+        res.message =
+            message.ok_or(DecodeError::MissingField("Dnstap.message"))?;
+        Ok(res)
+    }
+
+    /// Returns the protobuf encoding of the entry.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        if let Some(identity) = self.identity.as_ref() {
+            put_bytes(&mut res, 1, identity);
+        }
+        if let Some(version) = self.version.as_ref() {
+            put_bytes(&mut res, 2, version);
+        }
+        if let Some(extra) = self.extra.as_ref() {
+            put_bytes(&mut res, 3, extra);
+        }
+        put_bytes(&mut res, 14, &self.message.to_vec());
+        put_varint_field(&mut res, 15, DNSTAP_MESSAGE);
+        res
+    }
+}
+
+/// The value of `Dnstap.type` for entries containing a message.
+///
+/// This is the only type currently defined.
+const DNSTAP_MESSAGE: u64 = 1;
+
+//------------ Message -------------------------------------------------------
+
+/// A logged DNS message.
+///
+/// Which of the fields are present depends on the [`MessageType`]. For a
+/// query, the response fields are typically empty and vice versa. For
+/// responses, the query time and message can be included to allow
+/// calculating the response time.
+///
+/// The _query address_ is always the address of the party that sent the
+/// query, the _response address_ that of the party that responded, no
+/// matter which of the two messages is logged.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Message {
+    /// The type of the message.
+    pub message_type: MessageType,
+
+    /// The transport protocol the message was exchanged over.
+    pub socket_protocol: Option<SocketProtocol>,
+
+    /// The address the query was sent from.
+    pub query_address: Option<SocketAddr>,
+
+    /// The address the query was sent to.
+    pub response_address: Option<SocketAddr>,
+
+    /// The time the query was sent or received.
+    pub query_time: Option<SystemTime>,
+
+    /// The query message in wire format.
+    pub query_message: Option<Bytes>,
+
+    /// The zone the query was sent to by a resolver.
+    pub query_zone: Option<Dname<Bytes>>,
+
+    /// The time the response was sent or received.
+    pub response_time: Option<SystemTime>,
+
+    /// The response message in wire format.
+    pub response_message: Option<Bytes>,
+}
+
+impl Message {
+    /// Creates a new message of the given type with all fields empty.
+    pub fn new(message_type: MessageType) -> Self {
+        Message {
+            message_type,
+            socket_protocol: None,
+            query_address: None,
+            response_address: None,
+            query_time: None,
+            query_message: None,
+            query_zone: None,
+            response_time: None,
+            response_message: None,
+        }
+    }
+
+    /// Decodes a message from its protobuf encoding.
+    pub fn from_slice(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut res = Message::new(MessageType::ToolQuery);
+        let mut kind = None;
+        let mut family = None;
+        let (mut query_addr, mut response_addr) = (None, None);
+        let (mut query_port, mut response_port) = (0, 0);
+        let (mut query_sec, mut query_nsec) = (None, 0);
+        let (mut response_sec, mut response_nsec) = (None, 0);
+        for field in Fields::new(data) {
+            match field? {
+                (1, Value::Varint(value)) => {
+                    kind = Some(
+                        MessageType::from_int(value)
+                            .ok_or(DecodeError::BadValue("Message.type"))?,
+                    )
+                }
+                (2, Value::Varint(value)) => family = Some(value),
+                (3, Value::Varint(value)) => {
+                    res.socket_protocol = SocketProtocol::from_int(value)
+                }
+                (4, Value::Bytes(data)) => query_addr = Some(data),
+                (5, Value::Bytes(data)) => response_addr = Some(data),
+                (6, Value::Varint(value)) => query_port = port(value)?,
+                (7, Value::Varint(value)) => response_port = port(value)?,
+                (8, Value::Varint(value)) => query_sec = Some(value),
+                (9, Value::Fixed32(value)) => query_nsec = value,
+                (10, Value::Bytes(data)) => {
+                    res.query_message = Some(Bytes::copy_from_slice(data))
+                }
+                (11, Value::Bytes(data)) => {
+                    res.query_zone = Some(
+                        Dname::from_octets(Bytes::copy_from_slice(data))
+                            .map_err(|_| {
+                                DecodeError::BadValue("Message.query_zone")
+                            })?,
+                    )
+                }
+                (12, Value::Varint(value)) => response_sec = Some(value),
+                (13, Value::Fixed32(value)) => response_nsec = value,
+                (14, Value::Bytes(data)) => {
+                    res.response_message = Some(Bytes::copy_from_slice(data))
+                }
+                (1..=14, _) => return Err(DecodeError::BadValue("Message")),
+                _ => {}
+            }
+        }
+        res.message_type =
+            kind.ok_or(DecodeError::MissingField("Message.type"))?;
+        res.query_address = query_addr
+            .map(|addr| socket_addr(family, addr, query_port))
+            .transpose()?;
+        res.response_address = response_addr
+            .map(|addr| socket_addr(family, addr, response_port))
+            .transpose()?;
+        res.query_time = query_sec.map(|sec| time(sec, query_nsec));
+        res.response_time = response_sec.map(|sec| time(sec, response_nsec));
+        Ok(res)
+    }
+
+    /// Returns the protobuf encoding of the message.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        put_varint_field(&mut res, 1, self.message_type.to_int());
+        let family = self.query_address.or(self.response_address).map(
+            |addr| match addr {
+                SocketAddr::V4(_) => SOCKET_FAMILY_INET,
+                SocketAddr::V6(_) => SOCKET_FAMILY_INET6,
+            },
+        );
+        if let Some(family) = family {
+            put_varint_field(&mut res, 2, family);
+        }
+        if let Some(protocol) = self.socket_protocol {
+            put_varint_field(&mut res, 3, protocol.to_int());
+        }
+        if let Some(addr) = self.query_address {
+            put_addr(&mut res, 4, addr.ip());
+        }
+        if let Some(addr) = self.response_address {
+            put_addr(&mut res, 5, addr.ip());
+        }
+        if let Some(addr) = self.query_address {
+            put_varint_field(&mut res, 6, addr.port().into());
+        }
+        if let Some(addr) = self.response_address {
+            put_varint_field(&mut res, 7, addr.port().into());
+        }
+        if let Some(time) = self.query_time.and_then(since_epoch) {
+            put_varint_field(&mut res, 8, time.as_secs());
+            put_fixed32_field(&mut res, 9, time.subsec_nanos());
+        }
+        if let Some(message) = self.query_message.as_ref() {
+            put_bytes(&mut res, 10, message);
+        }
+        if let Some(zone) = self.query_zone.as_ref() {
+            put_bytes(&mut res, 11, zone.as_slice());
+        }
+        if let Some(time) = self.response_time.and_then(since_epoch) {
+            put_varint_field(&mut res, 12, time.as_secs());
+            put_fixed32_field(&mut res, 13, time.subsec_nanos());
+        }
+        if let Some(message) = self.response_message.as_ref() {
+            put_bytes(&mut res, 14, message);
+        }
+        res
+    }
+}
+
+/// The value of `SocketFamily` for IPv4.
+const SOCKET_FAMILY_INET: u64 = 1;
+
+/// The value of `SocketFamily` for IPv6.
+const SOCKET_FAMILY_INET6: u64 = 2;
+
+//------------ MessageType ---------------------------------------------------
+
+/// The type of a logged message.
+///
+/// The type describes the role of the party logging the message and
+/// whether it is a query or a response.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MessageType {
+    /// A query received by an authoritative server.
+    AuthQuery,
+
+    /// A response sent by an authoritative server.
+    AuthResponse,
+
+    /// A query sent by a recursive resolver to an authoritative server.
+    ResolverQuery,
+
+    /// A response received by a recursive resolver.
+    ResolverResponse,
+
+    /// A query received by a recursive resolver from a client.
+    ClientQuery,
+
+    /// A response sent by a recursive resolver to a client.
+    ClientResponse,
+
+    /// A query sent by a forwarder to an upstream server.
+    ForwarderQuery,
+
+    /// A response received by a forwarder from an upstream server.
+    ForwarderResponse,
+
+    /// A query sent by a stub resolver.
+    StubQuery,
+
+    /// A response received by a stub resolver.
+    StubResponse,
+
+    /// A query sent by a tool.
+    ToolQuery,
+
+    /// A response received by a tool.
+    ToolResponse,
+
+    /// A dynamic update received by an authoritative server.
+    UpdateQuery,
+
+    /// A response to a dynamic update sent by an authoritative server.
+    UpdateResponse,
+}
+
+impl MessageType {
+    /// Returns the type for a value of the protobuf enum.
+    pub fn from_int(value: u64) -> Option<Self> {
+        use MessageType::*;
+
+        Some(match value {
+            1 => AuthQuery,
+            2 => AuthResponse,
+            3 => ResolverQuery,
+            4 => ResolverResponse,
+            5 => ClientQuery,
+            6 => ClientResponse,
+            7 => ForwarderQuery,
+            8 => ForwarderResponse,
+            9 => StubQuery,
+            10 => StubResponse,
+            11 => ToolQuery,
+            12 => ToolResponse,
+            13 => UpdateQuery,
+            14 => UpdateResponse,
+            _ => return None,
+        })
+    }
+
+    /// Returns the value of the protobuf enum for the type.
+    pub fn to_int(self) -> u64 {
+        use MessageType::*;
+
+        match self {
+            AuthQuery => 1,
+            AuthResponse => 2,
+            ResolverQuery => 3,
+            ResolverResponse => 4,
+            ClientQuery => 5,
+            ClientResponse => 6,
+            ForwarderQuery => 7,
+            ForwarderResponse => 8,
+            StubQuery => 9,
+            StubResponse => 10,
+            ToolQuery => 11,
+            ToolResponse => 12,
+            UpdateQuery => 13,
+            UpdateResponse => 14,
+        }
+    }
+
+    /// Returns whether the type is for a query.
+    pub fn is_query(self) -> bool {
+        self.to_int() % 2 == 1
+    }
+}
+
+//------------ SocketProtocol ------------------------------------------------
+
+/// The transport protocol a message was exchanged over.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SocketProtocol {
+    /// Plain UDP.
+    Udp,
+
+    /// Plain TCP.
+    Tcp,
+
+    /// DNS over TLS.
+    Dot,
+
+    /// DNS over HTTPS.
+    Doh,
+
+    /// DNSCrypt over UDP.
+    DnscryptUdp,
+
+    /// DNSCrypt over TCP.
+    DnscryptTcp,
+
+    /// DNS over QUIC.
+    Doq,
+}
+
+impl SocketProtocol {
+    /// Returns the protocol for a value of the protobuf enum.
+    pub fn from_int(value: u64) -> Option<Self> {
+        use SocketProtocol::*;
+
+        Some(match value {
+            1 => Udp,
+            2 => Tcp,
+            3 => Dot,
+            4 => Doh,
+            5 => DnscryptUdp,
+            6 => DnscryptTcp,
+            7 => Doq,
+            _ => return None,
+        })
+    }
+
+    /// Returns the value of the protobuf enum for the protocol.
+    pub fn to_int(self) -> u64 {
+        use SocketProtocol::*;
+
+        match self {
+            Udp => 1,
+            Tcp => 2,
+            Dot => 3,
+            Doh => 4,
+            DnscryptUdp => 5,
+            DnscryptTcp => 6,
+            Doq => 7,
+        }
+    }
+}
+
+//------------ Fields --------------------------------------------------------
+
+/// An iterator over the fields of an encoded protobuf message.
+struct Fields<'a> {
+    /// The remaining data.
+    data: &'a [u8],
+}
+
+/// The value of a protobuf field.
+enum Value<'a> {
+    /// A varint value.
+    Varint(u64),
+
+    /// A 64 bit fixed-size value.
+    Fixed64,
+
+    /// A length-delimited value.
+    Bytes(&'a [u8]),
+
+    /// A 32 bit fixed-size value.
+    Fixed32(u32),
+}
+
+impl<'a> Fields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Fields { data }
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut res = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&octet, rest) =
+                self.data.split_first().ok_or(DecodeError::ShortInput)?;
+            self.data = rest;
+            res |= u64::from(octet & 0x7F) << shift;
+            if octet & 0x80 == 0 {
+                return Ok(res);
+            }
+        }
+        Err(DecodeError::BadVarint)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.data.len() < len {
+            return Err(DecodeError::ShortInput);
+        }
+        let (res, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(res)
+    }
+
+    fn field(&mut self) -> Result<(u64, Value<'a>), DecodeError> {
+        let key = self.varint()?;
+        let value = match key & 0x07 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed64
+            }
+            2 => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| DecodeError::ShortInput)?;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                let data = self.take(4)?;
+                Value::Fixed32(u32::from_le_bytes([
+                    data[0], data[1], data[2], data[3],
+                ]))
+            }
+            wire_type => return Err(DecodeError::BadWireType(wire_type)),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let res = self.field();
+        if res.is_err() {
+            self.data = &[];
+        }
+        Some(res)
+    }
+}
+
+//============ Helper Functions ==============================================
+
+fn put_varint(target: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        target.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.push(value as u8);
+}
+
+fn put_varint_field(target: &mut Vec<u8>, field: u64, value: u64) {
+    put_varint(target, field << 3);
+    put_varint(target, value);
+}
+
+fn put_fixed32_field(target: &mut Vec<u8>, field: u64, value: u32) {
+    put_varint(target, (field << 3) | 5);
+    target.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes(target: &mut Vec<u8>, field: u64, data: &[u8]) {
+    put_varint(target, (field << 3) | 2);
+    put_varint(target, data.len() as u64);
+    target.extend_from_slice(data);
+}
+
+fn put_addr(target: &mut Vec<u8>, field: u64, addr: IpAddr) {
+    match addr {
+        IpAddr::V4(addr) => put_bytes(target, field, &addr.octets()),
+        IpAddr::V6(addr) => put_bytes(target, field, &addr.octets()),
+    }
+}
+
+/// Returns the duration since the Unix epoch of a time.
+fn since_epoch(time: SystemTime) -> Option<Duration> {
+    time.duration_since(UNIX_EPOCH).ok()
+}
+
+/// Returns the time for seconds and nanoseconds since the Unix epoch.
+fn time(sec: u64, nsec: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::new(sec, nsec)
+}
+
+fn port(value: u64) -> Result<u16, DecodeError> {
+    u16::try_from(value).map_err(|_| DecodeError::BadValue("port"))
+}
+
+/// Creates a socket address from the encoded address and port.
+///
+/// The address family is taken from the length of the address. If the
+/// message states the family, it has to match.
+fn socket_addr(
+    family: Option<u64>,
+    addr: &[u8],
+    port: u16,
+) -> Result<SocketAddr, DecodeError> {
+    let addr = match (family, addr.len()) {
+        (None | Some(SOCKET_FAMILY_INET), 4) => {
+            IpAddr::V4(Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]))
+        }
+        (None | Some(SOCKET_FAMILY_INET6), 16) => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(addr);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return Err(DecodeError::BadValue("address")),
+    };
+    Ok(SocketAddr::new(addr, port))
+}
+
+//============ Error Types ===================================================
+
+//------------ DecodeError ---------------------------------------------------
+
+/// A dnstap entry couldn’t be decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The data ended in the middle of a field.
+    ShortInput,
+
+    /// A varint was longer than ten octets.
+    BadVarint,
+
+    /// A field used an unknown wire type.
+    BadWireType(u64),
+
+    /// A field had an invalid value.
+    BadValue(&'static str),
+
+    /// A required field was missing.
+    MissingField(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::ShortInput => f.write_str("short input"),
+            DecodeError::BadVarint => f.write_str("invalid varint"),
+            DecodeError::BadWireType(wire_type) => {
+                write!(f, "unknown wire type {}", wire_type)
+            }
+            DecodeError::BadValue(field) => {
+                write!(f, "invalid value in {}", field)
+            }
+            DecodeError::MissingField(field) => {
+                write!(f, "missing field {}", field)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    fn message() -> Message {
+        let mut message = Message::new(MessageType::ResolverResponse);
+        message.socket_protocol = Some(SocketProtocol::Tcp);
+        message.query_address = Some("[2001:db8::1]:4711".parse().unwrap());
+        message.response_address = Some("[2001:db8::53]:53".parse().unwrap());
+        message.query_time = Some(time(1_700_000_000, 123_456_789));
+        message.query_zone = Some(Dname::from_str("example.com").unwrap());
+        message.response_time = Some(time(1_700_000_001, 5));
+        message.response_message = Some(Bytes::from_static(b"\x12\x34"));
+        message
+    }
+
+    #[test]
+    fn round_trip() {
+        let dnstap = Dnstap::with_identity(message(), "ns1", "1.0");
+        let encoded = dnstap.to_vec();
+        assert_eq!(Dnstap::from_slice(&encoded).unwrap(), dnstap);
+
+        let mut message = Message::new(MessageType::AuthQuery);
+        message.query_address = Some("192.0.2.1:53".parse().unwrap());
+        let dnstap = Dnstap::new(message);
+        assert_eq!(Dnstap::from_slice(&dnstap.to_vec()).unwrap(), dnstap);
+    }
+
+    #[test]
+    fn encoding() {
+        let mut message = Message::new(MessageType::ClientQuery);
+        message.socket_protocol = Some(SocketProtocol::Udp);
+        message.query_address = Some("192.0.2.1:300".parse().unwrap());
+        message.query_message = Some(Bytes::from_static(b"abc"));
+        assert_eq!(
+            Dnstap::new(message).to_vec(),
+            b"\x72\x14\
+              \x08\x05\
+              \x10\x01\
+              \x18\x01\
+              \x22\x04\xc0\x00\x02\x01\
+              \x30\xac\x02\
+              \x52\x03abc\
+              \x78\x01"
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            Dnstap::from_slice(b"\x78\x01"),
+            Err(DecodeError::MissingField("Dnstap.message"))
+        );
+        assert_eq!(
+            Dnstap::from_slice(b"\x72\x02\x08\x01"),
+            Err(DecodeError::MissingField("Dnstap.type"))
+        );
+        assert_eq!(
+            Dnstap::from_slice(b"\x72\x00\x78\x01"),
+            Err(DecodeError::MissingField("Message.type"))
+        );
+        assert_eq!(
+            Dnstap::from_slice(b"\x72\x02\x08\x0F\x78\x01"),
+            Err(DecodeError::BadValue("Message.type"))
+        );
+        assert_eq!(
+            Dnstap::from_slice(b"\x72\x05"),
+            Err(DecodeError::ShortInput)
+        );
+        assert_eq!(
+            Message::from_slice(b"\x08\x01\x22\x03abc"),
+            Err(DecodeError::BadValue("address"))
+        );
+
+        // Unknown fields are skipped.
+        let message = Message::from_slice(b"\x08\x01\x78\x05\x85\x01abcd");
+        assert_eq!(message.unwrap().message_type, MessageType::AuthQuery);
+    }
+}
@@ -0,0 +1,421 @@
+//! Extracting DNS messages from captured traffic.
+//!
+//! **This module is experimental and likely to change significantly.**
+//!
+//! Tools analysing DNS traffic typically receive the payload of UDP
+//! datagrams and TCP segments from a packet capture library or a PCAP
+//! file. This module turns these payloads into DNS messages.
+//!
+//! For UDP, each datagram contains exactly one message. For TCP, messages
+//! are preceded by a two octet length and can be split across several
+//! segments or several messages can be contained in one segment. The
+//! [`TcpFramer`] reassembles the messages of a single TCP stream.
+//!
+//! The [`Dump`] type keeps track of all TCP streams by their source and
+//! destination addresses and collects the messages of both transports
+//! together with their timestamp and addresses as [`Captured`] values.
+//!
+//! The module expects the payloads of TCP segments to be provided in
+//! order and without duplicates, i.e., any retransmissions and out of
+//! order segments have already been dealt with by the capture layer.
+#![cfg(feature = "dump")]
+#![cfg_attr(docsrs, doc(cfg(feature = "dump")))]
+
+use crate::base::message::{Message, ShortMessage};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::SystemTime;
+use std::vec::Vec;
+
+//------------ Dump ----------------------------------------------------------
+
+/// A collector of DNS messages from captured traffic.
+///
+/// Payloads are added through [`push_udp`][Self::push_udp] and
+/// [`push_tcp`][Self::push_tcp]. All complete messages can then be taken
+/// out in the order they were completed via [`pop`][Self::pop] or by
+/// iterating over a mutable reference to the dump.
+///
+/// TCP streams are identified by their source and destination addresses.
+/// The two directions of a connection are treated as separate streams. A
+/// stream should be removed via [`close_tcp`][Self::close_tcp] when the
+/// connection is closed. Streams that didn’t see any traffic for a while
+/// can be removed via [`expire_tcp`][Self::expire_tcp].
+///
+/// Payloads that are too short to contain a DNS message are discarded.
+/// Their number is available via [`discarded`][Self::discarded].
+#[derive(Clone, Debug, Default)]
+pub struct Dump {
+    /// The state of all currently known TCP streams.
+    streams: HashMap<Flow, Stream>,
+
+    /// The complete messages not yet taken out.
+    messages: VecDeque<Captured>,
+
+    /// The number of discarded payloads.
+    discarded: u64,
+}
+
+impl Dump {
+    /// Creates a new, empty dump.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the payload of a UDP datagram.
+    pub fn push_udp(
+        &mut self,
+        timestamp: SystemTime,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) {
+        match Message::from_octets(payload.into()) {
+            Ok(message) => self.messages.push_back(Captured {
+                timestamp,
+                source,
+                destination,
+                transport: Transport::Udp,
+                message,
+            }),
+            Err(_) => self.discarded += 1,
+        }
+    }
+
+    /// Adds the payload of a TCP segment.
+    ///
+    /// The payload must be the next segment of the stream from `source`
+    /// to `destination`. If this is the first segment of the stream, a new
+    /// stream is started.
+    pub fn push_tcp(
+        &mut self,
+        timestamp: SystemTime,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) {
+        let stream = self
+            .streams
+            .entry(Flow {
+                source,
+                destination,
+            })
+            .or_default();
+        stream.last_seen = timestamp;
+        stream.framer.push(timestamp, payload);
+        while let Some(res) = stream.framer.pop() {
+            match res {
+                (timestamp, Ok(message)) => {
+                    self.messages.push_back(Captured {
+                        timestamp,
+                        source,
+                        destination,
+                        transport: Transport::Tcp,
+                        message,
+                    })
+                }
+                (_, Err(_)) => self.discarded += 1,
+            }
+        }
+    }
+
+    /// Removes the TCP stream from `source` to `destination`.
+    ///
+    /// Returns whether the stream contained an incomplete message which is
+    /// dropped.
+    pub fn close_tcp(
+        &mut self,
+        source: SocketAddr,
+        destination: SocketAddr,
+    ) -> bool {
+        match self.streams.remove(&Flow {
+            source,
+            destination,
+        }) {
+            Some(stream) => stream.framer.is_partial(),
+            None => false,
+        }
+    }
+
+    /// Removes all TCP streams that haven’t seen traffic since `before`.
+    ///
+    /// Any incomplete messages of these streams are dropped.
+    pub fn expire_tcp(&mut self, before: SystemTime) {
+        self.streams.retain(|_, stream| stream.last_seen >= before)
+    }
+
+    /// Returns the number of TCP streams currently tracked.
+    pub fn tcp_streams(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns the number of payloads discarded so far.
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Takes out the next complete message.
+    pub fn pop(&mut self) -> Option<Captured> {
+        self.messages.pop_front()
+    }
+}
+
+//--- Iterator
+
+impl Iterator for &mut Dump {
+    type Item = Captured;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop()
+    }
+}
+
+//------------ Flow ----------------------------------------------------------
+
+/// The key identifying a TCP stream.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Flow {
+    source: SocketAddr,
+    destination: SocketAddr,
+}
+
+//------------ Stream --------------------------------------------------------
+
+/// The state of a TCP stream.
+#[derive(Clone, Debug)]
+struct Stream {
+    /// The reassembly state.
+    framer: TcpFramer,
+
+    /// The timestamp of the last segment.
+    last_seen: SystemTime,
+}
+
+impl Default for Stream {
+    fn default() -> Self {
+        Stream {
+            framer: TcpFramer::new(),
+            last_seen: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+//------------ Captured ------------------------------------------------------
+
+/// A DNS message extracted from captured traffic.
+#[derive(Clone, Debug)]
+pub struct Captured {
+    /// The time the message was seen.
+    ///
+    /// For messages received over TCP, this is the timestamp of the
+    /// segment that contained the start of the message.
+    pub timestamp: SystemTime,
+
+    /// The address the message was sent from.
+    pub source: SocketAddr,
+
+    /// The address the message was sent to.
+    pub destination: SocketAddr,
+
+    /// The transport the message was received over.
+    pub transport: Transport,
+
+    /// The message itself.
+    pub message: Message<Vec<u8>>,
+}
+
+//------------ Transport -----------------------------------------------------
+
+/// The transport protocol a message was received over.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Transport {
+    /// The message was received in a UDP datagram.
+    Udp,
+
+    /// The message was received over a TCP stream.
+    Tcp,
+}
+
+//------------ TcpFramer -----------------------------------------------------
+
+/// Reassembles the DNS messages of a single TCP stream.
+///
+/// The payloads of the stream’s segments are added in order via
+/// [`push`][Self::push]. Complete messages are then available via
+/// [`pop`][Self::pop] together with the timestamp of the segment that
+/// contained their first octet.
+///
+/// Since messages are taken out once they are complete, the framer never
+/// buffers more data than the largest possible message.
+#[derive(Clone, Debug, Default)]
+pub struct TcpFramer {
+    /// The data of the stream not yet taken out.
+    buf: Vec<u8>,
+
+    /// The timestamps of the segments currently in the buffer.
+    ///
+    /// Each element contains the position in `buf` where a segment’s data
+    /// starts and its timestamp.
+    timestamps: VecDeque<(usize, SystemTime)>,
+}
+
+/// A message taken out of a [`TcpFramer`].
+type FramedMessage = Result<Message<Vec<u8>>, ShortMessage>;
+
+impl TcpFramer {
+    /// Creates a new framer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the payload of the next segment of the stream.
+    pub fn push(&mut self, timestamp: SystemTime, payload: &[u8]) {
+        if payload.is_empty() {
+            return;
+        }
+        self.timestamps.push_back((self.buf.len(), timestamp));
+        self.buf.extend_from_slice(payload);
+    }
+
+    /// Takes out the next complete message.
+    ///
+    /// Returns `None` if no complete message is available. Otherwise
+    /// returns the timestamp of the message and the message itself or an
+    /// error if the frame was too short to be a DNS message.
+    pub fn pop(&mut self) -> Option<(SystemTime, FramedMessage)> {
+        if self.buf.len() < 2 {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes([self.buf[0], self.buf[1]]));
+        if self.buf.len() < len + 2 {
+            return None;
+        }
+        let timestamp = self.timestamps.front()?.1;
+        let frame = self.buf[2..len + 2].to_vec();
+        self.buf.drain(..len + 2);
+
+        // Drop the timestamps of all segments that now lie completely
+        // before the start of the buffer and move the others.
+        while let Some(&(start, _)) = self.timestamps.get(1) {
+            if start > len + 2 {
+                break;
+            }
+            self.timestamps.pop_front();
+        }
+        for (start, _) in &mut self.timestamps {
+            *start = start.saturating_sub(len + 2);
+        }
+        if self.buf.is_empty() {
+            self.timestamps.clear();
+        }
+
+        Some((timestamp, Message::from_octets(frame)))
+    }
+
+    /// Returns whether the framer holds the beginning of a message.
+    pub fn is_partial(&self) -> bool {
+        !self.buf.is_empty()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use core::str::FromStr;
+    use std::time::Duration;
+
+    fn message(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish()
+    }
+
+    fn framed(id: u16) -> Vec<u8> {
+        let msg = message(id);
+        let mut res = (msg.len() as u16).to_be_bytes().to_vec();
+        res.extend_from_slice(&msg);
+        res
+    }
+
+    fn time(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, 1], port))
+    }
+
+    #[test]
+    fn tcp_framer() {
+        let mut framer = TcpFramer::new();
+        let mut stream = framed(1);
+        stream.extend_from_slice(&framed(2));
+        stream.extend_from_slice(&framed(3));
+
+        // Split the stream right after the first length and in the middle
+        // of the second message.
+        let (first, rest) = stream.split_at(1);
+        let (second, third) = rest.split_at(framed(1).len() + 10);
+        framer.push(time(1), first);
+        assert!(framer.pop().is_none());
+        assert!(framer.is_partial());
+        framer.push(time(2), second);
+        let (ts, msg) = framer.pop().unwrap();
+        let msg = msg.unwrap();
+        assert_eq!((ts, msg.header().id()), (time(1), 1));
+        assert!(framer.pop().is_none());
+        framer.push(time(3), third);
+        let (ts, msg) = framer.pop().unwrap();
+        let msg = msg.unwrap();
+        assert_eq!((ts, msg.header().id()), (time(2), 2));
+        let (ts, msg) = framer.pop().unwrap();
+        let msg = msg.unwrap();
+        assert_eq!((ts, msg.header().id()), (time(3), 3));
+        assert!(framer.pop().is_none());
+        assert!(!framer.is_partial());
+
+        // A frame that is too short.
+        framer.push(time(4), b"\0\x02ab");
+        assert!(framer.pop().unwrap().1.is_err());
+        assert!(!framer.is_partial());
+    }
+
+    #[test]
+    fn dump() {
+        let mut dump = Dump::new();
+        dump.push_udp(time(1), addr(1), addr(53), &message(1));
+        dump.push_udp(time(1), addr(1), addr(53), b"short");
+
+        let stream = framed(2);
+        let (first, second) = stream.split_at(5);
+        dump.push_tcp(time(2), addr(2), addr(53), first);
+        dump.push_tcp(time(2), addr(3), addr(53), &framed(3)[..3]);
+        dump.push_tcp(time(3), addr(2), addr(53), second);
+        assert_eq!(dump.tcp_streams(), 2);
+
+        let captured: Vec<_> = dump.collect();
+        assert_eq!(captured.len(), 2);
+        assert_eq!(captured[0].transport, Transport::Udp);
+        assert_eq!(captured[0].message.header().id(), 1);
+        assert_eq!(captured[1].transport, Transport::Tcp);
+        assert_eq!(captured[1].source, addr(2));
+        assert_eq!(captured[1].timestamp, time(2));
+        assert_eq!(captured[1].message.header().id(), 2);
+        assert_eq!(dump.discarded(), 1);
+
+        assert!(!dump.close_tcp(addr(2), addr(53)));
+        dump.expire_tcp(time(3));
+        assert_eq!(dump.tcp_streams(), 0);
+    }
+}
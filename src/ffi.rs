@@ -0,0 +1,394 @@
+//! A C interface to the message parser.
+//!
+//! **This module is experimental and likely to change significantly.**
+//!
+//! This module provides a small set of `extern "C"` functions that allow
+//! applications written in other languages to parse DNS messages in wire
+//! format and access their content in presentation format. In order to use
+//! them, build a static or dynamic C library from a crate that depends on
+//! this crate with the `ffi` feature enabled. The functions are exported
+//! under their own names.
+//!
+//! A message is parsed via [`domain_message_parse`] into an opaque handle
+//! of type [`DomainMessage`]. The handle must be released via
+//! [`domain_message_free`] once it isn’t needed anymore. All records of the
+//! message are parsed right away, so once a handle was successfully
+//! created, accessing its content cannot fail.
+//!
+//! Questions and records are accessed by their index, starting at zero for
+//! the first question and the first record of the answer section
+//! respectively. The records of the three record sections are numbered
+//! consecutively, the section of a record can be learned via
+//! [`domain_message_record_section`]. Strings are returned as pointers to
+//! NUL-terminated UTF-8 strings owned by the handle. They stay valid until
+//! the handle is freed.
+//!
+//! In C, the interface could be declared like this:
+//!
+//! ```c
+//! typedef struct DomainMessage DomainMessage;
+//!
+//! DomainMessage *domain_message_parse(const uint8_t *data, size_t len);
+//! void domain_message_free(DomainMessage *msg);
+//! uint16_t domain_message_id(const DomainMessage *msg);
+//! uint16_t domain_message_rcode(const DomainMessage *msg);
+//! size_t domain_message_question_count(const DomainMessage *msg);
+//! const char *domain_message_question(const DomainMessage *msg, size_t idx);
+//! size_t domain_message_record_count(const DomainMessage *msg);
+//! const char *domain_message_record(const DomainMessage *msg, size_t idx);
+//! int domain_message_record_section(const DomainMessage *msg, size_t idx);
+//! const char *domain_message_display(const DomainMessage *msg);
+//! ```
+#![cfg(feature = "ffi")]
+#![cfg_attr(docsrs, doc(cfg(feature = "ffi")))]
+
+use crate::base::iana::OptRcode;
+use crate::base::message::{Message, Section};
+use crate::base::name::ParsedDname;
+use crate::base::wire::ParseError;
+use crate::rdata::AllRecordData;
+use core::ffi::{c_char, c_int};
+use core::{ptr, slice};
+use std::boxed::Box;
+use std::ffi::CString;
+use std::string::ToString;
+use std::vec::Vec;
+
+//------------ Constants -----------------------------------------------------
+
+/// The value for a record in the answer section.
+pub const DOMAIN_SECTION_ANSWER: c_int = 0;
+
+/// The value for a record in the authority section.
+pub const DOMAIN_SECTION_AUTHORITY: c_int = 1;
+
+/// The value for a record in the additional section.
+pub const DOMAIN_SECTION_ADDITIONAL: c_int = 2;
+
+//------------ DomainMessage -------------------------------------------------
+
+/// An opaque handle to a parsed DNS message.
+///
+/// A handle is created via [`domain_message_parse`] and must be released
+/// via [`domain_message_free`].
+pub struct DomainMessage {
+    /// The message itself.
+    message: Message<Vec<u8>>,
+
+    /// The questions in presentation format.
+    questions: Vec<CString>,
+
+    /// The records in presentation format and their section.
+    records: Vec<(c_int, CString)>,
+
+    /// The message in the layout of dig.
+    display: CString,
+}
+
+impl DomainMessage {
+    /// Parses a message and renders all its content.
+    fn parse(octets: Vec<u8>) -> Result<Self, ParseError> {
+        let message = Message::from_octets(octets)
+            .map_err(|_| ParseError::ShortInput)?;
+        let mut questions = Vec::new();
+        for question in message.question() {
+            questions.push(c_string(question?.to_string()));
+        }
+        let mut records = Vec::new();
+        for item in &message {
+            let (record, section) = item?;
+            let record = match record
+                .into_record::<AllRecordData<_, ParsedDname<_>>>()?
+            {
+                Some(record) => record,
+                None => continue,
+            };
+            let section = match section {
+                Section::Answer => DOMAIN_SECTION_ANSWER,
+                Section::Authority => DOMAIN_SECTION_AUTHORITY,
+                Section::Additional => DOMAIN_SECTION_ADDITIONAL,
+            };
+            records.push((section, c_string(record.to_string())));
+        }
+        let display = c_string(message.display_dig().to_string());
+        Ok(DomainMessage {
+            message,
+            questions,
+            records,
+            display,
+        })
+    }
+}
+
+//------------ Functions -----------------------------------------------------
+
+/// Parses a message in wire format.
+///
+/// The `len` octets starting at `data` are copied, so the buffer can be
+/// reused once the function returns. Returns a null pointer if the data
+/// doesn’t contain a valid message.
+///
+/// # Safety
+///
+/// `data` must point to at least `len` readable octets. It may be null if
+/// `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_parse(
+    data: *const u8,
+    len: usize,
+) -> *mut DomainMessage {
+    let octets = if len == 0 {
+        Vec::new()
+    } else if data.is_null() {
+        return ptr::null_mut();
+    } else {
+        slice::from_raw_parts(data, len).to_vec()
+    };
+    match DomainMessage::parse(octets) {
+        Ok(msg) => Box::into_raw(Box::new(msg)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a message handle.
+///
+/// All strings obtained from the handle become invalid. Passing a null
+/// pointer is allowed and does nothing.
+///
+/// # Safety
+///
+/// `msg` must be null or a handle returned by [`domain_message_parse`]
+/// that hasn’t been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_free(msg: *mut DomainMessage) {
+    if !msg.is_null() {
+        drop(Box::from_raw(msg))
+    }
+}
+
+/// Returns the message ID.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_id(msg: *const DomainMessage) -> u16 {
+    let msg = &*msg;
+    msg.message.header().id()
+}
+
+/// Returns the response code.
+///
+/// If the message contains an OPT record, the extended response code is
+/// returned.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_rcode(
+    msg: *const DomainMessage,
+) -> u16 {
+    let message = &(*msg).message;
+    match message.opt() {
+        Some(opt) => opt.rcode(message.header()),
+        None => OptRcode::from(message.header().rcode()),
+    }
+    .to_int()
+}
+
+/// Returns the number of questions.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_question_count(
+    msg: *const DomainMessage,
+) -> usize {
+    let msg = &*msg;
+    msg.questions.len()
+}
+
+/// Returns the question with the given index in presentation format.
+///
+/// Returns a null pointer if there is no such question.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_question(
+    msg: *const DomainMessage,
+    idx: usize,
+) -> *const c_char {
+    let msg = &*msg;
+    match msg.questions.get(idx) {
+        Some(question) => question.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the number of records in all three record sections.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_record_count(
+    msg: *const DomainMessage,
+) -> usize {
+    let msg = &*msg;
+    msg.records.len()
+}
+
+/// Returns the record with the given index in presentation format.
+///
+/// Returns a null pointer if there is no such record.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_record(
+    msg: *const DomainMessage,
+    idx: usize,
+) -> *const c_char {
+    let msg = &*msg;
+    match msg.records.get(idx) {
+        Some((_, record)) => record.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the section of the record with the given index.
+///
+/// Returns one of the `DOMAIN_SECTION_*` constants or -1 if there is no
+/// such record.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_record_section(
+    msg: *const DomainMessage,
+    idx: usize,
+) -> c_int {
+    let msg = &*msg;
+    match msg.records.get(idx) {
+        Some((section, _)) => *section,
+        None => -1,
+    }
+}
+
+/// Returns the complete message in the layout of dig.
+///
+/// See [`Message::display_dig`] for details.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle returned by [`domain_message_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_display(
+    msg: *const DomainMessage,
+) -> *const c_char {
+    let msg = &*msg;
+    msg.display.as_ptr()
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a string into a C string, dropping any NUL characters.
+fn c_string(s: std::string::String) -> CString {
+    CString::new(s).unwrap_or_else(|err| {
+        let mut s = err.into_vec();
+        s.retain(|&ch| ch != 0);
+        CString::new(s).unwrap()
+    })
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::{Ns, A};
+    use core::str::FromStr;
+    use std::ffi::CStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    unsafe fn string(ptr: *const c_char) -> &'static str {
+        CStr::from_ptr(ptr).to_str().unwrap()
+    }
+
+    #[test]
+    fn parse_message() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        let mut msg = msg.question();
+        msg.push((name("example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com"), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut msg = msg.authority();
+        msg.push((name("example.com"), 3600, Ns::new(name("ns.example"))))
+            .unwrap();
+        let octets = msg.finish();
+
+        unsafe {
+            let msg = domain_message_parse(octets.as_ptr(), octets.len());
+            assert!(!msg.is_null());
+            assert_eq!(domain_message_id(msg), 4711);
+            assert_eq!(domain_message_rcode(msg), 0);
+            assert_eq!(domain_message_question_count(msg), 1);
+            assert_eq!(
+                string(domain_message_question(msg, 0)),
+                "example.com.\tA\tIN"
+            );
+            assert!(domain_message_question(msg, 1).is_null());
+            assert_eq!(domain_message_record_count(msg), 2);
+            assert_eq!(
+                string(domain_message_record(msg, 0)),
+                "example.com. 3600 IN A 192.0.2.1"
+            );
+            assert_eq!(
+                domain_message_record_section(msg, 0),
+                DOMAIN_SECTION_ANSWER
+            );
+            assert_eq!(
+                string(domain_message_record(msg, 1)),
+                "example.com. 3600 IN NS ns.example."
+            );
+            assert_eq!(
+                domain_message_record_section(msg, 1),
+                DOMAIN_SECTION_AUTHORITY
+            );
+            assert!(domain_message_record(msg, 2).is_null());
+            assert_eq!(domain_message_record_section(msg, 2), -1);
+            assert!(string(domain_message_display(msg))
+                .starts_with(";; ->>HEADER<<- opcode: QUERY"));
+            domain_message_free(msg);
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let mut msg = MessageBuilder::new_vec().answer();
+        msg.push((name("example.com"), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let octets = msg.finish();
+        unsafe {
+            assert!(domain_message_parse(octets.as_ptr(), 11).is_null());
+            assert!(domain_message_parse(octets.as_ptr(), octets.len() - 1)
+                .is_null());
+            assert!(domain_message_parse(ptr::null(), 0).is_null());
+            domain_message_free(ptr::null_mut());
+        }
+    }
+}
@@ -27,13 +27,45 @@
 //!
 //! Currently, there are the following modules:
 //!
+#![cfg_attr(any(feature = "sign", feature = "validate"), doc = "* [crypto]:")]
+#![cfg_attr(
+    not(any(feature = "sign", feature = "validate")),
+    doc = "* crypto:"
+)]
+//!   The cryptographic backends used for DNSSEC signing and validation.
+//!   It is enabled by either the `sign` or the `validate` feature.
+#![cfg_attr(feature = "dnstap", doc = "* [dnstap]:")]
+#![cfg_attr(not(feature = "dnstap"), doc = "* dnstap:")]
+//!   Experimental logging of DNS traffic in the dnstap format.
+#![cfg_attr(feature = "dump", doc = "* [dump]:")]
+#![cfg_attr(not(feature = "dump"), doc = "* dump:")]
+//!   Experimental extraction of DNS messages from captured traffic.
+#![cfg_attr(feature = "ffi", doc = "* [ffi]:")]
+#![cfg_attr(not(feature = "ffi"), doc = "* ffi:")]
+//!   An experimental C interface to the message parser.
+#![cfg_attr(feature = "mailauth", doc = "* [mailauth]:")]
+#![cfg_attr(not(feature = "mailauth"), doc = "* mailauth:")]
+//!   Parsing SPF, DMARC, and DKIM records published in TXT records.
+#![cfg_attr(any(feature = "resolv", feature = "serve"), doc = "* [metrics]:")]
+#![cfg_attr(
+    not(any(feature = "resolv", feature = "serve")),
+    doc = "* metrics:"
+)]
+//!   Instrumentation hooks for the resolver and servers. It is enabled by
+//!   either the `resolv` or the `serve` feature.
 #![cfg_attr(feature = "resolv", doc = "* [resolv]:")]
 #![cfg_attr(not(feature = "resolv"), doc = "* resolv:")]
 //!   An asynchronous DNS resolver based on the
 //!   [Tokio](https://tokio.rs/) async runtime.
+#![cfg_attr(feature = "serve", doc = "* [serve]:")]
+#![cfg_attr(not(feature = "serve"), doc = "* serve:")]
+//!   Asynchronous UDP and TCP servers for DNS requests based on Tokio.
 #![cfg_attr(feature = "sign", doc = "* [sign]:")]
 #![cfg_attr(not(feature = "sign"), doc = "* sign:")]
 //!   Experimental support for DNSSEC signing.
+#![cfg_attr(feature = "test-support", doc = "* [test_support]:")]
+#![cfg_attr(not(feature = "test-support"), doc = "* test_support:")]
+//!   Generating arbitrary DNS values for testing.
 #![cfg_attr(feature = "tsig", doc = "* [tsig]:")]
 #![cfg_attr(not(feature = "tsig"), doc = "* tsig:")]
 //!   Support for securing DNS transactions with TSIG records.
@@ -44,23 +76,63 @@
 #![cfg_attr(not(feature = "zonefile"), doc = "* zonefile:")]
 //!   Experimental reading and writing of zone files, i.e., the textual
 //!   representation of DNS data.
+#![cfg_attr(feature = "zonetree", doc = "* [zonetree]:")]
+#![cfg_attr(not(feature = "zonetree"), doc = "* zonetree:")]
+//!   In-memory storage of authoritative zone data and answering queries
+//!   from it.
 //!
 //!
 //! # Reference of Feature Flags
 //!
 //! The following is the complete list of the feature flags available.
 //!
+//! * `alloc`: Enables functionality that needs an allocator but not the
+//!   rest of the standard library. This feature is enabled by `std` and
+//!   `bytes`.
 //! * `bytes`: Enables using the types `Bytes` and `BytesMut` from the
 //!    [bytes](https://github.com/tokio-rs/bytes) crate as octet sequences.
+//!    This feature does not require `std` and enables `alloc`.
 //! * `chrono`: Adds the [chrono](https://github.com/chronotope/chrono)
 //!   crate as a dependency. This adds support for generating serial numbers
 //!   from time stamps.
+//! * `cli`: Builds the `domain-dig` binary, a small dig-like tool for
+//!   sending queries via the stub resolver. Implies `resolv-sync`.
+//! * `dnstap`: Enables the
+#![cfg_attr(feature = "dnstap", doc = "  [dnstap]")]
+#![cfg_attr(not(feature = "dnstap"), doc = "  dnstap")]
+//!   module for logging DNS traffic in the dnstap format. This feature
+//!   requires the `std` and `bytes` features.
+//! * `dump`: Enables the
+#![cfg_attr(feature = "dump", doc = "  [dump]")]
+#![cfg_attr(not(feature = "dump"), doc = "  dump")]
+//!   module for extracting messages from captured traffic. This feature
+//!   requires the `std` feature.
+//! * `ffi`: Enables the
+#![cfg_attr(feature = "ffi", doc = "  [ffi]")]
+#![cfg_attr(not(feature = "ffi"), doc = "  ffi")]
+//!   module with `extern "C"` functions for parsing messages. This feature
+//!   requires the `std` feature.
 //! * `heapless`: enables the use of the `Vec` type from the
 //!   [heapless](https://github.com/japaric/heapless) crate as octet
 //!   sequences.
+//! * `hickory-proto`: Enables conversions between the message, record,
+//!   and domain name types of this crate and those of the
+//!   [hickory-proto](https://github.com/hickory-dns/hickory-dns) crate via
+//!   the
+#![cfg_attr(feature = "hickory-proto", doc = "  [base::hickory]")]
+#![cfg_attr(not(feature = "hickory-proto"), doc = "  base::hickory")]
+//!   module. This feature requires the `bytes` and `std` features and
+//!   Rust 1.71.1 or newer.
 //! * `interop`: Activate interoperability tests that rely on other software
 //!   to be installed in the system (currently NSD and dig) and will fail if
 //!   it isn’t. This feature is not meaningful for users of the crate.
+//! * `mdns`: Enables the multicast DNS responder in the `serve::mdns`
+//!   module. This feature requires the `serve` and `rand` features.
+//! * `openssl`: Enables crypto functionality via the
+//!   [openssl](https://github.com/sfackler/rust-openssl) crate, i.e., the
+//!   `crypto::OpensslBackend` and the `sign::openssl` module. This feature
+//!   needs the OpenSSL library and its headers to be installed. Recent
+//!   versions of the openssl crate require Rust 1.70 or newer.
 //! * `rand`: Enables a number of methods that rely on a random number
 //!   generator being available in the system.
 //! * `resolv`: Enables the asynchronous stub resolver via the
@@ -71,13 +143,19 @@
 //! * `ring`: Enables crypto functionality via the
 //!   [ring](https://github.com/briansmith/ring) crate.
 //! * `serde`: Enables serde serialization for a number of basic types.
+//! * `serve`: Enables the
+#![cfg_attr(feature = "serve", doc = "  [serve]")]
+#![cfg_attr(not(feature = "serve"), doc = "  serve")]
+//!   module with servers for DNS requests. This feature requires the `std`
+//!   feature.
 //! * `sign`: basic DNSSEC signing support. This will enable the
 #![cfg_attr(feature = "sign", doc = "  [sign]")]
 #![cfg_attr(not(feature = "sign"), doc = "  sign")]
 //!   module and requires the `std` feature. Note that this will not directly
 //!   enable actual signing. For that you will also need to pick a crypto
-//!   module via an additional feature. Currently we only support the `ring`
-//!   module, but support for OpenSSL is coming soon.
+//!   module via an additional feature. Currently we provide the `ring` and
+//!   `openssl` modules, but other crypto libraries can be used by
+//!   implementing the traits described in the `crypto` module.
 //! * `siphasher`: enables the dependency on the
 //!   [siphasher](https://github.com/jedisct1/rust-siphash) crate which allows
 //!   generating and checking hashes in [standard server
@@ -87,6 +165,15 @@
 //!   sequences.
 //! * `std`: support for the Rust std library. This feature is enabled by
 //!   default.
+//! * `test-support`: generators for arbitrary DNS values for use in tests
+//!   via the
+#![cfg_attr(feature = "test-support", doc = "  [test_support]")]
+#![cfg_attr(not(feature = "test-support"), doc = "  test_support")]
+//!   module. This currently enables the `bytes`, `rand`, and `std`
+//!   features.
+//! * `tracing`: Creates [tracing](https://github.com/tokio-rs/tracing)
+//!   spans for the resolutions of the stub resolver. This feature enables
+//!   the `resolv` feature.
 //! * `tsig`: support for signing and validating message exchanges via TSIG
 //!   signatures. This enables the
 #![cfg_attr(feature = "tsig", doc = "  [tsig]")]
@@ -96,12 +183,35 @@
 //! * `validate`: basic DNSSEC validation support. This feature enables the
 #![cfg_attr(feature = "validate", doc = "  [validate]")]
 #![cfg_attr(not(feature = "validate"), doc = "  validate")]
-//!   module and currently also enables the `std` and `ring`
-//!   features.
+//!   module and currently also enables the `std` feature. The crypto
+//!   backend is picked via an additional feature such as `ring` or
+//!   `openssl`. Convenience functions using the ring backend are only
+//!   available with the `ring` feature.
 //! * `zonefile`: reading and writing of zonefiles. This feature enables the
 #![cfg_attr(feature = "zonefile", doc = "  [zonefile]")]
 #![cfg_attr(not(feature = "zonefile"), doc = "  zonefile")]
 //!   module and currently also enables the `bytes` and `std` features.
+//! * `zonetree`: in-memory storage of zone data. This feature enables the
+#![cfg_attr(feature = "zonetree", doc = "  [zonetree]")]
+#![cfg_attr(not(feature = "zonetree"), doc = "  zonetree")]
+//!   module and currently also enables the `bytes` and `std` features.
+//!
+//! # Use without std
+//!
+//! Without the `std` feature, the crate is `no_std` and neither needs the
+//! standard library nor an allocator. The [base] and [rdata] modules are
+//! available for parsing and building messages in wire format using octets
+//! sequences such as `&[u8]`, arrays, or, with the `heapless` feature,
+//! `heapless::Vec`.
+//!
+//! If an allocator is available, the `alloc` feature enables the parts of
+//! these modules that need one, such as encoding data into strings. Adding
+//! the `bytes` feature allows using the allocated `Bytes` and `BytesMut`
+//! types as octets sequences, e.g., via `Dname::bytes_from_str` or
+//! `MessageBuilder::new_bytes`. Using `Vec<u8>` as an octets sequence still
+//! requires `std` as the octseq crate only implements its traits for it
+//! with its own `std` feature. The zone file parser and the networking
+//! parts of the resolver require `std`, too.
 
 #![no_std]
 #![allow(renamed_and_removed_lints)]
@@ -114,15 +224,27 @@
 #[macro_use]
 extern crate std;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 #[macro_use]
 extern crate core;
 
 pub mod base;
+pub mod crypto;
+pub mod dnstap;
+pub mod dump;
+pub mod ffi;
+pub mod mailauth;
+pub mod metrics;
 pub mod rdata;
 pub mod resolv;
+pub mod serve;
 pub mod sign;
 pub mod test;
+pub mod test_support;
 pub mod tsig;
 pub mod utils;
 pub mod validate;
 pub mod zonefile;
+pub mod zonetree;
@@ -0,0 +1,236 @@
+//! DKIM key records.
+//!
+//! A DKIM key record published in a TXT record at
+//! `selector._domainkey.domain` provides the public key for verifying
+//! signatures made by the domain using the selector. The record is a
+//! tag-value list which may start with the version tag `v=DKIM1`. The
+//! details are defined in section 3.6.1 of RFC 6376.
+
+use super::{tag_list, txt_to_string, SyntaxError};
+use crate::rdata::Txt;
+use crate::utils::base64;
+use core::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ DkimKey -------------------------------------------------------
+
+/// The content of a DKIM key record.
+///
+/// Tags not given in the record have their default values. Unknown tags
+/// are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DkimKey {
+    /// The acceptable hash algorithms (`h`).
+    ///
+    /// If this is empty, all algorithms are allowed.
+    hash_algs: Vec<String>,
+
+    /// The key type (`k`).
+    key_type: KeyType,
+
+    /// Notes for humans (`n`).
+    notes: Option<String>,
+
+    /// The public key data (`p`).
+    ///
+    /// If this is empty, the key has been revoked.
+    public_key: Vec<u8>,
+
+    /// The service types the key applies to (`s`).
+    service_types: Vec<String>,
+
+    /// The flags (`t`).
+    flags: Vec<String>,
+}
+
+impl DkimKey {
+    /// Parses the DKIM key record from TXT record data.
+    pub fn from_txt<Octs: AsRef<[u8]>>(
+        txt: &Txt<Octs>,
+    ) -> Result<Self, SyntaxError> {
+        Self::from_str(&txt_to_string(txt)?)
+    }
+
+    /// Returns the acceptable hash algorithms.
+    ///
+    /// If the slice is empty, all hash algorithms are acceptable.
+    pub fn hash_algs(&self) -> &[String] {
+        &self.hash_algs
+    }
+
+    pub fn key_type(&self) -> &KeyType {
+        &self.key_type
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    /// Returns the decoded public key data.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns whether the key has been revoked.
+    ///
+    /// A key is revoked by publishing a record with empty key data.
+    pub fn is_revoked(&self) -> bool {
+        self.public_key.is_empty()
+    }
+
+    pub fn service_types(&self) -> &[String] {
+        &self.service_types
+    }
+
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// Returns whether the domain is testing DKIM (flag `y`).
+    pub fn is_testing(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "y")
+    }
+
+    /// Returns whether subdomains must not use the key (flag `s`).
+    pub fn is_strict(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "s")
+    }
+}
+
+//--- FromStr
+
+impl FromStr for DkimKey {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tags = tag_list(s)?;
+        let tags = match tags.split_first() {
+            Some((("v", version), tags)) => {
+                if *version != "DKIM1" {
+                    return Err(SyntaxError::new("unknown version"));
+                }
+                tags
+            }
+            _ => &tags,
+        };
+        let mut public_key = None;
+        let mut res = DkimKey {
+            hash_algs: Vec::new(),
+            key_type: KeyType::Rsa,
+            notes: None,
+            public_key: Vec::new(),
+            service_types: vec!["*".into()],
+            flags: Vec::new(),
+        };
+        for (name, value) in tags {
+            match *name {
+                "v" => return Err(SyntaxError::new("version not first")),
+                "h" => res.hash_algs = colon_list(value),
+                "k" => res.key_type = KeyType::from_str(value)?,
+                "n" => res.notes = Some(value.to_string()),
+                "p" => {
+                    let value: String = value
+                        .chars()
+                        .filter(|ch| !ch.is_ascii_whitespace())
+                        .collect();
+                    public_key =
+                        Some(base64::decode(&value).map_err(|_| {
+                            SyntaxError::new("bad public key")
+                        })?);
+                }
+                "s" => res.service_types = colon_list(value),
+                "t" => res.flags = colon_list(value),
+                _ => {}
+            }
+        }
+        res.public_key = match public_key {
+            Some(key) => key,
+            None => return Err(SyntaxError::new("missing public key")),
+        };
+        Ok(res)
+    }
+}
+
+//------------ KeyType -------------------------------------------------------
+
+/// The type of a DKIM key.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum KeyType {
+    /// An RSA key as defined in RFC 6376.
+    Rsa,
+
+    /// An Ed25519 key as defined in RFC 8463.
+    Ed25519,
+
+    /// Some other key type.
+    Other(String),
+}
+
+impl FromStr for KeyType {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(SyntaxError::new("missing key type"))
+        } else if s.eq_ignore_ascii_case("rsa") {
+            Ok(KeyType::Rsa)
+        } else if s.eq_ignore_ascii_case("ed25519") {
+            Ok(KeyType::Ed25519)
+        } else {
+            Ok(KeyType::Other(s.into()))
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Splits a colon-separated list into its trimmed, non-empty items.
+fn colon_list(s: &str) -> Vec<String> {
+    s.split(':')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(Into::into)
+        .collect()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let key = DkimKey::from_str(
+            "v=DKIM1; k=ed25519; h=sha256; t=y:s; \
+             p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=",
+        )
+        .unwrap();
+        assert_eq!(key.key_type(), &KeyType::Ed25519);
+        assert_eq!(key.hash_algs(), ["sha256"]);
+        assert_eq!(key.service_types(), ["*"]);
+        assert!(key.is_testing());
+        assert!(key.is_strict());
+        assert!(!key.is_revoked());
+        assert_eq!(key.public_key().len(), 32);
+        assert_eq!(key.public_key()[..2], [0xd7, 0x5a]);
+
+        let key = DkimKey::from_str("p=AQID BA==; n=rotated").unwrap();
+        assert_eq!(key.key_type(), &KeyType::Rsa);
+        assert_eq!(key.public_key(), [1, 2, 3, 4]);
+        assert_eq!(key.notes(), Some("rotated"));
+        assert!(!key.is_testing());
+
+        let key = DkimKey::from_str("v=DKIM1; p=").unwrap();
+        assert!(key.is_revoked());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(DkimKey::from_str("v=DKIM1; k=rsa").is_err());
+        assert!(DkimKey::from_str("v=DKIM2; p=").is_err());
+        assert!(DkimKey::from_str("p=; v=DKIM1").is_err());
+        assert!(DkimKey::from_str("p=!!!").is_err());
+    }
+}
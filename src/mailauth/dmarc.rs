@@ -0,0 +1,294 @@
+//! DMARC policy records.
+//!
+//! A DMARC record published in a TXT record at the `_dmarc` subdomain of a
+//! domain tells receivers what to do with mail that fails authentication
+//! and where to send reports about it. The record is a tag-value list
+//! starting with the version tag `v=DMARC1`. The details are defined in
+//! RFC 7489.
+
+use super::{tag_list, txt_to_string, SyntaxError};
+use crate::rdata::Txt;
+use core::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ DmarcRecord ---------------------------------------------------
+
+/// The content of a DMARC record.
+///
+/// Tags not given in the record have their default values. Unknown tags
+/// are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DmarcRecord {
+    /// The policy for the domain (`p`).
+    policy: Policy,
+
+    /// The policy for subdomains (`sp`).
+    subdomain_policy: Option<Policy>,
+
+    /// The percentage of messages the policy applies to (`pct`).
+    percent: u8,
+
+    /// The URIs for aggregate reports (`rua`).
+    aggregate_uris: Vec<String>,
+
+    /// The URIs for failure reports (`ruf`).
+    failure_uris: Vec<String>,
+
+    /// The DKIM alignment mode (`adkim`).
+    dkim_alignment: Alignment,
+
+    /// The SPF alignment mode (`aspf`).
+    spf_alignment: Alignment,
+
+    /// The failure reporting options (`fo`).
+    failure_options: String,
+
+    /// The interval between aggregate reports in seconds (`ri`).
+    report_interval: u32,
+}
+
+impl DmarcRecord {
+    /// Returns whether the text of a TXT record is a DMARC record.
+    ///
+    /// Only the version is checked, so the record may still be invalid.
+    pub fn is_dmarc(text: &str) -> bool {
+        match tag_list(text) {
+            Ok(tags) => tags.first() == Some(&("v", "DMARC1")),
+            Err(_) => false,
+        }
+    }
+
+    /// Parses the DMARC record from TXT record data.
+    pub fn from_txt<Octs: AsRef<[u8]>>(
+        txt: &Txt<Octs>,
+    ) -> Result<Self, SyntaxError> {
+        Self::from_str(&txt_to_string(txt)?)
+    }
+
+    /// Returns the policy for the domain.
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Returns the policy for subdomains.
+    ///
+    /// If the record doesn’t have an explicit policy for subdomains, this
+    /// is the policy for the domain itself.
+    pub fn subdomain_policy(&self) -> Policy {
+        self.subdomain_policy.unwrap_or(self.policy)
+    }
+
+    /// Returns the percentage of messages the policy applies to.
+    ///
+    /// This is 100 if the record doesn’t say otherwise.
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// Returns the URIs aggregate reports are sent to.
+    pub fn aggregate_uris(&self) -> &[String] {
+        &self.aggregate_uris
+    }
+
+    /// Returns the URIs failure reports are sent to.
+    pub fn failure_uris(&self) -> &[String] {
+        &self.failure_uris
+    }
+
+    /// Returns the DKIM alignment mode.
+    ///
+    /// This is relaxed if the record doesn’t say otherwise.
+    pub fn dkim_alignment(&self) -> Alignment {
+        self.dkim_alignment
+    }
+
+    /// Returns the SPF alignment mode.
+    ///
+    /// This is relaxed if the record doesn’t say otherwise.
+    pub fn spf_alignment(&self) -> Alignment {
+        self.spf_alignment
+    }
+
+    /// Returns the colon-separated failure reporting options.
+    pub fn failure_options(&self) -> &str {
+        &self.failure_options
+    }
+
+    /// Returns the interval between aggregate reports in seconds.
+    ///
+    /// This is 86400, i.e., one day, if the record doesn’t say otherwise.
+    pub fn report_interval(&self) -> u32 {
+        self.report_interval
+    }
+}
+
+//--- FromStr
+
+impl FromStr for DmarcRecord {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tags = tag_list(s)?;
+        if tags.first() != Some(&("v", "DMARC1")) {
+            return Err(SyntaxError::new("not a DMARC record"));
+        }
+        let mut policy = None;
+        let mut res = DmarcRecord {
+            policy: Policy::None,
+            subdomain_policy: None,
+            percent: 100,
+            aggregate_uris: Vec::new(),
+            failure_uris: Vec::new(),
+            dkim_alignment: Alignment::Relaxed,
+            spf_alignment: Alignment::Relaxed,
+            failure_options: "0".into(),
+            report_interval: 86400,
+        };
+        for (name, value) in &tags[1..] {
+            match *name {
+                "p" => policy = Some(Policy::from_str(value)?),
+                "sp" => res.subdomain_policy = Some(Policy::from_str(value)?),
+                "pct" => {
+                    res.percent = match u8::from_str(value) {
+                        Ok(pct) if pct <= 100 => pct,
+                        _ => return Err(SyntaxError::new("bad percentage")),
+                    }
+                }
+                "rua" => res.aggregate_uris = uri_list(value)?,
+                "ruf" => res.failure_uris = uri_list(value)?,
+                "adkim" => res.dkim_alignment = Alignment::from_str(value)?,
+                "aspf" => res.spf_alignment = Alignment::from_str(value)?,
+                "fo" => res.failure_options = value.to_string(),
+                "ri" => {
+                    res.report_interval = u32::from_str(value)
+                        .map_err(|_| SyntaxError::new("bad interval"))?
+                }
+                _ => {}
+            }
+        }
+        res.policy = match policy {
+            Some(policy) => policy,
+            None => return Err(SyntaxError::new("missing policy")),
+        };
+        Ok(res)
+    }
+}
+
+//------------ Policy --------------------------------------------------------
+
+/// The requested handling of mail failing authentication.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Policy {
+    /// No specific action is requested (`none`).
+    None,
+
+    /// The mail should be treated as suspicious (`quarantine`).
+    Quarantine,
+
+    /// The mail should be rejected (`reject`).
+    Reject,
+}
+
+impl FromStr for Policy {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("none") {
+            Ok(Policy::None)
+        } else if s.eq_ignore_ascii_case("quarantine") {
+            Ok(Policy::Quarantine)
+        } else if s.eq_ignore_ascii_case("reject") {
+            Ok(Policy::Reject)
+        } else {
+            Err(SyntaxError::new("unknown policy"))
+        }
+    }
+}
+
+//------------ Alignment -----------------------------------------------------
+
+/// The mode for checking identifier alignment.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Alignment {
+    /// The organizational domains must match (`r`).
+    Relaxed,
+
+    /// The domains must match exactly (`s`).
+    Strict,
+}
+
+impl FromStr for Alignment {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("r") {
+            Ok(Alignment::Relaxed)
+        } else if s.eq_ignore_ascii_case("s") {
+            Ok(Alignment::Strict)
+        } else {
+            Err(SyntaxError::new("unknown alignment mode"))
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses a comma-separated list of report URIs.
+fn uri_list(s: &str) -> Result<Vec<String>, SyntaxError> {
+    s.split(',')
+        .map(|uri| {
+            let uri = uri.trim();
+            if uri.is_empty() {
+                Err(SyntaxError::new("empty URI"))
+            } else {
+                Ok(uri.to_string())
+            }
+        })
+        .collect()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let record = DmarcRecord::from_str(
+            "v=DMARC1; p=quarantine; rua=mailto:agg@example.com, \
+             mailto:agg@example.net!10m; pct=50; adkim=s; fo=1:d",
+        )
+        .unwrap();
+        assert_eq!(record.policy(), Policy::Quarantine);
+        assert_eq!(record.subdomain_policy(), Policy::Quarantine);
+        assert_eq!(record.percent(), 50);
+        assert_eq!(
+            record.aggregate_uris(),
+            ["mailto:agg@example.com", "mailto:agg@example.net!10m"]
+        );
+        assert!(record.failure_uris().is_empty());
+        assert_eq!(record.dkim_alignment(), Alignment::Strict);
+        assert_eq!(record.spf_alignment(), Alignment::Relaxed);
+        assert_eq!(record.failure_options(), "1:d");
+        assert_eq!(record.report_interval(), 86400);
+
+        let record =
+            DmarcRecord::from_str("v=DMARC1;p=reject;sp=none;x=y").unwrap();
+        assert_eq!(record.policy(), Policy::Reject);
+        assert_eq!(record.subdomain_policy(), Policy::None);
+        assert_eq!(record.percent(), 100);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(DmarcRecord::is_dmarc("v=DMARC1; p=none"));
+        assert!(!DmarcRecord::is_dmarc("p=none; v=DMARC1"));
+        assert!(DmarcRecord::from_str("p=none; v=DMARC1").is_err());
+        assert!(DmarcRecord::from_str("v=DMARC1; rua=mailto:a@b").is_err());
+        assert!(DmarcRecord::from_str("v=DMARC1; p=block").is_err());
+        assert!(DmarcRecord::from_str("v=DMARC1; p=none; pct=101").is_err());
+        assert!(DmarcRecord::from_str("v=DMARC1; p=none; p=none").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Parsing mail authentication records.
+//!
+//! Several mechanisms for authenticating email publish their policies and
+//! keys in TXT records. This module provides types for the content of these
+//! records:
+//!
+//! * [`spf`] for Sender Policy Framework records defined in RFC 7208,
+//! * [`dmarc`] for DMARC policy records defined in RFC 7489, and
+//! * [`dkim`] for DKIM key records defined in RFC 6376.
+//!
+//! Each of the record types can be created either from the string content
+//! of the record via `FromStr` or directly from [`Txt`] record data via a
+//! `from_txt` function. In the latter case, the character strings of the
+//! record data are concatenated without any separator as required by all
+//! three specifications.
+//!
+//! The names at which the records are published can be created via
+//! [`Dname::dkim_name`][crate::base::Dname::dkim_name] and
+//! [`Dname::dmarc_name`][crate::base::Dname::dmarc_name].
+#![cfg(feature = "mailauth")]
+#![cfg_attr(docsrs, doc(cfg(feature = "mailauth")))]
+
+pub mod dkim;
+pub mod dmarc;
+pub mod spf;
+
+use crate::rdata::Txt;
+use core::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the concatenated text of TXT record data as a string.
+fn txt_to_string<Octs: AsRef<[u8]>>(
+    txt: &Txt<Octs>,
+) -> Result<String, SyntaxError> {
+    String::from_utf8(txt.text::<Vec<u8>>())
+        .map_err(|_| SyntaxError::new("record is not valid UTF-8"))
+}
+
+/// Splits a tag-value list into its tags and values.
+///
+/// Tag-value lists are used by both DKIM and DMARC. They are defined in
+/// section 3.2 of RFC 6376. Returns an error if a tag has an invalid name
+/// or appears more than once.
+fn tag_list(s: &str) -> Result<Vec<(&str, &str)>, SyntaxError> {
+    let mut res: Vec<(&str, &str)> = Vec::new();
+    let mut specs = s.split(';').peekable();
+    while let Some(spec) = specs.next() {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            // Only the last spec may be empty due to a trailing semicolon.
+            if specs.peek().is_none() {
+                break;
+            }
+            return Err(SyntaxError::new("empty tag"));
+        }
+        let (name, value) = match spec.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(SyntaxError::new("missing tag value")),
+        };
+        if !is_tag_name(name) {
+            return Err(SyntaxError::new("invalid tag name"));
+        }
+        if res.iter().any(|(other, _)| *other == name) {
+            return Err(SyntaxError::new("duplicate tag"));
+        }
+        res.push((name, value));
+    }
+    Ok(res)
+}
+
+/// Returns whether a string is a valid tag name.
+fn is_tag_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+//============ Error Types ===================================================
+
+//------------ SyntaxError ---------------------------------------------------
+
+/// The content of a mail authentication record is invalid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SyntaxError(&'static str);
+
+impl SyntaxError {
+    fn new(msg: &'static str) -> Self {
+        SyntaxError(msg)
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            tag_list("v=DKIM1; k = rsa ;p=abc;").unwrap(),
+            [("v", "DKIM1"), ("k", "rsa"), ("p", "abc")]
+        );
+        assert!(tag_list("v=DKIM1;;p=abc").is_err());
+        assert!(tag_list("v=DKIM1; v=DKIM1").is_err());
+        assert!(tag_list("v=DKIM1; 1p=abc").is_err());
+        assert!(tag_list("v=DKIM1; p").is_err());
+    }
+}
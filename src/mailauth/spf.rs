@@ -0,0 +1,599 @@
+//! Sender Policy Framework records.
+//!
+//! An SPF record published in a TXT record at a domain lists the hosts that
+//! are allowed to send mail for the domain. It consists of the version
+//! `v=spf1` followed by a sequence of terms which are either directives or
+//! modifiers. A directive is a [`Mechanism`] matching the sending host plus
+//! a [`Qualifier`] that defines the result if the mechanism matches. The
+//! details are defined in RFC 7208.
+//!
+//! Domain specifications of mechanisms and modifiers may contain macros.
+//! They are kept as strings and not expanded.
+
+use super::{txt_to_string, SyntaxError};
+use crate::rdata::Txt;
+use core::fmt;
+use core::str::FromStr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ SpfRecord -----------------------------------------------------
+
+/// The content of an SPF record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpfRecord {
+    /// The directives in the order they appear in the record.
+    directives: Vec<Directive>,
+
+    /// The domain of the redirect modifier, if present.
+    redirect: Option<String>,
+
+    /// The domain of the explanation modifier, if present.
+    exp: Option<String>,
+
+    /// Any unknown modifiers as pairs of name and value.
+    unknown: Vec<(String, String)>,
+}
+
+impl SpfRecord {
+    /// Returns whether the text of a TXT record is an SPF record.
+    ///
+    /// Only the version is checked, so the record may still be invalid.
+    pub fn is_spf(text: &str) -> bool {
+        let version = text.get(..6);
+        version.map(|v| v.eq_ignore_ascii_case("v=spf1")) == Some(true)
+            && matches!(text.as_bytes().get(6), None | Some(b' '))
+    }
+
+    /// Parses the SPF record from TXT record data.
+    pub fn from_txt<Octs: AsRef<[u8]>>(
+        txt: &Txt<Octs>,
+    ) -> Result<Self, SyntaxError> {
+        Self::from_str(&txt_to_string(txt)?)
+    }
+
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    pub fn redirect(&self) -> Option<&str> {
+        self.redirect.as_deref()
+    }
+
+    pub fn exp(&self) -> Option<&str> {
+        self.exp.as_deref()
+    }
+
+    /// Returns the unknown modifiers as pairs of name and value.
+    pub fn unknown_modifiers(&self) -> &[(String, String)] {
+        &self.unknown
+    }
+
+    /// Returns the qualifier of the `all` mechanism, if present.
+    pub fn all(&self) -> Option<Qualifier> {
+        self.directives.iter().find_map(|directive| {
+            if directive.mechanism == Mechanism::All {
+                Some(directive.qualifier)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Parses a single term and adds it to the record.
+    fn push_term(&mut self, term: &str) -> Result<(), SyntaxError> {
+        // A term is a modifier if it starts with a name followed by an
+        // equals sign.
+        if let Some((name, value)) = term.split_once('=') {
+            if is_modifier_name(name) {
+                let target = if name.eq_ignore_ascii_case("redirect") {
+                    &mut self.redirect
+                } else if name.eq_ignore_ascii_case("exp") {
+                    &mut self.exp
+                } else {
+                    self.unknown.push((name.into(), value.into()));
+                    return Ok(());
+                };
+                if target.is_some() {
+                    return Err(SyntaxError::new("duplicate modifier"));
+                }
+                if value.is_empty() {
+                    return Err(SyntaxError::new("missing domain"));
+                }
+                *target = Some(value.into());
+                return Ok(());
+            }
+        }
+        self.directives.push(Directive::from_str(term)?);
+        Ok(())
+    }
+}
+
+//--- FromStr
+
+impl FromStr for SpfRecord {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !Self::is_spf(s) {
+            return Err(SyntaxError::new("not an SPF record"));
+        }
+        let mut res = SpfRecord {
+            directives: Vec::new(),
+            redirect: None,
+            exp: None,
+            unknown: Vec::new(),
+        };
+        for term in s[6..].split(' ').filter(|term| !term.is_empty()) {
+            res.push_term(term)?;
+        }
+        Ok(res)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for SpfRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("v=spf1")?;
+        for directive in &self.directives {
+            write!(f, " {}", directive)?;
+        }
+        if let Some(redirect) = self.redirect.as_ref() {
+            write!(f, " redirect={}", redirect)?;
+        }
+        if let Some(exp) = self.exp.as_ref() {
+            write!(f, " exp={}", exp)?;
+        }
+        for (name, value) in &self.unknown {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Directive -----------------------------------------------------
+
+/// A mechanism together with its qualifier.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Directive {
+    /// The result if the mechanism matches.
+    qualifier: Qualifier,
+
+    /// The mechanism.
+    mechanism: Mechanism,
+}
+
+impl Directive {
+    /// Creates a new directive from its components.
+    pub fn new(qualifier: Qualifier, mechanism: Mechanism) -> Self {
+        Directive {
+            qualifier,
+            mechanism,
+        }
+    }
+
+    pub fn qualifier(&self) -> Qualifier {
+        self.qualifier
+    }
+
+    pub fn mechanism(&self) -> &Mechanism {
+        &self.mechanism
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for Directive {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (qualifier, s) =
+            match s.chars().next().and_then(Qualifier::from_char) {
+                Some(qualifier) => (qualifier, &s[1..]),
+                None => (Qualifier::Pass, s),
+            };
+        Ok(Directive::new(qualifier, Mechanism::from_str(s)?))
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.qualifier != Qualifier::Pass {
+            write!(f, "{}", self.qualifier)?;
+        }
+        self.mechanism.fmt(f)
+    }
+}
+
+//------------ Qualifier -----------------------------------------------------
+
+/// The result of a directive if its mechanism matches.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Qualifier {
+    /// The host is allowed to send mail (`+`).
+    #[default]
+    Pass,
+
+    /// The host is not allowed to send mail (`-`).
+    Fail,
+
+    /// The host is probably not allowed to send mail (`~`).
+    SoftFail,
+
+    /// No statement is made about the host (`?`).
+    Neutral,
+}
+
+impl Qualifier {
+    /// Returns the qualifier for its character.
+    pub fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            '+' => Some(Qualifier::Pass),
+            '-' => Some(Qualifier::Fail),
+            '~' => Some(Qualifier::SoftFail),
+            '?' => Some(Qualifier::Neutral),
+            _ => None,
+        }
+    }
+
+    /// Returns the character for the qualifier.
+    pub fn to_char(self) -> char {
+        match self {
+            Qualifier::Pass => '+',
+            Qualifier::Fail => '-',
+            Qualifier::SoftFail => '~',
+            Qualifier::Neutral => '?',
+        }
+    }
+}
+
+impl fmt::Display for Qualifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+//------------ Mechanism -----------------------------------------------------
+
+/// A mechanism matching the sending host.
+///
+/// Domain specifications are optional for some mechanisms. If they are
+/// missing, the domain the record was found at is used.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mechanism {
+    /// Always matches.
+    All,
+
+    /// Matches if the SPF record of the domain matches.
+    Include(String),
+
+    /// Matches if the host has one of the addresses of the domain.
+    ///
+    /// The prefix lengths for IPv4 and IPv6 addresses are optional.
+    A {
+        domain: Option<String>,
+        ip4_prefix: Option<u8>,
+        ip6_prefix: Option<u8>,
+    },
+
+    /// Matches if the host is one of the mail exchangers of the domain.
+    ///
+    /// The prefix lengths for IPv4 and IPv6 addresses are optional.
+    Mx {
+        domain: Option<String>,
+        ip4_prefix: Option<u8>,
+        ip6_prefix: Option<u8>,
+    },
+
+    /// Matches if the host’s validated reverse name is within the domain.
+    ///
+    /// The use of this mechanism is discouraged.
+    Ptr(Option<String>),
+
+    /// Matches if the host’s IPv4 address is within the network.
+    Ip4 { addr: Ipv4Addr, prefix: Option<u8> },
+
+    /// Matches if the host’s IPv6 address is within the network.
+    Ip6 { addr: Ipv6Addr, prefix: Option<u8> },
+
+    /// Matches if the domain has an A record.
+    Exists(String),
+}
+
+//--- FromStr
+
+impl FromStr for Mechanism {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let end = s.find([':', '/']).unwrap_or(s.len());
+        let (name, arg) = s.split_at(end);
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "all" => {
+                if !arg.is_empty() {
+                    return Err(SyntaxError::new("unexpected argument"));
+                }
+                Ok(Mechanism::All)
+            }
+            "include" => Ok(Mechanism::Include(required_domain(arg)?)),
+            "exists" => Ok(Mechanism::Exists(required_domain(arg)?)),
+            "ptr" => Ok(Mechanism::Ptr(optional_domain(arg)?)),
+            "a" | "mx" => {
+                let (domain, cidr) = split_cidr(arg);
+                let domain = optional_domain(domain)?;
+                let (ip4_prefix, ip6_prefix) = dual_cidr(cidr)?;
+                if name == "a" {
+                    Ok(Mechanism::A {
+                        domain,
+                        ip4_prefix,
+                        ip6_prefix,
+                    })
+                } else {
+                    Ok(Mechanism::Mx {
+                        domain,
+                        ip4_prefix,
+                        ip6_prefix,
+                    })
+                }
+            }
+            "ip4" | "ip6" => {
+                let arg = match arg.strip_prefix(':') {
+                    Some(arg) => arg,
+                    None => return Err(SyntaxError::new("missing address")),
+                };
+                let (addr, prefix) = match arg.split_once('/') {
+                    Some((addr, prefix)) => (addr, Some(prefix)),
+                    None => (arg, None),
+                };
+                if name == "ip4" {
+                    Ok(Mechanism::Ip4 {
+                        addr: addr
+                            .parse()
+                            .map_err(|_| SyntaxError::new("bad address"))?,
+                        prefix: prefix.map(|p| cidr(p, 32)).transpose()?,
+                    })
+                } else {
+                    Ok(Mechanism::Ip6 {
+                        addr: addr
+                            .parse()
+                            .map_err(|_| SyntaxError::new("bad address"))?,
+                        prefix: prefix.map(|p| cidr(p, 128)).transpose()?,
+                    })
+                }
+            }
+            _ => Err(SyntaxError::new("unknown mechanism")),
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mechanism::All => f.write_str("all"),
+            Mechanism::Include(domain) => write!(f, "include:{}", domain),
+            Mechanism::A {
+                domain,
+                ip4_prefix,
+                ip6_prefix,
+            } => {
+                f.write_str("a")?;
+                fmt_host(f, domain, ip4_prefix, ip6_prefix)
+            }
+            Mechanism::Mx {
+                domain,
+                ip4_prefix,
+                ip6_prefix,
+            } => {
+                f.write_str("mx")?;
+                fmt_host(f, domain, ip4_prefix, ip6_prefix)
+            }
+            Mechanism::Ptr(domain) => {
+                f.write_str("ptr")?;
+                fmt_host(f, domain, &None, &None)
+            }
+            Mechanism::Ip4 { addr, prefix } => {
+                write!(f, "ip4:{}", addr)?;
+                fmt_host(f, &None, prefix, &None)
+            }
+            Mechanism::Ip6 { addr, prefix } => {
+                write!(f, "ip6:{}", addr)?;
+                fmt_host(f, &None, prefix, &None)
+            }
+            Mechanism::Exists(domain) => write!(f, "exists:{}", domain),
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether a string is a valid modifier name.
+fn is_modifier_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || "-_.".contains(ch))
+}
+
+/// Parses the domain argument of a mechanism that requires it.
+fn required_domain(arg: &str) -> Result<String, SyntaxError> {
+    optional_domain(arg)?.ok_or(SyntaxError::new("missing domain"))
+}
+
+/// Parses the domain argument of a mechanism where it is optional.
+fn optional_domain(arg: &str) -> Result<Option<String>, SyntaxError> {
+    if arg.is_empty() {
+        return Ok(None);
+    }
+    match arg.strip_prefix(':') {
+        Some("") | None => Err(SyntaxError::new("missing domain")),
+        Some(domain) => Ok(Some(domain.to_string())),
+    }
+}
+
+/// Splits the argument of a mechanism into domain and CIDR lengths.
+///
+/// As domain specifications may contain macros with slashes as
+/// delimiters, only a slash outside of a macro starts the CIDR lengths.
+fn split_cidr(arg: &str) -> (&str, &str) {
+    let mut in_macro = false;
+    let mut prev = '\0';
+    for (pos, ch) in arg.char_indices() {
+        match ch {
+            '{' if prev == '%' => in_macro = true,
+            '}' => in_macro = false,
+            '/' if !in_macro => return arg.split_at(pos),
+            _ => {}
+        }
+        prev = ch;
+    }
+    (arg, "")
+}
+
+/// Parses the dual CIDR lengths of the `a` and `mx` mechanisms.
+fn dual_cidr(s: &str) -> Result<(Option<u8>, Option<u8>), SyntaxError> {
+    if s.is_empty() {
+        return Ok((None, None));
+    }
+    if let Some(ip6) = s.strip_prefix("//") {
+        return Ok((None, Some(cidr(ip6, 128)?)));
+    }
+    let s = &s[1..];
+    match s.split_once("//") {
+        Some((ip4, ip6)) => Ok((Some(cidr(ip4, 32)?), Some(cidr(ip6, 128)?))),
+        None => Ok((Some(cidr(s, 32)?), None)),
+    }
+}
+
+/// Parses a CIDR prefix length with the given maximum.
+fn cidr(s: &str, max: u8) -> Result<u8, SyntaxError> {
+    // Leading zeros are not allowed.
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) {
+        return Err(SyntaxError::new("bad prefix length"));
+    }
+    match u8::from_str(s) {
+        Ok(len) if len <= max => Ok(len),
+        _ => Err(SyntaxError::new("bad prefix length")),
+    }
+}
+
+/// Formats the optional domain and prefix lengths of a mechanism.
+fn fmt_host(
+    f: &mut fmt::Formatter,
+    domain: &Option<String>,
+    ip4_prefix: &Option<u8>,
+    ip6_prefix: &Option<u8>,
+) -> fmt::Result {
+    if let Some(domain) = domain {
+        write!(f, ":{}", domain)?;
+    }
+    if let Some(prefix) = ip4_prefix {
+        write!(f, "/{}", prefix)?;
+    }
+    if let Some(prefix) = ip6_prefix {
+        write!(f, "//{}", prefix)?;
+    }
+    Ok(())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let record = SpfRecord::from_str(
+            "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 a mx:mail.%{d}/24//64 \
+             -ptr ?include:_spf.example.com ~exists:%{ir/}.example.com \
+             redirect=_spf.example.net x-custom=1",
+        )
+        .unwrap();
+        assert_eq!(
+            record.directives(),
+            [
+                Directive::new(
+                    Qualifier::Pass,
+                    Mechanism::Ip4 {
+                        addr: Ipv4Addr::new(192, 0, 2, 0),
+                        prefix: Some(24)
+                    }
+                ),
+                Directive::new(
+                    Qualifier::Pass,
+                    Mechanism::Ip6 {
+                        addr: "2001:db8::".parse().unwrap(),
+                        prefix: Some(32)
+                    }
+                ),
+                Directive::new(
+                    Qualifier::Pass,
+                    Mechanism::A {
+                        domain: None,
+                        ip4_prefix: None,
+                        ip6_prefix: None
+                    }
+                ),
+                Directive::new(
+                    Qualifier::Pass,
+                    Mechanism::Mx {
+                        domain: Some("mail.%{d}".into()),
+                        ip4_prefix: Some(24),
+                        ip6_prefix: Some(64)
+                    }
+                ),
+                Directive::new(Qualifier::Fail, Mechanism::Ptr(None)),
+                Directive::new(
+                    Qualifier::Neutral,
+                    Mechanism::Include("_spf.example.com".into())
+                ),
+                Directive::new(
+                    Qualifier::SoftFail,
+                    Mechanism::Exists("%{ir/}.example.com".into())
+                ),
+            ]
+        );
+        assert_eq!(record.redirect(), Some("_spf.example.net"));
+        assert_eq!(record.exp(), None);
+        assert_eq!(record.all(), None);
+        assert_eq!(
+            record.unknown_modifiers(),
+            [("x-custom".into(), "1".into())]
+        );
+        assert_eq!(SpfRecord::from_str(&record.to_string()).unwrap(), record);
+
+        let record = SpfRecord::from_str("V=SPF1 MX -All").unwrap();
+        assert_eq!(record.all(), Some(Qualifier::Fail));
+        assert_eq!(record.to_string(), "v=spf1 mx -all");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(!SpfRecord::is_spf("v=spf10 -all"));
+        assert!(SpfRecord::from_str("v=DMARC1; p=none").is_err());
+        assert!(SpfRecord::from_str("v=spf1 all:example.com").is_err());
+        assert!(SpfRecord::from_str("v=spf1 include").is_err());
+        assert!(SpfRecord::from_str("v=spf1 ip4:192.0.2.0/33").is_err());
+        assert!(SpfRecord::from_str("v=spf1 ip4:2001:db8::").is_err());
+        assert!(SpfRecord::from_str("v=spf1 a/024").is_err());
+        assert!(SpfRecord::from_str("v=spf1 foo:example.com").is_err());
+        assert!(SpfRecord::from_str("v=spf1 redirect=a redirect=b").is_err());
+    }
+
+    #[test]
+    fn from_txt() {
+        let txt =
+            Txt::from_octets(b"\x0fv=spf1 ip4:192.\x0a0.2.1 -all".to_vec())
+                .unwrap();
+        let record = SpfRecord::from_txt(&txt).unwrap();
+        assert_eq!(record.directives().len(), 2);
+        assert_eq!(record.all(), Some(Qualifier::Fail));
+    }
+}
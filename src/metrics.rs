@@ -0,0 +1,528 @@
+//! Instrumentation of resolvers and servers.
+//!
+//! The stub resolver and the servers can report what they are doing to a
+//! type implementing the [`Metrics`] trait. Each method of the trait
+//! corresponds to an event, such as an answer received from an upstream
+//! server, a lookup in the cache, or a request served. All methods have
+//! empty default implementations, so an implementation only needs to
+//! provide those it is interested in.
+//!
+//! The methods are called directly by the task processing the event.
+//! They should therefore be cheap – updating a counter or a histogram of
+//! a metrics library is fine, writing to a file is not.
+//!
+//! All events of the resolver that happen while resolving a question
+//! carry the [`ResolutionId`] of that resolution. Its beginning and end
+//! are reported via [`Metrics::resolution_started`] and
+//! [`Metrics::resolution_finished`]. This allows correlating the events
+//! of a resolution. With the `tracing` feature, the resolver additionally
+//! creates spans for its resolutions that carry the same identifier.
+//! See the stub resolver’s module documentation for details.
+//! Answers needed for validation, such as DNSKEY or DS records, are
+//! resolutions of their own.
+//!
+//! A resolver receives the metrics via
+#![cfg_attr(
+    feature = "resolv",
+    doc = "[`StubResolver::with_metrics`][crate::resolv::StubResolver::with_metrics]."
+)]
+#![cfg_attr(not(feature = "resolv"), doc = "`StubResolver::with_metrics`.")]
+//! For servers, the
+#![cfg_attr(
+    feature = "serve",
+    doc = "[`InstrumentLayer`][crate::serve::middleware::InstrumentLayer]"
+)]
+#![cfg_attr(not(feature = "serve"), doc = "`InstrumentLayer`")]
+//! wraps a service and reports its requests and responses.
+//!
+//! The type [`Counters`] provides a simple implementation of the trait
+//! that counts events in atomic integers. It can be used to expose
+//! statistics without pulling in a metrics library.
+#![cfg(any(feature = "resolv", feature = "serve"))]
+#![cfg_attr(docsrs, doc(cfg(any(feature = "resolv", feature = "serve"))))]
+
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::name::ParsedDname;
+use crate::base::question::Question;
+#[cfg(feature = "validate")]
+use crate::validate::chain::SecurityStatus;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::vec::Vec;
+use std::{fmt, io};
+
+//------------ Metrics -------------------------------------------------------
+
+/// A type receiving events from resolvers and servers.
+///
+/// See the [module documentation][self] for details.
+pub trait Metrics: Send + Sync {
+    /// The resolution of a question has started.
+    fn resolution_started(
+        &self,
+        _id: ResolutionId,
+        _question: &Question<ParsedDname<&[u8]>>,
+    ) {
+    }
+
+    /// The resolution of a question has finished.
+    ///
+    /// The `elapsed` time is the time since the resolution started. The
+    /// result contains the rcode of the answer if there is one.
+    fn resolution_finished(
+        &self,
+        _id: ResolutionId,
+        _elapsed: Duration,
+        _result: Result<Rcode, &io::Error>,
+    ) {
+    }
+
+    /// An upstream server has responded to a query.
+    ///
+    /// The `rtt` is the time between sending the query and receiving the
+    /// response.
+    fn upstream_response(
+        &self,
+        _id: ResolutionId,
+        _server: SocketAddr,
+        _rtt: Duration,
+        _rcode: Rcode,
+    ) {
+    }
+
+    /// A query to an upstream server has failed.
+    ///
+    /// This includes timeouts as well as responses that were rejected.
+    fn upstream_error(
+        &self,
+        _id: ResolutionId,
+        _server: SocketAddr,
+        _error: &io::Error,
+    ) {
+    }
+
+    /// An answer was found in the cache.
+    ///
+    /// If `stale` is `true`, the answer had expired already.
+    fn cache_hit(&self, _id: ResolutionId, _stale: bool) {}
+
+    /// No answer was found in the cache.
+    fn cache_miss(&self, _id: ResolutionId) {}
+
+    /// An answer was validated.
+    #[cfg(feature = "validate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+    fn validated(&self, _id: ResolutionId, _status: SecurityStatus) {}
+
+    /// A server has processed a request.
+    ///
+    /// The `qtype` is that of the request’s first question, if there is
+    /// one. The `rcode` is that of the response or `None` if no response
+    /// is sent. The `elapsed` time is the time it took to produce the
+    /// response.
+    fn request_served(
+        &self,
+        _qtype: Option<Rtype>,
+        _rcode: Option<Rcode>,
+        _elapsed: Duration,
+    ) {
+    }
+
+    /// A server has sent a message of a zone transfer.
+    ///
+    /// The `octets` are the size of the message.
+    fn transfer_sent(&self, _octets: usize) {}
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn resolution_started(
+        &self,
+        id: ResolutionId,
+        question: &Question<ParsedDname<&[u8]>>,
+    ) {
+        (**self).resolution_started(id, question)
+    }
+
+    fn resolution_finished(
+        &self,
+        id: ResolutionId,
+        elapsed: Duration,
+        result: Result<Rcode, &io::Error>,
+    ) {
+        (**self).resolution_finished(id, elapsed, result)
+    }
+
+    fn upstream_response(
+        &self,
+        id: ResolutionId,
+        server: SocketAddr,
+        rtt: Duration,
+        rcode: Rcode,
+    ) {
+        (**self).upstream_response(id, server, rtt, rcode)
+    }
+
+    fn upstream_error(
+        &self,
+        id: ResolutionId,
+        server: SocketAddr,
+        error: &io::Error,
+    ) {
+        (**self).upstream_error(id, server, error)
+    }
+
+    fn cache_hit(&self, id: ResolutionId, stale: bool) {
+        (**self).cache_hit(id, stale)
+    }
+
+    fn cache_miss(&self, id: ResolutionId) {
+        (**self).cache_miss(id)
+    }
+
+    #[cfg(feature = "validate")]
+    fn validated(&self, id: ResolutionId, status: SecurityStatus) {
+        (**self).validated(id, status)
+    }
+
+    fn request_served(
+        &self,
+        qtype: Option<Rtype>,
+        rcode: Option<Rcode>,
+        elapsed: Duration,
+    ) {
+        (**self).request_served(qtype, rcode, elapsed)
+    }
+
+    fn transfer_sent(&self, octets: usize) {
+        (**self).transfer_sent(octets)
+    }
+}
+
+//------------ ResolutionId --------------------------------------------------
+
+/// The identifier of a resolution.
+///
+/// Identifiers are unique for the lifetime of the process.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ResolutionId(u64);
+
+impl ResolutionId {
+    /// Returns a new, unique identifier.
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ResolutionId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the identifier as an integer.
+    pub fn into_int(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ResolutionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+//------------ Counters ------------------------------------------------------
+
+/// Metrics counting events.
+///
+/// Since the resolvers and servers take ownership of their metrics, wrap
+/// the counters in an [`Arc`] and keep a clone for reading them.
+#[derive(Debug, Default)]
+pub struct Counters {
+    /// The number of upstream responses.
+    upstream_responses: AtomicU64,
+
+    /// The sum of the round trip times of upstream responses in
+    /// microseconds.
+    upstream_rtt: AtomicU64,
+
+    /// The number of failed upstream queries.
+    upstream_errors: AtomicU64,
+
+    /// The number of fresh cache hits.
+    cache_hits: AtomicU64,
+
+    /// The number of stale cache hits.
+    stale_hits: AtomicU64,
+
+    /// The number of cache misses.
+    cache_misses: AtomicU64,
+
+    /// The number of validated answers for each security status.
+    ///
+    /// The order is secure, insecure, bogus, and indeterminate.
+    #[cfg(feature = "validate")]
+    validated: [AtomicU64; 4],
+
+    /// The number of served requests per query type.
+    requests: Mutex<HashMap<Option<Rtype>, u64>>,
+
+    /// The number of responses for each rcode.
+    responses: [AtomicU64; 16],
+
+    /// The number of requests without a response.
+    dropped: AtomicU64,
+
+    /// The number of octets sent in zone transfers.
+    transfer_octets: AtomicU64,
+}
+
+impl Counters {
+    /// Creates a new set of counters, all zero.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn upstream_responses(&self) -> u64 {
+        self.upstream_responses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the mean round trip time of upstream responses.
+    ///
+    /// Returns `None` if there haven’t been any responses yet.
+    pub fn mean_upstream_rtt(&self) -> Option<Duration> {
+        let count = self.upstream_responses();
+        if count == 0 {
+            return None;
+        }
+        Some(Duration::from_micros(
+            self.upstream_rtt.load(Ordering::Relaxed) / count,
+        ))
+    }
+
+    pub fn upstream_errors(&self) -> u64 {
+        self.upstream_errors.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cache hits with fresh answers.
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cache hits with stale answers.
+    pub fn stale_hits(&self) -> u64 {
+        self.stale_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the share of cache lookups that found an answer.
+    ///
+    /// Stale answers count as hits. Returns `None` if there haven’t been
+    /// any lookups yet.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let hits = self.cache_hits() + self.stale_hits();
+        let total = hits + self.cache_misses();
+        if total == 0 {
+            None
+        } else {
+            Some(hits as f64 / total as f64)
+        }
+    }
+
+    /// Returns the number of validated answers with the given status.
+    ///
+    /// The reason of the status is ignored.
+    #[cfg(feature = "validate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+    pub fn validations(&self, status: SecurityStatus) -> u64 {
+        self.validated[Self::status_index(status)].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of served requests with the given query type.
+    ///
+    /// Requests without a question are counted for `None`.
+    pub fn requests(&self, qtype: Option<Rtype>) -> u64 {
+        self.requests
+            .lock()
+            .unwrap()
+            .get(&qtype)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of served requests for each query type.
+    pub fn requests_by_qtype(&self) -> Vec<(Option<Rtype>, u64)> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(qtype, count)| (*qtype, *count))
+            .collect()
+    }
+
+    /// Returns the number of responses sent with the given rcode.
+    pub fn responses(&self, rcode: Rcode) -> u64 {
+        self.responses[usize::from(rcode.to_int() & 0x0F)]
+            .load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests that didn’t receive a response.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of octets sent in zone transfers.
+    pub fn transfer_octets(&self) -> u64 {
+        self.transfer_octets.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "validate")]
+    fn status_index(status: SecurityStatus) -> usize {
+        match status {
+            SecurityStatus::Secure => 0,
+            SecurityStatus::Insecure(_) => 1,
+            SecurityStatus::Bogus(_) => 2,
+            SecurityStatus::Indeterminate(_) => 3,
+        }
+    }
+}
+
+impl Metrics for Counters {
+    fn upstream_response(
+        &self,
+        _id: ResolutionId,
+        _server: SocketAddr,
+        rtt: Duration,
+        _rcode: Rcode,
+    ) {
+        self.upstream_responses.fetch_add(1, Ordering::Relaxed);
+        self.upstream_rtt.fetch_add(
+            u64::try_from(rtt.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn upstream_error(
+        &self,
+        _id: ResolutionId,
+        _server: SocketAddr,
+        _error: &io::Error,
+    ) {
+        self.upstream_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cache_hit(&self, _id: ResolutionId, stale: bool) {
+        if stale {
+            self.stale_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn cache_miss(&self, _id: ResolutionId) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "validate")]
+    fn validated(&self, _id: ResolutionId, status: SecurityStatus) {
+        self.validated[Self::status_index(status)]
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn request_served(
+        &self,
+        qtype: Option<Rtype>,
+        rcode: Option<Rcode>,
+        _elapsed: Duration,
+    ) {
+        *self.requests.lock().unwrap().entry(qtype).or_default() += 1;
+        match rcode {
+            Some(rcode) => {
+                self.responses[usize::from(rcode.to_int() & 0x0F)]
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn transfer_sent(&self, octets: usize) {
+        self.transfer_octets.fetch_add(
+            u64::try_from(octets).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let counters = Arc::new(Counters::new());
+        let metrics: Arc<dyn Metrics> = counters.clone();
+        let server = "192.0.2.1:53".parse().unwrap();
+        let id = ResolutionId::next();
+        assert_ne!(id, ResolutionId::next());
+
+        assert!(counters.mean_upstream_rtt().is_none());
+        assert!(counters.cache_hit_ratio().is_none());
+
+        metrics.upstream_response(
+            id,
+            server,
+            Duration::from_millis(10),
+            Rcode::NoError,
+        );
+        metrics.upstream_response(
+            id,
+            server,
+            Duration::from_millis(30),
+            Rcode::NXDomain,
+        );
+        metrics.upstream_error(
+            id,
+            server,
+            &io::Error::new(io::ErrorKind::TimedOut, "timeout"),
+        );
+        assert_eq!(counters.upstream_responses(), 2);
+        assert_eq!(counters.upstream_errors(), 1);
+        assert_eq!(
+            counters.mean_upstream_rtt(),
+            Some(Duration::from_millis(20))
+        );
+
+        metrics.cache_hit(id, false);
+        metrics.cache_hit(id, true);
+        metrics.cache_miss(id);
+        metrics.cache_miss(id);
+        assert_eq!(counters.cache_hits(), 1);
+        assert_eq!(counters.stale_hits(), 1);
+        assert_eq!(counters.cache_hit_ratio(), Some(0.5));
+
+        metrics.request_served(
+            Some(Rtype::A),
+            Some(Rcode::NoError),
+            Duration::ZERO,
+        );
+        metrics.request_served(
+            Some(Rtype::A),
+            Some(Rcode::Refused),
+            Duration::ZERO,
+        );
+        metrics.request_served(None, None, Duration::ZERO);
+        metrics.transfer_sent(512);
+        assert_eq!(counters.requests(Some(Rtype::A)), 2);
+        assert_eq!(counters.requests(None), 1);
+        assert_eq!(counters.requests(Some(Rtype::Aaaa)), 0);
+        assert_eq!(counters.requests_by_qtype().len(), 2);
+        assert_eq!(counters.responses(Rcode::NoError), 1);
+        assert_eq!(counters.responses(Rcode::Refused), 1);
+        assert_eq!(counters.dropped(), 1);
+        assert_eq!(counters.transfer_octets(), 512);
+    }
+}
@@ -6,7 +6,7 @@
 
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::Rtype;
-use crate::base::net::Ipv6Addr;
+use crate::base::net::{IpAddr, Ipv6Addr};
 use crate::base::rdata::{ComposeRecordData, ParseRecordData, RecordData};
 use crate::base::scan::{Scanner, ScannerError};
 use crate::base::wire::{Composer, Parse, ParseError};
@@ -68,12 +68,24 @@ impl From<Ipv6Addr> for Aaaa {
     }
 }
 
+impl From<[u8; 16]> for Aaaa {
+    fn from(octets: [u8; 16]) -> Self {
+        Self::new(octets.into())
+    }
+}
+
 impl From<Aaaa> for Ipv6Addr {
     fn from(data: Aaaa) -> Self {
         data.addr
     }
 }
 
+impl From<Aaaa> for IpAddr {
+    fn from(data: Aaaa) -> Self {
+        IpAddr::V6(data.addr)
+    }
+}
+
 impl FromStr for Aaaa {
     type Err = <Ipv6Addr as core::str::FromStr>::Err;
 
@@ -181,4 +193,15 @@ mod test {
         test_compose_parse(&rdata, Aaaa::parse);
         test_scan(&[addr], Aaaa::scan, &rdata);
     }
+
+    #[test]
+    fn aaaa_convert() {
+        let mut octets = [0; 16];
+        octets[..2].copy_from_slice(&[0x20, 0x01]);
+        octets[15] = 1;
+        let rdata = Aaaa::from(octets);
+        assert_eq!(rdata, Aaaa::new(Ipv6Addr::from(octets)));
+        assert_eq!(Ipv6Addr::from(rdata.clone()).octets(), octets);
+        assert_eq!(IpAddr::from(rdata), IpAddr::from(octets));
+    }
 }
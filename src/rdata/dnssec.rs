@@ -14,10 +14,16 @@ use crate::base::Ttl;
 use crate::base::scan::{Scan, Scanner, ScannerError};
 use crate::base::serial::Serial;
 use crate::base::wire::{Compose, Composer, FormError, Parse, ParseError};
+#[cfg(any(feature = "sign", feature = "validate"))]
+use crate::crypto::{AlgorithmError, Backend};
+#[cfg(all(feature = "ring", any(feature = "sign", feature = "validate")))]
+use crate::crypto::RingBackend;
 use crate::utils::{base16, base64};
 use core::cmp::Ordering;
 use core::convert::TryInto;
 use core::{fmt, hash, ptr};
+#[cfg(any(feature = "sign", feature = "validate"))]
+use octseq::builder::infallible;
 use octseq::builder::{
     EmptyBuilder, FreezeBuilder, FromBuilder, OctetsBuilder, Truncate,
 };
@@ -573,22 +579,16 @@ impl<Name: ToDname> ProtoRrsig<Name> {
         self.signer_name.compose_canonical(target)
     }
 
-    fn compose_len(&self) -> u16 {
-        Rtype::COMPOSE_LEN
-            + SecAlg::COMPOSE_LEN
-            + u8::COMPOSE_LEN
-            + u32::COMPOSE_LEN
-            + Serial::COMPOSE_LEN
-            + Serial::COMPOSE_LEN
-            + u16::COMPOSE_LEN
-            + self.signer_name.compose_len()
-    }
-
+    /// Composes the fields preceding the signer name.
+    ///
+    /// The signed data starts with the RDATA itself, i.e., there is no
+    /// length prefix ([RFC 4034, section 3.1.8.1]).
+    ///
+    /// [RFC 4034, section 3.1.8.1]: https://tools.ietf.org/html/rfc4034#section-3.1.8.1
     fn compose_head<Target: Composer + ?Sized>(
         &self,
         target: &mut Target,
     ) -> Result<(), Target::AppendError> {
-        self.compose_len().compose(target)?;
         self.type_covered.compose(target)?;
         self.algorithm.compose(target)?;
         self.labels.compose(target)?;
@@ -1572,6 +1572,122 @@ impl<Octs> Ds<Octs> {
     }
 }
 
+#[cfg(any(feature = "sign", feature = "validate"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "sign", feature = "validate"))))]
+impl<Octs> Ds<Octs> {
+    /// Creates the DS record data for a DNSKEY record.
+    ///
+    /// The digest is calculated over the canonical form of the DNSKEY
+    /// record’s owner name followed by its record data as described in
+    /// [RFC 4034, section 5.1.4] using the [`RingBackend`] which supports
+    /// SHA-1, SHA-256, and SHA-384. Digests using GOST R 34.11-94 need a
+    /// backend that supports it via
+    /// [`from_dnskey_with`][Self::from_dnskey_with].
+    ///
+    /// [RFC 4034, section 5.1.4]: https://tools.ietf.org/html/rfc4034#section-5.1.4
+    #[cfg(feature = "ring")]
+    pub fn from_dnskey<N, KeyOcts>(
+        owner: &N,
+        dnskey: &Dnskey<KeyOcts>,
+        digest_type: DigestAlg,
+    ) -> Result<Self, AlgorithmError>
+    where
+        Octs: From<Vec<u8>> + AsRef<[u8]>,
+        N: ToDname + ?Sized,
+        KeyOcts: AsRef<[u8]>,
+    {
+        Self::from_dnskey_with(&RingBackend, owner, dnskey, digest_type)
+    }
+
+    /// Creates the DS record data for a DNSKEY record using a backend.
+    pub fn from_dnskey_with<N, KeyOcts>(
+        backend: &impl Backend,
+        owner: &N,
+        dnskey: &Dnskey<KeyOcts>,
+        digest_type: DigestAlg,
+    ) -> Result<Self, AlgorithmError>
+    where
+        Octs: From<Vec<u8>> + AsRef<[u8]>,
+        N: ToDname + ?Sized,
+        KeyOcts: AsRef<[u8]>,
+    {
+        let digest = dnskey_digest(backend, owner, dnskey, digest_type)?;
+        Ds::new(
+            dnskey.key_tag(),
+            dnskey.algorithm(),
+            digest_type,
+            digest.into(),
+        )
+        .map_err(|_| AlgorithmError::InvalidData)
+    }
+
+    /// Returns whether the DS record refers to a DNSKEY record.
+    ///
+    /// The key tag and algorithm of the DNSKEY record must match those of
+    /// the DS record and the digest calculated over `owner` and the
+    /// DNSKEY record must match the DS record’s digest. The digest is
+    /// calculated using the [`RingBackend`].
+    ///
+    /// Returns an error if key tag and algorithm match but the DS record’s
+    /// digest type isn’t supported.
+    #[cfg(feature = "ring")]
+    pub fn matches_dnskey<N, KeyOcts>(
+        &self,
+        owner: &N,
+        dnskey: &Dnskey<KeyOcts>,
+    ) -> Result<bool, AlgorithmError>
+    where
+        Octs: AsRef<[u8]>,
+        N: ToDname + ?Sized,
+        KeyOcts: AsRef<[u8]>,
+    {
+        self.matches_dnskey_with(&RingBackend, owner, dnskey)
+    }
+
+    /// Returns whether the DS record refers to a DNSKEY record.
+    ///
+    /// This is the same as [`matches_dnskey`][Self::matches_dnskey] but
+    /// calculates the digest using the given backend.
+    pub fn matches_dnskey_with<N, KeyOcts>(
+        &self,
+        backend: &impl Backend,
+        owner: &N,
+        dnskey: &Dnskey<KeyOcts>,
+    ) -> Result<bool, AlgorithmError>
+    where
+        Octs: AsRef<[u8]>,
+        N: ToDname + ?Sized,
+        KeyOcts: AsRef<[u8]>,
+    {
+        if self.key_tag != dnskey.key_tag()
+            || self.algorithm != dnskey.algorithm()
+        {
+            return Ok(false);
+        }
+        let digest =
+            dnskey_digest(backend, owner, dnskey, self.digest_type)?;
+        Ok(digest.as_slice() == self.digest.as_ref())
+    }
+}
+
+/// Calculates the digest of a DNSKEY record for its DS record.
+#[cfg(any(feature = "sign", feature = "validate"))]
+fn dnskey_digest<N, KeyOcts>(
+    backend: &impl Backend,
+    owner: &N,
+    dnskey: &Dnskey<KeyOcts>,
+    digest_type: DigestAlg,
+) -> Result<Vec<u8>, AlgorithmError>
+where
+    N: ToDname + ?Sized,
+    KeyOcts: AsRef<[u8]>,
+{
+    let mut buf = Vec::new();
+    infallible(owner.compose_canonical(&mut buf));
+    infallible(dnskey.compose_canonical_rdata(&mut buf));
+    backend.digest(digest_type, &buf)
+}
+
 impl<SrcOcts> Ds<SrcOcts> {
     pub fn flatten_into<Octs>(self) -> Result<Ds<Octs>, PushError>
     where
@@ -1825,10 +1941,10 @@ impl<Octs> RtypeBitmap<Octs> {
     pub fn scan<S: Scanner<Octets = Octs>>(
         scanner: &mut S,
     ) -> Result<Self, S::Error> {
-        let first = Rtype::scan(scanner)?;
+        // The bitmap may be empty, e.g., for the NSEC3 record of an empty
+        // non-terminal.
         let mut builder =
             RtypeBitmapBuilder::with_builder(scanner.octets_builder()?);
-        builder.add(first).map_err(|_| S::Error::short_buf())?;
         while scanner.continues() {
             builder
                 .add(Rtype::scan(scanner)?)
@@ -2478,6 +2594,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn rrsig_large_original_ttl() {
+        let rdata = Rrsig::new(
+            Rtype::A,
+            SecAlg::RsaSha1,
+            3,
+            Ttl::from_secs(0x8000_0000),
+            Serial::from(13),
+            Serial::from(14),
+            15,
+            Dname::<Vec<u8>>::from_str("example.com.").unwrap(),
+            b"key",
+        )
+        .unwrap();
+        test_compose_parse(&rdata, |parser| Rrsig::parse(parser));
+    }
+
     //--- Nsec
 
     #[test]
@@ -2505,6 +2638,74 @@ mod test {
         test_scan(&["10", "RSASHA1", "2", "6b6579"], Ds::scan, &rdata);
     }
 
+    #[test]
+    #[cfg(all(feature = "ring", any(feature = "sign", feature = "validate")))]
+    fn ds_from_dnskey() {
+        // The examples from RFC 4034, section 5.4 and RFC 4509,
+        // section 2.3.
+        let owner = Dname::<Vec<u8>>::from_str("dskey.example.com.").unwrap();
+        let dnskey = Dnskey::new(
+            256,
+            3,
+            SecAlg::RsaSha1,
+            base64::decode::<Vec<u8>>(
+                "AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/\
+                 2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMmmAphXdvx\
+                 egXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc\
+                 nOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let sha1 =
+            Ds::<Vec<u8>>::from_dnskey(&owner, &dnskey, DigestAlg::Sha1)
+                .unwrap();
+        assert_eq!(sha1.key_tag(), 60485);
+        assert_eq!(sha1.algorithm(), SecAlg::RsaSha1);
+        assert_eq!(
+            sha1.digest().as_slice(),
+            base16::decode_vec("2BB183AF5F22588179A53B0A98631FAD1A292118")
+                .unwrap()
+                .as_slice()
+        );
+        let sha256 =
+            Ds::<Vec<u8>>::from_dnskey(&owner, &dnskey, DigestAlg::Sha256)
+                .unwrap();
+        assert_eq!(
+            sha256.digest().as_slice(),
+            base16::decode_vec(
+                "D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A"
+            )
+            .unwrap()
+            .as_slice()
+        );
+        assert_eq!(sha1.matches_dnskey(&owner, &dnskey), Ok(true));
+        assert_eq!(sha256.matches_dnskey(&owner, &dnskey), Ok(true));
+        assert_eq!(
+            sha256.matches_dnskey(Dname::root_slice(), &dnskey),
+            Ok(false)
+        );
+
+        let sha384 =
+            Ds::<Vec<u8>>::from_dnskey(&owner, &dnskey, DigestAlg::Sha384)
+                .unwrap();
+        assert_eq!(sha384.digest().len(), 48);
+        assert_eq!(sha384.matches_dnskey(&owner, &dnskey), Ok(true));
+
+        assert_eq!(
+            Ds::<Vec<u8>>::from_dnskey(&owner, &dnskey, DigestAlg::Gost),
+            Err(AlgorithmError::Unsupported)
+        );
+        let gost =
+            Ds::new(60485, SecAlg::RsaSha1, DigestAlg::Gost, vec![0; 32])
+                .unwrap();
+        assert_eq!(
+            gost.matches_dnskey(&owner, &dnskey),
+            Err(AlgorithmError::Unsupported)
+        );
+    }
+
     //--- RtypeBitmape
 
     #[test]
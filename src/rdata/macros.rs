@@ -479,6 +479,29 @@ macro_rules! rdata_types {
         /// This enum collects the record data types for all currently
         /// implemented record types.
         #[derive(Clone)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize)
+        )]
+        #[cfg_attr(
+            feature = "serde",
+            serde(bound(
+                serialize = "
+                    O: AsRef<[u8]> + octseq::serde::SerializeOctets,
+                    N: serde::Serialize,
+                ",
+                deserialize = "
+                    O: AsRef<[u8]>
+                        + octseq::builder::FromBuilder
+                        + octseq::serde::DeserializeOctets<'de>,
+                    <O as octseq::builder::FromBuilder>::Builder:
+                          octseq::builder::EmptyBuilder
+                        + octseq::builder::Truncate
+                        + AsRef<[u8]> + AsMut<[u8]>,
+                    N: serde::Deserialize<'de>,
+                ",
+            ))
+        )]
         #[non_exhaustive]
         pub enum AllRecordData<O, N> {
             $( $( $(
@@ -513,6 +536,48 @@ macro_rules! rdata_types {
             }
         }
 
+        #[cfg(feature = "bytes")]
+        impl<Target> AllRecordData<Target, $crate::base::Dname<Target>>
+        where
+            Target: OctetsFrom<bytes::Bytes> + FromBuilder,
+            <Target as FromBuilder>::Builder: EmptyBuilder,
+        {
+            /// Converts any record data into the matching variant.
+            ///
+            /// The record data is composed into its wire format and parsed
+            /// again according to its record type. This turns record data
+            /// only known by its trait implementations – including
+            /// [`UnknownRecordData`] of a type that is actually known – into
+            /// the variant for its record type so that it can be matched
+            /// exhaustively.
+            ///
+            /// Returns an error if the wire format isn’t valid for the
+            /// record type.
+            ///
+            /// [`UnknownRecordData`]: $crate::base::rdata::UnknownRecordData
+            pub fn from_rdata<Data>(
+                data: &Data
+            ) -> Result<Self, $crate::base::wire::ParseError>
+            where Data: ComposeRecordData + ?Sized {
+                use $crate::base::rdata::ParseRecordData;
+                use $crate::base::wire::ParseError;
+
+                let mut buf = bytes::BytesMut::new();
+                data.compose_rdata(&mut buf).unwrap_or_else(|err| match err {});
+                let buf = buf.freeze();
+                let mut parser = octseq::parse::Parser::from_ref(&buf);
+                let res = AllRecordData::parse_rdata(
+                    data.rtype(), &mut parser
+                )?.ok_or_else(|| ParseError::form_error("no record data"))?;
+                if parser.remaining() != 0 {
+                    return Err(ParseError::form_error("trailing data"));
+                }
+                res.flatten_into().map_err(|_| {
+                    ParseError::form_error("long domain name")
+                })
+            }
+        }
+
         impl<Octs, NOcts> AllRecordData<Octs, ParsedDname<NOcts>> {
             pub fn flatten_into<Target>(
                 self,
@@ -588,6 +653,53 @@ macro_rules! rdata_types {
             }
         }
 
+        impl<O, N> From<ZoneRecordData<O, N>> for AllRecordData<O, N> {
+            fn from(value: ZoneRecordData<O, N>) -> Self {
+                match value {
+                    $( $( $(
+                        ZoneRecordData::$mtype(inner) => {
+                            AllRecordData::$mtype(inner)
+                        }
+                    )* )* )*
+                    ZoneRecordData::Unknown(inner) => {
+                        AllRecordData::Unknown(inner)
+                    }
+                }
+            }
+        }
+
+        $( $( $(
+            impl<O, N> TryFrom<AllRecordData<O, N>>
+            for $mtype $( < $( $mn ),* > )* {
+                type Error = AllRecordData<O, N>;
+
+                fn try_from(
+                    value: AllRecordData<O, N>
+                ) -> Result<Self, Self::Error> {
+                    match value {
+                        AllRecordData::$mtype(inner) => Ok(inner),
+                        value => Err(value),
+                    }
+                }
+            }
+        )* )* )*
+
+        $( $( $(
+            impl<O, N> TryFrom<AllRecordData<O, N>>
+            for $ptype $( < $( $pn ),* > )* {
+                type Error = AllRecordData<O, N>;
+
+                fn try_from(
+                    value: AllRecordData<O, N>
+                ) -> Result<Self, Self::Error> {
+                    match value {
+                        AllRecordData::$ptype(inner) => Ok(inner),
+                        value => Err(value),
+                    }
+                }
+            }
+        )* )* )*
+
         impl<O, N> From<AllRecordData<O, N>>
         for Result<ZoneRecordData<O, N>, AllRecordData<O, N>> {
             fn from(
@@ -23,6 +23,11 @@
 //! contains those types that can appear in zone files plus, again,
 //! [`UnknownRecordData`][crate::base::rdata::UnknownRecordData] for
 //! everything else.
+//!
+//! Each record data type converts into both enums via `From` and back out
+//! of [`AllRecordData`] via `TryFrom`. With the `bytes` feature,
+//! `AllRecordData::from_rdata` converts any record data, including unknown
+//! record data of a known type, into the variant for its record type.
 
 // A note on implementing record types with embedded domain names with regards
 // to compression and canonical representation:
@@ -130,3 +135,53 @@ rdata_types! {
         }
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(all(feature = "std", feature = "bytes"))]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::rdata::UnknownRecordData;
+    use crate::base::Dname as Name;
+    use core::str::FromStr;
+    use std::net::Ipv4Addr;
+    use std::vec::Vec;
+
+    type Data = AllRecordData<Vec<u8>, Name<Vec<u8>>>;
+
+    #[test]
+    fn from_rdata() {
+        let unknown =
+            UnknownRecordData::from_octets(Rtype::A, [192, 0, 2, 1]).unwrap();
+        assert_eq!(
+            Data::from_rdata(&unknown).unwrap(),
+            Data::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+
+        let mx =
+            Mx::new(10, Name::<Vec<u8>>::from_str("mx.example").unwrap());
+        assert_eq!(Data::from_rdata(&mx).unwrap(), Data::Mx(mx.clone()));
+
+        let unknown =
+            UnknownRecordData::from_octets(Rtype::A, [192, 0, 2]).unwrap();
+        assert!(Data::from_rdata(&unknown).is_err());
+        let unknown =
+            UnknownRecordData::from_octets(Rtype::Int(0xff00), [1, 2])
+                .unwrap();
+        assert!(matches!(
+            Data::from_rdata(&unknown).unwrap(),
+            AllRecordData::Unknown(_)
+        ));
+    }
+
+    #[test]
+    fn conversions() {
+        let a = A::new(Ipv4Addr::new(192, 0, 2, 1));
+        let zone = ZoneRecordData::<Vec<u8>, Name<Vec<u8>>>::from(a.clone());
+        let data = Data::from(zone);
+        assert_eq!(A::try_from(data.clone()).unwrap(), a);
+        assert_eq!(Aaaa::try_from(data.clone()).unwrap_err(), data);
+    }
+}
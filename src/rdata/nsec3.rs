@@ -968,8 +968,13 @@ impl<T: AsRef<[u8]> + ?Sized> hash::Hash for Nsec3Salt<T> {
 //--- Display and Debug
 
 impl<Octs: AsRef<[u8]> + ?Sized> fmt::Display for Nsec3Salt<Octs> {
+    /// Formats the salt in hex digits or as a single `-` if it is empty.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        base16::display(self.as_slice(), f)
+        if self.as_slice().is_empty() {
+            f.write_str("-")
+        } else {
+            base16::display(self.as_slice(), f)
+        }
     }
 }
 
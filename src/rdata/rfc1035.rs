@@ -8,11 +8,13 @@ use crate::base::charstr::CharStr;
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::Rtype;
 use crate::base::name::{Dname, ParsedDname, PushError, ToDname};
-use crate::base::net::Ipv4Addr;
+use crate::base::net::{IpAddr, Ipv4Addr};
 use crate::base::rdata::{
     ComposeRecordData, LongRecordData, ParseRecordData, RecordData,
 };
-use crate::base::scan::{Scan, Scanner, ScannerError, Symbol};
+#[cfg(feature = "serde")]
+use crate::base::scan::Symbol;
+use crate::base::scan::{Scan, Scanner, ScannerError};
 use crate::base::serial::Serial;
 use crate::base::wire::{Compose, Composer, FormError, Parse, ParseError};
 use crate::base::Ttl;
@@ -105,12 +107,24 @@ impl From<Ipv4Addr> for A {
     }
 }
 
+impl From<[u8; 4]> for A {
+    fn from(octets: [u8; 4]) -> Self {
+        Self::new(octets.into())
+    }
+}
+
 impl From<A> for Ipv4Addr {
     fn from(a: A) -> Self {
         a.addr
     }
 }
 
+impl From<A> for IpAddr {
+    fn from(a: A) -> Self {
+        IpAddr::V4(a.addr)
+    }
+}
+
 impl FromStr for A {
     type Err = <Ipv4Addr as FromStr>::Err;
 
@@ -406,7 +420,12 @@ impl<Octs: AsRef<[u8]>> ComposeRecordData for Hinfo<Octs> {
 
 impl<Octs: AsRef<[u8]>> fmt::Display for Hinfo<Octs> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {}", self.cpu, self.os)
+        write!(
+            f,
+            "{} {}",
+            self.cpu.display_quoted(),
+            self.os.display_quoted()
+        )
     }
 }
 
@@ -1244,6 +1263,15 @@ impl<N> Soa<N> {
         self.serial
     }
 
+    /// Sets the serial number.
+    ///
+    /// The methods for the various serial number schemes of [`Serial`]
+    /// can be used to determine the serial number of a new version of the
+    /// zone.
+    pub fn set_serial(&mut self, serial: Serial) {
+        self.serial = serial
+    }
+
     /// The time interval before the zone should be refreshed.
     pub fn refresh(&self) -> Ttl {
         self.refresh
@@ -1884,10 +1912,13 @@ impl<Octs: AsRef<[u8]>> ComposeRecordData for Txt<Octs> {
 
 impl<Octs: AsRef<[u8]>> fmt::Display for Txt<Octs> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for slice in self.iter() {
-            for ch in slice.iter() {
-                fmt::Display::fmt(&Symbol::from_octet(*ch), f)?
+        let mut first = true;
+        for slice in self.iter_char_strs() {
+            if !first {
+                f.write_str(" ")?;
             }
+            first = false;
+            fmt::Display::fmt(&slice.display_quoted(), f)?
         }
         Ok(())
     }
@@ -2269,6 +2300,17 @@ mod test {
         test_scan(&["1.2.3.4"], A::scan, &rdata);
     }
 
+    #[test]
+    fn a_convert() {
+        let rdata = A::from([192, 0, 2, 1]);
+        assert_eq!(rdata, A::from_octets(192, 0, 2, 1));
+        assert_eq!(
+            Ipv4Addr::from(rdata.clone()),
+            Ipv4Addr::new(192, 0, 2, 1)
+        );
+        assert_eq!(IpAddr::from(rdata), IpAddr::from([192, 0, 2, 1]));
+    }
+
     //--- Cname
     //
     // This covers all the other generated types, too.
@@ -2381,6 +2423,20 @@ mod test {
         );
     }
 
+    #[test]
+    fn soa_large_timers() {
+        let rdata = Soa::<Dname<Vec<u8>>>::new(
+            Dname::from_str("m.example.com").unwrap(),
+            Dname::from_str("r.example.com").unwrap(),
+            Serial(11),
+            Ttl::from_secs(0x8000_0000),
+            Ttl::from_secs(0x8000_0001),
+            Ttl::from_secs(0xFFFF_FFFE),
+            Ttl::MAX,
+        );
+        test_compose_parse(&rdata, |parser| Soa::parse(parser));
+    }
+
     //--- Txt
 
     #[test]
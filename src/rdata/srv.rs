@@ -270,7 +270,7 @@ impl<N: fmt::Display> fmt::Display for Srv<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} {} {} {}",
+            "{} {} {} {}.",
             self.priority, self.weight, self.port, self.target
         )
     }
@@ -404,7 +404,7 @@ where
     Name: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {}", self.priority, self.target, self.params)
+        write!(f, "{} {}. {}", self.priority, self.target, self.params)
     }
 }
 
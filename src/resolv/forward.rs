@@ -0,0 +1,236 @@
+//! A resolver forwarding queries to different upstreams by name.
+//!
+//! A forwarding resolver hands queries to one of several stub resolvers
+//! depending on the query name. Each of these routes is configured for a
+//! domain name and receives all queries for that name and all names below
+//! it. If the routes of several names match, the one for the longest name
+//! wins. All queries not matching any route go to the default resolver.
+//!
+//! Since each route is a [`StubResolver`] of its own, it has its own set of
+//! servers, its own transport, and its own cache and validator, if any.
+//! This way, queries for an internal domain can be sent to internal
+//! servers over plain UDP while all other queries go to a public resolver
+//! over an encrypted transport. Likewise, the DNSSEC policy is set per
+//! route: a route can validate answers itself via a validator, rely on
+//! its servers via the `dnssec_ok` option, or leave DNSSEC off entirely.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use core::str::FromStr;
+//! use domain::base::iana::Rtype;
+//! use domain::base::Dname;
+//! use domain::resolv::forward::Forwarder;
+//! use domain::resolv::stub::conf::{ResolvConf, ServerConf, Transport};
+//! use domain::resolv::StubResolver;
+//!
+//! # async fn run() {
+//! let mut conf = ResolvConf::new();
+//! conf.servers.push(ServerConf::new(
+//!     "10.0.0.53:53".parse().unwrap(),
+//!     Transport::Udp,
+//! ));
+//! conf.finalize();
+//! let internal = StubResolver::from_conf(conf);
+//!
+//! let mut forwarder = Forwarder::new(StubResolver::new());
+//! forwarder.insert(Dname::from_str("corp.example").unwrap(), internal);
+//!
+//! let qname = Dname::<Vec<u8>>::from_str("www.corp.example").unwrap();
+//! let answer = forwarder.query((qname, Rtype::A)).await.unwrap();
+//! # }
+//! ```
+
+use super::resolver::{Resolver, SearchNames};
+use super::stub::conf::SearchSuffix;
+use super::stub::{Answer, SearchIter, StubResolver};
+use crate::base::name::{Dname, ToDname};
+use crate::base::question::Question;
+use bytes::Bytes;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+
+//------------ Forwarder -----------------------------------------------------
+
+/// A resolver routing queries to stub resolvers by query name.
+///
+/// See the [module documentation][self] for more details.
+#[derive(Clone, Debug)]
+pub struct Forwarder {
+    /// The resolver for queries not matching any route.
+    default: StubResolver,
+
+    /// The resolvers for the names with a route.
+    routes: HashMap<Dname<Bytes>, StubResolver>,
+}
+
+impl Forwarder {
+    /// Creates a new forwarder sending all queries to `default`.
+    pub fn new(default: StubResolver) -> Self {
+        Forwarder {
+            default,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Routes queries for a name and all names below it to a resolver.
+    ///
+    /// Returns the resolver previously routed to for exactly this name, if
+    /// there was one.
+    pub fn insert(
+        &mut self,
+        name: Dname<Bytes>,
+        resolver: StubResolver,
+    ) -> Option<StubResolver> {
+        self.routes.insert(name, resolver)
+    }
+
+    /// Removes the route for exactly the given name.
+    pub fn remove<N: ToDname + ?Sized>(
+        &mut self,
+        name: &N,
+    ) -> Option<StubResolver> {
+        self.routes.remove(&name.to_dname::<Bytes>().ok()?)
+    }
+
+    pub fn default_resolver(&self) -> &StubResolver {
+        &self.default
+    }
+
+    /// Returns an iterator over the names and resolvers of all routes.
+    pub fn routes(
+        &self,
+    ) -> impl Iterator<Item = (&Dname<Bytes>, &StubResolver)> {
+        self.routes.iter()
+    }
+
+    /// Returns the resolver a query for the given name is routed to.
+    pub fn route<N: ToDname + ?Sized>(&self, qname: &N) -> &StubResolver {
+        if self.routes.is_empty() {
+            return &self.default;
+        }
+        let qname = match qname.to_dname::<Bytes>() {
+            Ok(qname) => qname,
+            Err(_) => return &self.default,
+        };
+        qname
+            .iter_suffixes()
+            .find_map(|suffix| self.routes.get(&suffix))
+            .unwrap_or(&self.default)
+    }
+
+    /// Answers a question via the resolver routed to for its name.
+    pub async fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer, io::Error> {
+        Resolver::query(&self, question).await
+    }
+}
+
+//--- Resolver and SearchNames
+
+impl<'a> Resolver for &'a Forwarder {
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query =
+        Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + Send + 'a>>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        let resolver = self.route(question.qname());
+        Resolver::query(&resolver, question)
+    }
+}
+
+/// The search list is the one of the default resolver.
+impl<'a> SearchNames for &'a Forwarder {
+    type Name = SearchSuffix;
+    type Iter = SearchIter<'a>;
+
+    fn search_iter(&self) -> Self::Iter {
+        (&self.default).search_iter()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::resolv::stub::conf::ResolvConf;
+    use crate::resolv::stub::transport::{
+        Request, Transport, TransportFuture,
+    };
+    use core::str::FromStr;
+
+    /// A transport answering every request with the given rcode.
+    struct RcodeTransport(Rcode);
+
+    impl Transport for RcodeTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                let answer = MessageBuilder::new_bytes()
+                    .start_answer(&request.message(), self.0)
+                    .unwrap();
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    fn resolver(rcode: Rcode) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        StubResolver::from_conf_with_transport(conf, RcodeTransport(rcode))
+    }
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    async fn rcode(forwarder: &Forwarder, qname: &str) -> Rcode {
+        let answer = forwarder.query((name(qname), Rtype::A)).await.unwrap();
+        answer.header().rcode()
+    }
+
+    #[tokio::test]
+    async fn routing() {
+        let mut forwarder = Forwarder::new(resolver(Rcode::NoError));
+        forwarder.insert(name("corp.example"), resolver(Rcode::NXDomain));
+        forwarder.insert(name("lab.corp.example"), resolver(Rcode::Refused));
+
+        assert_eq!(rcode(&forwarder, "example").await, Rcode::NoError);
+        assert_eq!(rcode(&forwarder, "xcorp.example").await, Rcode::NoError);
+        assert_eq!(rcode(&forwarder, "corp.example").await, Rcode::NXDomain);
+        assert_eq!(
+            rcode(&forwarder, "www.CORP.example").await,
+            Rcode::NXDomain
+        );
+        assert_eq!(
+            rcode(&forwarder, "lab.corp.example").await,
+            Rcode::Refused
+        );
+        assert_eq!(
+            rcode(&forwarder, "a.b.lab.corp.example").await,
+            Rcode::Refused
+        );
+
+        assert!(forwarder.remove(&name("lab.corp.example")).is_some());
+        assert_eq!(
+            rcode(&forwarder, "lab.corp.example").await,
+            Rcode::NXDomain
+        );
+    }
+}
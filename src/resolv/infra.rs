@@ -0,0 +1,812 @@
+//! A cache of delegations and name server information.
+//!
+//! A resolver that iterates from the root zone down to an answer needs to
+//! remember more than answers: which name servers are authoritative for
+//! the zones it has visited, their addresses, how fast they respond,
+//! whether they support EDNS, and which of them turned out to be lame for
+//! a zone. The [`InfraCache`] keeps this information apart from the answer
+//! [cache] so that it has its own expiry and can’t be replaced by
+//! answers.
+//!
+//! The crate doesn’t contain an iterative resolver yet. The cache is
+//! provided as a building block for one.
+//!
+//! # Poisoning defenses
+//!
+//! Delegations and addresses are only accepted for names within the
+//! bailiwick of the server that provided them, i.e., at or below the zone
+//! the server was asked about as an authoritative server. A referral from a
+//! server for `example.` can delegate `sub.example.` but not `example.org.`
+//! and can provide addresses for `ns.sub.example.` but not for
+//! `ns.example.org.`.
+//!
+//! In addition, data is ranked by its [`Credibility`] as suggested by
+//! [RFC 2181, section 5.4.1]. Data from an authoritative answer replaces
+//! data from a referral or glue but not the other way round until the
+//! more credible data has expired.
+//!
+//! [cache]: super::stub::cache
+//! [RFC 2181, section 5.4.1]: https://tools.ietf.org/html/rfc2181#section-5.4.1
+
+use crate::base::name::{Dname, ToDname};
+use crate::base::Ttl;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The round-trip time assumed for a server after its first timeout.
+const TIMEOUT_RTT: Duration = Duration::from_secs(1);
+
+/// The largest round-trip time recorded for a server.
+const MAX_RTT: Duration = Duration::from_secs(120);
+
+//------------ InfraCache ----------------------------------------------------
+
+/// A cache of delegations, name server addresses, and server information.
+///
+/// The cache keeps up to its capacity of each kind of entry. When one of
+/// them is full, expired entries are removed. If there still is no room,
+/// new entries of that kind are rejected until some have expired.
+///
+/// The cache can be shared between threads. All methods except for those
+/// changing the configuration take `&self`.
+pub struct InfraCache {
+    /// The maximum number of entries of each kind.
+    capacity: usize,
+
+    /// The maximum time delegations and addresses are kept.
+    max_ttl: Ttl,
+
+    /// The time a server is considered lame for a zone.
+    lame_ttl: Ttl,
+
+    /// The time information on a server is kept after its last update.
+    server_ttl: Ttl,
+
+    /// The entries.
+    inner: Mutex<Inner>,
+}
+
+impl InfraCache {
+    /// The default maximum time delegations and addresses are kept.
+    pub const DEFAULT_MAX_TTL: Ttl = Ttl::DAY;
+
+    /// The default time a server is considered lame for a zone.
+    pub const DEFAULT_LAME_TTL: Ttl = Ttl::from_mins(15);
+
+    /// The default time information on a server is kept.
+    pub const DEFAULT_SERVER_TTL: Ttl = Ttl::from_mins(15);
+
+    /// Creates a new cache holding up to `capacity` entries of each kind.
+    pub fn new(capacity: usize) -> Self {
+        InfraCache {
+            capacity,
+            max_ttl: Self::DEFAULT_MAX_TTL,
+            lame_ttl: Self::DEFAULT_LAME_TTL,
+            server_ttl: Self::DEFAULT_SERVER_TTL,
+            inner: Default::default(),
+        }
+    }
+
+    /// Sets the maximum time delegations and addresses are kept.
+    ///
+    /// Records with a larger TTL expire after this time.
+    pub fn set_max_ttl(&mut self, ttl: Ttl) {
+        self.max_ttl = ttl
+    }
+
+    pub fn max_ttl(&self) -> Ttl {
+        self.max_ttl
+    }
+
+    /// Sets the time a server is considered lame after it was marked.
+    pub fn set_lame_ttl(&mut self, ttl: Ttl) {
+        self.lame_ttl = ttl
+    }
+
+    pub fn lame_ttl(&self) -> Ttl {
+        self.lame_ttl
+    }
+
+    /// Sets the time information on a server is kept.
+    ///
+    /// The round-trip time and EDNS support of a server are forgotten if
+    /// they haven’t been updated for this long.
+    pub fn set_server_ttl(&mut self, ttl: Ttl) {
+        self.server_ttl = ttl
+    }
+
+    pub fn server_ttl(&self) -> Ttl {
+        self.server_ttl
+    }
+
+    /// Returns the number of entries of all kinds currently kept.
+    ///
+    /// This may include entries that have expired but haven’t been
+    /// removed yet.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.delegations.len()
+            + inner.addrs.len()
+            + inner.servers.len()
+            + inner.lame.len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = Default::default()
+    }
+
+    /// Adds the name servers of a zone.
+    ///
+    /// The `bailiwick` is the zone the name servers were received from.
+    /// A referral must delegate a zone strictly below it, an authoritative
+    /// answer may be for the zone itself. The name servers replace those
+    /// already kept for the zone unless those are more credible and
+    /// haven’t expired yet.
+    pub fn insert_delegation<N: ToDname>(
+        &self,
+        zone: &impl ToDname,
+        nameservers: impl IntoIterator<Item = N>,
+        ttl: Ttl,
+        bailiwick: &impl ToDname,
+        credibility: Credibility,
+    ) -> Result<(), Rejected> {
+        self.insert_delegation_at(
+            zone,
+            nameservers,
+            ttl,
+            bailiwick,
+            credibility,
+            Instant::now(),
+        )
+    }
+
+    /// Returns the delegation closest to `qname`.
+    ///
+    /// This is the delegation for the longest zone name that `qname` is
+    /// equal to or below.
+    pub fn delegation(&self, qname: &impl ToDname) -> Option<Delegation> {
+        self.delegation_at(qname, Instant::now())
+    }
+
+    /// Adds the addresses of a name server.
+    ///
+    /// The `bailiwick` is the zone the addresses were received from. The
+    /// name server must be at or below it. The addresses replace those
+    /// already kept for the name server unless those are more credible and
+    /// haven’t expired yet.
+    pub fn insert_addrs(
+        &self,
+        nameserver: &impl ToDname,
+        addrs: impl IntoIterator<Item = IpAddr>,
+        ttl: Ttl,
+        bailiwick: &impl ToDname,
+        credibility: Credibility,
+    ) -> Result<(), Rejected> {
+        self.insert_addrs_at(
+            nameserver,
+            addrs,
+            ttl,
+            bailiwick,
+            credibility,
+            Instant::now(),
+        )
+    }
+
+    /// Returns the addresses of a name server if they are known.
+    pub fn addrs(&self, nameserver: &impl ToDname) -> Option<Vec<IpAddr>> {
+        self.addrs_at(nameserver, Instant::now())
+    }
+
+    /// Records the round-trip time of a response from a server.
+    ///
+    /// The smoothed round-trip time of the server is updated from the
+    /// measurement as described in RFC 6298.
+    pub fn record_rtt(&self, addr: IpAddr, rtt: Duration) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(server) = self.server_mut(&mut inner, addr, now) {
+            server.srtt = Some(match server.srtt {
+                Some(srtt) => (srtt * 7 + rtt) / 8,
+                None => rtt,
+            });
+        }
+    }
+
+    /// Records that a request to a server has timed out.
+    ///
+    /// This doubles the smoothed round-trip time of the server, so that
+    /// other servers are preferred.
+    pub fn record_timeout(&self, addr: IpAddr) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(server) = self.server_mut(&mut inner, addr, now) {
+            server.srtt = Some(match server.srtt {
+                Some(srtt) => (srtt * 2).min(MAX_RTT),
+                None => TIMEOUT_RTT,
+            });
+        }
+    }
+
+    /// Returns the smoothed round-trip time of a server if it is known.
+    pub fn rtt(&self, addr: IpAddr) -> Option<Duration> {
+        self.server(addr, Instant::now())?.srtt
+    }
+
+    /// Sorts server addresses by their smoothed round-trip time.
+    ///
+    /// Servers without a known round-trip time are sorted first, so that
+    /// they get tried, too.
+    pub fn sort_by_rtt(&self, addrs: &mut [IpAddr]) {
+        let now = Instant::now();
+        addrs.sort_by_cached_key(|addr| {
+            self.server(*addr, now)
+                .and_then(|server| server.srtt)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Records whether a server supports EDNS.
+    pub fn set_edns(&self, addr: IpAddr, edns: EdnsSupport) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(server) = self.server_mut(&mut inner, addr, now) {
+            server.edns = Some(edns);
+        }
+    }
+
+    /// Returns whether a server supports EDNS if it is known.
+    pub fn edns(&self, addr: IpAddr) -> Option<EdnsSupport> {
+        self.server(addr, Instant::now())?.edns
+    }
+
+    /// Marks a server as lame for a zone.
+    ///
+    /// A server is lame if it is listed as a name server for the zone but
+    /// doesn’t answer authoritatively for it. It should not be asked
+    /// about the zone again until the lame TTL has passed.
+    pub fn mark_lame(&self, addr: IpAddr, zone: &impl ToDname) {
+        self.mark_lame_at(addr, zone, Instant::now())
+    }
+
+    /// Returns whether a server is currently considered lame for a zone.
+    pub fn is_lame(&self, addr: IpAddr, zone: &impl ToDname) -> bool {
+        self.is_lame_at(addr, zone, Instant::now())
+    }
+
+    fn insert_delegation_at<N: ToDname>(
+        &self,
+        zone: &impl ToDname,
+        nameservers: impl IntoIterator<Item = N>,
+        ttl: Ttl,
+        bailiwick: &impl ToDname,
+        credibility: Credibility,
+        now: Instant,
+    ) -> Result<(), Rejected> {
+        if !zone.ends_with(bailiwick)
+            || (credibility < Credibility::Answer && zone.name_eq(bailiwick))
+        {
+            return Err(Rejected::OutOfBailiwick);
+        }
+        let entry = Entry {
+            data: nameservers
+                .into_iter()
+                .map(|name| name.to_bytes())
+                .collect(),
+            credibility,
+            expires: now + ttl.min(self.max_ttl).into_duration(),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        insert(
+            &mut inner.delegations,
+            zone.to_bytes(),
+            entry,
+            self.capacity,
+            now,
+        )
+    }
+
+    fn delegation_at(
+        &self,
+        qname: &impl ToDname,
+        now: Instant,
+    ) -> Option<Delegation> {
+        let qname = qname.to_bytes();
+        let inner = self.inner.lock().unwrap();
+        qname.iter_suffixes().find_map(|zone| {
+            let zone = zone.to_bytes();
+            let entry = inner
+                .delegations
+                .get(&zone)
+                .filter(|entry| entry.expires > now)?;
+            Some(Delegation {
+                nameservers: entry.data.clone(),
+                credibility: entry.credibility,
+                zone,
+            })
+        })
+    }
+
+    fn insert_addrs_at(
+        &self,
+        nameserver: &impl ToDname,
+        addrs: impl IntoIterator<Item = IpAddr>,
+        ttl: Ttl,
+        bailiwick: &impl ToDname,
+        credibility: Credibility,
+        now: Instant,
+    ) -> Result<(), Rejected> {
+        if !nameserver.ends_with(bailiwick) {
+            return Err(Rejected::OutOfBailiwick);
+        }
+        let entry = Entry {
+            data: addrs.into_iter().collect(),
+            credibility,
+            expires: now + ttl.min(self.max_ttl).into_duration(),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        insert(
+            &mut inner.addrs,
+            nameserver.to_bytes(),
+            entry,
+            self.capacity,
+            now,
+        )
+    }
+
+    fn addrs_at(
+        &self,
+        nameserver: &impl ToDname,
+        now: Instant,
+    ) -> Option<Vec<IpAddr>> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .addrs
+            .get(&nameserver.to_bytes())
+            .filter(|entry| entry.expires > now)
+            .map(|entry| entry.data.clone())
+    }
+
+    fn mark_lame_at(&self, addr: IpAddr, zone: &impl ToDname, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.lame.len() >= self.capacity {
+            inner.lame.retain(|_, expires| *expires > now);
+            if inner.lame.len() >= self.capacity {
+                return;
+            }
+        }
+        inner.lame.insert(
+            (addr, zone.to_bytes()),
+            now + self.lame_ttl.into_duration(),
+        );
+    }
+
+    fn is_lame_at(
+        &self,
+        addr: IpAddr,
+        zone: &impl ToDname,
+        now: Instant,
+    ) -> bool {
+        let inner = self.inner.lock().unwrap();
+        matches!(
+            inner.lame.get(&(addr, zone.to_bytes())),
+            Some(expires) if *expires > now
+        )
+    }
+
+    /// Returns the unexpired information on a server.
+    fn server(&self, addr: IpAddr, now: Instant) -> Option<Server> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .servers
+            .get(&addr)
+            .filter(|server| server.expires > now)
+            .copied()
+    }
+
+    /// Returns the information on a server for updating.
+    ///
+    /// Expired information is reset and the server’s expiry is extended.
+    /// Returns `None` if the server isn’t known and there is no room.
+    fn server_mut<'a>(
+        &self,
+        inner: &'a mut Inner,
+        addr: IpAddr,
+        now: Instant,
+    ) -> Option<&'a mut Server> {
+        if !inner.servers.contains_key(&addr)
+            && inner.servers.len() >= self.capacity
+        {
+            inner.servers.retain(|_, server| server.expires > now);
+            if inner.servers.len() >= self.capacity {
+                return None;
+            }
+        }
+        let server = inner.servers.entry(addr).or_insert(Server {
+            srtt: None,
+            edns: None,
+            expires: now,
+        });
+        if server.expires <= now {
+            server.srtt = None;
+            server.edns = None;
+        }
+        server.expires = now + self.server_ttl.into_duration();
+        Some(server)
+    }
+}
+
+impl fmt::Debug for InfraCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InfraCache")
+            .field("capacity", &self.capacity)
+            .field("max_ttl", &self.max_ttl)
+            .field("lame_ttl", &self.lame_ttl)
+            .field("server_ttl", &self.server_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+//------------ Inner ---------------------------------------------------------
+
+/// The entries of the cache.
+#[derive(Default)]
+struct Inner {
+    /// The name servers by zone.
+    delegations: HashMap<Dname<Bytes>, Entry<Vec<Dname<Bytes>>>>,
+
+    /// The addresses by name server.
+    addrs: HashMap<Dname<Bytes>, Entry<Vec<IpAddr>>>,
+
+    /// The information on servers by address.
+    servers: HashMap<IpAddr, Server>,
+
+    /// When servers stop being lame for zones.
+    lame: HashMap<(IpAddr, Dname<Bytes>), Instant>,
+}
+
+/// Data received from a server.
+struct Entry<T> {
+    data: T,
+
+    /// How much the data can be trusted.
+    credibility: Credibility,
+
+    /// When the data expires.
+    expires: Instant,
+}
+
+/// The information on a server.
+#[derive(Clone, Copy)]
+struct Server {
+    /// The smoothed round-trip time.
+    srtt: Option<Duration>,
+
+    /// Whether the server supports EDNS.
+    edns: Option<EdnsSupport>,
+
+    /// When the information expires.
+    expires: Instant,
+}
+
+/// Adds an entry unless a more credible one exists or there is no room.
+fn insert<K: Eq + Hash, T>(
+    map: &mut HashMap<K, Entry<T>>,
+    key: K,
+    entry: Entry<T>,
+    capacity: usize,
+    now: Instant,
+) -> Result<(), Rejected> {
+    match map.get(&key) {
+        Some(old) if old.expires > now => {
+            if old.credibility > entry.credibility {
+                return Err(Rejected::LessCredible);
+            }
+        }
+        Some(_) => {}
+        None => {
+            if map.len() >= capacity {
+                map.retain(|_, entry| entry.expires > now);
+                if map.len() >= capacity {
+                    return Err(Rejected::Full);
+                }
+            }
+        }
+    }
+    map.insert(key, entry);
+    Ok(())
+}
+
+//------------ Delegation ----------------------------------------------------
+
+/// The name servers of a zone.
+#[derive(Clone, Debug)]
+pub struct Delegation {
+    zone: Dname<Bytes>,
+    nameservers: Vec<Dname<Bytes>>,
+    credibility: Credibility,
+}
+
+impl Delegation {
+    pub fn zone(&self) -> &Dname<Bytes> {
+        &self.zone
+    }
+
+    pub fn nameservers(&self) -> &[Dname<Bytes>] {
+        &self.nameservers
+    }
+
+    pub fn credibility(&self) -> Credibility {
+        self.credibility
+    }
+}
+
+//------------ Credibility ---------------------------------------------------
+
+/// How much data received from a server can be trusted.
+///
+/// The variants are ordered from least to most credible.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Credibility {
+    /// The data is from the additional section, e.g., glue addresses.
+    Additional,
+
+    /// The data is from the authority section of a referral.
+    Referral,
+
+    /// The data is from the answer section of an authoritative answer.
+    Answer,
+}
+
+//------------ EdnsSupport ---------------------------------------------------
+
+/// Whether a server supports EDNS.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EdnsSupport {
+    /// The server supports EDNS with the given UDP payload size.
+    Supported(u16),
+
+    /// The server doesn’t support EDNS.
+    Unsupported,
+}
+
+//------------ Rejected ------------------------------------------------------
+
+/// The reason why data was not added to the cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rejected {
+    /// The name is outside the bailiwick of the server.
+    OutOfBailiwick,
+
+    /// The cache has more credible data for the name.
+    LessCredible,
+
+    /// There is no room in the cache.
+    Full,
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Rejected::OutOfBailiwick => "name out of bailiwick",
+            Rejected::LessCredible => "more credible data present",
+            Rejected::Full => "cache full",
+        })
+    }
+}
+
+impl error::Error for Rejected {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        if s == "." {
+            Dname::root_vec()
+        } else {
+            Dname::from_str(s).unwrap()
+        }
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn delegations() {
+        let cache = InfraCache::new(16);
+        let now = Instant::now();
+        let ns = [name("ns.example.")];
+        cache
+            .insert_delegation_at(
+                &name("example."),
+                ns.iter(),
+                Ttl::HOUR,
+                &name("."),
+                Credibility::Referral,
+                now,
+            )
+            .unwrap();
+
+        let delegation =
+            cache.delegation_at(&name("www.example."), now).unwrap();
+        assert_eq!(delegation.zone(), &name("example."));
+        assert_eq!(delegation.nameservers(), &[name("ns.example.")]);
+        assert!(cache.delegation_at(&name("example.org."), now).is_none());
+
+        // Out of bailiwick.
+        assert_eq!(
+            cache.insert_delegation_at(
+                &name("example.org."),
+                ns.iter(),
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Referral,
+                now,
+            ),
+            Err(Rejected::OutOfBailiwick)
+        );
+        assert_eq!(
+            cache.insert_delegation_at(
+                &name("example."),
+                ns.iter(),
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Referral,
+                now,
+            ),
+            Err(Rejected::OutOfBailiwick)
+        );
+
+        // The authoritative NS set replaces the referral but not the
+        // other way round until it has expired.
+        let child_ns = [name("a.ns.example."), name("b.ns.example.")];
+        cache
+            .insert_delegation_at(
+                &name("example."),
+                child_ns.iter(),
+                Ttl::MINUTE,
+                &name("example."),
+                Credibility::Answer,
+                now,
+            )
+            .unwrap();
+        assert_eq!(
+            cache.insert_delegation_at(
+                &name("example."),
+                ns.iter(),
+                Ttl::HOUR,
+                &name("."),
+                Credibility::Referral,
+                now,
+            ),
+            Err(Rejected::LessCredible)
+        );
+        assert_eq!(
+            cache
+                .delegation_at(&name("example."), now)
+                .unwrap()
+                .nameservers()
+                .len(),
+            2
+        );
+        let later = now + Duration::from_secs(61);
+        assert!(cache.delegation_at(&name("example."), later).is_none());
+        cache
+            .insert_delegation_at(
+                &name("example."),
+                ns.iter(),
+                Ttl::HOUR,
+                &name("."),
+                Credibility::Referral,
+                later,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn addrs() {
+        let cache = InfraCache::new(1);
+        let now = Instant::now();
+        cache
+            .insert_addrs_at(
+                &name("ns.example."),
+                [addr("192.0.2.1")],
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Additional,
+                now,
+            )
+            .unwrap();
+        assert_eq!(
+            cache.addrs_at(&name("ns.example."), now),
+            Some(vec![addr("192.0.2.1")])
+        );
+        assert_eq!(
+            cache.insert_addrs_at(
+                &name("ns.example.org."),
+                [addr("192.0.2.66")],
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Additional,
+                now,
+            ),
+            Err(Rejected::OutOfBailiwick)
+        );
+        assert_eq!(
+            cache.insert_addrs_at(
+                &name("ns2.example."),
+                [addr("192.0.2.2")],
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Additional,
+                now,
+            ),
+            Err(Rejected::Full)
+        );
+
+        // The maximum TTL applies.
+        let later = now + Duration::from_secs(86401);
+        assert_eq!(cache.addrs_at(&name("ns.example."), later), None);
+        cache
+            .insert_addrs_at(
+                &name("ns2.example."),
+                [addr("192.0.2.2")],
+                Ttl::HOUR,
+                &name("example."),
+                Credibility::Additional,
+                later,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn servers() {
+        let cache = InfraCache::new(16);
+        let fast = addr("192.0.2.1");
+        let slow = addr("192.0.2.2");
+        let unknown = addr("192.0.2.3");
+        cache.record_rtt(fast, Duration::from_millis(80));
+        cache.record_rtt(fast, Duration::from_millis(160));
+        assert_eq!(cache.rtt(fast), Some(Duration::from_millis(90)));
+        cache.record_timeout(slow);
+        cache.record_timeout(slow);
+        assert_eq!(cache.rtt(slow), Some(Duration::from_secs(2)));
+
+        let mut addrs = [slow, fast, unknown];
+        cache.sort_by_rtt(&mut addrs);
+        assert_eq!(addrs, [unknown, fast, slow]);
+
+        assert_eq!(cache.edns(fast), None);
+        cache.set_edns(fast, EdnsSupport::Supported(1232));
+        assert_eq!(cache.edns(fast), Some(EdnsSupport::Supported(1232)));
+
+        let now = Instant::now();
+        cache.mark_lame_at(slow, &name("example."), now);
+        assert!(cache.is_lame_at(slow, &name("example."), now));
+        assert!(!cache.is_lame_at(fast, &name("example."), now));
+        assert!(!cache.is_lame_at(slow, &name("example.org."), now));
+        assert!(!cache.is_lame_at(
+            slow,
+            &name("example."),
+            now + InfraCache::DEFAULT_LAME_TTL.into_duration()
+        ));
+    }
+}
@@ -4,6 +4,7 @@ use crate::base::iana::Rtype;
 use crate::base::message::RecordIter;
 use crate::base::name::{Dname, DnameBuilder, ParsedDname};
 use crate::rdata::Ptr;
+use crate::resolv::lookup::dns64::Nat64Prefix;
 use crate::resolv::resolver::Resolver;
 use octseq::octets::Octets;
 use std::io;
@@ -24,11 +25,18 @@ type Octets128 = octseq::array::Array<128>;
 ///
 /// The value returned upon success can be turned into an iterator over
 /// host names via its `iter()` method. This is due to lifetime issues.
+///
+/// The address can be given as anything that converts into an `IpAddr`,
+/// such as `Ipv4Addr`, `Ipv6Addr`, or the octets of an address.
+///
+/// IPv6 addresses within the well-known NAT64 prefix `64:ff9b::/96` have
+/// been synthesized by DNS64 and are looked up via the IPv4 address
+/// embedded in them.
 pub async fn lookup_addr<R: Resolver>(
     resolv: &R,
-    addr: IpAddr,
+    addr: impl Into<IpAddr>,
 ) -> Result<FoundAddrs<R>, io::Error> {
-    let name = dname_from_addr(addr);
+    let name = dname_from_addr(unmap_nat64(addr.into()));
     resolv.query((name, Rtype::Ptr)).await.map(FoundAddrs)
 }
 
@@ -97,6 +105,17 @@ impl<'a, Octs: Octets> Iterator for FoundAddrsIter<'a, Octs> {
 
 //------------ Helper Functions ---------------------------------------------
 
+/// Returns the IPv4 address for an address in the well-known NAT64 prefix.
+fn unmap_nat64(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match Nat64Prefix::WELL_KNOWN.extract(v6) {
+            Some(v4) => v4.into(),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
 /// Translates an IP address into a domain name.
 fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
     match addr {
@@ -168,5 +187,11 @@ mod test {
             )
             .unwrap()
         );
+        assert_eq!(
+            dname_from_addr(unmap_nat64(
+                [0x64, 0xff9b, 0, 0, 0, 0, 0xc000, 0x20c].into()
+            )),
+            Dname::<Octets128>::from_str("12.2.0.192.in-addr.arpa").unwrap()
+        );
     }
 }
@@ -0,0 +1,196 @@
+//! Looking up all records of a name.
+//!
+//! Many servers don’t answer queries for QTYPE ANY anymore. Following
+//! RFC 8482 they either refuse them or answer with a single synthesized
+//! HINFO record or only some of the RRsets of the name. The functions in
+//! this module therefore allow decomposing an ANY query into queries for
+//! concrete record types.
+
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::ToDname;
+use crate::rdata::Hinfo;
+use crate::resolv::resolver::Resolver;
+use futures::future::join_all;
+use octseq::octets::Octets;
+use std::io;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The record types queried for when decomposing an ANY query.
+pub const ANY_RTYPES: &[Rtype] = &[
+    Rtype::A,
+    Rtype::Aaaa,
+    Rtype::Cname,
+    Rtype::Mx,
+    Rtype::Ns,
+    Rtype::Soa,
+    Rtype::Txt,
+    Rtype::Srv,
+    Rtype::Caa,
+    Rtype::Https,
+];
+
+//------------ lookup_any ----------------------------------------------------
+
+/// Looks up the records of a name via an ANY query.
+///
+/// The function first sends a query for QTYPE ANY. If the server refuses
+/// it or responds with a minimal answer as described in RFC 8482, the
+/// lookup falls back to [`lookup_any_decomposed`] with the record types
+/// of [`ANY_RTYPES`].
+pub async fn lookup_any<R: Resolver>(
+    resolver: &R,
+    qname: impl ToDname,
+) -> Result<FoundAny<R>, io::Error>
+where
+    R::Octets: Octets,
+{
+    if let Ok(answer) = resolver.query((&qname, Rtype::Any)).await {
+        if !is_minimal_any(answer.as_ref()) {
+            return Ok(FoundAny {
+                answers: vec![(Rtype::Any, answer)],
+            });
+        }
+    }
+    lookup_any_decomposed(resolver, qname, ANY_RTYPES).await
+}
+
+//------------ lookup_any_decomposed -----------------------------------------
+
+/// Looks up the records of the given types of a name.
+///
+/// Instead of an ANY query, the function sends one query for each of the
+/// record types in `rtypes` concurrently. Queries that fail are ignored
+/// unless all of them fail in which case the error of the first one is
+/// returned.
+pub async fn lookup_any_decomposed<R: Resolver>(
+    resolver: &R,
+    qname: impl ToDname,
+    rtypes: &[Rtype],
+) -> Result<FoundAny<R>, io::Error> {
+    let results =
+        join_all(rtypes.iter().map(|rtype| resolver.query((&qname, *rtype))))
+            .await;
+    let mut answers = Vec::new();
+    let mut error = None;
+    for (rtype, result) in rtypes.iter().zip(results) {
+        match result {
+            Ok(answer) => answers.push((*rtype, answer)),
+            Err(err) => {
+                if error.is_none() {
+                    error = Some(err)
+                }
+            }
+        }
+    }
+    match error {
+        Some(err) if answers.is_empty() => Err(err),
+        _ => Ok(FoundAny { answers }),
+    }
+}
+
+//------------ FoundAny ------------------------------------------------------
+
+/// The value returned by a successful lookup of all records of a name.
+///
+/// The value contains the responses to all the queries that succeeded,
+/// each together with the record type it was asked for. If the server
+/// answered the ANY query itself, there is a single response for
+/// [`Rtype::Any`].
+#[derive(Debug)]
+pub struct FoundAny<R: Resolver> {
+    /// The answers together with the record type they are for.
+    answers: Vec<(Rtype, R::Answer)>,
+}
+
+impl<R: Resolver> FoundAny<R> {
+    /// Returns whether the query was decomposed into concrete types.
+    pub fn is_decomposed(&self) -> bool {
+        !matches!(self.answers.as_slice(), [(Rtype::Any, _)])
+    }
+
+    /// Returns whether none of the responses contains answer records.
+    pub fn is_empty(&self) -> bool {
+        self.answers
+            .iter()
+            .all(|(_, answer)| answer.as_ref().header_counts().ancount() == 0)
+    }
+
+    /// Returns an iterator over the record types and their answers.
+    pub fn iter(&self) -> impl Iterator<Item = (Rtype, &R::Answer)> {
+        self.answers.iter().map(|(rtype, answer)| (*rtype, answer))
+    }
+}
+
+//------------ is_minimal_any ------------------------------------------------
+
+/// Returns whether a response to an ANY query is a minimal response.
+///
+/// A response is considered minimal if it has an rcode of REFUSED or
+/// NOTIMP or if its answer consists of the HINFO record with a CPU field
+/// of `"RFC8482"` suggested in section 4.2 of RFC 8482.
+pub fn is_minimal_any<Octs: Octets + ?Sized>(msg: &Message<Octs>) -> bool {
+    if matches!(msg.header().rcode(), Rcode::Refused | Rcode::NotImp) {
+        return true;
+    }
+    let answer = match msg.answer() {
+        Ok(answer) => answer,
+        Err(_) => return false,
+    };
+    answer.limit_to::<Hinfo<_>>().any(|record| match record {
+        Ok(record) => record.data().cpu().as_slice() == b"RFC8482",
+        Err(_) => false,
+    })
+}
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::charstr::CharStr;
+    use crate::base::{Dname, MessageBuilder, Ttl};
+    use crate::rdata::A;
+    use core::str::FromStr;
+
+    fn response(
+        rcode: Rcode,
+        data: Option<Hinfo<&'static [u8]>>,
+    ) -> Message<Vec<u8>> {
+        let qname = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.header_mut().set_rcode(rcode);
+        msg.push((&qname, Rtype::Any)).unwrap();
+        let mut msg = msg.answer();
+        match data {
+            Some(data) => msg.push((&qname, Ttl::HOUR, data)).unwrap(),
+            None => msg
+                .push((&qname, Ttl::HOUR, A::from_octets(192, 0, 2, 1)))
+                .unwrap(),
+        }
+        msg.into_message()
+    }
+
+    #[test]
+    fn minimal_any() {
+        let hinfo = |cpu| {
+            Hinfo::new(
+                CharStr::from_octets(cpu).unwrap(),
+                CharStr::from_octets(b"".as_ref()).unwrap(),
+            )
+        };
+        assert!(is_minimal_any(&response(
+            Rcode::NoError,
+            Some(hinfo(b"RFC8482".as_ref()))
+        )));
+        assert!(!is_minimal_any(&response(
+            Rcode::NoError,
+            Some(hinfo(b"x86_64".as_ref()))
+        )));
+        assert!(!is_minimal_any(&response(Rcode::NoError, None)));
+        assert!(is_minimal_any(&response(Rcode::Refused, None)));
+        assert!(is_minimal_any(&response(Rcode::NotImp, None)));
+    }
+}
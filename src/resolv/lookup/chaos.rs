@@ -0,0 +1,123 @@
+//! Looking up diagnostic information via CHAOS queries.
+
+use crate::base::chaos::ChaosName;
+use crate::base::iana::Class;
+use crate::base::message::RecordIter;
+use crate::rdata::Txt;
+use crate::resolv::resolver::Resolver;
+use octseq::octets::Octets;
+use std::io;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ lookup_chaos --------------------------------------------------
+
+/// Asks a server for diagnostic information about itself.
+///
+/// The function sends a TXT query in the CHAOS class for the given name
+/// using the resolver represented by `resolv`. Servers usually only answer
+/// these queries for themselves, so the resolver should be configured with
+/// the one server of interest.
+///
+/// The value returned upon success can be turned into an iterator over the
+/// texts via its `iter()` method.
+pub async fn lookup_chaos<R: Resolver>(
+    resolv: &R,
+    name: ChaosName,
+) -> Result<FoundChaos<R>, io::Error> {
+    resolv
+        .query(name.question())
+        .await
+        .map(|answer| FoundChaos { name, answer })
+}
+
+//------------ FoundChaos ----------------------------------------------------
+
+/// The success type of the `lookup_chaos()` function.
+pub struct FoundChaos<R: Resolver> {
+    /// The name that was asked for.
+    name: ChaosName,
+
+    /// The answer from the server.
+    answer: R::Answer,
+}
+
+impl<R: Resolver> FoundChaos<R> {
+    /// Returns the name that was asked for.
+    pub fn name(&self) -> ChaosName {
+        self.name
+    }
+
+    /// Returns an iterator over the TXT records of the answer.
+    pub fn iter(&self) -> FoundChaosIter<'_, R::Octets>
+    where
+        R::Octets: Octets,
+    {
+        FoundChaosIter {
+            name: self.name,
+            answer: {
+                self.answer
+                    .as_ref()
+                    .answer()
+                    .ok()
+                    .map(|sec| sec.limit_to::<Txt<_>>())
+            },
+        }
+    }
+
+    /// Returns the texts of all TXT records as strings.
+    ///
+    /// Each record is converted into one string by concatenating its
+    /// character strings. Invalid UTF-8 sequences are replaced.
+    pub fn texts(&self) -> Vec<String>
+    where
+        R::Octets: Octets,
+    {
+        self.iter()
+            .map(|txt| {
+                let mut res = Vec::new();
+                for item in txt.iter() {
+                    res.extend_from_slice(item);
+                }
+                String::from_utf8_lossy(&res).into_owned()
+            })
+            .collect()
+    }
+}
+
+impl<'a, R: Resolver> IntoIterator for &'a FoundChaos<R>
+where
+    R::Octets: Octets,
+{
+    type Item = Txt<<<R as Resolver>::Octets as Octets>::Range<'a>>;
+    type IntoIter = FoundChaosIter<'a, R::Octets>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+//------------ FoundChaosIter ------------------------------------------------
+
+/// An iterator over the TXT records returned by a CHAOS lookup.
+pub struct FoundChaosIter<'a, Octs: Octets> {
+    name: ChaosName,
+    answer: Option<RecordIter<'a, Octs, Txt<Octs::Range<'a>>>>,
+}
+
+impl<'a, Octs: Octets> Iterator for FoundChaosIter<'a, Octs> {
+    type Item = Txt<Octs::Range<'a>>;
+
+    #[allow(clippy::while_let_on_iterator)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let answer = self.answer.as_mut()?;
+        while let Some(Ok(record)) = answer.next() {
+            if record.class() == Class::Ch
+                && ChaosName::from_dname(record.owner()) == Some(self.name)
+            {
+                return Some(record.into_data());
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,225 @@
+//! Discovering the NAT64 prefixes used by DNS64.
+//!
+//! In IPv6-only networks, a DNS64 resolver as defined in RFC 6147
+//! synthesizes AAAA records for hosts that only have IPv4 addresses by
+//! embedding their IPv4 address into an IPv6 prefix routed to a NAT64
+//! gateway. Applications that want to synthesize such addresses
+//! themselves, e.g., for IPv4 address literals, need to know the prefix.
+//!
+//! RFC 7050 describes how to discover the prefix: The well-known name
+//! `ipv4only.arpa` only has the IPv4 addresses 192.0.0.170 and
+//! 192.0.0.171. If a DNS64 resolver is in use, a query for its AAAA
+//! records returns synthesized addresses and the prefix can be found by
+//! looking for the well-known IPv4 addresses within them.
+//! [`lookup_nat64_prefixes`] does exactly that.
+
+use crate::base::iana::Rtype;
+use crate::base::name::Dname;
+use crate::rdata::Aaaa;
+use crate::resolv::resolver::Resolver;
+use octseq::octets::Octets;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The well-known IPv4 addresses of `ipv4only.arpa`.
+const WELL_KNOWN_ADDRS: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// The prefix lengths allowed by RFC 6052 in order of preference.
+const PREFIX_LENS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+
+//------------ lookup_nat64_prefixes -----------------------------------------
+
+/// Discovers the NAT64 prefixes used by the resolver’s DNS64 service.
+///
+/// The function queries the AAAA records of `ipv4only.arpa` and derives
+/// the prefixes from the synthesized addresses as described in RFC 7050.
+/// It returns an empty list if the resolver doesn’t do DNS64.
+pub async fn lookup_nat64_prefixes<R: Resolver>(
+    resolver: &R,
+) -> Result<Vec<Nat64Prefix>, io::Error>
+where
+    R::Octets: Octets,
+{
+    let qname = Dname::<[u8]>::from_slice(b"\x08ipv4only\x04arpa\x00")
+        .expect("invalid ipv4only.arpa");
+    let answer = resolver.query((qname, Rtype::Aaaa)).await?;
+    let mut res = Vec::new();
+    let records = match answer.as_ref().answer() {
+        Ok(records) => records.limit_to::<Aaaa>(),
+        Err(_) => return Ok(res),
+    };
+    for record in records.flatten() {
+        if let Some(prefix) = Nat64Prefix::discover(record.data().addr()) {
+            if !res.contains(&prefix) {
+                res.push(prefix)
+            }
+        }
+    }
+    Ok(res)
+}
+
+//------------ Nat64Prefix ---------------------------------------------------
+
+/// An IPv6 prefix for synthesizing addresses with embedded IPv4 addresses.
+///
+/// The prefix uses one of the address formats of section 2.2 of RFC 6052
+/// with prefix lengths of 32, 40, 48, 56, 64, or 96 bits.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Nat64Prefix {
+    /// The prefix with all bits beyond its length cleared.
+    prefix: Ipv6Addr,
+
+    /// The length of the prefix in bits.
+    len: u8,
+}
+
+impl Nat64Prefix {
+    /// The well-known prefix `64:ff9b::/96` defined in RFC 6052.
+    pub const WELL_KNOWN: Self = Nat64Prefix {
+        prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+        len: 96,
+    };
+
+    /// Creates a new prefix from an address and a prefix length.
+    ///
+    /// Returns `None` if the length isn’t one allowed by RFC 6052. Bits of
+    /// `prefix` beyond the length are ignored.
+    pub fn new(prefix: Ipv6Addr, len: u8) -> Option<Self> {
+        if !PREFIX_LENS.contains(&len) {
+            return None;
+        }
+        let mut octets = prefix.octets();
+        octets[usize::from(len / 8)..].fill(0);
+        Some(Nat64Prefix {
+            prefix: octets.into(),
+            len,
+        })
+    }
+
+    /// Derives the prefix from an address synthesized for `ipv4only.arpa`.
+    ///
+    /// Returns `None` if neither of the well-known IPv4 addresses of the
+    /// name are embedded in `addr`.
+    pub fn discover(addr: Ipv6Addr) -> Option<Self> {
+        PREFIX_LENS.iter().find_map(|&len| {
+            let prefix = Self::new(addr, len)?;
+            let embedded = prefix.extract(addr)?;
+            if WELL_KNOWN_ADDRS.contains(&embedded) {
+                Some(prefix)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.prefix
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns whether this is the well-known prefix.
+    pub fn is_well_known(&self) -> bool {
+        *self == Self::WELL_KNOWN
+    }
+
+    /// Synthesizes the IPv6 address for an IPv4 address.
+    pub fn synthesize(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        for (pos, octet) in self.positions().zip(addr.octets()) {
+            octets[pos] = octet;
+        }
+        octets.into()
+    }
+
+    /// Returns the IPv4 address embedded in an IPv6 address.
+    ///
+    /// Returns `None` if the address isn’t covered by the prefix or if
+    /// bits 64 to 71 of the address aren’t zero as required by RFC 6052.
+    pub fn extract(&self, addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        let octets = addr.octets();
+        let prefix_len = usize::from(self.len / 8);
+        if octets[..prefix_len] != self.prefix.octets()[..prefix_len] {
+            return None;
+        }
+        if self.len < 96 && octets[8] != 0 {
+            return None;
+        }
+        let mut res = [0; 4];
+        for (octet, pos) in res.iter_mut().zip(self.positions()) {
+            *octet = octets[pos];
+        }
+        Some(res.into())
+    }
+
+    /// Returns the positions of the IPv4 address octets.
+    ///
+    /// The octets directly follow the prefix but skip bits 64 to 71.
+    fn positions(&self) -> impl Iterator<Item = usize> {
+        (usize::from(self.len / 8)..16)
+            .filter(|&pos| pos != 8)
+            .take(4)
+    }
+}
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    fn addr(s: &str) -> Ipv6Addr {
+        Ipv6Addr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn synthesize_and_extract() {
+        let v4 = Ipv4Addr::new(192, 0, 2, 33);
+        // The examples of section 2.4 of RFC 6052.
+        for (prefix, len, v6) in [
+            ("2001:db8::", 32, "2001:db8:c000:221::"),
+            ("2001:db8:100::", 40, "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::", 48, "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::", 56, "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::", 64, "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::", 96, "2001:db8:122:344::192.0.2.33"),
+        ] {
+            let prefix = Nat64Prefix::new(addr(prefix), len).unwrap();
+            assert_eq!(prefix.synthesize(v4), addr(v6));
+            assert_eq!(prefix.extract(addr(v6)), Some(v4));
+        }
+        assert_eq!(
+            Nat64Prefix::WELL_KNOWN.synthesize(v4),
+            addr("64:ff9b::192.0.2.33")
+        );
+        assert_eq!(
+            Nat64Prefix::WELL_KNOWN.extract(addr("2001:db8::192.0.2.33")),
+            None
+        );
+        assert!(Nat64Prefix::new(addr("2001:db8::"), 80).is_none());
+    }
+
+    #[test]
+    fn discover() {
+        assert_eq!(
+            Nat64Prefix::discover(addr("64:ff9b::192.0.0.170")),
+            Some(Nat64Prefix::WELL_KNOWN)
+        );
+        assert_eq!(
+            Nat64Prefix::discover(addr("2001:db8:c000:aa::")),
+            Nat64Prefix::new(addr("2001:db8::"), 32)
+        );
+        assert_eq!(
+            Nat64Prefix::discover(addr("2001:db8:122:344:c0:0:ab00:0")),
+            Nat64Prefix::new(addr("2001:db8:122:344::"), 64)
+        );
+        assert_eq!(Nat64Prefix::discover(addr("2001:db8::1")), None);
+    }
+}
@@ -4,10 +4,12 @@ use crate::base::iana::Rtype;
 use crate::base::message::RecordIter;
 use crate::base::name::{ParsedDname, ToDname, ToRelativeDname};
 use crate::rdata::{Aaaa, A};
+use crate::resolv::lookup::sort::{sort_destinations, PolicyTable};
 use crate::resolv::resolver::{Resolver, SearchNames};
 use octseq::octets::Octets;
 use std::io;
 use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::vec::Vec;
 
 //------------ lookup_host ---------------------------------------------------
 
@@ -161,6 +163,23 @@ where
         }
     }
 
+    /// Returns the IP addresses sorted for connecting to them.
+    ///
+    /// The addresses are sorted according to the destination address
+    /// selection rules of RFC 6724 using the default policy table, so
+    /// trying them in order tries the most suitable addresses first. See
+    /// the [`sort`][super::sort] module for details.
+    pub fn sorted(&self) -> Vec<IpAddr> {
+        self.sorted_with_policy(&PolicyTable::default())
+    }
+
+    /// Returns the IP addresses sorted using the given policy table.
+    pub fn sorted_with_policy(&self, policy: &PolicyTable) -> Vec<IpAddr> {
+        let mut addrs: Vec<_> = self.iter().collect();
+        sort_destinations(&mut addrs, policy);
+        addrs
+    }
+
     /// Returns an iterator over socket addresses gained from the lookup.
     ///
     /// The socket addresses are gained by combining the IP addresses with
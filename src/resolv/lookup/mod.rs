@@ -4,9 +4,18 @@
 //! implement applications of the DNS.
 
 pub use self::addr::lookup_addr;
+pub use self::any::{lookup_any, lookup_any_decomposed};
+pub use self::chaos::lookup_chaos;
+pub use self::dns64::lookup_nat64_prefixes;
 pub use self::host::{lookup_host, search_host};
 pub use self::srv::lookup_srv;
+pub use self::svcb::{lookup_https, lookup_svcb};
 
 pub mod addr;
+pub mod any;
+pub mod chaos;
+pub mod dns64;
 pub mod host;
+pub mod sort;
 pub mod srv;
+pub mod svcb;
@@ -0,0 +1,397 @@
+//! Sorting destination addresses.
+//!
+//! A host lookup often produces several addresses, both IPv4 and IPv6.
+//! Trying them in the order they were received may mean to try addresses
+//! that are unreachable from the local host first or to use a tunnel when
+//! native connectivity is available. [RFC 6724] defines rules for sorting
+//! destination addresses so that the most suitable ones come first. The
+//! function [`sort_destinations`] implements these rules.
+//!
+//! The rules rely on the source address the local host would use for each
+//! destination and on a [`PolicyTable`] assigning a precedence and a label
+//! to address prefixes. The source address is determined by asking the
+//! operating system via a connected UDP socket which doesn’t send any
+//! packets. If you know your source addresses, you can provide them via
+//! [`sort_destinations_with`] instead.
+//!
+//! The rules regarding deprecated, home, and native addresses are not
+//! applied as the necessary information isn’t available in a portable way.
+//!
+//! [RFC 6724]: https://tools.ietf.org/html/rfc6724
+
+use core::cmp::Ordering;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::vec::Vec;
+
+//------------ sort_destinations ---------------------------------------------
+
+/// Sorts destination addresses according to RFC 6724.
+///
+/// The source address for each destination is determined by asking the
+/// operating system. Destinations without a source address are considered
+/// unreachable and moved to the end. Addresses that the rules can’t tell
+/// apart keep their relative order.
+pub fn sort_destinations(addrs: &mut [IpAddr], policy: &PolicyTable) {
+    sort_destinations_with(addrs, policy, source_addr)
+}
+
+/// Sorts destination addresses using the given source addresses.
+///
+/// This is like [`sort_destinations`] but uses the closure `source` to
+/// determine the source address for each destination. It should return
+/// `None` if there is no source address for a destination.
+pub fn sort_destinations_with(
+    addrs: &mut [IpAddr],
+    policy: &PolicyTable,
+    mut source: impl FnMut(IpAddr) -> Option<IpAddr>,
+) {
+    let mut candidates: Vec<_> = addrs
+        .iter()
+        .map(|addr| Candidate::new(*addr, source(*addr), policy))
+        .collect();
+    // The sort is stable, which takes care of rule 10.
+    candidates.sort_by(Candidate::cmp);
+    for (addr, candidate) in addrs.iter_mut().zip(candidates) {
+        *addr = candidate.addr;
+    }
+}
+
+/// Returns the source address the operating system uses for a destination.
+fn source_addr(dest: IpAddr) -> Option<IpAddr> {
+    let local = match dest {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    // Connecting a UDP socket only selects a route and source address.
+    let sock = UdpSocket::bind(SocketAddr::new(local, 0)).ok()?;
+    sock.connect(SocketAddr::new(dest, 53)).ok()?;
+    Some(sock.local_addr().ok()?.ip())
+}
+
+//------------ PolicyTable ---------------------------------------------------
+
+/// A table assigning precedence and labels to address prefixes.
+///
+/// Each entry consists of an IPv6 prefix, a precedence, and a label. IPv4
+/// addresses are looked up as IPv4-mapped IPv6 addresses. An address uses
+/// the entry with the longest prefix matching it.
+///
+/// Destinations with a higher precedence are preferred. Destinations with
+/// the same label as their source address are preferred over those with a
+/// different label.
+///
+/// The default table is the one given in section 2.1 of RFC 6724.
+#[derive(Clone, Debug)]
+pub struct PolicyTable {
+    entries: Vec<PolicyEntry>,
+}
+
+impl PolicyTable {
+    /// Creates a new, empty policy table.
+    ///
+    /// Addresses not matched by any entry have precedence and label 0.
+    pub fn empty() -> Self {
+        PolicyTable {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds an entry to the table.
+    ///
+    /// If there already is an entry for the prefix, it is replaced.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if `len` is greater than 128.
+    pub fn insert(
+        &mut self,
+        prefix: Ipv6Addr,
+        len: u8,
+        precedence: u8,
+        label: u8,
+    ) {
+        assert!(len <= 128, "prefix length greater than 128");
+        let prefix = mask(u128::from(prefix), len);
+        self.entries
+            .retain(|entry| entry.prefix != prefix || entry.len != len);
+        self.entries.push(PolicyEntry {
+            prefix,
+            len,
+            precedence,
+            label,
+        });
+    }
+
+    /// Returns the precedence and label for an address.
+    pub fn lookup(&self, addr: IpAddr) -> (u8, u8) {
+        let addr = u128::from(to_ipv6(addr));
+        self.entries
+            .iter()
+            .filter(|entry| mask(addr, entry.len) == entry.prefix)
+            .max_by_key(|entry| entry.len)
+            .map_or((0, 0), |entry| (entry.precedence, entry.label))
+    }
+}
+
+//--- Default
+
+impl Default for PolicyTable {
+    fn default() -> Self {
+        let mut res = Self::empty();
+        for (prefix, len, precedence, label) in [
+            (Ipv6Addr::LOCALHOST, 128, 50, 0),
+            (Ipv6Addr::UNSPECIFIED, 0, 40, 1),
+            (Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96, 35, 4),
+            (Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), 16, 30, 2),
+            (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 32, 5, 5),
+            (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7, 3, 13),
+            (Ipv6Addr::UNSPECIFIED, 96, 1, 3),
+            (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10, 1, 11),
+            (Ipv6Addr::new(0x3ffe, 0, 0, 0, 0, 0, 0, 0), 16, 1, 12),
+        ] {
+            res.insert(prefix, len, precedence, label)
+        }
+        res
+    }
+}
+
+//------------ PolicyEntry ---------------------------------------------------
+
+/// An entry of the policy table.
+#[derive(Clone, Copy, Debug)]
+struct PolicyEntry {
+    /// The prefix with all bits beyond `len` cleared.
+    prefix: u128,
+
+    /// The length of the prefix in bits.
+    len: u8,
+
+    /// The precedence of addresses matching the prefix.
+    precedence: u8,
+
+    /// The label of addresses matching the prefix.
+    label: u8,
+}
+
+//------------ Candidate -----------------------------------------------------
+
+/// A destination address with everything needed for sorting.
+struct Candidate {
+    /// The destination address.
+    addr: IpAddr,
+
+    /// The scope of the destination.
+    scope: u8,
+
+    /// The precedence of the destination.
+    precedence: u8,
+
+    /// The label of the destination.
+    label: u8,
+
+    /// The source address, its scope and label, if there is one.
+    source: Option<(IpAddr, u8, u8)>,
+}
+
+impl Candidate {
+    fn new(
+        addr: IpAddr,
+        source: Option<IpAddr>,
+        policy: &PolicyTable,
+    ) -> Self {
+        let (precedence, label) = policy.lookup(addr);
+        Candidate {
+            addr,
+            scope: scope(addr),
+            precedence,
+            label,
+            source: source.map(|source| {
+                (source, scope(source), policy.lookup(source).1)
+            }),
+        }
+    }
+
+    /// Compares two candidates with the preferred one being less.
+    ///
+    /// The numbers refer to the rules in section 6 of RFC 6724.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (left, right) = match (self.source, other.source) {
+            (Some(left), Some(right)) => (left, right),
+            // Rule 1: Avoid unusable destinations.
+            (left, right) => return right.is_some().cmp(&left.is_some()),
+        };
+        // Rule 2: Prefer matching scope.
+        (other.scope == right.1)
+            .cmp(&(self.scope == left.1))
+            // Rule 5: Prefer matching label.
+            .then_with(|| {
+                (other.label == right.2).cmp(&(self.label == left.2))
+            })
+            // Rule 6: Prefer higher precedence.
+            .then_with(|| other.precedence.cmp(&self.precedence))
+            // Rule 8: Prefer smaller scope.
+            .then_with(|| self.scope.cmp(&other.scope))
+            // Rule 9: Use longest matching prefix.
+            .then_with(|| {
+                if self.addr.is_ipv4() != other.addr.is_ipv4() {
+                    return Ordering::Equal;
+                }
+                common_prefix_len(other.addr, right.0)
+                    .cmp(&common_prefix_len(self.addr, left.0))
+            })
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the scope of an address as defined in RFC 4291.
+///
+/// IPv4 addresses are scoped as described in section 3.2 of RFC 6724.
+fn scope(addr: IpAddr) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xe;
+
+    match addr {
+        IpAddr::V4(addr) => {
+            if addr.is_loopback() || addr.is_link_local() {
+                LINK_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+        IpAddr::V6(addr) => {
+            let octets = addr.octets();
+            if addr.is_multicast() {
+                octets[1] & 0x0f
+            } else if addr.is_loopback() || octets[0..2] == [0xfe, 0x80] {
+                LINK_LOCAL
+            } else if octets[0] == 0xfe && octets[1] & 0xc0 == 0xc0 {
+                SITE_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+    }
+}
+
+/// Returns the number of leading bits two addresses have in common.
+fn common_prefix_len(left: IpAddr, right: IpAddr) -> u32 {
+    (u128::from(to_ipv6(left)) ^ u128::from(to_ipv6(right))).leading_zeros()
+}
+
+/// Converts an address into IPv6, mapping IPv4 addresses.
+fn to_ipv6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    }
+}
+
+/// Clears all bits of an address beyond the prefix length.
+fn mask(addr: u128, len: u8) -> u128 {
+    match len {
+        0 => 0,
+        len => addr & (u128::MAX << (128 - u32::from(len))),
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    fn addr(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    /// Sorts the destination with the given source addresses.
+    fn sort(dests: &[(&str, &str)]) -> Vec<IpAddr> {
+        let mut addrs: Vec<_> =
+            dests.iter().map(|(dest, _)| addr(dest)).collect();
+        let sources: Vec<_> = dests
+            .iter()
+            .map(|(dest, source)| (addr(dest), addr(source)))
+            .collect();
+        sort_destinations_with(&mut addrs, &PolicyTable::default(), |dest| {
+            sources
+                .iter()
+                .find(|(addr, _)| *addr == dest)
+                .map(|(_, source)| *source)
+        });
+        addrs
+    }
+
+    #[test]
+    fn policy_table() {
+        let policy = PolicyTable::default();
+        assert_eq!(policy.lookup(addr("::1")), (50, 0));
+        assert_eq!(policy.lookup(addr("2001:db8::1")), (40, 1));
+        assert_eq!(policy.lookup(addr("192.0.2.1")), (35, 4));
+        assert_eq!(policy.lookup(addr("2002:c633:6401::1")), (30, 2));
+        assert_eq!(policy.lookup(addr("2001::1")), (5, 5));
+        assert_eq!(policy.lookup(addr("fd00::1")), (3, 13));
+
+        let mut policy = PolicyTable::empty();
+        assert_eq!(policy.lookup(addr("2001:db8::1")), (0, 0));
+        policy.insert(Ipv6Addr::from_str("2001:db8::").unwrap(), 32, 7, 9);
+        assert_eq!(policy.lookup(addr("2001:db8::1")), (7, 9));
+        assert_eq!(policy.lookup(addr("2001:db9::1")), (0, 0));
+    }
+
+    #[test]
+    fn sort_rules() {
+        // These are the examples from section 10.2 of RFC 6724.
+        assert_eq!(
+            sort(&[
+                ("198.51.100.121", "169.254.13.78"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            [addr("2001:db8:1::1"), addr("198.51.100.121")]
+        );
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", "fe80::1"),
+                ("198.51.100.121", "198.51.100.117"),
+            ]),
+            [addr("198.51.100.121"), addr("2001:db8:1::1")]
+        );
+        assert_eq!(
+            sort(&[
+                ("10.1.2.3", "10.1.2.4"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            [addr("2001:db8:1::1"), addr("10.1.2.3")]
+        );
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", "2001:db8:1::2"),
+                ("fe80::1", "fe80::2"),
+            ]),
+            [addr("fe80::1"), addr("2001:db8:1::1")]
+        );
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", "2002:c633:6401::2"),
+                ("2002:c633:6401::1", "2002:c633:6401::2"),
+            ]),
+            [addr("2002:c633:6401::1"), addr("2001:db8:1::1")]
+        );
+        assert_eq!(
+            sort(&[
+                ("2001:db8:3ffe::1", "2001:db8:1::2"),
+                ("2001:db8:1::1", "2001:db8:1::2"),
+            ]),
+            [addr("2001:db8:1::1"), addr("2001:db8:3ffe::1")]
+        );
+
+        // Unusable destinations come last.
+        let mut addrs = [addr("192.0.2.1"), addr("2001:db8::1")];
+        sort_destinations_with(&mut addrs, &PolicyTable::default(), |dest| {
+            dest.is_ipv6().then(|| addr("2001:db8::2"))
+        });
+        assert_eq!(addrs, [addr("2001:db8::1"), addr("192.0.2.1")]);
+    }
+}
@@ -0,0 +1,576 @@
+//! Looking up service bindings.
+//!
+//! SVCB and HTTPS records defined in [RFC 9460] tell a client where and how
+//! to connect to a service: which hosts and ports to use, which
+//! application protocols are supported, and which keys to use for
+//! encrypting the TLS ClientHello. This module provides the lookups a
+//! client needs to make use of these records. [`lookup_https`] is for
+//! HTTPS origins, [`lookup_svcb`] for other services that use SVCB
+//! records.
+//!
+//! The lookups follow AliasMode records, interpret the ServiceMode records
+//! and resolve their targets into addresses. The result is a list of
+//! [`ServiceEndpoint`]s in the order they should be tried. Unless the
+//! service records demand otherwise, the list ends with an endpoint for
+//! the origin itself – or the target of an AliasMode record – using its A
+//! and AAAA records, so that clients can fall back to connecting the way
+//! they would without service bindings.
+//!
+//! [RFC 9460]: https://tools.ietf.org/html/rfc9460
+
+use super::host::lookup_host;
+use crate::base::iana::{Class, Rtype, SvcParamKey};
+use crate::base::message::Message;
+use crate::base::name::{Dname, DnameBuilder, ParsedDname, ToDname};
+use crate::base::rdata::ParseRecordData;
+use crate::rdata::svcb::{HttpsVariant, SvcbRdata, SvcbVariant};
+use crate::resolv::resolver::Resolver;
+use crate::utils::rotate::order_by_weight;
+use octseq::octets::Octets;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum number of AliasMode records followed.
+const MAX_ALIASES: usize = 8;
+
+/// The default port of HTTPS.
+const HTTPS_PORT: u16 = 443;
+
+/// The ALPN protocol ID used by HTTPS unless `no-default-alpn` is given.
+const HTTPS_DEFAULT_ALPN: &[u8] = b"http/1.1";
+
+/// The service parameters understood by the lookups.
+const SUPPORTED_KEYS: &[SvcParamKey] = &[
+    SvcParamKey::Alpn,
+    SvcParamKey::NoDefaultAlpn,
+    SvcParamKey::Port,
+    SvcParamKey::Ipv4Hint,
+    SvcParamKey::Ech,
+    SvcParamKey::Ipv6Hint,
+    SvcParamKey::DohPath,
+];
+
+//------------ lookup_https --------------------------------------------------
+
+/// Looks up the endpoints for connecting to an HTTPS origin.
+///
+/// The origin is given by its `host` and `port`. HTTPS records are queried
+/// at the host name itself for port 443 and at `_port._https.host`
+/// otherwise, as described in section 9.1 of RFC 9460. All endpoints
+/// support `http/1.1` unless a record says otherwise.
+///
+/// Returns an empty list if an AliasMode record declares that the service
+/// is not available. Returns an error only if neither the service
+/// bindings nor the origin’s addresses could be resolved.
+pub async fn lookup_https<R: Resolver>(
+    resolver: &R,
+    host: impl ToDname,
+    port: u16,
+) -> Result<Vec<ServiceEndpoint>, io::Error>
+where
+    R::Octets: Octets,
+{
+    let qname = if port == HTTPS_PORT {
+        host.to_vec()
+    } else {
+        attrleaf_name(b"https", port, &host)?
+    };
+    lookup_bindings::<R, HttpsVariant>(
+        resolver,
+        Rtype::Https,
+        qname,
+        host,
+        port,
+        Some(HTTPS_DEFAULT_ALPN),
+    )
+    .await
+}
+
+//------------ lookup_svcb ---------------------------------------------------
+
+/// Looks up the endpoints for connecting to a service via SVCB records.
+///
+/// The service is given by the `scheme` without the leading underscore,
+/// e.g., `b"dns"`, its default port, and the `host` and `port` of the
+/// origin. SVCB records are queried at `_scheme.host` if the port is the
+/// default port and at `_port._scheme.host` otherwise. Since there are no
+/// default protocols for generic services, the endpoints only have the
+/// protocols listed in the records.
+///
+/// Returns an empty list if an AliasMode record declares that the service
+/// is not available. Returns an error only if neither the service
+/// bindings nor the origin’s addresses could be resolved.
+pub async fn lookup_svcb<R: Resolver>(
+    resolver: &R,
+    scheme: &[u8],
+    default_port: u16,
+    host: impl ToDname,
+    port: u16,
+) -> Result<Vec<ServiceEndpoint>, io::Error>
+where
+    R::Octets: Octets,
+{
+    let qname = if port == default_port {
+        let mut builder = DnameBuilder::new_vec();
+        builder.append_underscore_label(scheme).map_err(long_name)?;
+        builder.append_origin(&host).map_err(long_name)?
+    } else {
+        attrleaf_name(scheme, port, &host)?
+    };
+    lookup_bindings::<R, SvcbVariant>(
+        resolver,
+        Rtype::Svcb,
+        qname,
+        host,
+        port,
+        None,
+    )
+    .await
+}
+
+//------------ ServiceEndpoint -----------------------------------------------
+
+/// An endpoint for connecting to a service.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServiceEndpoint {
+    /// The priority of the service record or `None` for the origin.
+    priority: Option<u16>,
+
+    /// The name of the host providing the service.
+    target: Dname<Vec<u8>>,
+
+    /// The port to connect to.
+    port: u16,
+
+    /// The addresses of the host.
+    addrs: Vec<IpAddr>,
+
+    /// The ALPN protocol IDs supported by the endpoint.
+    alpn: Vec<Vec<u8>>,
+
+    /// The ECH configuration list if the endpoint supports ECH.
+    ech: Option<Vec<u8>>,
+}
+
+impl ServiceEndpoint {
+    /// Returns the priority of the service record for the endpoint.
+    ///
+    /// Returns `None` if the endpoint is the fallback to the origin.
+    pub fn priority(&self) -> Option<u16> {
+        self.priority
+    }
+
+    /// Returns whether the endpoint is the fallback to the origin.
+    pub fn is_fallback(&self) -> bool {
+        self.priority.is_none()
+    }
+
+    /// Returns the name of the host providing the service.
+    ///
+    /// Note that TLS connections still need to use the origin’s host name
+    /// for server name indication and certificate verification.
+    pub fn target(&self) -> &Dname<Vec<u8>> {
+        &self.target
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the addresses of the host.
+    ///
+    /// If the target’s addresses couldn’t be resolved, these are the
+    /// address hints given in the service record.
+    pub fn addrs(&self) -> &[IpAddr] {
+        &self.addrs
+    }
+
+    /// Returns an iterator over the socket addresses of the endpoint.
+    pub fn socket_addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.addrs
+            .iter()
+            .map(move |addr| SocketAddr::new(*addr, self.port))
+    }
+
+    /// Returns the ALPN protocol IDs supported by the endpoint.
+    ///
+    /// This includes the default protocol of the service unless the
+    /// service record contains the `no-default-alpn` parameter.
+    pub fn alpn(&self) -> &[Vec<u8>] {
+        &self.alpn
+    }
+
+    /// Returns the ECH configuration list for the endpoint if present.
+    pub fn ech_config(&self) -> Option<&[u8]> {
+        self.ech.as_deref()
+    }
+}
+
+impl ToSocketAddrs for &ServiceEndpoint {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(self.socket_addrs().collect::<Vec<_>>().into_iter())
+    }
+}
+
+//------------ ServiceRecord -------------------------------------------------
+
+/// The relevant content of a ServiceMode record.
+#[derive(Clone, Debug)]
+pub(crate) struct ServiceRecord {
+    pub priority: u16,
+    pub target: Dname<Vec<u8>>,
+    pub port: Option<u16>,
+    pub alpn: Vec<Vec<u8>>,
+    pub no_default_alpn: bool,
+    pub hints: Vec<IpAddr>,
+    pub ech: Option<Vec<u8>>,
+    pub dohpath: Option<String>,
+}
+
+impl ServiceRecord {
+    /// Converts the record data into a service record.
+    ///
+    /// A target of `"."` is replaced by `owner`. Returns `None` if the
+    /// record has mandatory parameters that aren’t supported.
+    fn from_rdata<V>(
+        rdata: &SvcbRdata<V, &[u8], impl ToDname>,
+        owner: &Dname<Vec<u8>>,
+    ) -> Option<Self> {
+        let params = rdata.params();
+        if let Some(mandatory) = params.mandatory() {
+            if mandatory.iter().any(|key| !SUPPORTED_KEYS.contains(&key)) {
+                return None;
+            }
+        }
+        let target = rdata.target().to_vec();
+        let target = if target.is_root() {
+            owner.clone()
+        } else {
+            target
+        };
+        let mut hints: Vec<IpAddr> = Vec::new();
+        if let Some(hint) = params.ipv6hint() {
+            hints.extend(hint.iter().map(IpAddr::from));
+        }
+        if let Some(hint) = params.ipv4hint() {
+            hints.extend(hint.iter().map(IpAddr::from));
+        }
+        Some(ServiceRecord {
+            priority: rdata.priority(),
+            target,
+            port: params.port().map(|port| port.port()),
+            alpn: params
+                .alpn()
+                .map(|alpn| alpn.iter().map(Into::into).collect())
+                .unwrap_or_default(),
+            no_default_alpn: params.no_default_alpn(),
+            hints,
+            ech: params.ech().map(|ech| ech.as_slice().into()),
+            dohpath: params.dohpath().and_then(|path| {
+                String::from_utf8(path.as_slice().into()).ok()
+            }),
+        })
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Looks up the service bindings for an origin.
+async fn lookup_bindings<R, V>(
+    resolver: &R,
+    rtype: Rtype,
+    qname: Dname<Vec<u8>>,
+    host: impl ToDname,
+    port: u16,
+    default_alpn: Option<&[u8]>,
+) -> Result<Vec<ServiceEndpoint>, io::Error>
+where
+    R: Resolver,
+    R::Octets: Octets,
+    for<'a> SvcbRdata<V, &'a [u8], ParsedDname<&'a [u8]>>:
+        ParseRecordData<'a, [u8]>,
+{
+    let mut qname = qname;
+    let mut alias_target = None;
+    let mut records = Vec::new();
+    for _ in 0..MAX_ALIASES {
+        let answer = match resolver.query((&qname, rtype)).await {
+            Ok(answer) => answer,
+            Err(_) => break,
+        };
+        let answer = answer.as_ref().for_slice();
+        match process_answer::<V>(answer) {
+            // A target of "." means the service isn’t available.
+            Some(Binding::Alias(alias)) if alias.is_root() => {
+                return Ok(Vec::new())
+            }
+            Some(Binding::Alias(alias)) => {
+                qname = alias.clone();
+                alias_target = Some(alias);
+            }
+            Some(Binding::Service(found)) => {
+                records = found;
+                break;
+            }
+            None => break,
+        }
+    }
+
+    // Records with the same priority are shuffled.
+    order_by_weight(&mut records, |record| (record.priority, 1));
+
+    let mut res = Vec::new();
+    for record in &records {
+        let mut alpn = record.alpn.clone();
+        if let Some(default_alpn) = default_alpn {
+            if !record.no_default_alpn
+                && !alpn.iter().any(|id| id.as_slice() == default_alpn)
+            {
+                alpn.push(default_alpn.into())
+            }
+        }
+        let addrs = match lookup_host(resolver, &record.target).await {
+            Ok(found) if !found.is_empty() => found.iter().collect(),
+            _ => record.hints.clone(),
+        };
+        if addrs.is_empty() {
+            continue;
+        }
+        res.push(ServiceEndpoint {
+            priority: Some(record.priority),
+            target: record.target.clone(),
+            port: record.port.unwrap_or(port),
+            addrs,
+            alpn,
+            ech: record.ech.clone(),
+        });
+    }
+
+    // If all records offer ECH, falling back to the origin would allow an
+    // attacker to defeat ECH by blocking the endpoints.
+    if !records.is_empty() && records.iter().all(|rec| rec.ech.is_some()) {
+        return Ok(res);
+    }
+
+    // After following an alias, its target takes the place of the origin.
+    let fallback = alias_target.unwrap_or_else(|| host.to_vec());
+    match lookup_host(resolver, &fallback).await {
+        Ok(found) if !found.is_empty() => {
+            res.push(ServiceEndpoint {
+                priority: None,
+                target: fallback,
+                port,
+                addrs: found.iter().collect(),
+                alpn: default_alpn
+                    .map(|alpn| alpn.into())
+                    .into_iter()
+                    .collect(),
+                ech: None,
+            });
+        }
+        Ok(_) => {}
+        Err(err) => {
+            if res.is_empty() {
+                return Err(err);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// The service bindings found in an answer.
+pub(crate) enum Binding {
+    /// The target of an AliasMode record.
+    Alias(Dname<Vec<u8>>),
+
+    /// The usable ServiceMode records.
+    Service(Vec<ServiceRecord>),
+}
+
+/// Processes the answer to a service binding query.
+///
+/// AliasMode records take precedence over ServiceMode records. Returns
+/// `None` if the answer is malformed.
+pub(crate) fn process_answer<'a, V>(
+    answer: &'a Message<[u8]>,
+) -> Option<Binding>
+where
+    SvcbRdata<V, &'a [u8], ParsedDname<&'a [u8]>>: ParseRecordData<'a, [u8]>,
+{
+    let owner = answer.canonical_name()?.to_vec();
+    let mut records = Vec::new();
+    for record in answer.answer().ok()? {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if record.class() != Class::In || record.owner() != owner {
+            continue;
+        }
+        let record = match record.to_record::<SvcbRdata<V, _, _>>() {
+            Ok(Some(record)) => record,
+            _ => continue,
+        };
+        let data = record.data();
+        if data.is_alias() {
+            return Some(Binding::Alias(data.target().to_vec()));
+        }
+        if let Some(record) = ServiceRecord::from_rdata(data, &owner) {
+            records.push(record)
+        }
+    }
+    Some(Binding::Service(records))
+}
+
+/// Creates the name `_port._scheme.host`.
+fn attrleaf_name(
+    scheme: &[u8],
+    port: u16,
+    host: &impl ToDname,
+) -> Result<Dname<Vec<u8>>, io::Error> {
+    let mut builder = DnameBuilder::new_vec();
+    builder
+        .append_underscore_label(port.to_string().as_bytes())
+        .map_err(long_name)?;
+    builder.append_underscore_label(scheme).map_err(long_name)?;
+    builder.append_origin(host).map_err(long_name)
+}
+
+/// Converts a name building error into an IO error.
+fn long_name<E>(_: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "name too long")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::MessageBuilder;
+    use crate::rdata::svcb::{SvcParams, UnknownSvcParam};
+    use crate::rdata::Https;
+    use core::str::FromStr;
+    use std::net::Ipv4Addr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        if s == "." {
+            return Dname::root_vec();
+        }
+        Dname::from_str(s).unwrap()
+    }
+
+    fn answer(
+        records: &[(u16, &str, SvcParams<Vec<u8>>)],
+    ) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rcode(Rcode::NoError);
+        let mut msg = msg.question();
+        msg.push((name("example.com"), Rtype::Https)).unwrap();
+        let mut msg = msg.answer();
+        for (priority, target, params) in records {
+            msg.push((
+                name("example.com"),
+                3600,
+                Https::new(*priority, name(target), params.clone()).unwrap(),
+            ))
+            .unwrap();
+        }
+        msg.into_message()
+    }
+
+    fn service(answer: &Message<Vec<u8>>) -> Vec<ServiceRecord> {
+        match process_answer::<HttpsVariant>(answer.for_slice()) {
+            Some(Binding::Service(records)) => records,
+            _ => panic!("expected service records"),
+        }
+    }
+
+    #[test]
+    fn process_service() {
+        let msg = answer(&[
+            (
+                1,
+                ".",
+                SvcParams::from_values(|params| {
+                    params.alpn(&[b"h2", b"h3"]).unwrap();
+                    params.port(8443).unwrap();
+                    params.ipv4hint([Ipv4Addr::new(192, 0, 2, 1)]).unwrap();
+                    params.ech(b"ech").unwrap();
+                    Ok(())
+                })
+                .unwrap(),
+            ),
+            (
+                2,
+                "svc.example.net",
+                SvcParams::from_values(|params| {
+                    params.no_default_alpn().unwrap();
+                    params.mandatory([SvcParamKey::Port]).unwrap();
+                    params.port(443).unwrap();
+                    Ok(())
+                })
+                .unwrap(),
+            ),
+            (
+                3,
+                "unsupported.example.net",
+                SvcParams::from_values(|params| {
+                    params.mandatory([SvcParamKey::from(0x029b)]).unwrap();
+                    params.push(
+                        &UnknownSvcParam::new(0x029b.into(), b"hello")
+                            .unwrap(),
+                    )
+                })
+                .unwrap(),
+            ),
+        ]);
+        let records = service(&msg);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].priority, 1);
+        assert_eq!(records[0].target, name("example.com"));
+        assert_eq!(records[0].port, Some(8443));
+        assert_eq!(records[0].alpn, [b"h2".to_vec(), b"h3".to_vec()]);
+        assert!(!records[0].no_default_alpn);
+        assert_eq!(records[0].hints, [IpAddr::from([192, 0, 2, 1])]);
+        assert_eq!(records[0].ech.as_deref(), Some(b"ech".as_ref()));
+        assert_eq!(records[1].target, name("svc.example.net"));
+        assert!(records[1].no_default_alpn);
+        assert!(records[1].ech.is_none());
+    }
+
+    #[test]
+    fn process_alias() {
+        let msg = answer(&[
+            (
+                1,
+                "svc.example.net",
+                SvcParams::from_values(|_| Ok(())).unwrap(),
+            ),
+            (
+                0,
+                "alias.example.net",
+                SvcParams::from_values(|_| Ok(())).unwrap(),
+            ),
+        ]);
+        assert!(matches!(
+            process_answer::<HttpsVariant>(msg.for_slice()),
+            Some(Binding::Alias(alias)) if alias == name("alias.example.net")
+        ));
+    }
+
+    #[test]
+    fn query_names() {
+        assert_eq!(
+            attrleaf_name(b"https", 8443, &name("example.com")).unwrap(),
+            name("_8443._https.example.com")
+        );
+    }
+}
@@ -19,6 +19,17 @@
 //!    See the [stub] module for more information on how to use the stub
 //!    resolver.
 //!
+//! *  [`Forwarder`] routes queries to different stub resolvers depending
+//!    on the query name. This allows sending queries for some domains to
+//!    dedicated servers, each with its own transport and DNSSEC policy.
+//!
+//!    See the [forward] module for more information.
+//!
+//! In addition, the [infra] module provides a cache of delegations and
+//! name server information for resolvers that iterate from the root zone
+//! down themselves, and the [scrub] module sanitizes the responses such
+//! resolvers receive.
+//!
 //! The lookups implemented by the crate are generic over the particular
 //! resolver, so you can pick the resolver most suitable for your own
 //! application or even implement your own specialised resolver. All
@@ -26,16 +37,24 @@
 //! convenience, they are also available as methods on the [`Resolver`]
 //! trait.
 //!
+//! [forward]: forward/index.html
+//! [infra]: infra/index.html
 //! [lookup]: lookup/index.html
+//! [scrub]: scrub/index.html
 //! [stub]: stub/index.html
+//! [`Forwarder`]: forward/struct.Forwarder.html
 //! [`Resolver`]: resolver/trait.Resolver.html
 //! [`StubResolver`]: stub/struct.StubResolver.html
 #![cfg(feature = "resolv")]
 #![cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
 
+pub use self::forward::Forwarder;
 pub use self::resolver::Resolver;
 pub use self::stub::StubResolver;
 
+pub mod forward;
+pub mod infra;
 pub mod lookup;
 pub mod resolver;
+pub mod scrub;
 pub mod stub;
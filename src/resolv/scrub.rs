@@ -0,0 +1,483 @@
+//! Sanitizing responses from authoritative servers.
+//!
+//! A resolver iterating from the root zone down asks many servers it has
+//! no particular reason to trust. Before anything from a response is kept,
+//! for instance in an [`InfraCache`], the response should therefore be
+//! stripped of all records the server had no business sending. This is
+//! what [`scrub`] does. Given the response and the bailiwick of the server,
+//! i.e., the zone it was asked about, it returns a copy of the response
+//! with these records dropped:
+//!
+//! * Records whose owner is outside the bailiwick. A server for `example.`
+//!   can’t provide records for `example.org.`.
+//! * Records in the answer section that are not for the queried name or
+//!   a name the query name is aliased to by CNAME or DNAME records of the
+//!   answer, or that are for a type other than the queried one.
+//! * Records in the authority section other than SOA, NS, and DS records
+//!   for a zone enclosing the queried name, NSEC and NSEC3 records, and
+//!   their signatures.
+//! * Records in the additional section other than address records for
+//!   the name servers listed in NS records of the response and their
+//!   signatures. The OPT record is kept.
+//!
+//! How many records were dropped for each reason is returned as
+//! [`ScrubStats`] and can be summed up over many responses in
+//! [`ScrubCounters`].
+//!
+//! The crate doesn’t contain an iterative resolver yet. The function is
+//! provided as a building block for one.
+//!
+//! [`InfraCache`]: super::infra::InfraCache
+
+use crate::base::iana::Rtype;
+use crate::base::message::{CopyRecordsError, Message, RecordSection};
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::record::Record;
+use crate::rdata::AllRecordData;
+use bytes::Bytes;
+use octseq::octets::Octets;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::vec::Vec;
+
+//------------ scrub ---------------------------------------------------------
+
+/// Returns a copy of a response with all unjustified records dropped.
+///
+/// The `bailiwick` is the zone the server was asked about. See the
+/// [module documentation][self] for the records that are dropped.
+pub fn scrub<Octs: Octets>(
+    response: &Message<Octs>,
+    bailiwick: &impl ToDname,
+) -> Result<(Message<Bytes>, ScrubStats), CopyRecordsError> {
+    let mut stats = ScrubStats::default();
+    let question = response.first_question();
+    let answer = records(response.answer()?)?;
+    let authority = records(response.authority()?)?;
+    let additional = records(response.additional()?)?;
+
+    // The names the answer is for: the query name and all names it is
+    // aliased to.
+    let mut names: Vec<Dname<Vec<u8>>> = Vec::new();
+    if let Some(question) = question.as_ref() {
+        names.push(question.qname().to_vec());
+    }
+    let qtype = question.as_ref().map(|question| question.qtype());
+    if qtype != Some(Rtype::Cname) {
+        loop {
+            let target =
+                answer.iter().find_map(|record| match record.data() {
+                    AllRecordData::Cname(cname)
+                        if is_for(record.owner(), &names) =>
+                    {
+                        Some(cname.cname().to_vec())
+                    }
+                    _ => None,
+                });
+            match target {
+                Some(target) if !is_for(&target, &names) => {
+                    names.push(target)
+                }
+                _ => break,
+            }
+        }
+    }
+
+    let mut nameservers: Vec<Dname<Vec<u8>>> = Vec::new();
+    let answer: Vec<_> = answer
+        .into_iter()
+        .filter(|record| {
+            let rtype = covered(record);
+            let keep = match qtype {
+                Some(_) if rtype == Rtype::Dname => {
+                    names.iter().any(|name| {
+                        name.ends_with(record.owner())
+                            && !name.name_eq(record.owner())
+                    })
+                }
+                Some(qtype) => {
+                    is_for(record.owner(), &names)
+                        && (rtype == qtype
+                            || rtype == Rtype::Cname
+                            || qtype == Rtype::Any)
+                }
+                None => false,
+            };
+            stats.check(record, keep, bailiwick, Reason::Unsolicited)
+        })
+        .inspect(|record| {
+            if let AllRecordData::Ns(ns) = record.data() {
+                nameservers.push(ns.nsdname().to_vec())
+            }
+        })
+        .collect();
+
+    let authority: Vec<_> = authority
+        .into_iter()
+        .filter(|record| {
+            let keep = match covered(record) {
+                Rtype::Soa | Rtype::Ns | Rtype::Ds => {
+                    names.iter().any(|name| name.ends_with(record.owner()))
+                }
+                Rtype::Nsec | Rtype::Nsec3 => true,
+                _ => false,
+            };
+            stats.check(record, keep, bailiwick, Reason::Unsolicited)
+        })
+        .inspect(|record| {
+            if let AllRecordData::Ns(ns) = record.data() {
+                nameservers.push(ns.nsdname().to_vec())
+            }
+        })
+        .collect();
+
+    let additional: Vec<_> = additional
+        .into_iter()
+        .filter(|record| {
+            if record.rtype() == Rtype::Opt {
+                return true;
+            }
+            let keep = matches!(covered(record), Rtype::A | Rtype::Aaaa)
+                && is_for(record.owner(), &nameservers);
+            stats.check(record, keep, bailiwick, Reason::Additional)
+        })
+        .collect();
+
+    let mut builder = MessageBuilder::new_bytes();
+    *builder.header_mut() = response.header();
+    let mut builder = builder.question();
+    for question in response.question() {
+        builder.push(question?).map_err(CopyRecordsError::Push)?;
+    }
+    let mut builder = builder.answer();
+    for record in answer {
+        builder.push(record).map_err(CopyRecordsError::Push)?;
+    }
+    let mut builder = builder.authority();
+    for record in authority {
+        builder.push(record).map_err(CopyRecordsError::Push)?;
+    }
+    let mut builder = builder.additional();
+    for record in additional {
+        builder.push(record).map_err(CopyRecordsError::Push)?;
+    }
+    Ok((builder.into_message(), stats))
+}
+
+type ParsedRecord<'a, Octs> = Record<
+    ParsedDname<<Octs as Octets>::Range<'a>>,
+    AllRecordData<
+        <Octs as Octets>::Range<'a>,
+        ParsedDname<<Octs as Octets>::Range<'a>>,
+    >,
+>;
+
+/// Parses all records of a section.
+fn records<Octs: Octets>(
+    section: RecordSection<'_, Octs>,
+) -> Result<Vec<ParsedRecord<'_, Octs>>, CopyRecordsError> {
+    let mut res = Vec::new();
+    for record in section {
+        if let Some(record) = record?.into_record()? {
+            res.push(record)
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the record type or the type covered by a signature.
+fn covered<O, N>(record: &Record<N, AllRecordData<O, N>>) -> Rtype {
+    match record.data() {
+        AllRecordData::Rrsig(rrsig) => rrsig.type_covered(),
+        _ => record.rtype(),
+    }
+}
+
+/// Returns whether a name is one of the given names.
+fn is_for(name: &impl ToDname, names: &[Dname<Vec<u8>>]) -> bool {
+    names.iter().any(|item| item.name_eq(name))
+}
+
+//------------ Reason --------------------------------------------------------
+
+/// The reason a record was dropped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Reason {
+    /// The record was not asked for.
+    Unsolicited,
+
+    /// The record is additional data not for a listed name server.
+    Additional,
+}
+
+//------------ ScrubCounters -------------------------------------------------
+
+/// Counters for records dropped from responses.
+///
+/// The statistics of scrubbed responses are added via
+/// [`add`][Self::add]. The current values can be retrieved via
+/// [`snapshot`][Self::snapshot].
+#[derive(Debug, Default)]
+pub struct ScrubCounters {
+    out_of_bailiwick: AtomicU64,
+    unsolicited: AtomicU64,
+    additional: AtomicU64,
+}
+
+impl ScrubCounters {
+    /// Creates a new set of counters all set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the records dropped from a response.
+    pub fn add(&self, stats: &ScrubStats) {
+        self.out_of_bailiwick
+            .fetch_add(stats.out_of_bailiwick, Ordering::Relaxed);
+        self.unsolicited
+            .fetch_add(stats.unsolicited, Ordering::Relaxed);
+        self.additional
+            .fetch_add(stats.additional, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> ScrubStats {
+        ScrubStats {
+            out_of_bailiwick: self.out_of_bailiwick.load(Ordering::Relaxed),
+            unsolicited: self.unsolicited.load(Ordering::Relaxed),
+            additional: self.additional.load(Ordering::Relaxed),
+        }
+    }
+}
+
+//------------ ScrubStats ----------------------------------------------------
+
+/// The number of records dropped for each reason.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ScrubStats {
+    /// Records with an owner outside the bailiwick of the server.
+    pub out_of_bailiwick: u64,
+
+    /// Records in the answer or authority sections not asked for.
+    pub unsolicited: u64,
+
+    /// Records in the additional section not for a listed name server.
+    pub additional: u64,
+}
+
+impl ScrubStats {
+    /// Returns the total number of dropped records.
+    pub fn total(&self) -> u64 {
+        self.out_of_bailiwick + self.unsolicited + self.additional
+    }
+
+    /// Counts a record if it is dropped and returns whether it is kept.
+    ///
+    /// Records outside the bailiwick are always dropped. Otherwise, the
+    /// record is dropped for the given reason unless `keep` is true.
+    fn check<N: ToDname, D>(
+        &mut self,
+        record: &Record<N, D>,
+        keep: bool,
+        bailiwick: &impl ToDname,
+        reason: Reason,
+    ) -> bool {
+        if !record.owner().ends_with(bailiwick) {
+            self.out_of_bailiwick += 1;
+            false
+        } else if !keep {
+            match reason {
+                Reason::Unsolicited => self.unsolicited += 1,
+                Reason::Additional => self.additional += 1,
+            }
+            false
+        } else {
+            true
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rcode};
+    use crate::base::Ttl;
+    use crate::rdata::{Cname, Ns, Soa, A};
+    use core::str::FromStr;
+    use std::net::Ipv4Addr;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn a(n: u8) -> A {
+        A::new(Ipv4Addr::new(192, 0, 2, n))
+    }
+
+    fn section_names(
+        section: RecordSection<'_, Bytes>,
+    ) -> Vec<(Name, Rtype)> {
+        section
+            .map(|record| {
+                let record = record.unwrap();
+                (record.owner().to_vec(), record.rtype())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn referral() {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name("www.sub.example."), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("evil.example."), Ttl::HOUR, a(66))).unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            name("sub.example."),
+            Ttl::HOUR,
+            Ns::new(name("ns.sub.example.")),
+        ))
+        .unwrap();
+        msg.push((
+            name("sub.example."),
+            Ttl::HOUR,
+            Ns::new(name("ns.example.org.")),
+        ))
+        .unwrap();
+        msg.push((name("org."), Ttl::HOUR, Ns::new(name("ns.example."))))
+            .unwrap();
+        msg.push((
+            name("other.example."),
+            Ttl::HOUR,
+            Ns::new(name("ns.example.")),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((name("ns.sub.example."), Ttl::HOUR, a(1)))
+            .unwrap();
+        msg.push((name("ns.example.org."), Ttl::HOUR, a(2)))
+            .unwrap();
+        msg.push((name("www.example."), Ttl::HOUR, a(3))).unwrap();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            Ok(())
+        })
+        .unwrap();
+        let msg = msg.into_message();
+
+        let (scrubbed, stats) = scrub(&msg, &name("example.")).unwrap();
+        assert_eq!(
+            stats,
+            ScrubStats {
+                out_of_bailiwick: 2,
+                unsolicited: 2,
+                additional: 1,
+            }
+        );
+        assert_eq!(stats.total(), 5);
+        assert_eq!(section_names(scrubbed.answer().unwrap()), []);
+        assert_eq!(
+            section_names(scrubbed.authority().unwrap()),
+            [
+                (name("sub.example."), Rtype::Ns),
+                (name("sub.example."), Rtype::Ns)
+            ]
+        );
+        assert_eq!(
+            section_names(scrubbed.additional().unwrap()),
+            [
+                (name("ns.sub.example."), Rtype::A),
+                (Dname::root_vec(), Rtype::Opt)
+            ]
+        );
+        assert!(scrubbed.opt().is_some());
+
+        let counters = ScrubCounters::new();
+        counters.add(&stats);
+        counters.add(&stats);
+        assert_eq!(counters.snapshot().total(), 10);
+    }
+
+    #[test]
+    fn answer() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_aa(true);
+        msg.header_mut().set_rcode(Rcode::NoError);
+        let mut msg = msg.question();
+        msg.push((name("www.example."), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            name("www.example."),
+            Ttl::HOUR,
+            Cname::new(name("web.example.")),
+        ))
+        .unwrap();
+        msg.push((name("web.example."), Ttl::HOUR, a(1))).unwrap();
+        msg.push((name("www.example."), Class::In, Ttl::HOUR, a(2)))
+            .unwrap();
+        msg.push((
+            name("web.example."),
+            Ttl::HOUR,
+            Ns::new(name("ns.example.")),
+        ))
+        .unwrap();
+        msg.push((name("mail.example."), Ttl::HOUR, a(3))).unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            name("example."),
+            Ttl::HOUR,
+            Soa::new(
+                name("ns.example."),
+                name("hostmaster.example."),
+                1.into(),
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+            ),
+        ))
+        .unwrap();
+        msg.push((
+            name("other.example."),
+            Ttl::HOUR,
+            Soa::new(
+                name("ns.example."),
+                name("hostmaster.example."),
+                1.into(),
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+            ),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+
+        let (scrubbed, stats) = scrub(&msg, &name("example.")).unwrap();
+        assert!(scrubbed.header().aa());
+        assert_eq!(
+            stats,
+            ScrubStats {
+                out_of_bailiwick: 0,
+                unsolicited: 3,
+                additional: 0,
+            }
+        );
+        assert_eq!(
+            section_names(scrubbed.answer().unwrap()),
+            [
+                (name("www.example."), Rtype::Cname),
+                (name("web.example."), Rtype::A),
+                (name("www.example."), Rtype::A),
+            ]
+        );
+        assert_eq!(
+            section_names(scrubbed.authority().unwrap()),
+            [(name("example."), Rtype::Soa)]
+        );
+    }
+}
@@ -0,0 +1,1480 @@
+//! Caching answers.
+//!
+//! A resolver shared by many tasks looks up the cache for every query, so
+//! the cache must not become the point where all of them wait for each
+//! other. The [`Cache`] provided by this module therefore splits its
+//! entries into a number of shards, each behind its own mutex. The shard
+//! for an entry is picked from the hash of its question, so concurrent
+//! lookups for different questions rarely contend for the same lock.
+//! Lookups hold the lock only briefly and don’t allocate while holding it.
+//!
+//! Each shard is bounded. When it is full, an entry that hasn’t been used
+//! recently is dropped to make room for a new one. Entries also expire
+//! once the smallest TTL of the records in the answer has passed or, for
+//! negative answers, the negative TTL given by the SOA record in the
+//! authority section as described in [RFC 2308]. Expired entries are
+//! removed from a shard whenever it is accessed. When an answer is served
+//! from the cache, the TTLs of its records are reduced to the time it has
+//! left in the cache.
+//!
+//! # Stale answers and prefetching
+//!
+//! The cache can keep answers for a while after they have expired, so that
+//! they can still be served if no fresh answer can be had as described in
+//! [RFC 8767]. This is enabled by setting a stale TTL via
+//! [`Cache::set_stale_ttl`]. In addition, the cache can ask for answers to
+//! be refreshed shortly before they expire if they are still in use. This
+//! is enabled by setting a prefetch threshold via
+//! [`Cache::set_prefetch_threshold`].
+//!
+//! Both stale and expiring answers are refreshed in the background by the
+//! future returned from [`StubResolver::refresh_cache`] while the cache
+//! keeps serving them. Since the resolver doesn’t spawn tasks itself, this
+//! future needs to be spawned by the application. If it isn’t running,
+//! the resolver tries to get a fresh answer for a question with a stale
+//! answer first and only falls back to the stale answer if that fails.
+//! Stale answers are served with the TTLs of all their records set to
+//! [`STALE_ANSWER_TTL`].
+//!
+//! # Client subnets
+//!
+//! An answer to a query with the EDNS client subnet option described in
+//! [RFC 7871] may only be valid for clients within the address prefix the
+//! server gave in the option of its response. Such answers are kept
+//! together with this [`SubnetScope`] and are only returned by
+//! [`Cache::get_for_client`] and [`Cache::lookup_for_client`] when the
+//! scope covers the client’s address. If several answers cover it, the
+//! one with the longest prefix is used. Answers with a scope prefix
+//! length of zero are valid for everyone. Answers kept for a scope are
+//! not refreshed in the background.
+//!
+//! # Snapshots
+//!
+//! A process that restarts with an empty cache has to ask upstream for
+//! everything again at once. To avoid this, the content of the cache can
+//! be written to a snapshot via [`Cache::write_snapshot`] or
+//! [`Cache::save_snapshot`] before shutting down and read back via
+//! [`Cache::read_snapshot`] or [`Cache::load_snapshot`] when starting up.
+//! Since the snapshot records the wall-clock time it was taken at, the
+//! answers expire at the same time they would have without the restart.
+//! The security status of validated answers is not kept.
+//!
+//! A cache can be attached to a [`StubResolver`] via
+//! [`StubResolver::with_cache`]. The [`Validator`] uses a cache of its own
+//! for the DNSKEY and DS answers it fetches.
+//!
+//! [`StubResolver`]: super::StubResolver
+//! [`StubResolver::with_cache`]: super::StubResolver::with_cache
+//! [`StubResolver::refresh_cache`]: super::StubResolver::refresh_cache
+//! [`Validator`]: super::validator::Validator
+//! [RFC 2308]: https://tools.ietf.org/html/rfc2308
+//! [RFC 7871]: https://tools.ietf.org/html/rfc7871
+//! [RFC 8767]: https://tools.ietf.org/html/rfc8767
+
+use super::Answer;
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::opt::subnet::SubnetScope;
+use crate::base::question::Question;
+use crate::base::wire::{Parse, ParseError};
+use crate::base::Ttl;
+use crate::rdata::Soa;
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::stream::StreamExt;
+use octseq::parse::Parser;
+use std::boxed::Box;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+use std::{fmt, fs, io};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The octets a snapshot starts with.
+///
+/// The last octet is the version of the snapshot format.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"DOMCACH\x01";
+
+/// The TTL of the records in a stale answer.
+///
+/// This is the value recommended by [RFC 8767, section 4].
+///
+/// [RFC 8767, section 4]: https://tools.ietf.org/html/rfc8767#section-4
+pub const STALE_ANSWER_TTL: Ttl = Ttl::from_secs(30);
+
+//------------ Cache ---------------------------------------------------------
+
+/// A sharded cache of answers.
+///
+/// Answers are keyed by their question. Only final answers, i.e., NOERROR
+/// and NXDOMAIN answers that aren’t truncated, with at least one record
+/// in the answer or authority sections are kept. Negative answers also
+/// need an SOA record in the authority section. Answers expire after the
+/// smallest TTL of these records – for negative answers the smaller of
+/// the SOA record’s TTL and its minimum field – clamped to the minimum and
+/// maximum TTL of the cache. If the cache has a stale TTL, expired answers
+/// are kept for that much longer.
+///
+/// The cache can be shared between threads. All methods except for those
+/// changing the configuration take `&self`.
+pub struct Cache {
+    /// The shards.
+    shards: Box<[Mutex<Shard>]>,
+
+    /// The hasher for picking the shard of a key.
+    hasher: RandomState,
+
+    /// The minimum time an answer is kept.
+    min_ttl: Ttl,
+
+    /// The maximum time an answer is kept.
+    max_ttl: Ttl,
+
+    /// The time an answer is kept after it has expired.
+    stale_ttl: Ttl,
+
+    /// The remaining TTL below which an answer is refreshed.
+    prefetch_threshold: Ttl,
+
+    /// The sending end of the queue of questions to refresh.
+    refresh_tx: mpsc::UnboundedSender<RefreshQuestion>,
+
+    /// The receiving end of the queue unless a refresher has it.
+    refresh_rx: Mutex<Option<mpsc::UnboundedReceiver<RefreshQuestion>>>,
+
+    /// Whether a refresher is currently running.
+    refreshing: AtomicBool,
+}
+
+impl Cache {
+    /// The default number of shards.
+    pub const DEFAULT_SHARDS: usize = 16;
+
+    /// The default maximum time an answer is kept.
+    pub const DEFAULT_MAX_TTL: Ttl = Ttl::DAY;
+
+    /// Creates a new cache holding up to about `capacity` answers.
+    ///
+    /// The cache uses the default number of shards.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_shards(capacity, Self::DEFAULT_SHARDS)
+    }
+
+    /// Creates a new cache with the given number of shards.
+    ///
+    /// The capacity is split evenly between the shards, rounding up. At
+    /// least one shard is used.
+    pub fn with_shards(capacity: usize, shards: usize) -> Self {
+        let shards = shards.max(1);
+        let shard_capacity =
+            capacity / shards + usize::from(capacity % shards != 0);
+        let (refresh_tx, refresh_rx) = mpsc::unbounded();
+        Cache {
+            shards: (0..shards)
+                .map(|_| Mutex::new(Shard::new(shard_capacity)))
+                .collect(),
+            hasher: RandomState::new(),
+            min_ttl: Ttl::ZERO,
+            max_ttl: Self::DEFAULT_MAX_TTL,
+            stale_ttl: Ttl::ZERO,
+            prefetch_threshold: Ttl::ZERO,
+            refresh_tx,
+            refresh_rx: Mutex::new(Some(refresh_rx)),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the minimum time an answer is kept.
+    ///
+    /// Answers with a smaller TTL are kept for this long anyway. This
+    /// reduces the number of queries for names with very short TTLs at
+    /// the price of serving outdated data. The default is zero.
+    pub fn set_min_ttl(&mut self, ttl: Ttl) {
+        self.min_ttl = ttl
+    }
+
+    /// Returns the minimum time in seconds an answer is kept.
+    ///
+    /// The default is zero.
+    pub fn min_ttl(&self) -> Ttl {
+        self.min_ttl
+    }
+
+    /// Sets the maximum time an answer is kept.
+    ///
+    /// This takes precedence over the minimum TTL.
+    pub fn set_max_ttl(&mut self, ttl: Ttl) {
+        self.max_ttl = ttl
+    }
+
+    /// Returns the maximum time in seconds an answer is kept.
+    ///
+    /// The default is [`DEFAULT_MAX_TTL`][Self::DEFAULT_MAX_TTL], i.e.,
+    /// one day.
+    pub fn max_ttl(&self) -> Ttl {
+        self.max_ttl
+    }
+
+    /// Sets the time answers are kept after they have expired.
+    ///
+    /// During this time, the answers can be served if no fresh answer can
+    /// be obtained. RFC 8767 suggests a value between one and three days.
+    /// The default is zero which disables serving stale answers.
+    pub fn set_stale_ttl(&mut self, ttl: Ttl) {
+        self.stale_ttl = ttl
+    }
+
+    /// Returns the time in seconds answers are kept after they expired.
+    ///
+    /// The default is zero, i.e., stale answers aren’t served.
+    pub fn stale_ttl(&self) -> Ttl {
+        self.stale_ttl
+    }
+
+    /// Sets the remaining TTL below which answers are refreshed.
+    ///
+    /// When an answer is looked up and it expires in less than this time,
+    /// it is queued for being refreshed. The default is zero which disables
+    /// prefetching.
+    pub fn set_prefetch_threshold(&mut self, ttl: Ttl) {
+        self.prefetch_threshold = ttl
+    }
+
+    /// Returns the remaining TTL in seconds below which answers are
+    /// refreshed.
+    ///
+    /// The default is zero, i.e., answers aren’t prefetched.
+    pub fn prefetch_threshold(&self) -> Ttl {
+        self.prefetch_threshold
+    }
+
+    /// Returns the number of answers currently kept.
+    ///
+    /// This may include answers that have expired but haven’t been
+    /// removed yet.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len)
+            .sum()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the cached answer for a question if there is one.
+    ///
+    /// Stale answers are not returned. Use [`lookup`][Self::lookup] to
+    /// get those, too. Answers kept for a client subnet are not returned
+    /// either. Use [`get_for_client`][Self::get_for_client] for those.
+    pub fn get<N: ToDname>(&self, question: &Question<N>) -> Option<Answer> {
+        Self::fresh(self.lookup_at(question, None, Instant::now()))
+    }
+
+    /// Returns the cached answer for a question asked by a client.
+    ///
+    /// Like [`get`][Self::get] but also considers answers kept for a
+    /// client subnet that covers `client`.
+    pub fn get_for_client<N: ToDname>(
+        &self,
+        question: &Question<N>,
+        client: IpAddr,
+    ) -> Option<Answer> {
+        Self::fresh(self.lookup_at(question, Some(client), Instant::now()))
+    }
+
+    /// Looks up the cached answer for a question and its freshness.
+    ///
+    /// If the answer is stale or about to expire and a refresher is
+    /// running, the question is queued for refreshing.
+    pub fn lookup<N: ToDname>(
+        &self,
+        question: &Question<N>,
+    ) -> Option<(Answer, Freshness)> {
+        self.lookup_at(question, None, Instant::now())
+    }
+
+    /// Looks up the cached answer for a question asked by a client.
+    ///
+    /// Like [`lookup`][Self::lookup] but also considers answers kept for a
+    /// client subnet that covers `client`.
+    pub fn lookup_for_client<N: ToDname>(
+        &self,
+        question: &Question<N>,
+        client: IpAddr,
+    ) -> Option<(Answer, Freshness)> {
+        self.lookup_at(question, Some(client), Instant::now())
+    }
+
+    /// Adds an answer to the cache.
+    ///
+    /// The answer is kept for its first question and, if it has a client
+    /// subnet option, the scope given there. It replaces an answer already
+    /// kept for the same question and scope. Answers that can’t be cached
+    /// are ignored.
+    pub fn insert(&self, answer: Answer) {
+        self.insert_at(answer, Instant::now())
+    }
+
+    /// Removes the answers for a question, including those for all scopes.
+    pub fn remove<N: ToDname>(&self, question: &Question<N>) {
+        let hash = self.hash(question);
+        self.shard(hash).lock().unwrap().remove(hash, question);
+    }
+
+    /// Removes all answers.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().clear()
+        }
+    }
+
+    /// Writes a snapshot of the cache.
+    ///
+    /// The snapshot contains all answers that haven’t been removed yet,
+    /// including stale answers, together with the time they expire. Returns
+    /// the number of answers written.
+    pub fn write_snapshot(
+        &self,
+        target: &mut impl io::Write,
+    ) -> Result<usize, io::Error> {
+        self.write_snapshot_at(target, Instant::now(), SystemTime::now())
+    }
+
+    /// Adds the answers from a snapshot to the cache.
+    ///
+    /// The time that has passed since the snapshot was taken is deducted
+    /// from the remaining time of each answer. Answers that should have
+    /// been removed by now are skipped and so are answers the cache
+    /// wouldn’t accept. The answers replace answers already kept for the
+    /// same question. Returns the number of answers added.
+    ///
+    /// If the snapshot is malformed, an error is returned. Answers read up
+    /// to that point will have been added.
+    pub fn read_snapshot(
+        &self,
+        source: &mut impl io::Read,
+    ) -> Result<usize, io::Error> {
+        self.read_snapshot_at(source, Instant::now(), SystemTime::now())
+    }
+
+    /// Saves a snapshot of the cache to a file.
+    ///
+    /// The snapshot is first written to a temporary file next to `path`
+    /// which then replaces the file at `path`, so an existing snapshot is
+    /// only replaced by a complete one.
+    pub fn save_snapshot(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, io::Error> {
+        let path = path.as_ref();
+        let mut tmp_path = std::ffi::OsString::from(path.as_os_str());
+        tmp_path.push(".tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        let res = self.write_snapshot(&mut file)?;
+        file.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(res)
+    }
+
+    /// Loads a snapshot of the cache from a file.
+    ///
+    /// See [`read_snapshot`][Self::read_snapshot] for details.
+    pub fn load_snapshot(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, io::Error> {
+        self.read_snapshot(&mut io::BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Returns whether a refresher is currently running.
+    pub fn is_refreshing(&self) -> bool {
+        self.refreshing.load(Ordering::Relaxed)
+    }
+
+    /// Starts refreshing answers.
+    ///
+    /// Returns `None` if another refresher is already running.
+    pub(super) fn refresher(&self) -> Option<Refresher<'_>> {
+        let rx = self.refresh_rx.lock().unwrap().take()?;
+        self.refreshing.store(true, Ordering::Relaxed);
+        Some(Refresher {
+            cache: self,
+            rx: Some(rx),
+        })
+    }
+
+    /// Marks that an attempt to refresh a question has finished.
+    ///
+    /// If the attempt didn’t produce a new answer, the old answer will be
+    /// queued again the next time it is looked up.
+    pub(super) fn refreshed<N: ToDname>(&self, question: &Question<N>) {
+        let hash = self.hash(question);
+        let mut shard = self.shard(hash).lock().unwrap();
+        if let Some(entry) = shard.find(hash, question, None) {
+            entry.refreshing = false;
+        }
+    }
+
+    /// Returns the answer of a lookup if it isn’t stale.
+    fn fresh(res: Option<(Answer, Freshness)>) -> Option<Answer> {
+        match res {
+            Some((answer, freshness)) if !freshness.is_stale() => {
+                Some(answer)
+            }
+            _ => None,
+        }
+    }
+
+    fn lookup_at<N: ToDname>(
+        &self,
+        question: &Question<N>,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> Option<(Answer, Freshness)> {
+        let hash = self.hash(question);
+        let mut shard = self.shard(hash).lock().unwrap();
+        let entry = shard.get(hash, question, client, now)?;
+        let expires = entry.expires;
+        let freshness = if entry.expires <= now {
+            Freshness::Stale
+        } else if entry.expires.duration_since(now)
+            < self.prefetch_threshold.into_duration()
+        {
+            Freshness::Expiring
+        } else {
+            Freshness::Fresh
+        };
+        if freshness != Freshness::Fresh
+            && entry.scope.is_none()
+            && !entry.refreshing
+            && self.is_refreshing()
+            && self
+                .refresh_tx
+                .unbounded_send(entry.question.clone())
+                .is_ok()
+        {
+            entry.refreshing = true;
+        }
+        let mut answer = entry.answer.clone();
+        drop(shard);
+        answer.message = if freshness.is_stale() {
+            rewrite_ttls(&answer.message, |_| STALE_ANSWER_TTL)
+        } else {
+            let remaining = Ttl::from_secs(secs_until(expires, now));
+            rewrite_ttls(&answer.message, |ttl| ttl.min(remaining))
+        }
+        .ok()?;
+        answer.stale = freshness.is_stale();
+        Some((answer, freshness))
+    }
+
+    fn insert_at(&self, answer: Answer, now: Instant) {
+        if !answer.is_final() {
+            return;
+        }
+        let ttl = match min_ttl(&answer) {
+            Some(ttl) => ttl.max(self.min_ttl).min(self.max_ttl),
+            None => return,
+        };
+        let question = match cache_question(&answer) {
+            Some(question) => question,
+            None => return,
+        };
+        let scope = cache_scope(&answer);
+        let hash = self.hash(&question);
+        let expires = now + ttl.into_duration();
+        let removes = expires + self.stale_ttl.into_duration();
+        self.shard(hash)
+            .lock()
+            .unwrap()
+            .insert(hash, question, scope, answer, expires, removes, now);
+    }
+
+    /// Writes a snapshot taken at the given time.
+    ///
+    /// The snapshot starts with the magic octets followed by the wall-clock
+    /// time in seconds since the Unix epoch as a `u64`. Each answer is
+    /// then written as the seconds until it expires and until it is
+    /// removed as `u32`s followed by the length of the message as a `u16`
+    /// and the message itself. A length of zero ends the snapshot.
+    fn write_snapshot_at(
+        &self,
+        target: &mut impl io::Write,
+        now: Instant,
+        wall: SystemTime,
+    ) -> Result<usize, io::Error> {
+        let wall = wall
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    "system time before 1970",
+                )
+            })?
+            .as_secs();
+        target.write_all(SNAPSHOT_MAGIC)?;
+        target.write_all(&wall.to_be_bytes())?;
+        let mut count = 0;
+        for shard in self.shards.iter() {
+            // Don’t hold the lock while writing.
+            let entries: Vec<_> = shard
+                .lock()
+                .unwrap()
+                .entries
+                .values()
+                .flatten()
+                .filter(|entry| entry.removes > now)
+                .map(|entry| {
+                    (
+                        secs_until(entry.expires, now),
+                        secs_until(entry.removes, now),
+                        entry.answer.as_octets().clone(),
+                    )
+                })
+                .collect();
+            for (expires, removes, message) in entries {
+                let len = match u16::try_from(message.len()) {
+                    Ok(len) if len > 0 => len,
+                    _ => continue,
+                };
+                target.write_all(&expires.to_be_bytes())?;
+                target.write_all(&removes.to_be_bytes())?;
+                target.write_all(&len.to_be_bytes())?;
+                target.write_all(&message)?;
+                count += 1;
+            }
+        }
+        target.write_all(&[0; 10])?;
+        Ok(count)
+    }
+
+    /// Reads a snapshot at the given time.
+    fn read_snapshot_at(
+        &self,
+        source: &mut impl io::Read,
+        now: Instant,
+        wall: SystemTime,
+    ) -> Result<usize, io::Error> {
+        let mut magic = [0u8; 8];
+        source.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a cache snapshot",
+            ));
+        }
+        let mut taken = [0u8; 8];
+        source.read_exact(&mut taken)?;
+        let taken =
+            UNIX_EPOCH + Duration::from_secs(u64::from_be_bytes(taken));
+
+        // If the clock went backwards, we pretend no time has passed.
+        let elapsed = wall.duration_since(taken).unwrap_or_default();
+        let elapsed = u32::try_from(elapsed.as_secs()).unwrap_or(u32::MAX);
+
+        let mut count = 0;
+        loop {
+            let mut header = [0u8; 10];
+            source.read_exact(&mut header)?;
+            let expires =
+                u32::from_be_bytes(header[0..4].try_into().unwrap());
+            let removes =
+                u32::from_be_bytes(header[4..8].try_into().unwrap());
+            let len = u16::from_be_bytes(header[8..10].try_into().unwrap());
+            if len == 0 {
+                return Ok(count);
+            }
+            let mut message = vec![0u8; len.into()];
+            source.read_exact(&mut message)?;
+            let answer = Answer::from(
+                Message::from_octets(Bytes::from(message)).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid message in cache snapshot",
+                    )
+                })?,
+            );
+
+            if removes <= elapsed || !answer.is_final() {
+                continue;
+            }
+            let question = match cache_question(&answer) {
+                Some(question) => question,
+                None => continue,
+            };
+            let expires =
+                Duration::from_secs(expires.saturating_sub(elapsed).into())
+                    .min(self.max_ttl.into_duration());
+            let removes = Duration::from_secs((removes - elapsed).into())
+                .min(expires + self.stale_ttl.into_duration());
+            if removes.is_zero() {
+                continue;
+            }
+            let scope = cache_scope(&answer);
+            let hash = self.hash(&question);
+            self.shard(hash).lock().unwrap().insert(
+                hash,
+                question,
+                scope,
+                answer,
+                now + expires,
+                now + removes,
+                now,
+            );
+            count += 1;
+        }
+    }
+
+    /// Returns the hash of a question.
+    ///
+    /// Since labels hash ignoring ASCII case, so does the question.
+    fn hash<N: ToDname>(&self, question: &Question<N>) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        for label in question.qname().iter_labels() {
+            label.hash(&mut hasher)
+        }
+        question.qtype().hash(&mut hasher);
+        question.qclass().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the shard for a hash.
+    fn shard(&self, hash: u64) -> &Mutex<Shard> {
+        &self.shards[(hash % self.shards.len() as u64) as usize]
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("shards", &self.shards.len())
+            .field("min_ttl", &self.min_ttl)
+            .field("max_ttl", &self.max_ttl)
+            .field("stale_ttl", &self.stale_ttl)
+            .field("prefetch_threshold", &self.prefetch_threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+//------------ Freshness -----------------------------------------------------
+
+/// How fresh a cached answer is.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Freshness {
+    /// The answer hasn’t expired yet.
+    Fresh,
+
+    /// The answer will expire within the prefetch threshold.
+    Expiring,
+
+    /// The answer has expired but is kept within the stale TTL.
+    Stale,
+}
+
+impl Freshness {
+    /// Returns whether the answer has expired.
+    pub fn is_stale(self) -> bool {
+        matches!(self, Freshness::Stale)
+    }
+}
+
+//------------ Refresher -----------------------------------------------------
+
+/// A question to be refreshed.
+type RefreshQuestion = Question<Dname<Bytes>>;
+
+/// Receives the questions whose answers should be refreshed.
+///
+/// There is only ever one refresher for a cache. When it is dropped, the
+/// questions not yet received are kept for the next refresher.
+pub(super) struct Refresher<'a> {
+    /// The cache to refresh.
+    cache: &'a Cache,
+
+    /// The receiving end of the queue.
+    ///
+    /// This is only `None` while being dropped.
+    rx: Option<mpsc::UnboundedReceiver<RefreshQuestion>>,
+}
+
+impl<'a> Refresher<'a> {
+    /// Returns the next question to refresh.
+    pub async fn next(&mut self) -> Option<RefreshQuestion> {
+        self.rx.as_mut()?.next().await
+    }
+}
+
+impl<'a> Drop for Refresher<'a> {
+    fn drop(&mut self) {
+        self.cache.refreshing.store(false, Ordering::Relaxed);
+        *self.cache.refresh_rx.lock().unwrap() = self.rx.take();
+    }
+}
+
+//------------ Shard ---------------------------------------------------------
+
+/// One shard of the cache.
+///
+/// Entries are kept by the hash of their question. This way, looking up
+/// an answer doesn’t require converting the question into an owned key
+/// first. Entries with colliding hashes share a vec.
+///
+/// Entries are dropped in approximately least recently used order using
+/// the CLOCK algorithm: all entries are kept in a ring in insertion order
+/// and each entry has a flag that is set when it is used. To make room,
+/// entries are taken from the front of the ring. If the entry’s flag is
+/// set, it is cleared and the entry goes to the back of the ring again.
+/// Otherwise the entry is dropped. This keeps lookups cheap since they
+/// only need to set the flag.
+///
+/// In addition, the shard keeps the entries ordered by the time they are
+/// to be removed – i.e., when they expire plus the stale TTL – so that
+/// these entries can be removed without going over all entries.
+///
+/// Both the ring and the expiry index refer to entries by their hash and
+/// a stamp that is unique within the shard. Removing an entry leaves it in
+/// the ring where it is skipped later.
+struct Shard {
+    /// The entries by the hash of their question.
+    entries: HashMap<u64, Vec<Entry>>,
+
+    /// The number of entries.
+    len: usize,
+
+    /// The hashes and stamps of the entries in insertion order.
+    ring: VecDeque<(u64, u64)>,
+
+    /// The hashes of the entries by removal time and stamp.
+    expiry: BTreeMap<(Instant, u64), u64>,
+
+    /// The maximum number of entries.
+    capacity: usize,
+
+    /// The next stamp to hand out.
+    next_stamp: u64,
+}
+
+/// An answer kept in the cache.
+struct Entry {
+    /// The question the answer is for.
+    question: Question<Dname<Bytes>>,
+
+    /// The client subnet the answer is valid for.
+    ///
+    /// If this is `None`, the answer is valid for all clients.
+    scope: Option<SubnetScope>,
+
+    /// The answer.
+    answer: Answer,
+
+    /// When the answer expires.
+    expires: Instant,
+
+    /// When the answer is removed.
+    removes: Instant,
+
+    /// The stamp of the insertion of the answer.
+    stamp: u64,
+
+    /// Whether the answer has been used since it was last looked at.
+    used: bool,
+
+    /// Whether the question has been queued for refreshing.
+    refreshing: bool,
+}
+
+impl Entry {
+    fn is_for<N: ToDname>(&self, question: &Question<N>) -> bool {
+        self.question.qtype() == question.qtype()
+            && self.question.qclass() == question.qclass()
+            && self.question.qname().name_eq(question.qname())
+    }
+
+    /// Returns whether the answer can be given to a client.
+    ///
+    /// Without a client, only answers valid for all clients can be given.
+    fn is_valid_for(&self, client: Option<IpAddr>) -> bool {
+        match (self.scope, client) {
+            (None, _) => true,
+            (Some(scope), Some(client)) => scope.covers(client),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Returns the prefix length of the entry’s scope.
+    fn prefix_len(&self) -> u8 {
+        self.scope.map_or(0, |scope| scope.prefix_len())
+    }
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Self {
+        Shard {
+            entries: HashMap::new(),
+            len: 0,
+            ring: VecDeque::new(),
+            expiry: BTreeMap::new(),
+            capacity,
+            next_stamp: 0,
+        }
+    }
+
+    fn get<N: ToDname>(
+        &mut self,
+        hash: u64,
+        question: &Question<N>,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> Option<&mut Entry> {
+        self.purge(now);
+        let entry = self.find(hash, question, client)?;
+        entry.used = true;
+        Some(entry)
+    }
+
+    /// Returns the entry for a question that is most specific for a client.
+    fn find<N: ToDname>(
+        &mut self,
+        hash: u64,
+        question: &Question<N>,
+        client: Option<IpAddr>,
+    ) -> Option<&mut Entry> {
+        self.entries
+            .get_mut(&hash)?
+            .iter_mut()
+            .filter(|entry| {
+                entry.is_for(question) && entry.is_valid_for(client)
+            })
+            .max_by_key(|entry| entry.prefix_len())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert(
+        &mut self,
+        hash: u64,
+        question: Question<Dname<Bytes>>,
+        scope: Option<SubnetScope>,
+        answer: Answer,
+        expires: Instant,
+        removes: Instant,
+        now: Instant,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.purge(now);
+        let replaced = self.entries.get(&hash).and_then(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.is_for(&question) && entry.scope == scope)
+                .map(|entry| entry.stamp)
+        });
+        if let Some(stamp) = replaced {
+            self.remove_stamp(hash, stamp);
+        }
+        while self.len >= self.capacity {
+            self.evict();
+        }
+        if self.ring.len() >= self.capacity * 2 {
+            self.compact();
+        }
+        self.next_stamp += 1;
+        let stamp = self.next_stamp;
+        self.ring.push_back((hash, stamp));
+        self.expiry.insert((removes, stamp), hash);
+        self.entries.entry(hash).or_default().push(Entry {
+            question,
+            scope,
+            answer,
+            expires,
+            removes,
+            stamp,
+            used: false,
+            refreshing: false,
+        });
+        self.len += 1;
+    }
+
+    /// Removes the entries for a question for all scopes.
+    fn remove<N: ToDname>(&mut self, hash: u64, question: &Question<N>) {
+        let stamps: Vec<_> = match self.entries.get(&hash) {
+            Some(entries) => entries
+                .iter()
+                .filter(|entry| entry.is_for(question))
+                .map(|entry| entry.stamp)
+                .collect(),
+            None => return,
+        };
+        for stamp in stamps {
+            self.remove_stamp(hash, stamp);
+        }
+    }
+
+    /// Removes the entry with the given hash and stamp.
+    fn remove_stamp(&mut self, hash: u64, stamp: u64) {
+        let entries = match self.entries.get_mut(&hash) {
+            Some(entries) => entries,
+            None => return,
+        };
+        let index =
+            match entries.iter().position(|entry| entry.stamp == stamp) {
+                Some(index) => index,
+                None => return,
+            };
+        let entry = entries.swap_remove(index);
+        if entries.is_empty() {
+            self.entries.remove(&hash);
+        }
+        self.expiry.remove(&(entry.removes, stamp));
+        self.len -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+        self.ring.clear();
+        self.expiry.clear();
+    }
+
+    /// Drops one entry that hasn’t been used recently.
+    fn evict(&mut self) {
+        while let Some((hash, stamp)) = self.ring.pop_front() {
+            let entry = self.entries.get_mut(&hash).and_then(|entries| {
+                entries.iter_mut().find(|entry| entry.stamp == stamp)
+            });
+            match entry {
+                Some(entry) if entry.used => {
+                    entry.used = false;
+                    self.ring.push_back((hash, stamp));
+                }
+                Some(_) => {
+                    self.remove_stamp(hash, stamp);
+                    return;
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Drops removed entries from the ring.
+    fn compact(&mut self) {
+        let entries = &self.entries;
+        self.ring.retain(|(hash, stamp)| {
+            entries.get(hash).map_or(false, |entries| {
+                entries.iter().any(|entry| entry.stamp == *stamp)
+            })
+        });
+    }
+
+    /// Removes all entries that are due for removal at `now`.
+    fn purge(&mut self, now: Instant) {
+        while let Some((&(removes, stamp), &hash)) = self.expiry.iter().next()
+        {
+            if removes > now {
+                break;
+            }
+            self.remove_stamp(hash, stamp);
+        }
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Returns the question an answer is kept under in the cache.
+fn cache_question(answer: &Answer) -> Option<Question<Dname<Bytes>>> {
+    answer.first_question().map(|question| {
+        Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        )
+    })
+}
+
+/// Returns the client subnet an answer is valid for.
+///
+/// This is the scope of the answer’s client subnet option. Answers without
+/// the option or with a scope prefix length of zero are valid for all
+/// clients and `None` is returned.
+fn cache_scope(answer: &Answer) -> Option<SubnetScope> {
+    let scope = answer.opt()?.opt().client_subnet()?.scope();
+    if scope.prefix_len() == 0 {
+        None
+    } else {
+        Some(scope)
+    }
+}
+
+/// Returns the number of whole seconds from `now` until `when`.
+///
+/// Returns zero if `when` has already passed.
+fn secs_until(when: Instant, now: Instant) -> u32 {
+    u32::try_from(when.saturating_duration_since(now).as_secs())
+        .unwrap_or(u32::MAX)
+}
+
+/// Returns the time an answer can be cached.
+///
+/// This is the smallest TTL of the records in the answer and authority
+/// sections for positive answers. For negative answers, i.e., NXDOMAIN or
+/// NODATA, it is the smallest TTL of the records in the answer section
+/// and the smaller of the TTL and the minimum field of the SOA record in
+/// the authority section as required by [RFC 2308, section 5]. Negative
+/// answers without an SOA record can’t be cached.
+///
+/// [RFC 2308, section 5]: https://tools.ietf.org/html/rfc2308#section-5
+fn min_ttl(answer: &Answer) -> Option<Ttl> {
+    fn merge(res: Option<Ttl>, ttl: Ttl) -> Option<Ttl> {
+        Some(res.map_or(ttl, |res| res.min(ttl)))
+    }
+
+    let mut res = None;
+    let mut answers = false;
+    for record in answer.answer().ok()? {
+        res = merge(res, record.ok()?.ttl());
+        answers = true;
+    }
+    let negative = !answers || answer.header().rcode() == Rcode::NXDomain;
+    let mut soa = None;
+    for record in answer.authority().ok()? {
+        let record = record.ok()?;
+        if !negative {
+            res = merge(res, record.ttl());
+        } else if soa.is_none() && record.rtype() == Rtype::Soa {
+            if let Some(record) = record.to_record::<Soa<_>>().ok()? {
+                soa = Some(record.ttl().min(record.data().minimum()));
+            }
+        }
+    }
+    if negative {
+        res = merge(res, soa?);
+    }
+    res
+}
+
+/// Returns a copy of a message with the TTLs of its records rewritten.
+///
+/// The new TTL of each record is determined by the closure from its
+/// current TTL. The OPT record is left alone since its TTL field holds
+/// the extended rcode and flags.
+fn rewrite_ttls(
+    message: &Message<Bytes>,
+    ttl: impl Fn(Ttl) -> Ttl,
+) -> Result<Message<Bytes>, ParseError> {
+    let mut octets = message.as_slice().to_vec();
+    let mut parser = Parser::from_ref(message.as_slice());
+    parser.advance(message.question().answer()?.pos())?;
+    let counts = message.header_counts();
+    let count = usize::from(counts.ancount())
+        + usize::from(counts.nscount())
+        + usize::from(counts.arcount());
+    for _ in 0..count {
+        ParsedDname::skip(&mut parser)?;
+        let rtype = Rtype::parse(&mut parser)?;
+        parser.advance(2)?;
+        let pos = parser.pos();
+        let old = Ttl::parse_header(&mut parser)?;
+        let rdlen = u16::parse(&mut parser)?;
+        parser.advance(rdlen.into())?;
+        if rtype != Rtype::Opt {
+            octets[pos..pos + 4]
+                .copy_from_slice(&ttl(old).as_secs().to_be_bytes());
+        }
+    }
+    Message::from_octets(Bytes::from(octets))
+        .map_err(|_| ParseError::ShortInput)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::Serial;
+    use crate::rdata::A;
+    use core::str::FromStr;
+    use std::time::Duration;
+
+    /// Creates an answer with an A record or, for NXDOMAIN, an SOA record.
+    fn answer(qname: &str, ttl: u32, rcode: Rcode) -> Answer {
+        negative_answer(qname, ttl, ttl, rcode)
+    }
+
+    /// Creates an answer with the given TTL and SOA minimum if negative.
+    fn negative_answer(
+        qname: &str,
+        ttl: u32,
+        minimum: u32,
+        rcode: Rcode,
+    ) -> Answer {
+        let qname = Dname::<Vec<u8>>::from_str(qname).unwrap();
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rcode(rcode);
+        let mut msg = msg.question();
+        msg.push((&qname, Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        if rcode != Rcode::NXDomain {
+            msg.push((&qname, ttl, A::from_octets(192, 0, 2, 1)))
+                .unwrap();
+            return msg.into_message().into();
+        }
+        let mut msg = msg.authority();
+        let ttl = Ttl::from_secs(ttl);
+        msg.push((
+            &qname,
+            ttl,
+            Soa::new(
+                &qname,
+                &qname,
+                Serial(1),
+                ttl,
+                ttl,
+                ttl,
+                Ttl::from_secs(minimum),
+            ),
+        ))
+        .unwrap();
+        msg.into_message().into()
+    }
+
+    /// Returns the TTLs of the records in an answer’s answer and
+    /// authority sections.
+    fn ttls(answer: &Answer) -> Vec<u32> {
+        answer
+            .answer()
+            .unwrap()
+            .chain(answer.authority().unwrap())
+            .map(|record| record.unwrap().ttl().as_secs())
+            .collect()
+    }
+
+    fn question(qname: &str) -> Question<Dname<Vec<u8>>> {
+        Question::new_in(Dname::from_str(qname).unwrap(), Rtype::A)
+    }
+
+    #[test]
+    fn get_and_insert() {
+        let cache = Cache::new(64);
+        cache.insert(answer("example.com", 300, Rcode::NoError));
+        cache.insert(answer("example.net", 300, Rcode::ServFail));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&question("EXAMPLE.com")).is_some());
+        assert!(cache.get(&question("example.net")).is_none());
+        assert!(cache
+            .get(&Question::new_in(
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                Rtype::Aaaa
+            ))
+            .is_none());
+
+        cache.remove(&question("example.com"));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn expire() {
+        let mut cache = Cache::with_shards(64, 1);
+        cache.set_max_ttl(Ttl::from_secs(600));
+        let now = Instant::now();
+        cache.insert_at(answer("short.example", 10, Rcode::NoError), now);
+        cache.insert_at(answer("long.example", 3600, Rcode::NoError), now);
+
+        let later = now + Duration::from_secs(11);
+        assert!(cache
+            .lookup_at(&question("short.example"), None, later)
+            .is_none());
+        assert!(cache
+            .lookup_at(&question("long.example"), None, later)
+            .is_some());
+        assert_eq!(cache.len(), 1);
+
+        // The long TTL is capped by the cache’s maximum.
+        let later = now + Duration::from_secs(601);
+        assert!(cache
+            .lookup_at(&question("long.example"), None, later)
+            .is_none());
+        assert!(cache.is_empty());
+
+        // The short TTL is raised to the cache’s minimum.
+        cache.set_min_ttl(Ttl::from_secs(60));
+        cache.insert_at(answer("short.example", 10, Rcode::NoError), now);
+        let later = now + Duration::from_secs(59);
+        assert!(cache
+            .lookup_at(&question("short.example"), None, later)
+            .is_some());
+        let later = now + Duration::from_secs(61);
+        assert!(cache
+            .lookup_at(&question("short.example"), None, later)
+            .is_none());
+    }
+
+    #[test]
+    fn served_ttls() {
+        let cache = Cache::with_shards(64, 1);
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+        cache.insert_at(answer("example.com", 300, Rcode::NoError), now);
+        let (answer, _) = cache
+            .lookup_at(&question("example.com"), None, at(100))
+            .unwrap();
+        assert_eq!(ttls(&answer), [200]);
+
+        // Negative answers live for the smaller of the SOA’s TTL and
+        // minimum.
+        cache.insert_at(
+            negative_answer("nx.example", 600, 60, Rcode::NXDomain),
+            now,
+        );
+        let (answer, _) = cache
+            .lookup_at(&question("nx.example"), None, at(20))
+            .unwrap();
+        assert_eq!(ttls(&answer), [40]);
+        assert!(cache
+            .lookup_at(&question("nx.example"), None, at(61))
+            .is_none());
+        cache.insert_at(
+            negative_answer("nx.example", 60, 600, Rcode::NXDomain),
+            now,
+        );
+        assert!(cache
+            .lookup_at(&question("nx.example"), None, at(59))
+            .is_some());
+        assert!(cache
+            .lookup_at(&question("nx.example"), None, at(61))
+            .is_none());
+
+        // Negative answers without an SOA record aren’t cached.
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rcode(Rcode::NXDomain);
+        let mut msg = msg.question();
+        msg.push((Dname::<Vec<u8>>::root_vec(), Rtype::A)).unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            Dname::<Vec<u8>>::root_vec(),
+            3600,
+            crate::rdata::Ns::new(Dname::<Vec<u8>>::root_vec()),
+        ))
+        .unwrap();
+        cache.insert_at(msg.into_message().into(), now);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evict_unused() {
+        let cache = Cache::with_shards(2, 1);
+        cache.insert(answer("one.example", 300, Rcode::NoError));
+        cache.insert(answer("two.example", 300, Rcode::NoError));
+        assert!(cache.get(&question("one.example")).is_some());
+        cache.insert(answer("three.example", 300, Rcode::NoError));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&question("one.example")).is_some());
+        assert!(cache.get(&question("two.example")).is_none());
+        assert!(cache.get(&question("three.example")).is_some());
+    }
+
+    #[test]
+    fn stale_and_prefetch() {
+        use futures::future::FutureExt;
+
+        let mut cache = Cache::with_shards(64, 1);
+        cache.set_stale_ttl(Ttl::from_secs(100));
+        cache.set_prefetch_threshold(Ttl::from_secs(30));
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+        let question = question("example.com");
+        cache.insert_at(answer("example.com", 60, Rcode::NoError), now);
+
+        let freshness = |secs| {
+            cache.lookup_at(&question, None, at(secs)).map(
+                |(answer, freshness)| {
+                    assert_eq!(answer.is_stale(), freshness.is_stale());
+                    let ttl = if freshness.is_stale() {
+                        STALE_ANSWER_TTL.as_secs()
+                    } else {
+                        60 - secs as u32
+                    };
+                    assert_eq!(ttls(&answer), [ttl]);
+                    freshness
+                },
+            )
+        };
+        assert_eq!(freshness(10), Some(Freshness::Fresh));
+        assert_eq!(freshness(40), Some(Freshness::Expiring));
+        assert_eq!(freshness(70), Some(Freshness::Stale));
+
+        // Without a refresher, nothing is queued. With one, stale and
+        // expiring answers are queued once until they have been
+        // refreshed.
+        let mut refresher = cache.refresher().unwrap();
+        assert!(cache.refresher().is_none());
+        assert!(refresher.next().now_or_never().is_none());
+        assert_eq!(freshness(40), Some(Freshness::Expiring));
+        assert_eq!(freshness(70), Some(Freshness::Stale));
+        let queued = refresher.next().now_or_never().unwrap().unwrap();
+        assert_eq!(queued.qname(), question.qname());
+        assert!(refresher.next().now_or_never().is_none());
+        cache.refreshed(&question);
+        assert_eq!(freshness(70), Some(Freshness::Stale));
+        assert!(refresher.next().now_or_never().is_some());
+
+        drop(refresher);
+        assert!(!cache.is_refreshing());
+        assert!(cache.refresher().is_some());
+
+        // Past the stale TTL, the answer is gone.
+        assert_eq!(freshness(161), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut cache = Cache::with_shards(64, 4);
+        cache.set_stale_ttl(Ttl::from_secs(100));
+        let now = Instant::now();
+        let wall = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        cache.insert_at(answer("short.example", 60, Rcode::NoError), now);
+        cache.insert_at(answer("long.example", 3600, Rcode::NoError), now);
+        cache.insert_at(answer("nx.example", 600, Rcode::NXDomain), now);
+
+        let mut snapshot = Vec::new();
+        assert_eq!(
+            cache.write_snapshot_at(&mut snapshot, now, wall).unwrap(),
+            3
+        );
+
+        // Restart 90 seconds later: the short answer is stale now and
+        // the others have 90 seconds less to live.
+        let mut cache = Cache::with_shards(64, 2);
+        cache.set_stale_ttl(Ttl::from_secs(100));
+        let restart = now + Duration::from_secs(1);
+        let at = |secs| restart + Duration::from_secs(secs);
+        assert_eq!(
+            cache
+                .read_snapshot_at(
+                    &mut snapshot.as_slice(),
+                    restart,
+                    wall + Duration::from_secs(90)
+                )
+                .unwrap(),
+            3
+        );
+        let freshness = |name, secs| {
+            cache
+                .lookup_at(&question(name), None, at(secs))
+                .map(|(_, freshness)| freshness)
+        };
+        assert_eq!(freshness("short.example", 0), Some(Freshness::Stale));
+        assert_eq!(freshness("long.example", 3509), Some(Freshness::Fresh));
+        assert_eq!(freshness("long.example", 3511), Some(Freshness::Stale));
+        assert_eq!(freshness("short.example", 71), None);
+
+        // Restart much later: only the long answer is left.
+        let cache = Cache::with_shards(64, 2);
+        assert_eq!(
+            cache
+                .read_snapshot_at(
+                    &mut snapshot.as_slice(),
+                    restart,
+                    wall + Duration::from_secs(1000)
+                )
+                .unwrap(),
+            1
+        );
+        assert!(cache.get(&question("long.example")).is_some());
+
+        // Broken snapshots are rejected.
+        assert!(cache.read_snapshot(&mut &b"DOMCACH\x02"[..]).is_err());
+        assert!(cache
+            .read_snapshot(&mut &snapshot[..snapshot.len() - 1])
+            .is_err());
+    }
+
+    #[test]
+    fn client_subnet() {
+        /// Creates an answer with a client subnet option.
+        fn scoped(addr: [u8; 4], scope: u8) -> Answer {
+            let qname = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+            let mut msg = MessageBuilder::new_bytes();
+            msg.header_mut().set_qr(true);
+            let mut msg = msg.question();
+            msg.push((&qname, Rtype::A)).unwrap();
+            let mut msg = msg.answer();
+            msg.push((&qname, 300, A::new(addr.into()))).unwrap();
+            let mut msg = msg.additional();
+            msg.opt(|opt| opt.client_subnet(24, scope, addr.into()))
+                .unwrap();
+            msg.into_message().into()
+        }
+
+        fn addr(answer: Option<Answer>) -> Option<[u8; 4]> {
+            let record = answer?.answer().unwrap().limit_to::<A>().next()?;
+            Some(record.unwrap().data().addr().octets())
+        }
+
+        let cache = Cache::new(64);
+        let question = question("example.com");
+        cache.insert(scoped([192, 0, 2, 1], 24));
+
+        // A /24-scoped answer is only served to clients within the prefix.
+        let get = |client: [u8; 4]| {
+            addr(cache.get_for_client(&question, client.into()))
+        };
+        assert_eq!(get([192, 0, 2, 200]), Some([192, 0, 2, 1]));
+        assert_eq!(get([198, 51, 100, 1]), None);
+        assert_eq!(addr(cache.get(&question)), None);
+
+        // Answers for other scopes are kept alongside and the most
+        // specific one wins.
+        cache.insert(scoped([198, 51, 100, 1], 24));
+        cache.insert(scoped([203, 0, 113, 1], 0));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(get([192, 0, 2, 200]), Some([192, 0, 2, 1]));
+        assert_eq!(get([198, 51, 100, 7]), Some([198, 51, 100, 1]));
+        assert_eq!(get([10, 0, 0, 1]), Some([203, 0, 113, 1]));
+        assert_eq!(addr(cache.get(&question)), Some([203, 0, 113, 1]));
+
+        // Re-inserting replaces only the answer for the same scope.
+        cache.insert(scoped([192, 0, 2, 2], 24));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(get([192, 0, 2, 200]), Some([192, 0, 2, 2]));
+
+        cache.remove(&question);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let cache = Cache::with_shards(1024, 8);
+        let names: Vec<_> = (0..256)
+            .map(|i| std::format!("host{}.example", i))
+            .collect();
+        std::thread::scope(|scope| {
+            for chunk in names.chunks(64) {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for name in chunk {
+                        cache.insert(answer(name, 300, Rcode::NoError));
+                        assert!(cache.get(&question(name)).is_some());
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.len(), 256);
+    }
+}
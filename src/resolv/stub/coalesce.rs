@@ -0,0 +1,206 @@
+//! Coalescing concurrent queries for the same question.
+//!
+//! When many tasks ask a resolver for the same name at the same time, for
+//! instance right after an answer dropped out of the cache, there is no
+//! point in sending the same query upstream for each of them. Instead, the
+//! first task starts the query and all tasks arriving while it is still in
+//! flight wait for its result.
+//!
+//! The query is driven by whichever of the waiting tasks is polled. It is
+//! only abandoned once all of them have been dropped. An error is shared
+//! by all waiting tasks. Each of them receives an error of the same kind
+//! wrapping the original error.
+
+use super::Answer;
+use crate::base::name::Dname;
+use crate::base::question::Question;
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+//------------ InFlightQueries -----------------------------------------------
+
+/// The result of a query shared between all tasks waiting for it.
+type SharedResult = Result<Answer, Arc<io::Error>>;
+
+/// The future performing a query.
+type QueryFuture = BoxFuture<'static, SharedResult>;
+
+/// A query shared between all tasks waiting for it.
+type SharedQuery = Shared<QueryFuture>;
+
+/// The queries currently in flight.
+#[derive(Default)]
+pub(super) struct InFlightQueries {
+    /// The queries by their question.
+    ///
+    /// Each query is kept together with a stamp so that a finished query
+    /// doesn’t remove a newer query for the same question.
+    queries: Mutex<Queries>,
+}
+
+/// The map of queries and the next stamp to hand out.
+#[derive(Default)]
+struct Queries {
+    /// The queries by their question together with their stamp.
+    map: HashMap<Question<Dname<Bytes>>, (u64, WeakShared<QueryFuture>)>,
+
+    /// The stamp for the next query.
+    next_stamp: u64,
+}
+
+impl InFlightQueries {
+    /// Returns the number of queries currently in flight.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.queries.lock().unwrap().map.len()
+    }
+
+    /// Resolves a question, joining a query already in flight.
+    ///
+    /// If there is no query for `question` in flight, the future returned
+    /// by `start` is used for resolving it.
+    pub async fn resolve<F>(
+        self: &Arc<Self>,
+        question: Question<Dname<Bytes>>,
+        start: impl FnOnce() -> F,
+    ) -> Result<Answer, io::Error>
+    where
+        F: Future<Output = Result<Answer, io::Error>> + Send + 'static,
+    {
+        let query = self.join(question, start);
+        query.await.map_err(|err| io::Error::new(err.kind(), err))
+    }
+
+    /// Returns the query for a question, starting it if necessary.
+    fn join<F>(
+        self: &Arc<Self>,
+        question: Question<Dname<Bytes>>,
+        start: impl FnOnce() -> F,
+    ) -> SharedQuery
+    where
+        F: Future<Output = Result<Answer, io::Error>> + Send + 'static,
+    {
+        let mut queries = self.queries.lock().unwrap();
+        if let Some(query) = queries
+            .map
+            .get(&question)
+            .and_then(|(_, query)| query.upgrade())
+        {
+            return query;
+        }
+        queries.next_stamp += 1;
+        let stamp = queries.next_stamp;
+        let guard = RemoveOnDrop {
+            queries: self.clone(),
+            question: question.clone(),
+            stamp,
+        };
+        let fut = start();
+        let query = async move {
+            // The guard removes the query once it is finished or when it is
+            // dropped because nobody is waiting for it anymore.
+            let _guard = guard;
+            fut.await.map_err(Arc::new)
+        }
+        .boxed()
+        .shared();
+        if let Some(weak) = query.downgrade() {
+            queries.map.insert(question, (stamp, weak));
+        }
+        query
+    }
+}
+
+//------------ RemoveOnDrop --------------------------------------------------
+
+/// Removes a query from the queries in flight when dropped.
+struct RemoveOnDrop {
+    queries: Arc<InFlightQueries>,
+    question: Question<Dname<Bytes>>,
+    stamp: u64,
+}
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let mut queries = self.queries.queries.lock().unwrap();
+        if matches!(
+            queries.map.get(&self.question),
+            Some((stamp, _)) if *stamp == self.stamp
+        ) {
+            queries.map.remove(&self.question);
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::MessageBuilder;
+    use core::str::FromStr;
+    use futures::channel::oneshot;
+    use std::boxed::Box;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn question(qname: &str) -> Question<Dname<Bytes>> {
+        Question::new_in(Dname::from_str(qname).unwrap(), Rtype::A)
+    }
+
+    fn answer() -> Answer {
+        MessageBuilder::new_bytes()
+            .start_answer(
+                &MessageBuilder::new_vec().into_message(),
+                Rcode::NoError,
+            )
+            .unwrap()
+            .into_message()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn coalesce() {
+        let queries = Arc::new(InFlightQueries::default());
+        let started = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = oneshot::channel::<io::ErrorKind>();
+        let rx = rx.shared();
+
+        let start = || {
+            started.fetch_add(1, Ordering::Relaxed);
+            let rx = rx.clone();
+            async move { Err(io::Error::from(rx.await.unwrap())) }
+        };
+        let one = queries.resolve(question("example.com"), start);
+        let two = queries.resolve(question("EXAMPLE.com"), start);
+        let other = queries.resolve(question("example.net"), start);
+        let mut one = Box::pin(one);
+        let mut two = Box::pin(two);
+        let mut other = Box::pin(other);
+        assert!((&mut one).now_or_never().is_none());
+        assert!((&mut two).now_or_never().is_none());
+        assert!((&mut other).now_or_never().is_none());
+        assert_eq!(started.load(Ordering::Relaxed), 2);
+        assert_eq!(queries.len(), 2);
+
+        // Dropping all waiters abandons the query.
+        drop(other);
+        assert_eq!(queries.len(), 1);
+
+        tx.send(io::ErrorKind::TimedOut).unwrap();
+        assert_eq!(one.await.err().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(two.await.err().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(queries.len(), 0);
+
+        // A new query is started once the old one has finished.
+        let res = queries
+            .resolve(question("example.com"), || async { Ok(answer()) })
+            .await;
+        assert!(res.is_ok());
+        assert_eq!(started.load(Ordering::Relaxed), 2);
+    }
+}
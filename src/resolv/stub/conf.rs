@@ -9,6 +9,7 @@
 //! Both parts are modeled along the lines of glibc’s resolver.
 
 use crate::base::name::{self, Dname};
+use crate::base::opt::ClientSubnet;
 use smallvec::SmallVec;
 use std::cmp::Ordering;
 use std::default::Default;
@@ -17,6 +18,7 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
 use std::slice::SliceIndex;
 use std::str::{self, FromStr, SplitWhitespace};
+use std::string::String;
 use std::time::Duration;
 use std::vec::Vec;
 use std::{convert, error, fmt, fs, io, ops};
@@ -46,6 +48,18 @@ pub struct ResolvOptions {
     /// Number of retries before giving up.
     pub attempts: usize,
 
+    /// The overall time a lookup may take.
+    ///
+    /// If set, a lookup fails with a timeout error once this time has
+    /// passed, no matter how many attempts are left. The limit covers all
+    /// retries of a query and, for lookups that need several queries such
+    /// as `search_host`, all of these queries. Requests still in flight are
+    /// abandoned.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub lookup_timeout: Option<Duration>,
+
     /// Accept authoritative answers only.
     ///
     /// Only responses with the AA bit set will be considered. If there
@@ -89,7 +103,11 @@ pub struct ResolvOptions {
 
     /// Keep TCP connections open between queries.
     ///
-    /// This is not currently implemented.
+    /// If enabled, the resolver keeps TCP connections to a server open and
+    /// reuses them for later queries, sending multiple queries over the same
+    /// connection concurrently as described in RFC 7766.
+    ///
+    /// This option is implemented by the default transport.
     pub stay_open: bool,
 
     /// Search hostnames in the current domain and parent domains.
@@ -165,6 +183,119 @@ pub struct ResolvOptions {
     /// `search` and `ndots` fields govern resolution of relative names of
     /// all kinds.
     pub no_tld_query: bool,
+
+    /// Send DNS cookies to servers.
+    ///
+    /// Enabled by default.
+    ///
+    /// If enabled, the resolver includes a DNS cookie as described in
+    /// RFC 7873 in all queries to servers that support EDNS. It creates a
+    /// random client cookie for each server and remembers the server cookie
+    /// from its responses. Responses containing a wrong client cookie are
+    /// dropped.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub cookies: bool,
+
+    /// Ask servers for their name server identifier.
+    ///
+    /// If enabled, the resolver includes an empty NSID option as described
+    /// in RFC 5001 in all queries to servers that support EDNS. The
+    /// identifier returned by the server can be retrieved from the answer
+    /// via `Answer::nsid`.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub nsid: bool,
+
+    /// Ask servers to include DNSSEC records in their responses.
+    ///
+    /// If enabled, the resolver sets the DO bit in the EDNS header of all
+    /// queries to servers that support EDNS. This is enabled automatically
+    /// when the resolver validates answers.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub dnssec_ok: bool,
+
+    /// Disable DNSSEC checking.
+    ///
+    /// If enabled, the resolver sets the CD bit in all queries, asking
+    /// servers not to validate their answers, and doesn’t validate answers
+    /// itself.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub checking_disabled: bool,
+
+    /// The EDNS version to use in queries.
+    ///
+    /// Defaults to 0, the only version currently defined.
+    ///
+    /// If a server answers with BADVERS, the resolver retries with the
+    /// highest version the server claims to support. If a server answers
+    /// with FORMERR or doesn’t support any usable version, the resolver
+    /// retries without EDNS. In both cases, the resolver remembers this
+    /// for all later queries to the server.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub edns_version: u8,
+
+    /// Adapt the advertised UDP payload size to each server.
+    ///
+    /// Enabled by default.
+    ///
+    /// If enabled, the resolver lowers the payload size advertised to a
+    /// server via UDP when requests time out repeatedly, since this is a
+    /// typical symptom of dropped IP fragments, and later probes whether
+    /// larger sizes work again. The size configured for the server via
+    /// [`ServerConf::udp_payload_size`] is never exceeded.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub probe_payload_size: bool,
+
+    /// The privacy profile for talking to servers.
+    ///
+    /// Defaults to [`PrivacyProfile::Off`].
+    ///
+    /// The profile decides which servers the resolver uses based on the
+    /// privacy the transport offers for them as described in RFC 8310.
+    /// See [`PrivacyProfile`] for the details.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub privacy: PrivacyProfile,
+
+    /// Use QNAME minimisation when asking servers.
+    ///
+    /// Disabled by default.
+    ///
+    /// If enabled, the resolver first sends queries for the ancestors of a
+    /// query name that wasn’t answered from the cache as described in RFC
+    /// 9156. If a server answers NXDOMAIN for an ancestor, which broken
+    /// servers do for empty non-terminals, or fails otherwise, the
+    /// resolver falls back to sending the full query name right away.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub qname_minimisation: bool,
+
+    /// The client subnet to include in queries.
+    ///
+    /// Defaults to `None`.
+    ///
+    /// If set, the resolver includes this EDNS client subnet option as
+    /// described in RFC 7871 in all queries to servers that support EDNS
+    /// and looks up cached answers for the option’s address. Use
+    /// [`ClientSubnet::opt_out`] to ask servers not to tailor answers to
+    /// the client’s network.
+    ///
+    /// This option has no equivalent in glibc and cannot be set in a config
+    /// file.
+    pub client_subnet: Option<ClientSubnet>,
 }
 
 impl Default for ResolvOptions {
@@ -176,11 +307,17 @@ impl Default for ResolvOptions {
             ndots: 1,
             timeout: Duration::new(5, 0),
             attempts: 2,
+            lookup_timeout: None,
+            edns_version: 0,
+            privacy: PrivacyProfile::Off,
+            client_subnet: None,
 
             // enabled by default:
             recurse: true,
             default_names: true,
             dn_search: true,
+            cookies: true,
+            probe_payload_size: true,
 
             // everthing else is not:
             aa_only: false,
@@ -188,6 +325,10 @@ impl Default for ResolvOptions {
             primary: false,
             ign_tc: false,
             stay_open: false,
+            nsid: false,
+            dnssec_ok: false,
+            checking_disabled: false,
+            qname_minimisation: false,
             use_inet6: false,
             rotate: false,
             no_check_name: false,
@@ -237,6 +378,90 @@ impl Transport {
     }
 }
 
+//------------ PrivacyProfile ------------------------------------------------
+
+/// The usage profile for encrypted transports as defined in RFC 8310.
+///
+/// Whether a server can be reached via an encrypted and authenticated
+/// transport is decided by the resolver’s transport via
+/// [`Transport::privacy`][super::transport::Transport::privacy]. The
+/// transport uses the [`ServerAuth`] of the server’s configuration to
+/// authenticate it.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum PrivacyProfile {
+    /// All servers are used in the order they are configured.
+    #[default]
+    Off,
+
+    /// Servers offering encryption are tried first.
+    ///
+    /// Servers with authenticated encryption come before servers with
+    /// encryption only which come before those without encryption. Falling
+    /// back to unencrypted DNS is allowed.
+    Opportunistic,
+
+    /// Only servers offering authenticated encryption are used.
+    ///
+    /// If there are no such servers, all queries fail.
+    Strict,
+}
+
+//------------ ServerAuth ----------------------------------------------------
+
+/// How a server reached via an encrypted transport is authenticated.
+///
+/// Following RFC 8310, a server can be authenticated by its authentication
+/// domain name, i.e., the name its certificate has to be valid for, by a
+/// set of SPKI pins as defined in RFC 7858, or both. If both are given,
+/// both have to match.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ServerAuth {
+    /// The authentication domain name of the server.
+    pub name: Option<String>,
+
+    /// The SHA-256 digests of acceptable subject public key infos.
+    pub spki_pins: Vec<[u8; 32]>,
+}
+
+impl ServerAuth {
+    /// Creates authentication by the given authentication domain name.
+    pub fn by_name(name: impl Into<String>) -> Self {
+        ServerAuth {
+            name: Some(name.into()),
+            spki_pins: Vec::new(),
+        }
+    }
+
+    /// Creates authentication by the given set of SPKI pins.
+    pub fn by_spki_pins(pins: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        ServerAuth {
+            name: None,
+            spki_pins: pins.into_iter().collect(),
+        }
+    }
+
+    /// Adds SPKI pins to authentication by name.
+    pub fn with_spki_pins(
+        mut self,
+        pins: impl IntoIterator<Item = [u8; 32]>,
+    ) -> Self {
+        self.spki_pins.extend(pins);
+        self
+    }
+
+    /// Returns whether the authentication requires anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.spki_pins.is_empty()
+    }
+
+    /// Checks the SPKI digest presented by a server against the pin set.
+    ///
+    /// Returns `true` if there are no pins or if `digest` is one of them.
+    pub fn check_spki(&self, digest: &[u8; 32]) -> bool {
+        self.spki_pins.is_empty() || self.spki_pins.contains(digest)
+    }
+}
+
 //------------ ServerConf ----------------------------------------------------
 
 /// Configuration for one upstream DNS server.
@@ -277,7 +502,18 @@ pub struct ServerConf {
     /// This values will be announced in request if EDNS is supported by the
     /// server. It will be included both for datagram and streaming transport
     /// but really only matters for UDP.
+    ///
+    /// This field defaults to 1232 bytes as recommended by DNS Flag Day
+    /// 2020. With the `probe_payload_size` option, the resolver may
+    /// advertise a smaller size for datagram transports if it suspects
+    /// fragmentation.
     pub udp_payload_size: u16,
+
+    /// How the server is authenticated by encrypted transports.
+    ///
+    /// This field defaults to `None`. It is only used by transports that
+    /// encrypt their connections.
+    pub auth: Option<ServerAuth>,
 }
 
 impl ServerConf {
@@ -292,6 +528,7 @@ impl ServerConf {
             request_timeout: Duration::from_secs(2),
             recv_size: 1232,
             udp_payload_size: 1232,
+            auth: None,
         }
     }
 }
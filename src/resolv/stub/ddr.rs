@@ -0,0 +1,539 @@
+//! Discovery of designated resolvers.
+//!
+//! [RFC 9462] defines how a client can learn from the resolver it talks to
+//! via unencrypted DNS which encrypted resolvers are designated to take
+//! its place. The client asks each of its configured resolvers for the
+//! SVCB records of the special name `_dns.resolver.arpa`. Each of these
+//! records describes a designated resolver: its name, addresses, port, and
+//! the protocols it supports as ALPN protocol IDs.
+//!
+//! Since the unencrypted answers could have been forged, the client has to
+//! verify the designation before using it. The designated resolver’s TLS
+//! certificate has to be valid for the IP address of the unencrypted
+//! resolver, proving that whoever operates that address also operates the
+//! designated resolver.
+//!
+//! [`discover`] performs the discovery and returns the [`Designation`]s
+//! found for all unencrypted servers of a stub resolver. [`upgrade`] goes
+//! one step further and switches a resolver over to DNS-over-HTTPS if one
+//! of its servers designates a resolver that can be verified. The stub
+//! resolver leaves HTTP to a client supplied by the user, so [`upgrade`]
+//! directs it to the unencrypted resolver’s IP address. The client verifies
+//! the server’s certificate for that address as it would for any URL and
+//! thereby verifies the designation.
+//!
+//! Discovery is never done automatically. Applications that want to use it
+//! call [`upgrade`] once after creating their resolver.
+//!
+//! [RFC 9462]: https://tools.ietf.org/html/rfc9462
+
+use super::conf::{
+    ResolvConf, ServerAuth, ServerConf, Transport as Protocol,
+};
+use super::transport::doh::{DohTransport, HttpClient};
+use super::transport::{Privacy, Transport};
+use super::StubResolver;
+use crate::base::iana::Rtype;
+use crate::base::name::Dname;
+use crate::rdata::svcb::SvcbVariant;
+use crate::resolv::lookup::host::lookup_host;
+use crate::resolv::lookup::svcb::{process_answer, Binding, ServiceRecord};
+use core::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The name queried for designated resolvers.
+const DDR_NAME: &[u8] = b"\x04_dns\x08resolver\x04arpa\x00";
+
+//------------ discover ------------------------------------------------------
+
+/// Discovers the resolvers designated by a resolver’s servers.
+///
+/// Each server of `resolver` that is reached without encryption is asked
+/// for the SVCB records of `_dns.resolver.arpa`. The designations are
+/// returned in the order of the servers and, for each server, in the order
+/// of the records’ priorities.
+///
+/// The addresses of a designated resolver are taken from the address hints
+/// of its record or, if there are none, looked up via the server that
+/// designated it. Servers that fail to answer are skipped.
+///
+/// None of the designations returned are verified.
+pub async fn discover(resolver: &StubResolver) -> Vec<Designation> {
+    let mut res = Vec::new();
+    for addr in unencrypted_servers(resolver) {
+        let server = server_resolver(resolver, addr);
+        res.extend(discover_server(&server, addr).await);
+    }
+    res
+}
+
+/// Discovers the resolvers designated by a single server.
+async fn discover_server(
+    server: &StubResolver,
+    unencrypted: IpAddr,
+) -> Vec<Designation> {
+    let qname = Dname::from_slice(DDR_NAME).unwrap();
+    let answer = match server.query((qname, Rtype::Svcb)).await {
+        Ok(answer) => answer,
+        Err(_) => return Vec::new(),
+    };
+    // AliasMode records are not used by discovery.
+    let mut records =
+        match process_answer::<SvcbVariant>(answer.as_ref().for_slice()) {
+            Some(Binding::Service(records)) => records,
+            _ => return Vec::new(),
+        };
+    records.sort_by_key(|record| record.priority);
+
+    let mut res = Vec::new();
+    for record in records {
+        let addrs = if record.hints.is_empty() {
+            match lookup_host(&server, &record.target).await {
+                Ok(found) => found.iter().collect(),
+                Err(_) => Vec::new(),
+            }
+        } else {
+            record.hints.clone()
+        };
+        res.push(Designation::new(unencrypted, record, addrs));
+    }
+    res
+}
+
+//------------ upgrade -------------------------------------------------------
+
+/// Upgrades a resolver to DNS-over-HTTPS via a designated resolver.
+///
+/// Runs [`discover`] and picks the first designation that supports
+/// DNS-over-HTTPS and can be verified as described for
+/// [`Designation::doh_url`]. If there is one, returns a new resolver that
+/// sends all queries via `client` to the URL of the designated resolver
+/// together with the designation. The new resolver keeps the options,
+/// retry policy, cache, and everything else attached to `resolver`.
+///
+/// If no suitable designation is found, returns `resolver` unchanged and
+/// `None`.
+pub async fn upgrade<C: HttpClient + 'static>(
+    resolver: StubResolver,
+    client: C,
+) -> (StubResolver, Option<Designation>) {
+    let designation = discover(&resolver)
+        .await
+        .into_iter()
+        .find(|designation| designation.doh_url().is_some());
+    let designation = match designation {
+        Some(designation) => designation,
+        None => return (resolver, None),
+    };
+    let url = designation.doh_url().unwrap();
+    let transport = DohTransport::new(url, client);
+
+    // The server address isn’t used by the transport but it determines the
+    // server’s privacy and will show up in statistics.
+    let mut conf = ResolvConf::new();
+    conf.options = resolver.options.clone();
+    let addr = SocketAddr::new(
+        designation.unencrypted_addr(),
+        designation.port(DesignatedProtocol::Https),
+    );
+    for protocol in [Protocol::Udp, Protocol::Tcp] {
+        let mut server = ServerConf::new(addr, protocol);
+        server.request_timeout = conf.options.timeout;
+        server.auth = Some(designation.server_auth());
+        conf.servers.push(server);
+    }
+
+    let mut res = StubResolver::from_conf_with_transport(conf, transport);
+    res.retry = resolver.retry.clone();
+    res.cache = resolver.cache.clone();
+    #[cfg(feature = "validate")]
+    {
+        res.validator = resolver.validator.clone();
+    }
+    res.metrics = resolver.metrics.clone();
+    #[cfg(feature = "zonetree")]
+    {
+        res.rpz = resolver.rpz.clone();
+    }
+    (res, Some(designation))
+}
+
+//------------ DesignatedProtocol --------------------------------------------
+
+/// An encrypted protocol offered by a designated resolver.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DesignatedProtocol {
+    /// DNS-over-HTTPS as defined in RFC 8484.
+    Https,
+
+    /// DNS-over-TLS as defined in RFC 7858.
+    Tls,
+
+    /// DNS-over-QUIC as defined in RFC 9250.
+    Quic,
+}
+
+impl DesignatedProtocol {
+    /// Returns whether an ALPN protocol ID selects this protocol.
+    fn matches(self, alpn: &[u8]) -> bool {
+        match self {
+            DesignatedProtocol::Https => alpn == b"h2" || alpn == b"h3",
+            DesignatedProtocol::Tls => alpn == b"dot",
+            DesignatedProtocol::Quic => alpn == b"doq",
+        }
+    }
+
+    /// Returns the port used if the designation doesn’t give one.
+    pub fn default_port(self) -> u16 {
+        match self {
+            DesignatedProtocol::Https => 443,
+            DesignatedProtocol::Tls | DesignatedProtocol::Quic => 853,
+        }
+    }
+}
+
+//------------ Designation ---------------------------------------------------
+
+/// An encrypted resolver designated by an unencrypted resolver.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Designation {
+    /// The address of the unencrypted resolver that made the designation.
+    unencrypted: IpAddr,
+
+    /// The priority of the designation.
+    priority: u16,
+
+    /// The name of the designated resolver.
+    target: Dname<Vec<u8>>,
+
+    /// The port of the designated resolver if given.
+    port: Option<u16>,
+
+    /// The addresses of the designated resolver.
+    addrs: Vec<IpAddr>,
+
+    /// The ALPN protocol IDs supported by the designated resolver.
+    alpn: Vec<Vec<u8>>,
+
+    /// The URI template for DNS-over-HTTPS if given.
+    dohpath: Option<String>,
+}
+
+impl Designation {
+    /// Creates a designation from a service record.
+    fn new(
+        unencrypted: IpAddr,
+        record: ServiceRecord,
+        addrs: Vec<IpAddr>,
+    ) -> Self {
+        Designation {
+            unencrypted,
+            priority: record.priority,
+            target: record.target,
+            port: record.port,
+            addrs,
+            alpn: record.alpn,
+            dohpath: record.dohpath,
+        }
+    }
+
+    /// Returns the address of the unencrypted resolver.
+    pub fn unencrypted_addr(&self) -> IpAddr {
+        self.unencrypted
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// Returns the name of the designated resolver.
+    ///
+    /// This is the name to use for server name indication.
+    pub fn target(&self) -> &Dname<Vec<u8>> {
+        &self.target
+    }
+
+    /// Returns the addresses of the designated resolver.
+    pub fn addrs(&self) -> &[IpAddr] {
+        &self.addrs
+    }
+
+    /// Returns the port to use for the given protocol.
+    pub fn port(&self, protocol: DesignatedProtocol) -> u16 {
+        self.port.unwrap_or_else(|| protocol.default_port())
+    }
+
+    /// Returns the ALPN protocol IDs supported by the designated resolver.
+    pub fn alpn(&self) -> &[Vec<u8>] {
+        &self.alpn
+    }
+
+    /// Returns the URI template for DNS-over-HTTPS if present.
+    pub fn dohpath(&self) -> Option<&str> {
+        self.dohpath.as_deref()
+    }
+
+    /// Returns whether the designated resolver supports a protocol.
+    ///
+    /// DNS-over-HTTPS is only supported if the designation also contains
+    /// a URI template.
+    pub fn supports(&self, protocol: DesignatedProtocol) -> bool {
+        if protocol == DesignatedProtocol::Https && self.dohpath.is_none() {
+            return false;
+        }
+        self.alpn.iter().any(|id| protocol.matches(id))
+    }
+
+    /// Returns whether the designated resolver uses the same address.
+    ///
+    /// If the unencrypted resolver’s address is one of the addresses of
+    /// the designated resolver, its certificate can be verified by simply
+    /// connecting to the unencrypted resolver’s address and checking the
+    /// certificate for it.
+    pub fn shares_addr(&self) -> bool {
+        self.addrs.contains(&self.unencrypted)
+    }
+
+    /// Returns the authentication of the designated resolver.
+    ///
+    /// This authenticates the server by the name of the designated
+    /// resolver. Note that for verifying the designation, the certificate
+    /// needs to be valid for the unencrypted resolver’s address, too.
+    pub fn server_auth(&self) -> ServerAuth {
+        let mut name = self.target.to_string();
+        if name.ends_with('.') {
+            name.pop();
+        }
+        ServerAuth::by_name(name)
+    }
+
+    /// Returns the URL for reaching the designated resolver via HTTPS.
+    ///
+    /// The URL uses the unencrypted resolver’s address as its host, so that
+    /// an HTTP client verifying the server’s certificate for the host of
+    /// the URL also verifies the designation. Consequently, a URL is only
+    /// returned if the designated resolver supports DNS-over-HTTPS and
+    /// [shares the address][Self::shares_addr] of the unencrypted
+    /// resolver. The variables of the URI template are removed.
+    pub fn doh_url(&self) -> Option<String> {
+        if !self.supports(DesignatedProtocol::Https) || !self.shares_addr() {
+            return None;
+        }
+        let path = self.dohpath.as_deref()?;
+        let path = match path.find('{') {
+            Some(pos) => &path[..pos],
+            None => path,
+        };
+        let mut res = String::from("https://");
+        let port = self.port(DesignatedProtocol::Https);
+        match self.unencrypted {
+            IpAddr::V4(addr) => write!(res, "{}:{}", addr, port),
+            IpAddr::V6(addr) => write!(res, "[{}]:{}", addr, port),
+        }
+        .expect("writing to a string failed");
+        if !path.starts_with('/') {
+            res.push('/');
+        }
+        res.push_str(path);
+        Some(res)
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the addresses of the servers reached without encryption.
+fn unencrypted_servers(resolver: &StubResolver) -> Vec<IpAddr> {
+    let mut res = Vec::new();
+    for info in resolver
+        .preferred
+        .servers
+        .iter()
+        .chain(resolver.stream.servers.iter())
+    {
+        let addr = info.conf.addr.ip();
+        if resolver.transport.privacy(&info.conf) == Privacy::Clear
+            && !res.contains(&addr)
+        {
+            res.push(addr)
+        }
+    }
+    res
+}
+
+/// Creates a resolver that only asks the servers with the given address.
+fn server_resolver(resolver: &StubResolver, addr: IpAddr) -> StubResolver {
+    let mut conf = ResolvConf::new();
+    conf.options = resolver.options.clone();
+    conf.servers = resolver
+        .preferred
+        .servers
+        .iter()
+        .chain(resolver.stream.servers.iter())
+        .filter(|info| info.conf.addr.ip() == addr)
+        .map(|info| info.conf.clone())
+        .collect();
+    StubResolver::from_conf_with_transport(conf, resolver.transport.clone())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message::Message;
+    use crate::base::MessageBuilder;
+    use crate::rdata::svcb::SvcParams;
+    use crate::rdata::Svcb;
+    use crate::resolv::stub::transport::doh::HttpRequest;
+    use crate::resolv::stub::transport::{Request, TransportFuture};
+    use core::str::FromStr;
+    use std::boxed::Box;
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+
+    /// A transport answering DDR queries with a fixed designation.
+    struct DdrTransport;
+
+    impl Transport for DdrTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                let query = request.message();
+                let mut msg = MessageBuilder::new_bytes()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap();
+                let question = query.sole_question().unwrap();
+                if question.qtype() == Rtype::Svcb {
+                    let peer = match request.peer().ip() {
+                        IpAddr::V4(addr) => addr,
+                        _ => unreachable!(),
+                    };
+                    let params =
+                        SvcParams::<Vec<u8>>::from_values(|params| {
+                            params.alpn(&[b"h2", b"dot"]).unwrap();
+                            params.ipv4hint([peer]).unwrap();
+                            params.dohpath("/dns-query{?dns}").unwrap();
+                            Ok(())
+                        })
+                        .unwrap();
+                    msg.push((
+                        question.qname(),
+                        3600,
+                        Svcb::new(
+                            1,
+                            Dname::<Vec<u8>>::from_str("dns.example.net")
+                                .unwrap(),
+                            params,
+                        )
+                        .unwrap(),
+                    ))
+                    .unwrap();
+                }
+                Ok(msg.into_message().into_octets())
+            })
+        }
+    }
+
+    fn resolver(addrs: &[&str]) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        for addr in addrs {
+            conf.servers.push(ServerConf::new(
+                SocketAddr::new(addr.parse().unwrap(), 53),
+                Protocol::Udp,
+            ));
+        }
+        conf.finalize();
+        StubResolver::from_conf_with_transport(conf, DdrTransport)
+    }
+
+    #[tokio::test]
+    async fn discover_and_upgrade() {
+        let resolver = resolver(&["192.0.2.1", "192.0.2.2"]);
+        let found = discover(&resolver).await;
+        assert_eq!(found.len(), 2);
+        let designation = &found[1];
+        assert_eq!(
+            designation.unencrypted_addr(),
+            IpAddr::from(Ipv4Addr::new(192, 0, 2, 2))
+        );
+        assert_eq!(designation.target().to_string(), "dns.example.net");
+        assert!(designation.supports(DesignatedProtocol::Https));
+        assert!(designation.supports(DesignatedProtocol::Tls));
+        assert!(!designation.supports(DesignatedProtocol::Quic));
+        assert_eq!(designation.port(DesignatedProtocol::Tls), 853);
+        assert_eq!(
+            designation.server_auth(),
+            ServerAuth::by_name("dns.example.net")
+        );
+        assert_eq!(
+            designation.doh_url().unwrap(),
+            "https://192.0.2.2:443/dns-query"
+        );
+
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let client = {
+            let urls = urls.clone();
+            move |request: HttpRequest| {
+                urls.lock().unwrap().push(String::from(request.url()));
+                let query = Message::from_octets(request.body().to_vec());
+                async move {
+                    Ok(MessageBuilder::new_vec()
+                        .start_answer(&query.unwrap(), Rcode::NoError)
+                        .unwrap()
+                        .finish())
+                }
+            }
+        };
+        let (upgraded, designation) = upgrade(resolver, client).await;
+        assert_eq!(
+            designation.unwrap().unencrypted_addr(),
+            IpAddr::from(Ipv4Addr::new(192, 0, 2, 1))
+        );
+        upgraded
+            .query((
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            *urls.lock().unwrap(),
+            ["https://192.0.2.1:443/dns-query"]
+        );
+    }
+
+    #[test]
+    fn unverifiable() {
+        let record = ServiceRecord {
+            priority: 1,
+            target: Dname::from_str("dns.example.net").unwrap(),
+            port: Some(8443),
+            alpn: vec![b"h2".to_vec()],
+            no_default_alpn: false,
+            hints: Vec::new(),
+            ech: None,
+            dohpath: Some("/q{?dns}".into()),
+        };
+        let unencrypted = IpAddr::from(Ipv4Addr::new(192, 0, 2, 1));
+        let other = Designation::new(
+            unencrypted,
+            record.clone(),
+            vec![IpAddr::from(Ipv4Addr::new(198, 51, 100, 1))],
+        );
+        assert!(other.supports(DesignatedProtocol::Https));
+        assert!(other.doh_url().is_none());
+        let same = Designation::new(
+            "2001:db8::1".parse().unwrap(),
+            record,
+            vec!["2001:db8::1".parse().unwrap()],
+        );
+        assert_eq!(same.doh_url().unwrap(), "https://[2001:db8::1]:8443/q");
+    }
+}
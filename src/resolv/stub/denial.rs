@@ -0,0 +1,754 @@
+//! Aggressive use of cached denial of existence.
+//!
+//! A validating resolver can use the NSEC and NSEC3 records it has
+//! validated as part of negative answers to answer later queries for other
+//! names without asking upstream, as described in [RFC 8198]. An NSEC
+//! record proves that no names exist between its owner and its next name,
+//! so a query for any name in between can be answered with NXDOMAIN if the
+//! wildcard at the name’s closest encloser is proven not to exist, too.
+//! NSEC3 records do the same for ranges of hashed names.
+//!
+//! The [`DenialCache`] keeps the validated records of each zone ordered by
+//! owner name or hash, so the record covering a name is found by looking
+//! for its predecessor. A synthesized answer contains the zone’s SOA
+//! record and the NSEC or NSEC3 records the denial rests on, together with
+//! their signatures. Records are kept for the smaller of their TTL and the
+//! negative TTL given by the SOA record. Denials relying on opt-out NSEC3
+//! records are never synthesized.
+//!
+//! [RFC 8198]: https://tools.ietf.org/html/rfc8198
+
+use crate::base::iana::{Nsec3HashAlg, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{AuthorityBuilder, MessageBuilder};
+use crate::base::name::{Dname, ToDname};
+use crate::base::rdata::ComposeRecordData;
+use crate::base::record::Record;
+use crate::base::Ttl;
+use crate::crypto::{Backend, Nsec3Hasher};
+use crate::rdata::{Nsec, Nsec3, Rrsig, Soa, ZoneRecordData};
+use crate::utils::base32;
+use crate::validate::denial::{
+    closest_encloser, wildcard, Denial, DenialError, Nsec3Denial, NsecDenial,
+};
+use bytes::{Bytes, BytesMut};
+use octseq::octets::Octets;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+use std::vec::Vec;
+
+//------------ SignedRrset ---------------------------------------------------
+
+pub(super) type Name = Dname<Bytes>;
+pub(super) type Data = ZoneRecordData<Bytes, Name>;
+
+/// A validated RRset together with its signatures.
+pub(super) struct SignedRrset {
+    pub records: Vec<Record<Name, Data>>,
+    pub rrsigs: Vec<Rrsig<Bytes, Name>>,
+}
+
+impl SignedRrset {
+    /// Returns whether all signatures were made by the given zone.
+    fn is_signed_by(&self, zone: &Name) -> bool {
+        !self.rrsigs.is_empty()
+            && self
+                .rrsigs
+                .iter()
+                .all(|rrsig| rrsig.signer_name().name_eq(zone))
+    }
+}
+
+//------------ DenialCache ---------------------------------------------------
+
+/// The validated NSEC and NSEC3 records of a number of zones.
+pub(super) struct DenialCache {
+    /// The maximum number of NSEC and NSEC3 records kept.
+    capacity: usize,
+
+    /// The records by zone apex.
+    zones: Mutex<HashMap<Name, Zone>>,
+}
+
+impl DenialCache {
+    /// Creates a new, empty cache keeping at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        DenialCache {
+            capacity,
+            zones: Default::default(),
+        }
+    }
+
+    /// Removes all records.
+    pub fn clear(&self) {
+        self.zones.lock().unwrap().clear()
+    }
+
+    /// Adds the records of the authority section of a validated answer.
+    ///
+    /// The RRsets must include the SOA RRset of the zone. Only NSEC and
+    /// NSEC3 RRsets signed by that zone are added. If the cache is full
+    /// even after removing expired records, no records are added.
+    pub fn insert(&self, rrsets: &[SignedRrset], now: Instant) {
+        let soa = rrsets.iter().find_map(|rrset| {
+            let record = rrset.records.first()?;
+            match record.data() {
+                ZoneRecordData::Soa(soa)
+                    if rrset.is_signed_by(record.owner()) =>
+                {
+                    Some((
+                        Record::new(
+                            record.owner().clone(),
+                            record.class(),
+                            record.ttl(),
+                            soa.clone(),
+                        ),
+                        rrset.rrsigs.clone(),
+                    ))
+                }
+                _ => None,
+            }
+        });
+        let (soa, soa_rrsigs) = match soa {
+            Some(soa) => soa,
+            None => return,
+        };
+        let apex = soa.owner().clone();
+        let max_ttl = soa.ttl().min(soa.data().minimum());
+        let expires = |ttl: Ttl| now + ttl.min(max_ttl).into_duration();
+
+        let mut zones = self.zones.lock().unwrap();
+        if len(&zones) >= self.capacity {
+            purge(&mut zones, now);
+        }
+        let mut room = self.capacity.saturating_sub(len(&zones));
+        if room == 0 {
+            return;
+        }
+        let soa = Signed {
+            expires: expires(soa.ttl()),
+            record: soa,
+            rrsigs: soa_rrsigs,
+        };
+        let zone = zones
+            .entry(apex.clone())
+            .and_modify(|zone| zone.soa = soa.clone())
+            .or_insert_with(|| Zone::new(soa));
+
+        for rrset in rrsets {
+            if !rrset.is_signed_by(&apex) {
+                continue;
+            }
+            for record in &rrset.records {
+                if room == 0 {
+                    return;
+                }
+                let stored = match record.data() {
+                    ZoneRecordData::Nsec(nsec) => {
+                        zone.insert_nsec(Signed {
+                            record: Record::new(
+                                record.owner().clone(),
+                                record.class(),
+                                record.ttl(),
+                                nsec.clone(),
+                            ),
+                            rrsigs: rrset.rrsigs.clone(),
+                            expires: expires(record.ttl()),
+                        });
+                        true
+                    }
+                    ZoneRecordData::Nsec3(nsec3) => {
+                        zone.insert_nsec3(Signed {
+                            record: Record::new(
+                                record.owner().clone(),
+                                record.class(),
+                                record.ttl(),
+                                nsec3.clone(),
+                            ),
+                            rrsigs: rrset.rrsigs.clone(),
+                            expires: expires(record.ttl()),
+                        })
+                    }
+                    _ => false,
+                };
+                if stored {
+                    room -= 1;
+                }
+            }
+        }
+    }
+
+    /// Synthesizes a negative answer to a query from the cached records.
+    ///
+    /// Returns `None` if the cached records don’t prove that the queried
+    /// name or type doesn’t exist. Hashes for NSEC3 are calculated using
+    /// `backend`.
+    pub fn synthesize<Octs: Octets + ?Sized>(
+        &self,
+        query: &Message<Octs>,
+        backend: &dyn Backend,
+        now: Instant,
+    ) -> Option<Message<Bytes>> {
+        let question = query.first_question()?;
+        let qname = question.qname().to_bytes();
+        let qtype = question.qtype();
+        let zones = self.zones.lock().unwrap();
+        let zone = qname
+            .iter_suffixes()
+            .find_map(|name| zones.get(&name.to_bytes()))?;
+        if zone.soa.expires <= now
+            || zone.soa.record.class() != question.qclass()
+        {
+            return None;
+        }
+        if let Some((rcode, proof)) = zone.nsec_proof(&qname, qtype, now) {
+            return respond(query, rcode, &zone.soa, &proof, now);
+        }
+        if let Some((rcode, proof)) =
+            zone.nsec3_proof(&qname, qtype, backend, now)
+        {
+            return respond(query, rcode, &zone.soa, &proof, now);
+        }
+        None
+    }
+}
+
+//------------ Zone ----------------------------------------------------------
+
+/// The cached records of a zone.
+struct Zone {
+    /// The SOA record of the zone.
+    soa: Signed<Soa<Name>>,
+
+    /// The NSEC records ordered by owner name.
+    nsecs: BTreeMap<Name, Signed<Nsec<Bytes, Name>>>,
+
+    /// The parameters of the NSEC3 records.
+    nsec3_params: Option<Nsec3Params>,
+
+    /// The NSEC3 records ordered by the hash in their owner name.
+    nsec3s: BTreeMap<Vec<u8>, Signed<Nsec3<Bytes>>>,
+}
+
+/// The hash algorithm, iterations, and salt of NSEC3 records.
+type Nsec3Params = (Nsec3HashAlg, u16, Vec<u8>);
+
+/// The rcode of a negative answer and the records proving it.
+type Proof<'a, T> = (Rcode, Vec<&'a Signed<T>>);
+
+impl Zone {
+    fn new(soa: Signed<Soa<Name>>) -> Self {
+        Zone {
+            soa,
+            nsecs: BTreeMap::new(),
+            nsec3_params: None,
+            nsec3s: BTreeMap::new(),
+        }
+    }
+
+    fn apex(&self) -> &Name {
+        self.soa.record.owner()
+    }
+
+    fn len(&self) -> usize {
+        self.nsecs.len() + self.nsec3s.len()
+    }
+
+    fn insert_nsec(&mut self, nsec: Signed<Nsec<Bytes, Name>>) {
+        self.nsecs.insert(nsec.record.owner().clone(), nsec);
+    }
+
+    /// Adds an NSEC3 record.
+    ///
+    /// If the record uses different parameters than the records already
+    /// present, the zone has been re-signed and those are dropped. Returns
+    /// whether the record was added.
+    fn insert_nsec3(&mut self, nsec3: Signed<Nsec3<Bytes>>) -> bool {
+        let hash = {
+            let owner = nsec3.record.owner();
+            let (label, parent) = match owner.split_first() {
+                Some(split) => split,
+                None => return false,
+            };
+            if !parent.name_eq(self.apex()) {
+                return false;
+            }
+            let label = match core::str::from_utf8(label.as_slice()) {
+                Ok(label) => label,
+                Err(_) => return false,
+            };
+            match base32::decode_hex::<Vec<u8>>(label) {
+                Ok(hash) => hash,
+                Err(_) => return false,
+            }
+        };
+        let data = nsec3.record.data();
+        let params = (
+            data.hash_algorithm(),
+            data.iterations(),
+            data.salt().as_slice().to_vec(),
+        );
+        if self.nsec3_params.as_ref() != Some(&params) {
+            self.nsec3s.clear();
+            self.nsec3_params = Some(params);
+        }
+        self.nsec3s.insert(hash, nsec3);
+        true
+    }
+
+    /// Removes all expired records.
+    fn purge(&mut self, now: Instant) {
+        self.nsecs.retain(|_, nsec| nsec.expires > now);
+        self.nsec3s.retain(|_, nsec3| nsec3.expires > now);
+    }
+
+    /// Tries to prove a negative answer via NSEC records.
+    ///
+    /// Returns the rcode of the answer and the records of the proof.
+    fn nsec_proof(
+        &self,
+        qname: &Name,
+        qtype: Rtype,
+        now: Instant,
+    ) -> Option<Proof<'_, Nsec<Bytes, Name>>> {
+        let matching = |name: &Name| {
+            self.nsecs.get(name).filter(|nsec| nsec.expires > now)
+        };
+        let covering = |name: &Name| {
+            self.nsecs
+                .range(..name)
+                .next_back()
+                .map(|(_, nsec)| nsec)
+                .filter(|nsec| nsec.expires > now)
+        };
+
+        let mut proof = Vec::new();
+        if let Some(nsec) = matching(qname) {
+            proof.push(nsec);
+        } else if let Some(nsec) = covering(qname) {
+            // Names below a delegation or a DNAME are not part of the
+            // zone even if they are covered by the record.
+            let owner = nsec.record.owner();
+            let types = nsec.record.data().types();
+            if qname.ends_with(owner)
+                && (types.contains(Rtype::Dname)
+                    || (types.contains(Rtype::Ns)
+                        && !types.contains(Rtype::Soa)))
+            {
+                return None;
+            }
+            proof.push(nsec);
+            let wildcard = wildcard(&closest_encloser(
+                &qname.to_vec(),
+                owner,
+                nsec.record.data().next_name(),
+            ))
+            .to_bytes();
+            if let Some(nsec) =
+                matching(&wildcard).or_else(|| covering(&wildcard))
+            {
+                if !proof.iter().any(|item| core::ptr::eq(*item, nsec)) {
+                    proof.push(nsec)
+                }
+            }
+        } else {
+            return None;
+        }
+
+        let records: Vec<_> =
+            proof.iter().map(|nsec| nsec.record.clone()).collect();
+        let denial = NsecDenial::new(&records);
+        let rcode = negative_rcode(denial.nxdomain(qname), || {
+            denial.nodata(qname, qtype)
+        })?;
+        Some((rcode, proof))
+    }
+
+    /// Tries to prove a negative answer via NSEC3 records.
+    ///
+    /// Returns the rcode of the answer and the records of the proof.
+    fn nsec3_proof(
+        &self,
+        qname: &Name,
+        qtype: Rtype,
+        backend: &dyn Backend,
+        now: Instant,
+    ) -> Option<Proof<'_, Nsec3<Bytes>>> {
+        let (algorithm, iterations, salt) = self.nsec3_params.as_ref()?;
+        let hasher = Nsec3Hasher::with_backend(backend);
+
+        // Collect the records matching or covering the hashes of all the
+        // possible closest encloser, next closer, and wildcard names.
+        let mut proof: Vec<&Signed<Nsec3<Bytes>>> = Vec::new();
+        for name in qname.iter_suffixes() {
+            let name = name.to_vec();
+            for name in [wildcard(&name), name.clone()] {
+                let hash =
+                    hasher.hash(&name, *algorithm, *iterations, salt).ok()?;
+                let nsec3 = match self.nsec3s.get(&hash) {
+                    Some(nsec3) => nsec3,
+                    None => match self
+                        .nsec3s
+                        .range(..hash)
+                        .next_back()
+                        .or_else(|| self.nsec3s.iter().next_back())
+                    {
+                        Some((_, nsec3)) => nsec3,
+                        None => return None,
+                    },
+                };
+                if nsec3.expires > now
+                    && !proof.iter().any(|item| core::ptr::eq(*item, nsec3))
+                {
+                    proof.push(nsec3)
+                }
+            }
+            if name.name_eq(self.apex()) {
+                break;
+            }
+        }
+
+        let records: Vec<_> =
+            proof.iter().map(|nsec3| nsec3.record.clone()).collect();
+        let denial =
+            Nsec3Denial::with_backend(self.apex(), &records, backend);
+        let rcode = negative_rcode(denial.nxdomain(qname), || {
+            denial.nodata(qname, qtype)
+        })?;
+        Some((rcode, proof))
+    }
+}
+
+//------------ Signed --------------------------------------------------------
+
+/// A cached record together with its signatures.
+#[derive(Clone)]
+struct Signed<T> {
+    record: Record<Name, T>,
+    rrsigs: Vec<Rrsig<Bytes, Name>>,
+
+    /// When the record expires.
+    expires: Instant,
+}
+
+impl<T: Clone + ComposeRecordData> Signed<T> {
+    /// Appends the record and its signatures with the given TTL.
+    fn push(
+        &self,
+        builder: &mut AuthorityBuilder<BytesMut>,
+        ttl: Ttl,
+    ) -> Option<()> {
+        let owner = self.record.owner();
+        let class = self.record.class();
+        builder
+            .push(Record::new(owner, class, ttl, self.record.data()))
+            .ok()?;
+        for rrsig in &self.rrsigs {
+            builder.push(Record::new(owner, class, ttl, rrsig)).ok()?;
+        }
+        Some(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the number of records in all zones.
+fn len(zones: &HashMap<Name, Zone>) -> usize {
+    zones.values().map(Zone::len).sum()
+}
+
+/// Removes all expired records and zones.
+fn purge(zones: &mut HashMap<Name, Zone>, now: Instant) {
+    zones.retain(|_, zone| {
+        zone.purge(now);
+        zone.soa.expires > now && zone.len() > 0
+    });
+}
+
+/// Returns the rcode for a proven NXDOMAIN or NODATA denial.
+///
+/// Denials via opt-out records are not proof enough.
+fn negative_rcode(
+    nxdomain: Result<Denial, DenialError>,
+    nodata: impl FnOnce() -> Result<Denial, DenialError>,
+) -> Option<Rcode> {
+    if nxdomain == Ok(Denial::Proven) {
+        Some(Rcode::NXDomain)
+    } else if nodata() == Ok(Denial::Proven) {
+        Some(Rcode::NoError)
+    } else {
+        None
+    }
+}
+
+/// Creates the answer to a query with the records of a denial.
+///
+/// The TTL of each record is the time until it expires. The SOA record
+/// can’t outlive the denial and gets the smallest TTL of all records.
+fn respond<Octs: Octets + ?Sized, T: Clone + ComposeRecordData>(
+    query: &Message<Octs>,
+    rcode: Rcode,
+    soa: &Signed<Soa<Name>>,
+    proof: &[&Signed<T>],
+    now: Instant,
+) -> Option<Message<Bytes>> {
+    let ttl = |expires: Instant| {
+        Ttl::from_duration_lossy(expires.saturating_duration_since(now))
+    };
+    let soa_ttl = proof
+        .iter()
+        .map(|item| item.expires)
+        .fold(soa.expires, Instant::min);
+
+    let mut answer = MessageBuilder::new_bytes()
+        .start_answer(query, rcode)
+        .ok()?;
+    answer.header_mut().set_ra(true);
+    let mut authority = answer.authority();
+    soa.push(&mut authority, ttl(soa_ttl))?;
+    for item in proof {
+        item.push(&mut authority, ttl(item.expires))?;
+    }
+    Some(authority.into_message())
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, SecAlg};
+    use crate::base::rdata::RecordData;
+    use crate::base::serial::Serial;
+    use crate::crypto::RingBackend;
+    use crate::rdata::dnssec::RtypeBitmap;
+    use crate::rdata::nsec3::{Nsec3Salt, OwnerHash};
+    use core::str::FromStr;
+    use std::time::Duration;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn rrset(owner: &str, ttl: u32, data: Data) -> SignedRrset {
+        let rtype = data.rtype();
+        SignedRrset {
+            records: vec![Record::new(
+                name(owner),
+                Class::In,
+                Ttl::from_secs(ttl),
+                data,
+            )],
+            rrsigs: vec![Rrsig::new(
+                rtype,
+                SecAlg::Ed25519,
+                name(owner).rrsig_label_count(),
+                Ttl::from_secs(ttl),
+                Serial(0),
+                Serial(0),
+                0,
+                name("example."),
+                Bytes::new(),
+            )
+            .unwrap()],
+        }
+    }
+
+    fn types(types: &[Rtype]) -> RtypeBitmap<Bytes> {
+        let mut bitmap = RtypeBitmap::<Bytes>::builder();
+        for rtype in types {
+            bitmap.add(*rtype).unwrap();
+        }
+        bitmap.finalize()
+    }
+
+    fn nsec(owner: &str, next: &str, rtypes: &[Rtype]) -> SignedRrset {
+        rrset(owner, 3600, Nsec::new(name(next), types(rtypes)).into())
+    }
+
+    /// Creates the NSEC3 chain for the given names and types.
+    fn nsec3s(names: &[(&str, &[Rtype])], opt_out: bool) -> Vec<SignedRrset> {
+        let hasher = Nsec3Hasher::new();
+        let mut hashes: Vec<_> = names
+            .iter()
+            .map(|(owner, rtypes)| {
+                let hash = hasher
+                    .hash(&name(owner), Nsec3HashAlg::Sha1, 0, &[])
+                    .unwrap();
+                (hash, *rtypes)
+            })
+            .collect();
+        hashes.sort();
+        hashes
+            .iter()
+            .enumerate()
+            .map(|(i, (hash, rtypes))| {
+                let next = hashes[(i + 1) % hashes.len()].0.clone();
+                rrset(
+                    &format!("{}.example.", base32::encode_string_hex(hash)),
+                    3600,
+                    Nsec3::new(
+                        Nsec3HashAlg::Sha1,
+                        u8::from(opt_out),
+                        0,
+                        Nsec3Salt::from_octets(Bytes::new()).unwrap(),
+                        OwnerHash::from_octets(Bytes::from(next)).unwrap(),
+                        types(rtypes),
+                    )
+                    .into(),
+                )
+            })
+            .collect()
+    }
+
+    fn soa(minimum: u32) -> SignedRrset {
+        let ns = name("ns.example.");
+        rrset(
+            "example.",
+            3600,
+            Soa::new(
+                ns.clone(),
+                ns,
+                1.into(),
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::from_secs(minimum),
+            )
+            .into(),
+        )
+    }
+
+    fn query(qname: &str, qtype: Rtype) -> Message<Bytes> {
+        let mut msg = MessageBuilder::new_bytes().question();
+        msg.push((name(qname), qtype)).unwrap();
+        msg.into_message()
+    }
+
+    fn rcode(
+        cache: &DenialCache,
+        qname: &str,
+        qtype: Rtype,
+        now: Instant,
+    ) -> Option<Rcode> {
+        cache
+            .synthesize(&query(qname, qtype), &RingBackend, now)
+            .map(|answer| answer.header().rcode())
+    }
+
+    #[test]
+    fn synthesize_nsec() {
+        let cache = DenialCache::new(16);
+        let now = Instant::now();
+        cache.insert(
+            &[
+                soa(300),
+                nsec("example.", "b.example.", &[Rtype::Soa, Rtype::Ns]),
+                nsec("b.example.", "d.example.", &[Rtype::A]),
+                nsec("d.example.", "x.y.example.", &[Rtype::Ns]),
+                nsec("x.y.example.", "example.", &[Rtype::A]),
+            ],
+            now,
+        );
+
+        assert_eq!(
+            rcode(&cache, "c.example.", Rtype::A, now),
+            Some(Rcode::NXDomain)
+        );
+        assert_eq!(
+            rcode(&cache, "b.example.", Rtype::Aaaa, now),
+            Some(Rcode::NoError)
+        );
+        assert_eq!(rcode(&cache, "b.example.", Rtype::A, now), None);
+
+        // An empty non-terminal.
+        assert_eq!(
+            rcode(&cache, "y.example.", Rtype::A, now),
+            Some(Rcode::NoError)
+        );
+
+        // Below a delegation.
+        assert_eq!(rcode(&cache, "c.d.example.", Rtype::A, now), None);
+
+        // The answer carries the proof with TTLs capped by the SOA minimum.
+        let answer = cache
+            .synthesize(&query("c.example.", Rtype::A), &RingBackend, now)
+            .unwrap();
+        assert_eq!(answer.header_counts().ancount(), 0);
+        let authority = answer.authority().unwrap();
+        let records = authority
+            .limit_to::<ZoneRecordData<_, _>>()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert!(records
+            .iter()
+            .all(|record| record.ttl() == Ttl::from_secs(300)));
+        assert_eq!(
+            records
+                .iter()
+                .filter(|record| record.rtype() == Rtype::Nsec)
+                .count(),
+            2
+        );
+
+        // Expired records are not used.
+        let later = now + Duration::from_secs(301);
+        assert_eq!(rcode(&cache, "c.example.", Rtype::A, later), None);
+    }
+
+    #[test]
+    fn synthesize_nsec3() {
+        const NAMES: &[(&str, &[Rtype])] = &[
+            ("example.", &[Rtype::Soa, Rtype::Ns]),
+            ("a.example.", &[Rtype::A]),
+        ];
+        let now = Instant::now();
+        let cache = DenialCache::new(16);
+        let mut rrsets = nsec3s(NAMES, false);
+        rrsets.push(soa(300));
+        cache.insert(&rrsets, now);
+        assert_eq!(
+            rcode(&cache, "b.example.", Rtype::A, now),
+            Some(Rcode::NXDomain)
+        );
+        assert_eq!(
+            rcode(&cache, "a.example.", Rtype::Aaaa, now),
+            Some(Rcode::NoError)
+        );
+        assert_eq!(rcode(&cache, "a.example.", Rtype::A, now), None);
+
+        // Opt-out records don’t prove anything.
+        let cache = DenialCache::new(16);
+        let mut rrsets = nsec3s(NAMES, true);
+        rrsets.push(soa(300));
+        cache.insert(&rrsets, now);
+        assert_eq!(rcode(&cache, "b.example.", Rtype::A, now), None);
+    }
+
+    #[test]
+    fn missing_wildcard_denial() {
+        let cache = DenialCache::new(16);
+        let now = Instant::now();
+        cache.insert(
+            &[soa(300), nsec("b.example.", "d.example.", &[Rtype::A])],
+            now,
+        );
+        assert_eq!(rcode(&cache, "c.example.", Rtype::A, now), None);
+    }
+
+    #[test]
+    fn capacity() {
+        let cache = DenialCache::new(1);
+        let now = Instant::now();
+        cache.insert(
+            &[
+                soa(300),
+                nsec("example.", "b.example.", &[Rtype::Soa, Rtype::Ns]),
+                nsec("b.example.", "example.", &[Rtype::A]),
+            ],
+            now,
+        );
+        assert_eq!(rcode(&cache, "c.example.", Rtype::A, now), None);
+    }
+}
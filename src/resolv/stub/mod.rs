@@ -8,46 +8,113 @@
 //!
 //! The main type is [`StubResolver`] that implements the [`Resolver`] trait
 //! and thus can be used with the various lookup functions.
+//!
+//! Answers can be kept in a [`Cache`] attached to the resolver via
+//! [`StubResolver::with_cache`]. Concurrent queries for the same question
+//! are sent upstream only once, with all of them receiving the answer to
+//! that single query.
+//!
+//! A resolver configured with unencrypted servers can discover encrypted
+//! resolvers designated by them and switch over to DNS-over-HTTPS using the
+//! [`ddr`] module.
+//!
+//! To avoid losing responses to IP fragmentation, the resolver advertises
+//! a UDP payload size of 1232 octets by default and lowers it for servers
+//! whose requests keep timing out, probing for larger sizes again later.
+//! This can be switched off via the `probe_payload_size` option.
+//!
+//! With the `validate` feature, the resolver can validate the answers it
+//! receives itself using DNSSEC. See the [`validator`] module for details.
+//!
+//! With the `tracing` feature, each resolution of a query runs inside a
+//! `resolution` span of the [tracing] crate carrying the resolution’s
+//! [`ResolutionId`], query name, and query type. Its children are a
+//! `cache_lookup` span for looking up the answer in the cache, an
+//! `upstream` span for each request sent to a server, and a `validation`
+//! span for validating the answer. Within the latter, each zone the chain
+//! of trust passes through gets a `chain_step` span. The DNSKEY and DS
+//! answers needed for validation are resolutions of their own inside the
+//! `validation` span.
+//! All spans are created at the debug level.
+//!
+//! With the `zonetree` feature, the resolver can rewrite its answers
+//! according to response policy zones attached via
+//! [`StubResolver::with_rpz`].
+//!
+//! With the `qname_minimisation` option, the resolver performs QNAME
+//! minimisation as described in [RFC 9156] when it has to ask a server:
+//! it first queries for the ancestors of the query name, adding labels
+//! step by step, before sending the actual query. Since some servers
+//! wrongly answer NXDOMAIN for empty non-terminals, the resolver falls
+//! back to the full query name if an ancestor doesn’t exist or cannot be
+//! resolved.
+//!
+//! [RFC 9156]: https://tools.ietf.org/html/rfc9156
+//! [tracing]: https://docs.rs/tracing/
 
+use self::cache::{Cache, Freshness};
+use self::coalesce::InFlightQueries;
 use self::conf::{
-    ResolvConf, ResolvOptions, SearchSuffix, ServerConf, Transport,
+    PrivacyProfile, ResolvConf, ResolvOptions, SearchSuffix, ServerConf,
 };
-use crate::base::iana::Rcode;
+use self::payload::PayloadSize;
+use self::retry::{FixedRetry, RetryPolicy};
+use self::transport::{NetTransport, Privacy, Request, TcpPool, Transport};
+#[cfg(feature = "validate")]
+use self::validator::Validator;
+use crate::base::iana::{OptRcode, Rcode, Rtype};
 use crate::base::message::Message;
 use crate::base::message_builder::{
-    AdditionalBuilder, MessageBuilder, StreamTarget,
+    AdditionalBuilder, MessageBuilder, PushError, SliceTarget, StreamTarget,
 };
-use crate::base::name::{ToDname, ToRelativeDname};
+use crate::base::name::{Dname, ToDname, ToRelativeDname};
+use crate::base::opt::padding::BlockPadding;
+use crate::base::opt::{Cookie, ExtendedError, Nsid};
 use crate::base::question::Question;
+use crate::base::wire::Composer;
+use crate::metrics::{Metrics, ResolutionId};
 use crate::resolv::lookup::addr::{lookup_addr, FoundAddrs};
 use crate::resolv::lookup::host::{lookup_host, search_host, FoundHosts};
 use crate::resolv::lookup::srv::{lookup_srv, FoundSrvs, SrvError};
 use crate::resolv::resolver::{Resolver, SearchNames};
+#[cfg(feature = "validate")]
+use crate::validate::chain::SecurityStatus;
+#[cfg(feature = "zonetree")]
+use crate::zonetree::rpz::{Rpz, Verdict};
 use bytes::Bytes;
+use core::cmp::Reverse;
 use octseq::array::Array;
+use octseq::builder::ShortBuf;
+use octseq::octets::Octets;
 use std::boxed::Box;
 use std::future::Future;
-use std::net::{IpAddr, SocketAddr};
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::slice::SliceIndex;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::vec::Vec;
-use std::{io, ops};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpStream, UdpSocket};
+use std::{fmt, io, ops};
 #[cfg(feature = "resolv-sync")]
 use tokio::runtime;
 use tokio::time::timeout;
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
 
 //------------ Sub-modules ---------------------------------------------------
 
+pub mod cache;
 pub mod conf;
+pub mod ddr;
+pub mod retry;
+pub mod transport;
+pub mod validator;
 
-//------------ Module Configuration ------------------------------------------
-
-/// How many times do we try a new random port if we get ‘address in use.’
-const RETRY_RANDOM_PORT: usize = 10;
+mod coalesce;
+#[cfg(feature = "validate")]
+mod denial;
+mod payload;
 
 //------------ StubResolver --------------------------------------------------
 
@@ -65,12 +132,23 @@ const RETRY_RANDOM_PORT: usize = 10;
 /// you can do so simply by using the [`run()`] or [`run_with_conf()`]
 /// associated functions.
 ///
+/// All messages are exchanged with the upstream servers through a
+/// [`Transport`]. Unless you provide your own via
+/// [`from_conf_with_transport()`], the resolver uses [`NetTransport`] which
+/// uses regular UDP and TCP sockets.
+///
+/// All futures returned by the resolver can be dropped at any time. Any
+/// requests they have in flight are abandoned and, if sent over a shared
+/// TCP connection, removed from it. The `lookup_timeout` option makes use
+/// of this to limit the overall time of a lookup.
+///
 /// [`new()`]: #method.new
 /// [`from_conf()`]: #method.from_conf
+/// [`from_conf_with_transport()`]: #method.from_conf_with_transport
 /// [`query()`]: #method.query
 /// [`run()`]: #method.run
 /// [`run_with_conf()`]: #method.run_with_conf
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct StubResolver {
     /// Preferred servers.
     preferred: ServerList,
@@ -80,6 +158,29 @@ pub struct StubResolver {
 
     /// Resolver options.
     options: ResolvOptions,
+
+    /// The transport used for exchanging messages with the servers.
+    transport: Arc<dyn Transport>,
+
+    /// The policy for retrying queries.
+    retry: Arc<dyn RetryPolicy>,
+
+    /// The cache for answers if the resolver caches.
+    cache: Option<Arc<Cache>>,
+
+    /// The queries currently sent upstream.
+    in_flight: Arc<InFlightQueries>,
+
+    /// The validator for answers if the resolver validates.
+    #[cfg(feature = "validate")]
+    validator: Option<Arc<Validator>>,
+
+    /// The metrics to report to if the resolver is instrumented.
+    metrics: Option<Arc<dyn Metrics>>,
+
+    /// The response policy to apply to answers.
+    #[cfg(feature = "zonetree")]
+    rpz: Option<Arc<Rpz>>,
 }
 
 impl StubResolver {
@@ -89,57 +190,544 @@ impl StubResolver {
     }
 
     /// Creates a new resolver using the given configuraiton.
+    ///
+    /// If the `stay_open` option is set in the configuration, the resolver
+    /// will keep TCP connections open and reuse them for later queries.
+    /// If the `use_vc` option is set, too, the resolver doesn’t use UDP at
+    /// all. This is useful on networks where UDP is blocked or fragmented
+    /// responses get lost. In this case, the connections can be opened
+    /// ahead of time via [`connect`][Self::connect].
     pub fn from_conf(conf: ResolvConf) -> Self {
+        let transport = if conf.options.stay_open {
+            NetTransport::with_tcp_pool(TcpPool::new())
+        } else {
+            NetTransport::new()
+        };
+        Self::from_conf_with_transport(conf, transport)
+    }
+
+    /// Creates a new resolver using a configuration and a transport.
+    ///
+    /// All messages to the servers given in `conf` will be exchanged via
+    /// `transport`. Which of the servers are used and in which order
+    /// depends on the privacy the transport offers for them and the
+    /// configured [`PrivacyProfile`].
+    pub fn from_conf_with_transport(
+        conf: ResolvConf,
+        transport: impl Transport + 'static,
+    ) -> Self {
         StubResolver {
-            preferred: ServerList::from_conf(&conf, |s| {
+            preferred: ServerList::from_conf(&conf, &transport, |s| {
                 s.transport.is_preferred()
             }),
-            stream: ServerList::from_conf(&conf, |s| s.transport.is_stream()),
+            stream: ServerList::from_conf(&conf, &transport, |s| {
+                s.transport.is_stream()
+            }),
+            retry: Arc::new(FixedRetry::new(conf.options.attempts)),
             options: conf.options,
+            transport: Arc::new(transport),
+            cache: None,
+            in_flight: Default::default(),
+            #[cfg(feature = "validate")]
+            validator: None,
+            metrics: None,
+            #[cfg(feature = "zonetree")]
+            rpz: None,
         }
     }
 
+    /// Makes the resolver retry queries according to the given policy.
+    ///
+    /// The policy replaces the `attempts` option. See the [`retry`]
+    /// module for details.
+    pub fn with_retry_policy(
+        mut self,
+        policy: impl RetryPolicy + 'static,
+    ) -> Self {
+        self.retry = Arc::new(policy);
+        self
+    }
+
+    /// Makes the resolver keep answers in the given cache.
+    ///
+    /// Queries for a question the cache has an answer for are answered
+    /// from the cache without contacting any server. The cache is shared
+    /// by all clones of the resolver.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Makes the resolver validate answers using the given validator.
+    ///
+    /// This also enables the `dnssec_ok` option so that servers include
+    /// the records necessary for validation.
+    #[cfg(feature = "validate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+    pub fn with_validator(mut self, validator: Validator) -> Self {
+        self.options.dnssec_ok = true;
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Makes the resolver report what it is doing to the given metrics.
+    ///
+    /// The resolver reports the responses and errors of upstream servers,
+    /// cache lookups, and, if it validates, the outcome of validation. The
+    /// metrics are shared by all clones of the resolver.
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Makes the resolver apply the given response policy to its answers.
+    ///
+    /// Queries matching a query name trigger of the policy are answered
+    /// without resolving them. All other answers, including those served
+    /// from the cache, are checked against the remaining triggers. Queries
+    /// dropped by the policy fail with an error. Since answers of the
+    /// resolver aren’t limited in size, TCP-only triggers have no effect.
+    #[cfg(feature = "zonetree")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "zonetree")))]
+    pub fn with_rpz(mut self, rpz: Rpz) -> Self {
+        self.rpz = Some(Arc::new(rpz));
+        self
+    }
+
     pub fn options(&self) -> &ResolvOptions {
         &self.options
     }
 
+    /// Returns the policy deciding how often and when to retry a query.
+    pub fn retry_policy(&self) -> &dyn RetryPolicy {
+        &*self.retry
+    }
+
+    /// Returns the cache if the resolver caches answers.
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_deref()
+    }
+
+    /// Returns the validator if the resolver validates answers.
+    #[cfg(feature = "validate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+    pub fn validator(&self) -> Option<&Validator> {
+        self.validator.as_deref()
+    }
+
+    /// Returns the metrics if the resolver is instrumented.
+    pub fn metrics(&self) -> Option<&dyn Metrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Returns the response policy if the resolver applies one.
+    #[cfg(feature = "zonetree")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "zonetree")))]
+    pub fn rpz(&self) -> Option<&Rpz> {
+        self.rpz.as_deref()
+    }
+
     pub async fn query<N: ToDname, Q: Into<Question<N>>>(
         &self,
         question: Q,
     ) -> Result<Answer, io::Error> {
-        Query::new(self)?
-            .run(Query::create_message(question.into()))
+        self.query_message(Query::create_message(question.into()))
             .await
     }
 
+    /// Sends a query using buffers provided by the caller.
+    ///
+    /// The request message is built in `request_buf` and the response is
+    /// received into `response_buf`. The returned message borrows from the
+    /// latter. This allows tight loops, such as health checks or load
+    /// generators, to reuse the same buffers for every query instead of
+    /// allocating new ones.
+    ///
+    /// The query is retried with the configured servers in the same way as
+    /// [`query`][Self::query] does. If an answer is SERVFAIL, later
+    /// attempts receive into the remaining space of `response_buf` so that
+    /// the answer can be returned if no server provides a better one.
+    ///
+    /// Note that the answer is neither validated nor cached even if the
+    /// resolver has a validator or a cache.
+    ///
+    /// Returns an error if the request doesn’t fit into `request_buf` or
+    /// the response doesn’t fit into `response_buf`.
+    pub async fn query_into<'b, N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+        request_buf: &mut [u8],
+        response_buf: &'b mut [u8],
+    ) -> Result<Message<&'b [u8]>, io::Error> {
+        let mut message = MessageBuilder::from_target(
+            StreamTarget::new(SliceTarget::new(request_buf))
+                .map_err(|_| short_request())?,
+        )
+        .map_err(|_| short_request())?;
+        message.header_mut().set_rd(true);
+        let mut message = message.question();
+        message.push(question.into()).map_err(|_| short_request())?;
+        let mut message = message.additional();
+        let len = Query::new(self)?
+            .run_into(&mut message, response_buf)
+            .await?;
+        let response_buf: &'b [u8] = response_buf;
+        Ok(Message::from_octets(&response_buf[..len])
+            .expect("checked response"))
+    }
+
+    /// Opens connections to the servers ahead of time.
+    ///
+    /// The resolver asks its transport to prepare for sending requests to
+    /// each server it uses via TCP. With the default transport, this opens
+    /// a persistent connection to each of these servers if the `stay_open`
+    /// option is set and does nothing otherwise.
+    ///
+    /// Returns an error only if the transport failed for all servers.
+    pub async fn connect(&self) -> Result<(), io::Error> {
+        let results = futures::future::join_all(
+            self.stream
+                .iter()
+                .map(|server| self.transport.connect(&server.conf)),
+        )
+        .await;
+        let mut res = Ok(());
+        for item in results {
+            match item {
+                Ok(()) => return Ok(()),
+                Err(err) => res = Err(err),
+            }
+        }
+        res
+    }
+
+    /// Refreshes cached answers in the background.
+    ///
+    /// If the resolver’s cache has a stale TTL or a prefetch threshold,
+    /// answers that are stale or about to expire are served from the cache
+    /// and queued for refreshing. The future returned by this method
+    /// performs these refreshes one after another. It never completes, so
+    /// it should be spawned as a task of its own. Since there can only be
+    /// one such future per cache, it completes immediately if another one
+    /// is already running or if the resolver doesn’t have a cache.
+    ///
+    /// See the [`cache`] module for details.
+    pub async fn refresh_cache(&self) {
+        let cache = match self.cache.as_deref() {
+            Some(cache) => cache,
+            None => return,
+        };
+        let mut refresher = match cache.refresher() {
+            Some(refresher) => refresher,
+            None => return,
+        };
+        while let Some(question) = refresher.next().await {
+            let message = Query::create_message(question.clone());
+            let (id, started) = self.start_resolution(&message);
+            #[cfg(feature = "tracing")]
+            let span = resolution_span(&message, id);
+            let res = self.resolve(message, id);
+            #[cfg(feature = "tracing")]
+            let res = res.instrument(span);
+            let res = res.await;
+            self.finish_resolution(id, started, &res);
+            cache.refreshed(&question);
+        }
+    }
+
     async fn query_message(
         &self,
         message: QueryMessage,
     ) -> Result<Answer, io::Error> {
-        Query::new(self)?.run(message).await
+        let (id, started) = self.start_resolution(&message);
+        #[cfg(feature = "tracing")]
+        let span = resolution_span(&message, id);
+        let res = async {
+            #[cfg(feature = "zonetree")]
+            match self.rpz.as_deref() {
+                Some(rpz) => self.lookup_with_rpz(rpz, message, id).await,
+                None => self.lookup_message(message, id).await,
+            }
+            #[cfg(not(feature = "zonetree"))]
+            self.lookup_message(message, id).await
+        };
+        #[cfg(feature = "tracing")]
+        let res = res.instrument(span);
+        let res = res.await;
+        self.finish_resolution(id, started, &res);
+        res
+    }
+
+    /// Answers a query message while applying a response policy.
+    #[cfg(feature = "zonetree")]
+    async fn lookup_with_rpz(
+        &self,
+        rpz: &Rpz,
+        message: QueryMessage,
+        id: ResolutionId,
+    ) -> Result<Answer, io::Error> {
+        let request = message.as_message();
+        let request =
+            Message::from_octets(Bytes::copy_from_slice(request.as_slice()))
+                .expect("valid message");
+        let hit = request
+            .first_question()
+            .and_then(|question| rpz.check_query(&question.qname()));
+        if let Some(hit) = hit {
+            return match policy_answer(hit.respond(
+                &request,
+                false,
+                u16::MAX.into(),
+            )) {
+                Some(res) => res,
+                None => self.lookup_message(message, id).await,
+            };
+        }
+        let answer = self.lookup_message(message, id).await?;
+        match rpz.check_response(&answer) {
+            Some(hit) => {
+                policy_answer(hit.respond(&request, false, u16::MAX.into()))
+                    .unwrap_or(Ok(answer))
+            }
+            None => Ok(answer),
+        }
+    }
+
+    /// Answers a query message from the cache or by resolving it.
+    async fn lookup_message(
+        &self,
+        message: QueryMessage,
+        id: ResolutionId,
+    ) -> Result<Answer, io::Error> {
+        // Stale answers are served right away if a refresher will take
+        // care of getting a fresh one. Otherwise we try to get a fresh
+        // answer ourselves and only serve the stale one if that fails.
+        let stale = match self.cached(&message, id) {
+            Some((answer, Freshness::Stale)) => {
+                if self.cache.as_ref().map_or(false, |c| c.is_refreshing()) {
+                    return Ok(answer);
+                }
+                Some(answer)
+            }
+            Some((answer, _)) => return Ok(answer),
+            None => None,
+        };
+        #[cfg(feature = "validate")]
+        if let Some(answer) = self.synthesized(&message) {
+            return Ok(answer);
+        }
+        match (self.resolve(message, id).await, stale) {
+            (Ok(answer), Some(stale)) if !answer.is_final() => Ok(stale),
+            (Err(_), Some(stale)) => Ok(stale),
+            (res, _) => res,
+        }
+    }
+
+    /// Resolves a query message, joining a query already in flight.
+    ///
+    /// Concurrent queries for the same question are sent upstream only
+    /// once and all of them receive the same answer.
+    async fn resolve(
+        &self,
+        message: QueryMessage,
+        id: ResolutionId,
+    ) -> Result<Answer, io::Error> {
+        let question = message.as_message().first_question().map(|q| {
+            Question::new(q.qname().to_bytes(), q.qtype(), q.qclass())
+        });
+        let question = match question {
+            Some(question) => question,
+            None => return self.resolve_upstream(message, id).await,
+        };
+        let resolver = self.clone();
+        self.in_flight
+            .resolve(question, move || async move {
+                resolver.resolve_upstream(message, id).await
+            })
+            .await
+    }
+
+    /// Resolves a query message upstream and caches the answer.
+    async fn resolve_upstream(
+        &self,
+        message: QueryMessage,
+        id: ResolutionId,
+    ) -> Result<Answer, io::Error> {
+        if self.options.qname_minimisation {
+            self.minimise(&message, id).await;
+        }
+        let mut query = Query::new(self)?;
+        query.id = id;
+        let answer = query.run(message).await?;
+        #[cfg(feature = "validate")]
+        let answer = match self.validator.as_ref() {
+            Some(validator) if !self.options.checking_disabled => {
+                #[cfg(feature = "tracing")]
+                let span = tracing::debug_span!(
+                    "validation",
+                    status = tracing::field::Empty
+                );
+                let answer = validator.check(self, answer);
+                #[cfg(feature = "tracing")]
+                let answer = answer.instrument(span.clone());
+                let answer = answer.await?;
+                #[cfg(feature = "tracing")]
+                if let Some(status) = answer.security_status() {
+                    span.record("status", tracing::field::display(status));
+                }
+                if let (Some(metrics), Some(status)) =
+                    (self.metrics(), answer.security_status())
+                {
+                    metrics.validated(id, status)
+                }
+                answer
+            }
+            _ => answer,
+        };
+        if let Some(cache) = self.cache.as_deref() {
+            cache.insert(answer.clone());
+        }
+        Ok(answer)
+    }
+
+    /// Queries for the ancestors of the query name of a query message.
+    ///
+    /// Stops at the first answer that isn’t a NOERROR answer without an
+    /// alias, including the NXDOMAIN answers broken servers give for empty
+    /// non-terminals, so that the full query is sent in any case.
+    async fn minimise(&self, message: &QueryMessage, id: ResolutionId) {
+        let question = match message.as_message().first_question() {
+            Some(question) => question,
+            None => return,
+        };
+        let qname = question.qname().to_bytes();
+        for name in minimised_names(&qname) {
+            let mut query = match Query::new(self) {
+                Ok(query) => query,
+                Err(_) => return,
+            };
+            query.id = id;
+            let message = Query::create_message(Question::new(
+                name,
+                Rtype::A,
+                question.qclass(),
+            ));
+            let answer = match query.run(message).await {
+                Ok(answer) => answer,
+                Err(_) => return,
+            };
+            if answer.header().rcode() != Rcode::NoError || has_alias(&answer)
+            {
+                return;
+            }
+        }
+    }
+
+    /// Returns the cached answer for the question of a query message.
+    fn cached(
+        &self,
+        message: &QueryMessage,
+        id: ResolutionId,
+    ) -> Option<(Answer, Freshness)> {
+        let cache = self.cache.as_deref()?;
+        let sent = Message::from_octets(message.as_target().as_dgram_slice())
+            .ok()?;
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "cache_lookup",
+            result = tracing::field::Empty
+        )
+        .entered();
+        let question = sent.first_question()?;
+        let res = match self.options.client_subnet {
+            Some(client_subnet) => {
+                cache.lookup_for_client(&question, client_subnet.addr())
+            }
+            None => cache.lookup(&question),
+        };
+        #[cfg(feature = "tracing")]
+        span.record(
+            "result",
+            match &res {
+                Some((_, freshness)) if freshness.is_stale() => "stale",
+                Some(_) => "hit",
+                None => "miss",
+            },
+        );
+        if let Some(metrics) = self.metrics() {
+            match &res {
+                Some((_, freshness)) => {
+                    metrics.cache_hit(id, freshness.is_stale())
+                }
+                None => metrics.cache_miss(id),
+            }
+        }
+        res
+    }
+
+    /// Returns a negative answer synthesized by the validator.
+    #[cfg(feature = "validate")]
+    fn synthesized(&self, message: &QueryMessage) -> Option<Answer> {
+        match self.validator.as_deref() {
+            Some(validator) if !self.options.checking_disabled => {
+                validator.synthesize(&message.as_message())
+            }
+            _ => None,
+        }
+    }
+
+    /// Reports the start of resolving a query message to the metrics.
+    fn start_resolution(
+        &self,
+        message: &QueryMessage,
+    ) -> (ResolutionId, Instant) {
+        let id = ResolutionId::next();
+        if let Some(metrics) = self.metrics() {
+            let sent = message.as_message();
+            if let Some(question) = sent.first_question() {
+                metrics.resolution_started(id, &question)
+            }
+        }
+        (id, Instant::now())
+    }
+
+    /// Reports the end of a resolution to the metrics.
+    fn finish_resolution(
+        &self,
+        id: ResolutionId,
+        started: Instant,
+        res: &Result<Answer, io::Error>,
+    ) {
+        if let Some(metrics) = self.metrics() {
+            metrics.resolution_finished(
+                id,
+                started.elapsed(),
+                res.as_ref().map(|answer| answer.header().rcode()),
+            )
+        }
     }
 }
 
 impl StubResolver {
     pub async fn lookup_addr(
         &self,
-        addr: IpAddr,
+        addr: impl Into<IpAddr>,
     ) -> Result<FoundAddrs<&Self>, io::Error> {
-        lookup_addr(&self, addr).await
+        self.limit_lookup(lookup_addr(&self, addr)).await
     }
 
     pub async fn lookup_host(
         &self,
         qname: impl ToDname,
     ) -> Result<FoundHosts<&Self>, io::Error> {
-        lookup_host(&self, qname).await
+        self.limit_lookup(lookup_host(&self, qname)).await
     }
 
     pub async fn search_host(
         &self,
         qname: impl ToRelativeDname,
     ) -> Result<FoundHosts<&Self>, io::Error> {
-        search_host(&self, qname).await
+        self.limit_lookup(search_host(&self, qname)).await
     }
 
     /// Performs an SRV lookup using this resolver.
@@ -151,7 +739,29 @@ impl StubResolver {
         name: impl ToDname,
         fallback_port: u16,
     ) -> Result<Option<FoundSrvs>, SrvError> {
-        lookup_srv(&self, service, name, fallback_port).await
+        self.limit_lookup(lookup_srv(&self, service, name, fallback_port))
+            .await
+    }
+
+    /// Runs a lookup of possibly several queries within the lookup timeout.
+    ///
+    /// Each query keeps to the lookup timeout by itself. This makes sure
+    /// that all of them together do, too.
+    async fn limit_lookup<T, E: From<io::Error>>(
+        &self,
+        lookup: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        match self.options.lookup_timeout {
+            Some(limit) => match timeout(limit, lookup).await {
+                Ok(res) => res,
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "lookup timed out",
+                )
+                .into()),
+            },
+            None => lookup.await,
+        }
     }
 }
 
@@ -204,6 +814,17 @@ impl Default for StubResolver {
     }
 }
 
+impl fmt::Debug for StubResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StubResolver")
+            .field("preferred", &self.preferred)
+            .field("stream", &self.stream)
+            .field("options", &self.options)
+            .field("retry", &self.retry)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<'a> Resolver for &'a StubResolver {
     type Octets = Bytes;
     type Answer = Answer;
@@ -244,9 +865,21 @@ pub struct Query<'a> {
     /// The number of attempts, starting with zero.
     attempt: usize,
 
+    /// The pause before the next request if a new round has started.
+    backoff: Option<Duration>,
+
     /// The index in the server list we currently trying.
     counter: ServerListCounter,
 
+    /// Have we already retried the current server after a BADCOOKIE?
+    cookie_retried: bool,
+
+    /// The time after which we stop trying.
+    deadline: Option<Instant>,
+
+    /// The resolution this query is part of for reporting to metrics.
+    id: ResolutionId,
+
     /// The preferred error to return.
     ///
     /// Every time we finish a single query, we see if we can update this with
@@ -262,6 +895,12 @@ impl<'a> Query<'a> {
         let (preferred, counter) =
             if resolver.options().use_vc || resolver.preferred.is_empty() {
                 if resolver.stream.is_empty() {
+                    if resolver.options().privacy == PrivacyProfile::Strict {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "no servers with authenticated encryption",
+                        ));
+                    }
                     return Err(io::Error::new(
                         io::ErrorKind::NotFound,
                         "no servers available",
@@ -275,7 +914,14 @@ impl<'a> Query<'a> {
             resolver,
             preferred,
             attempt: 0,
+            backoff: None,
             counter,
+            cookie_retried: false,
+            deadline: resolver
+                .options()
+                .lookup_timeout
+                .map(|limit| Instant::now() + limit),
+            id: ResolutionId::next(),
             error: Err(io::Error::new(
                 io::ErrorKind::TimedOut,
                 "all timed out",
@@ -283,18 +929,26 @@ impl<'a> Query<'a> {
         })
     }
 
+    /// Sends the query to the servers in turn until one gives an answer.
     pub async fn run(
         mut self,
         mut message: QueryMessage,
     ) -> Result<Answer, io::Error> {
         loop {
+            self.wait_backoff().await;
             match self.run_query(&mut message).await {
                 Ok(answer) => {
-                    if answer.header().rcode() == Rcode::FormErr
-                        && self.current_server().does_edns()
+                    if self
+                        .current_server()
+                        .retry_edns(&message.as_message(), &answer)
                     {
-                        // FORMERR with EDNS: turn off EDNS and try again.
-                        self.current_server().disable_edns();
+                        // EDNS trouble: try again with a lower version or
+                        // without EDNS.
+                        continue;
+                    } else if answer.is_bad_cookie() && !self.cookie_retried {
+                        // BADCOOKIE: we now know the server cookie, so try
+                        // again once (RFC 7873, section 5.3).
+                        self.cookie_retried = true;
                         continue;
                     } else if answer.header().rcode() == Rcode::ServFail {
                         // SERVFAIL: go to next server.
@@ -324,6 +978,92 @@ impl<'a> Query<'a> {
         }
     }
 
+    /// Like [`run`][Self::run] but receives the answer into `buf`.
+    ///
+    /// Returns the length of the answer at the start of `buf`.
+    pub async fn run_into<Target>(
+        mut self,
+        message: &mut AdditionalBuilder<StreamTarget<Target>>,
+        buf: &mut [u8],
+    ) -> Result<usize, io::Error>
+    where
+        Target: Composer,
+        Target::AppendError: Into<ShortBuf>,
+    {
+        // The preferred error to return. If it is a SERVFAIL answer, it is
+        // kept at the start of `buf`, its length is `start`, and further
+        // attempts receive into the rest of `buf`.
+        let mut error =
+            Err(io::Error::new(io::ErrorKind::TimedOut, "all timed out"));
+        let mut start = 0;
+        loop {
+            self.wait_backoff().await;
+            let server = self.current_server();
+            server
+                .prepare_message(message, self.resolver.options())
+                .map_err(|_| short_request())?;
+            let sent = Instant::now();
+            let res = server.query_into(
+                &*self.resolver.transport,
+                message,
+                &mut buf[start..],
+                self.request_timeout(server),
+            );
+            #[cfg(feature = "tracing")]
+            let res = res.instrument(self.upstream_span(server));
+            let res = res.await;
+            match res {
+                Ok(len) => {
+                    let answer = Message::from_octets(&buf[start..][..len])
+                        .expect("checked response");
+                    let rcode = answer.header().rcode();
+                    self.report(server, sent, Ok(rcode));
+                    let is_bad_cookie =
+                        answer.opt().map(|opt| opt.rcode(answer.header()))
+                            == Some(OptRcode::BadCookie);
+                    if server.retry_edns(&message.as_message(), &answer) {
+                        // EDNS trouble: try again with a lower version or
+                        // without EDNS.
+                        continue;
+                    } else if is_bad_cookie && !self.cookie_retried {
+                        // BADCOOKIE: we now know the server cookie, so try
+                        // again once (RFC 7873, section 5.3).
+                        self.cookie_retried = true;
+                        continue;
+                    } else if rcode == Rcode::ServFail {
+                        // SERVFAIL: keep the first one and go to the next
+                        // server.
+                        if start == 0 {
+                            start = len;
+                            error = Ok(len);
+                        }
+                    } else if answer.header().tc()
+                        && self.preferred
+                        && !self.resolver.options().ign_tc
+                        && self.switch_to_stream()
+                    {
+                        // Truncated: try again via stream transports.
+                        continue;
+                    } else {
+                        buf.copy_within(start..start + len, 0);
+                        return Ok(len);
+                    }
+                }
+                Err(err) => {
+                    self.report(server, sent, Err(&err));
+                    // See update_error for the rules.
+                    if err.kind() != io::ErrorKind::TimedOut && error.is_err()
+                    {
+                        error = Err(err)
+                    }
+                }
+            }
+            if !self.next_server() {
+                return error;
+            }
+        }
+    }
+
     fn create_message(question: Question<impl ToDname>) -> QueryMessage {
         let mut message = MessageBuilder::from_target(
             StreamTarget::new(Default::default()).unwrap(),
@@ -340,8 +1080,91 @@ impl<'a> Query<'a> {
         message: &mut QueryMessage,
     ) -> Result<Answer, io::Error> {
         let server = self.current_server();
-        server.prepare_message(message);
-        server.query(message).await
+        server
+            .prepare_message(message, self.resolver.options())
+            .map_err(|_| short_request())?;
+        let sent = Instant::now();
+        let res = server.query(
+            &*self.resolver.transport,
+            message,
+            self.request_timeout(server),
+        );
+        #[cfg(feature = "tracing")]
+        let res = res.instrument(self.upstream_span(server));
+        let res = res.await;
+        self.report(
+            server,
+            sent,
+            res.as_ref().map(|answer| answer.header().rcode()),
+        );
+        res
+    }
+
+    /// Reports the outcome of a query to a server to the metrics.
+    fn report(
+        &self,
+        server: &ServerInfo,
+        sent: Instant,
+        res: Result<Rcode, &io::Error>,
+    ) {
+        let metrics = match self.resolver.metrics() {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        match res {
+            Ok(rcode) => metrics.upstream_response(
+                self.id,
+                server.conf.addr,
+                sent.elapsed(),
+                rcode,
+            ),
+            Err(err) => {
+                metrics.upstream_error(self.id, server.conf.addr, err)
+            }
+        }
+    }
+
+    /// Creates the tracing span for a request to a server.
+    #[cfg(feature = "tracing")]
+    fn upstream_span(&self, server: &ServerInfo) -> tracing::Span {
+        tracing::debug_span!(
+            "upstream",
+            server = %server.conf.addr,
+            transport = ?server.conf.transport,
+            attempt = self.attempt,
+        )
+    }
+
+    /// Returns how long to wait for a response from a server.
+    ///
+    /// This is the request timeout given by the retry policy unless the
+    /// deadline is closer.
+    fn request_timeout(&self, server: &ServerInfo) -> Duration {
+        let timeout = self
+            .resolver
+            .retry
+            .request_timeout(self.attempt, server.conf.request_timeout);
+        match self.deadline {
+            Some(deadline) => timeout
+                .min(deadline.saturating_duration_since(Instant::now())),
+            None => timeout,
+        }
+    }
+
+    /// Pauses before the next request if a new round has started.
+    async fn wait_backoff(&mut self) {
+        let backoff = match self.backoff.take() {
+            Some(backoff) => backoff,
+            None => return,
+        };
+        let backoff = match self.deadline {
+            Some(deadline) => backoff
+                .min(deadline.saturating_duration_since(Instant::now())),
+            None => backoff,
+        };
+        if !backoff.is_zero() {
+            tokio::time::sleep(backoff).await
+        }
     }
 
     fn current_server(&self) -> &ServerInfo {
@@ -373,17 +1196,26 @@ impl<'a> Query<'a> {
         }
         self.preferred = false;
         self.attempt = 0;
+        self.cookie_retried = false;
         self.counter =
             self.resolver.stream.counter(self.resolver.options().rotate);
         true
     }
 
     fn next_server(&mut self) -> bool {
+        self.cookie_retried = false;
+        if self
+            .deadline
+            .map_or(false, |deadline| deadline <= Instant::now())
+        {
+            return false;
+        }
         if self.counter.next() {
             return true;
         }
         self.attempt += 1;
-        if self.attempt >= self.resolver.options().attempts {
+        let retry = &self.resolver.retry;
+        if self.attempt >= retry.attempts() {
             return false;
         }
         self.counter = if self.preferred {
@@ -393,13 +1225,94 @@ impl<'a> Query<'a> {
         } else {
             self.resolver.stream.counter(self.resolver.options().rotate)
         };
+        if retry.rotate_rounds() {
+            self.counter.shift(self.attempt)
+        }
+        self.backoff = Some(retry.backoff(self.attempt));
         true
     }
 }
 
+//------------ QNAME Minimisation --------------------------------------------
+
+/// The maximum number of queries sent for a single query name.
+///
+/// This is `MAX_MINIMISE_COUNT` of RFC 9156, section 2.3, and includes
+/// the final query with the full name.
+const MAX_MINIMISE_COUNT: usize = 10;
+
+/// The number of queries that add only a single label.
+///
+/// This is `MINIMISE_ONE_LAB` of RFC 9156, section 2.3.
+const MINIMISE_ONE_LAB: usize = 4;
+
+/// Returns the ancestors of `qname` to query for in order.
+///
+/// The first few ancestors add a single label each. For names with many
+/// labels, the remaining ones are added in larger steps so that there are
+/// at most `MAX_MINIMISE_COUNT` queries including the one for `qname`
+/// itself. Neither the root nor `qname` are included.
+fn minimised_names(qname: &Dname<Bytes>) -> Vec<Dname<Bytes>> {
+    // Suffixes from the top-level domain down to qname, without the root.
+    let mut suffixes: Vec<_> = qname.iter_suffixes().collect();
+    suffixes.pop();
+    suffixes.reverse();
+    let total = suffixes.len();
+    let mut res = Vec::new();
+    let mut labels = 1;
+    while labels < total {
+        res.push(suffixes[labels - 1].clone());
+        labels += if res.len() < MINIMISE_ONE_LAB {
+            1
+        } else {
+            let left = MAX_MINIMISE_COUNT.saturating_sub(res.len() + 1);
+            ((total - labels) / left.max(1)).max(1)
+        };
+    }
+    res
+}
+
+/// Returns whether an answer contains a CNAME or DNAME record.
+fn has_alias(answer: &Answer) -> bool {
+    let records = match answer.answer() {
+        Ok(records) => records,
+        Err(_) => return false,
+    };
+    records
+        .flatten()
+        .any(|record| matches!(record.rtype(), Rtype::Cname | Rtype::Dname))
+}
+
+//------------ Tracing -------------------------------------------------------
+
+/// Creates the tracing span for resolving a query message.
+#[cfg(feature = "tracing")]
+fn resolution_span(
+    message: &QueryMessage,
+    id: ResolutionId,
+) -> tracing::Span {
+    let sent = message.as_message();
+    match sent.first_question() {
+        Some(question) => tracing::debug_span!(
+            "resolution",
+            id = id.into_int(),
+            qname = %question.qname(),
+            qtype = %question.qtype(),
+        ),
+        None => tracing::debug_span!("resolution", id = id.into_int()),
+    }
+}
+
 //------------ QueryMessage --------------------------------------------------
 
-// XXX This needs to be re-evaluated if we start adding OPTtions to the query.
+/// A query message sent by the resolver.
+///
+/// A query has a single question and, with EDNS, an OPT record with at
+/// most the TCP keepalive, cookie, NSID, and client subnet options. With a
+/// name, a server cookie, and an IPv6 client subnet of the maximum
+/// lengths, this is 360 octets including the two octet length prefix for
+/// streams. Queries over encrypted transports are padded to a multiple of
+/// 128 octets which brings this to 386 octets. Both fit comfortably.
 pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Array<512>>>;
 
 //------------ Answer --------------------------------------------------------
@@ -411,6 +1324,13 @@ pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Array<512>>>;
 #[derive(Clone)]
 pub struct Answer {
     message: Message<Bytes>,
+
+    /// Whether the answer has been served stale from the cache.
+    stale: bool,
+
+    /// The outcome of validating the answer.
+    #[cfg(feature = "validate")]
+    security: Option<SecurityStatus>,
 }
 
 impl Answer {
@@ -421,11 +1341,61 @@ impl Answer {
             && !self.message.header().tc()
     }
 
+    /// Returns whether the answer has the extended rcode BADCOOKIE.
+    pub fn is_bad_cookie(&self) -> bool {
+        matches!(
+            self.message
+                .opt()
+                .map(|opt| opt.rcode(self.message.header())),
+            Some(OptRcode::BadCookie)
+        )
+    }
+
+    /// Returns the extended DNS errors included in the answer.
+    ///
+    /// Servers may include one or more extended DNS errors as defined in
+    /// RFC 8914 in a response to explain why they failed to provide an
+    /// answer or why the answer they provide may be incomplete. This is
+    /// particularly useful for SERVFAIL answers.
+    pub fn extended_errors(&self) -> Vec<ExtendedError<Bytes>> {
+        match self.message.opt() {
+            Some(opt) => opt.opt().extended_errors().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the name server identifier included in the answer.
+    ///
+    /// Servers only include their identifier if asked to, which the
+    /// resolver does if the `nsid` option is enabled.
+    pub fn nsid(&self) -> Option<Nsid<Bytes>> {
+        self.message.opt()?.opt().nsid()
+    }
+
     /// Returns whether the answer is truncated.
     pub fn is_truncated(&self) -> bool {
         self.message.header().tc()
     }
 
+    /// Returns whether the answer was served stale from a cache.
+    ///
+    /// Such an answer has expired and was served because no fresh answer
+    /// was available. See the [`cache`] module for details.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Returns the security status of the answer.
+    ///
+    /// This is only available if the answer was validated by the
+    /// resolver, which requires it to have a validator and the
+    /// `checking_disabled` option not to be set.
+    #[cfg(feature = "validate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+    pub fn security_status(&self) -> Option<SecurityStatus> {
+        self.security
+    }
+
     pub fn into_message(self) -> Message<Bytes> {
         self.message
     }
@@ -433,7 +1403,12 @@ impl Answer {
 
 impl From<Message<Bytes>> for Answer {
     fn from(message: Message<Bytes>) -> Self {
-        Answer { message }
+        Answer {
+            message,
+            stale: false,
+            #[cfg(feature = "validate")]
+            security: None,
+        }
     }
 }
 
@@ -460,8 +1435,32 @@ struct ServerInfo {
 
     /// Whether this server supports EDNS.
     ///
-    /// We start out with assuming it does and unset it if we get a FORMERR.
+    /// We start out with assuming it does and unset it if we get a FORMERR
+    /// or a BADVERS for a version it claims to support.
     edns: Arc<AtomicBool>,
+
+    /// The highest EDNS version this server supports.
+    ///
+    /// We start out with assuming it supports all versions and lower this
+    /// to the version given in a BADVERS response.
+    edns_version: Arc<AtomicU8>,
+
+    /// The DNS cookie to send to this server.
+    ///
+    /// The client cookie is chosen randomly when the server info is created.
+    /// The server cookie is learned from the server’s responses.
+    cookie: Arc<Mutex<Cookie>>,
+
+    /// The UDP payload size to advertise to this server.
+    ///
+    /// This starts out as the configured size and is adapted if we suspect
+    /// fragmentation.
+    payload: Arc<PayloadSize>,
+
+    /// The privacy the transport offers for this server.
+    ///
+    /// Queries over encrypted transports are padded.
+    privacy: Privacy,
 }
 
 impl ServerInfo {
@@ -473,139 +1472,255 @@ impl ServerInfo {
         self.edns.store(false, Ordering::Relaxed);
     }
 
-    pub fn prepare_message(&self, query: &mut QueryMessage) {
-        query.rewind();
-        if self.does_edns() {
-            query
-                .opt(|opt| {
-                    opt.set_udp_payload_size(self.conf.udp_payload_size);
-                    Ok(())
-                })
-                .unwrap();
-        }
+    /// Returns the EDNS version to use for queries to this server.
+    pub fn edns_version(&self, options: &ResolvOptions) -> u8 {
+        self.edns_version
+            .load(Ordering::Relaxed)
+            .min(options.edns_version)
     }
 
-    pub async fn query(
+    /// Checks a response for EDNS errors and learns from them.
+    ///
+    /// Returns whether the query should be retried. This is the case if the
+    /// server answered a query with EDNS with FORMERR, in which case EDNS is
+    /// disabled for the server, or with BADVERS. For the latter, the server
+    /// includes the highest version it supports (RFC 6891, section 6.1.3).
+    /// If that is lower than the version we sent, we use it from now on.
+    /// Otherwise the server makes no sense and we disable EDNS, too.
+    ///
+    /// Since each retry either lowers the version or disables EDNS, this
+    /// eventually returns `false`.
+    fn retry_edns<Q: Octets, A: Octets>(
         &self,
-        query: &QueryMessage,
-    ) -> Result<Answer, io::Error> {
-        let res = match self.conf.transport {
-            Transport::Udp => {
-                timeout(
-                    self.conf.request_timeout,
-                    Self::udp_query(
-                        query,
-                        self.conf.addr,
-                        self.conf.recv_size,
-                    ),
-                )
-                .await
+        query: &Message<Q>,
+        answer: &Message<A>,
+    ) -> bool {
+        let sent = match query.opt() {
+            Some(opt) => opt.version(),
+            None => return false,
+        };
+        match answer.opt() {
+            Some(opt) if opt.rcode(answer.header()) == OptRcode::BadVers => {
+                if opt.version() < sent {
+                    self.edns_version
+                        .fetch_min(opt.version(), Ordering::Relaxed);
+                } else {
+                    self.disable_edns();
+                }
+                true
             }
-            Transport::Tcp => {
-                timeout(
-                    self.conf.request_timeout,
-                    Self::tcp_query(query, self.conf.addr),
-                )
-                .await
+            _ if answer.header().rcode() == Rcode::FormErr => {
+                self.disable_edns();
+                true
             }
-        };
-        match res {
-            Ok(Ok(answer)) => Ok(answer),
-            Ok(Err(err)) => Err(err),
-            Err(_) => Err(io::Error::new(
-                io::ErrorKind::TimedOut,
-                "request timed out",
-            )),
+            _ => false,
         }
     }
 
-    pub async fn tcp_query(
-        query: &QueryMessage,
-        addr: SocketAddr,
-    ) -> Result<Answer, io::Error> {
-        let mut sock = TcpStream::connect(&addr).await?;
-        sock.write_all(query.as_target().as_stream_slice()).await?;
+    /// Returns the DNS cookie to send with the next query to the server.
+    pub fn cookie(&self) -> Cookie {
+        self.cookie.lock().unwrap().clone()
+    }
 
-        // This loop can be infinite because we have a timeout on this whole
-        // thing, anyway.
-        loop {
-            let mut buf = Vec::new();
-            let len = sock.read_u16().await? as u64;
-            AsyncReadExt::take(&mut sock, len)
-                .read_to_end(&mut buf)
-                .await?;
-            if let Ok(answer) = Message::from_octets(buf.into()) {
-                if answer.is_answer(&query.as_message()) {
-                    return Ok(answer.into());
+    /// Checks the cookie of a response and remembers the server cookie.
+    ///
+    /// Returns an error if the response contains a cookie with a different
+    /// client cookie than the one we sent, as described in section 5.3 of
+    /// RFC 7873. Responses from servers that don’t support cookies don’t
+    /// contain one and are always fine.
+    fn update_cookie<Octs: Octets>(
+        &self,
+        sent: &Cookie,
+        answer: &Message<Octs>,
+    ) -> Result<(), io::Error> {
+        let received = match answer.opt().and_then(|opt| opt.opt().cookie()) {
+            Some(cookie) => cookie,
+            None => return Ok(()),
+        };
+        if received.client() != sent.client() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response with wrong client cookie",
+            ));
+        }
+        if received.server().is_some() {
+            *self.cookie.lock().unwrap() = received;
+        }
+        Ok(())
+    }
+
+    /// Prepares a query message for sending it to the server.
+    ///
+    /// Gives the message a new ID and sets its OPT record according to
+    /// `options` and what we know about the server.
+    pub fn prepare_message<Target>(
+        &self,
+        query: &mut AdditionalBuilder<StreamTarget<Target>>,
+        options: &ResolvOptions,
+    ) -> Result<(), PushError>
+    where
+        Target: Composer,
+        Target::AppendError: Into<ShortBuf>,
+    {
+        query.rewind();
+        // Every attempt gets a new random ID. This makes it possible to
+        // pipeline requests over a shared connection and makes spoofing
+        // responses harder.
+        query.header_mut().set_random_id();
+        query.header_mut().set_cd(options.checking_disabled);
+        if self.does_edns() {
+            query.opt(|opt| {
+                opt.set_udp_payload_size(self.udp_payload_size(options));
+                opt.set_version(self.edns_version(options));
+                // Signal that we would like to keep the connection
+                // open (RFC 7828, section 3.2.1).
+                if options.stay_open && self.conf.transport.is_stream() {
+                    opt.tcp_keepalive(None)?;
                 }
-            // else try with the next message.
-            } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "short buf",
-                ));
-            }
+                if options.cookies {
+                    opt.cookie(self.cookie())?;
+                }
+                if options.nsid {
+                    opt.client_nsid()?;
+                }
+                if let Some(client_subnet) = options.client_subnet {
+                    opt.push(&client_subnet)?;
+                }
+                if options.dnssec_ok {
+                    opt.set_dnssec_ok(true);
+                }
+                // Pad queries over encrypted transports so their length
+                // reveals less about the query name (RFC 8467). This has
+                // to come last.
+                if self.privacy != Privacy::Clear {
+                    opt.pad_with(&BlockPadding::query())?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the UDP payload size to advertise in the next request.
+    fn udp_payload_size(&self, options: &ResolvOptions) -> u16 {
+        if options.probe_payload_size && !self.conf.transport.is_stream() {
+            self.payload.advertise(Instant::now())
+        } else {
+            self.conf.udp_payload_size
+        }
+    }
+
+    /// Learns from the outcome of a request whether its payload size works.
+    fn update_payload<Octs: Octets>(
+        &self,
+        query: &Message<Octs>,
+        timed_out: bool,
+    ) {
+        if self.conf.transport.is_stream() {
+            return;
+        }
+        let size = match query.opt() {
+            Some(opt) => opt.udp_payload_size(),
+            None => return,
+        };
+        if timed_out {
+            self.payload.timed_out(size, Instant::now())
+        } else {
+            self.payload.answered(size, Instant::now())
         }
     }
 
-    pub async fn udp_query(
+    /// Sends a prepared query to the server and returns the answer.
+    pub async fn query(
+        &self,
+        transport: &dyn Transport,
         query: &QueryMessage,
-        addr: SocketAddr,
-        recv_size: usize,
+        request_timeout: Duration,
     ) -> Result<Answer, io::Error> {
-        let sock = Self::udp_bind(addr.is_ipv4()).await?;
-        sock.connect(addr).await?;
-        let sent = sock.send(query.as_target().as_dgram_slice()).await?;
-        if sent != query.as_target().as_dgram_slice().len() {
+        let request =
+            Request::new(query.as_target().as_stream_slice(), &self.conf);
+        let res = timeout(request_timeout, transport.request(request)).await;
+        let answer = match res {
+            Ok(Ok(answer)) => answer,
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                self.update_payload(&query.as_message(), true);
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request timed out",
+                ));
+            }
+        };
+        let answer = Message::from_octets(answer).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "short response")
+        })?;
+        let query = query.as_message();
+        if !answer.is_answer(&query) {
             return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "short UDP send",
+                io::ErrorKind::InvalidData,
+                "response doesn’t match request",
             ));
         }
-        loop {
-            let mut buf = vec![0; recv_size]; // XXX use uninit'ed mem here.
-            let len = sock.recv(&mut buf).await?;
-            buf.truncate(len);
-
-            // We ignore garbage since there is a timer on this whole thing.
-            let answer = match Message::from_octets(buf.into()) {
-                Ok(answer) => answer,
-                Err(_) => continue,
-            };
-            if !answer.is_answer(&query.as_message()) {
-                continue;
-            }
-            return Ok(answer.into());
+        self.update_payload(&query, false);
+        if let Some(sent) = query.opt().and_then(|opt| opt.opt().cookie()) {
+            self.update_cookie(&sent, &answer)?;
         }
+        Ok(answer.into())
     }
 
-    async fn udp_bind(v4: bool) -> Result<UdpSocket, io::Error> {
-        let mut i = 0;
-        loop {
-            let local: SocketAddr = if v4 {
-                ([0u8; 4], 0).into()
-            } else {
-                ([0u16; 8], 0).into()
-            };
-            match UdpSocket::bind(&local).await {
-                Ok(sock) => return Ok(sock),
-                Err(err) => {
-                    if i == RETRY_RANDOM_PORT {
-                        return Err(err);
-                    } else {
-                        i += 1
-                    }
-                }
+    /// Like [`query`][Self::query] but receives the answer into `buf`.
+    ///
+    /// Returns the length of the answer at the start of `buf`.
+    pub async fn query_into<Target: AsRef<[u8]>>(
+        &self,
+        transport: &dyn Transport,
+        query: &AdditionalBuilder<StreamTarget<Target>>,
+        buf: &mut [u8],
+        request_timeout: Duration,
+    ) -> Result<usize, io::Error> {
+        let request =
+            Request::new(query.as_target().as_stream_slice(), &self.conf);
+        let res =
+            timeout(request_timeout, transport.request_into(request, buf))
+                .await;
+        let len = match res {
+            Ok(Ok(len)) => len,
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                self.update_payload(&query.as_message(), true);
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request timed out",
+                ));
             }
+        };
+        let answer = Message::from_octets(&buf[..len]).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "short response")
+        })?;
+        let query = query.as_message();
+        if !answer.is_answer(&query) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response doesn’t match request",
+            ));
+        }
+        self.update_payload(&query, false);
+        if let Some(sent) = query.opt().and_then(|opt| opt.opt().cookie()) {
+            self.update_cookie(&sent, &answer)?;
         }
+        Ok(len)
     }
 }
 
 impl From<ServerConf> for ServerInfo {
     fn from(conf: ServerConf) -> Self {
         ServerInfo {
-            conf,
             edns: Arc::new(AtomicBool::new(true)),
+            edns_version: Arc::new(AtomicU8::new(u8::MAX)),
+            cookie: Arc::new(Mutex::new(Cookie::create_initial())),
+            payload: Arc::new(PayloadSize::new(conf.udp_payload_size)),
+            privacy: Privacy::Clear,
+            conf,
         }
     }
 }
@@ -635,18 +1750,42 @@ struct ServerList {
 }
 
 impl ServerList {
-    pub fn from_conf<F>(conf: &ResolvConf, filter: F) -> Self
+    /// Creates the list from the servers in `conf` passing `filter`.
+    ///
+    /// The privacy profile of the configuration is applied based on the
+    /// privacy `transport` offers for each server: In strict mode, only
+    /// servers with authenticated encryption are kept, in opportunistic
+    /// mode, the more private servers are moved to the front.
+    pub fn from_conf<F>(
+        conf: &ResolvConf,
+        transport: &dyn Transport,
+        filter: F,
+    ) -> Self
     where
         F: Fn(&ServerConf) -> bool,
     {
+        let profile = conf.options.privacy;
+        let mut servers: Vec<_> = conf
+            .servers
+            .iter()
+            .filter(|s| filter(s))
+            .map(|s| (transport.privacy(s), s))
+            .filter(|(privacy, _)| {
+                profile != PrivacyProfile::Strict
+                    || *privacy == Privacy::Authenticated
+            })
+            .collect();
+        if profile == PrivacyProfile::Opportunistic {
+            servers.sort_by_key(|(privacy, _)| Reverse(*privacy));
+        }
         ServerList {
-            servers: {
-                conf.servers
-                    .iter()
-                    .filter(|f| filter(f))
-                    .map(Into::into)
-                    .collect()
-            },
+            servers: servers
+                .into_iter()
+                .map(|(privacy, s)| ServerInfo {
+                    privacy,
+                    ..s.into()
+                })
+                .collect(),
             start: Arc::new(AtomicUsize::new(0)),
         }
     }
@@ -712,6 +1851,12 @@ impl ServerListCounter {
         }
     }
 
+    /// Moves the start of the counter by `by` servers.
+    pub fn shift(&mut self, by: usize) {
+        self.cur += by;
+        self.end += by;
+    }
+
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> bool {
         let next = self.cur + 1;
@@ -777,3 +1922,674 @@ impl<'a> Iterator for SearchIter<'a> {
         }
     }
 }
+
+//============ Helper Functions ==============================================
+
+/// Returns the error for a request exceeding its buffer.
+fn short_request() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "request buffer too short")
+}
+
+/// Converts the verdict of a response policy into the resolver’s result.
+///
+/// Returns `None` if the answer should be resolved normally.
+#[cfg(feature = "zonetree")]
+fn policy_answer(verdict: Verdict) -> Option<Result<Answer, io::Error>> {
+    match verdict {
+        Verdict::Pass => None,
+        Verdict::Drop => Some(Err(io::Error::new(
+            io::ErrorKind::Other,
+            "query dropped by response policy",
+        ))),
+        Verdict::Respond(message) => Some(Ok(message.into())),
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::name::{Dname, RelativeDname};
+    use crate::resolv::stub::transport::TransportFuture;
+    use core::str::FromStr;
+    use std::string::{String, ToString};
+
+    /// How the test server deals with EDNS.
+    #[derive(Clone, Copy)]
+    enum Edns {
+        /// Supports EDNS up to the given version.
+        UpTo(u8),
+
+        /// Answers FORMERR to all queries with EDNS.
+        FormErr,
+
+        /// Answers BADVERS claiming it supports the version sent.
+        Broken,
+    }
+
+    /// A transport recording the EDNS version of each request.
+    struct EdnsTransport {
+        edns: Edns,
+        versions: Arc<Mutex<Vec<Option<u8>>>>,
+    }
+
+    impl Transport for EdnsTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                let query = request.message();
+                let sent = query.opt().map(|opt| opt.version());
+                self.versions.lock().unwrap().push(sent);
+                let (rcode, version) = match (sent, self.edns) {
+                    (None, _) => (None, None),
+                    (Some(sent), Edns::UpTo(max)) if sent > max => {
+                        (Some(OptRcode::BadVers), Some(max))
+                    }
+                    (Some(_), Edns::UpTo(max)) => (None, Some(max)),
+                    (Some(_), Edns::FormErr) => {
+                        (Some(OptRcode::FormErr), None)
+                    }
+                    (Some(sent), Edns::Broken) => {
+                        (Some(OptRcode::BadVers), Some(sent))
+                    }
+                };
+                let mut answer = MessageBuilder::new_bytes()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap()
+                    .additional();
+                match version {
+                    Some(version) => answer
+                        .opt(|opt| {
+                            opt.set_version(version);
+                            if let Some(rcode) = rcode {
+                                opt.set_rcode(rcode);
+                            }
+                            Ok(())
+                        })
+                        .unwrap(),
+                    None => {
+                        if let Some(rcode) = rcode {
+                            answer.header_mut().set_rcode(rcode.rcode());
+                        }
+                    }
+                }
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    fn setup(
+        edns: Edns,
+        version: u8,
+    ) -> (StubResolver, Arc<Mutex<Vec<Option<u8>>>>) {
+        let mut conf = ResolvConf::new();
+        conf.options.edns_version = version;
+        conf.finalize();
+        let versions = Arc::new(Mutex::new(Vec::new()));
+        let transport = EdnsTransport {
+            edns,
+            versions: versions.clone(),
+        };
+        (
+            StubResolver::from_conf_with_transport(conf, transport),
+            versions,
+        )
+    }
+
+    async fn query(resolver: &StubResolver) -> Rcode {
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+        let answer = resolver.query((qname, Rtype::A)).await.unwrap();
+        answer.header().rcode()
+    }
+
+    async fn query_into(resolver: &StubResolver) -> Rcode {
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+        let mut request = [0; 512];
+        let mut response = [0; 512];
+        let answer = resolver
+            .query_into((qname, Rtype::A), &mut request, &mut response)
+            .await
+            .unwrap();
+        answer.header().rcode()
+    }
+
+    /// A transport never answering and counting requests in flight.
+    struct SilentTransport(Arc<AtomicUsize>);
+
+    /// Marks a request as in flight while alive.
+    struct InFlight(Arc<AtomicUsize>);
+
+    impl Drop for InFlight {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    impl Transport for SilentTransport {
+        fn request<'a>(
+            &'a self,
+            _request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                let _guard = InFlight(self.0.clone());
+                futures::future::pending().await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn lookup_timeout() {
+        let mut conf = ResolvConf::new();
+        conf.options.timeout = Duration::from_secs(1);
+        conf.options.attempts = 10;
+        conf.options.lookup_timeout = Some(Duration::from_millis(100));
+        for suffix in ["one.example", "two.example"] {
+            conf.options
+                .search
+                .push(SearchSuffix::from_str(suffix).unwrap());
+        }
+        conf.finalize();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let resolver = StubResolver::from_conf_with_transport(
+            conf,
+            SilentTransport(in_flight.clone()),
+        );
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+
+        let start = Instant::now();
+        let res = resolver.query((qname, Rtype::A)).await;
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        let start = Instant::now();
+        let qname = RelativeDname::<Bytes>::from_str("www").unwrap();
+        let res = resolver.search_host(qname).await;
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    /// A transport encrypting for servers with authentication configured.
+    struct PrivacyTransport(Arc<Mutex<Vec<IpAddr>>>);
+
+    impl Transport for PrivacyTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                self.0.lock().unwrap().push(request.peer().ip());
+                Ok(MessageBuilder::new_bytes()
+                    .start_answer(&request.message(), Rcode::NoError)
+                    .unwrap()
+                    .into_message()
+                    .into_octets())
+            })
+        }
+
+        fn privacy(&self, server: &ServerConf) -> Privacy {
+            match server.auth {
+                Some(_) => Privacy::Authenticated,
+                None => Privacy::Clear,
+            }
+        }
+    }
+
+    fn privacy_setup(
+        profile: PrivacyProfile,
+        auth: bool,
+    ) -> (StubResolver, Arc<Mutex<Vec<IpAddr>>>) {
+        let mut conf = ResolvConf::new();
+        conf.options.privacy = profile;
+        conf.servers.push(ServerConf::new(
+            "192.0.2.1:53".parse().unwrap(),
+            conf::Transport::Udp,
+        ));
+        let mut server = ServerConf::new(
+            "192.0.2.2:853".parse().unwrap(),
+            conf::Transport::Udp,
+        );
+        if auth {
+            server.auth = Some(conf::ServerAuth::by_name("dns.example"));
+        }
+        conf.servers.push(server);
+        conf.finalize();
+        let peers = Arc::new(Mutex::new(Vec::new()));
+        let transport = PrivacyTransport(peers.clone());
+        (
+            StubResolver::from_conf_with_transport(conf, transport),
+            peers,
+        )
+    }
+
+    #[tokio::test]
+    async fn privacy_profile() {
+        let clear: IpAddr = "192.0.2.1".parse().unwrap();
+        let private: IpAddr = "192.0.2.2".parse().unwrap();
+
+        let (resolver, peers) = privacy_setup(PrivacyProfile::Off, true);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*peers.lock().unwrap(), [clear]);
+
+        let (resolver, peers) =
+            privacy_setup(PrivacyProfile::Opportunistic, true);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*peers.lock().unwrap(), [private]);
+
+        let (resolver, peers) =
+            privacy_setup(PrivacyProfile::Opportunistic, false);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*peers.lock().unwrap(), [clear]);
+
+        let (resolver, peers) = privacy_setup(PrivacyProfile::Strict, true);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*peers.lock().unwrap(), [private]);
+
+        let (resolver, peers) = privacy_setup(PrivacyProfile::Strict, false);
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+        let res = resolver.query((qname, Rtype::A)).await;
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::NotFound);
+        assert!(peers.lock().unwrap().is_empty());
+    }
+
+    /// A transport answering SERVFAIL and recording the servers asked.
+    struct ServFailTransport(Arc<Mutex<Vec<IpAddr>>>);
+
+    impl Transport for ServFailTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                self.0.lock().unwrap().push(request.peer().ip());
+                Ok(MessageBuilder::new_bytes()
+                    .start_answer(&request.message(), Rcode::ServFail)
+                    .unwrap()
+                    .into_message()
+                    .into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_policy() {
+        let mut conf = ResolvConf::new();
+        for addr in ["192.0.2.1:53", "192.0.2.2:53"] {
+            conf.servers.push(ServerConf::new(
+                addr.parse().unwrap(),
+                conf::Transport::Udp,
+            ));
+        }
+        conf.finalize();
+        let peers = Arc::new(Mutex::new(Vec::new()));
+        let mut policy = retry::ExponentialBackoff::new(3);
+        policy.set_initial_backoff(Duration::from_millis(50));
+        policy.set_jitter(0.);
+        policy.set_rotate_rounds(true);
+        let resolver = StubResolver::from_conf_with_transport(
+            conf,
+            ServFailTransport(peers.clone()),
+        )
+        .with_retry_policy(policy);
+
+        let start = Instant::now();
+        assert_eq!(query(&resolver).await, Rcode::ServFail);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        let one: IpAddr = "192.0.2.1".parse().unwrap();
+        let two: IpAddr = "192.0.2.2".parse().unwrap();
+        assert_eq!(*peers.lock().unwrap(), [one, two, two, one, one, two]);
+    }
+
+    /// A transport recording questions and answering NXDOMAIN for a name.
+    struct MinimisingTransport {
+        nxdomain: &'static str,
+        questions: Arc<Mutex<Vec<(String, Rtype)>>>,
+    }
+
+    impl Transport for MinimisingTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                let query = request.message();
+                let question = query.sole_question().unwrap();
+                let qname = question.qname().to_string();
+                let rcode = if qname == self.nxdomain {
+                    Rcode::NXDomain
+                } else {
+                    Rcode::NoError
+                };
+                self.questions
+                    .lock()
+                    .unwrap()
+                    .push((qname, question.qtype()));
+                Ok(MessageBuilder::new_bytes()
+                    .start_answer(&query, rcode)
+                    .unwrap()
+                    .into_message()
+                    .into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn qname_minimisation() {
+        async fn questions(nxdomain: &'static str) -> Vec<(String, Rtype)> {
+            let mut conf = ResolvConf::new();
+            conf.options.qname_minimisation = true;
+            conf.finalize();
+            let questions = Arc::new(Mutex::new(Vec::new()));
+            let resolver = StubResolver::from_conf_with_transport(
+                conf,
+                MinimisingTransport {
+                    nxdomain,
+                    questions: questions.clone(),
+                },
+            );
+            let qname =
+                Dname::<Bytes>::from_str("www.a.b.example.com").unwrap();
+            let answer = resolver.query((qname, Rtype::Aaaa)).await.unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+            let res = questions.lock().unwrap().clone();
+            res
+        }
+
+        let full = ("www.a.b.example.com".into(), Rtype::Aaaa);
+        assert_eq!(
+            questions("").await,
+            [
+                ("com".into(), Rtype::A),
+                ("example.com".into(), Rtype::A),
+                ("b.example.com".into(), Rtype::A),
+                ("a.b.example.com".into(), Rtype::A),
+                full.clone(),
+            ]
+        );
+
+        // An empty non-terminal wrongly answered with NXDOMAIN.
+        assert_eq!(
+            questions("b.example.com").await,
+            [
+                ("com".into(), Rtype::A),
+                ("example.com".into(), Rtype::A),
+                ("b.example.com".into(), Rtype::A),
+                full,
+            ]
+        );
+    }
+
+    /// A tracing subscriber recording the name and parent of each span.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct SpanRecorder {
+        spans: Mutex<Vec<(&'static str, Option<u64>)>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+        ) -> tracing::span::Id {
+            let parent = match attrs.parent() {
+                Some(parent) => Some(parent.into_u64()),
+                None if attrs.is_contextual() => {
+                    self.entered.lock().unwrap().last().copied()
+                }
+                None => None,
+            };
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((attrs.metadata().name(), parent));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(
+            &self,
+            _: &tracing::span::Id,
+            _: &tracing::span::Record<'_>,
+        ) {
+        }
+
+        fn record_follows_from(
+            &self,
+            _: &tracing::span::Id,
+            _: &tracing::span::Id,
+        ) {
+        }
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, span: &tracing::span::Id) {
+            self.entered.lock().unwrap().push(span.into_u64())
+        }
+
+        fn exit(&self, _: &tracing::span::Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        let recorder = Arc::new(SpanRecorder::default());
+        let rcode =
+            tracing::subscriber::with_default(recorder.clone(), || {
+                let mut conf = ResolvConf::new();
+                conf.finalize();
+                let resolver = StubResolver::from_conf_with_transport(
+                    conf,
+                    MinimisingTransport {
+                        nxdomain: "",
+                        questions: Default::default(),
+                    },
+                )
+                .with_cache(Cache::new(16));
+                runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(query(&resolver))
+            });
+        assert_eq!(rcode, Rcode::NoError);
+        assert_eq!(
+            *recorder.spans.lock().unwrap(),
+            [
+                ("resolution", None),
+                ("cache_lookup", Some(1)),
+                ("upstream", Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn minimised_names_limit() {
+        let qname = Dname::<Bytes>::from_str(
+            "l.k.j.i.h.g.f.e.d.c.b.a.m.l.k.j.i.h.g.f.example",
+        )
+        .unwrap();
+        let names = minimised_names(&qname);
+        assert!(names.len() < MAX_MINIMISE_COUNT);
+        assert_eq!(
+            names[..MINIMISE_ONE_LAB]
+                .iter()
+                .map(Dname::label_count)
+                .collect::<Vec<_>>(),
+            [2, 3, 4, 5]
+        );
+        assert!(names
+            .windows(2)
+            .all(|w| w[0].label_count() < w[1].label_count()));
+        assert!(names.last().unwrap().label_count() < qname.label_count());
+        assert!(minimised_names(&Dname::root_bytes()).is_empty());
+    }
+
+    #[test]
+    fn query_message_size() {
+        use crate::base::iana::Class;
+        use crate::base::opt::cookie::{ClientCookie, ServerCookie};
+        use crate::base::opt::ClientSubnet;
+
+        let mut qname = Vec::new();
+        for len in [63, 63, 63, 61] {
+            qname.push(len);
+            qname.extend_from_slice(&[b'a'; 63][..usize::from(len)]);
+        }
+        qname.push(0);
+        let qname = Dname::from_octets(Bytes::from(qname)).unwrap();
+        assert_eq!(qname.len(), 255);
+
+        let server = ServerInfo::from(ServerConf::new(
+            "192.0.2.1:53".parse().unwrap(),
+            conf::Transport::Tcp,
+        ));
+        *server.cookie.lock().unwrap() = Cookie::new(
+            ClientCookie::new_random(),
+            Some(ServerCookie::from_octets(&[0; 32])),
+        );
+        let options = ResolvOptions {
+            stay_open: true,
+            nsid: true,
+            dnssec_ok: true,
+            client_subnet: Some(ClientSubnet::new(
+                128,
+                0,
+                "2001:db8::1".parse().unwrap(),
+            )),
+            ..Default::default()
+        };
+
+        let mut message =
+            Query::create_message(Question::new(qname, Rtype::A, Class::In));
+        server.prepare_message(&mut message, &options).unwrap();
+        let opt = message.as_message().opt().unwrap();
+        assert!(opt.opt().tcp_keepalive().is_some());
+        assert!(opt.opt().nsid().is_some());
+        assert!(opt.opt().client_subnet().is_some());
+        assert_eq!(message.as_target().as_stream_slice().len(), 360);
+
+        // Padding for encrypted transports goes to the next block.
+        let server = ServerInfo {
+            privacy: Privacy::Authenticated,
+            ..server
+        };
+        server.prepare_message(&mut message, &options).unwrap();
+        assert_eq!(message.as_target().as_stream_slice().len(), 386);
+        assert_eq!(message.as_slice().len() % 128, 0);
+    }
+
+    #[tokio::test]
+    async fn lower_edns_version() {
+        let (resolver, versions) = setup(Edns::UpTo(1), 3);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(query_into(&resolver).await, Rcode::NoError);
+        assert_eq!(*versions.lock().unwrap(), [Some(3), Some(1), Some(1)]);
+    }
+
+    #[tokio::test]
+    async fn disable_edns() {
+        let (resolver, versions) = setup(Edns::FormErr, 0);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*versions.lock().unwrap(), [Some(0), None, None]);
+
+        let (resolver, versions) = setup(Edns::Broken, 0);
+        assert_eq!(query_into(&resolver).await, Rcode::NoError);
+        assert_eq!(query_into(&resolver).await, Rcode::NoError);
+        assert_eq!(*versions.lock().unwrap(), [Some(0), None, None]);
+    }
+
+    #[tokio::test]
+    async fn tcp_only() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // Only one connection is ever accepted.
+            let (mut sock, _) = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                let len = sock.read_u16().await.unwrap();
+                let mut buf = vec![0; usize::from(len)];
+                sock.read_exact(&mut buf).await.unwrap();
+                let query = Message::from_octets(buf).unwrap();
+                assert!(query.opt().unwrap().opt().tcp_keepalive().is_some());
+                let answer = MessageBuilder::new_stream_vec()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap();
+                sock.write_all(answer.finish().as_stream_slice())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut conf = ResolvConf::new();
+        conf.servers
+            .push(ServerConf::new(addr, conf::Transport::Udp));
+        conf.servers
+            .push(ServerConf::new(addr, conf::Transport::Tcp));
+        conf.options.use_vc = true;
+        conf.options.stay_open = true;
+        conf.finalize();
+        let resolver = StubResolver::from_conf(conf);
+        resolver.connect().await.unwrap();
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(query_into(&resolver).await, Rcode::NoError);
+        server.await.unwrap();
+    }
+
+    /// A transport dropping responses larger than 512 octets.
+    struct FragmentingTransport(Arc<Mutex<Vec<u16>>>);
+
+    impl Transport for FragmentingTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            Box::pin(async move {
+                let query = request.message();
+                let size = query.opt().unwrap().udp_payload_size();
+                self.0.lock().unwrap().push(size);
+                if size > 512 {
+                    futures::future::pending::<()>().await;
+                }
+                Ok(MessageBuilder::new_bytes()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap()
+                    .into_message()
+                    .into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_payload_size() {
+        let mut conf = ResolvConf::new();
+        conf.servers.push(ServerConf::new(
+            "192.0.2.1:53".parse().unwrap(),
+            conf::Transport::Udp,
+        ));
+        conf.options.timeout = Duration::from_millis(20);
+        conf.options.attempts = 3;
+        conf.finalize();
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let resolver = StubResolver::from_conf_with_transport(
+            conf,
+            FragmentingTransport(sizes.clone()),
+        );
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(query(&resolver).await, Rcode::NoError);
+        assert_eq!(*sizes.lock().unwrap(), [1232, 1232, 512, 512]);
+    }
+}
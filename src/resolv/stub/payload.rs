@@ -0,0 +1,205 @@
+//! Probing for a safe EDNS UDP payload size.
+//!
+//! Large responses over UDP are fragmented by the IP layer and fragments
+//! are regularly dropped by middleboxes. Since the response is lost
+//! entirely in this case, all the client sees is a timeout. Following the
+//! recommendations of DNS Flag Day 2020, servers are configured with an
+//! advertised payload size of 1232 octets by default which avoids
+//! fragmentation on almost all paths.
+//!
+//! Where even that is too large, the resolver adapts the size for each
+//! server individually: If several requests to a server advertising more
+//! than the minimum size time out in a row, this is taken as a sign of
+//! fragmentation and the advertised size is lowered to the next smaller
+//! step. Since the timeouts may also have had other causes, the resolver
+//! later probes whether the next larger size works again by advertising
+//! it in a single request. If that request is answered, the larger size is
+//! used from then on. Otherwise, the next probe happens after the same
+//! interval. The size never exceeds the one given in the server’s
+//! configuration.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The payload sizes to step down to.
+///
+/// The smallest size is the one every DNS implementation has to support.
+const STEPS: [u16; 2] = [512, 1232];
+
+/// The number of timeouts in a row after which the size is lowered.
+const TIMEOUT_THRESHOLD: u8 = 2;
+
+/// The time after which a larger size is probed again.
+const PROBE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+//------------ PayloadSize ---------------------------------------------------
+
+/// The EDNS UDP payload size to advertise to a server.
+#[derive(Debug)]
+pub(super) struct PayloadSize {
+    /// The configured and thus largest size.
+    max: u16,
+
+    /// The mutable state.
+    state: Mutex<State>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct State {
+    /// The size currently used.
+    size: u16,
+
+    /// The number of timeouts with this size in a row.
+    timeouts: u8,
+
+    /// When to probe the next larger size if the size has been lowered.
+    probe_at: Option<Instant>,
+
+    /// Is a probe currently in flight?
+    probing: bool,
+}
+
+impl PayloadSize {
+    /// Creates a new value starting with the configured size.
+    pub fn new(max: u16) -> Self {
+        PayloadSize {
+            max,
+            state: Mutex::new(State {
+                size: max,
+                timeouts: 0,
+                probe_at: None,
+                probing: false,
+            }),
+        }
+    }
+
+    /// Returns the size currently used without probing.
+    #[cfg(test)]
+    pub fn current(&self) -> u16 {
+        self.state.lock().unwrap().size
+    }
+
+    /// Returns the size to advertise in a request sent at `now`.
+    ///
+    /// If a probe is due, returns the next larger size.
+    pub fn advertise(&self, now: Instant) -> u16 {
+        let mut state = self.state.lock().unwrap();
+        match state.probe_at {
+            Some(probe_at) if !state.probing && probe_at <= now => {
+                state.probing = true;
+                self.step_up(state.size)
+            }
+            _ => state.size,
+        }
+    }
+
+    /// Records that a request advertising `size` has been answered.
+    pub fn answered(&self, size: u16, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if size > state.size {
+            // A successful probe.
+            state.size = size;
+            state.probing = false;
+            state.probe_at = (size < self.max).then(|| now + PROBE_INTERVAL);
+        }
+        if size == state.size {
+            state.timeouts = 0;
+        }
+    }
+
+    /// Records that a request advertising `size` has timed out.
+    pub fn timed_out(&self, size: u16, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if size > state.size {
+            // A failed probe. Try again later.
+            state.probing = false;
+            state.probe_at = Some(now + PROBE_INTERVAL);
+        } else if size == state.size && size > STEPS[0] {
+            state.timeouts += 1;
+            if state.timeouts >= TIMEOUT_THRESHOLD {
+                state.size = Self::step_down(size);
+                state.timeouts = 0;
+                state.probe_at = Some(now + PROBE_INTERVAL);
+            }
+        }
+    }
+
+    /// Returns the next smaller size.
+    fn step_down(size: u16) -> u16 {
+        STEPS
+            .iter()
+            .rev()
+            .copied()
+            .find(|step| *step < size)
+            .unwrap_or(STEPS[0])
+    }
+
+    /// Returns the next larger size.
+    fn step_up(&self, size: u16) -> u16 {
+        STEPS
+            .iter()
+            .copied()
+            .find(|step| *step > size)
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_down_and_probe() {
+        let payload = PayloadSize::new(4096);
+        let now = Instant::now();
+
+        // Timeouts in a row lower the size, a response in between resets.
+        payload.timed_out(4096, now);
+        payload.answered(4096, now);
+        payload.timed_out(4096, now);
+        assert_eq!(payload.advertise(now), 4096);
+        payload.timed_out(4096, now);
+        assert_eq!(payload.advertise(now), 1232);
+        payload.timed_out(1232, now);
+        payload.timed_out(1232, now);
+        assert_eq!(payload.advertise(now), 512);
+
+        // Nothing goes below the minimum.
+        payload.timed_out(512, now);
+        payload.timed_out(512, now);
+        assert_eq!(payload.current(), 512);
+
+        // A failed probe keeps the size.
+        let later = now + PROBE_INTERVAL;
+        assert_eq!(payload.advertise(later), 1232);
+        assert_eq!(payload.advertise(later), 512);
+        payload.timed_out(1232, later);
+        assert_eq!(payload.advertise(later), 512);
+
+        // Successful probes step up to the configured size.
+        let later = later + PROBE_INTERVAL;
+        assert_eq!(payload.advertise(later), 1232);
+        payload.answered(1232, later);
+        assert_eq!(payload.current(), 1232);
+        assert_eq!(payload.advertise(later), 1232);
+        let later = later + PROBE_INTERVAL;
+        assert_eq!(payload.advertise(later), 4096);
+        payload.answered(4096, later);
+        assert_eq!(payload.advertise(later + PROBE_INTERVAL), 4096);
+    }
+
+    #[test]
+    fn small_configured_size() {
+        let payload = PayloadSize::new(1000);
+        let now = Instant::now();
+        payload.timed_out(1000, now);
+        payload.timed_out(1000, now);
+        assert_eq!(payload.current(), 512);
+        assert_eq!(payload.advertise(now + PROBE_INTERVAL), 1000);
+    }
+}
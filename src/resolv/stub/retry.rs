@@ -0,0 +1,374 @@
+//! Policies for retrying queries.
+//!
+//! The stub resolver sends a query to its servers in rounds. In each
+//! round, it tries every server once, moving on to the next server when a
+//! request times out, fails, or is answered with SERVFAIL. If none of the
+//! servers provide an answer, the resolver starts another round until it
+//! runs out of attempts.
+//!
+//! A [`RetryPolicy`] decides how many rounds there are, how long to wait
+//! for each response, and how long to pause before starting another round.
+//! By default, the resolver uses [`FixedRetry`] with the number of
+//! attempts given in its options which mimics the behaviour of the C
+//! library. [`ExponentialBackoff`] pauses for increasing, randomized
+//! periods between rounds. Its presets [`ExponentialBackoff::datacenter`]
+//! and [`ExponentialBackoff::mobile`] provide starting points for reliable
+//! and unreliable networks, respectively.
+//!
+//! A policy is attached to a resolver via
+//! [`StubResolver::with_retry_policy`]. The overall time limit given by
+//! the `lookup_timeout` option applies regardless of the policy.
+//!
+//! [`StubResolver::with_retry_policy`]: super::StubResolver::with_retry_policy
+
+use core::fmt;
+use rand::Rng;
+use std::time::Duration;
+
+//------------ RetryPolicy ---------------------------------------------------
+
+/// A policy for retrying queries.
+///
+/// Rounds are counted from zero, so the first round is `0` and the first
+/// retry is round `1`.
+pub trait RetryPolicy: fmt::Debug + Send + Sync {
+    /// Returns the number of rounds through the server list.
+    ///
+    /// A value of zero is treated as one.
+    fn attempts(&self) -> usize;
+
+    /// Returns how long to wait for a response to a request.
+    ///
+    /// The `timeout` is the request timeout configured for the server the
+    /// request is sent to. The default implementation returns it
+    /// unchanged.
+    fn request_timeout(&self, round: usize, timeout: Duration) -> Duration {
+        let _ = round;
+        timeout
+    }
+
+    /// Returns how long to pause before starting a round.
+    ///
+    /// This is only called for rounds after the first one. The default
+    /// implementation doesn’t pause at all.
+    fn backoff(&self, round: usize) -> Duration {
+        let _ = round;
+        Duration::ZERO
+    }
+
+    /// Returns whether later rounds start with a different server.
+    ///
+    /// If this returns `true`, each round starts one server further down
+    /// the server list than the previous one, so that a server that is
+    /// slow to answer doesn’t delay each round. This is in addition to the
+    /// `rotate` option which spreads queries over all servers. The default
+    /// implementation returns `false`.
+    fn rotate_rounds(&self) -> bool {
+        false
+    }
+}
+
+//------------ FixedRetry ----------------------------------------------------
+
+/// A policy retrying a fixed number of rounds without pausing.
+///
+/// This is the policy used by the stub resolver by default with the
+/// number of attempts taken from its options.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FixedRetry {
+    /// The number of rounds.
+    attempts: usize,
+}
+
+impl FixedRetry {
+    /// Creates a policy with the given number of rounds.
+    pub fn new(attempts: usize) -> Self {
+        FixedRetry { attempts }
+    }
+}
+
+impl RetryPolicy for FixedRetry {
+    fn attempts(&self) -> usize {
+        self.attempts
+    }
+}
+
+//------------ ExponentialBackoff --------------------------------------------
+
+/// A policy pausing for exponentially increasing times between rounds.
+///
+/// The pause before the second round is the initial backoff. Each further
+/// pause is the previous one multiplied by the factor, limited to the
+/// maximum backoff. With jitter, a random part of each pause is dropped,
+/// so that many clients failing at the same time don’t all retry at the
+/// same time, too. A jitter of `0.5`, for instance, makes each pause
+/// somewhere between half and all of its nominal value.
+///
+/// Optionally, the request timeout can grow with each round by the same
+/// factor up to a limit. This helps on links whose round-trip time is
+/// occasionally much longer than usual.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExponentialBackoff {
+    /// The number of rounds.
+    attempts: usize,
+
+    /// The pause before the second round.
+    initial_backoff: Duration,
+
+    /// The maximum pause between rounds.
+    max_backoff: Duration,
+
+    /// The factor applied to the pause for each round.
+    factor: u32,
+
+    /// The fraction of each pause that is randomized.
+    jitter: f64,
+
+    /// The limit for growing request timeouts if they grow.
+    timeout_limit: Option<Duration>,
+
+    /// Whether later rounds start with a different server.
+    rotate_rounds: bool,
+}
+
+impl ExponentialBackoff {
+    /// Creates a policy with the given number of rounds.
+    ///
+    /// The policy starts with an initial backoff of 100 ms, doubles it for
+    /// each round up to five seconds, and uses a jitter of 0.5. Request
+    /// timeouts don’t grow and rounds aren’t rotated.
+    pub fn new(attempts: usize) -> Self {
+        ExponentialBackoff {
+            attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            factor: 2,
+            jitter: 0.5,
+            timeout_limit: None,
+            rotate_rounds: false,
+        }
+    }
+
+    /// Creates a policy for reliable networks with fast servers.
+    ///
+    /// The policy makes three attempts with short pauses starting at 10 ms
+    /// and rotates rounds so that an overloaded server doesn’t slow down
+    /// every round. The servers should be configured with a short request
+    /// timeout.
+    pub fn datacenter() -> Self {
+        let mut res = Self::new(3);
+        res.set_initial_backoff(Duration::from_millis(10));
+        res.set_max_backoff(Duration::from_millis(200));
+        res.set_rotate_rounds(true);
+        res
+    }
+
+    /// Creates a policy for unreliable networks.
+    ///
+    /// The policy makes four attempts with pauses starting at 500 ms and
+    /// doubles the request timeout for each round up to ten seconds to
+    /// cope with links that are temporarily slow.
+    pub fn mobile() -> Self {
+        let mut res = Self::new(4);
+        res.set_initial_backoff(Duration::from_millis(500));
+        res.set_max_backoff(Duration::from_secs(8));
+        res.set_timeout_limit(Some(Duration::from_secs(10)));
+        res
+    }
+
+    /// Sets the number of rounds.
+    pub fn set_attempts(&mut self, attempts: usize) {
+        self.attempts = attempts
+    }
+
+    /// Returns the pause before the second round.
+    ///
+    /// The default is 100 ms.
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    /// Sets the pause before the second round.
+    pub fn set_initial_backoff(&mut self, backoff: Duration) {
+        self.initial_backoff = backoff
+    }
+
+    /// Returns the maximum pause between rounds.
+    ///
+    /// The default is five seconds.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Sets the maximum pause between rounds.
+    pub fn set_max_backoff(&mut self, backoff: Duration) {
+        self.max_backoff = backoff
+    }
+
+    /// Returns the factor applied to the pause for each round.
+    ///
+    /// The default is two.
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+
+    /// Sets the factor applied to the pause for each round.
+    ///
+    /// A factor of one results in equal pauses. A factor of zero is treated
+    /// as one.
+    pub fn set_factor(&mut self, factor: u32) {
+        self.factor = factor.max(1)
+    }
+
+    /// Returns the fraction of each pause that is randomized.
+    ///
+    /// The default is 0.5.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Sets the fraction of each pause that is randomized.
+    ///
+    /// The value is clamped to the range from zero to one. A value of zero
+    /// disables jitter, a value of one makes each pause anything between
+    /// zero and its nominal value.
+    pub fn set_jitter(&mut self, jitter: f64) {
+        self.jitter = if jitter.is_nan() {
+            0.
+        } else {
+            jitter.clamp(0., 1.)
+        }
+    }
+
+    /// Returns the limit for growing request timeouts.
+    ///
+    /// The default is `None`, i.e., request timeouts don’t grow.
+    pub fn timeout_limit(&self) -> Option<Duration> {
+        self.timeout_limit
+    }
+
+    /// Sets whether and up to which limit request timeouts grow.
+    ///
+    /// If `limit` is `None`, the request timeout configured for a server
+    /// is used in every round. Otherwise, the timeout is multiplied by the
+    /// factor for each round but never exceeds the limit or falls below the
+    /// configured timeout.
+    pub fn set_timeout_limit(&mut self, limit: Option<Duration>) {
+        self.timeout_limit = limit
+    }
+
+    /// Sets whether later rounds start with a different server.
+    pub fn set_rotate_rounds(&mut self, rotate: bool) {
+        self.rotate_rounds = rotate
+    }
+
+    /// Returns the nominal pause before a round, i.e., without jitter.
+    fn nominal_backoff(&self, round: usize) -> Duration {
+        if round == 0 {
+            return Duration::ZERO;
+        }
+        grow(self.initial_backoff, self.factor, round - 1)
+            .min(self.max_backoff)
+    }
+
+    /// Applies the jitter to a pause given a random value in `0..1`.
+    fn apply_jitter(&self, backoff: Duration, random: f64) -> Duration {
+        backoff.mul_f64(1. - self.jitter * random)
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    fn request_timeout(&self, round: usize, timeout: Duration) -> Duration {
+        match self.timeout_limit {
+            Some(limit) => {
+                grow(timeout, self.factor, round).min(limit.max(timeout))
+            }
+            None => timeout,
+        }
+    }
+
+    fn backoff(&self, round: usize) -> Duration {
+        self.apply_jitter(
+            self.nominal_backoff(round),
+            rand::thread_rng().gen::<f64>(),
+        )
+    }
+
+    fn rotate_rounds(&self) -> bool {
+        self.rotate_rounds
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Multiplies a duration by `factor` `times` times, saturating.
+fn grow(duration: Duration, factor: u32, times: usize) -> Duration {
+    let mut res = duration;
+    for _ in 0..times {
+        res = match res.checked_mul(factor) {
+            Some(res) => res,
+            None => return Duration::MAX,
+        };
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let mut policy = ExponentialBackoff::new(5);
+        policy.set_max_backoff(Duration::from_millis(500));
+        assert_eq!(policy.nominal_backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.nominal_backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.nominal_backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.nominal_backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.nominal_backoff(100), Duration::from_millis(500));
+
+        let backoff = Duration::from_millis(400);
+        assert_eq!(policy.apply_jitter(backoff, 0.), backoff);
+        assert_eq!(
+            policy.apply_jitter(backoff, 0.5),
+            Duration::from_millis(300)
+        );
+        for _ in 0..100 {
+            let backoff = policy.backoff(3);
+            assert!(backoff > Duration::from_millis(200));
+            assert!(backoff <= Duration::from_millis(400));
+        }
+        policy.set_jitter(0.);
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn request_timeout() {
+        let timeout = Duration::from_secs(2);
+        let policy = ExponentialBackoff::datacenter();
+        assert_eq!(policy.request_timeout(3, timeout), timeout);
+
+        let policy = ExponentialBackoff::mobile();
+        assert_eq!(policy.request_timeout(0, timeout), timeout);
+        assert_eq!(policy.request_timeout(1, timeout), timeout * 2);
+        assert_eq!(policy.request_timeout(2, timeout), timeout * 4);
+        assert_eq!(
+            policy.request_timeout(3, timeout),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            policy.request_timeout(70, timeout),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            policy.request_timeout(3, Duration::from_secs(12)),
+            Duration::from_secs(12)
+        );
+    }
+}
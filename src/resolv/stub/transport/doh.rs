@@ -0,0 +1,380 @@
+//! A transport for DNS-over-HTTPS.
+//!
+//! [RFC 8484] defines how DNS messages in wire format are exchanged with a
+//! server via HTTPS. The request message is sent either as the body of a
+//! POST request or base64url-encoded in the `dns` query parameter of a GET
+//! request, the response message is returned as the body of the HTTP
+//! response. Both use the media type `application/dns-message`.
+//!
+//! The [`DohTransport`] in this module implements the DNS part of this. It
+//! leaves the actual HTTP exchange to a user-supplied [`HttpClient`]. This
+//! makes it possible to use whatever HTTP implementation is already in use
+//! by an application. The trait is implemented for closures returning a
+//! future, so a client is often simply a function.
+//!
+//! Since the resolver’s futures are `Send` so they can be spawned on a
+//! multi-threaded Tokio runtime, the future returned by the client has to
+//! be `Send`, too. This rules out clients built on the browser’s fetch
+//! API. The resolver can’t currently be compiled to
+//! `wasm32-unknown-unknown` anyway as it relies on Tokio’s sockets and
+//! timers.
+//!
+//! As with all encrypted transports, the resolver pads queries sent to an
+//! HTTPS URL to a multiple of 128 octets as recommended by [RFC 8467].
+//!
+//! Since all requests go to the URL the transport was created with, the
+//! addresses in the resolver’s server configuration aren’t used. However,
+//! the resolver still needs at least one server to send requests to.
+//!
+//! [RFC 8467]: https://tools.ietf.org/html/rfc8467
+//! [RFC 8484]: https://tools.ietf.org/html/rfc8484
+
+use super::{Privacy, Request, Transport, TransportFuture};
+use crate::base::message::Message;
+use crate::resolv::stub::conf::ServerConf;
+use crate::utils::base64;
+use bytes::Bytes;
+use std::boxed::Box;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The media type of DNS messages in wire format.
+pub const MEDIA_TYPE: &str = "application/dns-message";
+
+//------------ HttpClient ----------------------------------------------------
+
+/// A type that can perform HTTP requests for a [`DohTransport`].
+///
+/// The client is given an [`HttpRequest`] and returns a future resolving
+/// into the body of the response. It should send the `Accept` header and,
+/// for POST requests, the `Content-Type` header with the value of
+/// [`MEDIA_TYPE`]. If the server responds with anything other than a
+/// successful status, the client should return an error.
+///
+/// The trait is implemented for all closures taking a request and
+/// returning a suitable future.
+pub trait HttpClient: Send + Sync {
+    /// Sends the request and returns a future resolving into the response.
+    fn send(&self, request: HttpRequest) -> HttpFuture;
+}
+
+impl<F, Fut> HttpClient for F
+where
+    F: Fn(HttpRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<u8>, io::Error>> + Send + 'static,
+{
+    fn send(&self, request: HttpRequest) -> HttpFuture {
+        Box::pin(self(request))
+    }
+}
+
+/// The future returned by [`HttpClient::send`].
+pub type HttpFuture =
+    Pin<Box<dyn Future<Output = Result<Vec<u8>, io::Error>> + Send>>;
+
+//------------ HttpRequest ---------------------------------------------------
+
+/// An HTTP request to be sent by an [`HttpClient`].
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    /// The HTTP method of the request.
+    method: HttpMethod,
+
+    /// The URL to send the request to.
+    url: String,
+
+    /// The body of the request.
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Returns the HTTP method to use.
+    pub fn method(&self) -> HttpMethod {
+        self.method
+    }
+
+    /// Returns the URL to send the request to.
+    ///
+    /// For GET requests, this includes the query parameter with the DNS
+    /// request.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the body of the request.
+    ///
+    /// The body is empty for GET requests.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Converts the request into its URL and body.
+    pub fn into_parts(self) -> (String, Vec<u8>) {
+        (self.url, self.body)
+    }
+}
+
+//------------ HttpMethod ----------------------------------------------------
+
+/// The HTTP method used for DNS-over-HTTPS requests.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum HttpMethod {
+    /// The request message is added to the URL.
+    ///
+    /// This may allow HTTP caches to answer the request.
+    Get,
+
+    /// The request message is sent as the body.
+    #[default]
+    Post,
+}
+
+//------------ DohTransport --------------------------------------------------
+
+/// A transport sending requests via DNS-over-HTTPS.
+///
+/// All requests are sent to the URL given when creating the transport via
+/// the [`HttpClient`]. As recommended by RFC 8484, the message ID of the
+/// request is set to zero to make responses cacheable. The ID of the
+/// response is changed back to the original ID before it is returned to
+/// the resolver.
+#[derive(Clone, Debug)]
+pub struct DohTransport<C> {
+    /// The URL of the server.
+    url: String,
+
+    /// The HTTP method to use.
+    method: HttpMethod,
+
+    /// The client for making the HTTP requests.
+    client: C,
+}
+
+impl<C> DohTransport<C> {
+    /// Creates a new transport for the given URL and HTTP client.
+    ///
+    /// The URL is the URI template of the server without any variables,
+    /// e.g., `https://dns.example.com/dns-query`. By default, the transport
+    /// uses POST requests.
+    pub fn new(url: impl Into<String>, client: C) -> Self {
+        DohTransport {
+            url: url.into(),
+            method: HttpMethod::default(),
+            client,
+        }
+    }
+
+    /// Returns the URL of the server.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the HTTP method used for requests.
+    pub fn method(&self) -> HttpMethod {
+        self.method
+    }
+
+    /// Sets the HTTP method used for requests.
+    pub fn set_method(&mut self, method: HttpMethod) {
+        self.method = method
+    }
+
+    /// Returns a reference to the HTTP client.
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Creates the HTTP request for a DNS request message.
+    fn http_request(&self, mut message: Vec<u8>) -> HttpRequest {
+        message[..2].copy_from_slice(&[0, 0]);
+        match self.method {
+            HttpMethod::Get => {
+                let mut url = self.url.clone();
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str("dns=");
+                for ch in base64::encode_string(&message).chars() {
+                    match ch {
+                        '+' => url.push('-'),
+                        '/' => url.push('_'),
+                        '=' => {}
+                        ch => url.push(ch),
+                    }
+                }
+                HttpRequest {
+                    method: HttpMethod::Get,
+                    url,
+                    body: Vec::new(),
+                }
+            }
+            HttpMethod::Post => HttpRequest {
+                method: HttpMethod::Post,
+                url: self.url.clone(),
+                body: message,
+            },
+        }
+    }
+}
+
+impl<C: HttpClient> Transport for DohTransport<C> {
+    fn request<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a> {
+        let id = request.message().header().id();
+        let response =
+            self.client.send(self.http_request(request.dgram().into()));
+        Box::pin(async move {
+            let mut response = response.await?;
+            if Message::from_slice(&response).is_err() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "short DNS-over-HTTPS response",
+                ));
+            }
+            response[..2].copy_from_slice(&id.to_be_bytes());
+            Ok(Bytes::from(response))
+        })
+    }
+
+    /// Returns the privacy offered for a server.
+    ///
+    /// For an HTTPS URL, the HTTP client is expected to validate the
+    /// server’s certificate for the host name of the URL, which
+    /// authenticates the server by name. Since the client can’t check SPKI
+    /// pins, servers configured with pins are only considered encrypted.
+    fn privacy(&self, server: &ServerConf) -> Privacy {
+        if !self.url.starts_with("https://") {
+            Privacy::Clear
+        } else if server
+            .auth
+            .as_ref()
+            .map(|auth| auth.spki_pins.is_empty())
+            .unwrap_or(true)
+        {
+            Privacy::Authenticated
+        } else {
+            Privacy::Encrypted
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::Padding;
+    use crate::resolv::stub::conf::ResolvConf;
+    use crate::resolv::StubResolver;
+    use std::str::FromStr;
+
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let query = Message::from_octets(query).unwrap();
+        assert_eq!(query.header().id(), 0);
+        MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap()
+            .finish()
+    }
+
+    #[tokio::test]
+    async fn post() {
+        let transport = DohTransport::new(
+            "https://dns.example.com/dns-query",
+            |request: HttpRequest| async move {
+                assert_eq!(request.method(), HttpMethod::Post);
+                assert_eq!(
+                    request.url(),
+                    "https://dns.example.com/dns-query"
+                );
+                Ok(answer(request.body()))
+            },
+        );
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport);
+        let answer = resolver
+            .query((
+                Dname::<Bytes>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .await
+            .unwrap();
+        assert!(answer.header().qr());
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+    }
+
+    #[tokio::test]
+    async fn padded() {
+        let transport = DohTransport::new(
+            "https://dns.example.com/dns-query",
+            |request: HttpRequest| async move {
+                assert_eq!(request.body().len() % 128, 0);
+                let query = Message::from_octets(request.body()).unwrap();
+                assert!(query
+                    .opt()
+                    .unwrap()
+                    .opt()
+                    .iter::<Padding<_>>()
+                    .next()
+                    .is_some());
+                Ok(answer(request.body()))
+            },
+        );
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport);
+        let answer = resolver
+            .query((
+                Dname::<Bytes>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+    }
+
+    #[test]
+    fn get() {
+        let mut transport =
+            DohTransport::new("https://dns.example.com/q?x=1", ());
+        transport.set_method(HttpMethod::Get);
+
+        let mut query = MessageBuilder::new_vec();
+        query.header_mut().set_id(4711);
+        let mut query = query.question();
+        query
+            .push((Dname::<Vec<u8>>::root_vec(), Rtype::Ns))
+            .unwrap();
+        let request = transport.http_request(query.finish());
+        assert_eq!(request.method(), HttpMethod::Get);
+        assert!(request.body().is_empty());
+        assert_eq!(
+            request.url(),
+            "https://dns.example.com/q?x=1&dns=AAAAAAABAAAAAAAAAAACAAE"
+        );
+    }
+
+    #[tokio::test]
+    async fn short_response() {
+        let transport =
+            DohTransport::new("https://dns.example.com/", |_| async move {
+                Ok(vec![1, 2, 3])
+            });
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport);
+        assert!(resolver
+            .query((Dname::<Bytes>::root_bytes(), Rtype::Ns))
+            .await
+            .is_err());
+    }
+}
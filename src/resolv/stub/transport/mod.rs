@@ -0,0 +1,865 @@
+//! Pluggable transports for the stub resolver.
+//!
+//! The stub resolver doesn’t talk to the network directly. Instead, it
+//! hands the wire-format request for each attempt to a value implementing
+//! the [`Transport`] trait and waits for the wire-format response to come
+//! back. By default, [`NetTransport`] is used which sends messages over
+//! UDP and TCP using Tokio’s sockets.
+//!
+//! If you need to send queries in some other way – through a proxy, a
+//! userspace network stack, or simply some canned responses for testing –
+//! you can implement [`Transport`] yourself and create a resolver via
+//! [`StubResolver::from_conf_with_transport`].
+//!
+//! For DNS-over-HTTPS, the [doh] module provides [`DohTransport`] which
+//! leaves the HTTP part to a client of your choice.
+//!
+//! Transports that encrypt their connections report the [`Privacy`] they
+//! offer for each server so that the resolver can apply its privacy
+//! profile.
+//!
+//! [`StubResolver::from_conf_with_transport`]: super::StubResolver::from_conf_with_transport
+
+pub use self::doh::DohTransport;
+pub use self::stats::{TransportStats, UpstreamStats};
+pub use self::tcp::{ConnectionStats, TcpPool};
+pub use self::udp::{RejectCounters, RejectStats, Rejection};
+
+use self::stats::UpstreamCounters;
+use super::conf::{ServerConf, Transport as Protocol};
+use crate::base::message::Message;
+use crate::utils::stream::MessageStream;
+use bytes::Bytes;
+use std::boxed::Box;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+//------------ Sub-modules ---------------------------------------------------
+
+pub mod doh;
+pub mod stats;
+pub mod tcp;
+pub mod udp;
+
+//------------ Module Configuration ------------------------------------------
+
+/// How many times do we try a new random port if we get ‘address in use.’
+const RETRY_RANDOM_PORT: usize = 10;
+
+//------------ Transport -----------------------------------------------------
+
+/// A type that can exchange DNS messages with an upstream server.
+///
+/// A transport receives a [`Request`] containing the wire-format request
+/// message as well as information about the server it should be sent to.
+/// It returns a future that resolves into the octets of the wire-format
+/// response message.
+///
+/// The transport does not need to enforce the request timeout – the
+/// resolver wraps the returned future into a timeout of its own. It also
+/// doesn’t necessarily need to check whether the response actually answers
+/// the request as the resolver will check this, too. However, since a
+/// mismatched response counts as a failed attempt, datagram transports
+/// should keep waiting for the right response instead.
+///
+/// Transports can additionally receive the response into a buffer provided
+/// by the caller via [`request_into`][Self::request_into]. The default
+/// implementation of this method copies the response returned by
+/// [`request`][Self::request], so transports that can receive directly into
+/// the buffer should override it.
+pub trait Transport: Send + Sync {
+    /// Sends a request and returns a future resolving into the response.
+    fn request<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a>;
+
+    /// Sends a request and receives the response into a buffer.
+    ///
+    /// The returned future resolves into the length of the response which
+    /// has been placed at the start of `buf`. If the response is longer
+    /// than `buf`, it resolves into an error.
+    fn request_into<'a>(
+        &'a self,
+        request: Request<'a>,
+        buf: &'a mut [u8],
+    ) -> TransportIntoFuture<'a> {
+        Box::pin(async move {
+            let response = self.request(request).await?;
+            copy_response(&response, buf)
+        })
+    }
+
+    /// Returns the privacy the transport offers for a server.
+    ///
+    /// The resolver uses this to pick servers according to its
+    /// [`PrivacyProfile`][super::conf::PrivacyProfile]. Transports that encrypt their connections
+    /// should authenticate servers as given by the server
+    /// configuration’s [`auth`][ServerConf::auth] field.
+    ///
+    /// The default implementation returns [`Privacy::Clear`].
+    fn privacy(&self, server: &ServerConf) -> Privacy {
+        let _ = server;
+        Privacy::Clear
+    }
+
+    /// Prepares for sending requests to a server ahead of time.
+    ///
+    /// Transports that keep connections open should open a connection to
+    /// the server so that later requests don’t have to wait for it. The
+    /// default implementation does nothing.
+    fn connect<'a>(&'a self, server: &'a ServerConf) -> ConnectFuture<'a> {
+        let _ = server;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn request<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a> {
+        (**self).request(request)
+    }
+
+    fn request_into<'a>(
+        &'a self,
+        request: Request<'a>,
+        buf: &'a mut [u8],
+    ) -> TransportIntoFuture<'a> {
+        (**self).request_into(request, buf)
+    }
+
+    fn privacy(&self, server: &ServerConf) -> Privacy {
+        (**self).privacy(server)
+    }
+
+    fn connect<'a>(&'a self, server: &'a ServerConf) -> ConnectFuture<'a> {
+        (**self).connect(server)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn request<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a> {
+        (**self).request(request)
+    }
+
+    fn request_into<'a>(
+        &'a self,
+        request: Request<'a>,
+        buf: &'a mut [u8],
+    ) -> TransportIntoFuture<'a> {
+        (**self).request_into(request, buf)
+    }
+
+    fn privacy(&self, server: &ServerConf) -> Privacy {
+        (**self).privacy(server)
+    }
+
+    fn connect<'a>(&'a self, server: &'a ServerConf) -> ConnectFuture<'a> {
+        (**self).connect(server)
+    }
+}
+
+/// The future returned by [`Transport::request`].
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Bytes, io::Error>> + Send + 'a>>;
+
+/// The future returned by [`Transport::request_into`].
+pub type TransportIntoFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<usize, io::Error>> + Send + 'a>>;
+
+/// The future returned by [`Transport::connect`].
+pub type ConnectFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), io::Error>> + Send + 'a>>;
+
+/// Copies a response into the start of a buffer and returns its length.
+fn copy_response(
+    response: &[u8],
+    buf: &mut [u8],
+) -> Result<usize, io::Error> {
+    buf.get_mut(..response.len())
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "response buffer too short")
+        })?
+        .copy_from_slice(response);
+    Ok(response.len())
+}
+
+//------------ Privacy -------------------------------------------------------
+
+/// The privacy a transport offers for exchanges with a server.
+///
+/// The variants are ordered from least to most private.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Privacy {
+    /// Messages are exchanged in the clear.
+    Clear,
+
+    /// Messages are encrypted but the server isn’t authenticated.
+    Encrypted,
+
+    /// Messages are encrypted and the server is authenticated.
+    Authenticated,
+}
+
+//------------ Request -------------------------------------------------------
+
+/// A request to be sent by a transport.
+///
+/// The request contains the wire-format message as well as the
+/// configuration of the server the message should be sent to. The message
+/// is available both in its plain form for use with datagram transports
+/// via [`dgram`][Self::dgram] and with the two octet length prefix used by
+/// stream transports via [`stream`][Self::stream].
+#[derive(Clone, Copy)]
+pub struct Request<'a> {
+    /// The message including the length prefix.
+    stream: &'a [u8],
+
+    /// The configuration of the server to send the message to.
+    server: &'a ServerConf,
+}
+
+impl<'a> Request<'a> {
+    /// Creates a new request from a stream message and a server.
+    ///
+    /// The `stream` slice must contain a DNS message preceded by its
+    /// length as a 16 bit unsigned integer in network byte order.
+    pub(super) fn new(stream: &'a [u8], server: &'a ServerConf) -> Self {
+        debug_assert!(stream.len() >= 2);
+        Request { stream, server }
+    }
+
+    /// Returns the message without length prefix.
+    pub fn dgram(&self) -> &'a [u8] {
+        &self.stream[2..]
+    }
+
+    /// Returns the message preceded by its 16 bit length.
+    pub fn stream(&self) -> &'a [u8] {
+        self.stream
+    }
+
+    /// Returns the request message.
+    pub fn message(&self) -> Message<&'a [u8]> {
+        Message::from_octets(self.dgram()).expect("short request message")
+    }
+
+    /// Returns the address of the server the request is for.
+    pub fn peer(&self) -> SocketAddr {
+        self.server.addr
+    }
+
+    /// Returns the transport protocol configured for the server.
+    pub fn protocol(&self) -> Protocol {
+        self.server.transport
+    }
+
+    /// Returns the complete configuration of the server.
+    pub fn server_conf(&self) -> &'a ServerConf {
+        self.server
+    }
+}
+
+impl<'a> fmt::Debug for Request<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("message", &self.message())
+            .field("peer", &self.peer())
+            .field("protocol", &self.protocol())
+            .finish()
+    }
+}
+
+//------------ NetTransport --------------------------------------------------
+
+/// The default transport sending requests over the network.
+///
+/// Depending on the protocol given in the server configuration, the
+/// transport sends each request via a UDP socket bound to a random port or
+/// via TCP. By default, a new TCP connection is established for each
+/// request. If the transport is created with a [`TcpPool`] via
+/// [`with_tcp_pool`][Self::with_tcp_pool], connections are kept open and
+/// shared by multiple requests instead. They can then be opened ahead of
+/// time via [`Transport::connect`].
+///
+/// Datagrams received on a UDP socket are only accepted as the response if
+/// they pass the checks described in the [udp] module. All other datagrams
+/// are dropped and counted. The counters can be retrieved via
+/// [`udp_rejected`][Self::udp_rejected]. Since clones of a transport share
+/// these counters, you can keep a clone of the transport given to a
+/// resolver for this purpose.
+///
+/// The transport also keeps statistics for each upstream server. These
+/// and all other statistics can be retrieved as a snapshot via
+/// [`stats`][Self::stats]. They, too, are shared by clones.
+#[derive(Clone, Debug, Default)]
+pub struct NetTransport {
+    /// The pool of persistent TCP connections if we use one.
+    tcp_pool: Option<TcpPool>,
+
+    /// The counters for rejected UDP datagrams.
+    udp_rejected: Arc<RejectCounters>,
+
+    /// The statistics for the upstream servers.
+    upstreams: Arc<UpstreamCounters>,
+}
+
+impl NetTransport {
+    /// Creates a new network transport.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new network transport using persistent TCP connections.
+    pub fn with_tcp_pool(pool: TcpPool) -> Self {
+        NetTransport {
+            tcp_pool: Some(pool),
+            udp_rejected: Default::default(),
+            upstreams: Default::default(),
+        }
+    }
+
+    /// Returns the number of UDP datagrams rejected so far.
+    pub fn udp_rejected(&self) -> RejectStats {
+        self.udp_rejected.snapshot()
+    }
+
+    /// Returns the statistics for each upstream server and protocol.
+    ///
+    /// The statistics are ordered by server address with UDP before TCP.
+    pub fn upstream_stats(&self) -> Vec<UpstreamStats> {
+        self.upstreams.snapshot()
+    }
+
+    /// Returns a snapshot of all statistics of the transport.
+    pub fn stats(&self) -> TransportStats {
+        TransportStats {
+            upstreams: self.upstream_stats(),
+            connections: self
+                .tcp_pool
+                .as_ref()
+                .map(TcpPool::connection_stats)
+                .unwrap_or_default(),
+            udp_rejected: self.udp_rejected(),
+        }
+    }
+
+    /// Returns the pool of TCP connections if persistent connections are used.
+    pub fn tcp_pool(&self) -> Option<&TcpPool> {
+        self.tcp_pool.as_ref()
+    }
+
+    /// Sends a request via a new TCP connection.
+    pub async fn tcp_request(
+        request: Request<'_>,
+    ) -> Result<Bytes, io::Error> {
+        let mut sock = TcpStream::connect(&request.peer()).await?;
+        sock.write_all(request.stream()).await?;
+
+        // This loop can be infinite because we have a timeout on this whole
+        // thing, anyway.
+        let query = request.message();
+        let mut stream = MessageStream::new(sock);
+        loop {
+            let buf = stream.next_message().await?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed",
+                )
+            })?;
+            if let Ok(answer) = Message::from_octets(buf) {
+                if answer.is_answer(&query) {
+                    return Ok(answer.into_octets());
+                }
+            // else try with the next message.
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "short buf",
+                ));
+            }
+        }
+    }
+
+    /// Sends a request via UDP.
+    ///
+    /// Waits until an acceptable response has been received.
+    pub async fn udp_request(
+        &self,
+        request: Request<'_>,
+    ) -> Result<Bytes, io::Error> {
+        let addr = request.peer();
+        let sock = Self::udp_bind(addr.is_ipv4()).await?;
+        sock.connect(addr).await?;
+        let sent = sock.send(request.dgram()).await?;
+        if sent != request.dgram().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "short UDP send",
+            ));
+        }
+        loop {
+            // XXX use uninit'ed mem here.
+            let mut buf = vec![0; request.server_conf().recv_size];
+            let (len, source) = sock.recv_from(&mut buf).await?;
+            buf.truncate(len);
+
+            // We ignore garbage since there is a timer on this whole thing.
+            match udp::accept_response(&request, source, Bytes::from(buf)) {
+                Ok(answer) => return Ok(answer.into_octets()),
+                Err(rejection) => self.udp_rejected.count(rejection),
+            }
+        }
+    }
+
+    /// Sends a request via a new TCP connection receiving into a buffer.
+    pub async fn tcp_request_into(
+        request: Request<'_>,
+        buf: &mut [u8],
+    ) -> Result<usize, io::Error> {
+        let mut sock = TcpStream::connect(&request.peer()).await?;
+        sock.write_all(request.stream()).await?;
+
+        let query = request.message();
+        loop {
+            let len = usize::from(sock.read_u16().await?);
+            let buf = buf.get_mut(..len).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    "response buffer too short",
+                )
+            })?;
+            sock.read_exact(buf).await?;
+            if let Ok(answer) = Message::from_octets(&*buf) {
+                if answer.is_answer(&query) {
+                    return Ok(len);
+                }
+            // else try with the next message.
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "short buf",
+                ));
+            }
+        }
+    }
+
+    /// Sends a request via UDP receiving into a buffer.
+    ///
+    /// Waits until an acceptable response has been received. Datagrams
+    /// longer than the buffer are truncated and thus rejected.
+    pub async fn udp_request_into(
+        &self,
+        request: Request<'_>,
+        buf: &mut [u8],
+    ) -> Result<usize, io::Error> {
+        let addr = request.peer();
+        let sock = Self::udp_bind(addr.is_ipv4()).await?;
+        sock.connect(addr).await?;
+        let sent = sock.send(request.dgram()).await?;
+        if sent != request.dgram().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "short UDP send",
+            ));
+        }
+        loop {
+            let (len, source) = sock.recv_from(buf).await?;
+            match udp::accept_response(&request, source, &buf[..len]) {
+                Ok(_) => return Ok(len),
+                Err(rejection) => self.udp_rejected.count(rejection),
+            }
+        }
+    }
+
+    async fn udp_bind(v4: bool) -> Result<UdpSocket, io::Error> {
+        let mut i = 0;
+        loop {
+            let local: SocketAddr = if v4 {
+                ([0u8; 4], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            match UdpSocket::bind(&local).await {
+                Ok(sock) => return Ok(sock),
+                Err(err) => {
+                    if i == RETRY_RANDOM_PORT {
+                        return Err(err);
+                    } else {
+                        i += 1
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Transport for NetTransport {
+    fn request<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let measurement = self.upstreams.start(&request);
+            let res = match request.protocol() {
+                Protocol::Udp => self.udp_request(request).await,
+                Protocol::Tcp => match self.tcp_pool.as_ref() {
+                    Some(pool) => pool.request(request).await,
+                    None => Self::tcp_request(request).await,
+                },
+            };
+            measurement.finish(&res);
+            res
+        })
+    }
+
+    fn request_into<'a>(
+        &'a self,
+        request: Request<'a>,
+        buf: &'a mut [u8],
+    ) -> TransportIntoFuture<'a> {
+        Box::pin(async move {
+            let measurement = self.upstreams.start(&request);
+            let res = match request.protocol() {
+                Protocol::Udp => self.udp_request_into(request, buf).await,
+                Protocol::Tcp => match self.tcp_pool.as_ref() {
+                    Some(pool) => pool
+                        .request(request)
+                        .await
+                        .and_then(|response| copy_response(&response, buf)),
+                    None => Self::tcp_request_into(request, buf).await,
+                },
+            };
+            measurement.finish(&res);
+            res
+        })
+    }
+
+    fn connect<'a>(&'a self, server: &'a ServerConf) -> ConnectFuture<'a> {
+        Box::pin(async move {
+            match (server.transport, self.tcp_pool.as_ref()) {
+                (Protocol::Tcp, Some(pool)) => pool.open(server.addr).await,
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{OptRcode, Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::cookie::{Cookie, ServerCookie};
+    use crate::base::Ttl;
+    use crate::rdata::A;
+    use crate::resolv::stub::cache::Cache;
+    use crate::resolv::stub::conf::ResolvConf;
+    use crate::resolv::StubResolver;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A transport that answers every request with an empty response.
+    #[derive(Default)]
+    struct EchoTransport {
+        requests: AtomicUsize,
+    }
+
+    impl Transport for EchoTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                let answer = MessageBuilder::new_bytes()
+                    .start_answer(&request.message(), Rcode::NoError)
+                    .unwrap();
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_transport() {
+        let transport = Arc::new(EchoTransport::default());
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone());
+        let answer = resolver
+            .query((
+                Dname::<Bytes>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .await
+            .unwrap();
+        assert!(answer.header().qr());
+        assert_eq!(answer.header_counts().ancount(), 0);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 1);
+    }
+
+    /// A transport that insists on a server cookie.
+    #[derive(Default)]
+    struct CookieTransport {
+        requests: AtomicUsize,
+    }
+
+    impl Transport for CookieTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                let query = request.message();
+                let cookie = query.opt().unwrap().opt().cookie().unwrap();
+                let mut answer = MessageBuilder::new_bytes()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap()
+                    .additional();
+                answer
+                    .opt(|opt| {
+                        if cookie.server().is_none() {
+                            opt.set_rcode(OptRcode::BadCookie);
+                        }
+                        opt.cookie(Cookie::new(
+                            cookie.client(),
+                            Some(ServerCookie::from_octets(b"servercookie")),
+                        ))
+                    })
+                    .unwrap();
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn cookies() {
+        let transport = Arc::new(CookieTransport::default());
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone());
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+
+        // The first query gets a BADCOOKIE and is retried with the server
+        // cookie learned from it.
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 2);
+
+        // Later queries include the server cookie right away.
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 3);
+
+        // The same goes for queries into buffers.
+        let (mut request, mut response) = ([0; 512], [0; 512]);
+        let answer = resolver
+            .query_into((&qname, Rtype::A), &mut request, &mut response)
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 4);
+    }
+
+    /// A transport that answers with SERVFAIL every other time.
+    #[derive(Default)]
+    struct ServfailTransport {
+        requests: AtomicUsize,
+    }
+
+    impl Transport for ServfailTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            let count = self.requests.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                let rcode = if count % 2 == 0 {
+                    Rcode::ServFail
+                } else {
+                    Rcode::NoError
+                };
+                let answer = MessageBuilder::new_bytes()
+                    .start_answer(&request.message(), rcode)
+                    .unwrap();
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn query_into() {
+        let transport = Arc::new(ServfailTransport::default());
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone());
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+        let (mut request, mut response) = ([0; 512], [0; 512]);
+
+        // The SERVFAIL answer is skipped in favour of the second answer.
+        let answer = resolver
+            .query_into((&qname, Rtype::A), &mut request, &mut response)
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(answer.first_question().unwrap().qname(), &qname);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 2);
+
+        // If there is no space left after the SERVFAIL answer, it is
+        // returned.
+        let len = answer.as_slice().len();
+        let answer = resolver
+            .query_into(
+                (&qname, Rtype::A),
+                &mut request,
+                &mut response[..len + 10],
+            )
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::ServFail);
+
+        // Buffers that are too short result in errors.
+        assert!(resolver
+            .query_into((&qname, Rtype::A), &mut request[..20], &mut response)
+            .await
+            .is_err());
+        assert!(resolver
+            .query_into((&qname, Rtype::A), &mut request, &mut response[..4])
+            .await
+            .is_err());
+    }
+
+    /// A transport that answers every request with an A record.
+    #[derive(Default)]
+    struct AddressTransport {
+        requests: AtomicUsize,
+
+        /// The TTL of the record.
+        ttl: u32,
+
+        /// Whether to answer with SERVFAIL instead.
+        servfail: AtomicBool,
+    }
+
+    impl AddressTransport {
+        fn new(ttl: u32) -> Self {
+            AddressTransport {
+                ttl,
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Transport for AddressTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                let query = request.message();
+                if self.servfail.load(Ordering::Relaxed) {
+                    let answer = MessageBuilder::new_bytes()
+                        .start_answer(&query, Rcode::ServFail)
+                        .unwrap();
+                    return Ok(answer.into_message().into_octets());
+                }
+                let qname = query.first_question().unwrap().into_qname();
+                let mut answer = MessageBuilder::new_bytes()
+                    .start_answer(&query, Rcode::NoError)
+                    .unwrap();
+                answer
+                    .push((qname, self.ttl, A::from_octets(192, 0, 2, 1)))
+                    .unwrap();
+                Ok(answer.into_message().into_octets())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let transport = Arc::new(AddressTransport::new(300));
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone())
+                .with_cache(Cache::new(16));
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+
+        // The second query is answered from the cache.
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(answer.header_counts().ancount(), 1);
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(answer.header_counts().ancount(), 1);
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 1);
+
+        // Other questions are not.
+        resolver.query((&qname, Rtype::Aaaa)).await.unwrap();
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 2);
+
+        // Neither are queries into buffers.
+        let (mut request, mut response) = ([0; 512], [0; 512]);
+        resolver
+            .query_into((&qname, Rtype::A), &mut request, &mut response)
+            .await
+            .unwrap();
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 3);
+
+        resolver.cache().unwrap().clear();
+        resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(transport.requests.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn serve_stale() {
+        // Answers with a TTL of zero are stale right away.
+        let transport = Arc::new(AddressTransport::new(0));
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let mut cache = Cache::new(16);
+        cache.set_stale_ttl(Ttl::HOUR);
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone())
+                .with_cache(cache);
+        let qname = Dname::<Bytes>::from_str("example.com").unwrap();
+        let requests = || transport.requests.load(Ordering::Relaxed);
+
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert!(!answer.is_stale());
+        assert_eq!(requests(), 1);
+
+        // Without a refresher, a fresh answer is tried first and the stale
+        // answer is served if that fails.
+        transport.servfail.store(true, Ordering::Relaxed);
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert!(answer.is_stale());
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        let tried = requests();
+        assert!(tried > 1);
+
+        // With a refresher, the stale answer is served right away and
+        // refreshed in the background.
+        let refresher = tokio::spawn({
+            let resolver = resolver.clone();
+            async move { resolver.refresh_cache().await }
+        });
+        while !resolver.cache().unwrap().is_refreshing() {
+            tokio::task::yield_now().await;
+        }
+        resolver.refresh_cache().await;
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert!(answer.is_stale());
+        assert_eq!(requests(), tried);
+        while requests() == tried {
+            tokio::task::yield_now().await;
+        }
+        refresher.abort();
+    }
+}
@@ -0,0 +1,254 @@
+//! Statistics on the exchange with upstream servers.
+//!
+//! [`NetTransport`][super::NetTransport] keeps track of how the upstream
+//! servers are doing: how many requests were sent to each of them, how
+//! many were answered, failed, or timed out, and how long the answers took
+//! on average. If persistent TCP connections are used, the pool also keeps
+//! statistics for each of its connections.
+//!
+//! All these statistics can be retrieved at once as a [`TransportStats`]
+//! snapshot via [`NetTransport::stats`][super::NetTransport::stats], which
+//! makes it easy to export them periodically without having to instrument
+//! the resolver.
+
+use super::tcp::ConnectionStats;
+use super::udp::RejectStats;
+use super::Request;
+use crate::resolv::stub::conf::Transport as Protocol;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ TransportStats ------------------------------------------------
+
+/// A snapshot of the statistics of a transport.
+#[derive(Clone, Debug, Default)]
+pub struct TransportStats {
+    /// The statistics for each upstream server and protocol.
+    pub upstreams: Vec<UpstreamStats>,
+
+    /// The statistics for each open persistent TCP connection.
+    ///
+    /// This is empty if the transport doesn’t use persistent connections.
+    pub connections: Vec<ConnectionStats>,
+
+    /// The number of rejected UDP datagrams.
+    pub udp_rejected: RejectStats,
+}
+
+//------------ UpstreamStats -------------------------------------------------
+
+/// The statistics for an upstream server reached via a protocol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UpstreamStats {
+    /// The address of the server.
+    pub addr: SocketAddr,
+
+    /// The protocol used to reach the server.
+    pub protocol: Protocol,
+
+    /// The number of requests sent to the server.
+    pub queries: u64,
+
+    /// The number of responses received from the server.
+    pub responses: u64,
+
+    /// The number of requests that failed with an error.
+    pub errors: u64,
+
+    /// The number of requests abandoned before a response arrived.
+    ///
+    /// Since the resolver drops requests once their timeout has expired,
+    /// these are usually timeouts.
+    pub timeouts: u64,
+
+    /// The sum of the round-trip times of all responses.
+    pub rtt_total: Duration,
+}
+
+impl UpstreamStats {
+    fn new(addr: SocketAddr, protocol: Protocol) -> Self {
+        UpstreamStats {
+            addr,
+            protocol,
+            queries: 0,
+            responses: 0,
+            errors: 0,
+            timeouts: 0,
+            rtt_total: Duration::ZERO,
+        }
+    }
+
+    /// Returns the average round-trip time of all responses.
+    ///
+    /// Returns `None` if no response has been received yet.
+    pub fn average_rtt(&self) -> Option<Duration> {
+        average(self.rtt_total, self.responses)
+    }
+}
+
+//------------ UpstreamCounters ----------------------------------------------
+
+/// The collected statistics for all upstream servers.
+#[derive(Debug, Default)]
+pub(super) struct UpstreamCounters {
+    upstreams: Mutex<HashMap<(SocketAddr, Protocol), UpstreamStats>>,
+}
+
+impl UpstreamCounters {
+    /// Starts measuring a request.
+    ///
+    /// The request counts as timed out unless the returned value is
+    /// finished before being dropped.
+    pub fn start(&self, request: &Request) -> Measurement<'_> {
+        let key = (request.peer(), request.protocol());
+        self.update(key, |stats| stats.queries += 1);
+        Measurement {
+            counters: self,
+            key,
+            sent: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Returns the current statistics ordered by address and protocol.
+    pub fn snapshot(&self) -> Vec<UpstreamStats> {
+        let mut res: Vec<_> =
+            self.upstreams.lock().unwrap().values().copied().collect();
+        res.sort_by_key(|stats| (stats.addr, stats.protocol.is_stream()));
+        res
+    }
+
+    fn update(
+        &self,
+        key: (SocketAddr, Protocol),
+        op: impl FnOnce(&mut UpstreamStats),
+    ) {
+        op(self
+            .upstreams
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| UpstreamStats::new(key.0, key.1)))
+    }
+}
+
+//------------ Measurement ---------------------------------------------------
+
+/// A request in flight being measured.
+pub(super) struct Measurement<'a> {
+    counters: &'a UpstreamCounters,
+    key: (SocketAddr, Protocol),
+    sent: Instant,
+    finished: bool,
+}
+
+impl<'a> Measurement<'a> {
+    /// Records the outcome of the request.
+    pub fn finish<T, E>(mut self, res: &Result<T, E>) {
+        self.finished = true;
+        let rtt = self.sent.elapsed();
+        self.counters.update(self.key, |stats| {
+            if res.is_ok() {
+                stats.responses += 1;
+                stats.rtt_total += rtt;
+            } else {
+                stats.errors += 1;
+            }
+        })
+    }
+}
+
+impl<'a> Drop for Measurement<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.counters.update(self.key, |stats| stats.timeouts += 1)
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the average of a total duration over a number of values.
+pub(super) fn average(total: Duration, count: u64) -> Option<Duration> {
+    if count == 0 {
+        return None;
+    }
+    let nanos = total.as_nanos() / u128::from(count);
+    Some(Duration::from_nanos(
+        u64::try_from(nanos).unwrap_or(u64::MAX),
+    ))
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::{NetTransport, Transport};
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::resolv::stub::conf::ServerConf;
+    use std::str::FromStr;
+    use tokio::net::UdpSocket;
+
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_stream_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish().into_target()
+    }
+
+    #[tokio::test]
+    async fn upstream_stats() {
+        // The server answers the first query only.
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut buf = [0; 512];
+            let (len, peer) = server.recv_from(&mut buf).await.unwrap();
+            let query = Message::from_octets(&buf[..len]).unwrap();
+            let answer = MessageBuilder::new_vec()
+                .start_answer(&query, Rcode::NoError)
+                .unwrap()
+                .finish();
+            server.send_to(&answer, peer).await.unwrap();
+            server
+        });
+
+        let transport = NetTransport::new();
+        let conf = ServerConf::new(addr, Protocol::Udp);
+        let first = query(1);
+        assert!(transport.request(Request::new(&first, &conf)).await.is_ok());
+        // Keep the socket open so the second query goes unanswered.
+        let _server = server.await.unwrap();
+        let second = query(2);
+        assert!(tokio::time::timeout(
+            Duration::from_millis(50),
+            transport.request(Request::new(&second, &conf))
+        )
+        .await
+        .is_err());
+
+        let stats = transport.stats();
+        assert!(stats.connections.is_empty());
+        assert_eq!(stats.upstreams.len(), 1);
+        let upstream = stats.upstreams[0];
+        assert_eq!(upstream.addr, addr);
+        assert_eq!(upstream.protocol, Protocol::Udp);
+        assert_eq!(upstream.queries, 2);
+        assert_eq!(upstream.responses, 1);
+        assert_eq!(upstream.errors, 0);
+        assert_eq!(upstream.timeouts, 1);
+        assert!(upstream.average_rtt().is_some());
+    }
+}
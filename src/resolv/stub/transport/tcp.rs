@@ -0,0 +1,1079 @@
+//! Persistent TCP connections.
+//!
+//! [RFC 7766] recommends that clients keep TCP connections to a server open
+//! for a while so that they can be reused for later queries and that
+//! multiple queries are sent over a connection without waiting for the
+//! responses to earlier ones – so called pipelining. Since servers may
+//! process these queries concurrently, responses may arrive in any order
+//! and have to be matched to their queries via the message ID.
+//!
+//! This module provides [`TcpPool`], a set of such connections that can be
+//! used by [`NetTransport`][super::NetTransport] for all requests to servers
+//! configured with a stream transport.
+//!
+//! Connections are closed once they have been idle for a certain time. This
+//! time can be changed by the server via the edns-tcp-keepalive option
+//! defined in [RFC 7828].
+//!
+//! The number of connections to each server is limited. Once all of them
+//! are busy or have stopped answering, further requests wait for capacity
+//! to become available rather than opening ever more connections.
+//!
+//! Statistics for each open connection can be retrieved via
+//! [`TcpPool::connection_stats`].
+//!
+//! [RFC 7766]: https://tools.ietf.org/html/rfc7766
+//! [RFC 7828]: https://tools.ietf.org/html/rfc7828
+
+use super::stats::average;
+use super::Request;
+use crate::base::message::Message;
+use crate::utils::stream::MessageStream;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::future::{select, Either};
+use futures::lock::Mutex as AsyncMutex;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{fmt, io, mem};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The default maximum number of in-flight queries per connection.
+pub const DEFAULT_MAX_INFLIGHT: usize = 32;
+
+/// The default maximum number of connections per server.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 4;
+
+/// The default time an idle connection is kept open.
+///
+/// This is used unless the server tells us otherwise via the
+/// edns-tcp-keepalive option.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default time after which a connection without responses is stalled.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+//------------ TcpPool -------------------------------------------------------
+
+/// A pool of persistent, pipelining TCP connections.
+///
+/// The pool keeps a set of connections to each server it has been asked to
+/// send requests to. When a new request arrives, an existing connection is
+/// reused if it still has capacity for another in-flight query, i.e., if
+/// fewer than [`max_inflight`][Self::max_inflight] requests are currently
+/// waiting for a response on it. Otherwise a new connection is opened
+/// unless there already are [`max_connections`][Self::max_connections]
+/// connections to the server. In this case, the request waits until one
+/// of the connections has capacity again.
+///
+/// A connection that has requests waiting for a response but hasn’t
+/// received anything for the [`stall_timeout`][Self::stall_timeout] is
+/// considered stalled and doesn’t receive any new requests until a
+/// response arrives. If a request is abandoned while its connection is
+/// stalled, the connection is closed. Together, this provides
+/// back-pressure: requests pile up in front of the pool rather than on a
+/// connection that doesn’t make progress.
+///
+/// Connections can be opened ahead of time via [`open`][Self::open].
+///
+/// Values can be cloned cheaply. All clones share the same connections.
+#[derive(Clone, Debug)]
+pub struct TcpPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    /// The maximum number of in-flight queries per connection.
+    max_inflight: usize,
+
+    /// The idle timeout to use if the server doesn’t provide one.
+    idle_timeout: Duration,
+
+    /// The maximum number of connections per server.
+    max_connections: usize,
+
+    /// The time without responses after which a connection is stalled.
+    stall_timeout: Duration,
+
+    /// The connections for each server address.
+    servers: Mutex<HashMap<SocketAddr, Server>>,
+
+    /// The requests waiting for capacity.
+    waiters: Arc<Waiters>,
+}
+
+/// The connections to a single server.
+#[derive(Debug, Default)]
+struct Server {
+    /// The open connections.
+    connections: Vec<Arc<Connection>>,
+
+    /// The number of connections currently being established.
+    connecting: usize,
+}
+
+impl TcpPool {
+    /// Creates a new pool using the default limits.
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_MAX_INFLIGHT, DEFAULT_IDLE_TIMEOUT)
+    }
+
+    /// Creates a new pool with the given limits.
+    ///
+    /// At most `max_inflight` queries will be outstanding on a single
+    /// connection at any time. A connection will be closed once it has been
+    /// idle for `idle_timeout` unless the server has requested a different
+    /// timeout via the edns-tcp-keepalive option.
+    pub fn with_limits(max_inflight: usize, idle_timeout: Duration) -> Self {
+        TcpPool {
+            inner: Arc::new(PoolInner {
+                max_inflight: max_inflight.max(1),
+                idle_timeout,
+                max_connections: DEFAULT_MAX_CONNECTIONS,
+                stall_timeout: DEFAULT_STALL_TIMEOUT,
+                servers: Default::default(),
+                waiters: Default::default(),
+            }),
+        }
+    }
+
+    /// Sets the maximum number of connections to a single server.
+    ///
+    /// A value of zero is treated as one. This is meant to be used when
+    /// creating the pool. A pool that has been cloned already is replaced
+    /// by a new pool with the same limits.
+    pub fn with_max_connections(self, max_connections: usize) -> Self {
+        self.modify(|inner| inner.max_connections = max_connections.max(1))
+    }
+
+    /// Sets the time without responses after which a connection is stalled.
+    ///
+    /// This is meant to be used when creating the pool. A pool that has
+    /// been cloned already is replaced by a new pool with the same limits.
+    pub fn with_stall_timeout(self, stall_timeout: Duration) -> Self {
+        self.modify(|inner| inner.stall_timeout = stall_timeout)
+    }
+
+    /// Modifies the limits of the pool.
+    fn modify(mut self, op: impl FnOnce(&mut PoolInner)) -> Self {
+        if Arc::get_mut(&mut self.inner).is_none() {
+            self.inner = Arc::new(PoolInner {
+                max_inflight: self.inner.max_inflight,
+                idle_timeout: self.inner.idle_timeout,
+                max_connections: self.inner.max_connections,
+                stall_timeout: self.inner.stall_timeout,
+                servers: Default::default(),
+                waiters: Default::default(),
+            })
+        }
+        op(Arc::get_mut(&mut self.inner).expect("shared pool"));
+        self
+    }
+
+    /// Returns the maximum number of in-flight queries per connection.
+    pub fn max_inflight(&self) -> usize {
+        self.inner.max_inflight
+    }
+
+    /// Returns the default idle timeout for connections.
+    pub fn idle_timeout(&self) -> Duration {
+        self.inner.idle_timeout
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.inner.max_connections
+    }
+
+    pub fn stall_timeout(&self) -> Duration {
+        self.inner.stall_timeout
+    }
+
+    /// Returns the statistics of all open connections.
+    ///
+    /// The connections are ordered by the address of their server.
+    pub fn connection_stats(&self) -> Vec<ConnectionStats> {
+        let now = Instant::now();
+        let servers = self.inner.servers.lock().unwrap();
+        let mut res: Vec<_> = servers
+            .iter()
+            .flat_map(|(addr, server)| {
+                server
+                    .connections
+                    .iter()
+                    .filter(|conn| conn.is_usable(now))
+                    .map(|conn| conn.stats(*addr, now))
+            })
+            .collect();
+        res.sort_by_key(|stats| stats.peer);
+        res
+    }
+
+    /// Opens a connection to a server ahead of time.
+    ///
+    /// Nothing happens if there already is an open connection to the
+    /// server or if the maximum number of connections has been reached.
+    /// The connection uses the pool’s idle timeout until the server
+    /// advertises its own via the response to the first request.
+    pub async fn open(&self, addr: SocketAddr) -> Result<(), io::Error> {
+        let reservation = {
+            let now = Instant::now();
+            let mut servers = self.inner.servers.lock().unwrap();
+            let server = servers.entry(addr).or_default();
+            server.connections.retain(|conn| conn.is_usable(now));
+            if !server.connections.is_empty() {
+                return Ok(());
+            }
+            match self.reserve(addr, server) {
+                Some(reservation) => reservation,
+                None => return Ok(()),
+            }
+        };
+        let conn = self.establish(addr).await?;
+        self.add(addr, conn, reservation);
+        Ok(())
+    }
+
+    /// Sends a request over a pooled connection.
+    ///
+    /// If the request fails on a connection that has been used before, the
+    /// request is retried once on a new connection since the server may
+    /// have closed the old one in the meantime.
+    ///
+    /// If all connections to the server are busy or stalled and no new
+    /// connection can be opened, waits until capacity becomes available.
+    pub async fn request(
+        &self,
+        request: Request<'_>,
+    ) -> Result<Bytes, io::Error> {
+        let id = request.message().header().id();
+        let addr = request.peer();
+        let mut reuse = true;
+        loop {
+            let epoch = self.inner.waiters.epoch();
+            match self.register(addr, id, reuse) {
+                Slot::Existing(pending) => {
+                    if let Ok(response) = pending.request(request).await {
+                        return Ok(response);
+                    }
+                    reuse = false;
+                }
+                Slot::New(reservation) => {
+                    let conn = self.establish(addr).await?;
+                    let pending = Connection::register(
+                        &conn,
+                        id,
+                        self.inner.max_inflight,
+                        Instant::now(),
+                    )
+                    .expect("cannot register with new connection");
+                    self.add(addr, conn, reservation);
+                    return pending.request(request).await;
+                }
+                Slot::Full => {
+                    if let Some(rx) = self.inner.waiters.wait(epoch) {
+                        // An error only means the pool is gone.
+                        let _ = rx.await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Registers a request with a connection or reserves a new one.
+    ///
+    /// Existing connections are only considered if `reuse` is `true`.
+    fn register(&self, addr: SocketAddr, id: u16, reuse: bool) -> Slot<'_> {
+        let now = Instant::now();
+        let mut servers = self.inner.servers.lock().unwrap();
+        let server = servers.entry(addr).or_default();
+        server.connections.retain(|conn| conn.is_usable(now));
+        if reuse {
+            if let Some(pending) =
+                server.connections.iter().find_map(|conn| {
+                    Connection::register(
+                        conn,
+                        id,
+                        self.inner.max_inflight,
+                        now,
+                    )
+                })
+            {
+                return Slot::Existing(pending);
+            }
+        }
+        match self.reserve(addr, server) {
+            Some(reservation) => Slot::New(reservation),
+            None => Slot::Full,
+        }
+    }
+
+    /// Reserves a new connection to a server if the limit allows it.
+    fn reserve(
+        &self,
+        addr: SocketAddr,
+        server: &mut Server,
+    ) -> Option<Reservation<'_>> {
+        if server.connections.len() + server.connecting
+            >= self.inner.max_connections
+        {
+            return None;
+        }
+        server.connecting += 1;
+        Some(Reservation {
+            pool: &self.inner,
+            addr,
+        })
+    }
+
+    /// Establishes a new connection to a server.
+    async fn establish(
+        &self,
+        addr: SocketAddr,
+    ) -> Result<Arc<Connection>, io::Error> {
+        Ok(Arc::new(Connection::new(
+            TcpStream::connect(&addr).await?,
+            self.inner.idle_timeout,
+            self.inner.stall_timeout,
+            self.inner.waiters.clone(),
+        )))
+    }
+
+    /// Adds a new connection to the pool, redeeming its reservation.
+    fn add(
+        &self,
+        addr: SocketAddr,
+        conn: Arc<Connection>,
+        reservation: Reservation,
+    ) {
+        self.inner
+            .servers
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_default()
+            .connections
+            .push(conn);
+        drop(reservation);
+    }
+}
+
+impl Default for TcpPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ Slot ----------------------------------------------------------
+
+/// Where a request can be sent.
+enum Slot<'a> {
+    /// The request has been registered with an existing connection.
+    Existing(Pending),
+
+    /// A new connection may be opened for the request.
+    New(Reservation<'a>),
+
+    /// There is no capacity at the moment.
+    Full,
+}
+
+//------------ Reservation ---------------------------------------------------
+
+/// A new connection being established.
+///
+/// While the value exists, the connection counts towards the limit of
+/// connections of its server.
+struct Reservation<'a> {
+    pool: &'a PoolInner,
+    addr: SocketAddr,
+}
+
+impl<'a> Drop for Reservation<'a> {
+    fn drop(&mut self) {
+        if let Some(server) =
+            self.pool.servers.lock().unwrap().get_mut(&self.addr)
+        {
+            server.connecting -= 1;
+        }
+        self.pool.waiters.release();
+    }
+}
+
+//------------ Waiters -------------------------------------------------------
+
+/// The requests waiting for capacity to become available.
+///
+/// Whenever capacity may have become available, all waiting requests are
+/// woken up and try again. To avoid missing a release that happens between
+/// checking for capacity and starting to wait, each release advances an
+/// epoch. A request only waits if the epoch hasn’t changed since it
+/// started checking.
+#[derive(Debug, Default)]
+struct Waiters {
+    state: Mutex<WaitersState>,
+}
+
+#[derive(Debug, Default)]
+struct WaitersState {
+    /// The number of releases so far.
+    epoch: u64,
+
+    /// The senders for waking up the waiting requests.
+    waiting: Vec<oneshot::Sender<()>>,
+}
+
+impl Waiters {
+    /// Returns the current epoch.
+    fn epoch(&self) -> u64 {
+        self.state.lock().unwrap().epoch
+    }
+
+    /// Starts waiting if there has been no release since `epoch`.
+    fn wait(&self, epoch: u64) -> Option<oneshot::Receiver<()>> {
+        let mut state = self.state.lock().unwrap();
+        if state.epoch != epoch {
+            return None;
+        }
+        let (tx, rx) = oneshot::channel();
+        state.waiting.push(tx);
+        Some(rx)
+    }
+
+    /// Wakes up all waiting requests.
+    fn release(&self) {
+        let waiting = {
+            let mut state = self.state.lock().unwrap();
+            state.epoch = state.epoch.wrapping_add(1);
+            mem::take(&mut state.waiting)
+        };
+        for tx in waiting {
+            let _ = tx.send(());
+        }
+    }
+}
+
+//------------ Connection ----------------------------------------------------
+
+/// A single TCP connection.
+///
+/// Since we don’t want to require spawning tasks, there is no dedicated
+/// task reading from the socket. Instead, whichever request manages to grab
+/// the read half reads messages and hands them to the request they belong
+/// to until it has received its own response.
+struct Connection {
+    /// The write half of the socket.
+    write: AsyncMutex<OwnedWriteHalf>,
+
+    /// The read half of the socket.
+    read: AsyncMutex<MessageStream<OwnedReadHalf>>,
+
+    /// The state shared between all requests.
+    state: Mutex<ConnState>,
+
+    /// The time without responses after which the connection is stalled.
+    stall_timeout: Duration,
+
+    /// The requests of the pool waiting for capacity.
+    waiters: Arc<Waiters>,
+}
+
+/// The mutable state of a connection.
+struct ConnState {
+    /// The requests waiting for a response by their message IDs.
+    pending: HashMap<u16, oneshot::Sender<Bytes>>,
+
+    /// Has the connection been closed?
+    closed: bool,
+
+    /// How long the connection may stay idle.
+    idle_timeout: Duration,
+
+    /// When the connection was last used.
+    last_used: Instant,
+
+    /// When the connection last made progress.
+    ///
+    /// This is when the last message was received or, if there were no
+    /// pending requests, when the next request was registered.
+    last_progress: Instant,
+
+    /// When the connection was opened.
+    opened: Instant,
+
+    /// The idle timeout advertised by the server if it did.
+    keepalive: Option<Duration>,
+
+    /// The number of requests sent over the connection.
+    queries: u64,
+
+    /// The number of responses received for these requests.
+    responses: u64,
+
+    /// The number of requests abandoned before their response arrived.
+    timeouts: u64,
+
+    /// The sum of the round-trip times of all responses.
+    rtt_total: Duration,
+}
+
+impl ConnState {
+    /// Returns whether the connection is stalled at `now`.
+    fn is_stalled(&self, now: Instant, stall_timeout: Duration) -> bool {
+        !self.pending.is_empty()
+            && now.saturating_duration_since(self.last_progress)
+                >= stall_timeout
+    }
+}
+
+impl Connection {
+    fn new(
+        sock: TcpStream,
+        idle_timeout: Duration,
+        stall_timeout: Duration,
+        waiters: Arc<Waiters>,
+    ) -> Self {
+        let (read, write) = sock.into_split();
+        let now = Instant::now();
+        Connection {
+            write: AsyncMutex::new(write),
+            read: AsyncMutex::new(MessageStream::new(read)),
+            state: Mutex::new(ConnState {
+                pending: HashMap::new(),
+                closed: false,
+                idle_timeout,
+                last_used: now,
+                last_progress: now,
+                opened: now,
+                keepalive: None,
+                queries: 0,
+                responses: 0,
+                timeouts: 0,
+                rtt_total: Duration::ZERO,
+            }),
+            stall_timeout,
+            waiters,
+        }
+    }
+
+    /// Returns whether the connection can still be used at `now`.
+    fn is_usable(&self, now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.closed
+            && (!state.pending.is_empty()
+                || now.saturating_duration_since(state.last_used)
+                    < state.idle_timeout)
+    }
+
+    /// Registers a request with message ID `id`.
+    ///
+    /// Returns `None` if the connection has been closed, if there already is
+    /// a request with this ID, if there are too many requests already, or
+    /// if the connection is stalled.
+    fn register(
+        conn: &Arc<Self>,
+        id: u16,
+        max_inflight: usize,
+        now: Instant,
+    ) -> Option<Pending> {
+        let mut state = conn.state.lock().unwrap();
+        if state.closed
+            || state.pending.len() >= max_inflight
+            || state.pending.contains_key(&id)
+            || state.is_stalled(now, conn.stall_timeout)
+        {
+            return None;
+        }
+        if state.pending.is_empty() {
+            state.last_progress = now;
+        }
+        let (tx, rx) = oneshot::channel();
+        state.pending.insert(id, tx);
+        state.queries += 1;
+        Some(Pending {
+            conn: conn.clone(),
+            id,
+            rx,
+            sent: now,
+        })
+    }
+
+    /// Returns the statistics of the connection to `peer`.
+    fn stats(&self, peer: SocketAddr, now: Instant) -> ConnectionStats {
+        let state = self.state.lock().unwrap();
+        ConnectionStats {
+            peer,
+            age: now.saturating_duration_since(state.opened),
+            queries: state.queries,
+            responses: state.responses,
+            timeouts: state.timeouts,
+            in_flight: state.pending.len(),
+            rtt_total: state.rtt_total,
+            keepalive: state.keepalive,
+            idle_timeout: state.idle_timeout,
+            stalled: state.is_stalled(now, self.stall_timeout),
+        }
+    }
+
+    /// Closes the connection.
+    ///
+    /// This drops all the senders of pending requests, which will cause
+    /// these requests to fail.
+    fn close(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            state.pending.clear();
+        }
+        self.waiters.release();
+    }
+
+    /// Dispatches a received message.
+    ///
+    /// Returns the message if it is the response for the request with the
+    /// ID `own`. Otherwise hands the message to the request it belongs to
+    /// or drops it if there is no such request.
+    fn dispatch(&self, msg: Bytes, own: u16) -> Option<Bytes> {
+        let msg = Message::from_octets(msg).ok()?;
+        let keepalive = msg
+            .opt()
+            .and_then(|opt| opt.opt().tcp_keepalive())
+            .and_then(|opt| opt.timeout());
+
+        let res = {
+            let mut state = self.state.lock().unwrap();
+            state.last_used = Instant::now();
+            state.last_progress = state.last_used;
+            if let Some(timeout) = keepalive {
+                state.idle_timeout = timeout.into();
+                state.keepalive = Some(timeout.into());
+            }
+            let id = msg.header().id();
+            if id == own {
+                state.pending.remove(&id);
+                Some(msg.into_octets())
+            } else {
+                if let Some(tx) = state.pending.remove(&id) {
+                    // If the receiver is gone, we don’t care.
+                    let _ = tx.send(msg.into_octets());
+                }
+                None
+            }
+        };
+
+        // The connection may have been stalled before.
+        self.waiters.release();
+        res
+    }
+}
+
+//------------ ConnectionStats -----------------------------------------------
+
+/// The statistics of a persistent TCP connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConnectionStats {
+    /// The address of the server.
+    pub peer: SocketAddr,
+
+    /// How long the connection has been open.
+    pub age: Duration,
+
+    /// The number of requests sent over the connection.
+    pub queries: u64,
+
+    /// The number of responses received over the connection.
+    pub responses: u64,
+
+    /// The number of requests abandoned before a response arrived.
+    ///
+    /// Since the resolver drops requests once their timeout has expired,
+    /// these are usually timeouts.
+    pub timeouts: u64,
+
+    /// The number of requests currently waiting for a response.
+    pub in_flight: usize,
+
+    /// The sum of the round-trip times of all responses.
+    pub rtt_total: Duration,
+
+    /// The idle timeout advertised by the server via edns-tcp-keepalive.
+    pub keepalive: Option<Duration>,
+
+    /// The idle timeout currently used for the connection.
+    pub idle_timeout: Duration,
+
+    /// Whether the connection is currently stalled.
+    ///
+    /// A connection is stalled if requests are waiting for a response but
+    /// nothing has been received for a while.
+    pub stalled: bool,
+}
+
+impl ConnectionStats {
+    /// Returns how often the connection has been reused.
+    ///
+    /// This is the number of requests sent after the first one.
+    pub fn reused(&self) -> u64 {
+        self.queries.saturating_sub(1)
+    }
+
+    /// Returns the average round-trip time of all responses.
+    ///
+    /// Returns `None` if no response has been received yet.
+    pub fn average_rtt(&self) -> Option<Duration> {
+        average(self.rtt_total, self.responses)
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Connection")
+            .field("pending", &state.pending.len())
+            .field("closed", &state.closed)
+            .field("idle_timeout", &state.idle_timeout)
+            .field("last_used", &state.last_used)
+            .finish()
+    }
+}
+
+//------------ Pending -------------------------------------------------------
+
+/// A request registered with a connection.
+///
+/// Dropping the value removes the request from the connection.
+struct Pending {
+    /// The connection the request is registered with.
+    conn: Arc<Connection>,
+
+    /// The message ID of the request.
+    id: u16,
+
+    /// The receiver for the response if someone else read it.
+    rx: oneshot::Receiver<Bytes>,
+
+    /// When the request was registered.
+    sent: Instant,
+}
+
+impl Pending {
+    /// Sends the request and waits for its response.
+    async fn request(
+        mut self,
+        request: Request<'_>,
+    ) -> Result<Bytes, io::Error> {
+        let res = self.exchange(request).await;
+        if res.is_ok() {
+            let mut state = self.conn.state.lock().unwrap();
+            state.responses += 1;
+            state.rtt_total += self.sent.elapsed();
+        }
+        res
+    }
+
+    /// Sends the request and receives the response.
+    async fn exchange(
+        &mut self,
+        request: Request<'_>,
+    ) -> Result<Bytes, io::Error> {
+        self.send(request).await?;
+
+        // Wait until either someone else hands us our response or we get
+        // to read ourselves.
+        let mut read = match select(&mut self.rx, self.conn.read.lock()).await
+        {
+            Either::Left((res, _)) => return res.map_err(|_| closed_err()),
+            Either::Right((read, _)) => read,
+        };
+
+        // Someone may have read our response while we were waiting for the
+        // read half.
+        match self.rx.try_recv() {
+            Ok(Some(response)) => return Ok(response),
+            Ok(None) => {}
+            Err(_) => return Err(closed_err()),
+        }
+
+        loop {
+            match read.next_message().await {
+                Ok(Some(msg)) => {
+                    if let Some(msg) = self.conn.dispatch(msg, self.id) {
+                        return Ok(msg);
+                    }
+                }
+                Ok(None) => {
+                    self.conn.close();
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "connection closed",
+                    ));
+                }
+                Err(err) => {
+                    self.conn.close();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Writes the request to the socket.
+    async fn send(&self, request: Request<'_>) -> Result<(), io::Error> {
+        let mut write = self.conn.write.lock().await;
+
+        // If we are cancelled half-way through writing, the stream is
+        // broken. The guard will then close the connection.
+        let guard = CloseGuard(Some(&self.conn));
+        let res = write.write_all(request.stream()).await;
+        guard.disarm();
+        if res.is_err() {
+            self.conn.close();
+        }
+        res
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        {
+            let mut state = self.conn.state.lock().unwrap();
+            // Only remove the entry if it is still ours. If our response has
+            // been dispatched already, a new request may have reused the ID.
+            if state
+                .pending
+                .get(&self.id)
+                .map(|tx| tx.is_connected_to(&self.rx))
+                .unwrap_or(false)
+            {
+                // An abandoned request on a stalled connection suggests
+                // that the connection is dead, so we rather close it.
+                let now = Instant::now();
+                if state.is_stalled(now, self.conn.stall_timeout) {
+                    state.closed = true;
+                    state.pending.clear();
+                } else {
+                    state.pending.remove(&self.id);
+                }
+                state.timeouts += 1;
+            }
+            state.last_used = Instant::now();
+        }
+        self.conn.waiters.release();
+    }
+}
+
+//------------ CloseGuard ----------------------------------------------------
+
+/// Closes a connection when dropped unless disarmed.
+struct CloseGuard<'a>(Option<&'a Connection>);
+
+impl<'a> CloseGuard<'a> {
+    fn disarm(mut self) {
+        self.0 = None
+    }
+}
+
+impl<'a> Drop for CloseGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.0 {
+            conn.close()
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+fn closed_err() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::keepalive::IdleTimeout;
+    use crate::resolv::stub::conf::{ServerConf, Transport};
+    use std::boxed::Box;
+    use std::str::FromStr;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn query(id: u16, qname: &str) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_stream_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((Dname::<Vec<u8>>::from_str(qname).unwrap(), Rtype::A))
+            .unwrap();
+        msg.finish().into_target()
+    }
+
+    async fn read_query(sock: &mut TcpStream) -> Message<Vec<u8>> {
+        let len = sock.read_u16().await.unwrap();
+        let mut buf = vec![0; usize::from(len)];
+        sock.read_exact(&mut buf).await.unwrap();
+        Message::from_octets(buf).unwrap()
+    }
+
+    async fn write_answer(sock: &mut TcpStream, query: &Message<Vec<u8>>) {
+        let mut answer = MessageBuilder::new_stream_vec()
+            .start_answer(query, Rcode::NoError)
+            .unwrap()
+            .additional();
+        answer
+            .opt(|opt| opt.tcp_keepalive(Some(IdleTimeout::from(600))))
+            .unwrap();
+        sock.write_all(answer.finish().as_stream_slice())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn pipelined_out_of_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let first = read_query(&mut sock).await;
+            write_answer(&mut sock, &first).await;
+            let second = read_query(&mut sock).await;
+            let third = read_query(&mut sock).await;
+            write_answer(&mut sock, &third).await;
+            write_answer(&mut sock, &second).await;
+        });
+
+        let pool = TcpPool::new();
+        let conf = ServerConf::new(addr, Transport::Tcp);
+
+        let one = query(1, "one.example");
+        let one = pool.request(Request::new(&one, &conf)).await.unwrap();
+        assert_eq!(Message::from_octets(one).unwrap().header().id(), 1);
+
+        // The connection is reused for both queries which are answered in
+        // reverse order.
+        let (two, three) =
+            (query(2, "two.example"), query(3, "three.example"));
+        let (two, three) = futures::join!(
+            pool.request(Request::new(&two, &conf)),
+            pool.request(Request::new(&three, &conf)),
+        );
+        assert_eq!(
+            Message::from_octets(two.unwrap()).unwrap().header().id(),
+            2
+        );
+        assert_eq!(
+            Message::from_octets(three.unwrap()).unwrap().header().id(),
+            3
+        );
+
+        // There is only one connection and it has picked up the keepalive
+        // value.
+        {
+            let servers = pool.inner.servers.lock().unwrap();
+            let list = &servers.get(&addr).unwrap().connections;
+            assert_eq!(list.len(), 1);
+            assert_eq!(
+                list[0].state.lock().unwrap().idle_timeout,
+                Duration::from_secs(60)
+            );
+        }
+        let stats = pool.connection_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].peer, addr);
+        assert_eq!(stats[0].queries, 3);
+        assert_eq!(stats[0].responses, 3);
+        assert_eq!(stats[0].reused(), 2);
+        assert_eq!(stats[0].timeouts, 0);
+        assert_eq!(stats[0].in_flight, 0);
+        assert_eq!(stats[0].keepalive, Some(Duration::from_secs(60)));
+        assert!(stats[0].average_rtt().is_some());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn back_pressure() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // Only one connection is ever accepted.
+            let (mut sock, _) = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                let query = read_query(&mut sock).await;
+                write_answer(&mut sock, &query).await;
+            }
+        });
+
+        let pool = TcpPool::with_limits(1, DEFAULT_IDLE_TIMEOUT)
+            .with_max_connections(1);
+        let conf = ServerConf::new(addr, Transport::Tcp);
+        pool.open(addr).await.unwrap();
+        assert_eq!(pool.connection_stats().len(), 1);
+
+        // The second request has to wait for the first one.
+        let (one, two) = (query(1, "one.example"), query(2, "two.example"));
+        let (one, two) =
+            tokio::time::timeout(Duration::from_secs(5), async {
+                futures::join!(
+                    pool.request(Request::new(&one, &conf)),
+                    pool.request(Request::new(&two, &conf)),
+                )
+            })
+            .await
+            .unwrap();
+        assert!(one.is_ok());
+        assert!(two.is_ok());
+        let stats = pool.connection_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].queries, 2);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stalled_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // The first connection never answers.
+            let (mut stalled, _) = listener.accept().await.unwrap();
+            read_query(&mut stalled).await;
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let query = read_query(&mut sock).await;
+            write_answer(&mut sock, &query).await;
+            stalled
+        });
+
+        let pool = TcpPool::new()
+            .with_max_connections(2)
+            .with_stall_timeout(Duration::from_millis(50));
+        let conf = ServerConf::new(addr, Transport::Tcp);
+        let one = query(1, "one.example");
+        let mut one = Box::pin(pool.request(Request::new(&one, &conf)));
+        assert!(tokio::time::timeout(Duration::from_millis(100), &mut one)
+            .await
+            .is_err());
+        let stats = pool.connection_stats();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].stalled);
+
+        // A new request goes to a new connection.
+        let two = query(2, "two.example");
+        assert!(pool.request(Request::new(&two, &conf)).await.is_ok());
+        assert_eq!(pool.connection_stats().len(), 2);
+
+        // Abandoning the request closes the stalled connection.
+        drop(one);
+        let stats = pool.connection_stats();
+        assert_eq!(stats.len(), 1);
+        assert!(!stats[0].stalled);
+        drop(server.await.unwrap());
+    }
+}
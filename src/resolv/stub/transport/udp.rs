@@ -0,0 +1,309 @@
+//! Acceptance of UDP responses.
+//!
+//! Since UDP is connectionless, anyone who can send packets to the port a
+//! query was sent from can try to inject a forged response. In order to
+//! make this harder, a datagram received on the socket of a query is only
+//! accepted as the response if it comes from the address and port the
+//! query was sent to, if it is a well-formed response, and if it has the
+//! same message ID and question as the query. Since servers that can’t
+//! parse a query may not be able to echo its question, a FORMERR or NOTIMP
+//! response with an empty question section is accepted as well.
+//!
+//! Any datagram that fails these checks is discarded and the transport
+//! keeps waiting for the real response. The reason for each rejection is
+//! counted in [`RejectCounters`] so that spoofing attempts or broken
+//! servers can be noticed.
+
+use super::Request;
+use crate::base::iana::Rcode;
+use crate::base::message::Message;
+use octseq::octets::Octets;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+//------------ accept_response -----------------------------------------------
+
+/// Checks whether a datagram is an acceptable response to a request.
+///
+/// The datagram `response` has been received from `source`. If it passes
+/// all checks, it is returned as a message. Otherwise, the reason for
+/// rejecting it is returned.
+pub fn accept_response<Octs: Octets>(
+    request: &Request,
+    source: SocketAddr,
+    response: Octs,
+) -> Result<Message<Octs>, Rejection> {
+    if source != request.peer() {
+        return Err(Rejection::Source);
+    }
+    let response =
+        Message::from_octets(response).map_err(|_| Rejection::Format)?;
+    let query = request.message();
+    if !response.header().qr() {
+        return Err(Rejection::NotResponse);
+    }
+    if response.header().id() != query.header().id() {
+        return Err(Rejection::Id);
+    }
+    if response.header_counts().qdcount() == 0
+        && matches!(response.header().rcode(), Rcode::FormErr | Rcode::NotImp)
+    {
+        return Ok(response);
+    }
+    if response.header_counts().qdcount() != query.header_counts().qdcount()
+        || response.question() != query.question()
+    {
+        return Err(Rejection::Question);
+    }
+    Ok(response)
+}
+
+//------------ Rejection -----------------------------------------------------
+
+/// The reason a datagram was not accepted as a response.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Rejection {
+    /// The datagram came from a different address or port.
+    Source,
+
+    /// The datagram is too short to be a DNS message.
+    Format,
+
+    /// The datagram is not a response, i.e., the QR bit is not set.
+    NotResponse,
+
+    /// The message ID differs from that of the query.
+    Id,
+
+    /// The question section differs from that of the query.
+    Question,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Rejection::Source => "unexpected source address",
+            Rejection::Format => "malformed message",
+            Rejection::NotResponse => "not a response",
+            Rejection::Id => "message ID mismatch",
+            Rejection::Question => "question mismatch",
+        })
+    }
+}
+
+//------------ RejectCounters ------------------------------------------------
+
+/// Counters for rejected datagrams.
+///
+/// There is one counter for each possible [`Rejection`]. The current values
+/// can be retrieved via [`snapshot`][Self::snapshot].
+#[derive(Debug, Default)]
+pub struct RejectCounters {
+    source: AtomicU64,
+    format: AtomicU64,
+    not_response: AtomicU64,
+    id: AtomicU64,
+    question: AtomicU64,
+}
+
+impl RejectCounters {
+    /// Creates a new set of counters all set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increases the counter for the given reason by one.
+    pub fn count(&self, rejection: Rejection) {
+        let counter = match rejection {
+            Rejection::Source => &self.source,
+            Rejection::Format => &self.format,
+            Rejection::NotResponse => &self.not_response,
+            Rejection::Id => &self.id,
+            Rejection::Question => &self.question,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> RejectStats {
+        RejectStats {
+            source: self.source.load(Ordering::Relaxed),
+            format: self.format.load(Ordering::Relaxed),
+            not_response: self.not_response.load(Ordering::Relaxed),
+            id: self.id.load(Ordering::Relaxed),
+            question: self.question.load(Ordering::Relaxed),
+        }
+    }
+}
+
+//------------ RejectStats ---------------------------------------------------
+
+/// The number of datagrams rejected for each reason.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RejectStats {
+    /// Datagrams from an unexpected address or port.
+    pub source: u64,
+
+    /// Datagrams too short to be a DNS message.
+    pub format: u64,
+
+    /// Datagrams that weren’t responses.
+    pub not_response: u64,
+
+    /// Responses with the wrong message ID.
+    pub id: u64,
+
+    /// Responses with the wrong question.
+    pub question: u64,
+}
+
+impl RejectStats {
+    /// Returns the total number of rejected datagrams.
+    pub fn total(&self) -> u64 {
+        self.source
+            + self.format
+            + self.not_response
+            + self.id
+            + self.question
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::resolv::stub::conf::{ServerConf, Transport};
+    use bytes::Bytes;
+    use std::str::FromStr;
+    use std::vec::Vec;
+
+    fn query(id: u16, qname: &str) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_stream_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((Dname::<Vec<u8>>::from_str(qname).unwrap(), Rtype::A))
+            .unwrap();
+        msg.finish().into_target()
+    }
+
+    fn answer(query: &[u8]) -> Bytes {
+        MessageBuilder::new_bytes()
+            .start_answer(
+                &Message::from_octets(&query[2..]).unwrap(),
+                Rcode::NoError,
+            )
+            .unwrap()
+            .into_message()
+            .into_octets()
+    }
+
+    #[test]
+    fn accept() {
+        let peer = SocketAddr::from(([192, 0, 2, 1], 53));
+        let conf = ServerConf::new(peer, Transport::Udp);
+        let msg = query(0x1234, "example.com");
+        let request = Request::new(&msg, &conf);
+
+        assert!(accept_response(&request, peer, answer(&msg)).is_ok());
+        assert_eq!(
+            accept_response(
+                &request,
+                SocketAddr::from(([192, 0, 2, 1], 5353)),
+                answer(&msg)
+            )
+            .unwrap_err(),
+            Rejection::Source
+        );
+        assert_eq!(
+            accept_response(&request, peer, Bytes::from_static(b"\x12\x34"))
+                .unwrap_err(),
+            Rejection::Format
+        );
+        assert_eq!(
+            accept_response(
+                &request,
+                peer,
+                Bytes::copy_from_slice(&msg[2..])
+            )
+            .unwrap_err(),
+            Rejection::NotResponse
+        );
+        assert_eq!(
+            accept_response(&request, peer, answer(&query(1, "example.com")))
+                .unwrap_err(),
+            Rejection::Id
+        );
+        assert_eq!(
+            accept_response(
+                &request,
+                peer,
+                answer(&query(0x1234, "example.org"))
+            )
+            .unwrap_err(),
+            Rejection::Question
+        );
+
+        // Names in the question are compared case-insensitively.
+        assert!(accept_response(
+            &request,
+            peer,
+            answer(&query(0x1234, "ExAmple.COM"))
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn accept_error_without_question() {
+        let peer = SocketAddr::from(([192, 0, 2, 1], 53));
+        let conf = ServerConf::new(peer, Transport::Udp);
+        let msg = query(0x1234, "example.com");
+        let request = Request::new(&msg, &conf);
+        let error = |id, rcode| {
+            let mut msg = MessageBuilder::new_bytes();
+            msg.header_mut().set_id(id);
+            msg.header_mut().set_qr(true);
+            msg.header_mut().set_rcode(rcode);
+            msg.into_message().into_octets()
+        };
+
+        assert!(accept_response(
+            &request,
+            peer,
+            error(0x1234, Rcode::FormErr)
+        )
+        .is_ok());
+        assert!(accept_response(
+            &request,
+            peer,
+            error(0x1234, Rcode::NotImp)
+        )
+        .is_ok());
+        assert_eq!(
+            accept_response(&request, peer, error(0x1234, Rcode::ServFail))
+                .unwrap_err(),
+            Rejection::Question
+        );
+        assert_eq!(
+            accept_response(&request, peer, error(1, Rcode::FormErr))
+                .unwrap_err(),
+            Rejection::Id
+        );
+    }
+
+    #[test]
+    fn counters() {
+        let counters = RejectCounters::new();
+        counters.count(Rejection::Id);
+        counters.count(Rejection::Id);
+        counters.count(Rejection::Source);
+        let stats = counters.snapshot();
+        assert_eq!(stats.id, 2);
+        assert_eq!(stats.source, 1);
+        assert_eq!(stats.total(), 3);
+    }
+}
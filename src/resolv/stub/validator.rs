@@ -0,0 +1,1219 @@
+//! DNSSEC validation of answers.
+//!
+//! Normally, a stub resolver trusts the upstream server to validate the
+//! answers it receives. If that isn’t good enough, the resolver can
+//! validate answers itself. This is enabled by handing a [`Validator`]
+//! with the trust anchors to start from to the resolver via
+//! [`StubResolver::with_validator`].
+//!
+//! A validating resolver sets the DO bit in all its queries so that the
+//! servers include the RRSIG records and the NSEC or NSEC3 records needed
+//! for proving negative answers. It fetches the DNSKEY and DS records
+//! needed for following the chain of trust down from the trust anchors
+//! and keeps them in a cache for as long as their TTLs allow. The outcome
+//! of validation is available for each answer via
+//! [`Answer::security_status`]. In strict mode, bogus answers are turned
+//! into errors instead.
+//!
+//! The NSEC and NSEC3 records of validated negative answers are kept, too,
+//! and used to answer queries for other names they prove not to exist
+//! without asking upstream as described in RFC 8198. This can be disabled
+//! via [`Validator::set_aggressive_nsec`].
+//!
+//! If the `checking_disabled` option is set, the resolver sets the CD bit
+//! in its queries and skips validation.
+//!
+//! [`StubResolver::with_validator`]: super::StubResolver::with_validator
+//! [`Answer::security_status`]: super::Answer::security_status
+#![cfg(feature = "validate")]
+#![cfg_attr(docsrs, doc(cfg(feature = "validate")))]
+
+use super::cache::Cache;
+use super::denial::{DenialCache, SignedRrset};
+use super::{Answer, Query, StubResolver};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::{Message, RecordSection};
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::question::Question;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::base::wire::ParseError;
+use crate::crypto::Backend;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::rdata::ZoneRecordData;
+use crate::validate::chain::{
+    ChainValidator, Reason, SecurityStatus, TrustAnchors,
+};
+use crate::validate::denial::{Denial, DenialError, Nsec3Denial, NsecDenial};
+use bytes::Bytes;
+use octseq::octets::Octets;
+use std::boxed::Box;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+use std::vec::Vec;
+use std::{fmt, io};
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+
+//------------ Validator -----------------------------------------------------
+
+/// The DNSSEC validator of a stub resolver.
+///
+/// The validator holds the trust anchors, a cache of the DNSKEY and DS
+/// answers it has fetched, and the NSEC and NSEC3 records of validated
+/// negative answers. It is shared by all clones of the resolver it is
+/// attached to.
+pub struct Validator {
+    /// The trust anchors to start validation from.
+    anchors: TrustAnchors,
+
+    /// Whether bogus answers are turned into errors.
+    strict: bool,
+
+    /// The cached answers for DNSKEY and DS queries.
+    cache: Cache,
+
+    /// Whether negative answers are synthesized from cached denials.
+    aggressive_nsec: bool,
+
+    /// The cached NSEC and NSEC3 records of validated negative answers.
+    denials: DenialCache,
+
+    /// The backend for cryptographic operations.
+    backend: Box<dyn Backend + Send + Sync>,
+}
+
+impl Validator {
+    /// The maximum number of answers kept in the cache.
+    const CACHE_CAPACITY: usize = 1024;
+
+    /// The maximum number of NSEC and NSEC3 records kept.
+    const DENIAL_CAPACITY: usize = 4096;
+
+    /// Creates a new validator using the given trust anchors.
+    ///
+    /// The validator uses the [`RingBackend`], is not in strict mode, and
+    /// synthesizes negative answers from cached NSEC and NSEC3 records.
+    #[cfg(feature = "ring")]
+    pub fn new(anchors: TrustAnchors) -> Self {
+        Self::with_backend(anchors, RingBackend)
+    }
+
+    /// Creates a new validator using the given crypto backend.
+    pub fn with_backend(
+        anchors: TrustAnchors,
+        backend: impl Backend + Send + Sync + 'static,
+    ) -> Self {
+        Validator {
+            anchors,
+            strict: false,
+            cache: Cache::new(Self::CACHE_CAPACITY),
+            aggressive_nsec: true,
+            denials: DenialCache::new(Self::DENIAL_CAPACITY),
+            backend: Box::new(backend),
+        }
+    }
+
+    /// Sets whether the validator is in strict mode.
+    ///
+    /// In strict mode, queries that result in a bogus answer fail with an
+    /// error.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets whether negative answers are synthesized from cached records.
+    ///
+    /// If enabled, queries for names or types proven not to exist by the
+    /// NSEC or NSEC3 records of earlier validated answers are answered
+    /// without asking upstream as described in [RFC 8198]. Answers relying
+    /// on opt-out NSEC3 records are never synthesized. This is enabled by
+    /// default.
+    ///
+    /// [RFC 8198]: https://tools.ietf.org/html/rfc8198
+    pub fn set_aggressive_nsec(&mut self, aggressive_nsec: bool) {
+        self.aggressive_nsec = aggressive_nsec
+    }
+
+    pub fn is_aggressive_nsec(&self) -> bool {
+        self.aggressive_nsec
+    }
+
+    pub fn anchors(&self) -> &TrustAnchors {
+        &self.anchors
+    }
+
+    /// Removes all answers and NSEC and NSEC3 records from the caches.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+        self.denials.clear()
+    }
+
+    /// Synthesizes a negative answer to a query from cached records.
+    ///
+    /// The answer is secure since it was built from validated records.
+    pub(super) fn synthesize<Octs: Octets + ?Sized>(
+        &self,
+        query: &Message<Octs>,
+    ) -> Option<Answer> {
+        if !self.aggressive_nsec {
+            return None;
+        }
+        let question = query.first_question()?;
+        if self.anchors.is_negative(question.qname(), Serial::now()) {
+            return None;
+        }
+        let message =
+            self.denials
+                .synthesize(query, &*self.backend, Instant::now())?;
+        let mut answer = Answer::from(message);
+        answer.security = Some(SecurityStatus::Secure);
+        Some(answer)
+    }
+
+    /// Validates an answer and attaches the resulting status.
+    ///
+    /// Answers other than NOERROR and NXDOMAIN are left alone.
+    pub(super) async fn check(
+        &self,
+        resolver: &StubResolver,
+        mut answer: Answer,
+    ) -> Result<Answer, io::Error> {
+        if !matches!(
+            answer.header().rcode(),
+            Rcode::NoError | Rcode::NXDomain
+        ) {
+            return Ok(answer);
+        }
+        let status = self.validate(resolver, &answer, Serial::now()).await;
+        if self.strict {
+            if let SecurityStatus::Bogus(reason) = status {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bogus answer: {}", reason),
+                ));
+            }
+        }
+        answer.security = Some(status);
+        Ok(answer)
+    }
+
+    /// Determines the security status of an answer.
+    async fn validate(
+        &self,
+        resolver: &StubResolver,
+        answer: &Answer,
+        now: Serial,
+    ) -> SecurityStatus {
+        let question = match answer.first_question() {
+            Some(question) => question,
+            None => return SecurityStatus::Bogus(Reason::Unavailable),
+        };
+        if self.anchors.is_negative(question.qname(), now) {
+            return SecurityStatus::Insecure(Reason::NegativeTrustAnchor);
+        }
+        let (mut answers, mut authority) =
+            match (rrsets(answer.answer()), rrsets(answer.authority())) {
+                (Ok(answers), Ok(authority)) => (answers, authority),
+                _ => return SecurityStatus::Bogus(Reason::Unavailable),
+            };
+
+        // Follow the CNAME chain to the name the final answer is for.
+        let qtype = question.qtype();
+        let mut target = question.qname().to_vec();
+        if qtype != Rtype::Cname {
+            while let Some(cname) = answers.iter().find_map(|rrset| {
+                match rrset.records.first().map(Record::data) {
+                    Some(ZoneRecordData::Cname(cname))
+                        if rrset.owner().name_eq(&target) =>
+                    {
+                        Some(cname.cname().to_vec())
+                    }
+                    _ => None,
+                }
+            }) {
+                if cname.name_eq(&target) {
+                    break;
+                }
+                target = cname;
+            }
+        }
+
+        let mut status = SecurityStatus::Secure;
+        for rrset in &mut answers {
+            let rrset_status = self
+                .rrset_status(resolver, rrset, &mut authority, now)
+                .await;
+            status = worse(status, rrset_status);
+        }
+
+        let negative = answer.header().rcode() == Rcode::NXDomain
+            || !answers.iter().any(|rrset| {
+                rrset.owner().name_eq(&target)
+                    && (rrset.rtype() == qtype || qtype == Rtype::Any)
+            });
+        if negative {
+            let check = if answer.header().rcode() == Rcode::NXDomain {
+                Check::NxDomain(&target)
+            } else {
+                Check::NoData(&target, qtype)
+            };
+            let denial_status = self
+                .negative_status(
+                    resolver,
+                    &target,
+                    &mut authority,
+                    check,
+                    now,
+                )
+                .await;
+            status = worse(status, denial_status);
+            if self.aggressive_nsec && status.is_secure() {
+                self.learn(&authority);
+            }
+        }
+        status
+    }
+
+    /// Keeps the NSEC and NSEC3 records of a secure negative answer.
+    fn learn(&self, authority: &[Rrset]) {
+        let rrsets: Vec<_> = authority
+            .iter()
+            .filter(|rrset| {
+                matches!(
+                    rrset.rtype(),
+                    Rtype::Soa | Rtype::Nsec | Rtype::Nsec3
+                )
+            })
+            .filter_map(Rrset::flatten)
+            .collect();
+        self.denials.insert(&rrsets, Instant::now())
+    }
+
+    /// Determines the security status of an RRset of the answer section.
+    async fn rrset_status(
+        &self,
+        resolver: &StubResolver,
+        rrset: &mut Rrset,
+        authority: &mut [Rrset],
+        now: Serial,
+    ) -> SecurityStatus {
+        let signer = match rrset.signer() {
+            Some(signer) => signer,
+            None => {
+                return self
+                    .unsigned_status(resolver, rrset.owner().to_vec(), now)
+                    .await
+            }
+        };
+        let chain = match self.chain_to(resolver, &signer, now).await {
+            Ok(chain) => chain,
+            Err(status) => return status,
+        };
+        let status = chain.validate_rrset(&mut rrset.records, &rrset.rrsigs);
+        if !status.is_secure() {
+            return status;
+        }
+
+        // An answer expanded from a wildcard needs proof that the name
+        // itself doesn’t exist.
+        let owner = rrset.owner().to_vec();
+        match rrset.rrsigs.first().map(Rrsig::labels) {
+            Some(labels) if labels < owner.rrsig_label_count() => {
+                denial_status(
+                    &chain,
+                    &signer,
+                    authority,
+                    Check::Wildcard(&owner, labels),
+                )
+            }
+            _ => status,
+        }
+    }
+
+    /// Determines the security status of a negative answer.
+    async fn negative_status(
+        &self,
+        resolver: &StubResolver,
+        target: &Dname<Vec<u8>>,
+        authority: &mut [Rrset],
+        check: Check<'_>,
+        now: Serial,
+    ) -> SecurityStatus {
+        let signer = authority
+            .iter()
+            .filter(|rrset| {
+                matches!(
+                    rrset.rtype(),
+                    Rtype::Soa | Rtype::Nsec | Rtype::Nsec3
+                )
+            })
+            .find_map(Rrset::signer);
+        let signer = match signer {
+            Some(signer) => signer,
+            None => {
+                return self
+                    .unsigned_status(resolver, target.clone(), now)
+                    .await
+            }
+        };
+        let chain = match self.chain_to(resolver, &signer, now).await {
+            Ok(chain) => chain,
+            Err(status) => return status,
+        };
+        if let Some(soa) = authority
+            .iter_mut()
+            .find(|rrset| rrset.rtype() == Rtype::Soa)
+        {
+            let status = chain.validate_rrset(&mut soa.records, &soa.rrsigs);
+            if !status.is_secure() {
+                return status;
+            }
+        }
+        denial_status(&chain, &signer, authority, check)
+    }
+
+    /// Determines the status of unsigned data for `name`.
+    ///
+    /// Unsigned data is insecure if there is proof of an insecure
+    /// delegation somewhere between the closest trust anchor and `name`.
+    /// This is checked by asking for the DS records of `name` and, as long
+    /// as the answers are unsigned, of its ancestors. A signed answer must
+    /// be a proven insecure delegation. If there is none, the data is
+    /// bogus.
+    fn unsigned_status<'a>(
+        &'a self,
+        resolver: &'a StubResolver,
+        mut name: Dname<Vec<u8>>,
+        now: Serial,
+    ) -> Pin<Box<dyn Future<Output = SecurityStatus> + Send + 'a>> {
+        Box::pin(async move {
+            if self.anchors.is_negative(&name, now) {
+                return SecurityStatus::Insecure(Reason::NegativeTrustAnchor);
+            }
+            let anchor = match self.anchors.closest(&name) {
+                Some(anchor) => anchor,
+                None => {
+                    return SecurityStatus::Indeterminate(
+                        Reason::NoTrustAnchor,
+                    )
+                }
+            };
+            while !name.name_eq(anchor) {
+                let answer =
+                    match self.fetch(resolver, &name, Rtype::Ds).await {
+                        Ok(answer) => answer,
+                        Err(_) => {
+                            return SecurityStatus::Bogus(Reason::Unavailable)
+                        }
+                    };
+                let (answers, mut authority) = match (
+                    rrsets(answer.answer()),
+                    rrsets(answer.authority()),
+                ) {
+                    (Ok(answers), Ok(authority)) => (answers, authority),
+                    _ => return SecurityStatus::Bogus(Reason::Unavailable),
+                };
+
+                // With a DS RRset, the zone should have been signed.
+                if let Some(ds) = answers.iter().find(|rrset| {
+                    rrset.rtype() == Rtype::Ds && rrset.owner().name_eq(&name)
+                }) {
+                    if ds.rrsigs.is_empty() {
+                        return SecurityStatus::Bogus(
+                            Reason::MissingSignature,
+                        );
+                    }
+                    return match self.chain_to(resolver, &name, now).await {
+                        Ok(_) => {
+                            SecurityStatus::Bogus(Reason::MissingSignature)
+                        }
+                        Err(status) => status,
+                    };
+                }
+
+                // A signed answer from the parent must prove an insecure
+                // delegation.
+                let signer = authority.iter().find_map(Rrset::signer);
+                if let Some(signer) = signer {
+                    if !signer.name_eq(&name) && name.ends_with(&signer) {
+                        let chain =
+                            match self.chain_to(resolver, &signer, now).await
+                            {
+                                Ok(chain) => chain,
+                                Err(status) => return status,
+                            };
+                        return match denial_status(
+                            &chain,
+                            &signer,
+                            &mut authority,
+                            Check::Delegation(&name),
+                        ) {
+                            SecurityStatus::Secure => {
+                                SecurityStatus::Insecure(
+                                    Reason::InsecureDelegation,
+                                )
+                            }
+                            status => status,
+                        };
+                    }
+                }
+
+                name = match name.parent() {
+                    Some(parent) => parent.to_vec(),
+                    None => break,
+                };
+            }
+            SecurityStatus::Bogus(Reason::MissingSignature)
+        })
+    }
+
+    /// Follows the chain of trust down to `zone`.
+    ///
+    /// Returns a chain validator that has the keys of `zone` or the status
+    /// of the chain if it isn’t secure.
+    async fn chain_to(
+        &self,
+        resolver: &StubResolver,
+        zone: &Dname<Vec<u8>>,
+        now: Serial,
+    ) -> Result<Chain<'_>, SecurityStatus> {
+        let anchor = self
+            .anchors
+            .closest(zone)
+            .ok_or(SecurityStatus::Indeterminate(Reason::NoTrustAnchor))?;
+
+        // Collect the DS RRsets from `zone` up to the anchor.
+        let mut delegations = Vec::new();
+        let mut zone = zone.clone();
+        while !zone.name_eq(anchor) {
+            let answer = self
+                .fetch(resolver, &zone, Rtype::Ds)
+                .await
+                .map_err(|_| SecurityStatus::Bogus(Reason::Unavailable))?;
+            let ds = rrsets(answer.answer())
+                .map_err(|_| SecurityStatus::Bogus(Reason::Unavailable))?
+                .into_iter()
+                .find(|rrset| {
+                    rrset.rtype() == Rtype::Ds && rrset.owner().name_eq(&zone)
+                });
+            let ds = match ds {
+                Some(ds) => ds,
+                None => {
+                    return Err(self
+                        .unsigned_status(resolver, zone.clone(), now)
+                        .await)
+                }
+            };
+            let parent = ds
+                .signer()
+                .ok_or(SecurityStatus::Bogus(Reason::MissingSignature))?;
+            if parent.name_eq(&zone) || !zone.ends_with(&parent) {
+                return Err(SecurityStatus::Bogus(Reason::NotInZone));
+            }
+            delegations.push(ds);
+            zone = parent;
+        }
+
+        let mut chain =
+            ChainValidator::with_backend(&self.anchors, &*self.backend, now);
+        let step = self.add_dnskeys(resolver, &mut chain, anchor);
+        #[cfg(feature = "tracing")]
+        let step = step.instrument(chain_step_span(anchor));
+        step.await?;
+        for ds in delegations.into_iter().rev() {
+            let mut records = ds.typed(|data| match data {
+                ZoneRecordData::Ds(ds) => Some(ds),
+                _ => None,
+            });
+            let status = chain.add_ds(&mut records, &ds.rrsigs);
+            if !status.is_secure() {
+                return Err(status);
+            }
+            let zone = ds.owner().to_vec();
+            let step = self.add_dnskeys(resolver, &mut chain, &zone);
+            #[cfg(feature = "tracing")]
+            let step = step.instrument(chain_step_span(&zone));
+            step.await?;
+        }
+        Ok(chain)
+    }
+
+    /// Fetches the DNSKEY RRset of a zone and adds it to the chain.
+    async fn add_dnskeys(
+        &self,
+        resolver: &StubResolver,
+        chain: &mut Chain<'_>,
+        zone: &Dname<Vec<u8>>,
+    ) -> Result<(), SecurityStatus> {
+        let answer = self
+            .fetch(resolver, zone, Rtype::Dnskey)
+            .await
+            .map_err(|_| SecurityStatus::Bogus(Reason::Unavailable))?;
+        let rrset = rrsets(answer.answer())
+            .map_err(|_| SecurityStatus::Bogus(Reason::Unavailable))?
+            .into_iter()
+            .find(|rrset| {
+                rrset.rtype() == Rtype::Dnskey && rrset.owner().name_eq(zone)
+            })
+            .ok_or(SecurityStatus::Bogus(Reason::NoMatchingKey))?;
+        let mut records = rrset.typed(|data| match data {
+            ZoneRecordData::Dnskey(key) => Some(key),
+            _ => None,
+        });
+        let status = chain.add_dnskeys(&mut records, &rrset.rrsigs);
+        if status.is_secure() {
+            Ok(())
+        } else {
+            Err(status)
+        }
+    }
+
+    /// Returns the answer for a query, using the cache if possible.
+    async fn fetch(
+        &self,
+        resolver: &StubResolver,
+        qname: &Dname<Vec<u8>>,
+        qtype: Rtype,
+    ) -> Result<Answer, io::Error> {
+        let question = Question::new_in(qname, qtype);
+        if let Some(answer) = self.cache.get(&question) {
+            return Ok(answer);
+        }
+        let answer = Query::new(resolver)?
+            .run(Query::create_message(question))
+            .await?;
+        self.cache.insert(answer.clone());
+        Ok(answer)
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Validator")
+            .field("anchors", &self.anchors)
+            .field("strict", &self.strict)
+            .finish_non_exhaustive()
+    }
+}
+
+//------------ Chain ---------------------------------------------------------
+
+/// The chain validator using the validator’s crypto backend.
+type Chain<'a> = ChainValidator<'a, &'a (dyn Backend + Send + Sync)>;
+
+/// Creates the tracing span for adding the keys of a zone to the chain.
+#[cfg(feature = "tracing")]
+fn chain_step_span(zone: &Dname<Vec<u8>>) -> tracing::Span {
+    tracing::debug_span!("chain_step", zone = %zone)
+}
+
+//------------ Rrset ---------------------------------------------------------
+
+type Name = ParsedDname<Bytes>;
+type Data = ZoneRecordData<Bytes, Name>;
+type Rrsig = crate::rdata::Rrsig<Bytes, Name>;
+
+/// An RRset of a message together with its signatures.
+struct Rrset {
+    records: Vec<Record<Name, Data>>,
+    rrsigs: Vec<Rrsig>,
+}
+
+impl Rrset {
+    fn owner(&self) -> &Name {
+        self.records[0].owner()
+    }
+
+    fn rtype(&self) -> Rtype {
+        self.records[0].rtype()
+    }
+
+    /// Returns the signer name of the first signature.
+    fn signer(&self) -> Option<Dname<Vec<u8>>> {
+        self.rrsigs
+            .first()
+            .map(|rrsig| rrsig.signer_name().to_vec())
+    }
+
+    /// Returns the RRset with all names converted for keeping.
+    fn flatten(&self) -> Option<SignedRrset> {
+        Some(SignedRrset {
+            records: self
+                .records
+                .iter()
+                .map(|record| {
+                    Some(Record::new(
+                        record.owner().to_bytes(),
+                        record.class(),
+                        record.ttl(),
+                        record.data().clone().flatten_into().ok()?,
+                    ))
+                })
+                .collect::<Option<_>>()?,
+            rrsigs: self
+                .rrsigs
+                .iter()
+                .map(|rrsig| rrsig.clone().flatten_into().ok())
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Returns the records with the record data converted.
+    fn typed<T: Clone>(
+        &self,
+        op: impl Fn(&Data) -> Option<&T>,
+    ) -> Vec<Record<Name, T>> {
+        self.records
+            .iter()
+            .filter_map(|record| {
+                op(record.data()).map(|data| {
+                    Record::new(
+                        record.owner().clone(),
+                        record.class(),
+                        record.ttl(),
+                        data.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Collects the records of a section into RRsets.
+fn rrsets(
+    section: Result<RecordSection<Bytes>, ParseError>,
+) -> Result<Vec<Rrset>, ParseError> {
+    let mut res: Vec<Rrset> = Vec::new();
+    let mut rrsigs = Vec::new();
+    for record in section?.limit_to::<Data>() {
+        let record = record?;
+        if let ZoneRecordData::Rrsig(rrsig) = record.data() {
+            rrsigs.push((record.owner().clone(), rrsig.clone()));
+            continue;
+        }
+        let rrset = res.iter_mut().find(|rrset| {
+            rrset.owner().name_eq(record.owner())
+                && rrset.rtype() == record.rtype()
+                && rrset.records[0].class() == record.class()
+        });
+        match rrset {
+            Some(rrset) => rrset.records.push(record),
+            None => res.push(Rrset {
+                records: vec![record],
+                rrsigs: Vec::new(),
+            }),
+        }
+    }
+    for (owner, rrsig) in rrsigs {
+        if let Some(rrset) = res.iter_mut().find(|rrset| {
+            rrset.owner().name_eq(&owner)
+                && rrset.rtype() == rrsig.type_covered()
+        }) {
+            rrset.rrsigs.push(rrsig)
+        }
+    }
+    Ok(res)
+}
+
+//------------ Denial of Existence -------------------------------------------
+
+/// The negative answer to prove.
+#[derive(Clone, Copy)]
+enum Check<'a> {
+    /// The name doesn’t exist.
+    NxDomain(&'a Dname<Vec<u8>>),
+
+    /// The name doesn’t have records of the type.
+    NoData(&'a Dname<Vec<u8>>, Rtype),
+
+    /// The name was expanded from a wildcard with the given labels.
+    Wildcard(&'a Dname<Vec<u8>>, u8),
+
+    /// The name is a delegation without DS records.
+    Delegation(&'a Dname<Vec<u8>>),
+}
+
+/// Validates the NSEC or NSEC3 records of a section and checks a denial.
+///
+/// Returns a secure status if the denial is proven and an insecure status
+/// if it relies on an opt-out NSEC3 record.
+fn denial_status(
+    chain: &Chain,
+    zone: &Dname<Vec<u8>>,
+    authority: &mut [Rrset],
+    check: Check,
+) -> SecurityStatus {
+    let mut nsecs = Vec::new();
+    let mut nsec3s = Vec::new();
+    for rrset in authority.iter_mut() {
+        if !matches!(rrset.rtype(), Rtype::Nsec | Rtype::Nsec3) {
+            continue;
+        }
+        let status = chain.validate_rrset(&mut rrset.records, &rrset.rrsigs);
+        if !status.is_secure() {
+            return status;
+        }
+        nsecs.extend(rrset.typed(|data| match data {
+            ZoneRecordData::Nsec(nsec) => Some(nsec),
+            _ => None,
+        }));
+        nsec3s.extend(rrset.typed(|data| match data {
+            ZoneRecordData::Nsec3(nsec3) => Some(nsec3),
+            _ => None,
+        }));
+    }
+
+    let res: Result<Denial, DenialError> = if !nsec3s.is_empty() {
+        let denial =
+            Nsec3Denial::with_backend(zone, &nsec3s, chain.backend());
+        match check {
+            Check::NxDomain(name) => denial.nxdomain(name),
+            Check::NoData(name, rtype) => denial.nodata(name, rtype),
+            Check::Wildcard(name, labels) => {
+                denial.wildcard_answer(name, labels)
+            }
+            Check::Delegation(name) => denial.insecure_delegation(name),
+        }
+    } else {
+        let denial = NsecDenial::new(&nsecs);
+        match check {
+            Check::NxDomain(name) => denial.nxdomain(name),
+            Check::NoData(name, rtype) => denial.nodata(name, rtype),
+            Check::Wildcard(name, labels) => {
+                denial.wildcard_answer(name, labels)
+            }
+            Check::Delegation(name) => denial.insecure_delegation(name),
+        }
+    };
+    match res {
+        Ok(Denial::Proven) => SecurityStatus::Secure,
+        Ok(Denial::OptOut) => {
+            SecurityStatus::Insecure(Reason::InsecureDelegation)
+        }
+        // RFC 9276 recommends treating expensive NSEC3 chains as insecure.
+        Err(err @ DenialError::TooManyIterations) => {
+            SecurityStatus::Insecure(Reason::InvalidDenial(err))
+        }
+        Err(err) => SecurityStatus::Bogus(Reason::InvalidDenial(err)),
+    }
+}
+
+/// Returns the worse of two statuses.
+///
+/// Bogus is worse than indeterminate which is worse than insecure which
+/// is worse than secure.
+fn worse(left: SecurityStatus, right: SecurityStatus) -> SecurityStatus {
+    fn rank(status: SecurityStatus) -> u8 {
+        match status {
+            SecurityStatus::Secure => 0,
+            SecurityStatus::Insecure(_) => 1,
+            SecurityStatus::Indeterminate(_) => 2,
+            SecurityStatus::Bogus(_) => 3,
+        }
+    }
+    if rank(right) > rank(left) {
+        right
+    } else {
+        left
+    }
+}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "sign", feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, DigestAlg, SecAlg};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::Ttl;
+    use crate::rdata::{Dnskey, Ns, Soa, A};
+    use crate::resolv::stub::conf::ResolvConf;
+    use crate::resolv::stub::transport::{
+        Request, Transport, TransportFuture,
+    };
+    use crate::sign::key::SigningKey;
+    use crate::sign::records::{SortedRecords, ZoneSigner};
+    use crate::sign::ring::Key;
+    use ring::rand::SystemRandom;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    type TestName = Dname<Vec<u8>>;
+    type TestData = ZoneRecordData<Vec<u8>, TestName>;
+    type Zone = SortedRecords<TestName, TestData>;
+
+    fn name(s: &str) -> TestName {
+        if s == "." {
+            TestName::root_vec()
+        } else {
+            TestName::from_str(s).unwrap()
+        }
+    }
+
+    fn unsigned_zone(
+        apex: &str,
+        extra: Vec<(&str, TestData)>,
+    ) -> Vec<Record<TestName, TestData>> {
+        let ttl = Ttl::HOUR;
+        let ns = format!("ns.{}", apex.trim_start_matches('.'));
+        let mut res = vec![
+            Record::new(
+                name(apex),
+                Class::In,
+                ttl,
+                Soa::new(name(&ns), name(&ns), 1.into(), ttl, ttl, ttl, ttl)
+                    .into(),
+            ),
+            Record::new(
+                name(apex),
+                Class::In,
+                ttl,
+                Ns::new(name(&ns)).into(),
+            ),
+        ];
+        res.extend(extra.into_iter().map(|(owner, data)| {
+            Record::new(name(owner), Class::In, ttl, data)
+        }));
+        res
+    }
+
+    fn sign(
+        apex: &str,
+        key: &Key,
+        records: Vec<Record<TestName, TestData>>,
+    ) -> Zone {
+        let now = Serial::now();
+        let mut signer = ZoneSigner::new(
+            Serial(now.into_int().wrapping_sub(3600)),
+            now.add(3600),
+        );
+        signer.add_ksk(key);
+        signer.sign(&name(apex), records).unwrap()
+    }
+
+    /// A transport that answers from a set of zones.
+    struct ZoneTransport {
+        zones: Vec<(TestName, Zone)>,
+        dnssec_ok: AtomicBool,
+        queries: AtomicUsize,
+    }
+
+    impl ZoneTransport {
+        /// Creates the zones and returns the transport and root key.
+        ///
+        /// The root zone delegates to the signed zone `example.` which in
+        /// turn delegates to the unsigned zone `insecure.example.`. If
+        /// `tamper` is true, the address of `www.example.` is changed
+        /// after signing.
+        fn new(tamper: bool) -> (Self, Dnskey<Vec<u8>>) {
+            let rng = SystemRandom::new();
+            let root_key = Key::generate(SecAlg::Ed25519, 257, &rng).unwrap();
+            let example_key =
+                Key::generate(SecAlg::Ed25519, 257, &rng).unwrap();
+
+            let root = sign(
+                ".",
+                &root_key,
+                unsigned_zone(
+                    ".",
+                    vec![
+                        ("example.", Ns::new(name("ns.example.")).into()),
+                        (
+                            "example.",
+                            example_key
+                                .ds_with(name("example."), DigestAlg::Sha256)
+                                .unwrap()
+                                .into(),
+                        ),
+                    ],
+                ),
+            );
+            let mut example = sign(
+                "example.",
+                &example_key,
+                unsigned_zone(
+                    "example.",
+                    vec![
+                        ("www.example.", A::from_octets(192, 0, 2, 1).into()),
+                        (
+                            "insecure.example.",
+                            Ns::new(name("ns.insecure.example.")).into(),
+                        ),
+                    ],
+                ),
+            );
+            if tamper {
+                example = example
+                    .iter()
+                    .map(|record| match record.data() {
+                        ZoneRecordData::A(_) => Record::new(
+                            record.owner().clone(),
+                            record.class(),
+                            record.ttl(),
+                            A::from_octets(192, 0, 2, 66).into(),
+                        ),
+                        _ => record.clone(),
+                    })
+                    .collect();
+            }
+            let insecure = unsigned_zone(
+                "insecure.example.",
+                vec![(
+                    "www.insecure.example.",
+                    A::from_octets(192, 0, 2, 2).into(),
+                )],
+            )
+            .into();
+            let transport = ZoneTransport {
+                zones: vec![
+                    (name("."), root),
+                    (name("example."), example),
+                    (name("insecure.example."), insecure),
+                ],
+                dnssec_ok: AtomicBool::new(false),
+                queries: AtomicUsize::new(0),
+            };
+            (transport, root_key.dnskey().unwrap())
+        }
+
+        /// Returns the zone responsible for a question.
+        fn zone(&self, qname: &TestName, qtype: Rtype) -> &Zone {
+            let (_, zone) = self
+                .zones
+                .iter()
+                .filter(|(apex, _)| {
+                    qname.ends_with(apex)
+                        && !(qtype == Rtype::Ds && qname == apex)
+                })
+                .max_by_key(|(apex, _)| apex.label_count())
+                .unwrap();
+            zone
+        }
+
+        fn answer(&self, request: Request) -> Bytes {
+            self.queries.fetch_add(1, Ordering::Relaxed);
+            let query = request.message();
+            if let Some(opt) = query.opt() {
+                self.dnssec_ok.store(opt.dnssec_ok(), Ordering::Relaxed);
+            }
+            let question = query.sole_question().unwrap();
+            let qname = question.qname().to_vec();
+            let qtype = question.qtype();
+            let zone = self.zone(&qname, qtype);
+            let select = |owner: &TestName, rtype: Rtype| {
+                zone.iter()
+                    .filter(move |record| {
+                        record.owner() == owner
+                            && match record.data() {
+                                ZoneRecordData::Rrsig(rrsig) => {
+                                    rrsig.type_covered() == rtype
+                                }
+                                _ => record.rtype() == rtype,
+                            }
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+
+            let found = select(&qname, qtype);
+            let exists = zone.iter().any(|record| record.owner() == &qname);
+            let rcode = if found.is_empty() && !exists {
+                Rcode::NXDomain
+            } else {
+                Rcode::NoError
+            };
+            let mut answer = MessageBuilder::new_bytes()
+                .start_answer(&query, rcode)
+                .unwrap();
+            for record in found.iter() {
+                answer.push(record).unwrap();
+            }
+            let mut authority = answer.authority();
+            if found.is_empty() {
+                let soa = zone.find_soa().unwrap().owner().clone();
+                for record in select(&soa, Rtype::Soa) {
+                    authority.push(record).unwrap();
+                }
+                let nsecs: Vec<_> = if exists {
+                    select(&qname, Rtype::Nsec)
+                } else {
+                    zone.iter()
+                        .filter(|record| match record.data() {
+                            ZoneRecordData::Rrsig(rrsig) => {
+                                rrsig.type_covered() == Rtype::Nsec
+                            }
+                            _ => record.rtype() == Rtype::Nsec,
+                        })
+                        .cloned()
+                        .collect()
+                };
+                for record in nsecs {
+                    authority.push(record).unwrap();
+                }
+            }
+            authority.into_message().into_octets()
+        }
+    }
+
+    impl Transport for ZoneTransport {
+        fn request<'a>(
+            &'a self,
+            request: Request<'a>,
+        ) -> TransportFuture<'a> {
+            let answer = self.answer(request);
+            Box::pin(async move { Ok(answer) })
+        }
+    }
+
+    /// Creates a validating resolver for the test zones.
+    fn setup(
+        tamper: bool,
+        strict: bool,
+        checking_disabled: bool,
+    ) -> (StubResolver, Arc<ZoneTransport>) {
+        let (transport, root_key) = ZoneTransport::new(tamper);
+        let transport = Arc::new(transport);
+        let mut anchors = TrustAnchors::new();
+        anchors.add_dnskey(name("."), root_key);
+        let mut validator = Validator::new(anchors);
+        validator.set_strict(strict);
+        let mut conf = ResolvConf::new();
+        conf.options.checking_disabled = checking_disabled;
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport.clone())
+                .with_validator(validator);
+        (resolver, transport)
+    }
+
+    async fn status(
+        resolver: &StubResolver,
+        qname: &str,
+        qtype: Rtype,
+    ) -> Option<SecurityStatus> {
+        resolver
+            .query((name(qname), qtype))
+            .await
+            .unwrap()
+            .security_status()
+    }
+
+    #[tokio::test]
+    async fn secure() {
+        let (resolver, transport) = setup(false, false, false);
+        assert_eq!(
+            status(&resolver, "www.example.", Rtype::A).await,
+            Some(SecurityStatus::Secure)
+        );
+        assert!(transport.dnssec_ok.load(Ordering::Relaxed));
+        assert_eq!(
+            status(&resolver, "nope.example.", Rtype::A).await,
+            Some(SecurityStatus::Secure)
+        );
+        assert_eq!(
+            status(&resolver, "www.example.", Rtype::Aaaa).await,
+            Some(SecurityStatus::Secure)
+        );
+    }
+
+    #[tokio::test]
+    async fn insecure() {
+        let (resolver, _) = setup(false, false, false);
+        assert_eq!(
+            status(&resolver, "www.insecure.example.", Rtype::A).await,
+            Some(SecurityStatus::Insecure(Reason::InsecureDelegation))
+        );
+    }
+
+    #[tokio::test]
+    async fn bogus() {
+        let (resolver, _) = setup(true, false, false);
+        assert!(matches!(
+            status(&resolver, "www.example.", Rtype::A).await,
+            Some(SecurityStatus::Bogus(Reason::InvalidSignature(_)))
+        ));
+
+        let (resolver, _) = setup(true, true, false);
+        let err = resolver
+            .query((name("www.example."), Rtype::A))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn negative_trust_anchor() {
+        let (transport, root_key) = ZoneTransport::new(true);
+        let mut anchors = TrustAnchors::new();
+        anchors.add_dnskey(name("."), root_key);
+        anchors.add_negative(name("example."), Serial::now().add(3600));
+        let mut validator = Validator::new(anchors);
+        validator.set_strict(true);
+        let mut conf = ResolvConf::new();
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_transport(conf, transport)
+                .with_validator(validator);
+        assert_eq!(
+            status(&resolver, "www.example.", Rtype::A).await,
+            Some(SecurityStatus::Insecure(Reason::NegativeTrustAnchor))
+        );
+    }
+
+    #[tokio::test]
+    async fn aggressive_nsec() {
+        let (resolver, transport) = setup(false, false, false);
+        assert_eq!(
+            status(&resolver, "nope.example.", Rtype::A).await,
+            Some(SecurityStatus::Secure)
+        );
+        let queries = transport.queries.load(Ordering::Relaxed);
+
+        // Other names and types denied by the same records are answered
+        // from the cached records.
+        let answer = resolver
+            .query((name("other.example."), Rtype::A))
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NXDomain);
+        assert_eq!(answer.security_status(), Some(SecurityStatus::Secure));
+        let answer = resolver
+            .query((name("www.example."), Rtype::Aaaa))
+            .await
+            .unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(answer.header_counts().ancount(), 0);
+        assert_eq!(answer.security_status(), Some(SecurityStatus::Secure));
+        assert_eq!(transport.queries.load(Ordering::Relaxed), queries);
+
+        // Names below a delegation and existing records are not denied.
+        assert_eq!(
+            status(&resolver, "nope.insecure.example.", Rtype::A).await,
+            Some(SecurityStatus::Insecure(Reason::InsecureDelegation))
+        );
+        assert_eq!(
+            status(&resolver, "www.example.", Rtype::A).await,
+            Some(SecurityStatus::Secure)
+        );
+        let queries = transport.queries.load(Ordering::Relaxed);
+
+        resolver.validator().unwrap().clear_cache();
+        status(&resolver, "other.example.", Rtype::A).await;
+        assert!(transport.queries.load(Ordering::Relaxed) > queries);
+    }
+
+    #[tokio::test]
+    async fn checking_disabled() {
+        let (resolver, _) = setup(true, true, true);
+        assert_eq!(status(&resolver, "www.example.", Rtype::A).await, None);
+    }
+}
@@ -0,0 +1,631 @@
+//! Access control and views.
+//!
+//! This module provides the means to decide what a client is allowed to
+//! do and which data it gets to see based on its address and the TSIG
+//! key it used.
+//!
+//! An [`Acl`] is an ordered list of [`Matcher`]s, each of which either
+//! allows or denies access. The first entry matching a [`Client`] decides.
+//! If no entry matches, access is denied. A [`Policy`] combines an ACL
+//! for each [`Action`] – queries, zone transfers, and dynamic updates.
+//!
+//! A [`View`] has an ACL selecting its clients, a policy for them, and a
+//! set of zones. If the zones are a [`ZoneTree`], this allows for split
+//! horizon configurations where internal clients get different answers
+//! than everyone else. [`Views::check`] picks the view for a request and
+//! checks that the request is allowed in it:
+//!
+//! ```
+//! use domain::serve::acl::{Acl, Client, Policy, Prefix, View, Views};
+//!
+//! let internal = Acl::new().allow("10.0.0.0/8".parse::<Prefix>().unwrap());
+//! let mut views = Views::new();
+//! views.push(View::new(
+//!     "internal",
+//!     internal.clone(),
+//!     Policy::new(Acl::any()).with_transfer(internal),
+//!     "internal zones",
+//! ));
+//! views.push(View::new(
+//!     "external",
+//!     Acl::any(),
+//!     Policy::new(Acl::any()),
+//!     "external zones",
+//! ));
+//!
+//! let client = Client::new("10.1.2.3".parse().unwrap());
+//! assert_eq!(views.select(&client).unwrap().zones(), &"internal zones");
+//! let client = Client::new("192.0.2.1".parse().unwrap());
+//! assert_eq!(views.select(&client).unwrap().name(), "external");
+//! ```
+//!
+//! The TSIG key of a client is not checked here. It has to be verified
+//! before it is given to [`Client::with_key`].
+//!
+//! [`ZoneTree`]: crate::zonetree::ZoneTree
+
+use super::service::Request;
+use crate::base::iana::{Opcode, Rcode, Rtype};
+use crate::base::name::{Dname, ToDname};
+use bytes::Bytes;
+use core::str::FromStr;
+use std::net::IpAddr;
+use std::string::String;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Client --------------------------------------------------------
+
+/// The client of a request as far as access control is concerned.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The address of the client.
+    addr: IpAddr,
+
+    /// The name of the TSIG key used by the client if any.
+    key: Option<Dname<Bytes>>,
+}
+
+impl Client {
+    /// Creates a client with the given address that didn’t use a key.
+    pub fn new(addr: IpAddr) -> Self {
+        Client { addr, key: None }
+    }
+
+    /// Creates the client for a request that didn’t use a key.
+    pub fn from_request(request: &Request) -> Self {
+        Self::new(request.client_addr().ip())
+    }
+
+    /// Adds the name of the TSIG key the client used.
+    ///
+    /// The key must have been verified already.
+    pub fn with_key(mut self, key: &impl ToDname) -> Self {
+        self.key = key.to_dname().ok();
+        self
+    }
+
+    /// Returns the address of the client.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the name of the TSIG key used by the client if any.
+    pub fn key(&self) -> Option<&Dname<Bytes>> {
+        self.key.as_ref()
+    }
+}
+
+//------------ Prefix --------------------------------------------------------
+
+/// An IP address prefix.
+///
+/// The string representation is an address followed by a slash and the
+/// prefix length. If the length is missing, the prefix covers the single
+/// address.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Prefix {
+    /// The address with all bits beyond the prefix length cleared.
+    addr: IpAddr,
+
+    /// The prefix length.
+    len: u8,
+}
+
+impl Prefix {
+    /// Creates a new prefix.
+    ///
+    /// Bits of the address beyond the prefix length are ignored.
+    pub fn new(addr: IpAddr, len: u8) -> Result<Self, PrefixError> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if len > max {
+            return Err(PrefixError::BadLength);
+        }
+        Ok(Prefix {
+            addr: mask(addr, len),
+            len,
+        })
+    }
+
+    /// Returns the address with all bits beyond the prefix length cleared.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the prefix length in bits.
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns whether the prefix covers the whole address family.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the prefix contains an address.
+    ///
+    /// IPv4-mapped IPv6 addresses are treated as IPv4 addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => addr,
+            },
+            addr => addr,
+        };
+        addr.is_ipv4() == self.addr.is_ipv4()
+            && mask(addr, self.len) == self.addr
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = PrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, len) = match s.split_once('/') {
+            Some((addr, len)) => {
+                let len = len.parse().map_err(|_| PrefixError::BadLength)?;
+                (addr, Some(len))
+            }
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| PrefixError::BadAddr)?;
+        let len = len.unwrap_or(if addr.is_ipv4() { 32 } else { 128 });
+        Self::new(addr, len)
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+//------------ Matcher -------------------------------------------------------
+
+/// A condition a client can match.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// Matches every client.
+    Any,
+
+    /// Matches clients with an address within the prefix.
+    Prefix(Prefix),
+
+    /// Matches clients that used the TSIG key with the given name.
+    Key(Dname<Bytes>),
+
+    /// Matches clients that match all the conditions.
+    All(Vec<Matcher>),
+}
+
+impl Matcher {
+    /// Returns whether a client matches.
+    pub fn matches(&self, client: &Client) -> bool {
+        match self {
+            Matcher::Any => true,
+            Matcher::Prefix(prefix) => prefix.contains(client.addr),
+            Matcher::Key(name) => {
+                client.key.as_ref().map_or(false, |key| key.name_eq(name))
+            }
+            Matcher::All(all) => all.iter().all(|item| item.matches(client)),
+        }
+    }
+}
+
+impl From<Prefix> for Matcher {
+    fn from(prefix: Prefix) -> Self {
+        Matcher::Prefix(prefix)
+    }
+}
+
+impl From<Dname<Bytes>> for Matcher {
+    fn from(key: Dname<Bytes>) -> Self {
+        Matcher::Key(key)
+    }
+}
+
+//------------ Acl -----------------------------------------------------------
+
+/// An access control list.
+///
+/// The list consists of matchers that either allow or deny access. The
+/// first matcher that matches a client decides whether the client has
+/// access. If there is no matching entry, access is denied.
+#[derive(Clone, Debug, Default)]
+pub struct Acl {
+    /// The entries and whether they allow access.
+    entries: Vec<(Matcher, bool)>,
+}
+
+impl Acl {
+    /// Creates a new, empty list which denies access to everyone.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a list that allows access to everyone.
+    pub fn any() -> Self {
+        Self::new().allow(Matcher::Any)
+    }
+
+    /// Adds an entry allowing access to matching clients.
+    pub fn allow(mut self, matcher: impl Into<Matcher>) -> Self {
+        self.entries.push((matcher.into(), true));
+        self
+    }
+
+    /// Adds an entry denying access to matching clients.
+    pub fn deny(mut self, matcher: impl Into<Matcher>) -> Self {
+        self.entries.push((matcher.into(), false));
+        self
+    }
+
+    /// Returns whether a client is allowed access.
+    pub fn allows(&self, client: &Client) -> bool {
+        self.entries
+            .iter()
+            .find(|(matcher, _)| matcher.matches(client))
+            .map(|(_, allow)| *allow)
+            .unwrap_or(false)
+    }
+}
+
+//------------ Action --------------------------------------------------------
+
+/// An action a client can request.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    /// A regular query.
+    Query,
+
+    /// A zone transfer via AXFR or IXFR.
+    Transfer,
+
+    /// A dynamic update as defined in RFC 2136.
+    Update,
+}
+
+impl Action {
+    /// Determines the action requested by a request message.
+    ///
+    /// Returns `None` for requests with other opcodes than QUERY and
+    /// UPDATE.
+    pub fn of_request(request: &Request) -> Option<Self> {
+        let message = request.message();
+        match message.header().opcode() {
+            Opcode::Update => Some(Action::Update),
+            Opcode::Query => {
+                match message.first_question().map(|q| q.qtype()) {
+                    Some(Rtype::Axfr) | Some(Rtype::Ixfr) => {
+                        Some(Action::Transfer)
+                    }
+                    _ => Some(Action::Query),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+//------------ Policy --------------------------------------------------------
+
+/// The ACLs for all actions.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// Who may query.
+    query: Acl,
+
+    /// Who may transfer zones.
+    transfer: Acl,
+
+    /// Who may update zones.
+    update: Acl,
+}
+
+impl Policy {
+    /// Creates a policy with the given ACL for queries.
+    ///
+    /// Transfers and updates are denied to everyone.
+    pub fn new(query: Acl) -> Self {
+        Policy {
+            query,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the ACL for zone transfers.
+    pub fn with_transfer(mut self, transfer: Acl) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    /// Sets the ACL for dynamic updates.
+    pub fn with_update(mut self, update: Acl) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Returns the ACL for an action.
+    pub fn acl(&self, action: Action) -> &Acl {
+        match action {
+            Action::Query => &self.query,
+            Action::Transfer => &self.transfer,
+            Action::Update => &self.update,
+        }
+    }
+
+    /// Returns whether a client may perform an action.
+    pub fn allows(&self, action: Action, client: &Client) -> bool {
+        self.acl(action).allows(client)
+    }
+}
+
+//------------ View ----------------------------------------------------------
+
+/// A set of zones with the clients that see it and what they may do.
+#[derive(Clone, Debug)]
+pub struct View<Z> {
+    /// The name of the view.
+    name: String,
+
+    /// The clients the view is for.
+    clients: Acl,
+
+    /// What the clients of the view may do.
+    policy: Policy,
+
+    /// The zones of the view.
+    zones: Z,
+}
+
+impl<Z> View<Z> {
+    /// Creates a new view.
+    pub fn new(
+        name: impl Into<String>,
+        clients: Acl,
+        policy: Policy,
+        zones: Z,
+    ) -> Self {
+        View {
+            name: name.into(),
+            clients,
+            policy,
+            zones,
+        }
+    }
+
+    /// Returns the name of the view.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the clients the view is for.
+    pub fn clients(&self) -> &Acl {
+        &self.clients
+    }
+
+    /// Returns what the clients of the view may do.
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Returns a reference to the zones of the view.
+    pub fn zones(&self) -> &Z {
+        &self.zones
+    }
+
+    /// Returns a mutable reference to the zones of the view.
+    pub fn zones_mut(&mut self) -> &mut Z {
+        &mut self.zones
+    }
+}
+
+//------------ Views ---------------------------------------------------------
+
+/// An ordered list of views.
+///
+/// A client gets the first view whose client ACL allows it.
+#[derive(Clone, Debug)]
+pub struct Views<Z> {
+    /// The views in order.
+    views: Vec<View<Z>>,
+}
+
+impl<Z> Views<Z> {
+    /// Creates a new, empty list of views.
+    pub fn new() -> Self {
+        Views { views: Vec::new() }
+    }
+
+    /// Appends a view to the list.
+    pub fn push(&mut self, view: View<Z>) {
+        self.views.push(view)
+    }
+
+    /// Returns an iterator over the views.
+    pub fn iter(&self) -> impl Iterator<Item = &View<Z>> {
+        self.views.iter()
+    }
+
+    /// Returns the view for a client if there is one.
+    pub fn select(&self, client: &Client) -> Option<&View<Z>> {
+        self.views.iter().find(|view| view.clients.allows(client))
+    }
+
+    /// Returns the view for a request if the request is allowed in it.
+    ///
+    /// If there is no view for the client or the policy of the view
+    /// doesn’t allow the action requested, returns the rcode to respond
+    /// with. This is REFUSED in both cases or NOTIMP for requests with an
+    /// unknown opcode.
+    pub fn check(
+        &self,
+        request: &Request,
+        client: &Client,
+    ) -> Result<&View<Z>, Rcode> {
+        let action = Action::of_request(request).ok_or(Rcode::NotImp)?;
+        match self.select(client) {
+            Some(view) if view.policy.allows(action, client) => Ok(view),
+            _ => Err(Rcode::Refused),
+        }
+    }
+}
+
+impl<Z> Default for Views<Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Clears all bits of an address beyond the prefix length.
+pub(super) fn mask(addr: IpAddr, len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+            IpAddr::V4((u32::from(addr) & mask).into())
+        }
+        IpAddr::V6(addr) => {
+            let mask =
+                u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+            IpAddr::V6((u128::from(addr) & mask).into())
+        }
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ PrefixError ---------------------------------------------------
+
+/// A prefix was invalid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrefixError {
+    /// The address couldn’t be parsed.
+    BadAddr,
+
+    /// The prefix length is invalid or too long for the address.
+    BadLength,
+}
+
+impl fmt::Display for PrefixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PrefixError::BadAddr => f.write_str("invalid address"),
+            PrefixError::BadLength => f.write_str("invalid prefix length"),
+        }
+    }
+}
+
+impl error::Error for PrefixError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::serve::Transport;
+    use std::string::ToString;
+
+    fn prefix(s: &str) -> Prefix {
+        s.parse().unwrap()
+    }
+
+    fn client(addr: &str) -> Client {
+        Client::new(addr.parse().unwrap())
+    }
+
+    fn key(name: &str) -> Dname<Bytes> {
+        Dname::from_str(name).unwrap()
+    }
+
+    fn request(opcode: Opcode, qtype: Rtype) -> Request {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_opcode(opcode);
+        let mut msg = msg.question();
+        msg.push((key("example.com"), qtype)).unwrap();
+        Request::new(
+            msg.into_message(),
+            "192.0.2.1:53".parse().unwrap(),
+            Transport::Tcp,
+        )
+    }
+
+    #[test]
+    fn prefix_contains() {
+        let net = prefix("192.0.2.77/24");
+        assert_eq!(net.to_string(), "192.0.2.0/24");
+        assert!(net.contains("192.0.2.1".parse().unwrap()));
+        assert!(net.contains("::ffff:192.0.2.1".parse().unwrap()));
+        assert!(!net.contains("192.0.3.1".parse().unwrap()));
+        assert!(!net.contains("2001:db8::1".parse().unwrap()));
+        assert!(prefix("0.0.0.0/0").contains(client("0.1.2.3").addr()));
+        assert!(
+            prefix("2001:db8::/32").contains(client("2001:db8::1").addr())
+        );
+        assert_eq!(prefix("2001:db8::1").len(), 128);
+        assert_eq!(
+            Prefix::from_str("192.0.2.0/33"),
+            Err(PrefixError::BadLength)
+        );
+        assert_eq!(Prefix::from_str("foo/8"), Err(PrefixError::BadAddr));
+    }
+
+    #[test]
+    fn acl() {
+        let acl = Acl::new()
+            .deny(prefix("192.0.2.128/25"))
+            .allow(prefix("192.0.2.0/24"))
+            .allow(Matcher::All(vec![
+                prefix("198.51.100.0/24").into(),
+                key("xfr.key").into(),
+            ]));
+        assert!(acl.allows(&client("192.0.2.1")));
+        assert!(!acl.allows(&client("192.0.2.129")));
+        assert!(!acl.allows(&client("198.51.100.1")));
+        let with_key = client("198.51.100.1").with_key(&key("XFR.key"));
+        assert!(acl.allows(&with_key));
+        let with_key = client("203.0.113.1").with_key(&key("xfr.key"));
+        assert!(!acl.allows(&with_key));
+        assert!(!Acl::new().allows(&client("192.0.2.1")));
+    }
+
+    #[test]
+    fn views() {
+        let internal = Acl::new().allow(prefix("10.0.0.0/8"));
+        let mut views = Views::new();
+        views.push(View::new(
+            "internal",
+            internal.clone(),
+            Policy::new(Acl::any())
+                .with_transfer(internal)
+                .with_update(Acl::new().allow(key("update.key"))),
+            1,
+        ));
+        views.push(View::new(
+            "external",
+            Acl::any(),
+            Policy::new(Acl::any()),
+            2,
+        ));
+
+        let query = request(Opcode::Query, Rtype::A);
+        let axfr = request(Opcode::Query, Rtype::Axfr);
+        let update = request(Opcode::Update, Rtype::Soa);
+        let notify = request(Opcode::Notify, Rtype::Soa);
+
+        let inside = client("10.1.2.3");
+        assert_eq!(views.check(&query, &inside).map(View::zones), Ok(&1));
+        assert_eq!(views.check(&axfr, &inside).map(View::zones), Ok(&1));
+        assert_eq!(views.check(&update, &inside).err(), Some(Rcode::Refused));
+        let with_key = inside.clone().with_key(&key("update.key"));
+        assert!(views.check(&update, &with_key).is_ok());
+        assert_eq!(views.check(&notify, &inside).err(), Some(Rcode::NotImp));
+
+        let outside = client("192.0.2.1");
+        assert_eq!(views.check(&query, &outside).map(View::zones), Ok(&2));
+        assert_eq!(views.check(&axfr, &outside).err(), Some(Rcode::Refused));
+    }
+}
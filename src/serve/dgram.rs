@@ -0,0 +1,376 @@
+//! Serving requests over UDP.
+//!
+//! The [`DgramServer`] receives requests from one or more UDP sockets bound
+//! to the same address. Each socket is served by a task of its own that
+//! receives requests and spawns a task for processing each of them.
+//!
+//! On systems that support the `SO_REUSEPORT` socket option, the server
+//! binds one socket per available CPU by default. The kernel then spreads
+//! incoming requests over these sockets and thus over the receiving tasks,
+//! so a busy server isn’t limited to the throughput of a single task.
+
+use super::service::{Request, Service, Transport};
+use super::Tasks;
+use crate::base::message::Message;
+use bytes::BytesMut;
+use core::future::Future;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::net::UdpSocket;
+
+//------------ Module Configuration ------------------------------------------
+
+/// Whether the system supports `SO_REUSEPORT` for distributing datagrams.
+const REUSE_PORT: bool = cfg!(all(
+    unix,
+    not(any(
+        target_os = "solaris",
+        target_os = "illumos",
+        target_os = "cygwin"
+    ))
+));
+
+/// The amount of buffer space allocated at once for request messages.
+///
+/// Request messages are copied from the receive buffer into a larger
+/// buffer which they then share. Once all requests referencing the buffer
+/// have been processed, the buffer is reused.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+//------------ DgramServer ---------------------------------------------------
+
+/// A server receiving requests over UDP.
+///
+/// Requests that can’t be parsed as a DNS message or are actually
+/// responses are silently dropped. So are responses produced by the
+/// service that exceed the request’s
+/// [`max_response_size`][Request::max_response_size].
+pub struct DgramServer<S> {
+    /// The sockets to receive requests on.
+    sockets: Vec<Arc<UdpSocket>>,
+
+    /// The service to process requests with.
+    service: Arc<S>,
+
+    /// The size of the receive buffer.
+    recv_size: usize,
+}
+
+impl<S: Service> DgramServer<S> {
+    /// The default size of the receive buffer.
+    ///
+    /// Requests larger than this are truncated and will therefore fail to
+    /// be parsed.
+    pub const DEFAULT_RECV_SIZE: usize = 4096;
+
+    /// Creates a new server bound to the given address.
+    ///
+    /// If the system supports `SO_REUSEPORT`, the server binds one socket
+    /// per available CPU. Otherwise it binds a single socket.
+    ///
+    /// This needs to be called from within a Tokio runtime.
+    pub fn bind(addr: SocketAddr, service: S) -> Result<Self, io::Error> {
+        let shards = if REUSE_PORT {
+            std::thread::available_parallelism().map_or(1, usize::from)
+        } else {
+            1
+        };
+        Self::bind_sharded(addr, shards, service)
+    }
+
+    /// Creates a new server with the given number of sockets.
+    ///
+    /// If there is more than one socket, `SO_REUSEPORT` is set on all of
+    /// them, so this fails on systems that don’t support the option. If
+    /// the port of `addr` is zero, all sockets are bound to the port
+    /// picked by the system for the first one.
+    ///
+    /// This needs to be called from within a Tokio runtime.
+    pub fn bind_sharded(
+        addr: SocketAddr,
+        shards: usize,
+        service: S,
+    ) -> Result<Self, io::Error> {
+        let reuse_port = shards > 1;
+        let first = bind_socket(addr, reuse_port)?;
+        let addr = first.local_addr()?;
+        let mut sockets = Vec::with_capacity(shards.max(1));
+        sockets.push(Arc::new(first));
+        for _ in 1..shards {
+            sockets.push(Arc::new(bind_socket(addr, reuse_port)?));
+        }
+        Ok(DgramServer {
+            sockets,
+            service: Arc::new(service),
+            recv_size: Self::DEFAULT_RECV_SIZE,
+        })
+    }
+
+    /// Creates a new server using an existing socket.
+    pub fn from_socket(socket: UdpSocket, service: S) -> Self {
+        DgramServer {
+            sockets: vec![Arc::new(socket)],
+            service: Arc::new(service),
+            recv_size: Self::DEFAULT_RECV_SIZE,
+        }
+    }
+
+    /// Sets the size of the receive buffer.
+    pub fn set_recv_size(&mut self, size: usize) {
+        self.recv_size = size
+    }
+
+    pub fn recv_size(&self) -> usize {
+        self.recv_size
+    }
+
+    /// Returns the local address the server is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.sockets[0].local_addr()
+    }
+
+    /// Runs the server until `shutdown` completes.
+    ///
+    /// Once `shutdown` has completed, the server stops receiving requests
+    /// and returns after responses to all requests received so far have
+    /// been sent.
+    ///
+    /// If receiving on one of the sockets fails, the server shuts down and
+    /// returns the error.
+    pub async fn run(
+        self,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), io::Error> {
+        let DgramServer {
+            sockets,
+            service,
+            recv_size,
+        } = self;
+        Tasks::run(
+            |tasks| async move {
+                let receivers: Vec<_> = sockets
+                    .into_iter()
+                    .map(|sock| {
+                        tokio::spawn(receive(
+                            sock,
+                            service.clone(),
+                            recv_size,
+                            tasks.clone(),
+                        ))
+                    })
+                    .collect();
+                drop(tasks);
+                let mut res = Ok(());
+                for receiver in receivers {
+                    let err = match receiver.await {
+                        Ok(Ok(())) => continue,
+                        Ok(Err(err)) => err,
+                        Err(err) => io::Error::new(io::ErrorKind::Other, err),
+                    };
+                    if res.is_ok() {
+                        res = Err(err)
+                    }
+                }
+                res
+            },
+            shutdown,
+        )
+        .await
+    }
+}
+
+impl<S> fmt::Debug for DgramServer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DgramServer")
+            .field("sockets", &self.sockets)
+            .field("recv_size", &self.recv_size)
+            .finish_non_exhaustive()
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Creates a UDP socket bound to `addr`.
+fn bind_socket(
+    addr: SocketAddr,
+    reuse_port: bool,
+) -> Result<UdpSocket, io::Error> {
+    let sock = Socket::new(
+        Domain::for_address(addr),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if reuse_port {
+        set_reuse_port(&sock)?;
+    }
+    sock.set_nonblocking(true)?;
+    sock.bind(&addr.into())?;
+    UdpSocket::from_std(sock.into())
+}
+
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "solaris",
+        target_os = "illumos",
+        target_os = "cygwin"
+    ))
+))]
+fn set_reuse_port(sock: &Socket) -> Result<(), io::Error> {
+    sock.set_reuse_port(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(
+        target_os = "solaris",
+        target_os = "illumos",
+        target_os = "cygwin"
+    ))
+)))]
+fn set_reuse_port(_sock: &Socket) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT not supported",
+    ))
+}
+
+/// Receives requests on a socket until the server shuts down.
+async fn receive<S: Service>(
+    sock: Arc<UdpSocket>,
+    service: Arc<S>,
+    recv_size: usize,
+    mut tasks: Tasks,
+) -> Result<(), io::Error> {
+    let mut recv_buf = vec![0; recv_size];
+    let mut buf = BytesMut::new();
+    loop {
+        let (len, client) = tokio::select! {
+            res = sock.recv_from(&mut recv_buf) => match res {
+                Ok(res) => res,
+                Err(err) if is_transient(&err) => continue,
+                Err(err) => return Err(err),
+            },
+            _ = tasks.shutdown() => return Ok(()),
+        };
+        if buf.capacity() < len {
+            buf.reserve(CHUNK_SIZE.max(len));
+        }
+        buf.extend_from_slice(&recv_buf[..len]);
+        let message = match Message::from_octets(buf.split().freeze()) {
+            Ok(message) if !message.header().qr() => message,
+            _ => continue,
+        };
+        let request = Request::new(message, client, Transport::Udp);
+        let max_len = request.max_response_size();
+        let sock = sock.clone();
+        let response = service.call(request);
+        let task = tasks.clone();
+        tokio::spawn(async move {
+            if let Some(response) = response.await {
+                let response = response.as_slice();
+                if response.len() <= max_len {
+                    let _ = sock.send_to(response, client).await;
+                }
+            }
+            drop(task);
+        });
+    }
+}
+
+/// Returns whether an error receiving a datagram can be ignored.
+///
+/// Some systems report ICMP errors for earlier datagrams sent from the
+/// socket when receiving.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use bytes::Bytes;
+    use core::str::FromStr;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    async fn echo(request: Request) -> Option<Message<Bytes>> {
+        if request.message().header().id() == 0 {
+            return None;
+        }
+        let answer = MessageBuilder::new_bytes()
+            .start_answer(request.message(), Rcode::NoError)
+            .unwrap();
+        Some(answer.into_message())
+    }
+
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish()
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let server = DgramServer::bind_sharded(
+            "127.0.0.1:0".parse().unwrap(),
+            2,
+            echo,
+        )
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let server = tokio::spawn(server.run(async {
+            let _ = shutdown_rx.await;
+        }));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(addr).await.unwrap();
+        let mut buf = [0; 512];
+
+        // Requests that aren’t answered, responses, and garbage are
+        // dropped.
+        client.send(&query(0)).await.unwrap();
+        let mut response = query(1);
+        response[2] |= 0x80;
+        client.send(&response).await.unwrap();
+        client.send(b"foo").await.unwrap();
+
+        for id in 1..10 {
+            client.send(&query(id)).await.unwrap();
+            let len = tokio::time::timeout(
+                Duration::from_secs(5),
+                client.recv(&mut buf),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            let answer = Message::from_octets(&buf[..len]).unwrap();
+            assert!(answer.header().qr());
+            assert_eq!(answer.header().id(), id);
+        }
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
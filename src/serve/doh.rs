@@ -0,0 +1,375 @@
+//! Serving requests via DNS over HTTPS.
+//!
+//! DNS over HTTPS as defined in RFC 8484 transfers DNS messages in the
+//! bodies of HTTP requests and responses, typically using HTTP/2. A
+//! request is either a POST request with the request message as its body
+//! or a GET request with the message encoded in *base64url* in the `dns`
+//! variable of the URI’s query.
+//!
+//! This module implements the DNS side of the protocol and leaves HTTP
+//! itself – including HTTP/2 and TLS – to an HTTP server library. A
+//! [`DohHandler`] wraps the same [`Service`] used by the other servers.
+//! The application hands it the relevant parts of each HTTP request for
+//! its DNS endpoint and receives a [`DohResponse`] with the status, header
+//! values, and body of the HTTP response to send back. With
+//! [hyper](https://hyper.rs/), for instance, the handler can be used like
+//! this:
+//!
+//! ```ignore
+//! let response = match *req.method() {
+//!     Method::GET => {
+//!         handler.get(req.uri().query().unwrap_or(""), client).await
+//!     }
+//!     Method::POST => {
+//!         let content_type = req
+//!             .headers()
+//!             .get(CONTENT_TYPE)
+//!             .and_then(|value| value.to_str().ok())
+//!             .map(String::from);
+//!         let body = hyper::body::to_bytes(req.into_body()).await?;
+//!         handler.post(content_type.as_deref(), body, client).await
+//!     }
+//!     _ => DohResponse::error(405),
+//! };
+//! ```
+
+use super::service::{Request, Service, Transport};
+use crate::base::message::Message;
+use crate::utils::base64;
+use bytes::Bytes;
+use core::fmt;
+use core::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The media type of DNS messages.
+pub const MEDIA_TYPE: &str = "application/dns-message";
+
+/// The HTTP status for a successful request.
+const OK: u16 = 200;
+
+/// The HTTP status for a request without a valid DNS message.
+const BAD_REQUEST: u16 = 400;
+
+/// The HTTP status for a request with a too large body.
+const PAYLOAD_TOO_LARGE: u16 = 413;
+
+/// The HTTP status for a POST request with the wrong content type.
+const UNSUPPORTED_MEDIA_TYPE: u16 = 415;
+
+/// The HTTP status used if the service doesn’t produce a response.
+const INTERNAL_SERVER_ERROR: u16 = 500;
+
+//------------ DohHandler ----------------------------------------------------
+
+/// Processes DNS over HTTPS requests with a service.
+///
+/// Requests are handed to the service with [`Transport::Https`]. Since
+/// an HTTP request always needs a response, requests for which the
+/// service doesn’t produce a response message get an empty response with
+/// status 500.
+pub struct DohHandler<S> {
+    /// The service to process requests with.
+    service: Arc<S>,
+}
+
+impl<S: Service> DohHandler<S> {
+    /// Creates a new handler using the given service.
+    pub fn new(service: S) -> Self {
+        DohHandler {
+            service: Arc::new(service),
+        }
+    }
+
+    /// Processes a GET request.
+    ///
+    /// The `query` is the query component of the request URI, i.e., the
+    /// part after the question mark. `client` is the address the request
+    /// was received from.
+    pub fn get(
+        &self,
+        query: &str,
+        client: SocketAddr,
+    ) -> impl Future<Output = DohResponse> + Send + 'static {
+        let message = query
+            .split('&')
+            .find_map(|item| item.strip_prefix("dns="))
+            .ok_or(BAD_REQUEST)
+            .and_then(|value| {
+                // Padding isn’t used with DoH but may appear escaped.
+                base64::decode_url(value.trim_end_matches("%3D"))
+                    .map_err(|_| BAD_REQUEST)
+            });
+        self.respond(message, client)
+    }
+
+    /// Processes a POST request.
+    ///
+    /// The `content_type` is the value of the Content-Type header if
+    /// present. `body` is the body of the request and `client` the address
+    /// it was received from.
+    pub fn post(
+        &self,
+        content_type: Option<&str>,
+        body: Bytes,
+        client: SocketAddr,
+    ) -> impl Future<Output = DohResponse> + Send + 'static {
+        let message = match content_type {
+            Some(content_type) if is_media_type(content_type) => Ok(body),
+            _ => Err(UNSUPPORTED_MEDIA_TYPE),
+        };
+        self.respond(message, client)
+    }
+
+    /// Processes the request message or creates an error response.
+    fn respond(
+        &self,
+        message: Result<Bytes, u16>,
+        client: SocketAddr,
+    ) -> impl Future<Output = DohResponse> + Send + 'static {
+        let response = message
+            .and_then(|octets| {
+                if octets.len() > usize::from(u16::MAX) {
+                    return Err(PAYLOAD_TOO_LARGE);
+                }
+                match Message::from_octets(octets) {
+                    Ok(message) if !message.header().qr() => Ok(message),
+                    _ => Err(BAD_REQUEST),
+                }
+            })
+            .map(|message| {
+                self.service.call(Request::new(
+                    message,
+                    client,
+                    Transport::Https,
+                ))
+            });
+        async move {
+            match response {
+                Ok(response) => match response.await {
+                    Some(response) => DohResponse::from_message(response),
+                    None => DohResponse::error(INTERNAL_SERVER_ERROR),
+                },
+                Err(status) => DohResponse::error(status),
+            }
+        }
+    }
+}
+
+impl<S> Clone for DohHandler<S> {
+    fn clone(&self) -> Self {
+        DohHandler {
+            service: self.service.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for DohHandler<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DohHandler").finish_non_exhaustive()
+    }
+}
+
+//------------ DohResponse ---------------------------------------------------
+
+/// The HTTP response to a DNS over HTTPS request.
+#[derive(Clone, Debug)]
+pub struct DohResponse {
+    /// The HTTP status code.
+    status: u16,
+
+    /// The maximum age of the response in seconds.
+    max_age: Option<u32>,
+
+    /// The body of the response.
+    body: Bytes,
+}
+
+impl DohResponse {
+    /// Creates a successful response with the given response message.
+    ///
+    /// The maximum age of the response is the smallest TTL of the records
+    /// in the answer and authority sections as required by RFC 8484.
+    pub fn from_message(message: Message<Bytes>) -> Self {
+        DohResponse {
+            status: OK,
+            max_age: min_ttl(&message),
+            body: message.into_octets(),
+        }
+    }
+
+    /// Creates an error response with the given HTTP status and no body.
+    pub fn error(status: u16) -> Self {
+        DohResponse {
+            status,
+            max_age: None,
+            body: Bytes::new(),
+        }
+    }
+
+    /// Returns the HTTP status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the value of the Content-Type header if there is one.
+    pub fn content_type(&self) -> Option<&'static str> {
+        if self.status == OK {
+            Some(MEDIA_TYPE)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the maximum age of the response in seconds.
+    ///
+    /// If present, this should be used with the Cache-Control header as
+    /// `max-age`.
+    pub fn max_age(&self) -> Option<u32> {
+        self.max_age
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Converts the response into its body.
+    pub fn into_body(self) -> Bytes {
+        self.body
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Returns whether a Content-Type header value is the DNS media type.
+fn is_media_type(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .eq_ignore_ascii_case(MEDIA_TYPE)
+}
+
+/// Returns the smallest TTL of the answer and authority records.
+fn min_ttl(message: &Message<Bytes>) -> Option<u32> {
+    let mut res: Option<u32> = None;
+    for section in [message.answer(), message.authority()] {
+        for record in section.ok()? {
+            let ttl = record.ok()?.ttl().as_secs();
+            res = Some(res.map_or(ttl, |res| res.min(ttl)));
+        }
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::Ttl;
+    use crate::rdata::A;
+    use core::str::FromStr;
+    use std::string::String;
+    use std::vec::Vec;
+
+    async fn answer(request: Request) -> Option<Message<Bytes>> {
+        assert_eq!(request.transport(), Transport::Https);
+        if request.message().header().id() == 1 {
+            return None;
+        }
+        let mut answer = MessageBuilder::new_bytes()
+            .start_answer(request.message(), Rcode::NoError)
+            .unwrap();
+        let name = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        for ttl in [300, 60, 3600] {
+            answer
+                .push((
+                    &name,
+                    Class::In,
+                    Ttl::from_secs(ttl),
+                    A::from_octets(192, 0, 2, 1),
+                ))
+                .unwrap();
+        }
+        Some(answer.into_message())
+    }
+
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish()
+    }
+
+    fn encode_url(data: &[u8]) -> String {
+        base64::encode_string(data)
+            .replace('+', "-")
+            .replace('/', "_")
+            .trim_end_matches('=')
+            .into()
+    }
+
+    fn client() -> SocketAddr {
+        "192.0.2.2:443".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn get() {
+        let handler = DohHandler::new(answer);
+        let uri_query = format!("ct=foo&dns={}", encode_url(&query(0)));
+        let response = handler.get(&uri_query, client()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), Some(MEDIA_TYPE));
+        assert_eq!(response.max_age(), Some(60));
+        let message = Message::from_octets(response.into_body()).unwrap();
+        assert!(message.header().qr());
+        assert_eq!(message.header_counts().ancount(), 3);
+
+        assert_eq!(handler.get("", client()).await.status(), 400);
+        assert_eq!(handler.get("dns=Zm9v", client()).await.status(), 400);
+        let mut response = query(0);
+        response[2] |= 0x80;
+        let response = format!("dns={}", encode_url(&response));
+        assert_eq!(handler.get(&response, client()).await.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn post() {
+        let handler = DohHandler::new(answer);
+        let response = handler
+            .post(
+                Some("Application/DNS-Message; charset=foo"),
+                query(0).into(),
+                client(),
+            )
+            .await;
+        assert_eq!(response.status(), 200);
+        assert!(Message::from_octets(response.into_body()).is_ok());
+
+        let response = handler.post(None, query(0).into(), client()).await;
+        assert_eq!(response.status(), 415);
+        let response = handler
+            .post(Some("text/plain"), query(0).into(), client())
+            .await;
+        assert_eq!(response.status(), 415);
+        let response = handler
+            .post(Some(MEDIA_TYPE), query(1).into(), client())
+            .await;
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.content_type(), None);
+        assert!(response.body().is_empty());
+    }
+}
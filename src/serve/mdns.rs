@@ -0,0 +1,1164 @@
+//! Advertising records via multicast DNS.
+//!
+//! Multicast DNS as defined in [RFC 6762] allows hosts on a local link to
+//! publish records, such as their own addresses or the services they
+//! offer, without a DNS server. Every host answers queries for its own
+//! records sent to a multicast group.
+//!
+//! A [`Responder`] implements the host side of the protocol for a set of
+//! records:
+//!
+//! * Records are either *unique*, i.e., only this host may have records of
+//!   that name, type, and class, or *shared*. Before unique records are
+//!   used, their names are probed: three queries 250 milliseconds apart
+//!   make sure no other host uses them (section 8.1). Simultaneous probes
+//!   of two hosts are resolved by comparing the records they propose
+//!   (section 8.2).
+//! * Once probing has succeeded, all records are announced twice, one
+//!   second apart, in unsolicited responses (section 8.3).
+//! * Matching queries are answered with the cache-flush bit set on unique
+//!   records. Answers the querier already knows are suppressed (section
+//!   7.1). Answers containing shared records are delayed by 20 to 120
+//!   milliseconds and answers for several queries arriving during that
+//!   time are aggregated into a single response (section 6).
+//! * If another host answers with a conflicting record for one of the
+//!   unique names, during probing or later, the name is changed by adding
+//!   or increasing a numeric suffix to its first label and probing starts
+//!   over (section 9). Records pointing to the old name via PTR, SRV, or
+//!   CNAME records are updated, too. The renames can be retrieved via
+//!   [`Responder::take_renames`].
+//! * When shutting down, a goodbye response with a TTL of zero for all
+//!   announced records tells other hosts to remove them from their caches
+//!   (section 10.1).
+//!
+//! The responder doesn’t do any networking itself. Instead, received
+//! messages are fed to it via [`Responder::handle`] and the messages it
+//! wants to send are retrieved via [`Responder::poll`] whenever the time
+//! returned by [`Responder::next_timeout`] has come. This makes it possible
+//! to use it with any network stack. [`MdnsServer`] does all this for an
+//! IPv4 UDP socket joined to the multicast group.
+//!
+//! All responses are sent via multicast. Unicast responses requested via
+//! the QU bit and legacy unicast queries are not supported.
+//!
+//! [RFC 6762]: https://tools.ietf.org/html/rfc6762
+
+use crate::base::iana::{Class, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, DnameBuilder, ParsedDname, ToDname};
+use crate::base::question::Question;
+use crate::base::rdata::ComposeRecordData;
+use crate::base::record::{Record, Ttl};
+use crate::rdata::{Cname, Ptr, Srv, ZoneRecordData};
+use bytes::Bytes;
+use core::future::Future;
+use octseq::octets::Octets;
+use rand::Rng;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{format, io};
+use tokio::net::UdpSocket;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The port used by multicast DNS.
+pub const MDNS_PORT: u16 = 5353;
+
+/// The IPv4 multicast group used by multicast DNS.
+pub const MDNS_IPV4_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// The bit in the class of a record signalling cache flush.
+///
+/// In questions, the same bit requests a unicast response.
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// The number of probes sent for a name.
+const PROBE_COUNT: u8 = 3;
+
+/// The time between probes.
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long to wait before probing again after losing a tiebreak.
+const PROBE_DEFER: Duration = Duration::from_secs(1);
+
+/// The number of announcements sent for a record.
+const ANNOUNCE_COUNT: u8 = 2;
+
+/// The time between announcements.
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait before multicasting a record again.
+///
+/// When defending a name against a probe, the shorter
+/// [`PROBE_INTERVAL`] is used instead.
+const MULTICAST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The range of delays in milliseconds for answers with shared records.
+const AGGREGATE_DELAY: (u64, u64) = (20, 120);
+
+/// The maximum size of a message we send.
+///
+/// This is the maximum message size allowed in section 17 of RFC 6762.
+const MAX_MESSAGE_SIZE: usize = 9000;
+
+//------------ MdnsRecord ----------------------------------------------------
+
+/// The type of records published by a responder.
+pub type MdnsRecord =
+    Record<Dname<Bytes>, ZoneRecordData<Bytes, Dname<Bytes>>>;
+
+//------------ Responder -----------------------------------------------------
+
+/// A multicast DNS responder for a set of records.
+///
+/// See the [module documentation][self] for details.
+#[derive(Clone, Debug, Default)]
+pub struct Responder {
+    /// The records we publish.
+    entries: Vec<Entry>,
+
+    /// The owner names of our unique records.
+    names: Vec<UniqueName>,
+
+    /// The answers waiting to be sent.
+    answers: Option<PendingAnswers>,
+
+    /// The names changed due to conflicts since last asked.
+    renames: Vec<(Dname<Bytes>, Dname<Bytes>)>,
+}
+
+impl Responder {
+    /// Creates a new responder without any records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a unique record.
+    ///
+    /// If the record’s owner name isn’t in use for unique records yet, it
+    /// is probed before the record is announced. Otherwise the record is
+    /// announced once its name has been probed successfully.
+    pub fn add_unique(&mut self, record: MdnsRecord, now: Instant) {
+        if let Some(name) =
+            self.names.iter().find(|name| name.name == *record.owner())
+        {
+            let announce = name.state == NameState::Established;
+            self.push_entry(record, true, announce, now);
+            return;
+        }
+        self.names.push(UniqueName {
+            name: record.owner().clone(),
+            state: NameState::probing(now),
+        });
+        self.push_entry(record, true, false, now);
+    }
+
+    /// Adds a shared record.
+    ///
+    /// Shared records are not probed and announced right away.
+    pub fn add_shared(&mut self, record: MdnsRecord, now: Instant) {
+        self.push_entry(record, false, true, now)
+    }
+
+    fn push_entry(
+        &mut self,
+        record: MdnsRecord,
+        unique: bool,
+        announce: bool,
+        now: Instant,
+    ) {
+        let mut entry = Entry {
+            record,
+            unique,
+            announce_left: 0,
+            next_announce: now,
+            last_multicast: None,
+        };
+        if announce {
+            entry.start_announcing(now);
+        }
+        self.entries.push(entry)
+    }
+
+    /// Returns whether all unique names have been probed successfully.
+    pub fn is_established(&self) -> bool {
+        self.names
+            .iter()
+            .all(|name| name.state == NameState::Established)
+    }
+
+    /// Returns the names changed due to conflicts since the last call.
+    ///
+    /// Each item contains the old and the new name.
+    pub fn take_renames(&mut self) -> Vec<(Dname<Bytes>, Dname<Bytes>)> {
+        std::mem::take(&mut self.renames)
+    }
+
+    /// Returns the time when [`poll`][Self::poll] should be called next.
+    ///
+    /// Returns `None` if there is nothing to do until the next message is
+    /// received.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        let probes = self.names.iter().filter_map(|name| match name.state {
+            NameState::Probing { next, .. } => Some(next),
+            NameState::Established => None,
+        });
+        let announcements = self
+            .entries
+            .iter()
+            .filter(|entry| entry.announce_left > 0)
+            .map(|entry| entry.next_announce);
+        let answers = self.answers.as_ref().map(|answers| answers.due);
+        probes.chain(announcements).chain(answers).min()
+    }
+
+    /// Processes a message received from the multicast group.
+    ///
+    /// Queries are answered by scheduling a response. Queries that are
+    /// probes of other hosts may make us defer our own probes. Responses
+    /// are checked for records conflicting with our unique records.
+    pub fn handle<Octs: Octets>(
+        &mut self,
+        msg: &Message<Octs>,
+        now: Instant,
+    ) {
+        if msg.header().qr() {
+            self.handle_response(msg, now)
+        } else {
+            self.handle_query(msg, now)
+        }
+    }
+
+    /// Returns the messages to be sent at `now`.
+    ///
+    /// This contains at most one probe query and one response combining
+    /// all announcements and answers due.
+    pub fn poll(&mut self, now: Instant) -> Vec<Message<Bytes>> {
+        let mut res = Vec::new();
+        if let Some(probe) = self.poll_probes(now) {
+            res.push(probe);
+        }
+
+        let mut records = Vec::new();
+        for (idx, entry) in self.entries.iter_mut().enumerate() {
+            if entry.announce_left > 0 && entry.next_announce <= now {
+                entry.announce_left -= 1;
+                entry.next_announce = now + ANNOUNCE_INTERVAL;
+                records.push(idx);
+            }
+        }
+        if self
+            .answers
+            .as_ref()
+            .map_or(false, |answers| answers.due <= now)
+        {
+            if let Some(answers) = self.answers.take() {
+                for idx in answers.records {
+                    if self.is_ready(idx) && !records.contains(&idx) {
+                        records.push(idx)
+                    }
+                }
+            }
+        }
+        if !records.is_empty() {
+            for &idx in &records {
+                self.entries[idx].last_multicast = Some(now);
+            }
+            res.push(self.response(&records, false));
+        }
+        res
+    }
+
+    /// Returns the goodbye response for all announced records.
+    ///
+    /// The response contains all records that have been multicast with a
+    /// TTL of zero. Returns `None` if no record has been multicast yet.
+    pub fn goodbye(self) -> Option<Message<Bytes>> {
+        let records: Vec<_> = (0..self.entries.len())
+            .filter(|&idx| self.entries[idx].last_multicast.is_some())
+            .collect();
+        if records.is_empty() {
+            None
+        } else {
+            Some(self.response(&records, true))
+        }
+    }
+
+    /// Returns whether the entry with the given index may be used.
+    fn is_ready(&self, idx: usize) -> bool {
+        let entry = &self.entries[idx];
+        !entry.unique
+            || self.names.iter().any(|name| {
+                name.name == *entry.record.owner()
+                    && name.state == NameState::Established
+            })
+    }
+
+    /// Sends the probes due and advances the probing state.
+    fn poll_probes(&mut self, now: Instant) -> Option<Message<Bytes>> {
+        let mut probing = Vec::new();
+        let mut first = false;
+        for name in &mut self.names {
+            let sent = match name.state {
+                NameState::Probing { sent, next } if next <= now => sent,
+                _ => continue,
+            };
+            if sent < PROBE_COUNT {
+                name.state = NameState::Probing {
+                    sent: sent + 1,
+                    next: now + PROBE_INTERVAL,
+                };
+                first |= sent == 0;
+                probing.push(name.name.clone());
+            } else {
+                // The last probe went unanswered: the name is ours.
+                name.state = NameState::Established;
+                for entry in &mut self.entries {
+                    if entry.unique && entry.record.owner() == &name.name {
+                        entry.start_announcing(now)
+                    }
+                }
+            }
+        }
+        if probing.is_empty() {
+            return None;
+        }
+
+        let mut msg = MessageBuilder::new_bytes().question();
+        for name in &probing {
+            // The first probe asks for a unicast response, so we don’t
+            // bother the whole link (section 8.1).
+            let class = if first {
+                Class::from_int(Class::In.to_int() | CACHE_FLUSH)
+            } else {
+                Class::In
+            };
+            let _ = msg.push(Question::new(name, Rtype::Any, class));
+        }
+        let mut msg = msg.authority();
+        for entry in &self.entries {
+            if entry.unique && probing.contains(entry.record.owner()) {
+                let _ = msg.push(&entry.record);
+            }
+        }
+        Some(msg.into_message())
+    }
+
+    /// Creates a response containing the given entries.
+    fn response(&self, records: &[usize], goodbye: bool) -> Message<Bytes> {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_aa(true);
+        let mut msg = msg.answer();
+        for &idx in records {
+            let entry = &self.entries[idx];
+            let record = &entry.record;
+            let class = if entry.unique {
+                Class::from_int(record.class().to_int() | CACHE_FLUSH)
+            } else {
+                record.class()
+            };
+            let ttl = if goodbye { Ttl::ZERO } else { record.ttl() };
+            let res = msg.push(Record::new(
+                record.owner(),
+                class,
+                ttl,
+                record.data(),
+            ));
+            if res.is_err() || msg.as_slice().len() > MAX_MESSAGE_SIZE {
+                break;
+            }
+        }
+        msg.into_message()
+    }
+
+    fn handle_query<Octs: Octets>(
+        &mut self,
+        msg: &Message<Octs>,
+        now: Instant,
+    ) {
+        let probe = msg.header_counts().nscount() > 0;
+        if probe {
+            self.tiebreak(msg, now);
+        }
+        let known: Vec<_> = match msg.answer() {
+            Ok(answer) => answer
+                .limit_to::<ZoneRecordData<_, ParsedDname<_>>>()
+                .flatten()
+                .collect(),
+            Err(_) => return,
+        };
+
+        // When defending our names against probes, we may answer more
+        // often.
+        let interval = if probe {
+            PROBE_INTERVAL
+        } else {
+            MULTICAST_INTERVAL
+        };
+        let mut records = Vec::new();
+        let mut unique = true;
+        for question in msg.question().flatten() {
+            for idx in 0..self.entries.len() {
+                let entry = &self.entries[idx];
+                if !entry.matches(&question)
+                    || !self.is_ready(idx)
+                    || entry.last_multicast.map_or(false, |last| {
+                        now.saturating_duration_since(last) < interval
+                    })
+                {
+                    continue;
+                }
+                // Known-answer suppression, section 7.1.
+                let suppressed = known.iter().any(|known| {
+                    known.owner() == entry.record.owner()
+                        && known.data() == entry.record.data()
+                        && known.ttl().as_secs()
+                            >= entry.record.ttl().as_secs() / 2
+                });
+                if !suppressed && !records.contains(&idx) {
+                    unique &= entry.unique;
+                    records.push(idx)
+                }
+            }
+        }
+        if records.is_empty() {
+            return;
+        }
+
+        // Answers with only unique records go out right away. Everything
+        // else waits a bit to be aggregated.
+        let due = if unique {
+            now
+        } else {
+            now + Duration::from_millis(
+                rand::thread_rng()
+                    .gen_range(AGGREGATE_DELAY.0..=AGGREGATE_DELAY.1),
+            )
+        };
+        match self.answers.as_mut() {
+            Some(answers) => {
+                answers.due = answers.due.min(due);
+                for idx in records {
+                    if !answers.records.contains(&idx) {
+                        answers.records.push(idx)
+                    }
+                }
+            }
+            None => self.answers = Some(PendingAnswers { due, records }),
+        }
+    }
+
+    /// Resolves simultaneous probing, section 8.2.
+    ///
+    /// If the proposed records of another host’s probe for a name we are
+    /// probing are lexicographically later than ours, we lose and probe
+    /// again after a second.
+    fn tiebreak<Octs: Octets>(&mut self, msg: &Message<Octs>, now: Instant) {
+        let theirs: Vec<_> = match msg.authority() {
+            Ok(authority) => authority
+                .limit_to::<ZoneRecordData<_, ParsedDname<_>>>()
+                .flatten()
+                .collect(),
+            Err(_) => return,
+        };
+        for name in &mut self.names {
+            if !matches!(name.state, NameState::Probing { .. }) {
+                continue;
+            }
+            let mut their_keys: Vec<_> = theirs
+                .iter()
+                .filter(|record| record.owner() == &name.name)
+                .map(|record| tiebreak_key(record.class(), record.data()))
+                .collect();
+            if their_keys.is_empty() {
+                continue;
+            }
+            let mut our_keys: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|entry| {
+                    entry.unique && entry.record.owner() == &name.name
+                })
+                .map(|entry| {
+                    tiebreak_key(entry.record.class(), entry.record.data())
+                })
+                .collect();
+            their_keys.sort();
+            our_keys.sort();
+            if their_keys > our_keys {
+                name.state = NameState::Probing {
+                    sent: 0,
+                    next: now + PROBE_DEFER,
+                };
+            }
+        }
+    }
+
+    fn handle_response<Octs: Octets>(
+        &mut self,
+        msg: &Message<Octs>,
+        now: Instant,
+    ) {
+        let mut sections = Vec::new();
+        if let Ok(answer) = msg.answer() {
+            sections.push(answer);
+        }
+        if let Ok(additional) = msg.additional() {
+            sections.push(additional);
+        }
+        let mut conflicts = Vec::new();
+        for section in sections {
+            let records = section
+                .limit_to::<ZoneRecordData<_, ParsedDname<_>>>()
+                .flatten();
+            for record in records {
+                if record.ttl() == Ttl::ZERO {
+                    // Goodbyes never conflict.
+                    continue;
+                }
+                let class = strip_cache_flush(record.class());
+                let pos = self
+                    .names
+                    .iter()
+                    .position(|name| record.owner() == &name.name);
+                let pos = match pos {
+                    Some(pos) if !conflicts.contains(&pos) => pos,
+                    _ => continue,
+                };
+                let probing = matches!(
+                    self.names[pos].state,
+                    NameState::Probing { .. }
+                );
+                let mut same_type = self.entries.iter().filter(|entry| {
+                    entry.unique
+                        && entry.record.owner() == record.owner()
+                        && entry.record.class() == class
+                        && (probing || entry.record.rtype() == record.rtype())
+                });
+                let mut same_type = same_type.by_ref().peekable();
+                // After probing, only records of a type we have can
+                // conflict (section 9). During probing, any record for the
+                // name that isn’t ours does (section 8.1).
+                if !probing && same_type.peek().is_none() {
+                    continue;
+                }
+                if !same_type
+                    .any(|entry| entry.record.data() == record.data())
+                {
+                    conflicts.push(pos)
+                }
+            }
+        }
+        for pos in conflicts {
+            self.rename(pos, now)
+        }
+    }
+
+    /// Renames a unique name after a conflict and starts probing again.
+    fn rename(&mut self, pos: usize, now: Instant) {
+        let old = self.names[pos].name.clone();
+        let new = next_name(&old);
+        for entry in &mut self.entries {
+            if entry.record.owner() == &old {
+                entry.record = Record::new(
+                    new.clone(),
+                    entry.record.class(),
+                    entry.record.ttl(),
+                    entry.record.data().clone(),
+                );
+                entry.last_multicast = None;
+                if entry.unique {
+                    entry.announce_left = 0;
+                } else {
+                    entry.start_announcing(now);
+                }
+            } else if let Some(data) =
+                replace_target(entry.record.data(), &old, &new)
+            {
+                entry.record = Record::new(
+                    entry.record.owner().clone(),
+                    entry.record.class(),
+                    entry.record.ttl(),
+                    data,
+                );
+                entry.start_announcing(now);
+            }
+        }
+        self.names[pos] = UniqueName {
+            name: new.clone(),
+            state: NameState::probing(now),
+        };
+        self.renames.push((old, new));
+    }
+}
+
+//------------ Entry ---------------------------------------------------------
+
+/// A record published by a responder.
+#[derive(Clone, Debug)]
+struct Entry {
+    /// The record.
+    record: MdnsRecord,
+
+    /// Whether the record is unique.
+    unique: bool,
+
+    /// The number of announcements still to be sent.
+    announce_left: u8,
+
+    /// When the next announcement is due.
+    next_announce: Instant,
+
+    /// When the record was last multicast.
+    last_multicast: Option<Instant>,
+}
+
+impl Entry {
+    fn start_announcing(&mut self, now: Instant) {
+        self.announce_left = ANNOUNCE_COUNT;
+        self.next_announce = now;
+    }
+
+    /// Returns whether the record answers a question.
+    fn matches<N: ToDname>(&self, question: &Question<N>) -> bool {
+        let qclass = strip_cache_flush(question.qclass());
+        self.record.owner() == question.qname()
+            && (question.qtype() == Rtype::Any
+                || question.qtype() == self.record.rtype())
+            && (qclass == Class::Any || qclass == self.record.class())
+    }
+}
+
+//------------ UniqueName ----------------------------------------------------
+
+/// The owner name of unique records.
+#[derive(Clone, Debug)]
+struct UniqueName {
+    /// The name.
+    name: Dname<Bytes>,
+
+    /// Where we are with claiming the name.
+    state: NameState,
+}
+
+//------------ NameState -----------------------------------------------------
+
+/// The state of claiming a unique name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum NameState {
+    /// We are probing the name.
+    ///
+    /// `sent` probes have been sent so far. The next step is due at `next`.
+    Probing { sent: u8, next: Instant },
+
+    /// The name is ours.
+    Established,
+}
+
+impl NameState {
+    /// Starts probing after a random delay of up to 250 ms (section 8.1).
+    fn probing(now: Instant) -> Self {
+        let delay = rand::thread_rng().gen_range(0..=250);
+        NameState::Probing {
+            sent: 0,
+            next: now + Duration::from_millis(delay),
+        }
+    }
+}
+
+//------------ PendingAnswers ------------------------------------------------
+
+/// Answers waiting to be sent.
+#[derive(Clone, Debug)]
+struct PendingAnswers {
+    /// When the answers are due.
+    due: Instant,
+
+    /// The indexes of the entries to be sent.
+    records: Vec<usize>,
+}
+
+//------------ MdnsServer ----------------------------------------------------
+
+/// A multicast DNS server running a responder on an IPv4 socket.
+pub struct MdnsServer {
+    /// The socket joined to the multicast group.
+    socket: UdpSocket,
+
+    /// The responder.
+    responder: Responder,
+}
+
+impl MdnsServer {
+    /// Creates a new server for the given interface address.
+    ///
+    /// The socket is bound to the multicast DNS port with address reuse
+    /// enabled, so that other responders on the same host keep working,
+    /// and joins the multicast group on the interface. Use
+    /// [`Ipv4Addr::UNSPECIFIED`] for the system’s default interface.
+    ///
+    /// This needs to be called from within a Tokio runtime.
+    pub fn bind_v4(
+        interface: Ipv4Addr,
+        responder: Responder,
+    ) -> Result<Self, io::Error> {
+        let socket =
+            Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(all(
+            unix,
+            not(any(target_os = "solaris", target_os = "illumos"))
+        ))]
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(
+            &SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into(),
+        )?;
+        socket.join_multicast_v4(&MDNS_IPV4_GROUP, &interface)?;
+        socket.set_multicast_if_v4(&interface)?;
+        // Section 11: all multicast DNS messages are sent with a TTL of
+        // 255.
+        socket.set_multicast_ttl_v4(255)?;
+        Ok(MdnsServer {
+            socket: UdpSocket::from_std(socket.into())?,
+            responder,
+        })
+    }
+
+    /// Runs the server until `shutdown` completes.
+    ///
+    /// Once `shutdown` has completed, the server sends the goodbye response
+    /// and returns.
+    pub async fn run(
+        mut self,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), io::Error> {
+        let group =
+            SocketAddr::V4(SocketAddrV4::new(MDNS_IPV4_GROUP, MDNS_PORT));
+        let mut buf = vec![0; MAX_MESSAGE_SIZE];
+        tokio::pin!(shutdown);
+        loop {
+            for msg in self.responder.poll(Instant::now()) {
+                self.socket.send_to(msg.as_slice(), group).await?;
+            }
+            let timeout = self.responder.next_timeout();
+            let sleep = async {
+                match timeout {
+                    Some(when) => tokio::time::sleep_until(when.into()).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            tokio::select! {
+                res = self.socket.recv_from(&mut buf) => {
+                    let (len, _) = res?;
+                    if let Ok(msg) = Message::from_octets(&buf[..len]) {
+                        self.responder.handle(&msg, Instant::now());
+                    }
+                }
+                _ = sleep => { }
+                _ = &mut shutdown => break,
+            }
+        }
+        if let Some(msg) = self.responder.goodbye() {
+            self.socket.send_to(msg.as_slice(), group).await?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Removes the cache-flush or unicast-response bit from a class.
+fn strip_cache_flush(class: Class) -> Class {
+    Class::from_int(class.to_int() & !CACHE_FLUSH)
+}
+
+/// Returns the key for comparing a record during tiebreaking.
+///
+/// Records are compared by class, type, and the raw record data.
+fn tiebreak_key(
+    class: Class,
+    data: &impl ComposeRecordData,
+) -> (u16, u16, Vec<u8>) {
+    let mut rdata = Vec::new();
+    let _ = data.compose_rdata(&mut rdata);
+    (
+        strip_cache_flush(class).to_int(),
+        data.rtype().to_int(),
+        rdata,
+    )
+}
+
+/// Returns the name to use after a conflict.
+///
+/// If the first label ends in a hyphen followed by a number, the number is
+/// increased. Otherwise `-2` is appended to the label.
+fn next_name(name: &Dname<Bytes>) -> Dname<Bytes> {
+    let mut labels = name.iter();
+    let first = match labels.next() {
+        Some(label) if !label.is_root() => label.as_slice(),
+        _ => return name.clone(),
+    };
+    let (base, number) = match first.iter().rposition(|&ch| ch == b'-') {
+        Some(pos) => {
+            match core::str::from_utf8(&first[pos + 1..])
+                .ok()
+                .and_then(|num| num.parse::<u32>().ok())
+            {
+                Some(num) => (&first[..pos], num.saturating_add(1)),
+                None => (first, 2),
+            }
+        }
+        None => (first, 2),
+    };
+    let suffix = format!("-{}", number);
+    let base = &base[..base.len().min(63 - suffix.len())];
+    let mut builder = DnameBuilder::new_bytes();
+    // The label fits and the rest of the name was valid before.
+    builder.append_slice(base).unwrap();
+    builder.append_slice(suffix.as_bytes()).unwrap();
+    builder.end_label();
+    builder
+        .append_origin(&name.parent().expect("non-root name"))
+        .unwrap()
+}
+
+/// Returns the record data with the target name replaced if it is `old`.
+fn replace_target(
+    data: &ZoneRecordData<Bytes, Dname<Bytes>>,
+    old: &Dname<Bytes>,
+    new: &Dname<Bytes>,
+) -> Option<ZoneRecordData<Bytes, Dname<Bytes>>> {
+    match data {
+        ZoneRecordData::Ptr(ptr) if ptr.ptrdname() == old => {
+            Some(ZoneRecordData::Ptr(Ptr::new(new.clone())))
+        }
+        ZoneRecordData::Cname(cname) if cname.cname() == old => {
+            Some(ZoneRecordData::Cname(Cname::new(new.clone())))
+        }
+        ZoneRecordData::Srv(srv) if srv.target() == old => {
+            Some(ZoneRecordData::Srv(Srv::new(
+                srv.priority(),
+                srv.weight(),
+                srv.port(),
+                new.clone(),
+            )))
+        }
+        _ => None,
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::A;
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn a(owner: &str, addr: u8) -> MdnsRecord {
+        Record::new(
+            name(owner),
+            Class::In,
+            Ttl::from_secs(120),
+            ZoneRecordData::A(A::from_octets(192, 0, 2, addr)),
+        )
+    }
+
+    fn ptr(owner: &str, target: &str) -> MdnsRecord {
+        Record::new(
+            name(owner),
+            Class::In,
+            Ttl::from_secs(4500),
+            ZoneRecordData::Ptr(Ptr::new(name(target))),
+        )
+    }
+
+    /// Creates a message from another host.
+    fn message(
+        qr: bool,
+        questions: &[(&str, Rtype)],
+        answers: &[MdnsRecord],
+        authority: &[MdnsRecord],
+    ) -> Message<Bytes> {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(qr);
+        let mut msg = msg.question();
+        for &(qname, qtype) in questions {
+            msg.push((name(qname), qtype)).unwrap();
+        }
+        let mut msg = msg.answer();
+        for record in answers {
+            msg.push(record).unwrap();
+        }
+        let mut msg = msg.authority();
+        for record in authority {
+            msg.push(record).unwrap();
+        }
+        msg.into_message()
+    }
+
+    type ParsedRecord =
+        Record<ParsedDname<Bytes>, ZoneRecordData<Bytes, ParsedDname<Bytes>>>;
+
+    fn records(msg: &Message<Bytes>) -> Vec<ParsedRecord> {
+        msg.answer()
+            .unwrap()
+            .limit_to::<ZoneRecordData<_, ParsedDname<_>>>()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// Polls the responder at its next timeout.
+    fn step(responder: &mut Responder) -> (Instant, Vec<Message<Bytes>>) {
+        let now = responder.next_timeout().unwrap();
+        (now, responder.poll(now))
+    }
+
+    /// Runs the responder until all names are established.
+    fn establish(responder: &mut Responder) -> Instant {
+        loop {
+            let (now, _) = step(responder);
+            if responder.is_established() {
+                return now;
+            }
+        }
+    }
+
+    #[test]
+    fn probe_and_announce() {
+        let start = Instant::now();
+        let mut responder = Responder::new();
+        responder.add_unique(a("host.local", 1), start);
+        assert!(!responder.is_established());
+
+        let mut last = start;
+        for i in 0..PROBE_COUNT {
+            let (now, msgs) = step(&mut responder);
+            assert!(now >= last);
+            if i > 0 {
+                assert_eq!(now - last, PROBE_INTERVAL);
+            }
+            last = now;
+            assert_eq!(msgs.len(), 1);
+            let probe = &msgs[0];
+            assert!(!probe.header().qr());
+            let question = probe.sole_question().unwrap();
+            assert_eq!(question.qname(), &name("host.local"));
+            assert_eq!(question.qtype(), Rtype::Any);
+            assert_eq!(question.qclass().to_int() & CACHE_FLUSH != 0, i == 0);
+            assert_eq!(probe.header_counts().nscount(), 1);
+        }
+
+        // The first announcement comes after the last probe’s wait.
+        let (now, msgs) = step(&mut responder);
+        assert_eq!(now - last, PROBE_INTERVAL);
+        assert!(responder.is_established());
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].header().qr());
+        assert!(msgs[0].header().aa());
+        let answer = records(&msgs[0]);
+        assert_eq!(answer.len(), 1);
+        assert_eq!(
+            answer[0].class().to_int(),
+            Class::In.to_int() | CACHE_FLUSH
+        );
+
+        let (second, msgs) = step(&mut responder);
+        assert_eq!(second - now, ANNOUNCE_INTERVAL);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(responder.next_timeout(), None);
+
+        // Queries are answered right away since the record is unique.
+        let later = second + Duration::from_secs(5);
+        responder.handle(
+            &message(false, &[("HOST.local", Rtype::A)], &[], &[]),
+            later,
+        );
+        assert_eq!(responder.next_timeout(), Some(later));
+        assert_eq!(records(&responder.poll(later)[0]).len(), 1);
+
+        // Within a second, the record isn’t multicast again.
+        let soon = later + Duration::from_millis(500);
+        responder.handle(
+            &message(false, &[("host.local", Rtype::Any)], &[], &[]),
+            soon,
+        );
+        assert_eq!(responder.next_timeout(), None);
+    }
+
+    #[test]
+    fn conflict() {
+        let start = Instant::now();
+        let mut responder = Responder::new();
+        responder.add_unique(a("host.local", 1), start);
+        responder.add_shared(ptr("_ws._tcp.local", "host.local"), start);
+
+        // The shared record is announced without probing.
+        let (now, msgs) = step(&mut responder);
+        assert!(msgs.iter().any(|msg| msg.header().qr()));
+
+        // Another host already uses the name.
+        responder
+            .handle(&message(true, &[], &[a("host.local", 99)], &[]), now);
+        assert_eq!(
+            responder.take_renames(),
+            [(name("host.local"), name("host-2.local"))]
+        );
+        assert!(responder.take_renames().is_empty());
+
+        // Probing starts over for the new name and the PTR record pointing
+        // to it is announced again.
+        let now = establish(&mut responder);
+        let msgs = responder.poll(now);
+        assert!(msgs.is_empty());
+        let goodbye = responder.clone().goodbye().unwrap();
+        let goodbye = records(&goodbye);
+        assert!(goodbye
+            .iter()
+            .any(|record| record.owner() == &name("host-2.local")));
+        let moved = ptr("_ws._tcp.local", "host-2.local");
+        assert!(goodbye.iter().any(|record| record.data() == moved.data()));
+
+        // A conflict after probing renames again.
+        responder
+            .handle(&message(true, &[], &[a("host-2.local", 99)], &[]), now);
+        assert_eq!(
+            responder.take_renames(),
+            [(name("host-2.local"), name("host-3.local"))]
+        );
+
+        // Our own records, records of other types, and goodbyes don’t
+        // conflict.
+        let mut responder = Responder::new();
+        responder.add_unique(a("host.local", 1), start);
+        let now = establish(&mut responder);
+        let mut goodbye = a("host.local", 99);
+        goodbye.set_ttl(Ttl::ZERO);
+        responder.handle(
+            &message(
+                true,
+                &[],
+                &[a("host.local", 1), goodbye, ptr("host.local", "a.local")],
+                &[],
+            ),
+            now,
+        );
+        assert!(responder.take_renames().is_empty());
+    }
+
+    #[test]
+    fn next_names() {
+        assert_eq!(next_name(&name("host.local")), name("host-2.local"));
+        assert_eq!(next_name(&name("host-2.local")), name("host-3.local"));
+        assert_eq!(
+            next_name(&name("my-host.local")),
+            name("my-host-2.local")
+        );
+        assert_eq!(next_name(&name("host-.local")), name("host--2.local"));
+    }
+
+    #[test]
+    fn aggregate_shared() {
+        let start = Instant::now();
+        let mut responder = Responder::new();
+        responder.add_shared(ptr("_a._tcp.local", "one.local"), start);
+        responder.add_shared(ptr("_b._tcp.local", "two.local"), start);
+        let (_, msgs) = step(&mut responder);
+        assert_eq!(records(&msgs[0]).len(), 2);
+        let (now, _) = step(&mut responder);
+        assert_eq!(responder.next_timeout(), None);
+
+        let now = now + Duration::from_secs(5);
+        responder.handle(
+            &message(false, &[("_a._tcp.local", Rtype::Ptr)], &[], &[]),
+            now,
+        );
+        let due = responder.next_timeout().unwrap();
+        assert!(due >= now + Duration::from_millis(AGGREGATE_DELAY.0));
+        assert!(due <= now + Duration::from_millis(AGGREGATE_DELAY.1));
+        responder.handle(
+            &message(false, &[("_b._tcp.local", Rtype::Ptr)], &[], &[]),
+            now + Duration::from_millis(10),
+        );
+        assert!(responder.poll(now).is_empty());
+        let msgs = responder.poll(due);
+        assert_eq!(msgs.len(), 1);
+        let answer = records(&msgs[0]);
+        assert_eq!(answer.len(), 2);
+        assert!(answer.iter().all(|record| record.class() == Class::In));
+
+        // Known answers are suppressed.
+        let now = due + Duration::from_secs(5);
+        responder.handle(
+            &message(
+                false,
+                &[("_a._tcp.local", Rtype::Ptr)],
+                &[ptr("_a._tcp.local", "one.local")],
+                &[],
+            ),
+            now,
+        );
+        assert_eq!(responder.next_timeout(), None);
+    }
+
+    #[test]
+    fn tiebreak() {
+        let start = Instant::now();
+        let mut responder = Responder::new();
+        responder.add_unique(a("host.local", 2), start);
+        let (now, _) = step(&mut responder);
+
+        // We win against a lexicographically earlier probe.
+        responder.handle(
+            &message(
+                false,
+                &[("host.local", Rtype::Any)],
+                &[],
+                &[a("host.local", 1)],
+            ),
+            now,
+        );
+        assert_eq!(responder.next_timeout(), Some(now + PROBE_INTERVAL));
+
+        // We lose against a later one and start over after a second.
+        responder.handle(
+            &message(
+                false,
+                &[("host.local", Rtype::Any)],
+                &[],
+                &[a("host.local", 3)],
+            ),
+            now,
+        );
+        assert_eq!(responder.next_timeout(), Some(now + PROBE_DEFER));
+        let (_, msgs) = step(&mut responder);
+        let question = msgs[0].sole_question().unwrap();
+        assert_ne!(question.qclass().to_int() & CACHE_FLUSH, 0);
+        assert!(responder.take_renames().is_empty());
+    }
+
+    #[test]
+    fn goodbye() {
+        let start = Instant::now();
+        let mut responder = Responder::new();
+        responder.add_unique(a("host.local", 1), start);
+        responder.add_unique(a("other.local", 1), start);
+        assert!(responder.clone().goodbye().is_none());
+
+        establish(&mut responder);
+        let goodbye = responder.goodbye().unwrap();
+        assert!(goodbye.header().qr());
+        let goodbye = records(&goodbye);
+        assert_eq!(goodbye.len(), 2);
+        assert!(goodbye.iter().all(|record| record.ttl() == Ttl::ZERO));
+    }
+}
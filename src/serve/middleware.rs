@@ -0,0 +1,551 @@
+//! Composing services from layers of middleware.
+//!
+//! Many concerns of a server such as logging, collecting metrics, or rate
+//! limiting are independent of how requests are actually answered. They
+//! can be implemented as services that wrap another service, processing
+//! the request before handing it on and the response before returning it.
+//!
+//! A [`Layer`] produces such a wrapping service from the inner service.
+//! Layers are stacked with a [`ServiceBuilder`] around the core service:
+//!
+//! ```
+//! use bytes::Bytes;
+//! use domain::base::iana::Rcode;
+//! use domain::base::{Message, MessageBuilder};
+//! use domain::serve::middleware::{
+//!     FilterLayer, MapResponseLayer, ServiceBuilder,
+//! };
+//! use domain::serve::rrl::{RrlConfig, RrlLayer};
+//! use domain::serve::Request;
+//!
+//! async fn refuse(request: Request) -> Option<Message<Bytes>> {
+//!     let answer = MessageBuilder::new_bytes()
+//!         .start_answer(request.message(), Rcode::Refused)
+//!         .ok()?;
+//!     Some(answer.into_message())
+//! }
+//!
+//! let service = ServiceBuilder::new()
+//!     // Only accept encrypted requests. Drop everything else.
+//!     .layer(FilterLayer::new(|request| {
+//!         if request.transport().is_encrypted() {
+//!             Ok(())
+//!         } else {
+//!             Err(None)
+//!         }
+//!     }))
+//!     .layer(MapResponseLayer::new(
+//!         |request, response| {
+//!             println!(
+//!                 "{}: {}",
+//!                 request.client_addr(),
+//!                 response.header().rcode()
+//!             );
+//!             Some(response)
+//!         },
+//!     ))
+//!     .layer(RrlLayer::new(RrlConfig::default()))
+//!     .service(refuse);
+//! ```
+//!
+//! The first layer added is the outermost one. It sees the request first
+//! and the response last.
+
+use super::service::{Request, Service};
+use crate::base::iana::Rtype;
+use crate::base::message::Message;
+use crate::metrics::Metrics;
+use bytes::Bytes;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use std::boxed::Box;
+use std::sync::Arc;
+use std::time::Instant;
+
+//------------ Layer ---------------------------------------------------------
+
+/// A type that wraps a service into another service.
+pub trait Layer<S> {
+    /// The wrapping service.
+    type Service;
+
+    /// Wraps the given service.
+    fn layer(&self, inner: S) -> Self::Service;
+}
+
+//------------ Identity ------------------------------------------------------
+
+/// A layer that doesn’t wrap the service at all.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl<S> Layer<S> for Identity {
+    type Service = S;
+
+    fn layer(&self, inner: S) -> S {
+        inner
+    }
+}
+
+//------------ Stack ---------------------------------------------------------
+
+/// Two layers stacked onto each other.
+///
+/// The service is first wrapped by `inner` and then by `outer`.
+#[derive(Clone, Debug)]
+pub struct Stack<Inner, Outer> {
+    /// The layer applied first.
+    inner: Inner,
+
+    /// The layer applied second.
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Creates a new stack from its two layers.
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Stack { inner, outer }
+    }
+}
+
+impl<S, Inner, Outer> Layer<S> for Stack<Inner, Outer>
+where
+    Inner: Layer<S>,
+    Outer: Layer<Inner::Service>,
+{
+    type Service = Outer::Service;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+//------------ ServiceBuilder ------------------------------------------------
+
+/// Builds a service by wrapping it in layers.
+///
+/// Layers are added via [`layer`][Self::layer] from the outside in. The
+/// core service is finally given to [`service`][Self::service].
+#[derive(Clone, Debug)]
+pub struct ServiceBuilder<L> {
+    /// The layers added so far.
+    layer: L,
+}
+
+impl ServiceBuilder<Identity> {
+    /// Creates a new builder without any layers.
+    pub fn new() -> Self {
+        ServiceBuilder { layer: Identity }
+    }
+}
+
+impl Default for ServiceBuilder<Identity> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L> ServiceBuilder<L> {
+    /// Adds a layer inside the layers added so far.
+    pub fn layer<T>(self, layer: T) -> ServiceBuilder<Stack<T, L>> {
+        ServiceBuilder {
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Returns the stack of layers.
+    pub fn into_inner(self) -> L {
+        self.layer
+    }
+
+    /// Wraps a service in all the layers.
+    pub fn service<S>(&self, service: S) -> L::Service
+    where
+        L: Layer<S>,
+    {
+        self.layer.layer(service)
+    }
+}
+
+//------------ MapResponse ---------------------------------------------------
+
+/// A service processing the responses of another service.
+///
+/// The closure receives the request and the response of the inner service
+/// and returns the response to send, if any. It isn’t called if the inner
+/// service doesn’t produce a response.
+pub struct MapResponse<S, F> {
+    /// The inner service.
+    service: S,
+
+    /// The closure processing responses.
+    op: Arc<F>,
+}
+
+impl<S, F> MapResponse<S, F> {
+    /// Creates a new service wrapping `service`.
+    pub fn new(service: S, op: F) -> Self
+    where
+        F: Fn(&Request, Message<Bytes>) -> Option<Message<Bytes>>,
+    {
+        MapResponse {
+            service,
+            op: Arc::new(op),
+        }
+    }
+}
+
+impl<S, F> Service for MapResponse<S, F>
+where
+    S: Service,
+    F: Fn(&Request, Message<Bytes>) -> Option<Message<Bytes>>
+        + Send
+        + Sync
+        + 'static,
+{
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let op = self.op.clone();
+        let response = self.service.call(request.clone());
+        Box::pin(async move { op(&request, response.await?) })
+    }
+}
+
+impl<S, F> fmt::Debug for MapResponse<S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapResponse").finish_non_exhaustive()
+    }
+}
+
+//------------ MapResponseLayer ----------------------------------------------
+
+/// A layer producing [`MapResponse`] services.
+#[derive(Clone)]
+pub struct MapResponseLayer<F> {
+    /// The closure processing responses.
+    op: Arc<F>,
+}
+
+impl<F> MapResponseLayer<F> {
+    /// Creates a new layer using the given closure.
+    pub fn new(op: F) -> Self
+    where
+        F: Fn(&Request, Message<Bytes>) -> Option<Message<Bytes>>,
+    {
+        MapResponseLayer { op: Arc::new(op) }
+    }
+}
+
+impl<S, F> Layer<S> for MapResponseLayer<F> {
+    type Service = MapResponse<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MapResponse {
+            service: inner,
+            op: self.op.clone(),
+        }
+    }
+}
+
+impl<F> fmt::Debug for MapResponseLayer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapResponseLayer").finish_non_exhaustive()
+    }
+}
+
+//------------ Filter --------------------------------------------------------
+
+/// A service checking requests before handing them to another service.
+///
+/// The closure receives each request. If it returns `Ok(())`, the request
+/// is processed by the inner service. Otherwise, the inner service isn’t
+/// called and the response contained in the error is sent instead, if
+/// any.
+pub struct Filter<S, F> {
+    /// The inner service.
+    service: S,
+
+    /// The closure checking requests.
+    check: Arc<F>,
+}
+
+impl<S, F> Filter<S, F> {
+    /// Creates a new service wrapping `service`.
+    pub fn new(service: S, check: F) -> Self
+    where
+        F: Fn(&Request) -> Result<(), Option<Message<Bytes>>>,
+    {
+        Filter {
+            service,
+            check: Arc::new(check),
+        }
+    }
+}
+
+impl<S, F> Service for Filter<S, F>
+where
+    S: Service,
+    F: Fn(&Request) -> Result<(), Option<Message<Bytes>>>
+        + Send
+        + Sync
+        + 'static,
+{
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        match (self.check)(&request) {
+            Ok(()) => Box::pin(self.service.call(request)),
+            Err(response) => Box::pin(async move { response }),
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for Filter<S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Filter").finish_non_exhaustive()
+    }
+}
+
+//------------ FilterLayer ---------------------------------------------------
+
+/// A layer producing [`Filter`] services.
+#[derive(Clone)]
+pub struct FilterLayer<F> {
+    /// The closure checking requests.
+    check: Arc<F>,
+}
+
+impl<F> FilterLayer<F> {
+    /// Creates a new layer using the given closure.
+    pub fn new(check: F) -> Self
+    where
+        F: Fn(&Request) -> Result<(), Option<Message<Bytes>>>,
+    {
+        FilterLayer {
+            check: Arc::new(check),
+        }
+    }
+}
+
+impl<S, F> Layer<S> for FilterLayer<F> {
+    type Service = Filter<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Filter {
+            service: inner,
+            check: self.check.clone(),
+        }
+    }
+}
+
+impl<F> fmt::Debug for FilterLayer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FilterLayer").finish_non_exhaustive()
+    }
+}
+
+//------------ Instrument ----------------------------------------------------
+
+/// A service reporting the requests of another service to metrics.
+///
+/// For every request, the service reports the query type, the rcode of
+/// the response, and the processing time via
+/// [`Metrics::request_served`]. Responses to AXFR and IXFR requests are
+/// also reported via [`Metrics::transfer_sent`].
+pub struct Instrument<S> {
+    /// The inner service.
+    service: S,
+
+    /// The metrics to report to.
+    metrics: Arc<dyn Metrics>,
+}
+
+impl<S> Instrument<S> {
+    /// Creates a new service wrapping `service`.
+    pub fn new(service: S, metrics: impl Metrics + 'static) -> Self {
+        Instrument {
+            service,
+            metrics: Arc::new(metrics),
+        }
+    }
+
+    pub fn metrics(&self) -> &dyn Metrics {
+        self.metrics.as_ref()
+    }
+}
+
+impl<S: Service> Service for Instrument<S> {
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let metrics = self.metrics.clone();
+        let qtype = request
+            .message()
+            .first_question()
+            .map(|question| question.qtype());
+        let start = Instant::now();
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            metrics.request_served(
+                qtype,
+                response.as_ref().map(|response| response.header().rcode()),
+                start.elapsed(),
+            );
+            if let (Some(Rtype::Axfr | Rtype::Ixfr), Some(response)) =
+                (qtype, response.as_ref())
+            {
+                metrics.transfer_sent(response.as_slice().len())
+            }
+            response
+        })
+    }
+}
+
+impl<S> fmt::Debug for Instrument<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Instrument").finish_non_exhaustive()
+    }
+}
+
+//------------ InstrumentLayer -----------------------------------------------
+
+/// A layer producing [`Instrument`] services.
+///
+/// All services produced by the layer report to the same metrics.
+#[derive(Clone)]
+pub struct InstrumentLayer {
+    /// The metrics to report to.
+    metrics: Arc<dyn Metrics>,
+}
+
+impl InstrumentLayer {
+    /// Creates a new layer reporting to the given metrics.
+    pub fn new(metrics: impl Metrics + 'static) -> Self {
+        InstrumentLayer {
+            metrics: Arc::new(metrics),
+        }
+    }
+}
+
+impl<S> Layer<S> for InstrumentLayer {
+    type Service = Instrument<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Instrument {
+            service: inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for InstrumentLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InstrumentLayer").finish_non_exhaustive()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::metrics::Counters;
+    use crate::serve::Transport;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    async fn answer(request: Request) -> Option<Message<Bytes>> {
+        let answer = MessageBuilder::new_bytes()
+            .start_answer(request.message(), Rcode::NoError)
+            .ok()?;
+        Some(answer.into_message())
+    }
+
+    fn request(id: u16) -> Request {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_id(id);
+        Request::new(
+            msg.into_message(),
+            "192.0.2.1:53".parse().unwrap(),
+            Transport::Udp,
+        )
+    }
+
+    #[tokio::test]
+    async fn stack() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (first, second) = (log.clone(), log.clone());
+        let service = ServiceBuilder::new()
+            .layer(MapResponseLayer::new(move |_, response| {
+                first.lock().unwrap().push("outer");
+                Some(response)
+            }))
+            .layer(FilterLayer::new(|request| {
+                match request.message().header().id() {
+                    1 => Err(None),
+                    2 => {
+                        let mut refused = MessageBuilder::new_bytes();
+                        refused.header_mut().set_rcode(Rcode::Refused);
+                        Err(Some(refused.into_message()))
+                    }
+                    _ => Ok(()),
+                }
+            }))
+            .layer(MapResponseLayer::new(move |_, response| {
+                second.lock().unwrap().push("inner");
+                Some(response)
+            }))
+            .service(answer);
+
+        let response = service.call(request(0)).await.unwrap();
+        assert_eq!(response.header().rcode(), Rcode::NoError);
+        assert_eq!(*log.lock().unwrap(), ["inner", "outer"]);
+
+        log.lock().unwrap().clear();
+        assert!(service.call(request(1)).await.is_none());
+        let response = service.call(request(2)).await.unwrap();
+        assert_eq!(response.header().rcode(), Rcode::Refused);
+        assert_eq!(*log.lock().unwrap(), ["outer"]);
+    }
+
+    #[tokio::test]
+    async fn instrument() {
+        let counters = Arc::new(Counters::new());
+        let service = ServiceBuilder::new()
+            .layer(InstrumentLayer::new(counters.clone()))
+            .layer(FilterLayer::new(|request| {
+                if request.message().header().id() == 1 {
+                    Err(None)
+                } else {
+                    Ok(())
+                }
+            }))
+            .service(answer);
+
+        let mut msg = MessageBuilder::new_bytes().question();
+        msg.push((Dname::root_bytes(), Rtype::Axfr)).unwrap();
+        let axfr = Request::new(
+            msg.into_message(),
+            "192.0.2.1:53".parse().unwrap(),
+            Transport::Tcp,
+        );
+        let len = service.call(axfr).await.unwrap().as_slice().len();
+        assert!(service.call(request(0)).await.is_some());
+        assert!(service.call(request(1)).await.is_none());
+
+        assert_eq!(counters.requests(Some(Rtype::Axfr)), 1);
+        assert_eq!(counters.requests(None), 2);
+        assert_eq!(counters.responses(Rcode::NoError), 2);
+        assert_eq!(counters.dropped(), 1);
+        assert_eq!(counters.transfer_octets(), len as u64);
+    }
+}
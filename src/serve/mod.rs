@@ -0,0 +1,164 @@
+//! Serving DNS requests.
+//!
+//! This module provides the building blocks for DNS servers such as
+//! authoritative name servers or forwarders. It takes care of receiving
+//! requests over the network and of sending responses back, leaving the
+//! actual processing of requests to a [`Service`].
+//!
+//! A service is a type that maps a [`Request`] to a future resolving into
+//! the response message or `None` if no response should be sent. The
+//! trait is implemented for closures, so simple services can be written
+//! inline.
+//!
+//! The following servers are available that run a service on sockets:
+//!
+//! * [`DgramServer`] receives requests over UDP. It can bind a number of
+//!   sockets to the same address using `SO_REUSEPORT` where available so
+//!   that the operating system spreads the requests over multiple
+//!   receiving tasks.
+//! * [`StreamServer`] accepts TCP connections and receives requests over
+//!   them using the two octet length prefix defined in RFC 1035. Requests
+//!   received over a connection are processed concurrently and responses
+//!   are sent in the order they become available as described in
+//!   RFC 7766. Given a TLS acceptor, the server provides DNS over TLS.
+//! * [`DohHandler`] processes DNS over HTTPS requests received by an HTTP
+//!   server library and produces the HTTP responses.
+//!
+//! Services can be wrapped to add functionality. [`Rrl`] limits the rate
+//! of responses sent over UDP to protect against reflection attacks. The
+//! [`acl`] module helps services decide what clients may do and which
+//! view of the zones they see. The [`middleware`] module allows stacking
+//! such wrappers as [`Layer`]s around a service via a
+//! [`ServiceBuilder`]. Its [`InstrumentLayer`][middleware::InstrumentLayer]
+//! reports requests and responses to [`metrics`][crate::metrics]. The
+//! [`querylog`] module logs each request with optional redaction of
+//! client addresses and query names.
+//!
+//! [`NegativeAnswer`] helps services build NXDOMAIN and NODATA responses
+//! with the SOA record and, for signed zones, the NSEC or NSEC3 proofs in
+//! the authority section.
+//!
+//! A [`Notifier`][notify::Notifier] sends NOTIFY messages to the
+//! secondaries of a zone after it has changed.
+//!
+//! With the `mdns` feature, the `mdns` module provides a multicast DNS
+//! responder that advertises a host’s records on the local link.
+//!
+//! The first two servers spawn a task for each request so they need to be run
+//! within a Tokio runtime. Their `run` methods take a future that triggers
+//! a graceful shutdown when it completes: the servers stop receiving new
+//! requests and return once all requests have been responded to.
+//!
+//! # Example
+//!
+//! A server answering every query with REFUSED:
+//!
+//! ```no_run
+//! use bytes::Bytes;
+//! use domain::base::iana::Rcode;
+//! use domain::base::{Message, MessageBuilder};
+//! use domain::serve::{DgramServer, Request, StreamServer};
+//! use futures::future::pending;
+//!
+//! async fn refuse(request: Request) -> Option<Message<Bytes>> {
+//!     let answer = MessageBuilder::new_bytes()
+//!         .start_answer(request.message(), Rcode::Refused)
+//!         .ok()?;
+//!     Some(answer.into_message())
+//! }
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let addr = "127.0.0.1:8053".parse().unwrap();
+//! let udp = DgramServer::bind(addr, refuse)?;
+//! let tcp = StreamServer::bind(addr, refuse).await?;
+//!
+//! // Run forever. Pass a future that completes upon a signal instead to
+//! // shut down gracefully.
+//! tokio::try_join!(udp.run(pending()), tcp.run(pending()))?;
+//! # Ok(())
+//! # }
+//! ```
+#![cfg(feature = "serve")]
+#![cfg_attr(docsrs, doc(cfg(feature = "serve")))]
+
+pub use self::dgram::DgramServer;
+pub use self::doh::{DohHandler, DohResponse};
+pub use self::middleware::{Layer, ServiceBuilder};
+pub use self::negative::NegativeAnswer;
+pub use self::rrl::{Rrl, RrlConfig};
+pub use self::service::{Request, Service, Transport};
+pub use self::stream::{Accept, StreamServer};
+
+pub mod acl;
+pub mod dgram;
+pub mod doh;
+#[cfg(feature = "mdns")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mdns")))]
+pub mod mdns;
+pub mod middleware;
+pub mod negative;
+pub mod notify;
+pub mod querylog;
+pub mod rrl;
+pub mod service;
+pub mod stream;
+
+use core::future::Future;
+use tokio::sync::{mpsc, watch};
+
+//------------ Tasks ---------------------------------------------------------
+
+/// The tasks of a server.
+///
+/// This is used to tell all the tasks of a server that it is shutting down
+/// and to wait for them to finish. Each task keeps a clone of the value.
+#[derive(Clone)]
+struct Tasks {
+    /// Receives the shutdown signal.
+    shutdown: watch::Receiver<bool>,
+
+    /// Keeps the channel open while the task is running.
+    _running: mpsc::Sender<()>,
+}
+
+impl Tasks {
+    /// Runs `op` with a new set of tasks until `shutdown` completes.
+    ///
+    /// Once `shutdown` has completed, the tasks are told to shut down and
+    /// the function waits until they all have dropped their clone.
+    async fn run<F, Fut>(
+        op: F,
+        shutdown: impl Future<Output = ()>,
+    ) -> Fut::Output
+    where
+        F: FnOnce(Tasks) -> Fut,
+        Fut: Future,
+    {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (running_tx, mut running_rx) = mpsc::channel(1);
+        let res = op(Tasks {
+            shutdown: shutdown_rx,
+            _running: running_tx,
+        });
+        tokio::pin!(res);
+        let res = tokio::select! {
+            res = &mut res => res,
+            _ = shutdown => {
+                let _ = shutdown_tx.send(true);
+                res.await
+            }
+        };
+        let _ = shutdown_tx.send(true);
+        while running_rx.recv().await.is_some() {}
+        res
+    }
+
+    /// Waits until the server is shutting down.
+    async fn shutdown(&mut self) {
+        while !*self.shutdown.borrow() {
+            if self.shutdown.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
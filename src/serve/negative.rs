@@ -0,0 +1,323 @@
+//! Building negative responses.
+//!
+//! A negative response tells a client that the query name doesn’t exist
+//! (NXDOMAIN) or that it doesn’t have records of the query type (NODATA).
+//! Following RFC 2308, both carry the SOA record of the zone in the
+//! authority section so that resolvers know how long they may cache the
+//! negative answer. The TTL of that record is the lower of its own TTL
+//! and the MINIMUM field of its data. RFC 9077 extends this rule to the
+//! NSEC and NSEC3 records proving the non-existence in a signed zone.
+//!
+//! [`NegativeAnswer`] collects the SOA record and, for signed zones, the
+//! proofs and turns them into a response message for a [`Request`].
+
+use super::service::Request;
+use crate::base::iana::Rcode;
+use crate::base::message::Message;
+use crate::base::message_builder::{
+    AdditionalBuilder, MessageBuilder, PushError, StaticCompressor,
+};
+use crate::base::name::ToDname;
+use crate::base::rdata::ComposeRecordData;
+use crate::base::record::{Record, Ttl};
+use crate::base::wire::Composer;
+use crate::rdata::Soa;
+use bytes::{Bytes, BytesMut};
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The UDP payload size announced in responses to EDNS requests.
+const UDP_PAYLOAD_SIZE: u16 = 1232;
+
+//------------ NegativeAnswer ------------------------------------------------
+
+/// A negative answer to be turned into a response.
+///
+/// Create a value via [`nxdomain`][Self::nxdomain] or
+/// [`nodata`][Self::nodata] from the SOA record of the zone. For signed
+/// zones, add the NSEC or NSEC3 records and their signatures that prove
+/// the negative answer via [`push_proof`][Self::push_proof]. They are
+/// only included in responses to requests that have the DO bit set.
+///
+/// Finally, [`to_message`][Self::to_message] produces the response.
+#[derive(Clone, Debug)]
+pub struct NegativeAnswer<N, D> {
+    /// The rcode of the response.
+    rcode: Rcode,
+
+    /// Whether the response is authoritative.
+    authoritative: bool,
+
+    /// The SOA record of the zone.
+    soa: Record<N, Soa<N>>,
+
+    /// The records proving the negative answer.
+    proofs: Vec<Record<N, D>>,
+}
+
+impl<N, D> NegativeAnswer<N, D> {
+    /// Creates a negative answer stating that the query name doesn’t exist.
+    pub fn nxdomain(soa: Record<N, Soa<N>>) -> Self {
+        Self::new(Rcode::NXDomain, soa)
+    }
+
+    /// Creates a negative answer stating that there is no data of the
+    /// query type.
+    pub fn nodata(soa: Record<N, Soa<N>>) -> Self {
+        Self::new(Rcode::NoError, soa)
+    }
+
+    /// Creates a new negative answer.
+    fn new(rcode: Rcode, soa: Record<N, Soa<N>>) -> Self {
+        NegativeAnswer {
+            rcode,
+            authoritative: true,
+            soa,
+            proofs: Vec::new(),
+        }
+    }
+
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    pub fn is_authoritative(&self) -> bool {
+        self.authoritative
+    }
+
+    /// Sets whether the response is authoritative.
+    ///
+    /// Negative answers are authoritative by default. A server answering
+    /// from cached or forwarded data should clear the AA flag.
+    pub fn set_authoritative(&mut self, authoritative: bool) {
+        self.authoritative = authoritative
+    }
+
+    pub fn soa(&self) -> &Record<N, Soa<N>> {
+        &self.soa
+    }
+
+    pub fn proofs(&self) -> &[Record<N, D>] {
+        &self.proofs
+    }
+
+    /// Adds a record proving the negative answer.
+    ///
+    /// This should be the NSEC or NSEC3 records and their RRSIG records.
+    pub fn push_proof(&mut self, record: Record<N, D>) {
+        self.proofs.push(record)
+    }
+
+    /// Returns the TTL for the negative answer.
+    ///
+    /// This is the lower of the SOA record’s TTL and its MINIMUM field.
+    /// Both the SOA record and the proofs are sent with at most this TTL.
+    pub fn negative_ttl(&self) -> Ttl {
+        self.soa.ttl().min(self.soa.data().minimum())
+    }
+}
+
+impl<N: ToDname, D: ComposeRecordData> NegativeAnswer<N, D> {
+    /// Creates the response message for the given request.
+    ///
+    /// The response copies ID, opcode, RD flag, and question from the
+    /// request. If the request contains an OPT record, so does the
+    /// response, and the proofs are included if the request has the DO
+    /// bit set. If the response would exceed the request’s maximum
+    /// response size, it has the TC flag set and contains no records.
+    pub fn to_message(&self, request: &Request) -> Message<Bytes> {
+        let opt = request.message().opt();
+        let dnssec_ok = opt.as_ref().map(|opt| opt.dnssec_ok()) == Some(true);
+        self.compose(request, opt.is_some(), dnssec_ok)
+            .unwrap_or_else(|| self.truncated(request, opt.is_some()))
+    }
+
+    /// Tries to compose the response within the size limit.
+    fn compose(
+        &self,
+        request: &Request,
+        edns: bool,
+        dnssec_ok: bool,
+    ) -> Option<Message<Bytes>> {
+        let mut builder = MessageBuilder::from_target(StaticCompressor::new(
+            BytesMut::new(),
+        ))
+        .ok()?;
+        builder.header_mut().set_aa(self.authoritative);
+        let mut builder = builder
+            .start_answer(request.message(), self.rcode)
+            .ok()?
+            .authority();
+        let ttl = self.negative_ttl();
+        builder
+            .push((self.soa.owner(), ttl, self.soa.data()))
+            .ok()?;
+        if dnssec_ok {
+            for proof in &self.proofs {
+                builder
+                    .push((
+                        proof.owner(),
+                        proof.class(),
+                        proof.ttl().min(ttl),
+                        proof.data(),
+                    ))
+                    .ok()?;
+            }
+        }
+        let mut builder = builder.additional();
+        if edns {
+            push_opt(&mut builder, dnssec_ok).ok()?;
+        }
+        let octets = builder.finish().into_target().freeze();
+        if octets.len() > request.max_response_size() {
+            return None;
+        }
+        Message::from_octets(octets).ok()
+    }
+
+    /// Creates a truncated response without any records.
+    fn truncated(&self, request: &Request, edns: bool) -> Message<Bytes> {
+        let mut builder = MessageBuilder::new_bytes();
+        builder.header_mut().set_aa(self.authoritative);
+        builder.header_mut().set_tc(true);
+        match builder.start_answer(request.message(), self.rcode) {
+            Ok(builder) => {
+                let mut builder = builder.additional();
+                if edns {
+                    // An OPT record always fits into the minimum size.
+                    let _ = push_opt(&mut builder, false);
+                }
+                builder.into_message()
+            }
+            Err(_) => {
+                let mut builder = MessageBuilder::new_bytes();
+                builder.header_mut().set_id(request.message().header().id());
+                builder.header_mut().set_qr(true);
+                builder.header_mut().set_tc(true);
+                builder.into_message()
+            }
+        }
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Adds the OPT record of the response.
+fn push_opt<Target: Composer>(
+    builder: &mut AdditionalBuilder<Target>,
+    dnssec_ok: bool,
+) -> Result<(), PushError> {
+    builder.opt(|opt| {
+        opt.set_udp_payload_size(UDP_PAYLOAD_SIZE);
+        opt.set_dnssec_ok(dnssec_ok);
+        Ok(())
+    })
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rtype};
+    use crate::base::name::Dname;
+    use crate::base::Serial;
+    use crate::rdata::dnssec::RtypeBitmap;
+    use crate::rdata::{Nsec, ZoneRecordData};
+    use crate::serve::Transport;
+    use core::str::FromStr;
+
+    type Name = Dname<Bytes>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn soa() -> Record<Name, Soa<Name>> {
+        Record::new(
+            name("example.com"),
+            Class::In,
+            Ttl::HOUR,
+            Soa::new(
+                name("ns1.example.com"),
+                name("hostmaster.example.com"),
+                Serial(1),
+                Ttl::HOUR,
+                Ttl::MINUTE,
+                Ttl::DAY,
+                Ttl::from_secs(300),
+            ),
+        )
+    }
+
+    fn answer() -> NegativeAnswer<Name, ZoneRecordData<Bytes, Name>> {
+        let mut types = RtypeBitmap::<Bytes>::builder();
+        for rtype in [Rtype::Soa, Rtype::Ns, Rtype::Nsec] {
+            types.add(rtype).unwrap();
+        }
+        let mut answer = NegativeAnswer::nxdomain(soa());
+        answer.push_proof(Record::new(
+            name("example.com"),
+            Class::In,
+            Ttl::HOUR,
+            Nsec::new(name("www.example.com"), types.finalize()).into(),
+        ));
+        answer
+    }
+
+    fn request(dnssec_ok: Option<bool>) -> Request {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_id(7);
+        let mut msg = msg.question();
+        msg.push((name("nope.example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.additional();
+        if let Some(dnssec_ok) = dnssec_ok {
+            push_opt(&mut msg, dnssec_ok).unwrap();
+        }
+        Request::new(
+            msg.into_message(),
+            "192.0.2.1:53".parse().unwrap(),
+            Transport::Udp,
+        )
+    }
+
+    #[test]
+    fn nxdomain() {
+        let answer = answer();
+        assert_eq!(answer.negative_ttl(), Ttl::from_secs(300));
+
+        let response = answer.to_message(&request(None));
+        assert_eq!(response.header().id(), 7);
+        assert_eq!(response.header().rcode(), Rcode::NXDomain);
+        assert!(response.header().aa());
+        assert_eq!(response.header_counts().ancount(), 0);
+        assert_eq!(response.header_counts().nscount(), 1);
+        assert_eq!(response.header_counts().arcount(), 0);
+        let soa = response.authority().unwrap().next().unwrap().unwrap();
+        assert_eq!(soa.rtype(), Rtype::Soa);
+        assert_eq!(soa.ttl(), Ttl::from_secs(300));
+
+        let response = answer.to_message(&request(Some(false)));
+        assert_eq!(response.header_counts().nscount(), 1);
+        assert!(!response.opt().unwrap().dnssec_ok());
+
+        let response = answer.to_message(&request(Some(true)));
+        assert_eq!(response.header_counts().nscount(), 2);
+        assert!(response.opt().unwrap().dnssec_ok());
+        for record in response.authority().unwrap() {
+            assert_eq!(record.unwrap().ttl(), Ttl::from_secs(300));
+        }
+    }
+
+    #[test]
+    fn nodata() {
+        let mut answer =
+            NegativeAnswer::<_, ZoneRecordData<Bytes, Name>>::nodata(soa());
+        answer.set_authoritative(false);
+        let response = answer.to_message(&request(None));
+        assert_eq!(response.header().rcode(), Rcode::NoError);
+        assert!(!response.header().aa());
+        assert_eq!(response.header_counts().nscount(), 1);
+    }
+}
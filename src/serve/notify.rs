@@ -0,0 +1,647 @@
+//! Sending NOTIFY messages to secondaries.
+//!
+//! When a zone changes, its primary tells the secondaries about it with a
+//! NOTIFY message as defined in RFC 1996, so that they don’t need to wait
+//! for their next scheduled refresh. A [`Notifier`] sends these messages
+//! for any number of zones.
+//!
+//! The secondaries to notify are the name servers of the zone – passed to
+//! [`Notifier::notify`] as addresses and contacted on port 53 – plus an
+//! explicit list of additional targets configured via
+//! [`NotifyConfig::add_also_notify`]. Name servers can be left out
+//! entirely via [`NotifyConfig::set_notify_ns`] and individual addresses
+//! via [`NotifyConfig::add_exclude`]. The primary itself, i.e., the name
+//! server in the SOA record’s MNAME field, should not be part of the name
+//! servers passed in. With the `zonetree` feature, [`ns_addrs`] collects
+//! the addresses of the name servers of a [`Zone`] that way.
+//!
+//! Each target is notified only once per call even if it appears several
+//! times. If a target is still being notified about an earlier change of
+//! the same zone, the running notification continues with the newer
+//! serial instead of starting a second one.
+//!
+//! A target is notified until it has acknowledged the NOTIFY and a query
+//! for its SOA record shows that it has caught up with the serial. Until
+//! then, the NOTIFY is repeated with an exponentially growing delay for a
+//! configurable number of times. If the target doesn’t answer the SOA
+//! query, the acknowledgement alone is accepted. The number of targets
+//! contacted at the same time is limited; waiting between retries doesn’t
+//! count towards the limit.
+//!
+//! [`Zone`]: crate::zonetree::Zone
+
+use crate::base::iana::{Class, Opcode, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::Dname;
+use crate::base::Serial;
+use crate::rdata::Soa;
+#[cfg(feature = "zonetree")]
+use crate::{rdata::ZoneRecordData, zonetree::Zone};
+use bytes::Bytes;
+use futures::future::join_all;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::vec::Vec;
+use tokio::net::UdpSocket;
+use tokio::sync::Semaphore;
+use tokio::time::{sleep, timeout_at, Instant};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The longest delay between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The size of the receive buffer.
+const RECV_SIZE: usize = 4096;
+
+//------------ NotifyConfig --------------------------------------------------
+
+/// The configuration of a [`Notifier`].
+#[derive(Clone, Debug)]
+pub struct NotifyConfig {
+    /// Additional targets to notify.
+    also_notify: Vec<SocketAddr>,
+
+    /// Whether to notify the name servers of the zone.
+    notify_ns: bool,
+
+    /// Addresses never to notify.
+    exclude: Vec<IpAddr>,
+
+    /// The maximum number of targets contacted at the same time.
+    max_concurrent: usize,
+
+    /// How long to wait for a response.
+    timeout: Duration,
+
+    /// The delay before the first retry.
+    retry_delay: Duration,
+
+    /// The number of retries.
+    max_retries: u32,
+}
+
+impl NotifyConfig {
+    /// Adds a target to notify in addition to the name servers.
+    pub fn add_also_notify(&mut self, addr: SocketAddr) {
+        self.also_notify.push(addr)
+    }
+
+    /// Returns the targets notified in addition to the name servers.
+    pub fn also_notify(&self) -> &[SocketAddr] {
+        &self.also_notify
+    }
+
+    /// Sets whether the name servers of a zone are notified.
+    ///
+    /// If disabled, only the targets given via
+    /// [`add_also_notify`][Self::add_also_notify] are notified. The
+    /// default is to notify the name servers.
+    pub fn set_notify_ns(&mut self, notify_ns: bool) {
+        self.notify_ns = notify_ns
+    }
+
+    /// Returns whether the name servers of a zone are notified.
+    pub fn notify_ns(&self) -> bool {
+        self.notify_ns
+    }
+
+    /// Adds an address that is never notified.
+    ///
+    /// This applies to name servers and additional targets alike,
+    /// regardless of the port.
+    pub fn add_exclude(&mut self, addr: IpAddr) {
+        self.exclude.push(addr)
+    }
+
+    /// Returns the addresses that are never notified.
+    pub fn exclude(&self) -> &[IpAddr] {
+        &self.exclude
+    }
+
+    /// Sets the maximum number of targets contacted at the same time.
+    ///
+    /// The default is 8. A value of zero is treated as one.
+    pub fn set_max_concurrent(&mut self, max_concurrent: usize) {
+        self.max_concurrent = max_concurrent
+    }
+
+    /// Returns the maximum number of targets contacted at the same time.
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Sets how long to wait for a response from a target.
+    ///
+    /// The default is two seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout
+    }
+
+    /// Returns how long to wait for a response from a target.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the delay before the first retry.
+    ///
+    /// The delay doubles with every further retry up to five minutes. The
+    /// default is five seconds.
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay
+    }
+
+    /// Returns the delay before the first retry.
+    pub fn retry_delay(&self) -> Duration {
+        self.retry_delay
+    }
+
+    /// Sets how often a NOTIFY is repeated before giving up.
+    ///
+    /// The default is five times.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries
+    }
+
+    /// Returns how often a NOTIFY is repeated before giving up.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            also_notify: Vec::new(),
+            notify_ns: true,
+            exclude: Vec::new(),
+            max_concurrent: 8,
+            timeout: Duration::from_secs(2),
+            retry_delay: Duration::from_secs(5),
+            max_retries: 5,
+        }
+    }
+}
+
+//------------ Notifier ------------------------------------------------------
+
+/// Sends NOTIFY messages for changed zones.
+///
+/// See the [module documentation][self] for details.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    /// The configuration.
+    config: Arc<NotifyConfig>,
+
+    /// Limits the number of targets contacted at the same time.
+    limit: Arc<Semaphore>,
+
+    /// The serials currently being notified per zone and target.
+    inflight: Arc<Mutex<Inflight>>,
+}
+
+/// A map from zone and target to a serial.
+type Inflight = HashMap<(Dname<Bytes>, SocketAddr), Serial>;
+
+impl Notifier {
+    /// Creates a new notifier with the given configuration.
+    pub fn new(config: NotifyConfig) -> Self {
+        Notifier {
+            limit: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            config: Arc::new(config),
+            inflight: Default::default(),
+        }
+    }
+
+    /// Returns the configuration of the notifier.
+    pub fn config(&self) -> &NotifyConfig {
+        &self.config
+    }
+
+    /// Returns the targets to notify for a zone with the given name servers.
+    ///
+    /// The additional targets come first, followed by the name servers on
+    /// port 53. Each target appears only once.
+    pub fn targets(&self, ns: &[IpAddr]) -> Vec<SocketAddr> {
+        let ns = ns
+            .iter()
+            .filter(|_| self.config.notify_ns)
+            .map(|addr| SocketAddr::new(*addr, 53));
+        let mut res: Vec<SocketAddr> = Vec::new();
+        for addr in self.config.also_notify.iter().copied().chain(ns) {
+            if !self.config.exclude.contains(&addr.ip())
+                && !res.contains(&addr)
+            {
+                res.push(addr)
+            }
+        }
+        res
+    }
+
+    /// Notifies the secondaries of a zone about a new serial.
+    ///
+    /// The name servers of the zone are given via `ns`. The returned future
+    /// resolves once all targets have been dealt with and produces the
+    /// outcome for each of them in the order given by
+    /// [`targets`][Self::targets].
+    pub async fn notify(
+        &self,
+        apex: &Dname<Bytes>,
+        serial: Serial,
+        ns: &[IpAddr],
+    ) -> Vec<(SocketAddr, NotifyOutcome)> {
+        let targets = self.targets(ns);
+        let outcomes = join_all(targets.iter().map(|addr| async move {
+            if !self.start(apex, *addr, serial) {
+                return NotifyOutcome::InProgress;
+            }
+            self.notify_target(apex, *addr).await
+        }))
+        .await;
+        targets.into_iter().zip(outcomes).collect()
+    }
+
+    /// Registers a notification for a target.
+    ///
+    /// Returns `false` if one is already running. In this case, its serial
+    /// is raised if necessary.
+    fn start(
+        &self,
+        apex: &Dname<Bytes>,
+        addr: SocketAddr,
+        serial: Serial,
+    ) -> bool {
+        let mut inflight = self.inflight.lock().unwrap();
+        match inflight.get_mut(&(apex.clone(), addr)) {
+            Some(current) => {
+                if serial > *current {
+                    *current = serial
+                }
+                false
+            }
+            None => {
+                inflight.insert((apex.clone(), addr), serial);
+                true
+            }
+        }
+    }
+
+    /// Finishes the notification for a target if its serial hasn’t changed.
+    ///
+    /// Returns `false` if a newer serial has been registered meanwhile.
+    fn finish(
+        &self,
+        apex: &Dname<Bytes>,
+        addr: SocketAddr,
+        serial: Serial,
+    ) -> bool {
+        let mut inflight = self.inflight.lock().unwrap();
+        let key = (apex.clone(), addr);
+        if inflight.get(&key) != Some(&serial) {
+            return false;
+        }
+        inflight.remove(&key);
+        true
+    }
+
+    /// Returns the serial currently to be notified for a target.
+    fn serial(&self, apex: &Dname<Bytes>, addr: SocketAddr) -> Serial {
+        self.inflight.lock().unwrap()[&(apex.clone(), addr)]
+    }
+
+    /// Notifies a single target until it is done.
+    async fn notify_target(
+        &self,
+        apex: &Dname<Bytes>,
+        addr: SocketAddr,
+    ) -> NotifyOutcome {
+        let mut delay = self.config.retry_delay;
+        let mut retries = 0;
+        loop {
+            let serial = self.serial(apex, addr);
+            let outcome = {
+                let _permit = self.limit.acquire().await;
+                self.attempt(apex, addr, serial).await
+            };
+            let outcome = match outcome {
+                Some(outcome) => outcome,
+                None if retries < self.config.max_retries => {
+                    retries += 1;
+                    sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+                None => NotifyOutcome::Failed,
+            };
+            if self.finish(apex, addr, serial) {
+                return outcome;
+            }
+        }
+    }
+
+    /// Makes a single attempt at notifying a target.
+    ///
+    /// Returns `None` if the attempt should be repeated.
+    async fn attempt(
+        &self,
+        apex: &Dname<Bytes>,
+        addr: SocketAddr,
+        serial: Serial,
+    ) -> Option<NotifyOutcome> {
+        let sock = UdpSocket::bind(match addr {
+            SocketAddr::V4(_) => {
+                SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)
+            }
+            SocketAddr::V6(_) => {
+                SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
+            }
+        })
+        .await
+        .ok()?;
+        sock.connect(addr).await.ok()?;
+
+        let response = self.exchange(&sock, apex, Opcode::Notify).await?;
+        if response.header().rcode() != Rcode::NoError {
+            return Some(NotifyOutcome::Rejected(response.header().rcode()));
+        }
+        let response = match self.exchange(&sock, apex, Opcode::Query).await {
+            Some(response) => response,
+            None => return Some(NotifyOutcome::Acknowledged),
+        };
+        let soa = response
+            .answer()
+            .ok()
+            .and_then(|mut answer| answer.next())
+            .and_then(|record| record.ok())
+            .and_then(|record| record.into_record::<Soa<_>>().ok().flatten());
+        match soa {
+            Some(soa) if soa.data().serial() >= serial => {
+                Some(NotifyOutcome::CaughtUp)
+            }
+            Some(_) => None,
+            None => Some(NotifyOutcome::Acknowledged),
+        }
+    }
+
+    /// Sends a request for the SOA of `apex` and waits for the response.
+    ///
+    /// Returns `None` if there is no response in time.
+    async fn exchange(
+        &self,
+        sock: &UdpSocket,
+        apex: &Dname<Bytes>,
+        opcode: Opcode,
+    ) -> Option<Message<Vec<u8>>> {
+        let mut request = MessageBuilder::new_vec();
+        request.header_mut().set_id(random_id());
+        request.header_mut().set_opcode(opcode);
+        request.header_mut().set_aa(opcode == Opcode::Notify);
+        let mut request = request.question();
+        request.push((apex, Rtype::Soa, Class::In)).ok()?;
+        let request = request.into_message();
+        sock.send(request.as_slice()).await.ok()?;
+
+        let deadline = Instant::now() + self.config.timeout;
+        loop {
+            let mut buf = vec![0; RECV_SIZE];
+            let len =
+                timeout_at(deadline, sock.recv(&mut buf)).await.ok()?.ok()?;
+            buf.truncate(len);
+            let response = match Message::from_octets(buf) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+            if response.is_answer(&request)
+                && response.header().opcode() == opcode
+            {
+                return Some(response);
+            }
+        }
+    }
+}
+
+//------------ NotifyOutcome -------------------------------------------------
+
+/// The outcome of notifying a target.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NotifyOutcome {
+    /// The target has the new serial.
+    CaughtUp,
+
+    /// The target acknowledged the NOTIFY but didn’t reveal its serial.
+    Acknowledged,
+
+    /// The target was already being notified about the zone.
+    ///
+    /// That notification continues with the new serial.
+    InProgress,
+
+    /// The target answered the NOTIFY with the given error.
+    Rejected(Rcode),
+
+    /// The target didn’t catch up before all retries were used up.
+    Failed,
+}
+
+//============ Helper Functions ==============================================
+
+/// Returns the addresses of the name servers of a zone.
+///
+/// These are the addresses from the zone’s A and AAAA records for the
+/// name servers in the NS records at its apex except the one named as the
+/// primary in the SOA record. Since only the data of the zone itself is
+/// used, addresses of name servers outside the zone are not included.
+#[cfg(feature = "zonetree")]
+pub fn ns_addrs(zone: &Zone) -> Vec<IpAddr> {
+    let primary = zone.soa().and_then(|soa| match soa.first() {
+        Some(ZoneRecordData::Soa(soa)) => Some(soa.mname().clone()),
+        _ => None,
+    });
+    let answer = zone.query(zone.apex(), Rtype::Ns);
+    let mut res = Vec::new();
+    for (_, rrset) in answer.answer() {
+        for data in rrset.data() {
+            let name = match data {
+                ZoneRecordData::Ns(ns) => ns.nsdname(),
+                _ => continue,
+            };
+            if Some(name) == primary.as_ref() {
+                continue;
+            }
+            for rtype in [Rtype::A, Rtype::Aaaa] {
+                for (_, rrset) in zone.query(name, rtype).answer() {
+                    for data in rrset.data() {
+                        let addr = match data {
+                            ZoneRecordData::A(a) => a.addr().into(),
+                            ZoneRecordData::Aaaa(aaaa) => aaaa.addr().into(),
+                            _ => continue,
+                        };
+                        if !res.contains(&addr) {
+                            res.push(addr)
+                        }
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Returns a message ID that is hard to guess.
+fn random_id() -> u16 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish() as u16
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::Ttl;
+    use core::str::FromStr;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Starts a secondary that reaches `serial` after `lag` SOA queries.
+    ///
+    /// Returns its address and the number of NOTIFYs received.
+    async fn secondary(
+        serial: u32,
+        lag: usize,
+    ) -> (SocketAddr, Arc<AtomicUsize>) {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = sock.local_addr().unwrap();
+        let notifies = Arc::new(AtomicUsize::new(0));
+        let count = notifies.clone();
+        tokio::spawn(async move {
+            let mut queries = 0;
+            loop {
+                let mut buf = vec![0; RECV_SIZE];
+                let (len, peer) = sock.recv_from(&mut buf).await.unwrap();
+                buf.truncate(len);
+                let request = Message::from_octets(buf).unwrap();
+                let mut answer = MessageBuilder::new_vec()
+                    .start_answer(&request, Rcode::NoError)
+                    .unwrap();
+                if request.header().opcode() == Opcode::Notify {
+                    count.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let current =
+                        if queries < lag { serial - 1 } else { serial };
+                    queries += 1;
+                    let apex = request.sole_question().unwrap().into_qname();
+                    answer
+                        .push((
+                            apex,
+                            Class::In,
+                            3600,
+                            Soa::new(
+                                apex,
+                                apex,
+                                Serial(current),
+                                Ttl::HOUR,
+                                Ttl::HOUR,
+                                Ttl::HOUR,
+                                Ttl::HOUR,
+                            ),
+                        ))
+                        .unwrap();
+                }
+                sock.send_to(answer.as_slice(), peer).await.unwrap();
+            }
+        });
+        (addr, notifies)
+    }
+
+    #[tokio::test]
+    async fn notify_until_caught_up() {
+        let (lagging, notifies) = secondary(5, 2).await;
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+
+        let mut config = NotifyConfig::default();
+        config.add_also_notify(lagging);
+        config.add_also_notify(silent_addr);
+        config.add_also_notify(lagging);
+        config.add_exclude("192.0.2.1".parse().unwrap());
+        config.set_timeout(Duration::from_millis(50));
+        config.set_retry_delay(Duration::from_millis(10));
+        config.set_max_retries(3);
+        let notifier = Notifier::new(config);
+
+        let ns = ["192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap()];
+        let targets = notifier.targets(&ns);
+        assert_eq!(
+            targets,
+            [lagging, silent_addr, "192.0.2.2:53".parse().unwrap()]
+        );
+
+        let apex = Dname::from_str("example.com").unwrap();
+        let outcomes = notifier.notify(&apex, Serial(5), &ns[..1]).await;
+        assert_eq!(
+            outcomes,
+            [
+                (lagging, NotifyOutcome::CaughtUp),
+                (silent_addr, NotifyOutcome::Failed)
+            ]
+        );
+        assert_eq!(notifies.load(Ordering::Relaxed), 3);
+        assert!(notifier.inflight.lock().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "zonetree")]
+    #[test]
+    fn zone_ns_addrs() {
+        use crate::base::Record;
+        use crate::rdata::{Aaaa, Ns, A};
+
+        let name = |s| Dname::<Bytes>::from_str(s).unwrap();
+        let mut zone = Zone::new(name("example.com"), Class::In);
+        let mut insert = |owner, data| {
+            zone.insert(Record::new(name(owner), Class::In, Ttl::HOUR, data))
+                .unwrap()
+        };
+        insert(
+            "example.com",
+            ZoneRecordData::Soa(Soa::new(
+                name("ns1.example.com"),
+                name("hostmaster.example.com"),
+                Serial(1),
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+                Ttl::HOUR,
+            )),
+        );
+        for ns in ["ns1.example.com", "ns2.example.com", "ns.example.net"] {
+            insert("example.com", ZoneRecordData::Ns(Ns::new(name(ns))));
+        }
+        insert(
+            "ns1.example.com",
+            ZoneRecordData::A(A::from_octets(192, 0, 2, 1)),
+        );
+        insert(
+            "ns2.example.com",
+            ZoneRecordData::A(A::from_octets(192, 0, 2, 2)),
+        );
+        insert(
+            "ns2.example.com",
+            ZoneRecordData::Aaaa(Aaaa::new("2001:db8::2".parse().unwrap())),
+        );
+        assert_eq!(
+            ns_addrs(&zone),
+            [
+                IpAddr::from_str("192.0.2.2").unwrap(),
+                IpAddr::from_str("2001:db8::2").unwrap()
+            ]
+        );
+    }
+}
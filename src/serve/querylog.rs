@@ -0,0 +1,551 @@
+//! Logging queries with privacy redaction.
+//!
+//! A query log records an entry for every request a server processes. The
+//! [`QueryLogger`] service wraps the service actually processing requests
+//! and produces a [`QueryLog`] entry for each request once its response is
+//! available. The entry carries the time the request was received, the
+//! client address, the transport, the question, the rcode of the response,
+//! and the time it took to produce the response. It is then handed to a
+//! [`QueryLogSink`] which can write it to a file, send it to a log
+//! collector, or whatever else is desired.
+//!
+//! Since client addresses and query names can be personal data, entries
+//! can be redacted before they reach the sink. A [`Redaction`] can
+//! truncate client addresses to their network – by default a /24 for IPv4
+//! and a /48 for IPv6 – and replace query names with a keyed hash. The
+//! hash key is chosen randomly when the [`Redaction`] is created, so the
+//! same name produces the same hash for as long as the value (or a clone
+//! of it) is in use, which still allows counting queries for a name
+//! without revealing it.
+//!
+//! The service doesn’t know whether a response was produced from a cache,
+//! so its entries don’t carry a [`CacheStatus`]. Services that do know,
+//! e.g., because they forward requests to a caching resolver, can produce
+//! entries themselves via [`QueryLog::new`], add the status via
+//! [`QueryLog::set_cache_status`], and pass them through
+//! [`Redaction::apply`] before handing them to a sink.
+
+use super::acl::mask;
+use super::middleware::Layer;
+use super::service::{Request, Service, Transport};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ToDname};
+use bytes::Bytes;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use std::boxed::Box;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//------------ QueryLog ------------------------------------------------------
+
+/// An entry of the query log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueryLog {
+    /// The time the request was received.
+    time: SystemTime,
+
+    /// The address of the client.
+    client: IpAddr,
+
+    /// The transport the request was received over.
+    transport: Transport,
+
+    /// The name of the first question if there is one.
+    qname: Option<LoggedName>,
+
+    /// The type of the first question if there is one.
+    qtype: Option<Rtype>,
+
+    /// The rcode of the response or `None` if none was sent.
+    rcode: Option<Rcode>,
+
+    /// The time it took to produce the response.
+    latency: Duration,
+
+    /// Whether the response came from a cache, if known.
+    cache_status: Option<CacheStatus>,
+}
+
+impl QueryLog {
+    /// Creates a new entry for a request received now.
+    ///
+    /// The entry starts out without a response.
+    pub fn new(request: &Request) -> Self {
+        let question = request.message().first_question();
+        QueryLog {
+            time: SystemTime::now(),
+            client: request.client_addr().ip(),
+            transport: request.transport(),
+            qname: question.as_ref().map(|question| {
+                LoggedName::Name(question.qname().to_bytes())
+            }),
+            qtype: question.map(|question| question.qtype()),
+            rcode: None,
+            latency: Duration::ZERO,
+            cache_status: None,
+        }
+    }
+
+    /// Returns the time the request was received.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the address of the client, possibly redacted.
+    pub fn client(&self) -> IpAddr {
+        self.client
+    }
+
+    /// Returns the transport the request was received over.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the name of the first question if there is one.
+    pub fn qname(&self) -> Option<&LoggedName> {
+        self.qname.as_ref()
+    }
+
+    /// Returns the type of the first question if there is one.
+    pub fn qtype(&self) -> Option<Rtype> {
+        self.qtype
+    }
+
+    /// Returns the rcode of the response or `None` if none was sent.
+    pub fn rcode(&self) -> Option<Rcode> {
+        self.rcode
+    }
+
+    /// Sets the rcode of the response.
+    pub fn set_rcode(&mut self, rcode: Option<Rcode>) {
+        self.rcode = rcode
+    }
+
+    /// Returns the time it took to produce the response.
+    ///
+    /// This is zero until it has been set.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Sets the time it took to produce the response.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency
+    }
+
+    /// Returns whether the response came from a cache, if known.
+    pub fn cache_status(&self) -> Option<CacheStatus> {
+        self.cache_status
+    }
+
+    /// Sets whether the response came from a cache.
+    pub fn set_cache_status(&mut self, status: Option<CacheStatus>) {
+        self.cache_status = status
+    }
+}
+
+//--- Display
+
+impl fmt::Display for QueryLog {
+    /// Formats the entry as a single line.
+    ///
+    /// The line contains the time as seconds since the Unix epoch, the
+    /// client, the transport, the question, the rcode or `DROPPED`, the
+    /// latency in milliseconds, and the cache status. Missing values are
+    /// given as `-`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(
+            f,
+            "{}.{:03} {} {} ",
+            time.as_secs(),
+            time.subsec_millis(),
+            self.client,
+            match self.transport {
+                Transport::Udp => "udp",
+                Transport::Tcp => "tcp",
+                Transport::Tls => "tls",
+                Transport::Https => "https",
+            }
+        )?;
+        match self.qname.as_ref() {
+            Some(qname) => write!(f, "{} ", qname)?,
+            None => f.write_str("- ")?,
+        }
+        match self.qtype {
+            Some(qtype) => write!(f, "{} ", qtype)?,
+            None => f.write_str("- ")?,
+        }
+        match self.rcode {
+            Some(rcode) => write!(f, "{} ", rcode)?,
+            None => f.write_str("DROPPED ")?,
+        }
+        write!(f, "{:.3}ms ", self.latency.as_secs_f64() * 1000.)?;
+        match self.cache_status {
+            Some(status) => write!(f, "{}", status),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+//------------ LoggedName ----------------------------------------------------
+
+/// A query name as it appears in the log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LoggedName {
+    /// The name itself.
+    Name(Dname<Bytes>),
+
+    /// A hash of the name.
+    Hashed(u64),
+}
+
+impl fmt::Display for LoggedName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoggedName::Name(ref name) => write!(f, "{}.", name),
+            LoggedName::Hashed(hash) => write!(f, "#{:016x}", hash),
+        }
+    }
+}
+
+//------------ CacheStatus ---------------------------------------------------
+
+/// Whether a response was produced from a cache.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CacheStatus {
+    /// The response was produced from a cached answer.
+    Hit,
+
+    /// The response was produced from an expired cached answer.
+    Stale,
+
+    /// The answer wasn’t cached.
+    Miss,
+}
+
+impl fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Stale => "stale",
+            CacheStatus::Miss => "miss",
+        })
+    }
+}
+
+//------------ Redaction -----------------------------------------------------
+
+/// How entries are redacted before they are logged.
+///
+/// By default, nothing is redacted.
+#[derive(Clone, Debug, Default)]
+pub struct Redaction {
+    /// The prefix lengths to truncate IPv4 and IPv6 clients to.
+    client_prefix_lens: Option<(u8, u8)>,
+
+    /// The keys for hashing query names if they are hashed.
+    qname_hasher: Option<RandomState>,
+}
+
+impl Redaction {
+    /// The default prefix length for truncating IPv4 clients.
+    pub const IPV4_PREFIX_LEN: u8 = 24;
+
+    /// The default prefix length for truncating IPv6 clients.
+    pub const IPV6_PREFIX_LEN: u8 = 48;
+
+    /// Creates a redaction truncating clients and hashing query names.
+    pub fn strict() -> Self {
+        let mut res = Self::default();
+        res.set_truncate_clients(true);
+        res.set_hash_qnames(true);
+        res
+    }
+
+    /// Sets whether client addresses are truncated to their network.
+    ///
+    /// The default prefix lengths are
+    /// [`IPV4_PREFIX_LEN`][Self::IPV4_PREFIX_LEN] and
+    /// [`IPV6_PREFIX_LEN`][Self::IPV6_PREFIX_LEN].
+    pub fn set_truncate_clients(&mut self, truncate: bool) {
+        self.client_prefix_lens =
+            truncate.then_some((Self::IPV4_PREFIX_LEN, Self::IPV6_PREFIX_LEN))
+    }
+
+    /// Sets the prefix lengths client addresses are truncated to.
+    pub fn set_client_prefix_lens(&mut self, ipv4: u8, ipv6: u8) {
+        self.client_prefix_lens = Some((ipv4.min(32), ipv6.min(128)))
+    }
+
+    /// Returns the prefix lengths if client addresses are truncated.
+    pub fn client_prefix_lens(&self) -> Option<(u8, u8)> {
+        self.client_prefix_lens
+    }
+
+    /// Sets whether query names are replaced with their hash.
+    ///
+    /// Each call enabling hashing chooses a new key, so hashes from
+    /// before and after the call aren’t comparable.
+    pub fn set_hash_qnames(&mut self, hash: bool) {
+        self.qname_hasher = hash.then(RandomState::new)
+    }
+
+    /// Returns whether query names are replaced with their hash.
+    pub fn hashes_qnames(&self) -> bool {
+        self.qname_hasher.is_some()
+    }
+
+    /// Redacts an entry.
+    pub fn apply(&self, log: &mut QueryLog) {
+        if let Some((ipv4, ipv6)) = self.client_prefix_lens {
+            log.client = match log.client {
+                IpAddr::V4(_) => mask(log.client, ipv4),
+                IpAddr::V6(_) => mask(log.client, ipv6),
+            }
+        }
+        if let (Some(hasher), Some(LoggedName::Name(name))) =
+            (self.qname_hasher.as_ref(), log.qname.as_ref())
+        {
+            // Name hashing is case-insensitive.
+            let mut hasher = hasher.build_hasher();
+            name.hash(&mut hasher);
+            log.qname = Some(LoggedName::Hashed(hasher.finish()))
+        }
+    }
+}
+
+//------------ QueryLogSink --------------------------------------------------
+
+/// A type receiving query log entries.
+///
+/// The sink is called by the task processing the request, so it should
+/// hand expensive work such as writing to a file off to a task of its own.
+/// The trait is implemented for closures.
+pub trait QueryLogSink: Send + Sync {
+    /// Logs an entry.
+    fn log(&self, entry: QueryLog);
+}
+
+impl<F: Fn(QueryLog) + Send + Sync> QueryLogSink for F {
+    fn log(&self, entry: QueryLog) {
+        (self)(entry)
+    }
+}
+
+//------------ QueryLogger ---------------------------------------------------
+
+/// A service logging the requests of another service.
+///
+/// See the [module documentation][self] for details.
+pub struct QueryLogger<S> {
+    /// The inner service.
+    service: S,
+
+    /// The sink to hand entries to.
+    sink: Arc<dyn QueryLogSink>,
+
+    /// The redaction applied to entries.
+    redaction: Redaction,
+}
+
+impl<S> QueryLogger<S> {
+    /// Creates a new service wrapping `service`.
+    pub fn new(
+        service: S,
+        sink: impl QueryLogSink + 'static,
+        redaction: Redaction,
+    ) -> Self {
+        QueryLogger {
+            service,
+            sink: Arc::new(sink),
+            redaction,
+        }
+    }
+
+    /// Returns the redaction applied to entries.
+    pub fn redaction(&self) -> &Redaction {
+        &self.redaction
+    }
+}
+
+impl<S: Service> Service for QueryLogger<S> {
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let sink = self.sink.clone();
+        let redaction = self.redaction.clone();
+        let mut entry = QueryLog::new(&request);
+        let start = Instant::now();
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            entry.set_rcode(
+                response.as_ref().map(|response| response.header().rcode()),
+            );
+            entry.set_latency(start.elapsed());
+            redaction.apply(&mut entry);
+            sink.log(entry);
+            response
+        })
+    }
+}
+
+impl<S> fmt::Debug for QueryLogger<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QueryLogger")
+            .field("redaction", &self.redaction)
+            .finish_non_exhaustive()
+    }
+}
+
+//------------ QueryLogLayer -------------------------------------------------
+
+/// A layer producing [`QueryLogger`] services.
+///
+/// All services produced by the layer log to the same sink.
+#[derive(Clone)]
+pub struct QueryLogLayer {
+    /// The sink to hand entries to.
+    sink: Arc<dyn QueryLogSink>,
+
+    /// The redaction applied to entries.
+    redaction: Redaction,
+}
+
+impl QueryLogLayer {
+    /// Creates a new layer logging to the given sink.
+    pub fn new(
+        sink: impl QueryLogSink + 'static,
+        redaction: Redaction,
+    ) -> Self {
+        QueryLogLayer {
+            sink: Arc::new(sink),
+            redaction,
+        }
+    }
+}
+
+impl<S> Layer<S> for QueryLogLayer {
+    type Service = QueryLogger<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QueryLogger {
+            service: inner,
+            sink: self.sink.clone(),
+            redaction: self.redaction.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for QueryLogLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QueryLogLayer")
+            .field("redaction", &self.redaction)
+            .finish_non_exhaustive()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    fn request(qname: &str, client: &str) -> Request {
+        let mut msg = MessageBuilder::new_bytes().question();
+        msg.push((Dname::<Bytes>::from_str(qname).unwrap(), Rtype::A))
+            .unwrap();
+        Request::new(
+            msg.into_message(),
+            client.parse().unwrap(),
+            Transport::Udp,
+        )
+    }
+
+    async fn log(
+        redaction: Redaction,
+        requests: &[Request],
+    ) -> Vec<QueryLog> {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let sink = entries.clone();
+        let service = QueryLogLayer::new(
+            move |entry| sink.lock().unwrap().push(entry),
+            redaction,
+        )
+        .layer(|request: Request| async move {
+            if request.client_addr().port() == 0 {
+                return None;
+            }
+            let answer = MessageBuilder::new_bytes()
+                .start_answer(request.message(), Rcode::NXDomain)
+                .ok()?;
+            Some(answer.into_message())
+        });
+        for request in requests {
+            service.call(request.clone()).await;
+        }
+        let res = entries.lock().unwrap().clone();
+        res
+    }
+
+    #[tokio::test]
+    async fn plain() {
+        let entries = log(
+            Redaction::default(),
+            &[
+                request("www.example.com", "192.0.2.1:53000"),
+                request("www.example.com", "[2001:db8::1]:0"),
+            ],
+        )
+        .await;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].client(),
+            "192.0.2.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(entries[0].rcode(), Some(Rcode::NXDomain));
+        let line = entries[0].to_string();
+        assert!(line.ends_with("ms -"));
+        assert!(line.contains(" 192.0.2.1 udp www.example.com. A NXDOMAIN "));
+        assert_eq!(entries[1].rcode(), None);
+        assert!(entries[1].to_string().contains(" DROPPED "));
+    }
+
+    #[tokio::test]
+    async fn redacted() {
+        let entries = log(
+            Redaction::strict(),
+            &[
+                request("www.example.com", "192.0.2.1:53000"),
+                request("WWW.Example.com", "[2001:db8:1:2::1]:53000"),
+                request("mail.example.com", "192.0.2.1:53000"),
+            ],
+        )
+        .await;
+        assert_eq!(
+            entries[0].client(),
+            "192.0.2.0".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            entries[1].client(),
+            "2001:db8:1::".parse::<IpAddr>().unwrap()
+        );
+        assert!(matches!(entries[0].qname(), Some(LoggedName::Hashed(_))));
+        assert_eq!(entries[0].qname(), entries[1].qname());
+        assert_ne!(entries[0].qname(), entries[2].qname());
+        assert!(!entries[0].to_string().contains("example"));
+    }
+}
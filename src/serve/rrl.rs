@@ -0,0 +1,607 @@
+//! Response rate limiting.
+//!
+//! Since requests over UDP can be sent with a forged source address, an
+//! authoritative name server can be abused to flood a victim with its
+//! responses. Response rate limiting (RRL) as pioneered by BIND mitigates
+//! this by limiting the rate at which essentially identical responses are
+//! sent to the same network.
+//!
+//! The [`Rrl`] service wraps the service actually processing requests and
+//! accounts each response to UDP requests to a token bucket. Buckets are
+//! kept per network of the client – the address with the host bits
+//! removed – and per kind of response given by its [`Category`] and a
+//! name: For positive answers, this is the query name and type. For
+//! negative answers and referrals it is the owner of the record in the
+//! authority section, i.e., the zone or delegation, so random query names
+//! can’t be used to escape the limit. All errors share one bucket per
+//! network.
+//!
+//! Every second, a bucket receives as many tokens as the rate configured
+//! for its category and every response takes one token. If the bucket is
+//! empty, the response is dropped. Every so many dropped responses, as
+//! configured via [`RrlConfig::set_slip`], a truncated response is sent
+//! instead which makes legitimate clients retry over TCP. Requests over
+//! other transports are never limited.
+
+use super::acl::mask;
+use super::middleware::Layer;
+use super::service::{Request, Service, Transport};
+use crate::base::iana::Rcode;
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use bytes::Bytes;
+use core::future::Future;
+use core::pin::Pin;
+use std::boxed::Box;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+//------------ Rrl -----------------------------------------------------------
+
+/// A service limiting the rate of responses of another service.
+///
+/// See the [module documentation][self] for details.
+pub struct Rrl<S> {
+    /// The service producing the responses.
+    service: S,
+
+    /// The limiter shared with the response futures.
+    limiter: Arc<Limiter>,
+}
+
+impl<S> Rrl<S> {
+    /// Creates a new rate limiting service.
+    pub fn new(service: S, config: RrlConfig) -> Self {
+        Rrl {
+            service,
+            limiter: Arc::new(Limiter::new(config)),
+        }
+    }
+
+    /// Returns the configuration of the rate limiting.
+    pub fn config(&self) -> &RrlConfig {
+        &self.limiter.config
+    }
+}
+
+impl<S: Service> Service for Rrl<S> {
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        if request.transport() != Transport::Udp {
+            return Box::pin(self.service.call(request));
+        }
+        let limiter = self.limiter.clone();
+        let response = self.service.call(request.clone());
+        Box::pin(async move {
+            limiter.limit(&request, response.await?, Instant::now())
+        })
+    }
+}
+
+impl<S> fmt::Debug for Rrl<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rrl")
+            .field("config", &self.limiter.config)
+            .finish_non_exhaustive()
+    }
+}
+
+//------------ RrlLayer ------------------------------------------------------
+
+/// A layer producing [`Rrl`] services.
+///
+/// Each service produced by the layer keeps its own rate limits.
+#[derive(Clone, Debug, Default)]
+pub struct RrlLayer {
+    /// The configuration for the produced services.
+    config: RrlConfig,
+}
+
+impl RrlLayer {
+    /// Creates a new layer with the given configuration.
+    pub fn new(config: RrlConfig) -> Self {
+        RrlLayer { config }
+    }
+}
+
+impl<S> Layer<S> for RrlLayer {
+    type Service = Rrl<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Rrl::new(inner, self.config.clone())
+    }
+}
+
+//------------ RrlConfig -----------------------------------------------------
+
+/// The configuration of response rate limiting.
+#[derive(Clone, Debug)]
+pub struct RrlConfig {
+    /// The rates per second for each category.
+    rates: [u32; Category::COUNT],
+
+    /// How many seconds of excess responses a bucket remembers.
+    window: u32,
+
+    /// Every how many limited responses one is sent truncated.
+    slip: u32,
+
+    /// The prefix length of IPv4 client networks.
+    ipv4_prefix_len: u8,
+
+    /// The prefix length of IPv6 client networks.
+    ipv6_prefix_len: u8,
+
+    /// The maximum number of buckets.
+    max_buckets: usize,
+}
+
+impl RrlConfig {
+    /// The default rate of responses per second for all categories.
+    pub const DEFAULT_RATE: u32 = 5;
+
+    /// Sets the number of responses per second for a category.
+    ///
+    /// A rate of zero disables limiting for the category. The default is
+    /// [`DEFAULT_RATE`][Self::DEFAULT_RATE] for all categories.
+    pub fn set_rate(&mut self, category: Category, rate: u32) {
+        self.rates[category as usize] = rate
+    }
+
+    /// Returns the number of responses per second for a category.
+    pub fn rate(&self, category: Category) -> u32 {
+        self.rates[category as usize]
+    }
+
+    /// Sets the number of seconds over which the rate is averaged.
+    ///
+    /// A client exceeding the rate needs to stay below it for up to this
+    /// long before its responses are sent again. The default is 15
+    /// seconds. A window of less than a second is treated as one second.
+    pub fn set_window(&mut self, window: u32) {
+        self.window = window
+    }
+
+    /// Returns the number of seconds over which the rate is averaged.
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Sets every how many limited responses a truncated one is sent.
+    ///
+    /// A truncated response makes a legitimate client retry over TCP. A
+    /// value of zero means that limited responses are always dropped, a
+    /// value of one that they are always truncated. The default is 2.
+    pub fn set_slip(&mut self, slip: u32) {
+        self.slip = slip
+    }
+
+    /// Returns every how many limited responses a truncated one is sent.
+    pub fn slip(&self) -> u32 {
+        self.slip
+    }
+
+    /// Sets the prefix lengths that determine the networks of clients.
+    ///
+    /// The defaults are 24 for IPv4 and 56 for IPv6.
+    pub fn set_prefix_lens(&mut self, ipv4: u8, ipv6: u8) {
+        self.ipv4_prefix_len = ipv4.min(32);
+        self.ipv6_prefix_len = ipv6.min(128);
+    }
+
+    /// Returns the prefix length in bits of IPv4 client networks.
+    pub fn ipv4_prefix_len(&self) -> u8 {
+        self.ipv4_prefix_len
+    }
+
+    /// Returns the prefix length in bits of IPv6 client networks.
+    pub fn ipv6_prefix_len(&self) -> u8 {
+        self.ipv6_prefix_len
+    }
+
+    /// Sets the maximum number of buckets kept.
+    ///
+    /// If all buckets are in use, responses for new buckets are not
+    /// limited. The default is 100,000 buckets.
+    pub fn set_max_buckets(&mut self, max: usize) {
+        self.max_buckets = max
+    }
+
+    /// Returns the maximum number of buckets kept.
+    pub fn max_buckets(&self) -> usize {
+        self.max_buckets
+    }
+}
+
+impl Default for RrlConfig {
+    fn default() -> Self {
+        RrlConfig {
+            rates: [Self::DEFAULT_RATE; Category::COUNT],
+            window: 15,
+            slip: 2,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+            max_buckets: 100_000,
+        }
+    }
+}
+
+//------------ Category ------------------------------------------------------
+
+/// The kind of a response for the purpose of rate limiting.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Category {
+    /// A response with records in the answer section.
+    Response,
+
+    /// A response without records stating that the type doesn’t exist.
+    NoData,
+
+    /// A response stating that the name doesn’t exist.
+    NxDomain,
+
+    /// A referral to a delegated zone.
+    Referral,
+
+    /// A response with any other rcode.
+    Error,
+}
+
+impl Category {
+    /// The number of categories.
+    const COUNT: usize = 5;
+
+    /// Determines the category of a response message.
+    pub fn of_response(response: &Message<Bytes>) -> Self {
+        let header = response.header();
+        let counts = response.header_counts();
+        match header.rcode() {
+            Rcode::NoError => {}
+            Rcode::NXDomain => return Category::NxDomain,
+            _ => return Category::Error,
+        }
+        if counts.ancount() > 0 {
+            Category::Response
+        } else if !header.aa() && counts.nscount() > 0 {
+            Category::Referral
+        } else {
+            Category::NoData
+        }
+    }
+}
+
+//------------ Limiter -------------------------------------------------------
+
+/// The buckets of all clients and responses.
+struct Limiter {
+    /// The configuration.
+    config: RrlConfig,
+
+    /// The buckets by the hash of their key.
+    buckets: Mutex<HashMap<u64, Bucket>>,
+
+    /// The hasher for the bucket keys.
+    hasher: RandomState,
+}
+
+impl Limiter {
+    /// Creates a new limiter.
+    fn new(config: RrlConfig) -> Self {
+        Limiter {
+            config,
+            buckets: Default::default(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Accounts a response and returns what to actually send.
+    fn limit(
+        &self,
+        request: &Request,
+        response: Message<Bytes>,
+        now: Instant,
+    ) -> Option<Message<Bytes>> {
+        let category = Category::of_response(&response);
+        let rate = self.config.rate(category);
+        if rate == 0 {
+            return Some(response);
+        }
+        let key = self.key(request, &response, category);
+        let window = u64::from(self.config.window.max(1));
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if !buckets.contains_key(&key)
+            && buckets.len() >= self.config.max_buckets
+        {
+            // Buckets that haven’t been used for a whole window are full
+            // again and can be forgotten.
+            let idle = Duration::from_secs(window);
+            buckets
+                .retain(|_, bucket| now.duration_since(bucket.last) < idle);
+            if buckets.len() >= self.config.max_buckets {
+                return Some(response);
+            }
+        }
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket::new(now));
+        let slip = match bucket.take(rate, window, now) {
+            None => return Some(response),
+            Some(count) => {
+                self.config.slip != 0 && count % self.config.slip == 0
+            }
+        };
+        drop(buckets);
+
+        if slip {
+            truncated(request.message(), response.header().rcode())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the hash of the bucket key for a response.
+    fn key(
+        &self,
+        request: &Request,
+        response: &Message<Bytes>,
+        category: Category,
+    ) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        self.network(request.client_addr().ip()).hash(&mut hasher);
+        category.hash(&mut hasher);
+        match category {
+            Category::Response => {
+                if let Ok(question) = response.sole_question() {
+                    question.qname().iter().for_each(|label| {
+                        label.hash(&mut hasher);
+                    });
+                    question.qtype().hash(&mut hasher);
+                }
+            }
+            Category::NoData | Category::NxDomain | Category::Referral => {
+                let owner = response
+                    .authority()
+                    .ok()
+                    .and_then(|mut section| section.next())
+                    .and_then(Result::ok);
+                if let Some(record) = owner {
+                    record.owner().iter().for_each(|label| {
+                        label.hash(&mut hasher);
+                    });
+                }
+            }
+            Category::Error => {}
+        }
+        hasher.finish()
+    }
+
+    /// Returns the network of a client address.
+    fn network(&self, addr: IpAddr) -> IpAddr {
+        let len = match addr {
+            IpAddr::V4(_) => self.config.ipv4_prefix_len,
+            IpAddr::V6(_) => self.config.ipv6_prefix_len,
+        };
+        mask(addr, len)
+    }
+}
+
+//------------ Bucket --------------------------------------------------------
+
+/// A token bucket.
+struct Bucket {
+    /// The current balance in thousandths of a token.
+    ///
+    /// This becomes negative if responses keep arriving while the bucket
+    /// is empty, down to the rate times the window.
+    balance: i64,
+
+    /// The time of the last update of the balance.
+    last: Instant,
+
+    /// The number of responses limited since the bucket ran empty.
+    limited: u32,
+
+    /// Whether the bucket is new and needs to be filled.
+    new: bool,
+}
+
+impl Bucket {
+    /// Creates a new bucket.
+    fn new(now: Instant) -> Self {
+        Bucket {
+            balance: 0,
+            last: now,
+            limited: 0,
+            new: true,
+        }
+    }
+
+    /// Takes a token from the bucket.
+    ///
+    /// Returns `None` if the response is allowed or the number of limited
+    /// responses including this one otherwise.
+    fn take(&mut self, rate: u32, window: u64, now: Instant) -> Option<u32> {
+        let rate = i64::from(rate) * 1000;
+        if self.new {
+            self.balance = rate;
+            self.new = false;
+        } else {
+            let elapsed = i64::try_from(
+                now.saturating_duration_since(self.last).as_millis(),
+            )
+            .unwrap_or(i64::MAX);
+            self.balance = self
+                .balance
+                .saturating_add(elapsed.saturating_mul(rate) / 1000)
+                .min(rate);
+        }
+        self.last = now;
+        self.balance =
+            (self.balance - 1000).max(-rate.saturating_mul(window as i64));
+        if self.balance >= 0 {
+            self.limited = 0;
+            None
+        } else {
+            self.limited = self.limited.wrapping_add(1);
+            Some(self.limited)
+        }
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Creates a truncated response without records.
+fn truncated(
+    request: &Message<Bytes>,
+    rcode: Rcode,
+) -> Option<Message<Bytes>> {
+    let mut builder = MessageBuilder::new_bytes();
+    builder.header_mut().set_tc(true);
+    Some(builder.start_answer(request, rcode).ok()?.into_message())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rtype};
+    use crate::base::name::Dname;
+    use crate::base::Ttl;
+    use crate::rdata::{Soa, A};
+    use core::str::FromStr;
+    use std::net::SocketAddr;
+    use std::vec::Vec;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn request(qname: &str, client: &str) -> Request {
+        let mut msg = MessageBuilder::new_bytes().question();
+        msg.push((name(qname), Rtype::A)).unwrap();
+        Request::new(
+            msg.into_message(),
+            SocketAddr::new(client.parse().unwrap(), 53),
+            Transport::Udp,
+        )
+    }
+
+    fn response(request: &Request) -> Message<Bytes> {
+        let qname = request.message().sole_question().unwrap().into_qname();
+        if qname.first().as_slice().starts_with(b"nx") {
+            let mut msg = MessageBuilder::new_bytes()
+                .start_answer(request.message(), Rcode::NXDomain)
+                .unwrap()
+                .authority();
+            msg.push((
+                name("example.com"),
+                Class::In,
+                Ttl::HOUR,
+                Soa::new(
+                    name("ns.example.com"),
+                    name("hostmaster.example.com"),
+                    1.into(),
+                    Ttl::HOUR,
+                    Ttl::HOUR,
+                    Ttl::HOUR,
+                    Ttl::HOUR,
+                ),
+            ))
+            .unwrap();
+            return msg.into_message();
+        }
+        let mut msg = MessageBuilder::new_bytes()
+            .start_answer(request.message(), Rcode::NoError)
+            .unwrap();
+        msg.push((qname, Class::In, Ttl::HOUR, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        msg.into_message()
+    }
+
+    /// Returns what happens to the response to a request.
+    ///
+    /// This is `'s'` for sent, `'t'` for truncated, and `'d'` for dropped.
+    fn outcome(
+        rrl: &Rrl<()>,
+        qname: &str,
+        client: &str,
+        now: Instant,
+    ) -> char {
+        let request = request(qname, client);
+        match rrl.limiter.limit(&request, response(&request), now) {
+            Some(msg) if msg.header().tc() => {
+                assert_eq!(msg.header_counts().ancount(), 0);
+                't'
+            }
+            Some(_) => 's',
+            None => 'd',
+        }
+    }
+
+    #[test]
+    fn limit() {
+        let mut config = RrlConfig::default();
+        config.set_rate(Category::Response, 2);
+        config.set_rate(Category::NxDomain, 2);
+        config.set_window(2);
+        config.set_slip(3);
+        let rrl = Rrl::new((), config);
+        let start = Instant::now();
+        let run = |qname: &str, client: &str, secs: f64, count: usize| {
+            let now = start + Duration::from_secs_f64(secs);
+            (0..count)
+                .map(|_| outcome(&rrl, qname, client, now))
+                .collect::<std::string::String>()
+        };
+
+        assert_eq!(run("www.example.com", "192.0.2.1", 0., 8), "ssddtddt");
+        // Other names and networks have their own buckets.
+        assert_eq!(run("mail.example.com", "192.0.2.1", 0., 3), "ssd");
+        assert_eq!(run("www.example.com", "192.0.2.200", 0., 1), "d");
+        assert_eq!(run("www.example.com", "198.51.100.1", 0., 3), "ssd");
+
+        // The debt of -4 tokens takes more than two seconds to pay off.
+        assert_eq!(run("www.example.com", "192.0.2.1", 2., 1), "d");
+        assert_eq!(run("www.example.com", "192.0.2.1", 5., 3), "ssd");
+
+        // NXDOMAIN responses share the bucket of their zone.
+        assert_eq!(run("nx1.example.com", "192.0.2.1", 0., 1), "s");
+        assert_eq!(run("nx2.example.com", "192.0.2.1", 0., 1), "s");
+        assert_eq!(run("nx3.example.com", "192.0.2.1", 0., 1), "d");
+    }
+
+    #[test]
+    fn exempt() {
+        let mut config = RrlConfig::default();
+        config.set_rate(Category::Response, 0);
+        config.set_rate(Category::NxDomain, 1);
+        config.set_slip(1);
+        config.set_max_buckets(1);
+        let rrl = Rrl::new((), config);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(
+                outcome(&rrl, "www.example.com", "192.0.2.1", now),
+                's'
+            );
+        }
+        assert_eq!(outcome(&rrl, "nx.example.com", "192.0.2.1", now), 's');
+        assert_eq!(outcome(&rrl, "nx.example.com", "192.0.2.1", now), 't');
+        // All buckets in use: the new client isn’t limited.
+        for _ in 0..3 {
+            assert_eq!(
+                outcome(&rrl, "nx.example.com", "2001:db8::1", now),
+                's'
+            );
+        }
+    }
+}
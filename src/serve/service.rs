@@ -0,0 +1,152 @@
+//! The service processing requests.
+//!
+//! A server hands each request it receives to a [`Service`] as a
+//! [`Request`] and sends back the response message the service produces.
+
+use crate::base::message::Message;
+use bytes::Bytes;
+use core::future::Future;
+use std::net::SocketAddr;
+
+//------------ Service -------------------------------------------------------
+
+/// A type processing DNS requests.
+///
+/// The service is shared by all tasks of a server, so [`call`][Self::call]
+/// only takes `&self`. The returned future resolves into the response
+/// message or `None` if no response should be sent at all.
+///
+/// For responses sent over UDP, the service should make sure the message
+/// doesn’t exceed [`Request::max_response_size`] by truncating it and
+/// setting the TC bit if necessary. Messages exceeding the size are not
+/// sent.
+///
+/// The trait is implemented for closures and functions taking a request
+/// and returning a suitable future.
+pub trait Service: Send + Sync + 'static {
+    /// The future resolving into the response.
+    type Future: Future<Output = Option<Message<Bytes>>> + Send + 'static;
+
+    /// Processes a request.
+    fn call(&self, request: Request) -> Self::Future;
+}
+
+impl<F, Fut> Service for F
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<Message<Bytes>>> + Send + 'static,
+{
+    type Future = Fut;
+
+    fn call(&self, request: Request) -> Self::Future {
+        (self)(request)
+    }
+}
+
+//------------ Request -------------------------------------------------------
+
+/// A request received by a server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// The request message.
+    message: Message<Bytes>,
+
+    /// The address the request was received from.
+    client: SocketAddr,
+
+    /// The transport the request was received over.
+    transport: Transport,
+}
+
+impl Request {
+    /// The minimum maximum size of responses sent over UDP.
+    ///
+    /// Clients that don’t indicate a larger size via EDNS can only receive
+    /// messages of this size.
+    pub const MIN_UDP_SIZE: usize = 512;
+
+    /// Creates a new request.
+    pub fn new(
+        message: Message<Bytes>,
+        client: SocketAddr,
+        transport: Transport,
+    ) -> Self {
+        Request {
+            message,
+            client,
+            transport,
+        }
+    }
+
+    /// Returns the request message.
+    pub fn message(&self) -> &Message<Bytes> {
+        &self.message
+    }
+
+    /// Converts the request into the request message.
+    pub fn into_message(self) -> Message<Bytes> {
+        self.message
+    }
+
+    /// Returns the address the request was received from.
+    pub fn client_addr(&self) -> SocketAddr {
+        self.client
+    }
+
+    /// Returns the transport the request was received over.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the maximum size of the response.
+    ///
+    /// For requests received over UDP, this is the UDP payload size
+    /// indicated by the client via EDNS but at least
+    /// [`MIN_UDP_SIZE`][Self::MIN_UDP_SIZE]. For all other transports,
+    /// this is the maximum size of a DNS message.
+    pub fn max_response_size(&self) -> usize {
+        if self.transport != Transport::Udp {
+            return usize::from(u16::MAX);
+        }
+        match self.message.opt() {
+            Some(opt) => {
+                usize::from(opt.udp_payload_size()).max(Self::MIN_UDP_SIZE)
+            }
+            None => Self::MIN_UDP_SIZE,
+        }
+    }
+}
+
+//------------ Transport -----------------------------------------------------
+
+/// The transport protocol a request was received over.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Transport {
+    /// Unencrypted UDP transport.
+    Udp,
+
+    /// Unencrypted TCP transport.
+    Tcp,
+
+    /// DNS over TLS as defined in RFC 7858.
+    Tls,
+
+    /// DNS over HTTPS as defined in RFC 8484.
+    Https,
+}
+
+impl Transport {
+    /// Returns whether the transport is a streaming protocol.
+    ///
+    /// Messages sent over a streaming protocol are preceded by their
+    /// length as a 16 bit integer.
+    pub fn is_stream(self) -> bool {
+        matches!(self, Transport::Tcp | Transport::Tls)
+    }
+
+    /// Returns whether the transport is encrypted.
+    pub fn is_encrypted(self) -> bool {
+        matches!(self, Transport::Tls | Transport::Https)
+    }
+}
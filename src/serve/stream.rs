@@ -0,0 +1,455 @@
+//! Serving requests over TCP.
+//!
+//! The [`StreamServer`] accepts TCP connections and receives requests over
+//! them. Following RFC 7766, requests received over a single connection
+//! are processed concurrently and their responses are sent in the order
+//! they become available. Connections that don’t see a new request for a
+//! while are closed by the server.
+//!
+//! The same server provides DNS over TLS as defined in RFC 7858 if it is
+//! given an [`Accept`] implementation that performs the TLS handshake on
+//! newly accepted connections via [`StreamServer::with_tls`]. The crate
+//! doesn’t depend on a TLS library itself. With
+//! [tokio-rustls](https://github.com/rustls/tokio-rustls), for instance,
+//! a closure calling the acceptor is enough:
+//!
+//! ```ignore
+//! let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+//! let server = StreamServer::bind(addr, service)
+//!     .await?
+//!     .with_tls(move |sock| acceptor.accept(sock));
+//! ```
+
+use super::service::{Request, Service, Transport};
+use super::Tasks;
+use crate::base::message::Message;
+use crate::utils::stream::MessageStream;
+use bytes::Bytes;
+use core::future::{ready, Future, Ready};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+//------------ Module Configuration ------------------------------------------
+
+/// How long to pause accepting connections after an error.
+///
+/// Errors accepting connections are usually caused by running out of
+/// file descriptors. Pausing gives existing connections a chance to close.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The number of responses that can be queued for a connection.
+const RESPONSE_QUEUE: usize = 16;
+
+/// The default idle timeout.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+//------------ Accept --------------------------------------------------------
+
+/// A type preparing accepted connections for use.
+///
+/// The future returned by [`accept`][Self::accept] resolves into the
+/// stream requests are read from and responses written to. This is where
+/// a TLS handshake would happen.
+///
+/// The trait is implemented for closures taking a [`TcpStream`] and
+/// returning a suitable future.
+pub trait Accept: Send + Sync + 'static {
+    /// The stream produced from a connection.
+    type Stream: AsyncRead + AsyncWrite + Send + 'static;
+
+    /// The future resolving into the stream.
+    type Future: Future<Output = Result<Self::Stream, io::Error>>
+        + Send
+        + 'static;
+
+    /// Prepares a newly accepted connection.
+    fn accept(&self, sock: TcpStream) -> Self::Future;
+}
+
+impl<F, Fut, St> Accept for F
+where
+    F: Fn(TcpStream) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<St, io::Error>> + Send + 'static,
+    St: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Stream = St;
+    type Future = Fut;
+
+    fn accept(&self, sock: TcpStream) -> Self::Future {
+        (self)(sock)
+    }
+}
+
+//------------ PlainAccept ---------------------------------------------------
+
+/// Accepting connections for unencrypted TCP.
+///
+/// This is the [`Accept`] implementation used by default. It uses
+/// connections as they are.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlainAccept;
+
+impl Accept for PlainAccept {
+    type Stream = TcpStream;
+    type Future = Ready<Result<TcpStream, io::Error>>;
+
+    fn accept(&self, sock: TcpStream) -> Self::Future {
+        ready(Ok(sock))
+    }
+}
+
+//------------ StreamServer --------------------------------------------------
+
+/// A server accepting requests over TCP or TLS.
+///
+/// Messages that can’t be parsed or that aren’t requests are ignored but
+/// the connection stays open.
+pub struct StreamServer<S, A = PlainAccept> {
+    /// The listener to accept connections from.
+    listener: TcpListener,
+
+    /// The service to process requests with.
+    service: Arc<S>,
+
+    /// The acceptor preparing new connections.
+    acceptor: Arc<A>,
+
+    /// The transport to report for requests.
+    transport: Transport,
+
+    /// How long to keep idle connections open.
+    idle_timeout: Duration,
+}
+
+impl<S: Service> StreamServer<S> {
+    /// Creates a new server listening on the given address.
+    pub async fn bind(
+        addr: SocketAddr,
+        service: S,
+    ) -> Result<Self, io::Error> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?, service))
+    }
+
+    /// Creates a new server using an existing listener.
+    pub fn from_listener(listener: TcpListener, service: S) -> Self {
+        StreamServer {
+            listener,
+            service: Arc::new(service),
+            acceptor: Arc::new(PlainAccept),
+            transport: Transport::Tcp,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Converts the server into one serving DNS over TLS.
+    ///
+    /// The acceptor is expected to perform the TLS handshake on each new
+    /// connection. Requests are then reported as received via
+    /// [`Transport::Tls`].
+    pub fn with_tls<A: Accept>(self, acceptor: A) -> StreamServer<S, A> {
+        StreamServer {
+            listener: self.listener,
+            service: self.service,
+            acceptor: Arc::new(acceptor),
+            transport: Transport::Tls,
+            idle_timeout: self.idle_timeout,
+        }
+    }
+}
+
+impl<S: Service, A: Accept> StreamServer<S, A> {
+    /// The default idle timeout.
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = DEFAULT_IDLE_TIMEOUT;
+
+    /// Sets how long to keep a connection open without a new request.
+    ///
+    /// A connection is only closed once all its requests have been
+    /// responded to. The timeout also limits how long the acceptor may
+    /// take to prepare a new connection.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = timeout
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Returns the local address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.listener.local_addr()
+    }
+
+    /// Runs the server until `shutdown` completes.
+    ///
+    /// Once `shutdown` has completed, the server stops accepting
+    /// connections and reading new requests from existing connections. It
+    /// returns after responses to all requests received so far have been
+    /// sent.
+    pub async fn run(
+        self,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), io::Error> {
+        let StreamServer {
+            listener,
+            service,
+            acceptor,
+            transport,
+            idle_timeout,
+        } = self;
+        Tasks::run(
+            |mut tasks| async move {
+                loop {
+                    let (sock, client) = tokio::select! {
+                        res = listener.accept() => match res {
+                            Ok(res) => res,
+                            Err(_) => {
+                                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                                continue;
+                            }
+                        },
+                        _ = tasks.shutdown() => return Ok(()),
+                    };
+                    let accept = acceptor.accept(sock);
+                    let service = service.clone();
+                    let tasks = tasks.clone();
+                    tokio::spawn(async move {
+                        let sock = match timeout(idle_timeout, accept).await {
+                            Ok(Ok(sock)) => sock,
+                            _ => return,
+                        };
+                        serve_connection(
+                            sock,
+                            client,
+                            transport,
+                            service,
+                            idle_timeout,
+                            tasks,
+                        )
+                        .await
+                    });
+                }
+            },
+            shutdown,
+        )
+        .await
+    }
+}
+
+impl<S, A> fmt::Debug for StreamServer<S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamServer")
+            .field("listener", &self.listener)
+            .field("transport", &self.transport)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Serves requests received over a stream.
+///
+/// Requests are read and handed to a task of their own for processing
+/// until the stream is closed by the client, it has been idle for
+/// `idle_timeout`, or the server shuts down. Responses are written as
+/// they become available. The function returns once responses to all
+/// requests have been written.
+async fn serve_connection<Sock, S>(
+    sock: Sock,
+    client: SocketAddr,
+    transport: Transport,
+    service: Arc<S>,
+    idle_timeout: Duration,
+    mut tasks: Tasks,
+) where
+    Sock: AsyncRead + AsyncWrite + Send + 'static,
+    S: Service,
+{
+    let (read, mut write) = tokio::io::split(sock);
+    let (tx, mut rx) = mpsc::channel::<Message<Bytes>>(RESPONSE_QUEUE);
+
+    let reader = async move {
+        let mut read = MessageStream::new(read);
+        loop {
+            let message = tokio::select! {
+                res = timeout(idle_timeout, read.next_message()) => match res {
+                    Ok(Ok(Some(message))) => message,
+                    _ => return,
+                },
+                _ = tasks.shutdown() => return,
+                _ = tx.closed() => return,
+            };
+            let message = match Message::from_octets(message) {
+                Ok(message) if !message.header().qr() => message,
+                _ => continue,
+            };
+            let response =
+                service.call(Request::new(message, client, transport));
+            let tx = tx.clone();
+            let task = tasks.clone();
+            tokio::spawn(async move {
+                if let Some(response) = response.await {
+                    let _ = tx.send(response).await;
+                }
+                drop(task);
+            });
+        }
+    };
+
+    let writer = async move {
+        let mut frame = Vec::new();
+        while let Some(response) = rx.recv().await {
+            let response = response.as_slice();
+            let len = match u16::try_from(response.len()) {
+                Ok(len) => len,
+                Err(_) => continue,
+            };
+            frame.clear();
+            frame.extend_from_slice(&len.to_be_bytes());
+            frame.extend_from_slice(response);
+            if write.write_all(&frame).await.is_err() {
+                return;
+            }
+        }
+        let _ = write.shutdown().await;
+    };
+
+    tokio::join!(reader, writer);
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use core::str::FromStr;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    /// Answers requests in reverse order of their IDs.
+    async fn delayed(request: Request) -> Option<Message<Bytes>> {
+        let id = request.message().header().id();
+        tokio::time::sleep(Duration::from_millis(100 * u64::from(10 - id)))
+            .await;
+        let answer = MessageBuilder::new_bytes()
+            .start_answer(request.message(), Rcode::NoError)
+            .unwrap();
+        Some(answer.into_message())
+    }
+
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_stream_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish().into_target()
+    }
+
+    async fn connect(addr: SocketAddr) -> MessageStream<TcpStream> {
+        MessageStream::new(TcpStream::connect(addr).await.unwrap())
+    }
+
+    async fn read_id(sock: &mut MessageStream<TcpStream>) -> u16 {
+        let message = sock.next_message().await.unwrap().unwrap();
+        let message = Message::from_octets(message).unwrap();
+        assert!(message.header().qr());
+        message.header().id()
+    }
+
+    #[tokio::test]
+    async fn pipelining_and_shutdown() {
+        let server =
+            StreamServer::bind("127.0.0.1:0".parse().unwrap(), delayed)
+                .await
+                .unwrap();
+        let addr = server.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let server = tokio::spawn(server.run(async {
+            let _ = shutdown_rx.await;
+        }));
+
+        let mut sock = connect(addr).await;
+        sock.get_mut().write_all(&query(8)).await.unwrap();
+        sock.get_mut().write_all(&query(9)).await.unwrap();
+        assert_eq!(read_id(&mut sock).await, 9);
+
+        // Requests still being processed are answered after shutdown.
+        sock.get_mut().write_all(&query(7)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown_tx.send(()).unwrap();
+        assert_eq!(read_id(&mut sock).await, 8);
+        assert_eq!(read_id(&mut sock).await, 7);
+        assert!(sock.next_message().await.unwrap().is_none());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() {
+        let mut server =
+            StreamServer::bind("127.0.0.1:0".parse().unwrap(), delayed)
+                .await
+                .unwrap();
+        server.set_idle_timeout(Duration::from_millis(100));
+        let addr = server.local_addr().unwrap();
+        let server = tokio::spawn(server.run(futures::future::pending()));
+
+        let mut sock = connect(addr).await;
+        sock.get_mut().write_all(&query(9)).await.unwrap();
+
+        // The connection isn’t closed while the request is processed.
+        assert_eq!(read_id(&mut sock).await, 9);
+        assert!(sock.next_message().await.unwrap().is_none());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn tls() {
+        async fn tls_only(request: Request) -> Option<Message<Bytes>> {
+            assert_eq!(request.transport(), Transport::Tls);
+            delayed(request).await
+        }
+
+        // The “handshake” expects the client to send a single zero octet
+        // first.
+        let server =
+            StreamServer::bind("127.0.0.1:0".parse().unwrap(), tls_only)
+                .await
+                .unwrap()
+                .with_tls(|mut sock: TcpStream| async move {
+                    match sock.read_u8().await? {
+                        0 => Ok(sock),
+                        _ => {
+                            Err(io::Error::new(io::ErrorKind::Other, "nope"))
+                        }
+                    }
+                });
+        let addr = server.local_addr().unwrap();
+        let server = tokio::spawn(server.run(futures::future::pending()));
+
+        let mut sock = connect(addr).await;
+        sock.get_mut().write_all(&[0]).await.unwrap();
+        sock.get_mut().write_all(&query(9)).await.unwrap();
+        assert_eq!(read_id(&mut sock).await, 9);
+
+        // A failed handshake closes the connection.
+        let mut sock = connect(addr).await;
+        sock.get_mut().write_all(&[1]).await.unwrap();
+        assert!(sock.next_message().await.unwrap().is_none());
+        server.abort();
+    }
+}
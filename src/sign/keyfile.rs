@@ -0,0 +1,471 @@
+//! Reading and writing key files.
+//!
+//! BIND and many tools following its lead store a DNSSEC key in a pair of
+//! files. The public key file with the extension `.key` contains the DNSKEY
+//! record in zone file format while the private key file with the
+//! extension `.private` contains the secret key material as a list of
+//! `Field: value` lines. Both files share a base name of the form
+//! `K<owner>+<algorithm>+<key tag>` which is produced by [`file_name`].
+//!
+//! Public key files are read with [`parse_public`] and written with
+//! [`format_public`]. The content of private key files is represented by
+//! [`SecretKey`] which can be parsed from a string and written using its
+//! `Display` implementation.
+
+use crate::base::iana::SecAlg;
+use crate::base::name::{Dname, ToDname};
+use crate::rdata::Dnskey;
+use crate::utils::base64;
+use core::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ File Names and Public Keys ------------------------------------
+
+/// Returns the base name of the key files for a key.
+///
+/// The name has the form `K<owner>+<algorithm>+<key tag>` with the owner
+/// including its trailing dot and the algorithm and key tag as zero-padded
+/// decimal numbers. The extension `.key` or `.private` needs to be added to
+/// get the actual file names.
+pub fn file_name(
+    owner: &impl ToDname,
+    algorithm: SecAlg,
+    key_tag: u16,
+) -> String {
+    format!(
+        "K{}+{:03}+{:05}",
+        absolute(owner),
+        algorithm.to_int(),
+        key_tag
+    )
+}
+
+/// Returns the content of a public key file.
+///
+/// The file contains a comment describing the key followed by the DNSKEY
+/// record without a TTL.
+pub fn format_public<Octs: AsRef<[u8]>>(
+    owner: &impl ToDname,
+    dnskey: &Dnskey<Octs>,
+) -> String {
+    format!(
+        "; This is a {} key, keyid {}, for {}\n{} IN DNSKEY {}\n",
+        if dnskey.is_secure_entry_point() {
+            "key-signing"
+        } else {
+            "zone-signing"
+        },
+        dnskey.key_tag(),
+        absolute(owner),
+        absolute(owner),
+        dnskey
+    )
+}
+
+/// Parses the content of a public key file.
+///
+/// Comments are ignored. The record may contain a TTL and a class.
+#[allow(clippy::type_complexity)]
+pub fn parse_public(
+    s: &str,
+) -> Result<(Dname<Vec<u8>>, Dnskey<Vec<u8>>), KeyFileError> {
+    let mut tokens = s
+        .lines()
+        .map(|line| line.split(';').next().unwrap_or(""))
+        .flat_map(str::split_whitespace)
+        .filter(|token| *token != "(" && *token != ")");
+    let owner = tokens
+        .next()
+        .and_then(|token| Dname::from_str(token).ok())
+        .ok_or(KeyFileError::Format)?;
+    let mut token = tokens.next().ok_or(KeyFileError::Format)?;
+    if token.bytes().all(|ch| ch.is_ascii_digit()) {
+        token = tokens.next().ok_or(KeyFileError::Format)?;
+    }
+    if !token.eq_ignore_ascii_case("DNSKEY") {
+        token = tokens.next().ok_or(KeyFileError::Format)?;
+    }
+    if !token.eq_ignore_ascii_case("DNSKEY") {
+        return Err(KeyFileError::Format);
+    }
+    let flags = parse_field(tokens.next())?;
+    let protocol = parse_field(tokens.next())?;
+    let algorithm = parse_field(tokens.next())?;
+    let public_key = base64::decode(&tokens.collect::<String>())
+        .map_err(|_| KeyFileError::Format)?;
+    let dnskey = Dnskey::new(flags, protocol, algorithm, public_key)
+        .map_err(|_| KeyFileError::Format)?;
+    Ok((owner, dnskey))
+}
+
+fn parse_field<T: FromStr>(token: Option<&str>) -> Result<T, KeyFileError> {
+    token
+        .and_then(|token| token.parse().ok())
+        .ok_or(KeyFileError::Format)
+}
+
+/// Returns the string of an absolute name including the trailing dot.
+fn absolute(name: &impl ToDname) -> String {
+    let name = name.to_vec();
+    if name.is_root() {
+        ".".into()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+//------------ SecretKey -----------------------------------------------------
+
+/// The secret key material of a private key file.
+///
+/// The type implements `Display` producing the content of a private key
+/// file in the format `v1.3` and can be parsed from any `v1.x` private key
+/// file. Fields unknown to or not needed by this type, such as the key
+/// timing information, are ignored when parsing.
+///
+/// For obvious reasons, the type does not implement `Debug`.
+#[derive(Clone)]
+pub enum SecretKey {
+    /// A key for one of the RSA algorithms.
+    Rsa(SecAlg, RsaSecretKey),
+
+    /// An ECDSA key using P-256 and SHA-256 given by its private scalar.
+    EcdsaP256Sha256(Vec<u8>),
+
+    /// An ECDSA key using P-384 and SHA-384 given by its private scalar.
+    EcdsaP384Sha384(Vec<u8>),
+
+    /// An Ed25519 key given by its 32 octet seed.
+    Ed25519(Vec<u8>),
+}
+
+impl SecretKey {
+    /// Returns the algorithm of the key.
+    pub fn algorithm(&self) -> SecAlg {
+        match *self {
+            SecretKey::Rsa(algorithm, _) => algorithm,
+            SecretKey::EcdsaP256Sha256(_) => SecAlg::EcdsaP256Sha256,
+            SecretKey::EcdsaP384Sha384(_) => SecAlg::EcdsaP384Sha384,
+            SecretKey::Ed25519(_) => SecAlg::Ed25519,
+        }
+    }
+
+    /// Parses the content of a private key file.
+    pub fn parse(s: &str) -> Result<Self, KeyFileError> {
+        let mut fields = s.lines().filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim(), value.trim()))
+        });
+
+        match fields.next() {
+            Some(("Private-key-format", version))
+                if version.starts_with("v1.") => {}
+            _ => return Err(KeyFileError::UnsupportedFormat),
+        }
+
+        let mut algorithm = None;
+        let mut private_key = None;
+        let mut rsa: [Option<Vec<u8>>; 8] = Default::default();
+        for (key, value) in fields {
+            let idx = match key {
+                "Algorithm" => {
+                    algorithm = Some(
+                        value
+                            .split_whitespace()
+                            .next()
+                            .and_then(|alg| alg.parse::<u8>().ok())
+                            .map(SecAlg::from_int)
+                            .ok_or(KeyFileError::Format)?,
+                    );
+                    continue;
+                }
+                "PrivateKey" => {
+                    private_key = Some(decode(value)?);
+                    continue;
+                }
+                key => match RsaSecretKey::FIELDS
+                    .iter()
+                    .position(|field| *field == key)
+                {
+                    Some(idx) => idx,
+                    None => continue,
+                },
+            };
+            rsa[idx] = Some(decode(value)?);
+        }
+
+        let algorithm = algorithm.ok_or(KeyFileError::Format)?;
+        match algorithm {
+            SecAlg::RsaSha1
+            | SecAlg::RsaSha1Nsec3Sha1
+            | SecAlg::RsaSha256
+            | SecAlg::RsaSha512 => {
+                let mut rsa = rsa.into_iter();
+                let mut next =
+                    || rsa.next().flatten().ok_or(KeyFileError::Format);
+                Ok(SecretKey::Rsa(
+                    algorithm,
+                    RsaSecretKey {
+                        modulus: next()?,
+                        public_exponent: next()?,
+                        private_exponent: next()?,
+                        prime1: next()?,
+                        prime2: next()?,
+                        exponent1: next()?,
+                        exponent2: next()?,
+                        coefficient: next()?,
+                    },
+                ))
+            }
+            SecAlg::EcdsaP256Sha256 => private_key
+                .map(SecretKey::EcdsaP256Sha256)
+                .ok_or(KeyFileError::Format),
+            SecAlg::EcdsaP384Sha384 => private_key
+                .map(SecretKey::EcdsaP384Sha384)
+                .ok_or(KeyFileError::Format),
+            SecAlg::Ed25519 => private_key
+                .map(SecretKey::Ed25519)
+                .ok_or(KeyFileError::Format),
+            _ => Err(KeyFileError::UnsupportedAlgorithm),
+        }
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, KeyFileError> {
+    base64::decode(value).map_err(|_| KeyFileError::Format)
+}
+
+//--- FromStr
+
+impl FromStr for SecretKey {
+    type Err = KeyFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let algorithm = self.algorithm();
+        writeln!(f, "Private-key-format: v1.3")?;
+        writeln!(f, "Algorithm: {} ({})", algorithm.to_int(), algorithm)?;
+        match *self {
+            SecretKey::Rsa(_, ref key) => {
+                for (field, value) in
+                    RsaSecretKey::FIELDS.iter().zip(key.values())
+                {
+                    writeln!(
+                        f,
+                        "{}: {}",
+                        field,
+                        base64::encode_string(value)
+                    )?;
+                }
+                Ok(())
+            }
+            SecretKey::EcdsaP256Sha256(ref key)
+            | SecretKey::EcdsaP384Sha384(ref key)
+            | SecretKey::Ed25519(ref key) => {
+                writeln!(f, "PrivateKey: {}", base64::encode_string(key))
+            }
+        }
+    }
+}
+
+//------------ RsaSecretKey --------------------------------------------------
+
+/// The components of an RSA secret key.
+///
+/// All components are big-endian unsigned integers.
+#[derive(Clone)]
+pub struct RsaSecretKey {
+    pub modulus: Vec<u8>,
+    pub public_exponent: Vec<u8>,
+    pub private_exponent: Vec<u8>,
+    pub prime1: Vec<u8>,
+    pub prime2: Vec<u8>,
+    pub exponent1: Vec<u8>,
+    pub exponent2: Vec<u8>,
+    pub coefficient: Vec<u8>,
+}
+
+impl RsaSecretKey {
+    /// The names of the fields in the order of `values`.
+    const FIELDS: [&'static str; 8] = [
+        "Modulus",
+        "PublicExponent",
+        "PrivateExponent",
+        "Prime1",
+        "Prime2",
+        "Exponent1",
+        "Exponent2",
+        "Coefficient",
+    ];
+
+    fn values(&self) -> [&Vec<u8>; 8] {
+        [
+            &self.modulus,
+            &self.public_exponent,
+            &self.private_exponent,
+            &self.prime1,
+            &self.prime2,
+            &self.exponent1,
+            &self.exponent2,
+            &self.coefficient,
+        ]
+    }
+
+    /// Returns the public key in the format of the DNSKEY record.
+    ///
+    /// This format is defined in [RFC 3110, section 2].
+    ///
+    /// [RFC 3110, section 2]: https://tools.ietf.org/html/rfc3110#section-2
+    pub fn public_key(&self) -> Vec<u8> {
+        let exponent = strip_zeros(&self.public_exponent);
+        let mut res =
+            Vec::with_capacity(exponent.len() + self.modulus.len() + 3);
+        match u8::try_from(exponent.len()) {
+            Ok(len) => res.push(len),
+            Err(_) => {
+                res.push(0);
+                res.extend_from_slice(&(exponent.len() as u16).to_be_bytes());
+            }
+        }
+        res.extend_from_slice(exponent);
+        res.extend_from_slice(strip_zeros(&self.modulus));
+        res
+    }
+}
+
+/// Returns an integer without its leading zeros.
+fn strip_zeros(value: &[u8]) -> &[u8] {
+    let start = value.iter().position(|ch| *ch != 0).unwrap_or(value.len());
+    &value[start..]
+}
+
+//------------ KeyFileError --------------------------------------------------
+
+/// A key file could not be used.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyFileError {
+    /// The content of the file is not formatted correctly.
+    Format,
+
+    /// The private key file uses an unsupported format version.
+    UnsupportedFormat,
+
+    /// The algorithm of the key is not supported.
+    UnsupportedAlgorithm,
+
+    /// The key material was rejected.
+    ///
+    /// This happens if the secret key is malformed or doesn’t match the
+    /// public key.
+    InvalidKey,
+}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyFileError::Format => "invalid key file",
+            KeyFileError::UnsupportedFormat => {
+                "unsupported private key format"
+            }
+            KeyFileError::UnsupportedAlgorithm => "unsupported algorithm",
+            KeyFileError::InvalidKey => "invalid key",
+        })
+    }
+}
+
+impl error::Error for KeyFileError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    const PUBLIC: &str = "\
+        ; This is a key-signing key, keyid 3613, for example.com.\n\
+        ; Created: 20230101000000 (Sun Jan  1 00:00:00 2023)\n\
+        example.com. 3600 IN DNSKEY 257 3 15 \
+        l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=\n";
+
+    const PRIVATE: &str = "\
+        Private-key-format: v1.3\n\
+        Algorithm: 15 (ED25519)\n\
+        PrivateKey: ODIyNjAzODQ2MjgwODAxMjI2NDUxOTAyMDQxNDIyNjI=\n\
+        Created: 20230101000000\n";
+
+    #[test]
+    fn public() {
+        let (owner, dnskey) = parse_public(PUBLIC).unwrap();
+        assert_eq!(owner.to_string(), "example.com");
+        assert_eq!(dnskey.flags(), 257);
+        assert_eq!(dnskey.algorithm(), SecAlg::Ed25519);
+        assert_eq!(dnskey.key_tag(), 3613);
+        assert_eq!(
+            file_name(&owner, dnskey.algorithm(), dnskey.key_tag()),
+            "Kexample.com.+015+03613"
+        );
+        assert_eq!(
+            parse_public(&format_public(&owner, &dnskey)).unwrap(),
+            (owner, dnskey)
+        );
+        assert_eq!(
+            parse_public("example. IN A 192.0.2.1"),
+            Err(KeyFileError::Format)
+        );
+        assert_eq!(
+            file_name(&Dname::root_vec(), SecAlg::RsaSha256, 20326),
+            "K.+008+20326"
+        );
+    }
+
+    #[test]
+    fn private() {
+        let key = SecretKey::parse(PRIVATE).unwrap();
+        assert_eq!(key.algorithm(), SecAlg::Ed25519);
+        assert_eq!(
+            key.to_string(),
+            "Private-key-format: v1.3\n\
+             Algorithm: 15 (ED25519)\n\
+             PrivateKey: ODIyNjAzODQ2MjgwODAxMjI2NDUxOTAyMDQxNDIyNjI=\n"
+        );
+        assert!(matches!(
+            SecretKey::parse("Private-key-format: v2.0\nAlgorithm: 15\n"),
+            Err(KeyFileError::UnsupportedFormat)
+        ));
+        assert!(matches!(
+            SecretKey::parse("Private-key-format: v1.3\nAlgorithm: 3\n"),
+            Err(KeyFileError::UnsupportedAlgorithm)
+        ));
+
+        let rsa = SecretKey::parse(
+            "Private-key-format: v1.2\n\
+             Algorithm: 8 (RSASHA256)\n\
+             Modulus: AQID\n\
+             PublicExponent: AQAB\n\
+             PrivateExponent: AQ==\n\
+             Prime1: AQ==\n\
+             Prime2: AQ==\n\
+             Exponent1: AQ==\n\
+             Exponent2: AQ==\n\
+             Coefficient: AQ==\n",
+        )
+        .unwrap();
+        match rsa {
+            SecretKey::Rsa(SecAlg::RsaSha256, ref key) => {
+                assert_eq!(key.public_key(), b"\x03\x01\x00\x01\x01\x02\x03");
+            }
+            _ => panic!("not an RSA key"),
+        }
+        assert!(SecretKey::parse(&rsa.to_string()).is_ok());
+    }
+}
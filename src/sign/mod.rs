@@ -5,6 +5,7 @@
 #![cfg_attr(docsrs, doc(cfg(feature = "sign")))]
 
 pub mod key;
-//pub mod openssl;
+pub mod keyfile;
+pub mod openssl;
 pub mod records;
 pub mod ring;
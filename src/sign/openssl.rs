@@ -2,30 +2,218 @@
 #![cfg(feature = "openssl")]
 #![cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
 
-use std::vec::Vec;
-use openssl::error::ErrorStack;
-use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
-use openssl::sha::sha256;
-use openssl::sign::Signer as OpenSslSigner;
-use unwrap::unwrap;
-use crate::base::iana::DigestAlg;
-use crate::base::name::ToDname;
-use crate::base::octets::Compose;
-use crate::rdata::{Ds, Dnskey};
 use super::key::SigningKey;
+use super::keyfile::{self, KeyFileError, RsaSecretKey, SecretKey};
+use crate::base::iana::{DigestAlg, SecAlg};
+use crate::base::name::{Dname, ToDname};
+use crate::crypto::OpensslBackend;
+use crate::rdata::{Dnskey, Ds};
+use openssl::bn::{BigNum, BigNumContext, BigNumRef};
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::Signer;
+use std::string::String;
+use std::vec::Vec;
+use std::{error, fmt};
 
+//------------ Key -----------------------------------------------------------
 
+/// A signing key using OpenSSL.
 pub struct Key {
     dnskey: Dnskey<Vec<u8>>,
     key: PKey<Private>,
-    digest: MessageDigest,
+    secret: SecretKey,
+}
+
+impl Key {
+    /// The size of generated RSA keys in bits.
+    const RSA_BITS: u32 = 2048;
+
+    /// Generates a new key.
+    ///
+    /// Keys can be generated for RSA with SHA-256 or SHA-512, ECDSA with
+    /// P-256 or P-384, and Ed25519. RSA keys are 2048 bits long.
+    pub fn generate(algorithm: SecAlg, flags: u16) -> Result<Self, Error> {
+        let (key, secret, public_key) = match algorithm {
+            SecAlg::RsaSha256 | SecAlg::RsaSha512 => {
+                let rsa = Rsa::generate(Self::RSA_BITS)?;
+                let components = RsaSecretKey {
+                    modulus: rsa.n().to_vec(),
+                    public_exponent: rsa.e().to_vec(),
+                    private_exponent: rsa.d().to_vec(),
+                    prime1: to_vec(rsa.p())?,
+                    prime2: to_vec(rsa.q())?,
+                    exponent1: to_vec(rsa.dmp1())?,
+                    exponent2: to_vec(rsa.dmq1())?,
+                    coefficient: to_vec(rsa.iqmp())?,
+                };
+                let public_key = components.public_key();
+                (
+                    PKey::from_rsa(rsa)?,
+                    SecretKey::Rsa(algorithm, components),
+                    public_key,
+                )
+            }
+            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+                let curve = Curve::new(algorithm);
+                let group = EcGroup::from_curve_name(curve.nid)?;
+                let key = EcKey::generate(&group)?;
+                let scalar =
+                    key.private_key().to_vec_padded(curve.scalar_len)?;
+                let mut ctx = BigNumContext::new()?;
+                let public_key = key.public_key().to_bytes(
+                    &group,
+                    PointConversionForm::UNCOMPRESSED,
+                    &mut ctx,
+                )?;
+                (
+                    PKey::from_ec_key(key)?,
+                    curve.secret(scalar),
+                    public_key[1..].into(),
+                )
+            }
+            SecAlg::Ed25519 => {
+                let key = PKey::generate_ed25519()?;
+                let seed = key.raw_private_key()?;
+                let public_key = key.raw_public_key()?;
+                (key, SecretKey::Ed25519(seed), public_key)
+            }
+            _ => return Err(Error::Unsupported),
+        };
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, algorithm, public_key)
+                .expect("long key"),
+            key,
+            secret,
+        })
+    }
+
+    /// Creates a key from its public and secret parts.
+    ///
+    /// Returns an error if the algorithm isn’t supported or the secret key
+    /// doesn’t match the public key in `dnskey`.
+    pub fn from_secret_key(
+        dnskey: Dnskey<Vec<u8>>,
+        secret: SecretKey,
+    ) -> Result<Self, Error> {
+        if secret.algorithm() != dnskey.algorithm() {
+            return Err(Error::InvalidKey);
+        }
+        let key = match secret {
+            SecretKey::Rsa(algorithm, ref components) => {
+                if !matches!(algorithm, SecAlg::RsaSha256 | SecAlg::RsaSha512)
+                {
+                    return Err(Error::Unsupported);
+                }
+                if components.public_key() != dnskey.public_key().as_slice() {
+                    return Err(Error::InvalidKey);
+                }
+                let rsa = Rsa::from_private_components(
+                    BigNum::from_slice(&components.modulus)?,
+                    BigNum::from_slice(&components.public_exponent)?,
+                    BigNum::from_slice(&components.private_exponent)?,
+                    BigNum::from_slice(&components.prime1)?,
+                    BigNum::from_slice(&components.prime2)?,
+                    BigNum::from_slice(&components.exponent1)?,
+                    BigNum::from_slice(&components.exponent2)?,
+                    BigNum::from_slice(&components.coefficient)?,
+                )?;
+                if !rsa.check_key()? {
+                    return Err(Error::InvalidKey);
+                }
+                PKey::from_rsa(rsa)?
+            }
+            SecretKey::EcdsaP256Sha256(ref scalar)
+            | SecretKey::EcdsaP384Sha384(ref scalar) => {
+                let curve = Curve::new(secret.algorithm());
+                let group = EcGroup::from_curve_name(curve.nid)?;
+                let mut point = vec![0x4];
+                point.extend_from_slice(dnskey.public_key());
+                let mut ctx = BigNumContext::new()?;
+                let point = EcPoint::from_bytes(&group, &point, &mut ctx)
+                    .map_err(|_| Error::InvalidKey)?;
+                let key = EcKey::from_private_components(
+                    &group,
+                    &*BigNum::from_slice(scalar)?,
+                    &point,
+                )?;
+                key.check_key().map_err(|_| Error::InvalidKey)?;
+                PKey::from_ec_key(key)?
+            }
+            SecretKey::Ed25519(ref seed) => {
+                let key = PKey::private_key_from_raw_bytes(seed, Id::ED25519)
+                    .map_err(|_| Error::InvalidKey)?;
+                if key.raw_public_key()? != dnskey.public_key().as_slice() {
+                    return Err(Error::InvalidKey);
+                }
+                key
+            }
+        };
+        Ok(Key {
+            dnskey,
+            key,
+            secret,
+        })
+    }
+
+    /// Creates a key from the content of its public and private key files.
+    ///
+    /// Returns the owner of the key given in the public key file and the
+    /// key.
+    pub fn from_key_files(
+        public: &str,
+        private: &str,
+    ) -> Result<(Dname<Vec<u8>>, Self), KeyFileError> {
+        let (owner, dnskey) = keyfile::parse_public(public)?;
+        let secret = SecretKey::parse(private)?;
+        let key = Self::from_secret_key(dnskey, secret)
+            .map_err(|_| KeyFileError::InvalidKey)?;
+        Ok((owner, key))
+    }
+
+    /// Returns the secret key material.
+    ///
+    /// Its `Display` implementation produces the content of the private
+    /// key file.
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret
+    }
+
+    /// Returns the content of the public key file for the key.
+    pub fn public_key_file(&self, owner: &impl ToDname) -> String {
+        keyfile::format_public(owner, &self.dnskey)
+    }
+
+    /// Returns the DS record data for the key using the given digest.
+    ///
+    /// The digest algorithm can be SHA-256 or SHA-384.
+    pub fn ds_with<N: ToDname>(
+        &self,
+        owner: N,
+        digest_alg: DigestAlg,
+    ) -> Result<Ds<Vec<u8>>, Error> {
+        if !matches!(digest_alg, DigestAlg::Sha256 | DigestAlg::Sha384) {
+            return Err(Error::Unsupported);
+        }
+        Ds::from_dnskey_with(
+            &OpensslBackend,
+            &owner,
+            &self.dnskey,
+            digest_alg,
+        )
+        .map_err(|_| Error::Unsupported)
+    }
 }
 
 impl SigningKey for Key {
     type Octets = Vec<u8>;
     type Signature = Vec<u8>;
-    type Error = ErrorStack;
+    type Error = Error;
 
     fn dnskey(&self) -> Result<Dnskey<Self::Octets>, Self::Error> {
         Ok(self.dnskey.clone())
@@ -33,26 +221,225 @@ impl SigningKey for Key {
 
     fn ds<N: ToDname>(
         &self,
-        owner: N
+        owner: N,
     ) -> Result<Ds<Self::Octets>, Self::Error> {
-        let mut buf = Vec::new();
-        unwrap!(owner.compose_canonical(&mut buf));
-        unwrap!(self.dnskey.compose_canonical(&mut buf));
-        let digest = Vec::from(sha256(&buf).as_ref());
-        Ok(Ds::new(
-            self.key_tag()?,
-            self.dnskey.algorithm(),
-            DigestAlg::Sha256,
-            digest,
-        ))
+        self.ds_with(owner, DigestAlg::Sha256)
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Self::Signature, Self::Error> {
+        match self.dnskey.algorithm() {
+            SecAlg::RsaSha256 => {
+                let mut signer =
+                    Signer::new(MessageDigest::sha256(), &self.key)?;
+                Ok(signer.sign_oneshot_to_vec(msg)?)
+            }
+            SecAlg::RsaSha512 => {
+                let mut signer =
+                    Signer::new(MessageDigest::sha512(), &self.key)?;
+                Ok(signer.sign_oneshot_to_vec(msg)?)
+            }
+            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+                // OpenSSL produces DER encoded signatures while DNSSEC uses
+                // the concatenated integers.
+                let curve = Curve::new(self.dnskey.algorithm());
+                let digest = hash(curve.digest, msg)?;
+                let sig = EcdsaSig::sign(&digest, &*self.key.ec_key()?)?;
+                let mut res = sig.r().to_vec_padded(curve.scalar_len)?;
+                res.extend_from_slice(
+                    &sig.s().to_vec_padded(curve.scalar_len)?,
+                );
+                Ok(res)
+            }
+            SecAlg::Ed25519 => {
+                let mut signer = Signer::new_without_digest(&self.key)?;
+                Ok(signer.sign_oneshot_to_vec(msg)?)
+            }
+            _ => Err(Error::Unsupported),
+        }
+    }
+}
+
+//------------ Curve ---------------------------------------------------------
+
+/// The parameters of an ECDSA algorithm.
+struct Curve {
+    nid: Nid,
+    digest: MessageDigest,
+    scalar_len: i32,
+}
+
+impl Curve {
+    fn new(algorithm: SecAlg) -> Self {
+        if algorithm == SecAlg::EcdsaP256Sha256 {
+            Curve {
+                nid: Nid::X9_62_PRIME256V1,
+                digest: MessageDigest::sha256(),
+                scalar_len: 32,
+            }
+        } else {
+            Curve {
+                nid: Nid::SECP384R1,
+                digest: MessageDigest::sha384(),
+                scalar_len: 48,
+            }
+        }
+    }
+
+    fn secret(&self, scalar: Vec<u8>) -> SecretKey {
+        if self.scalar_len == 32 {
+            SecretKey::EcdsaP256Sha256(scalar)
+        } else {
+            SecretKey::EcdsaP384Sha384(scalar)
+        }
+    }
+}
+
+/// Returns the octets of an optional RSA component.
+///
+/// OpenSSL only knows these for keys it has generated or imported with all
+/// components.
+fn to_vec(value: Option<&BigNumRef>) -> Result<Vec<u8>, Error> {
+    value.map(BigNumRef::to_vec).ok_or(Error::InvalidKey)
+}
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened while creating a key or signing.
+#[derive(Debug)]
+pub enum Error {
+    /// The algorithm isn’t supported.
+    Unsupported,
+
+    /// The secret key is invalid or doesn’t match the public key.
+    InvalidKey,
+
+    /// OpenSSL reported an error.
+    Openssl(ErrorStack),
+}
+
+impl From<ErrorStack> for Error {
+    fn from(err: ErrorStack) -> Self {
+        Error::Openssl(err)
     }
+}
 
-    fn sign(&self, data: &[u8]) -> Result<Self::Signature, Self::Error> {
-        let mut signer = OpenSslSigner::new(
-            self.digest, &self.key
-        )?;
-        signer.update(data)?;
-        signer.sign_to_vec()
+//--- Display and Error
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Unsupported => f.write_str("unsupported algorithm"),
+            Error::InvalidKey => f.write_str("invalid key"),
+            Error::Openssl(ref err) => err.fmt(f),
+        }
     }
 }
 
+impl error::Error for Error {}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "validate", feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::crypto::{Backend, RingBackend};
+    use crate::utils::base16;
+    use std::str::FromStr;
+    use std::string::ToString;
+
+    const ED25519_KEY: &str =
+        include_str!("../../test-data/keys/Kexample.com.+015+03613.key");
+    const ED25519_PRIVATE: &str =
+        include_str!("../../test-data/keys/Kexample.com.+015+03613.private");
+    const RSA_KEY: &str =
+        include_str!("../../test-data/keys/Kexample.com.+008+59801.key");
+    const RSA_PRIVATE: &str =
+        include_str!("../../test-data/keys/Kexample.com.+008+59801.private");
+
+    /// Checks that signatures verify with both backends.
+    fn check_signature(key: &Key) {
+        let signature = key.sign(b"some data").unwrap();
+        let dnskey = key.dnskey().unwrap();
+        for backend in [&OpensslBackend as &dyn Backend, &RingBackend] {
+            backend
+                .verify(
+                    dnskey.algorithm(),
+                    dnskey.public_key(),
+                    b"some data",
+                    &signature,
+                )
+                .unwrap();
+            assert_eq!(
+                backend.verify(
+                    dnskey.algorithm(),
+                    dnskey.public_key(),
+                    b"other data",
+                    &signature,
+                ),
+                Err(crate::crypto::AlgorithmError::BadSig)
+            );
+        }
+    }
+
+    #[test]
+    fn generate() {
+        for algorithm in [
+            SecAlg::RsaSha256,
+            SecAlg::EcdsaP256Sha256,
+            SecAlg::EcdsaP384Sha384,
+            SecAlg::Ed25519,
+        ] {
+            let key = Key::generate(algorithm, 257).unwrap();
+            assert_eq!(key.algorithm().unwrap(), algorithm);
+            check_signature(&key);
+
+            // Writing and reading back the key files results in the same
+            // key.
+            let owner = Dname::<Vec<u8>>::from_str("example.com.").unwrap();
+            let (read_owner, read) = Key::from_key_files(
+                &key.public_key_file(&owner),
+                &key.secret_key().to_string(),
+            )
+            .unwrap();
+            assert_eq!(read_owner, owner);
+            assert_eq!(read.dnskey().unwrap(), key.dnskey().unwrap());
+            check_signature(&read);
+        }
+        assert!(matches!(
+            Key::generate(SecAlg::Dsa, 257),
+            Err(Error::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn key_files() {
+        // The example key from RFC 8080, section 6.1.
+        let (owner, key) =
+            Key::from_key_files(ED25519_KEY, ED25519_PRIVATE).unwrap();
+        assert_eq!(key.key_tag().unwrap(), 3613);
+        check_signature(&key);
+        assert_eq!(
+            base16::encode_string(key.ds(&owner).unwrap().digest()),
+            "3AA5AB37EFCE57F737FC1627013FEE07BDF241BD10F3B1964AB55C78E79A304B"
+        );
+        let ds = key.ds_with(&owner, DigestAlg::Sha384).unwrap();
+        assert_eq!(ds.digest_type(), DigestAlg::Sha384);
+        assert_eq!(ds.digest().len(), 48);
+        assert!(key.ds_with(&owner, DigestAlg::Sha1).is_err());
+
+        let (_, key) = Key::from_key_files(RSA_KEY, RSA_PRIVATE).unwrap();
+        assert_eq!(key.key_tag().unwrap(), 59801);
+        check_signature(&key);
+
+        // The secret key has to match the public key.
+        assert!(matches!(
+            Key::from_key_files(RSA_KEY, ED25519_PRIVATE),
+            Err(KeyFileError::InvalidKey)
+        ));
+        let other = Key::generate(SecAlg::Ed25519, 257).unwrap();
+        assert!(matches!(
+            Key::from_key_files(ED25519_KEY, &other.secret_key().to_string()),
+            Err(KeyFileError::InvalidKey)
+        ));
+    }
+}
@@ -2,18 +2,27 @@
 
 use super::key::SigningKey;
 use crate::base::cmp::CanonicalOrd;
-use crate::base::iana::{Class, Rtype};
-use crate::base::name::ToDname;
+use crate::base::iana::{Class, Nsec3HashAlg, Rtype};
+use crate::base::name::{Dname, DnameBuilder, ToDname};
 use crate::base::rdata::{ComposeRecordData, RecordData};
 use crate::base::record::Record;
 use crate::base::serial::Serial;
 use crate::base::Ttl;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::crypto::{AlgorithmError, Backend, Nsec3Hasher};
 use crate::rdata::dnssec::{ProtoRrsig, RtypeBitmap};
-use crate::rdata::{Dnskey, Ds, Nsec, Rrsig};
+use crate::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use crate::rdata::{
+    Dnskey, Ds, Nsec, Nsec3, Nsec3param, Rrsig, ZoneRecordData,
+};
+use crate::utils::base32;
+use core::cmp;
 use octseq::builder::{EmptyBuilder, FromBuilder, OctetsBuilder, Truncate};
+use std::collections::BTreeMap;
 use std::iter::FromIterator;
 use std::vec::Vec;
-use std::{fmt, io, slice};
+use std::{error, fmt, io, slice};
 
 //------------ SortedRecords -------------------------------------------------
 
@@ -71,6 +80,26 @@ impl<N, D> SortedRecords<N, D> {
         inception: Serial,
         key: Key,
     ) -> Result<Vec<Record<N, Rrsig<Octets, ApexName>>>, Key::Error>
+    where
+        N: ToDname + Clone,
+        D: RecordData + ComposeRecordData,
+        Key: SigningKey,
+        Octets: From<Key::Signature> + AsRef<[u8]>,
+        ApexName: ToDname + Clone,
+    {
+        self.sign_where(apex, expiration, inception, key, |_| true)
+    }
+
+    /// Signs the RRsets of the zone whose record type satisfies `filter`.
+    #[allow(clippy::type_complexity)]
+    fn sign_where<Octets, Key, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        expiration: Serial,
+        inception: Serial,
+        key: Key,
+        filter: impl Fn(Rtype) -> bool,
+    ) -> Result<Vec<Record<N, Rrsig<Octets, ApexName>>>, Key::Error>
     where
         N: ToDname + Clone,
         D: RecordData + ComposeRecordData,
@@ -117,6 +146,9 @@ impl<N, D> SortedRecords<N, D> {
             };
 
             for rrset in family.rrsets() {
+                if !filter(rrset.rtype()) {
+                    continue;
+                }
                 if cut.is_some() {
                     // If we are at a zone cut, we only sign DS and NSEC
                     // records. NS records we must not sign and everything
@@ -245,6 +277,153 @@ impl<N, D> SortedRecords<N, D> {
         res
     }
 
+    /// Generates the NSEC3 chain for the zone.
+    ///
+    /// All owner names of the zone, including empty non-terminals, are
+    /// hashed using `params` and one NSEC3 record is returned for each
+    /// hashed name in the order of the hashes. If opt-out is enabled,
+    /// delegations without a DS record are left out of the chain.
+    ///
+    /// The names are hashed using the [`RingBackend`]. Use
+    /// [`nsec3s_with`][Self::nsec3s_with] to provide a different backend.
+    #[cfg(feature = "ring")]
+    pub fn nsec3s<Octets, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        ttl: Ttl,
+        params: &Nsec3Params<Octets>,
+    ) -> Vec<Record<Dname<Octets>, Nsec3<Octets>>>
+    where
+        N: ToDname,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder + Truncate + AsRef<[u8]> + AsMut<[u8]>,
+        <Octets::Builder as OctetsBuilder>::AppendError: fmt::Debug,
+        ApexName: ToDname,
+    {
+        self.nsec3s_with(apex, ttl, params, RingBackend)
+            .expect("ring supports SHA-1")
+    }
+
+    /// Generates the NSEC3 chain for the zone using the given backend.
+    ///
+    /// This is the same as [`nsec3s`][Self::nsec3s] but uses `backend` for
+    /// hashing the owner names. It fails if the backend doesn’t support
+    /// SHA-1.
+    #[allow(clippy::type_complexity)]
+    pub fn nsec3s_with<Octets, ApexName, B>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        ttl: Ttl,
+        params: &Nsec3Params<Octets>,
+        backend: B,
+    ) -> Result<Vec<Record<Dname<Octets>, Nsec3<Octets>>>, AlgorithmError>
+    where
+        N: ToDname,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder + Truncate + AsRef<[u8]> + AsMut<[u8]>,
+        <Octets::Builder as OctetsBuilder>::AppendError: fmt::Debug,
+        ApexName: ToDname,
+        B: Backend,
+    {
+        // The record types of the hashed names, ordered by the hash.
+        let mut hashes = BTreeMap::new();
+
+        // The owner name of a zone cut if we currently are at or below one.
+        let mut cut: Option<Dname<Vec<u8>>> = None;
+
+        let mut families = self.families();
+
+        // Since the records are ordered, the first family is the apex --
+        // we can skip everything before that.
+        families.skip_before(apex);
+
+        for family in families {
+            // If the owner is out of zone, we have moved out of our zone and
+            // are done.
+            if !family.is_in_zone(apex) {
+                break;
+            }
+
+            // If the family is below a zone cut, we must ignore it.
+            if let Some(ref cut) = cut {
+                if family.owner().ends_with(cut) {
+                    continue;
+                }
+            }
+
+            let owner = family.owner().to_vec();
+            let is_cut = family.is_zone_cut(apex);
+            cut = if is_cut { Some(owner.clone()) } else { None };
+
+            // Only the parent side of a secure delegation is signed.
+            let signed = !is_cut
+                || family.rrsets().any(|rrset| rrset.rtype() == Rtype::Ds);
+            if !signed && params.opt_out() {
+                continue;
+            }
+
+            let mut bitmap = RtypeBitmap::<Octets>::builder();
+            if signed {
+                bitmap.add(Rtype::Rrsig).unwrap();
+            }
+            for rrset in family.rrsets() {
+                bitmap.add(rrset.rtype()).unwrap()
+            }
+            hashes.insert(params.hash(&backend, &owner)?, bitmap.finalize());
+
+            // Any names between the owner and the apex that we haven’t seen
+            // yet are empty non-terminals.
+            for parent in owner.iter_suffixes().skip(1) {
+                if parent.name_eq(apex.owner())
+                    || !parent.ends_with(apex.owner())
+                {
+                    break;
+                }
+                hashes
+                    .entry(params.hash(&backend, &parent)?)
+                    .or_insert_with(|| {
+                        RtypeBitmap::<Octets>::builder().finalize()
+                    });
+            }
+        }
+
+        let hashes: Vec<_> = hashes.into_iter().collect();
+        let mut res = Vec::with_capacity(hashes.len());
+        for (idx, (hash, bitmap)) in hashes.iter().enumerate() {
+            let next = &hashes[(idx + 1) % hashes.len()].0;
+            let mut owner = DnameBuilder::<Octets::Builder>::new();
+            owner
+                .append_label(
+                    base32::encode_string_hex(hash)
+                        .to_ascii_lowercase()
+                        .as_bytes(),
+                )
+                .unwrap();
+            res.push(Record::new(
+                owner.append_origin(apex.owner()).expect("long owner name"),
+                apex.class(),
+                ttl,
+                Nsec3::new(
+                    Nsec3HashAlg::Sha1,
+                    params.flags(),
+                    params.iterations(),
+                    params.salt().clone(),
+                    OwnerHash::from_octets(octets_from_slice(next))
+                        .expect("long hash"),
+                    bitmap.clone(),
+                ),
+            ));
+        }
+        Ok(res)
+    }
+
+    /// Returns an iterator over the records in canonical order.
+    pub fn iter(&self) -> slice::Iter<Record<N, D>> {
+        self.records.iter()
+    }
+
     pub fn write<W>(&self, target: &mut W) -> Result<(), io::Error>
     where
         N: fmt::Display,
@@ -301,6 +480,366 @@ where
     }
 }
 
+//------------ Nsec3Params ---------------------------------------------------
+
+/// The parameters for generating an NSEC3 chain.
+///
+/// The hash algorithm is always SHA-1, the only one defined. The default
+/// parameters use no additional iterations, an empty salt, and no opt-out,
+/// as recommended by [RFC 9276].
+///
+/// [RFC 9276]: https://tools.ietf.org/html/rfc9276
+#[derive(Clone)]
+pub struct Nsec3Params<Octs> {
+    iterations: u16,
+    salt: Nsec3Salt<Octs>,
+    opt_out: bool,
+}
+
+impl<Octs> Nsec3Params<Octs> {
+    /// Creates new parameters.
+    pub fn new(
+        iterations: u16,
+        salt: Nsec3Salt<Octs>,
+        opt_out: bool,
+    ) -> Self {
+        Nsec3Params {
+            iterations,
+            salt,
+            opt_out,
+        }
+    }
+
+    /// Returns the number of additional hash iterations.
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    /// Returns the salt.
+    pub fn salt(&self) -> &Nsec3Salt<Octs> {
+        &self.salt
+    }
+
+    /// Returns whether insecure delegations are left out of the chain.
+    pub fn opt_out(&self) -> bool {
+        self.opt_out
+    }
+
+    /// Returns the flags field for the NSEC3 records.
+    pub fn flags(&self) -> u8 {
+        u8::from(self.opt_out)
+    }
+
+    /// Returns the record data of the NSEC3PARAM record for the zone apex.
+    ///
+    /// The flags of the record are always zero.
+    pub fn to_nsec3param(&self) -> Nsec3param<Octs>
+    where
+        Octs: Clone,
+    {
+        Nsec3param::new(
+            Nsec3HashAlg::Sha1,
+            0,
+            self.iterations,
+            self.salt.clone(),
+        )
+    }
+
+    /// Returns the hashed owner name for the given name.
+    pub(crate) fn hash<NOcts>(
+        &self,
+        backend: &impl Backend,
+        name: &Dname<NOcts>,
+    ) -> Result<Vec<u8>, AlgorithmError>
+    where
+        Octs: AsRef<[u8]>,
+        NOcts: AsRef<[u8]>,
+    {
+        // The iteration limit is for validators. Signers should heed the
+        // recommendations of RFC 9276 but we don’t force them to.
+        let mut hasher = Nsec3Hasher::with_backend(backend);
+        hasher.set_max_iterations(u16::MAX);
+        hasher
+            .hash(
+                name,
+                Nsec3HashAlg::Sha1,
+                self.iterations,
+                self.salt.as_slice(),
+            )
+            .map_err(|_| AlgorithmError::Unsupported)
+    }
+}
+
+impl<Octs: From<&'static [u8]>> Default for Nsec3Params<Octs> {
+    fn default() -> Self {
+        Self::new(0, Nsec3Salt::empty(), false)
+    }
+}
+
+//------------ DenialConfig --------------------------------------------------
+
+/// How a signed zone proves the non-existence of names.
+#[derive(Clone)]
+pub enum DenialConfig<Octs> {
+    /// Use an NSEC chain.
+    Nsec,
+
+    /// Use an NSEC3 chain with the given parameters.
+    Nsec3(Nsec3Params<Octs>),
+}
+
+//------------ ZoneSigner ----------------------------------------------------
+
+/// Signs a complete zone.
+///
+/// The signer takes the records of a zone and a set of keys and produces
+/// the signed zone. Any DNSSEC records present in the zone other than
+/// DNSKEY and DS records are dropped and regenerated: the DNSKEY records
+/// for all keys are added to the apex, the NSEC or NSEC3 chain is
+/// generated as configured via [`DenialConfig`], and all authoritative
+/// RRsets are signed.
+///
+/// Keys are added either as key signing keys, which only sign the DNSKEY
+/// RRset, or as zone signing keys, which sign everything else. If only one
+/// kind of key is given, those keys sign all RRsets.
+///
+/// The signatures are created by the keys themselves. The crypto backend
+/// `B` is only used for hashing owner names when generating an NSEC3
+/// chain.
+pub struct ZoneSigner<Key, Octs, B> {
+    ksks: Vec<Key>,
+    zsks: Vec<Key>,
+    inception: Serial,
+    expiration: Serial,
+    denial: DenialConfig<Octs>,
+    backend: B,
+}
+
+#[cfg(feature = "ring")]
+impl<Key, Octs> ZoneSigner<Key, Octs, RingBackend> {
+    /// Creates a new signer for signatures valid between the given times.
+    ///
+    /// The signer initially has no keys, uses an NSEC chain, and uses the
+    /// [`RingBackend`].
+    pub fn new(inception: Serial, expiration: Serial) -> Self {
+        Self::with_backend(inception, expiration, RingBackend)
+    }
+}
+
+impl<Key, Octs, B> ZoneSigner<Key, Octs, B> {
+    /// Creates a new signer using the given crypto backend.
+    ///
+    /// The signer initially has no keys and uses an NSEC chain.
+    pub fn with_backend(
+        inception: Serial,
+        expiration: Serial,
+        backend: B,
+    ) -> Self {
+        ZoneSigner {
+            ksks: Vec::new(),
+            zsks: Vec::new(),
+            inception,
+            expiration,
+            denial: DenialConfig::Nsec,
+            backend,
+        }
+    }
+
+    /// Adds a key signing key.
+    pub fn add_ksk(&mut self, key: Key) {
+        self.ksks.push(key)
+    }
+
+    /// Adds a zone signing key.
+    pub fn add_zsk(&mut self, key: Key) {
+        self.zsks.push(key)
+    }
+
+    /// Sets the times between which the signatures are valid.
+    pub fn set_validity(&mut self, inception: Serial, expiration: Serial) {
+        self.inception = inception;
+        self.expiration = expiration;
+    }
+
+    /// Sets how the denial of existence records are generated.
+    pub fn set_denial(&mut self, denial: DenialConfig<Octs>) {
+        self.denial = denial
+    }
+
+    /// Signs the zone with the given apex.
+    ///
+    /// The zone must contain an SOA record at the apex. The TTL of the
+    /// DNSKEY records is that of the SOA record while the NSEC or NSEC3
+    /// records use the lesser of the SOA record’s TTL and its minimum
+    /// field as per [RFC 9077].
+    ///
+    /// [RFC 9077]: https://tools.ietf.org/html/rfc9077
+    #[allow(clippy::type_complexity)]
+    pub fn sign<Iter>(
+        &self,
+        apex: &Dname<Octs>,
+        records: Iter,
+    ) -> Result<
+        SortedRecords<Dname<Octs>, ZoneRecordData<Octs, Dname<Octs>>>,
+        SignError<Key::Error>,
+    >
+    where
+        Key: SigningKey,
+        B: Backend,
+        Octs: FromBuilder + Clone + From<Key::Octets> + From<Key::Signature>,
+        Octs::Builder: EmptyBuilder + Truncate + AsRef<[u8]> + AsMut<[u8]>,
+        <Octs::Builder as OctetsBuilder>::AppendError: fmt::Debug,
+        Iter: IntoIterator<
+            Item = Record<Dname<Octs>, ZoneRecordData<Octs, Dname<Octs>>>,
+        >,
+    {
+        let (ksks, zsks) = match (self.ksks.is_empty(), self.zsks.is_empty())
+        {
+            (true, true) => return Err(SignError::NoKeys),
+            (true, false) => (&self.zsks, &self.zsks),
+            (false, true) => (&self.ksks, &self.ksks),
+            (false, false) => (&self.ksks, &self.zsks),
+        };
+
+        let mut zone: SortedRecords<_, _> = records
+            .into_iter()
+            .filter(|record| {
+                !matches!(
+                    record.rtype(),
+                    Rtype::Rrsig
+                        | Rtype::Nsec
+                        | Rtype::Nsec3
+                        | Rtype::Nsec3param
+                )
+            })
+            .collect();
+
+        let (class, soa_ttl, nsec_ttl) = zone
+            .iter()
+            .find_map(|record| match record.data() {
+                ZoneRecordData::Soa(soa) if record.owner().name_eq(apex) => {
+                    Some((
+                        record.class(),
+                        record.ttl(),
+                        cmp::min(record.ttl(), soa.minimum()),
+                    ))
+                }
+                _ => None,
+            })
+            .ok_or(SignError::NoSoa)?;
+        let family = FamilyName::new(apex.clone(), class);
+
+        for key in ksks.iter().chain(zsks.iter()) {
+            let record =
+                family.dnskey(soa_ttl, key).map_err(SignError::Key)?;
+            let _ = zone.insert(into_zone_record(record));
+        }
+
+        match self.denial {
+            DenialConfig::Nsec => {
+                let nsecs = zone.nsecs::<Octs, _>(&family, nsec_ttl);
+                zone.extend(nsecs.into_iter().map(into_zone_record));
+            }
+            DenialConfig::Nsec3(ref params) => {
+                let _ = zone.insert(into_zone_record(
+                    family
+                        .clone()
+                        .into_record(nsec_ttl, params.to_nsec3param()),
+                ));
+                let nsec3s = zone
+                    .nsec3s_with(&family, nsec_ttl, params, &self.backend)
+                    .map_err(SignError::Algorithm)?;
+                zone.extend(nsec3s.into_iter().map(into_zone_record));
+            }
+        }
+
+        let mut rrsigs = Vec::new();
+        for key in ksks {
+            rrsigs.extend(
+                zone.sign_where(
+                    &family,
+                    self.expiration,
+                    self.inception,
+                    key,
+                    |rtype| rtype == Rtype::Dnskey,
+                )
+                .map_err(SignError::Key)?,
+            );
+        }
+        for key in zsks {
+            rrsigs.extend(
+                zone.sign_where(
+                    &family,
+                    self.expiration,
+                    self.inception,
+                    key,
+                    |rtype| rtype != Rtype::Dnskey,
+                )
+                .map_err(SignError::Key)?,
+            );
+        }
+        zone.extend(rrsigs.into_iter().map(into_zone_record));
+        Ok(zone)
+    }
+}
+
+/// Converts a record into one with zone record data.
+fn into_zone_record<N, Data, Octs>(
+    record: Record<N, Data>,
+) -> Record<N, ZoneRecordData<Octs, N>>
+where
+    Data: Into<ZoneRecordData<Octs, N>>,
+{
+    let (class, ttl) = (record.class(), record.ttl());
+    let (owner, data) = record.into_owner_and_data();
+    Record::new(owner, class, ttl, data.into())
+}
+
+/// Creates an octets value from a slice.
+fn octets_from_slice<Octets>(slice: &[u8]) -> Octets
+where
+    Octets: FromBuilder,
+    Octets::Builder: EmptyBuilder,
+    <Octets::Builder as OctetsBuilder>::AppendError: fmt::Debug,
+{
+    let mut builder = Octets::Builder::empty();
+    builder.append_slice(slice).unwrap();
+    Octets::from_builder(builder)
+}
+
+//------------ SignError -----------------------------------------------------
+
+/// An error happened while signing a zone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignError<E> {
+    /// There is no SOA record at the apex.
+    NoSoa,
+
+    /// No keys were given.
+    NoKeys,
+
+    /// A key failed.
+    Key(E),
+
+    /// The crypto backend failed.
+    Algorithm(AlgorithmError),
+}
+
+impl<E: fmt::Display> fmt::Display for SignError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignError::NoSoa => f.write_str("no SOA record at zone apex"),
+            SignError::NoKeys => f.write_str("no signing keys"),
+            SignError::Key(ref err) => write!(f, "signing failed: {}", err),
+            SignError::Algorithm(ref err) => {
+                write!(f, "hashing failed: {}", err)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for SignError<E> {}
+
 //------------ Family --------------------------------------------------------
 
 /// A set of records with the same owner name and class.
@@ -604,3 +1143,241 @@ where
         Some(Rrset::new(res))
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "validate", feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{DigestAlg, SecAlg};
+    use crate::crypto::RingBackend;
+    use crate::rdata::{Ns, Soa, A};
+    use crate::sign::ring::Key;
+    use crate::validate::denial::{Denial, Nsec3Denial, NsecDenial};
+    use crate::validate::RrsigExt;
+    use ring::rand::SystemRandom;
+    use std::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+    type Data = ZoneRecordData<Vec<u8>, Name>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn zone() -> Vec<Record<Name, Data>> {
+        let ttl = Ttl::from_secs(3600);
+        let record = |owner: &str, data: Data| {
+            Record::new(name(owner), Class::In, ttl, data)
+        };
+        vec![
+            record(
+                "example.",
+                Soa::new(
+                    name("ns1.example."),
+                    name("hostmaster.example."),
+                    1.into(),
+                    ttl,
+                    ttl,
+                    ttl,
+                    Ttl::from_secs(300),
+                )
+                .into(),
+            ),
+            record("example.", Ns::new(name("ns1.example.")).into()),
+            record("ns1.example.", A::from_octets(192, 0, 2, 1).into()),
+            record("www.example.", A::from_octets(192, 0, 2, 2).into()),
+            record("a.b.example.", A::from_octets(192, 0, 2, 3).into()),
+            record("sub.example.", Ns::new(name("ns.sub.example.")).into()),
+            record("ns.sub.example.", A::from_octets(192, 0, 2, 4).into()),
+            record("sec.example.", Ns::new(name("ns1.example.")).into()),
+            record(
+                "sec.example.",
+                Ds::new(
+                    1,
+                    SecAlg::EcdsaP256Sha256,
+                    DigestAlg::Sha256,
+                    vec![0; 32],
+                )
+                .unwrap()
+                .into(),
+            ),
+        ]
+    }
+
+    /// Verifies all signatures and returns what was signed by which key.
+    fn verify(
+        zone: &SortedRecords<Name, Data>,
+        keys: &[&Key],
+    ) -> Vec<(Name, Rtype, u16)> {
+        let mut res = Vec::new();
+        for record in zone.iter() {
+            let rrsig = match record.data() {
+                ZoneRecordData::Rrsig(rrsig) => rrsig,
+                _ => continue,
+            };
+            let mut rrset: Vec<_> = zone
+                .iter()
+                .filter(|item| {
+                    item.owner() == record.owner()
+                        && item.rtype() == rrsig.type_covered()
+                })
+                .cloned()
+                .collect();
+            let dnskey = keys
+                .iter()
+                .map(|key| key.dnskey().unwrap())
+                .find(|dnskey| dnskey.key_tag() == rrsig.key_tag())
+                .unwrap();
+            rrsig
+                .verify_rrset(
+                    &RingBackend,
+                    &name("example."),
+                    &dnskey,
+                    &mut rrset,
+                    1500.into(),
+                )
+                .unwrap();
+            res.push((
+                record.owner().clone(),
+                rrsig.type_covered(),
+                rrsig.key_tag(),
+            ));
+        }
+        res
+    }
+
+    #[test]
+    fn sign_nsec() {
+        let rng = SystemRandom::new();
+        let ksk = Key::throwaway_13(257, &rng).unwrap();
+        let zsk = Key::throwaway_13(256, &rng).unwrap();
+        let (ksk_tag, zsk_tag) =
+            (ksk.key_tag().unwrap(), zsk.key_tag().unwrap());
+        let mut signer = ZoneSigner::new(1000.into(), 2000.into());
+        signer.add_ksk(&ksk);
+        signer.add_zsk(&zsk);
+        let apex = name("example.");
+        let signed = signer.sign(&apex, zone()).unwrap();
+
+        let sigs = verify(&signed, &[&ksk, &zsk]);
+        assert!(sigs.contains(&(apex.clone(), Rtype::Dnskey, ksk_tag)));
+        assert!(!sigs.contains(&(apex.clone(), Rtype::Dnskey, zsk_tag)));
+        assert!(sigs.contains(&(apex.clone(), Rtype::Soa, zsk_tag)));
+        assert!(sigs.contains(&(name("sec.example."), Rtype::Ds, zsk_tag)));
+        assert!(sigs.contains(&(name("sub.example."), Rtype::Nsec, zsk_tag)));
+        assert!(sigs.iter().all(|(owner, rtype, _)| {
+            *rtype != Rtype::Ns || *owner == apex
+        }));
+        assert!(!sigs
+            .iter()
+            .any(|(owner, _, _)| *owner == name("ns.sub.example.")));
+
+        let nsecs: Vec<_> = signed
+            .iter()
+            .filter_map(|record| match record.data() {
+                ZoneRecordData::Nsec(nsec) => Some(Record::new(
+                    record.owner().clone(),
+                    record.class(),
+                    record.ttl(),
+                    nsec.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nsecs.len(), 6);
+        assert!(nsecs.iter().all(|nsec| nsec.ttl() == Ttl::from_secs(300)));
+        let denial = NsecDenial::new(&nsecs);
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.")),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("www.example."), Rtype::Aaaa),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("b.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+
+        // Signing again replaces the old signatures and chain.
+        let resigned = signer.sign(&apex, signed.iter().cloned()).unwrap();
+        assert_eq!(resigned.iter().count(), signed.iter().count());
+    }
+
+    #[test]
+    fn sign_nsec3() {
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let tag = key.key_tag().unwrap();
+        let mut signer = ZoneSigner::new(1000.into(), 2000.into());
+        signer.add_ksk(&key);
+        signer.set_denial(DenialConfig::Nsec3(Nsec3Params::new(
+            1,
+            Nsec3Salt::from_octets(vec![0xab, 0xcd]).unwrap(),
+            true,
+        )));
+        let apex = name("example.");
+        let signed = signer.sign(&apex, zone()).unwrap();
+
+        let sigs = verify(&signed, &[&key]);
+        assert!(sigs.contains(&(apex.clone(), Rtype::Dnskey, tag)));
+        assert!(sigs.contains(&(apex.clone(), Rtype::Nsec3param, tag)));
+        assert!(sigs.iter().all(|(_, rtype, _)| *rtype != Rtype::Nsec));
+
+        let nsec3s: Vec<_> = signed
+            .iter()
+            .filter_map(|record| match record.data() {
+                ZoneRecordData::Nsec3(nsec3) => Some(Record::new(
+                    record.owner().clone(),
+                    record.class(),
+                    record.ttl(),
+                    nsec3.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        // The apex, ns1, www, b (an empty non-terminal), a.b, and sec.
+        // The insecure delegation sub is opted out.
+        assert_eq!(nsec3s.len(), 6);
+        assert!(nsec3s.iter().all(|nsec3| nsec3.data().opt_out()));
+        assert_eq!(
+            sigs.iter()
+                .filter(|(_, rtype, _)| *rtype == Rtype::Nsec3)
+                .count(),
+            6
+        );
+
+        let denial = Nsec3Denial::new(&apex, &nsec3s);
+        assert!(denial.nxdomain(&name("nope.example.")).is_ok());
+        assert_eq!(
+            denial.nodata(&name("www.example."), Rtype::Aaaa),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("b.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("sub.example."), Rtype::Ds),
+            Ok(Denial::OptOut)
+        );
+    }
+
+    #[test]
+    fn sign_errors() {
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let mut signer = ZoneSigner::new(1000.into(), 2000.into());
+        assert!(matches!(
+            signer.sign(&name("example."), zone()),
+            Err(SignError::NoKeys)
+        ));
+        signer.add_zsk(&key);
+        assert!(matches!(
+            signer.sign(&name("example."), zone().into_iter().skip(1)),
+            Err(SignError::NoSoa)
+        ));
+    }
+}
@@ -3,29 +3,34 @@
 #![cfg_attr(docsrs, doc(cfg(feature = "ring")))]
 
 use super::key::SigningKey;
+use super::keyfile::{self, KeyFileError, RsaSecretKey, SecretKey};
 use crate::base::iana::{DigestAlg, SecAlg};
-use crate::base::name::ToDname;
-use crate::base::rdata::ComposeRecordData;
+use crate::base::name::{Dname, ToDname};
 use crate::rdata::{Dnskey, Ds};
 #[cfg(feature = "bytes")]
 use bytes::Bytes;
-use octseq::builder::infallible;
-use ring::digest;
 use ring::error::Unspecified;
 use ring::rand::SecureRandom;
 use ring::signature::{
-    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaEncoding, RsaKeyPair,
-    Signature as RingSignature, ECDSA_P256_SHA256_FIXED_SIGNING,
+    EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair,
+    RsaEncoding, RsaKeyPair, Signature as RingSignature,
+    ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING,
+    RSA_PKCS1_SHA256, RSA_PKCS1_SHA512,
 };
+use std::string::String;
 use std::vec::Vec;
 
+//------------ Key -----------------------------------------------------------
+
+/// A signing key using ring.
 pub struct Key<'a> {
     dnskey: Dnskey<Vec<u8>>,
     key: RingKey,
+    secret: SecretKey,
     rng: &'a dyn SecureRandom,
 }
 
-#[allow(dead_code, clippy::large_enum_variant)]
+#[allow(clippy::large_enum_variant)]
 enum RingKey {
     Ecdsa(EcdsaKeyPair),
     Ed25519(Ed25519KeyPair),
@@ -37,27 +42,153 @@ impl<'a> Key<'a> {
         flags: u16,
         rng: &'a dyn SecureRandom,
     ) -> Result<Self, Unspecified> {
-        let pkcs8 = EcdsaKeyPair::generate_pkcs8(
-            &ECDSA_P256_SHA256_FIXED_SIGNING,
+        Self::generate(SecAlg::EcdsaP256Sha256, flags, rng)
+    }
+
+    /// Generates a new key.
+    ///
+    /// Keys can be generated for ECDSA with P-256 or P-384 and for Ed25519.
+    /// Since ring cannot generate RSA keys, these can only be imported via
+    /// [`from_secret_key`][Self::from_secret_key].
+    pub fn generate(
+        algorithm: SecAlg,
+        flags: u16,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        let (key, secret, public_key) = match algorithm {
+            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+                let template = EcdsaTemplate::new(algorithm);
+                let pkcs8 =
+                    EcdsaKeyPair::generate_pkcs8(template.signing, rng)?;
+                let scalar = template.scalar(pkcs8.as_ref())?;
+                let keypair = EcdsaKeyPair::from_pkcs8(
+                    template.signing,
+                    pkcs8.as_ref(),
+                )?;
+                let public_key = keypair.public_key().as_ref()[1..].into();
+                (
+                    RingKey::Ecdsa(keypair),
+                    template.secret(scalar.into()),
+                    public_key,
+                )
+            }
+            SecAlg::Ed25519 => {
+                let mut seed = vec![0; 32];
+                rng.fill(&mut seed)?;
+                let keypair = Ed25519KeyPair::from_seed_unchecked(&seed)
+                    .map_err(|_| Unspecified)?;
+                let public_key = keypair.public_key().as_ref().into();
+                (
+                    RingKey::Ed25519(keypair),
+                    SecretKey::Ed25519(seed),
+                    public_key,
+                )
+            }
+            _ => return Err(Unspecified),
+        };
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, algorithm, public_key)
+                .expect("long key"),
+            key,
+            secret,
             rng,
-        )?;
-        let keypair = EcdsaKeyPair::from_pkcs8(
-            &ECDSA_P256_SHA256_FIXED_SIGNING,
-            pkcs8.as_ref(),
-        )?;
-        let public_key = keypair.public_key().as_ref()[1..].into();
+        })
+    }
+
+    /// Creates a key from its public and secret parts.
+    ///
+    /// Returns an error if the algorithm isn’t supported or the secret key
+    /// doesn’t match the public key in `dnskey`.
+    pub fn from_secret_key(
+        dnskey: Dnskey<Vec<u8>>,
+        secret: SecretKey,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        if secret.algorithm() != dnskey.algorithm() {
+            return Err(Unspecified);
+        }
+        let key = match secret {
+            SecretKey::EcdsaP256Sha256(ref scalar)
+            | SecretKey::EcdsaP384Sha384(ref scalar) => {
+                let template = EcdsaTemplate::new(secret.algorithm());
+                let pkcs8 = template.pkcs8(scalar, dnskey.public_key())?;
+                RingKey::Ecdsa(EcdsaKeyPair::from_pkcs8(
+                    template.signing,
+                    &pkcs8,
+                )?)
+            }
+            SecretKey::Ed25519(ref seed) => {
+                RingKey::Ed25519(Ed25519KeyPair::from_seed_and_public_key(
+                    seed,
+                    dnskey.public_key(),
+                )?)
+            }
+            SecretKey::Rsa(algorithm, ref components) => {
+                let encoding: &'static dyn RsaEncoding = match algorithm {
+                    SecAlg::RsaSha256 => &RSA_PKCS1_SHA256,
+                    SecAlg::RsaSha512 => &RSA_PKCS1_SHA512,
+                    _ => return Err(Unspecified),
+                };
+                if components.public_key() != dnskey.public_key().as_slice() {
+                    return Err(Unspecified);
+                }
+                RingKey::Rsa(
+                    RsaKeyPair::from_der(&rsa_der(components))?,
+                    encoding,
+                )
+            }
+        };
         Ok(Key {
-            dnskey: Dnskey::new(
-                flags,
-                3,
-                SecAlg::EcdsaP256Sha256,
-                public_key,
-            )
-            .expect("long key"),
-            key: RingKey::Ecdsa(keypair),
+            dnskey,
+            key,
+            secret,
             rng,
         })
     }
+
+    /// Creates a key from the content of its public and private key files.
+    ///
+    /// Returns the owner of the key given in the public key file and the
+    /// key.
+    pub fn from_key_files(
+        public: &str,
+        private: &str,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<(Dname<Vec<u8>>, Self), KeyFileError> {
+        let (owner, dnskey) = keyfile::parse_public(public)?;
+        let secret = SecretKey::parse(private)?;
+        let key = Self::from_secret_key(dnskey, secret, rng)
+            .map_err(|_| KeyFileError::InvalidKey)?;
+        Ok((owner, key))
+    }
+
+    /// Returns the secret key material.
+    ///
+    /// Its `Display` implementation produces the content of the private
+    /// key file.
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret
+    }
+
+    /// Returns the content of the public key file for the key.
+    pub fn public_key_file(&self, owner: &impl ToDname) -> String {
+        keyfile::format_public(owner, &self.dnskey)
+    }
+
+    /// Returns the DS record data for the key using the given digest.
+    ///
+    /// The digest algorithm can be SHA-256 or SHA-384.
+    pub fn ds_with<N: ToDname>(
+        &self,
+        owner: N,
+        digest_alg: DigestAlg,
+    ) -> Result<Ds<Vec<u8>>, Unspecified> {
+        if !matches!(digest_alg, DigestAlg::Sha256 | DigestAlg::Sha384) {
+            return Err(Unspecified);
+        }
+        Ds::from_dnskey(&owner, &self.dnskey, digest_alg)
+            .map_err(|_| Unspecified)
+    }
 }
 
 impl<'a> SigningKey for Key<'a> {
@@ -73,18 +204,7 @@ impl<'a> SigningKey for Key<'a> {
         &self,
         owner: N,
     ) -> Result<Ds<Self::Octets>, Self::Error> {
-        let mut buf = Vec::new();
-        infallible(owner.compose_canonical(&mut buf));
-        infallible(self.dnskey.compose_canonical_rdata(&mut buf));
-        let digest =
-            Vec::from(digest::digest(&digest::SHA256, &buf).as_ref());
-        Ok(Ds::new(
-            self.key_tag()?,
-            self.dnskey.algorithm(),
-            DigestAlg::Sha256,
-            digest,
-        )
-        .expect("long digest"))
+        self.ds_with(owner, DigestAlg::Sha256)
     }
 
     fn sign(&self, msg: &[u8]) -> Result<Self::Signature, Self::Error> {
@@ -102,6 +222,135 @@ impl<'a> SigningKey for Key<'a> {
     }
 }
 
+//------------ EcdsaTemplate -------------------------------------------------
+
+/// The PKCS#8 document of an ECDSA key as produced and accepted by ring.
+///
+/// The document consists of a fixed prefix, the private scalar, a fixed
+/// middle part, and the uncompressed public point sans its leading 0x04.
+struct EcdsaTemplate {
+    signing: &'static EcdsaSigningAlgorithm,
+    prefix: &'static [u8],
+    middle: &'static [u8],
+    scalar_len: usize,
+}
+
+impl EcdsaTemplate {
+    fn new(algorithm: SecAlg) -> Self {
+        if algorithm == SecAlg::EcdsaP256Sha256 {
+            EcdsaTemplate {
+                signing: &ECDSA_P256_SHA256_FIXED_SIGNING,
+                prefix: b"\x30\x81\x87\x02\x01\x00\x30\x13\x06\x07\
+                          \x2a\x86\x48\xce\x3d\x02\x01\x06\x08\x2a\
+                          \x86\x48\xce\x3d\x03\x01\x07\x04\x6d\x30\
+                          \x6b\x02\x01\x01\x04\x20",
+                middle: b"\xa1\x44\x03\x42\x00\x04",
+                scalar_len: 32,
+            }
+        } else {
+            EcdsaTemplate {
+                signing: &ECDSA_P384_SHA384_FIXED_SIGNING,
+                prefix: b"\x30\x81\xb6\x02\x01\x00\x30\x10\x06\x07\
+                          \x2a\x86\x48\xce\x3d\x02\x01\x06\x05\x2b\
+                          \x81\x04\x00\x22\x04\x81\x9e\x30\x81\x9b\
+                          \x02\x01\x01\x04\x30",
+                middle: b"\xa1\x64\x03\x62\x00\x04",
+                scalar_len: 48,
+            }
+        }
+    }
+
+    fn secret(&self, scalar: Vec<u8>) -> SecretKey {
+        if self.scalar_len == 32 {
+            SecretKey::EcdsaP256Sha256(scalar)
+        } else {
+            SecretKey::EcdsaP384Sha384(scalar)
+        }
+    }
+
+    /// Extracts the private scalar from a PKCS#8 document.
+    fn scalar<'s>(&self, pkcs8: &'s [u8]) -> Result<&'s [u8], Unspecified> {
+        let end = self.prefix.len() + self.scalar_len;
+        if !pkcs8.starts_with(self.prefix)
+            || pkcs8.get(end..end + self.middle.len()) != Some(self.middle)
+        {
+            return Err(Unspecified);
+        }
+        Ok(&pkcs8[self.prefix.len()..end])
+    }
+
+    /// Creates a PKCS#8 document from the scalar and public key.
+    fn pkcs8(
+        &self,
+        scalar: &[u8],
+        public_key: &[u8],
+    ) -> Result<Vec<u8>, Unspecified> {
+        if scalar.len() != self.scalar_len
+            || public_key.len() != 2 * self.scalar_len
+        {
+            return Err(Unspecified);
+        }
+        let mut res = Vec::new();
+        res.extend_from_slice(self.prefix);
+        res.extend_from_slice(scalar);
+        res.extend_from_slice(self.middle);
+        res.extend_from_slice(public_key);
+        Ok(res)
+    }
+}
+
+/// Returns the DER encoded RSAPrivateKey structure for an RSA key.
+///
+/// The structure is defined in [RFC 8017, appendix A.1.2].
+///
+/// [RFC 8017, appendix A.1.2]: https://tools.ietf.org/html/rfc8017#appendix-A.1.2
+fn rsa_der(key: &RsaSecretKey) -> Vec<u8> {
+    fn push_len(target: &mut Vec<u8>, len: usize) {
+        if len < 0x80 {
+            target.push(len as u8);
+        } else if len < 0x100 {
+            target.extend_from_slice(&[0x81, len as u8]);
+        } else {
+            target.push(0x82);
+            target.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+
+    fn push_int(target: &mut Vec<u8>, value: &[u8]) {
+        let start =
+            value.iter().position(|ch| *ch != 0).unwrap_or(value.len());
+        let value = &value[start..];
+        let pad = value.first().map(|ch| *ch & 0x80 != 0).unwrap_or(true);
+        target.push(0x02);
+        push_len(target, value.len() + usize::from(pad));
+        if pad {
+            target.push(0);
+        }
+        target.extend_from_slice(value);
+    }
+
+    let mut content = Vec::new();
+    push_int(&mut content, b"");
+    for value in [
+        &key.modulus,
+        &key.public_exponent,
+        &key.private_exponent,
+        &key.prime1,
+        &key.prime2,
+        &key.exponent1,
+        &key.exponent2,
+        &key.coefficient,
+    ] {
+        push_int(&mut content, value);
+    }
+    let mut res = vec![0x30];
+    push_len(&mut res, content.len());
+    res.extend_from_slice(&content);
+    res
+}
+
+//------------ Signature -----------------------------------------------------
+
 pub struct Signature(SignatureInner);
 
 enum SignatureInner {
@@ -137,3 +386,112 @@ impl From<Signature> for Bytes {
         }
     }
 }
+
+impl From<Signature> for Vec<u8> {
+    fn from(sig: Signature) -> Self {
+        match sig.0 {
+            SignatureInner::Sig(sig) => Vec::from(sig.as_ref()),
+            SignatureInner::Vec(sig) => sig,
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "validate"))]
+mod test {
+    use super::*;
+    use crate::crypto::{Backend, RingBackend};
+    use crate::utils::base16;
+    use ring::rand::SystemRandom;
+    use std::str::FromStr;
+    use std::string::ToString;
+
+    const ED25519_KEY: &str =
+        include_str!("../../test-data/keys/Kexample.com.+015+03613.key");
+    const ED25519_PRIVATE: &str =
+        include_str!("../../test-data/keys/Kexample.com.+015+03613.private");
+    const RSA_KEY: &str =
+        include_str!("../../test-data/keys/Kexample.com.+008+59801.key");
+    const RSA_PRIVATE: &str =
+        include_str!("../../test-data/keys/Kexample.com.+008+59801.private");
+
+    fn check_signature(key: &Key) {
+        let signature = key.sign(b"some data").unwrap();
+        RingBackend
+            .verify(
+                key.algorithm().unwrap(),
+                key.dnskey().unwrap().public_key(),
+                b"some data",
+                signature.as_ref(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn generate() {
+        let rng = SystemRandom::new();
+        for algorithm in [
+            SecAlg::EcdsaP256Sha256,
+            SecAlg::EcdsaP384Sha384,
+            SecAlg::Ed25519,
+        ] {
+            let key = Key::generate(algorithm, 257, &rng).unwrap();
+            assert_eq!(key.algorithm().unwrap(), algorithm);
+            check_signature(&key);
+
+            // Writing and reading back the key files results in the same
+            // key.
+            let owner = Dname::<Vec<u8>>::from_str("example.com.").unwrap();
+            let (read_owner, read) = Key::from_key_files(
+                &key.public_key_file(&owner),
+                &key.secret_key().to_string(),
+                &rng,
+            )
+            .unwrap();
+            assert_eq!(read_owner, owner);
+            assert_eq!(read.dnskey().unwrap(), key.dnskey().unwrap());
+            check_signature(&read);
+        }
+        assert!(Key::generate(SecAlg::RsaSha256, 257, &rng).is_err());
+    }
+
+    #[test]
+    fn key_files() {
+        let rng = SystemRandom::new();
+
+        // The example key from RFC 8080, section 6.1.
+        let (owner, key) =
+            Key::from_key_files(ED25519_KEY, ED25519_PRIVATE, &rng).unwrap();
+        assert_eq!(key.key_tag().unwrap(), 3613);
+        check_signature(&key);
+        assert_eq!(
+            base16::encode_string(key.ds(&owner).unwrap().digest()),
+            "3AA5AB37EFCE57F737FC1627013FEE07BDF241BD10F3B1964AB55C78E79A304B"
+        );
+        let ds = key.ds_with(&owner, DigestAlg::Sha384).unwrap();
+        assert_eq!(ds.digest_type(), DigestAlg::Sha384);
+        assert_eq!(ds.digest().len(), 48);
+        assert!(key.ds_with(&owner, DigestAlg::Sha1).is_err());
+
+        let (_, key) =
+            Key::from_key_files(RSA_KEY, RSA_PRIVATE, &rng).unwrap();
+        assert_eq!(key.key_tag().unwrap(), 59801);
+        check_signature(&key);
+
+        // The secret key has to match the public key.
+        assert!(matches!(
+            Key::from_key_files(RSA_KEY, ED25519_PRIVATE, &rng),
+            Err(KeyFileError::InvalidKey)
+        ));
+        let other = Key::generate(SecAlg::Ed25519, 257, &rng).unwrap();
+        assert!(matches!(
+            Key::from_key_files(
+                ED25519_KEY,
+                &other.secret_key().to_string(),
+                &rng
+            ),
+            Err(KeyFileError::InvalidKey)
+        ));
+    }
+}
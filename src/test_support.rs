@@ -0,0 +1,449 @@
+//! Generating arbitrary DNS values for testing.
+//!
+//! This module provides the [`Arbitrary`] trait for types that can make up
+//! random yet valid values of themselves. It is implemented for domain
+//! names, questions, records, and the record data types of the [rdata]
+//! module that can appear in zones. Together with a seedable random number
+//! generator, this allows testing code with a large number of different
+//! DNS values, for instance to check that everything that is composed can
+//! be parsed again.
+//!
+//! The values are generated from a [`rand::Rng`]. Property testing
+//! frameworks such as proptest or quickcheck can be bridged by seeding a
+//! generator from their own source of randomness.
+//!
+//! # Example
+//!
+//! ```
+//! use bytes::Bytes;
+//! use domain::base::{Dname, Record};
+//! use domain::rdata::ZoneRecordData;
+//! use domain::test_support::Arbitrary;
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
+//!
+//! let mut rng = StdRng::seed_from_u64(1);
+//! for _ in 0..100 {
+//!     let record = Record::<
+//!         Dname<Bytes>,
+//!         ZoneRecordData<Bytes, Dname<Bytes>>,
+//!     >::arbitrary(&mut rng);
+//!     assert!(record.owner().label_count() <= 5);
+//! }
+//! ```
+//!
+//! [rdata]: crate::rdata
+#![cfg(feature = "test-support")]
+#![cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+
+use crate::base::charstr::CharStr;
+use crate::base::iana::{Class, DigestAlg, Nsec3HashAlg, Rtype, SecAlg};
+use crate::base::name::{Dname, DnameBuilder};
+use crate::base::question::Question;
+use crate::base::rdata::UnknownRecordData;
+use crate::base::record::{Record, Ttl};
+use crate::base::serial::Serial;
+use crate::rdata::dnssec::{RtypeBitmap, RtypeBitmapBuilder};
+use crate::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use crate::rdata::rfc1035::TxtBuilder;
+use crate::rdata::{
+    dname, Aaaa, Cdnskey, Cds, Cname, Dnskey, Ds, Hinfo, Mb, Md, Mf, Mg,
+    Minfo, Mr, Mx, Ns, Nsec, Nsec3, Nsec3param, Ptr, Rrsig, Soa, Srv, Txt,
+    ZoneRecordData, A,
+};
+use bytes::{Bytes, BytesMut};
+use core::ops::RangeInclusive;
+use rand::Rng;
+use std::vec::Vec;
+
+//------------ Arbitrary -----------------------------------------------------
+
+/// A type that can generate arbitrary values of itself.
+pub trait Arbitrary: Sized {
+    /// Generates a value using the given random number generator.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self;
+}
+
+//--- Basic types
+
+impl Arbitrary for Rtype {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Rtype::from_int(rng.gen())
+    }
+}
+
+impl Arbitrary for Class {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Class::from_int(rng.gen())
+    }
+}
+
+impl Arbitrary for Ttl {
+    /// Generates a TTL within the range permitted by RFC 2181.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Ttl::from_secs(rng.gen_range(0..=0x7FFF_FFFF))
+    }
+}
+
+impl Arbitrary for Serial {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Serial(rng.gen())
+    }
+}
+
+impl Arbitrary for Bytes {
+    /// Generates up to 64 octets.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        octets(rng, 0..=64)
+    }
+}
+
+impl Arbitrary for CharStr<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        CharStr::from_octets(octets(rng, 0..=CharStr::MAX_LEN))
+            .expect("short character string")
+    }
+}
+
+impl Arbitrary for Dname<Bytes> {
+    /// Generates a name of up to four labels plus the root label.
+    ///
+    /// Most labels consist of lowercase letters, digits, and hyphens, but
+    /// some contain arbitrary octets.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        const LDH: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789-";
+        let mut res = DnameBuilder::<BytesMut>::new();
+        for _ in 0..rng.gen_range(0..=4) {
+            let len = rng.gen_range(1..=12);
+            let label: Vec<u8> = if rng.gen_ratio(1, 8) {
+                (0..len).map(|_| rng.gen()).collect()
+            } else {
+                (0..len).map(|_| LDH[rng.gen_range(0..LDH.len())]).collect()
+            };
+            res.append_label(&label).expect("short name");
+        }
+        res.into_dname().expect("short name")
+    }
+}
+
+impl Arbitrary for Question<Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Question::new(
+            Dname::arbitrary(rng),
+            Rtype::arbitrary(rng),
+            Class::arbitrary(rng),
+        )
+    }
+}
+
+impl<Data: Arbitrary> Arbitrary for Record<Dname<Bytes>, Data> {
+    /// Generates a record of class IN.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Record::new(
+            Dname::arbitrary(rng),
+            Class::In,
+            Ttl::arbitrary(rng),
+            Data::arbitrary(rng),
+        )
+    }
+}
+
+//--- Record data
+
+impl Arbitrary for ZoneRecordData<Bytes, Dname<Bytes>> {
+    /// Generates record data of any of the types.
+    ///
+    /// Unknown record data uses a type from the private use range.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        match rng.gen_range(0..26) {
+            0 => A::arbitrary(rng).into(),
+            1 => Cname::arbitrary(rng).into(),
+            2 => Hinfo::arbitrary(rng).into(),
+            3 => Mb::arbitrary(rng).into(),
+            4 => Md::arbitrary(rng).into(),
+            5 => Mf::arbitrary(rng).into(),
+            6 => Mg::arbitrary(rng).into(),
+            7 => Minfo::arbitrary(rng).into(),
+            8 => Mr::arbitrary(rng).into(),
+            9 => Mx::arbitrary(rng).into(),
+            10 => Ns::arbitrary(rng).into(),
+            11 => Ptr::arbitrary(rng).into(),
+            12 => Soa::arbitrary(rng).into(),
+            13 => Txt::arbitrary(rng).into(),
+            14 => Aaaa::arbitrary(rng).into(),
+            15 => Cdnskey::arbitrary(rng).into(),
+            16 => Cds::arbitrary(rng).into(),
+            17 => dname::Dname::arbitrary(rng).into(),
+            18 => Dnskey::arbitrary(rng).into(),
+            19 => Rrsig::arbitrary(rng).into(),
+            20 => Nsec::arbitrary(rng).into(),
+            21 => Ds::arbitrary(rng).into(),
+            22 => Nsec3::arbitrary(rng).into(),
+            23 => Nsec3param::arbitrary(rng).into(),
+            24 => Srv::arbitrary(rng).into(),
+            _ => ZoneRecordData::Unknown(
+                UnknownRecordData::from_octets(
+                    Rtype::from_int(rng.gen_range(0xFF00..=0xFFFE)),
+                    octets(rng, 0..=64),
+                )
+                .expect("short record data"),
+            ),
+        }
+    }
+}
+
+impl Arbitrary for A {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        A::new(rng.gen::<[u8; 4]>().into())
+    }
+}
+
+impl Arbitrary for Aaaa {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Aaaa::new(rng.gen::<[u8; 16]>().into())
+    }
+}
+
+/// Implements `Arbitrary` for record data consisting of a single name.
+macro_rules! arbitrary_dname_type {
+    ( $( $target:ty ),* ) => {
+        $(
+            impl Arbitrary for $target {
+                fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+                    <$target>::new(Dname::arbitrary(rng))
+                }
+            }
+        )*
+    }
+}
+
+arbitrary_dname_type!(
+    Cname<Dname<Bytes>>,
+    Mb<Dname<Bytes>>,
+    Md<Dname<Bytes>>,
+    Mf<Dname<Bytes>>,
+    Mg<Dname<Bytes>>,
+    Mr<Dname<Bytes>>,
+    Ns<Dname<Bytes>>,
+    Ptr<Dname<Bytes>>,
+    dname::Dname<Dname<Bytes>>
+);
+
+impl Arbitrary for Hinfo<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Hinfo::new(CharStr::arbitrary(rng), CharStr::arbitrary(rng))
+    }
+}
+
+impl Arbitrary for Minfo<Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Minfo::new(Dname::arbitrary(rng), Dname::arbitrary(rng))
+    }
+}
+
+impl Arbitrary for Mx<Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Mx::new(rng.gen(), Dname::arbitrary(rng))
+    }
+}
+
+impl Arbitrary for Soa<Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Soa::new(
+            Dname::arbitrary(rng),
+            Dname::arbitrary(rng),
+            Serial::arbitrary(rng),
+            Ttl::arbitrary(rng),
+            Ttl::arbitrary(rng),
+            Ttl::arbitrary(rng),
+            Ttl::arbitrary(rng),
+        )
+    }
+}
+
+impl Arbitrary for Txt<Bytes> {
+    /// Generates text of up to 600 octets, spanning several strings.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut res = TxtBuilder::<BytesMut>::new();
+        res.append_slice(&octets(rng, 1..=600)).expect("short text");
+        res.finish()
+    }
+}
+
+impl Arbitrary for Srv<Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Srv::new(rng.gen(), rng.gen(), rng.gen(), Dname::arbitrary(rng))
+    }
+}
+
+impl Arbitrary for Dnskey<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Dnskey::new(
+            rng.gen(),
+            rng.gen(),
+            SecAlg::from_int(rng.gen()),
+            octets(rng, 1..=128),
+        )
+        .expect("short key")
+    }
+}
+
+impl Arbitrary for Cdnskey<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Cdnskey::new(
+            rng.gen(),
+            rng.gen(),
+            SecAlg::from_int(rng.gen()),
+            octets(rng, 1..=128),
+        )
+        .expect("short key")
+    }
+}
+
+impl Arbitrary for Ds<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Ds::new(
+            rng.gen(),
+            SecAlg::from_int(rng.gen()),
+            DigestAlg::from_int(rng.gen()),
+            octets(rng, 1..=64),
+        )
+        .expect("short digest")
+    }
+}
+
+impl Arbitrary for Cds<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Cds::new(
+            rng.gen(),
+            SecAlg::from_int(rng.gen()),
+            DigestAlg::from_int(rng.gen()),
+            octets(rng, 1..=64),
+        )
+        .expect("short digest")
+    }
+}
+
+impl Arbitrary for Rrsig<Bytes, Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Rrsig::new(
+            Rtype::arbitrary(rng),
+            SecAlg::from_int(rng.gen()),
+            rng.gen(),
+            Ttl::arbitrary(rng),
+            Serial::arbitrary(rng),
+            Serial::arbitrary(rng),
+            rng.gen(),
+            Dname::arbitrary(rng),
+            octets(rng, 1..=128),
+        )
+        .expect("short signature")
+    }
+}
+
+impl Arbitrary for RtypeBitmap<Bytes> {
+    /// Generates a bitmap with up to eight record types.
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut res = RtypeBitmapBuilder::<BytesMut>::new();
+        for _ in 0..rng.gen_range(0..=8) {
+            res.add(Rtype::arbitrary(rng)).expect("short bitmap")
+        }
+        res.finalize()
+    }
+}
+
+impl Arbitrary for Nsec<Bytes, Dname<Bytes>> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Nsec::new(Dname::arbitrary(rng), RtypeBitmap::arbitrary(rng))
+    }
+}
+
+impl Arbitrary for Nsec3Salt<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Nsec3Salt::from_octets(octets(rng, 0..=32)).expect("short salt")
+    }
+}
+
+impl Arbitrary for OwnerHash<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        OwnerHash::from_octets(octets(rng, 1..=32)).expect("short hash")
+    }
+}
+
+impl Arbitrary for Nsec3<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Nsec3::new(
+            Nsec3HashAlg::from_int(rng.gen()),
+            rng.gen(),
+            rng.gen(),
+            Nsec3Salt::arbitrary(rng),
+            OwnerHash::arbitrary(rng),
+            RtypeBitmap::arbitrary(rng),
+        )
+    }
+}
+
+impl Arbitrary for Nsec3param<Bytes> {
+    fn arbitrary<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Nsec3param::new(
+            Nsec3HashAlg::from_int(rng.gen()),
+            rng.gen(),
+            rng.gen(),
+            Nsec3Salt::arbitrary(rng),
+        )
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Generates random octets with a length from the given range.
+fn octets<R: Rng + ?Sized>(rng: &mut R, len: RangeInclusive<usize>) -> Bytes {
+    let len = rng.gen_range(len);
+    (0..len).map(|_| rng.gen::<u8>()).collect()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::ParsedDname;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    type StoredRecord =
+        Record<Dname<Bytes>, ZoneRecordData<Bytes, Dname<Bytes>>>;
+
+    #[test]
+    fn question_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let question = Question::<Dname<Bytes>>::arbitrary(&mut rng);
+            let mut msg = MessageBuilder::new_vec().question();
+            msg.push(&question).unwrap();
+            let msg = msg.into_message();
+            assert_eq!(msg.sole_question().unwrap(), question);
+        }
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..2000 {
+            let record = StoredRecord::arbitrary(&mut rng);
+            let mut msg = MessageBuilder::new_vec().answer();
+            msg.push(&record).unwrap();
+            let msg = Message::from_octets(msg.finish()).unwrap();
+            let parsed = msg
+                .answer()
+                .unwrap()
+                .limit_to::<ZoneRecordData<_, ParsedDname<_>>>()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(parsed, record);
+        }
+    }
+}
@@ -11,12 +11,12 @@
 //! [RFC 4648]: https://tools.ietf.org/html/rfc4648
 
 use crate::base::scan::{ConvertSymbols, EntrySymbol, ScannerError};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 use core::fmt;
 use octseq::builder::{
     EmptyBuilder, FreezeBuilder, FromBuilder, OctetsBuilder,
 };
-#[cfg(feature = "std")]
-use std::string::String;
 
 //------------ Re-exports ----------------------------------------------------
 
@@ -75,7 +75,7 @@ where
 }
 
 /// Encodes binary data in Base 16 and returns the encoded data as a string.
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 pub fn encode_string<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> String {
     let mut res = String::with_capacity(bytes.as_ref().len() * 2);
     display(bytes, &mut res).unwrap();
@@ -21,12 +21,12 @@
 //! [`Decoder`]: struct.Decoder.html
 
 use crate::base::scan::{ConvertSymbols, EntrySymbol, ScannerError};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 use core::fmt;
 use octseq::builder::{
     EmptyBuilder, FreezeBuilder, FromBuilder, OctetsBuilder,
 };
-#[cfg(feature = "std")]
-use std::string::String;
 
 //------------ Re-exports ----------------------------------------------------
 
@@ -106,7 +106,7 @@ where
 }
 
 /// Encodes binary data in *base32hex* and returns the encoded data as a string.
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 pub fn encode_string_hex<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> String {
     let mut res = String::with_capacity((bytes.as_ref().len() / 5 + 1) * 8);
     display_hex(bytes, &mut res).unwrap();
@@ -3,8 +3,9 @@
 //! The Base 64 encoding is defined in [RFC 4648]. There are two variants
 //! defined in the RFC, dubbed *base64* and *base64url* which are
 //! differenciated by the last two characters in the alphabet. The DNS uses
-//! only the original *base64* variant, so this is what is implemented by the
-//! module for now.
+//! mostly the original *base64* variant, so this is what is implemented by
+//! the module for now. Only decoding of *base64url* is available via
+//! [`decode_url`] as it is used for requests in DNS over HTTPS.
 //!
 //! The module defines the type [`Decoder`] which keeps the state necessary
 //! for decoding. The various functions offered use such a decoder to decode
@@ -13,12 +14,12 @@
 //! [RFC 4648]: https://tools.ietf.org/html/rfc4648
 
 use crate::base::scan::{ConvertSymbols, EntrySymbol, ScannerError};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 use core::fmt;
 use octseq::builder::{
     EmptyBuilder, FreezeBuilder, FromBuilder, OctetsBuilder, ShortBuf,
 };
-#[cfg(feature = "std")]
-use std::string::String;
 
 //------------ Convenience Functions -----------------------------------------
 
@@ -38,6 +39,32 @@ where
     decoder.finalize()
 }
 
+/// Decodes a string with *base64url* encoded data.
+///
+/// Padding at the end of the string is optional as it is commonly left
+/// out for this variant.
+pub fn decode_url<Octets>(s: &str) -> Result<Octets, DecodeError>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: OctetsBuilder + EmptyBuilder,
+{
+    let mut decoder = Decoder::<<Octets as FromBuilder>::Builder>::new();
+    for ch in s.chars() {
+        decoder.push(match ch {
+            '-' => '+',
+            '_' => '/',
+            '+' | '/' => return Err(DecodeError::IllegalChar(ch)),
+            ch => ch,
+        })?;
+    }
+    if matches!(decoder.next, 2 | 3) {
+        while decoder.next != 0xF0 {
+            decoder.push(PAD)?;
+        }
+    }
+    decoder.finalize()
+}
+
 /// Encodes binary data in *base64* and writes it into a format stream.
 ///
 /// This function is intended to be used in implementations of formatting
@@ -91,7 +118,7 @@ where
 }
 
 /// Encodes binary data in *base64* and returns the encoded data as a string.
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 pub fn encode_string<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> String {
     let mut res = String::with_capacity((bytes.as_ref().len() / 3 + 1) * 4);
     display(bytes, &mut res).unwrap();
@@ -367,20 +394,24 @@ impl SymbolConverter {
         self.next += 1;
 
         if self.next == 4 {
+            // The number of octets in the output depends on the padding.
+            let mut len = 1;
             self.output[0] = self.input[0] << 2 | self.input[1] >> 4;
             if self.input[2] != 0x80 {
                 self.output[1] = self.input[1] << 4 | self.input[2] >> 2;
+                len = 2;
             }
             if self.input[3] != 0x80 {
                 if self.input[2] == 0x80 {
                     return Err(Error::custom("trailing Base 64 data"));
                 }
                 self.output[2] = (self.input[2] << 6) | self.input[3];
+                len = 3;
                 self.next = 0
             } else {
                 self.next = 0xF0
             }
-            Ok(Some(&self.output))
+            Ok(Some(&self.output[..len]))
         } else {
             Ok(None)
         }
@@ -537,6 +568,57 @@ mod test {
         );
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn decode_url_str() {
+        use super::DecodeError;
+
+        fn decode(s: &str) -> Result<std::vec::Vec<u8>, DecodeError> {
+            super::decode_url(s)
+        }
+
+        assert_eq!(&decode("").unwrap(), b"");
+        assert_eq!(&decode("Zg").unwrap(), b"f");
+        assert_eq!(&decode("Zm8").unwrap(), b"fo");
+        assert_eq!(&decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(&decode("Zm9v").unwrap(), b"foo");
+        assert_eq!(&decode("-_8").unwrap(), b"\xfb\xff");
+
+        assert_eq!(decode("Zm9vY").unwrap_err(), DecodeError::ShortInput);
+        assert_eq!(decode("+_8").unwrap_err(), DecodeError::IllegalChar('+'));
+        assert_eq!(decode("Zg=a").unwrap_err(), DecodeError::TrailingInput);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn symbol_converter() {
+        use super::SymbolConverter;
+        use crate::base::scan::{ConvertSymbols, Symbol};
+
+        fn convert(s: &str) -> std::vec::Vec<u8> {
+            let mut conv = SymbolConverter::new();
+            let mut res = std::vec::Vec::new();
+            for ch in s.chars() {
+                let data =
+                    ConvertSymbols::<_, std::io::Error>::process_symbol(
+                        &mut conv,
+                        Symbol::Char(ch),
+                    )
+                    .unwrap();
+                if let Some(data) = data {
+                    res.extend_from_slice(data);
+                }
+            }
+            res
+        }
+
+        assert_eq!(convert("Zg=="), b"f");
+        assert_eq!(convert("Zm8="), b"fo");
+        assert_eq!(convert("Zm9v"), b"foo");
+        assert_eq!(convert("Zm9vYg=="), b"foob");
+        assert_eq!(convert("Zm9vYmE="), b"fooba");
+    }
+
     #[test]
     #[cfg(feature = "std")]
     fn display_bytes() {
@@ -5,3 +5,6 @@
 pub mod base16;
 pub mod base32;
 pub mod base64;
+pub mod rotate;
+#[cfg(all(feature = "bytes", feature = "std", feature = "tokio"))]
+pub mod stream;
@@ -0,0 +1,254 @@
+//! Reordering the records of an RRset.
+//!
+//! Since an RRset is a set, the order of its records in a response carries
+//! no meaning. Many clients simply use the first record, though, so servers
+//! commonly change the order between responses to spread load over all
+//! the records. Clients that want to do their own load spreading can use
+//! the same tools on the records they received.
+//!
+//! A [`Rotator`] reorders the records of an RRset according to a
+//! [`Rotation`]. Independently of a rotator, [`order_by_weight`] orders
+//! records by priority and weight following the algorithm for SRV records
+//! defined in RFC 2782.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "rand")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+//------------ Rotation ------------------------------------------------------
+
+/// How the records of an RRset are reordered.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Rotation {
+    /// The records are left in their original order.
+    #[default]
+    Fixed,
+
+    /// The records are rotated by one position for each RRset.
+    ///
+    /// The records keep their relative order but each RRset starts with
+    /// the record following the one the previous RRset started with.
+    RoundRobin,
+
+    /// The records are shuffled randomly.
+    #[cfg(feature = "rand")]
+    Random,
+
+    /// SRV records are ordered by priority and weight.
+    ///
+    /// The records are ordered as described by RFC 2782: records with a
+    /// lower priority come first and within a priority, records are picked
+    /// randomly with a probability proportional to their weight. Records
+    /// without priority and weight are shuffled randomly.
+    #[cfg(feature = "rand")]
+    SrvWeighted,
+}
+
+//------------ Rotator -------------------------------------------------------
+
+/// Reorders the records of RRsets according to a rotation.
+///
+/// The rotator keeps the state necessary for round-robin rotation. It can
+/// be shared between threads, so a single rotator can be used for all the
+/// responses of a server.
+#[derive(Debug, Default)]
+pub struct Rotator {
+    /// The rotation to apply.
+    rotation: Rotation,
+
+    /// The offset for the next round-robin rotation.
+    next: AtomicUsize,
+}
+
+impl Rotator {
+    /// Creates a new rotator for the given rotation.
+    pub fn new(rotation: Rotation) -> Self {
+        Rotator {
+            rotation,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Returns whether the rotator leaves records unchanged.
+    pub fn is_fixed(&self) -> bool {
+        matches!(self.rotation, Rotation::Fixed)
+    }
+
+    /// Reorders the records in `items`.
+    ///
+    /// Since the items carry no priority or weight, SRV-weighted rotation
+    /// shuffles them randomly.
+    pub fn rotate<T>(&self, items: &mut [T]) {
+        if items.len() < 2 {
+            return;
+        }
+        match self.rotation {
+            Rotation::Fixed => {}
+            Rotation::RoundRobin => {
+                let offset = self.next.fetch_add(1, Ordering::Relaxed);
+                items.rotate_left(offset % items.len());
+            }
+            #[cfg(feature = "rand")]
+            Rotation::Random | Rotation::SrvWeighted => {
+                items.shuffle(&mut rand::thread_rng())
+            }
+        }
+    }
+
+    /// Reorders records that have a priority and weight.
+    ///
+    /// The closure `key` returns the priority and weight of a record. They
+    /// are only used for SRV-weighted rotation. Otherwise, this behaves
+    /// exactly like [`rotate`][Self::rotate].
+    pub fn rotate_weighted<T>(
+        &self,
+        items: &mut [T],
+        key: impl Fn(&T) -> (u16, u16),
+    ) {
+        match self.rotation {
+            #[cfg(feature = "rand")]
+            Rotation::SrvWeighted => order_by_weight(items, key),
+            _ => {
+                let _ = key;
+                self.rotate(items)
+            }
+        }
+    }
+}
+
+//--- Clone
+
+impl Clone for Rotator {
+    fn clone(&self) -> Self {
+        Rotator {
+            rotation: self.rotation,
+            next: AtomicUsize::new(self.next.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+//------------ order_by_weight -----------------------------------------------
+
+/// Orders records by priority and weight as described in RFC 2782.
+///
+/// The closure `key` returns the priority and weight of a record. Records
+/// are sorted by ascending priority. Within a priority, the records are
+/// picked one after another randomly, with the chance of a record being
+/// picked proportional to its weight. Records with a weight of zero have a
+/// very small chance of being picked before others.
+#[cfg(feature = "rand")]
+pub fn order_by_weight<T>(items: &mut [T], key: impl Fn(&T) -> (u16, u16)) {
+    // Sorting by weight, too, moves records with weight zero to the start
+    // of their priority as required by RFC 2782.
+    items.sort_by_key(&key);
+    let mut rng = rand::thread_rng();
+    let mut start = 0;
+    while start < items.len() {
+        let priority = key(&items[start]).0;
+        let end = items[start..]
+            .iter()
+            .position(|item| key(item).0 != priority)
+            .map_or(items.len(), |len| start + len);
+        let group = &mut items[start..end];
+        let mut weight_sum: u32 =
+            group.iter().map(|item| u32::from(key(item).1)).sum();
+        for i in 0..group.len() {
+            let pick = rng.gen_range(0..=weight_sum);
+            let mut sum = 0;
+            for j in i..group.len() {
+                let weight = u32::from(key(&group[j]).1);
+                sum += weight;
+                if sum >= pick {
+                    weight_sum -= weight;
+                    // Move the pick to the front, keeping the order of the
+                    // remaining records intact.
+                    group[i..=j].rotate_right(1);
+                    break;
+                }
+            }
+        }
+        start = end;
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_robin() {
+        let rotator = Rotator::new(Rotation::RoundRobin);
+        let mut items = [1, 2, 3];
+        rotator.rotate(&mut items);
+        assert_eq!(items, [1, 2, 3]);
+        let mut items = [1, 2, 3];
+        rotator.rotate(&mut items);
+        assert_eq!(items, [2, 3, 1]);
+        let mut items = [1, 2, 3];
+        rotator.rotate(&mut items);
+        assert_eq!(items, [3, 1, 2]);
+        let mut items = [1, 2, 3];
+        rotator.rotate(&mut items);
+        assert_eq!(items, [1, 2, 3]);
+
+        // Single records don’t advance the rotation.
+        rotator.rotate(&mut [1]);
+        let mut items = [1, 2, 3];
+        rotator.rotate(&mut items);
+        assert_eq!(items, [2, 3, 1]);
+
+        let mut items = [1, 2, 3];
+        Rotator::default().rotate(&mut items);
+        assert_eq!(items, [1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random() {
+        let rotator = Rotator::new(Rotation::Random);
+        let mut items: [u8; 16] = core::array::from_fn(|i| i as u8);
+        rotator.rotate(&mut items);
+        let mut sorted = items;
+        sorted.sort();
+        assert_eq!(sorted, core::array::from_fn::<u8, 16, _>(|i| i as u8));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn weighted() {
+        // (priority, weight, id)
+        let key = |item: &(u16, u16, u8)| (item.0, item.1);
+        let mut first_b = 0;
+        for _ in 0..1000 {
+            let mut items = [
+                (20, 0, b'd'),
+                (10, 10, b'a'),
+                (10, 0, b'c'),
+                (10, 90, b'b'),
+            ];
+            Rotator::new(Rotation::SrvWeighted)
+                .rotate_weighted(&mut items, key);
+            assert_eq!(items[3].2, b'd');
+            if items[0].2 == b'b' {
+                first_b += 1;
+            }
+            let mut ids: [u8; 3] = [items[0].2, items[1].2, items[2].2];
+            ids.sort();
+            assert_eq!(&ids, b"abc");
+        }
+        // The heavy record should come first about 90 % of the time.
+        assert!(first_b > 700, "{}", first_b);
+
+        let mut items = [(1, 5, b'a'), (0, 5, b'b')];
+        Rotator::new(Rotation::RoundRobin).rotate_weighted(&mut items, key);
+        assert_eq!(items, [(1, 5, b'a'), (0, 5, b'b')]);
+    }
+}
@@ -0,0 +1,206 @@
+//! Reading DNS messages from byte streams.
+//!
+//! On stream transports such as TCP, each DNS message is preceded by its
+//! length as a 16 bit unsigned integer in network byte order. A
+//! [`MessageStream`] reads such length-prefixed messages from an
+//! asynchronous reader and yields each complete message as soon as it has
+//! arrived. It is used by the stub resolver’s TCP transport and the
+//! server’s stream transport and is equally suitable for receiving the
+//! many response messages of a zone transfer over a single connection.
+
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The amount of buffer space reserved when the length isn’t known yet.
+const READ_SIZE: usize = 4096;
+
+//------------ MessageStream -------------------------------------------------
+
+/// A reader of length-prefixed DNS messages.
+///
+/// The stream wraps an asynchronous reader and a buffer. It reads as much
+/// data as is available and keeps whatever belongs to the next message in
+/// its buffer, so it copes with messages arriving in pieces as well as
+/// with several messages arriving at once.
+///
+/// Messages longer than a configurable maximum size are rejected with an
+/// error before any of their content is read. Since the stream can’t
+/// recover from that, the connection should be closed afterwards.
+#[derive(Debug)]
+pub struct MessageStream<R> {
+    /// The reader to read from.
+    sock: R,
+
+    /// The data read but not yet returned.
+    buf: BytesMut,
+
+    /// The maximum length of a message.
+    max_size: usize,
+}
+
+impl<R> MessageStream<R> {
+    /// Creates a new stream reading from `sock`.
+    ///
+    /// Messages may have any length up to the maximum of 65,535 octets.
+    pub fn new(sock: R) -> Self {
+        MessageStream {
+            sock,
+            buf: BytesMut::new(),
+            max_size: usize::from(u16::MAX),
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Sets the maximum length of a message.
+    ///
+    /// The length doesn’t include the two octets of the length prefix.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size
+    }
+
+    /// Returns the number of octets read but not yet returned.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.sock
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    ///
+    /// Reading from the reader directly will very likely break the framing
+    /// of the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.sock
+    }
+
+    /// Converts the stream into the underlying reader.
+    ///
+    /// Any data buffered is lost.
+    pub fn into_inner(self) -> R {
+        self.sock
+    }
+
+    /// Returns the next complete message in the buffer if there is one.
+    fn take_message(&mut self) -> Result<Option<Bytes>, io::Error> {
+        if self.buf.len() < 2 {
+            return Ok(None);
+        }
+        let len = usize::from(u16::from_be_bytes([self.buf[0], self.buf[1]]));
+        if len > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message too long",
+            ));
+        }
+        if self.buf.len() < len + 2 {
+            self.buf.reserve(len + 2 - self.buf.len());
+            return Ok(None);
+        }
+        self.buf.advance(2);
+        Ok(Some(self.buf.split_to(len).freeze()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> MessageStream<R> {
+    /// Reads the next message from the stream.
+    ///
+    /// Returns the octets of the message without the length prefix or
+    /// `Ok(None)` if the stream was closed cleanly before a new message
+    /// started. If it is closed in the middle of a message, returns an
+    /// error of kind `UnexpectedEof`.
+    ///
+    /// This method is cancel safe: all data read so far is kept in the
+    /// buffer, so reading can continue later, e.g., after a timeout.
+    pub async fn next_message(&mut self) -> Result<Option<Bytes>, io::Error> {
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(Some(message));
+            }
+            if self.buf.capacity() == self.buf.len() {
+                self.buf.reserve(READ_SIZE);
+            }
+            if self.sock.read_buf(&mut self.buf).await? == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "stream closed within a message",
+                ));
+            }
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn partial_and_combined() {
+        let (mut write, read) = tokio::io::duplex(64);
+        let mut stream = MessageStream::new(read);
+
+        // One message in two pieces.
+        write.write_all(&[0, 3, b'a']).await.unwrap();
+        write.write_all(b"bc").await.unwrap();
+        assert_eq!(stream.next_message().await.unwrap().unwrap(), "abc");
+
+        // Two messages and the start of a third at once.
+        write.write_all(b"\x00\x01d\x00\x00\x00").await.unwrap();
+        assert_eq!(stream.next_message().await.unwrap().unwrap(), "d");
+        assert_eq!(stream.next_message().await.unwrap().unwrap(), "");
+        assert_eq!(stream.buffered(), 1);
+        write.write_all(b"\x02ef").await.unwrap();
+        assert_eq!(stream.next_message().await.unwrap().unwrap(), "ef");
+
+        // A message longer than the duplex buffer.
+        let long: Vec<u8> = (0..200).collect();
+        let task = tokio::spawn(async move {
+            write.write_all(&[0, 200]).await.unwrap();
+            write.write_all(&long).await.unwrap();
+            write
+        });
+        let message = stream.next_message().await.unwrap().unwrap();
+        assert_eq!(message.len(), 200);
+        assert_eq!(message[199], 199);
+
+        // Clean end of the stream.
+        drop(task.await.unwrap());
+        assert!(stream.next_message().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let (mut write, read) = tokio::io::duplex(64);
+        let mut stream = MessageStream::new(read);
+        stream.set_max_size(4);
+        write.write_all(&[0, 5]).await.unwrap();
+        assert_eq!(
+            stream.next_message().await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let (mut write, read) = tokio::io::duplex(64);
+        let mut stream = MessageStream::new(read);
+        write.write_all(&[0, 5, 1, 2]).await.unwrap();
+        drop(write);
+        assert_eq!(
+            stream.next_message().await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,765 @@
+//! Maintaining DS records via CDS and CDNSKEY records.
+//!
+//! [RFC 7344] allows a child zone to tell its parent which DS records it
+//! would like to have by publishing CDS or CDNSKEY records at its apex. A
+//! parent that scans for these records can then update the DS RRset of
+//! the child without manual intervention. [RFC 8078] adds a special form
+//! of both records that requests the removal of all DS records, turning
+//! the child into an insecure delegation.
+//!
+//! The [`DsMaintainer`] implements the checks a parent has to perform
+//! before acting upon such a request and calculates the resulting change
+//! to the DS RRset. It is fed the child’s DNSKEY RRset and its CDS and
+//! CDNSKEY RRsets together with their signatures and results in a
+//! [`DsChange`].
+//!
+//! Only updates of a delegation that already is secure are covered. How a
+//! parent establishes trust in the CDS or CDNSKEY records of an unsigned
+//! delegation as described in [RFC 8078, section 3] is a matter of local
+//! policy.
+//!
+//! [RFC 7344]: https://tools.ietf.org/html/rfc7344
+//! [RFC 8078]: https://tools.ietf.org/html/rfc8078
+//! [RFC 8078, section 3]: https://tools.ietf.org/html/rfc8078#section-3
+
+use super::RrsigExt;
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::DigestAlg;
+use crate::base::name::{Dname, ToDname};
+use crate::base::rdata::{ComposeRecordData, RecordData};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::crypto::{AlgorithmError, Backend};
+use crate::rdata::{Cdnskey, Cds, Dnskey, Ds, Rrsig};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ DsMaintainer --------------------------------------------------
+
+/// Determines the DS changes requested by a child zone.
+///
+/// The maintainer is created for a child zone and its current DS records.
+/// First, the child’s DNSKEY RRset has to be added via
+/// [`add_dnskeys`][Self::add_dnskeys]. It must be signed by a key referred
+/// to by one of the current DS records. Then the child’s CDS and CDNSKEY
+/// RRsets, if present, are added via [`add_cds`][Self::add_cds] and
+/// [`add_cdnskeys`][Self::add_cdnskeys]. As required by RFC 7344, these
+/// also need to be signed by a key referred to by a current DS record.
+/// Finally, [`finish`][Self::finish] checks that the requested DS RRset
+/// doesn’t break the chain of trust and returns the necessary changes.
+///
+/// DS records for CDNSKEY records are created with the digest type set via
+/// [`set_digest_type`][Self::set_digest_type] which defaults to SHA-256.
+#[derive(Clone, Debug)]
+pub struct DsMaintainer<B> {
+    /// The apex of the child zone.
+    zone: Dname<Vec<u8>>,
+
+    /// The current DS records of the child zone.
+    current: Vec<Ds<Vec<u8>>>,
+
+    /// The backend for cryptographic operations.
+    backend: B,
+
+    /// The time to check signature validity periods against.
+    now: Serial,
+
+    /// The digest type for DS records created from CDNSKEY records.
+    digest_type: DigestAlg,
+
+    /// The keys that signed the DNSKEY RRset.
+    ///
+    /// This is `None` until the RRset has been added.
+    signing_keys: Option<Vec<Dnskey<Vec<u8>>>>,
+
+    /// The keys that are referred to by the current DS records.
+    ///
+    /// Only signatures by these keys are accepted for the CDS and CDNSKEY
+    /// RRsets.
+    ds_keys: Vec<Dnskey<Vec<u8>>>,
+
+    /// The CDS records of the child if they have been added.
+    cds: Option<Vec<Ds<Vec<u8>>>>,
+
+    /// The CDNSKEY records of the child if they have been added.
+    cdnskeys: Option<Vec<Dnskey<Vec<u8>>>>,
+}
+
+#[cfg(feature = "ring")]
+impl DsMaintainer<RingBackend> {
+    /// Creates a new maintainer using the [`RingBackend`].
+    ///
+    /// The maintainer checks the records of the child zone `zone` which
+    /// currently has the DS records `current`. Signatures are checked
+    /// against the time `now` given as a UNIX timestamp wrapped into a
+    /// serial.
+    pub fn new(
+        zone: Dname<Vec<u8>>,
+        current: Vec<Ds<Vec<u8>>>,
+        now: Serial,
+    ) -> Self {
+        Self::with_backend(zone, current, RingBackend, now)
+    }
+}
+
+impl<B: Backend> DsMaintainer<B> {
+    /// Creates a new maintainer using the given crypto backend.
+    pub fn with_backend(
+        zone: Dname<Vec<u8>>,
+        current: Vec<Ds<Vec<u8>>>,
+        backend: B,
+        now: Serial,
+    ) -> Self {
+        DsMaintainer {
+            zone,
+            current,
+            backend,
+            now,
+            digest_type: DigestAlg::Sha256,
+            signing_keys: None,
+            ds_keys: Vec::new(),
+            cds: None,
+            cdnskeys: None,
+        }
+    }
+
+    /// Sets the digest type for DS records created from CDNSKEY records.
+    pub fn set_digest_type(&mut self, digest_type: DigestAlg) {
+        self.digest_type = digest_type
+    }
+
+    /// Returns the apex of the child zone.
+    pub fn zone(&self) -> &Dname<Vec<u8>> {
+        &self.zone
+    }
+
+    /// Returns the current DS records of the child zone.
+    pub fn current(&self) -> &[Ds<Vec<u8>>] {
+        &self.current
+    }
+
+    /// Adds the DNSKEY RRset of the child zone.
+    ///
+    /// The RRset must be signed by a key that is referred to by one of the
+    /// current DS records.
+    pub fn add_dnskeys<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Dnskey<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> Result<(), CdsError>
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        self.check_owner(records)?;
+        let keys: Vec<_> = records
+            .iter()
+            .map(|record| to_vec_key(record.data()))
+            .collect();
+        let ds_keys: Vec<_> = keys
+            .iter()
+            .filter(|key| {
+                self.current.iter().any(|ds| {
+                    ds.matches_dnskey_with(&self.backend, &self.zone, *key)
+                        .unwrap_or(false)
+                })
+            })
+            .cloned()
+            .collect();
+        if ds_keys.is_empty() {
+            return Err(CdsError::NoDsKey);
+        }
+        self.verify(&ds_keys, records, rrsigs)?;
+        self.signing_keys = Some(self.verify(&keys, records, rrsigs)?);
+        self.ds_keys = ds_keys;
+        Ok(())
+    }
+
+    /// Adds the CDS RRset of the child zone.
+    ///
+    /// The DNSKEY RRset must have been added before and the CDS RRset must
+    /// be signed by a key that is referred to by a current DS record.
+    pub fn add_cds<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Cds<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> Result<(), CdsError>
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        self.check_owner(records)?;
+        if self.signing_keys.is_none() {
+            return Err(CdsError::MissingDnskeys);
+        }
+        self.verify(&self.ds_keys, records, rrsigs)?;
+        self.cds = Some(
+            records
+                .iter()
+                .map(|record| cds_to_ds(record.data()))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// Adds the CDNSKEY RRset of the child zone.
+    ///
+    /// The DNSKEY RRset must have been added before and the CDNSKEY RRset
+    /// must be signed by a key that is referred to by a current DS record.
+    pub fn add_cdnskeys<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Cdnskey<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> Result<(), CdsError>
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        self.check_owner(records)?;
+        if self.signing_keys.is_none() {
+            return Err(CdsError::MissingDnskeys);
+        }
+        self.verify(&self.ds_keys, records, rrsigs)?;
+        self.cdnskeys = Some(
+            records
+                .iter()
+                .map(|record| cdnskey_to_dnskey(record.data()))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// Determines the change to the DS RRset requested by the child.
+    ///
+    /// If neither CDS nor CDNSKEY records have been added, nothing
+    /// changes. If both have been added, they must refer to the same set
+    /// of keys. A request to delete the DS RRset must consist of a single
+    /// delete record in each RRset present. Otherwise, the requested DS
+    /// RRset must refer to at least one key that signed the child’s
+    /// DNSKEY RRset using a supported digest type.
+    pub fn finish(self) -> Result<DsChange, CdsError> {
+        let signing_keys = match self.signing_keys {
+            Some(ref keys) => keys,
+            None => return Err(CdsError::MissingDnskeys),
+        };
+
+        // Check for and handle delete requests first.
+        let cds_delete = match self.cds {
+            Some(ref cds) => is_delete(cds, |ds| ds.algorithm().to_int())?,
+            None => None,
+        };
+        let cdnskey_delete = match self.cdnskeys {
+            Some(ref keys) => {
+                is_delete(keys, |key| key.algorithm().to_int())?
+            }
+            None => None,
+        };
+        match (cds_delete, cdnskey_delete) {
+            (None, None) => return Ok(DsChange::Unchanged),
+            (Some(true), Some(true) | None) | (None, Some(true)) => {
+                return Ok(DsChange::Delete)
+            }
+            (Some(true), Some(false)) | (Some(false), Some(true)) => {
+                return Err(CdsError::Inconsistent)
+            }
+            _ => {}
+        }
+
+        // Assemble the requested DS RRset.
+        let requested = match (self.cds.as_ref(), self.cdnskeys.as_ref()) {
+            (Some(cds), Some(keys)) => {
+                let consistent = cds.iter().all(|ds| {
+                    keys.iter().any(|key| self.ds_matches(ds, key))
+                }) && keys
+                    .iter()
+                    .all(|key| cds.iter().any(|ds| self.ds_matches(ds, key)));
+                if !consistent {
+                    return Err(CdsError::Inconsistent);
+                }
+                cds.clone()
+            }
+            (Some(cds), None) => cds.clone(),
+            (None, Some(keys)) => {
+                let mut res = Vec::with_capacity(keys.len());
+                for key in keys {
+                    res.push(
+                        Ds::from_dnskey_with(
+                            &self.backend,
+                            &self.zone,
+                            key,
+                            self.digest_type,
+                        )
+                        .map_err(CdsError::Algorithm)?,
+                    );
+                }
+                res
+            }
+            (None, None) => unreachable!(),
+        };
+
+        // The new DS RRset must not break the chain of trust.
+        if !requested
+            .iter()
+            .any(|ds| signing_keys.iter().any(|key| self.ds_matches(ds, key)))
+        {
+            return Err(CdsError::BreaksChain);
+        }
+
+        let add: Vec<_> = requested
+            .iter()
+            .filter(|ds| !self.current.contains(ds))
+            .cloned()
+            .collect();
+        let remove: Vec<_> = self
+            .current
+            .iter()
+            .filter(|ds| !requested.contains(ds))
+            .cloned()
+            .collect();
+        if add.is_empty() && remove.is_empty() {
+            Ok(DsChange::Unchanged)
+        } else {
+            Ok(DsChange::Update { add, remove })
+        }
+    }
+
+    /// Checks that the records are owned by the child’s apex.
+    fn check_owner<N: ToDname, D>(
+        &self,
+        records: &[Record<N, D>],
+    ) -> Result<(), CdsError> {
+        match records.first() {
+            Some(record) if record.owner().name_eq(&self.zone) => Ok(()),
+            Some(_) => Err(CdsError::WrongOwner),
+            None => Err(CdsError::Empty),
+        }
+    }
+
+    /// Returns those keys that produced a valid signature for the RRset.
+    ///
+    /// Returns an error if none did.
+    fn verify<N, D, SigOcts, SigName>(
+        &self,
+        keys: &[Dnskey<Vec<u8>>],
+        records: &mut [Record<N, D>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> Result<Vec<Dnskey<Vec<u8>>>, CdsError>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        let mut res = Vec::new();
+        for key in keys {
+            let signed = rrsigs.iter().any(|rrsig| {
+                rrsig
+                    .verify_rrset(
+                        &self.backend,
+                        &self.zone,
+                        key,
+                        records,
+                        self.now,
+                    )
+                    .is_ok()
+            });
+            if signed {
+                res.push(key.clone())
+            }
+        }
+        if res.is_empty() {
+            Err(CdsError::InvalidSignature)
+        } else {
+            Ok(res)
+        }
+    }
+
+    /// Returns whether a DS record refers to a key.
+    fn ds_matches(&self, ds: &Ds<Vec<u8>>, key: &Dnskey<Vec<u8>>) -> bool {
+        ds.matches_dnskey_with(&self.backend, &self.zone, key)
+            .unwrap_or(false)
+    }
+}
+
+//------------ DsChange ------------------------------------------------------
+
+/// The change to a DS RRset requested by a child zone.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DsChange {
+    /// The DS RRset stays as it is.
+    Unchanged,
+
+    /// DS records need to be added and removed.
+    Update {
+        /// The DS records to add.
+        add: Vec<Ds<Vec<u8>>>,
+
+        /// The DS records to remove.
+        remove: Vec<Ds<Vec<u8>>>,
+    },
+
+    /// All DS records need to be removed.
+    ///
+    /// The child zone will become an insecure delegation.
+    Delete,
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Checks whether an RRset is a delete request.
+///
+/// Returns `None` if there are no records, whether the RRset is a delete
+/// request otherwise, and an error if the RRset contains a delete record
+/// alongside other records.
+fn is_delete<T>(
+    records: &[T],
+    algorithm: impl Fn(&T) -> u8,
+) -> Result<Option<bool>, CdsError> {
+    let deletes = records.iter().filter(|item| algorithm(item) == 0).count();
+    if records.is_empty() {
+        Ok(None)
+    } else if deletes == 0 {
+        Ok(Some(false))
+    } else if deletes == 1 && records.len() == 1 {
+        Ok(Some(true))
+    } else {
+        Err(CdsError::InvalidDelete)
+    }
+}
+
+/// Copies DNSKEY record data into a vec.
+fn to_vec_key(key: &Dnskey<impl AsRef<[u8]>>) -> Dnskey<Vec<u8>> {
+    // Safety: The data has come from valid record data.
+    unsafe {
+        Dnskey::new_unchecked(
+            key.flags(),
+            key.protocol(),
+            key.algorithm(),
+            key.public_key().as_ref().into(),
+        )
+    }
+}
+
+/// Converts CDNSKEY record data into DNSKEY record data.
+fn cdnskey_to_dnskey(key: &Cdnskey<impl AsRef<[u8]>>) -> Dnskey<Vec<u8>> {
+    // Safety: The data has come from valid record data of the same format.
+    unsafe {
+        Dnskey::new_unchecked(
+            key.flags(),
+            key.protocol(),
+            key.algorithm(),
+            key.public_key().as_ref().into(),
+        )
+    }
+}
+
+/// Converts CDS record data into DS record data.
+fn cds_to_ds(cds: &Cds<impl AsRef<[u8]>>) -> Ds<Vec<u8>> {
+    // Safety: The data has come from valid record data of the same format.
+    unsafe {
+        Ds::new_unchecked(
+            cds.key_tag(),
+            cds.algorithm(),
+            cds.digest_type(),
+            cds.digest().as_ref().into(),
+        )
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ CdsError ------------------------------------------------------
+
+/// The records of a child zone can’t be used for updating its DS records.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CdsError {
+    /// An RRset was empty.
+    Empty,
+
+    /// The records aren’t owned by the child zone’s apex.
+    WrongOwner,
+
+    /// None of the current DS records refers to a key of the child.
+    NoDsKey,
+
+    /// The RRset isn’t signed by an acceptable key.
+    InvalidSignature,
+
+    /// The DNSKEY RRset hasn’t been added yet.
+    MissingDnskeys,
+
+    /// The CDS and CDNSKEY RRsets don’t agree.
+    Inconsistent,
+
+    /// A delete record is accompanied by other records.
+    InvalidDelete,
+
+    /// The requested DS RRset doesn’t refer to any key signing the DNSKEY
+    /// RRset.
+    BreaksChain,
+
+    /// Creating a DS record from a CDNSKEY record failed.
+    Algorithm(AlgorithmError),
+}
+
+//--- Display and Error
+
+impl fmt::Display for CdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CdsError::Empty => f.write_str("empty RRset"),
+            CdsError::WrongOwner => f.write_str("records not at zone apex"),
+            CdsError::NoDsKey => f.write_str("no key matching current DS"),
+            CdsError::InvalidSignature => f.write_str("no valid signature"),
+            CdsError::MissingDnskeys => f.write_str("missing DNSKEY RRset"),
+            CdsError::Inconsistent => {
+                f.write_str("CDS and CDNSKEY records differ")
+            }
+            CdsError::InvalidDelete => f.write_str("invalid delete request"),
+            CdsError::BreaksChain => {
+                f.write_str("DS records would break chain of trust")
+            }
+            CdsError::Algorithm(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for CdsError {}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, SecAlg};
+    use crate::base::Ttl;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+
+    const NOW: u32 = 1_500_000;
+
+    struct TestKey {
+        pair: Ed25519KeyPair,
+        dnskey: Dnskey<Vec<u8>>,
+    }
+
+    impl TestKey {
+        fn new(seed: u8, flags: u16) -> Self {
+            let pair =
+                Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+            let dnskey = Dnskey::new(
+                flags,
+                3,
+                SecAlg::Ed25519,
+                pair.public_key().as_ref().into(),
+            )
+            .unwrap();
+            TestKey { pair, dnskey }
+        }
+
+        fn ds(&self) -> Ds<Vec<u8>> {
+            Ds::from_dnskey(&zone(), &self.dnskey, DigestAlg::Sha256).unwrap()
+        }
+
+        fn cds(&self) -> Cds<Vec<u8>> {
+            let ds = self.ds();
+            Cds::new(
+                ds.key_tag(),
+                ds.algorithm(),
+                ds.digest_type(),
+                ds.into_digest(),
+            )
+            .unwrap()
+        }
+
+        fn cdnskey(&self) -> Cdnskey<Vec<u8>> {
+            Cdnskey::new(
+                self.dnskey.flags(),
+                self.dnskey.protocol(),
+                self.dnskey.algorithm(),
+                self.dnskey.public_key().clone(),
+            )
+            .unwrap()
+        }
+
+        fn sign<D>(
+            &self,
+            records: &mut [Record<Name, D>],
+        ) -> Rrsig<Vec<u8>, Name>
+        where
+            D: RecordData + CanonicalOrd + ComposeRecordData,
+        {
+            let mut rrsig = Rrsig::new(
+                records[0].rtype(),
+                SecAlg::Ed25519,
+                1,
+                Ttl::from_secs(3600),
+                (NOW + 1000).into(),
+                (NOW - 1000).into(),
+                self.dnskey.key_tag(),
+                zone(),
+                Vec::new(),
+            )
+            .unwrap();
+            let mut buf = Vec::new();
+            rrsig.signed_data(&mut buf, records).unwrap();
+            rrsig.set_signature(self.pair.sign(&buf).as_ref().into());
+            rrsig
+        }
+    }
+
+    fn zone() -> Name {
+        Name::from_str("example.").unwrap()
+    }
+
+    fn rrset<D>(data: Vec<D>) -> Vec<Record<Name, D>> {
+        data.into_iter()
+            .map(|data| {
+                Record::new(zone(), Class::In, Ttl::from_secs(3600), data)
+            })
+            .collect()
+    }
+
+    /// Returns a maintainer for a child with a KSK rollover in progress.
+    ///
+    /// The current DS refers to `old` which, together with `new`, signs
+    /// the DNSKEY RRset.
+    fn maintainer(old: &TestKey, new: &TestKey) -> DsMaintainer<RingBackend> {
+        let mut res = DsMaintainer::new(zone(), vec![old.ds()], NOW.into());
+        let mut keys = rrset(vec![old.dnskey.clone(), new.dnskey.clone()]);
+        let sigs = vec![old.sign(&mut keys), new.sign(&mut keys)];
+        res.add_dnskeys(&mut keys, &sigs).unwrap();
+        res
+    }
+
+    #[test]
+    fn update() {
+        let old = TestKey::new(1, 257);
+        let new = TestKey::new(2, 257);
+
+        // No CDS or CDNSKEY, nothing changes.
+        assert_eq!(maintainer(&old, &new).finish(), Ok(DsChange::Unchanged));
+
+        // CDS for the new key only.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![new.cds()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        assert_eq!(
+            updater.finish(),
+            Ok(DsChange::Update {
+                add: vec![new.ds()],
+                remove: vec![old.ds()],
+            })
+        );
+
+        // Matching CDS and CDNSKEY for both keys.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![old.cds(), new.cds()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        let mut cdnskeys = rrset(vec![old.cdnskey(), new.cdnskey()]);
+        let sig = old.sign(&mut cdnskeys);
+        updater.add_cdnskeys(&mut cdnskeys, &[sig]).unwrap();
+        assert_eq!(
+            updater.finish(),
+            Ok(DsChange::Update {
+                add: vec![new.ds()],
+                remove: Vec::new(),
+            })
+        );
+
+        // CDNSKEY for the current key only.
+        let mut updater = maintainer(&old, &new);
+        let mut cdnskeys = rrset(vec![old.cdnskey()]);
+        let sig = old.sign(&mut cdnskeys);
+        updater.add_cdnskeys(&mut cdnskeys, &[sig]).unwrap();
+        assert_eq!(updater.finish(), Ok(DsChange::Unchanged));
+    }
+
+    #[test]
+    fn delete() {
+        let old = TestKey::new(1, 257);
+        let new = TestKey::new(2, 257);
+        let delete_cds =
+            Cds::new(0, SecAlg::from_int(0), DigestAlg::from_int(0), vec![0])
+                .unwrap();
+        let delete_cdnskey =
+            Cdnskey::new(0, 3, SecAlg::from_int(0), vec![0]).unwrap();
+
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![delete_cds.clone()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        let mut cdnskeys = rrset(vec![delete_cdnskey]);
+        let sig = old.sign(&mut cdnskeys);
+        updater.add_cdnskeys(&mut cdnskeys, &[sig]).unwrap();
+        assert_eq!(updater.finish(), Ok(DsChange::Delete));
+
+        // A delete record must be alone.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![delete_cds, old.cds()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        assert_eq!(updater.finish(), Err(CdsError::InvalidDelete));
+    }
+
+    #[test]
+    fn rejected() {
+        let old = TestKey::new(1, 257);
+        let new = TestKey::new(2, 257);
+        let zsk = TestKey::new(3, 256);
+
+        // The CDS RRset must be signed by the key with the current DS.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![new.cds()]);
+        let sig = new.sign(&mut cds);
+        assert_eq!(
+            updater.add_cds(&mut cds, &[sig]),
+            Err(CdsError::InvalidSignature)
+        );
+
+        // The DNSKEY RRset must be signed by the key with the current DS.
+        let mut updater =
+            DsMaintainer::new(zone(), vec![old.ds()], NOW.into());
+        let mut keys = rrset(vec![old.dnskey.clone(), new.dnskey.clone()]);
+        let sig = new.sign(&mut keys);
+        assert_eq!(
+            updater.add_dnskeys(&mut keys, &[sig]),
+            Err(CdsError::InvalidSignature)
+        );
+        let mut cds = rrset(vec![new.cds()]);
+        let sig = old.sign(&mut cds);
+        assert_eq!(
+            updater.add_cds(&mut cds, &[sig]),
+            Err(CdsError::MissingDnskeys)
+        );
+
+        // The new DS must refer to a key signing the DNSKEY RRset.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![zsk.cds()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        assert_eq!(updater.finish(), Err(CdsError::BreaksChain));
+
+        // CDS and CDNSKEY must agree.
+        let mut updater = maintainer(&old, &new);
+        let mut cds = rrset(vec![new.cds()]);
+        let sig = old.sign(&mut cds);
+        updater.add_cds(&mut cds, &[sig]).unwrap();
+        let mut cdnskeys = rrset(vec![old.cdnskey()]);
+        let sig = old.sign(&mut cdnskeys);
+        updater.add_cdnskeys(&mut cdnskeys, &[sig]).unwrap();
+        assert_eq!(updater.finish(), Err(CdsError::Inconsistent));
+    }
+}
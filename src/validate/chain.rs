@@ -0,0 +1,1077 @@
+//! Building a chain of trust.
+//!
+//! An RRset is only secure if there is an unbroken chain of signed DS and
+//! DNSKEY records leading from a configured trust anchor down to the zone
+//! that signed the RRset. This module provides the types for building and
+//! following such a chain.
+//!
+//! The trust anchors are collected in a [`TrustAnchors`] value. The
+//! [`ChainValidator`] then starts at one of the anchors and is fed the
+//! DNSKEY and DS RRsets of the zones down to the target zone via
+//! [`add_dnskeys`][ChainValidator::add_dnskeys] and
+//! [`add_ds`][ChainValidator::add_ds]. Finally, the target RRset is checked
+//! via [`validate_rrset`][ChainValidator::validate_rrset]. Each step
+//! results in a [`SecurityStatus`] that describes the outcome and, for
+//! anything but a secure result, the reason for it.
+//!
+//! The validator doesn’t fetch any records itself. It is up to the caller
+//! to look up the records necessary and hand them to the validator in the
+//! right order.
+
+use super::denial::DenialError;
+use super::{RrsigExt, ValidationError};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{DigestAlg, SecAlg};
+use crate::base::name::{Dname, ToDname};
+use crate::base::rdata::{ComposeRecordData, RecordData};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::crypto::Backend;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::rdata::{Dnskey, Ds, Rrsig};
+use std::fmt;
+use std::vec::Vec;
+
+//------------ TrustAnchors --------------------------------------------------
+
+/// A set of trust anchors.
+///
+/// A trust anchor is either a DS or a DNSKEY record for a zone that is
+/// trusted without further validation. There can be any number of anchors
+/// for any number of zones.
+///
+/// In addition, the set can contain negative trust anchors as described
+/// in [RFC 7646]. These mark a domain and everything below it as insecure
+/// until the anchor expires, allowing operators to temporarily switch off
+/// validation for a subtree with broken DNSSEC. A positive trust anchor
+/// below a negative one takes precedence for its own subtree.
+///
+/// [RFC 7646]: https://tools.ietf.org/html/rfc7646
+#[derive(Clone, Debug, Default)]
+pub struct TrustAnchors {
+    /// The DS records acting as trust anchors.
+    ds: Vec<Owned<Ds<Vec<u8>>>>,
+
+    /// The DNSKEY records acting as trust anchors.
+    dnskeys: Vec<Owned<Dnskey<Vec<u8>>>>,
+
+    /// The negative trust anchors and their expiry times.
+    negative: Vec<Owned<Serial>>,
+}
+
+impl TrustAnchors {
+    /// The key tags and SHA-256 digests of the root zone’s KSKs.
+    ///
+    /// These are KSK-2017 and KSK-2024 as published by IANA.
+    const ROOT_ANCHORS: [(u16, [u8; 32]); 2] = [
+        (
+            20326,
+            [
+                0xe0, 0x6d, 0x44, 0xb8, 0x0b, 0x8f, 0x1d, 0x39, 0xa9, 0x5c,
+                0x0b, 0x0d, 0x7c, 0x65, 0xd0, 0x84, 0x58, 0xe8, 0x80, 0x40,
+                0x9b, 0xbc, 0x68, 0x34, 0x57, 0x10, 0x42, 0x37, 0xc7, 0xf8,
+                0xec, 0x8d,
+            ],
+        ),
+        (
+            38696,
+            [
+                0x68, 0x3d, 0x2d, 0x0a, 0xcb, 0x8c, 0x9b, 0x71, 0x2a, 0x19,
+                0x48, 0xb2, 0x7f, 0x74, 0x12, 0x19, 0x29, 0x8d, 0x0a, 0x45,
+                0x0d, 0x61, 0x2c, 0x48, 0x3a, 0xf4, 0x44, 0xa4, 0xc0, 0xfb,
+                0x2b, 0x16,
+            ],
+        ),
+    ];
+
+    /// Creates a new, empty set of trust anchors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a set containing the trust anchors of the root zone.
+    ///
+    /// The set contains the DS records for the root zone’s key signing
+    /// keys as published by IANA.
+    pub fn iana_root() -> Self {
+        let mut res = Self::new();
+        for (key_tag, digest) in Self::ROOT_ANCHORS {
+            res.add_ds(
+                Dname::root_vec(),
+                Ds::new(
+                    key_tag,
+                    SecAlg::RsaSha256,
+                    DigestAlg::Sha256,
+                    digest.into(),
+                )
+                .expect("long digest"),
+            );
+        }
+        res
+    }
+
+    /// Adds a DS record as a trust anchor for the zone `owner`.
+    pub fn add_ds(&mut self, owner: Dname<Vec<u8>>, ds: Ds<Vec<u8>>) {
+        self.ds.push((owner, ds))
+    }
+
+    /// Adds a DNSKEY record as a trust anchor for the zone `owner`.
+    pub fn add_dnskey(
+        &mut self,
+        owner: Dname<Vec<u8>>,
+        dnskey: Dnskey<Vec<u8>>,
+    ) {
+        self.dnskeys.push((owner, dnskey))
+    }
+
+    /// Adds a negative trust anchor for the domain `owner`.
+    ///
+    /// The anchor is in effect until `expires`, a UNIX timestamp wrapped
+    /// into a serial. RFC 7646 recommends not to keep negative trust
+    /// anchors for longer than a week. If there already is a negative
+    /// anchor for `owner`, its expiry time is replaced.
+    pub fn add_negative(&mut self, owner: Dname<Vec<u8>>, expires: Serial) {
+        match self
+            .negative
+            .iter_mut()
+            .find(|(item, _)| item.name_eq(&owner))
+        {
+            Some((_, item)) => *item = expires,
+            None => self.negative.push((owner, expires)),
+        }
+    }
+
+    /// Removes the negative trust anchor for the domain `owner`.
+    ///
+    /// Returns whether there was such an anchor.
+    pub fn remove_negative<N: ToDname + ?Sized>(
+        &mut self,
+        owner: &N,
+    ) -> bool {
+        let len = self.negative.len();
+        self.negative.retain(|(item, _)| !item.name_eq(owner));
+        self.negative.len() != len
+    }
+
+    /// Removes all negative trust anchors that have expired at `now`.
+    pub fn remove_expired_negative(&mut self, now: Serial) {
+        self.negative.retain(|(_, expires)| now < *expires)
+    }
+
+    /// Returns an iterator over the negative trust anchors.
+    ///
+    /// The iterator returns the domain and expiry time of each anchor,
+    /// including anchors that have already expired.
+    pub fn negative(
+        &self,
+    ) -> impl Iterator<Item = (&Dname<Vec<u8>>, Serial)> {
+        self.negative
+            .iter()
+            .map(|(owner, expires)| (owner, *expires))
+    }
+
+    /// Returns whether `name` is covered by a negative trust anchor.
+    ///
+    /// This is the case if there is a negative anchor that hasn’t expired
+    /// at `now` for `name` or one of its ancestors and there is no
+    /// positive trust anchor closer to `name`.
+    pub fn is_negative<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+        now: Serial,
+    ) -> bool {
+        let negative = self
+            .negative
+            .iter()
+            .filter(|(owner, expires)| {
+                now < *expires && name.ends_with(owner)
+            })
+            .map(|(owner, _)| owner.label_count())
+            .max();
+        match (negative, self.closest(name)) {
+            (None, _) => false,
+            (Some(negative), Some(positive)) => {
+                negative >= positive.label_count()
+            }
+            (Some(_), None) => true,
+        }
+    }
+
+    /// Returns whether the set is empty.
+    ///
+    /// Negative trust anchors are not considered.
+    pub fn is_empty(&self) -> bool {
+        self.ds.is_empty() && self.dnskeys.is_empty()
+    }
+
+    /// Returns whether there are anchors for the given zone.
+    pub fn contains<N: ToDname + ?Sized>(&self, zone: &N) -> bool {
+        self.ds.iter().any(|(owner, _)| owner.name_eq(zone))
+            || self.dnskeys.iter().any(|(owner, _)| owner.name_eq(zone))
+    }
+
+    /// Returns an iterator over the DS anchors for the given zone.
+    pub fn ds<'a, N: ToDname + ?Sized>(
+        &'a self,
+        zone: &'a N,
+    ) -> impl Iterator<Item = &'a Ds<Vec<u8>>> + 'a {
+        self.ds
+            .iter()
+            .filter(move |(owner, _)| owner.name_eq(zone))
+            .map(|(_, ds)| ds)
+    }
+
+    /// Returns an iterator over the DNSKEY anchors for the given zone.
+    pub fn dnskeys<'a, N: ToDname + ?Sized>(
+        &'a self,
+        zone: &'a N,
+    ) -> impl Iterator<Item = &'a Dnskey<Vec<u8>>> + 'a {
+        self.dnskeys
+            .iter()
+            .filter(move |(owner, _)| owner.name_eq(zone))
+            .map(|(_, key)| key)
+    }
+
+    /// Returns the closest zone with an anchor that encloses `name`.
+    ///
+    /// This is the zone where validation of `name` needs to start.
+    pub fn closest<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Option<&Dname<Vec<u8>>> {
+        self.ds
+            .iter()
+            .map(|(owner, _)| owner)
+            .chain(self.dnskeys.iter().map(|(owner, _)| owner))
+            .filter(|owner| name.ends_with(*owner))
+            .max_by_key(|owner| owner.label_count())
+    }
+}
+
+//------------ ChainValidator ------------------------------------------------
+
+/// Follows a chain of trust from a trust anchor down to an RRset.
+///
+/// The validator keeps track of how far down the chain it has progressed.
+/// It starts out without any trusted keys. The first call to
+/// [`add_dnskeys`][Self::add_dnskeys] has to provide the DNSKEY RRset of a
+/// zone that has a trust anchor. If this RRset can be validated, its keys
+/// become the trusted keys. A call to [`add_ds`][Self::add_ds] with the
+/// DS RRset of a child zone signed by these keys then allows the next call
+/// to `add_dnskeys` to move on to that child zone. Any RRset within the
+/// current zone can be checked via [`validate_rrset`][Self::validate_rrset].
+///
+/// Once the chain has been found to be insecure or bogus, all further
+/// steps result in that same status.
+#[derive(Clone, Debug)]
+pub struct ChainValidator<'a, B> {
+    /// The trust anchors to start from.
+    anchors: &'a TrustAnchors,
+
+    /// The backend for cryptographic operations.
+    backend: B,
+
+    /// The time to check signature validity periods against.
+    now: Serial,
+
+    /// The deepest zone reached so far and its keys.
+    zone: Option<Owned<Vec<Dnskey<Vec<u8>>>>>,
+
+    /// The validated DS RRset for the next zone down the chain.
+    delegation: Option<Owned<Vec<Ds<Vec<u8>>>>>,
+
+    /// The final status if the chain turned out not to be secure.
+    broken: Option<SecurityStatus>,
+}
+
+#[cfg(feature = "ring")]
+impl<'a> ChainValidator<'a, RingBackend> {
+    /// Creates a new validator using the [`RingBackend`].
+    ///
+    /// The validator starts from `anchors` and checks signatures against
+    /// the time `now` given as a UNIX timestamp wrapped into a serial.
+    pub fn new(anchors: &'a TrustAnchors, now: Serial) -> Self {
+        Self::with_backend(anchors, RingBackend, now)
+    }
+}
+
+impl<'a, B: Backend> ChainValidator<'a, B> {
+    /// Creates a new validator using the given crypto backend.
+    pub fn with_backend(
+        anchors: &'a TrustAnchors,
+        backend: B,
+        now: Serial,
+    ) -> Self {
+        ChainValidator {
+            anchors,
+            backend,
+            now,
+            zone: None,
+            delegation: None,
+            broken: None,
+        }
+    }
+
+    /// Returns a reference to the crypto backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the deepest zone the chain of trust has reached.
+    pub fn zone(&self) -> Option<&Dname<Vec<u8>>> {
+        self.zone.as_ref().map(|(zone, _)| zone)
+    }
+
+    /// Returns the trusted keys of the deepest zone reached.
+    pub fn keys(&self) -> &[Dnskey<Vec<u8>>] {
+        match self.zone {
+            Some((_, ref keys)) => keys,
+            None => &[],
+        }
+    }
+
+    /// Adds the DNSKEY RRset of the next zone.
+    ///
+    /// If the zone has a trust anchor, the RRset is validated against it.
+    /// Otherwise, it is validated against the DS RRset previously added
+    /// via [`add_ds`][Self::add_ds]. In either case, the RRset must be
+    /// signed by one of the keys matching an anchor or DS record.
+    ///
+    /// If validation succeeded, the keys become the trusted keys used for
+    /// all further steps and a secure status is returned.
+    pub fn add_dnskeys<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Dnskey<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> SecurityStatus
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        if let Some(status) = self.broken {
+            return status;
+        }
+        let owner = match records.first() {
+            Some(record) => record.owner().to_vec(),
+            None => return self.bogus(Reason::NoMatchingKey),
+        };
+        if self.anchors.is_negative(&owner, self.now) {
+            return self.insecure(Reason::NegativeTrustAnchor);
+        }
+
+        // Collect the DS records and keys the RRset can be validated with.
+        let mut ds = Vec::new();
+        let mut anchor_keys = Vec::new();
+        if self.anchors.contains(&owner) {
+            ds.extend(self.anchors.ds(&owner).cloned());
+            anchor_keys.extend(self.anchors.dnskeys(&owner).cloned());
+        } else {
+            match self.delegation.take() {
+                Some((zone, zone_ds)) if zone.name_eq(&owner) => ds = zone_ds,
+                _ => {
+                    return if self.zone.is_none() {
+                        self.indeterminate(Reason::NoTrustAnchor)
+                    } else {
+                        self.indeterminate(Reason::MissingDelegation)
+                    }
+                }
+            }
+        }
+
+        // If there are only DS records for algorithms we don’t support,
+        // the zone is treated as insecure as per RFC 4035, section 5.2.
+        if anchor_keys.is_empty()
+            && !ds.is_empty()
+            && !ds.iter().any(|ds| self.supports_ds(ds))
+        {
+            return self.insecure(Reason::UnsupportedAlgorithm);
+        }
+
+        let candidates: Vec<_> = records
+            .iter()
+            .map(|record| record.data())
+            .filter(|key| {
+                key.is_zsk() && !key.is_revoked() && key.protocol() == 3
+            })
+            .filter(|key| {
+                anchor_keys.iter().any(|anchor| anchor == *key)
+                    || ds.iter().any(|ds| self.ds_matches(&owner, ds, key))
+            })
+            .map(to_vec_key)
+            .collect();
+        if candidates.is_empty() {
+            return self.bogus(Reason::NoMatchingKey);
+        }
+
+        if let Err(reason) = self.verify(&owner, &candidates, records, rrsigs)
+        {
+            return self.bogus(reason);
+        }
+
+        let keys = records.iter().map(|record| to_vec_key(record.data()));
+        self.zone = Some((owner, keys.collect()));
+        self.delegation = None;
+        SecurityStatus::Secure
+    }
+
+    /// Adds the DS RRset for a child zone.
+    ///
+    /// The child zone must be below the current zone and the RRset must be
+    /// signed by one of the current zone’s keys. If it is, the DS RRset
+    /// will be used to validate the child zone’s DNSKEY RRset in the next
+    /// call to [`add_dnskeys`][Self::add_dnskeys].
+    ///
+    /// If none of the DS records uses an algorithm supported by the
+    /// backend, the child zone is insecure.
+    pub fn add_ds<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Ds<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> SecurityStatus
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        if let Some(status) = self.broken {
+            return status;
+        }
+        let owner = match records.first() {
+            Some(record) => record.owner().to_vec(),
+            None => return self.bogus(Reason::MissingSignature),
+        };
+        if self.anchors.is_negative(&owner, self.now) {
+            return self.insecure(Reason::NegativeTrustAnchor);
+        }
+        let (zone, keys) = match self.zone {
+            Some((ref zone, ref keys)) => (zone, keys),
+            None => return self.indeterminate(Reason::NoTrustAnchor),
+        };
+        if owner.name_eq(zone) || !owner.ends_with(zone) {
+            return self.indeterminate(Reason::NotInZone);
+        }
+        if let Err(reason) = self.verify(zone, keys, records, rrsigs) {
+            return self.bogus(reason);
+        }
+
+        let ds: Vec<_> = records
+            .iter()
+            .map(|record| to_vec_ds(record.data()))
+            .collect();
+        if !ds.iter().any(|ds| self.supports_ds(ds)) {
+            return self.insecure(Reason::UnsupportedAlgorithm);
+        }
+        self.delegation = Some((owner, ds));
+        SecurityStatus::Secure
+    }
+
+    /// Validates an RRset of the current zone.
+    ///
+    /// The RRset must be at or below the current zone and signed by one of
+    /// its keys. The records are sorted into canonical order in the
+    /// process.
+    pub fn validate_rrset<N, D, SigOcts, SigName>(
+        &self,
+        records: &mut [Record<N, D>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> SecurityStatus
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        if let Some(status) = self.broken {
+            return status;
+        }
+        if let Some(record) = records.first() {
+            if self.anchors.is_negative(record.owner(), self.now) {
+                return SecurityStatus::Insecure(Reason::NegativeTrustAnchor);
+            }
+        }
+        let (zone, keys) = match self.zone {
+            Some((ref zone, ref keys)) => (zone, keys),
+            None => {
+                return SecurityStatus::Indeterminate(Reason::NoTrustAnchor)
+            }
+        };
+        match records.first() {
+            Some(record) if record.owner().ends_with(zone) => {}
+            Some(_) => {
+                return SecurityStatus::Indeterminate(Reason::NotInZone)
+            }
+            None => return SecurityStatus::Bogus(Reason::MissingSignature),
+        }
+        match self.verify(zone, keys, records, rrsigs) {
+            Ok(()) => SecurityStatus::Secure,
+            Err(reason) => SecurityStatus::Bogus(reason),
+        }
+    }
+
+    /// Verifies that an RRset is signed by one of the given keys.
+    fn verify<N, D, SigOcts, SigName>(
+        &self,
+        zone: &Dname<Vec<u8>>,
+        keys: &[Dnskey<Vec<u8>>],
+        records: &mut [Record<N, D>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+    ) -> Result<(), Reason>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        let rtype = match records.first() {
+            Some(record) => record.rtype(),
+            None => return Err(Reason::MissingSignature),
+        };
+        let mut err = None;
+        for rrsig in rrsigs.iter().filter(|sig| sig.type_covered() == rtype) {
+            for key in keys.iter().filter(|key| rrsig.matches_key(zone, key))
+            {
+                match rrsig.verify_rrset(
+                    &self.backend,
+                    zone,
+                    key,
+                    records,
+                    self.now,
+                ) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => err = Some(e),
+                }
+            }
+        }
+        match err {
+            Some(err) => Err(Reason::InvalidSignature(err)),
+            None => Err(Reason::MissingSignature),
+        }
+    }
+
+    /// Returns whether the backend supports a DS record’s algorithms.
+    fn supports_ds(&self, ds: &Ds<Vec<u8>>) -> bool {
+        self.backend.supports_algorithm(ds.algorithm())
+            && self.backend.supports_digest(ds.digest_type())
+    }
+
+    /// Returns whether a DS record refers to a key.
+    fn ds_matches(
+        &self,
+        owner: &Dname<Vec<u8>>,
+        ds: &Ds<Vec<u8>>,
+        key: &Dnskey<impl AsRef<[u8]>>,
+    ) -> bool {
+        ds.matches_dnskey_with(&self.backend, owner, key)
+            .unwrap_or(false)
+    }
+
+    fn insecure(&mut self, reason: Reason) -> SecurityStatus {
+        self.broken = Some(SecurityStatus::Insecure(reason));
+        SecurityStatus::Insecure(reason)
+    }
+
+    fn bogus(&mut self, reason: Reason) -> SecurityStatus {
+        self.broken = Some(SecurityStatus::Bogus(reason));
+        SecurityStatus::Bogus(reason)
+    }
+
+    fn indeterminate(&self, reason: Reason) -> SecurityStatus {
+        SecurityStatus::Indeterminate(reason)
+    }
+}
+
+/// Copies DNSKEY record data into a vec.
+fn to_vec_key(key: &Dnskey<impl AsRef<[u8]>>) -> Dnskey<Vec<u8>> {
+    // Safety: The data has come from valid record data.
+    unsafe {
+        Dnskey::new_unchecked(
+            key.flags(),
+            key.protocol(),
+            key.algorithm(),
+            key.public_key().as_ref().into(),
+        )
+    }
+}
+
+/// Copies DS record data into a vec.
+fn to_vec_ds(ds: &Ds<impl AsRef<[u8]>>) -> Ds<Vec<u8>> {
+    // Safety: The data has come from valid record data.
+    unsafe {
+        Ds::new_unchecked(
+            ds.key_tag(),
+            ds.algorithm(),
+            ds.digest_type(),
+            ds.digest().as_ref().into(),
+        )
+    }
+}
+
+/// Some data together with the domain name it belongs to.
+type Owned<T> = (Dname<Vec<u8>>, T);
+
+//------------ SecurityStatus ------------------------------------------------
+
+/// The security status of an RRset as defined in RFC 4035, section 4.3.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SecurityStatus {
+    /// There is an unbroken chain of trust from a trust anchor.
+    Secure,
+
+    /// There is proof that no chain of trust exists.
+    Insecure(Reason),
+
+    /// There should be a chain of trust but it is broken.
+    Bogus(Reason),
+
+    /// It could not be determined whether there should be a chain of
+    /// trust.
+    Indeterminate(Reason),
+}
+
+impl SecurityStatus {
+    /// Returns whether the status is secure.
+    pub fn is_secure(self) -> bool {
+        matches!(self, SecurityStatus::Secure)
+    }
+
+    /// Returns whether the status is insecure.
+    pub fn is_insecure(self) -> bool {
+        matches!(self, SecurityStatus::Insecure(_))
+    }
+
+    /// Returns whether the status is bogus.
+    pub fn is_bogus(self) -> bool {
+        matches!(self, SecurityStatus::Bogus(_))
+    }
+
+    /// Returns whether the status is indeterminate.
+    pub fn is_indeterminate(self) -> bool {
+        matches!(self, SecurityStatus::Indeterminate(_))
+    }
+
+    /// Returns the reason for a status other than secure.
+    pub fn reason(self) -> Option<Reason> {
+        match self {
+            SecurityStatus::Secure => None,
+            SecurityStatus::Insecure(reason)
+            | SecurityStatus::Bogus(reason)
+            | SecurityStatus::Indeterminate(reason) => Some(reason),
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for SecurityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SecurityStatus::Secure => f.write_str("secure"),
+            SecurityStatus::Insecure(reason) => {
+                write!(f, "insecure: {}", reason)
+            }
+            SecurityStatus::Bogus(reason) => write!(f, "bogus: {}", reason),
+            SecurityStatus::Indeterminate(reason) => {
+                write!(f, "indeterminate: {}", reason)
+            }
+        }
+    }
+}
+
+//------------ Reason --------------------------------------------------------
+
+/// The reason why an RRset isn’t secure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reason {
+    /// There is no trust anchor to start the chain from.
+    NoTrustAnchor,
+
+    /// The DS RRset for the zone hasn’t been provided.
+    MissingDelegation,
+
+    /// The records are not within the zone reached by the chain.
+    NotInZone,
+
+    /// The zone only uses algorithms that aren’t supported.
+    UnsupportedAlgorithm,
+
+    /// None of the DNSKEY records matches a trust anchor or DS record.
+    NoMatchingKey,
+
+    /// The RRset isn’t signed by any of the trusted keys.
+    MissingSignature,
+
+    /// None of the signatures by trusted keys could be validated.
+    InvalidSignature(ValidationError),
+
+    /// The NSEC or NSEC3 records don’t prove a negative answer.
+    InvalidDenial(DenialError),
+
+    /// The records are below a delegation proven to be unsigned.
+    InsecureDelegation,
+
+    /// The records are covered by a negative trust anchor.
+    NegativeTrustAnchor,
+
+    /// The records needed for validation couldn’t be retrieved.
+    Unavailable,
+}
+
+//--- Display
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Reason::NoTrustAnchor => f.write_str("no trust anchor"),
+            Reason::MissingDelegation => f.write_str("missing DS records"),
+            Reason::NotInZone => f.write_str("records outside of zone"),
+            Reason::UnsupportedAlgorithm => {
+                f.write_str("unsupported algorithms")
+            }
+            Reason::NoMatchingKey => f.write_str("no matching DNSKEY"),
+            Reason::MissingSignature => f.write_str("no valid signature"),
+            Reason::InvalidSignature(err) => err.fmt(f),
+            Reason::InvalidDenial(err) => err.fmt(f),
+            Reason::InsecureDelegation => f.write_str("insecure delegation"),
+            Reason::NegativeTrustAnchor => {
+                f.write_str("negative trust anchor")
+            }
+            Reason::Unavailable => f.write_str("records unavailable"),
+        }
+    }
+}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::Ttl;
+    use crate::rdata::A;
+    use crate::utils::base64;
+    use crate::validate::DnskeyExt;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+
+    struct TestKey {
+        pair: Ed25519KeyPair,
+        dnskey: Dnskey<Vec<u8>>,
+    }
+
+    impl TestKey {
+        fn new(seed: u8, flags: u16) -> Self {
+            let pair =
+                Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+            let dnskey = Dnskey::new(
+                flags,
+                3,
+                SecAlg::Ed25519,
+                pair.public_key().as_ref().into(),
+            )
+            .unwrap();
+            TestKey { pair, dnskey }
+        }
+
+        fn ds(&self, owner: &Name) -> Ds<Vec<u8>> {
+            Ds::new(
+                self.dnskey.key_tag(),
+                SecAlg::Ed25519,
+                DigestAlg::Sha256,
+                self.dnskey
+                    .digest(owner, DigestAlg::Sha256)
+                    .unwrap()
+                    .as_ref()
+                    .into(),
+            )
+            .unwrap()
+        }
+
+        fn sign<D>(
+            &self,
+            signer: &Name,
+            records: &mut [Record<Name, D>],
+        ) -> Rrsig<Vec<u8>, Name>
+        where
+            D: RecordData + CanonicalOrd + ComposeRecordData,
+        {
+            let mut rrsig = Rrsig::new(
+                records[0].rtype(),
+                SecAlg::Ed25519,
+                records[0].owner().rrsig_label_count(),
+                Ttl::from_secs(3600),
+                2000000.into(),
+                1000000.into(),
+                self.dnskey.key_tag(),
+                signer.clone(),
+                Vec::new(),
+            )
+            .unwrap();
+            let mut buf = Vec::new();
+            rrsig.signed_data(&mut buf, records).unwrap();
+            rrsig.set_signature(self.pair.sign(&buf).as_ref().into());
+            rrsig
+        }
+    }
+
+    fn rrset<D: Clone>(owner: &Name, data: &[D]) -> Vec<Record<Name, D>> {
+        data.iter()
+            .map(|data| {
+                Record::new(
+                    owner.clone(),
+                    Class::In,
+                    Ttl::from_secs(3600),
+                    data.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// A signed root zone with a signed delegation to `example.`.
+    struct TestTree {
+        root: Name,
+        root_ksk: TestKey,
+        root_keys: Vec<Record<Name, Dnskey<Vec<u8>>>>,
+        root_sig: Rrsig<Vec<u8>, Name>,
+        ds: Vec<Record<Name, Ds<Vec<u8>>>>,
+        ds_sig: Rrsig<Vec<u8>, Name>,
+        child_keys: Vec<Record<Name, Dnskey<Vec<u8>>>>,
+        child_sig: Rrsig<Vec<u8>, Name>,
+        a: Vec<Record<Name, A>>,
+        a_sig: Rrsig<Vec<u8>, Name>,
+    }
+
+    impl TestTree {
+        fn new() -> Self {
+            let root = Name::root();
+            let child = Name::from_str("example.").unwrap();
+            let root_ksk = TestKey::new(1, 257);
+            let root_zsk = TestKey::new(2, 256);
+            let child_ksk = TestKey::new(3, 257);
+            let child_zsk = TestKey::new(4, 256);
+
+            let mut root_keys = rrset(
+                &root,
+                &[root_ksk.dnskey.clone(), root_zsk.dnskey.clone()],
+            );
+            let root_sig = root_ksk.sign(&root, &mut root_keys);
+            let mut ds = rrset(&child, &[child_ksk.ds(&child)]);
+            let ds_sig = root_zsk.sign(&root, &mut ds);
+            let mut child_keys = rrset(
+                &child,
+                &[child_ksk.dnskey.clone(), child_zsk.dnskey.clone()],
+            );
+            let child_sig = child_ksk.sign(&child, &mut child_keys);
+            let mut a = rrset(
+                &Name::from_str("www.example.").unwrap(),
+                &[A::from_octets(192, 0, 2, 1)],
+            );
+            let a_sig = child_zsk.sign(&child, &mut a);
+            TestTree {
+                root,
+                root_ksk,
+                root_keys,
+                root_sig,
+                ds,
+                ds_sig,
+                child_keys,
+                child_sig,
+                a,
+                a_sig,
+            }
+        }
+
+        fn anchors(&self) -> TrustAnchors {
+            let mut anchors = TrustAnchors::new();
+            anchors.add_ds(self.root.clone(), self.root_ksk.ds(&self.root));
+            anchors
+        }
+    }
+
+    #[test]
+    fn secure_chain() {
+        let mut tree = TestTree::new();
+        let anchors = tree.anchors();
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        assert_eq!(
+            chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]),
+            SecurityStatus::Secure
+        );
+        assert_eq!(chain.zone(), Some(&tree.root));
+        assert_eq!(
+            chain.add_ds(&mut tree.ds, &[tree.ds_sig.clone()]),
+            SecurityStatus::Secure
+        );
+        assert_eq!(
+            chain
+                .add_dnskeys(&mut tree.child_keys, &[tree.child_sig.clone()]),
+            SecurityStatus::Secure
+        );
+        assert_eq!(chain.keys().len(), 2);
+        assert_eq!(
+            chain.validate_rrset(&mut tree.a, &[tree.a_sig.clone()]),
+            SecurityStatus::Secure
+        );
+
+        // The A RRset isn’t signed by the root zone.
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]);
+        assert_eq!(
+            chain.validate_rrset(&mut tree.a, &[tree.a_sig.clone()]),
+            SecurityStatus::Bogus(Reason::MissingSignature)
+        );
+
+        // Skipping the DS RRset breaks the chain.
+        assert_eq!(
+            chain
+                .add_dnskeys(&mut tree.child_keys, &[tree.child_sig.clone()]),
+            SecurityStatus::Indeterminate(Reason::MissingDelegation)
+        );
+    }
+
+    #[test]
+    fn broken_chain() {
+        let mut tree = TestTree::new();
+
+        // No anchor for the zone.
+        let anchors = TrustAnchors::new();
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        assert_eq!(
+            chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]),
+            SecurityStatus::Indeterminate(Reason::NoTrustAnchor)
+        );
+
+        // Expired signatures.
+        let anchors = tree.anchors();
+        let mut chain = ChainValidator::new(&anchors, 2500000.into());
+        assert_eq!(
+            chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]),
+            SecurityStatus::Bogus(Reason::InvalidSignature(
+                ValidationError::Expired
+            ))
+        );
+
+        // A modified RRset is bogus and so is everything after it.
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]);
+        chain.add_ds(&mut tree.ds, &[tree.ds_sig.clone()]);
+        tree.child_keys.pop();
+        let status = chain
+            .add_dnskeys(&mut tree.child_keys, &[tree.child_sig.clone()]);
+        assert!(status.is_bogus());
+        assert_eq!(
+            chain.validate_rrset(&mut tree.a, &[tree.a_sig.clone()]),
+            status
+        );
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        let mut tree = TestTree::new();
+        let anchors = tree.anchors();
+        let mut ds = rrset(
+            &Name::from_str("example.").unwrap(),
+            &[Ds::new(1, SecAlg::Dsa, DigestAlg::Sha256, vec![0; 32])
+                .unwrap()],
+        );
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]);
+
+        // We can’t sign with the root ZSK here, so fake a validated DS
+        // RRset.
+        chain.delegation = Some((
+            ds[0].owner().clone(),
+            ds.iter_mut().map(|rr| rr.data().clone()).collect(),
+        ));
+        assert_eq!(
+            chain
+                .add_dnskeys(&mut tree.child_keys, &[tree.child_sig.clone()]),
+            SecurityStatus::Insecure(Reason::UnsupportedAlgorithm)
+        );
+        assert_eq!(
+            chain.validate_rrset(&mut tree.a, &[tree.a_sig.clone()]),
+            SecurityStatus::Insecure(Reason::UnsupportedAlgorithm)
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        let anchors = TrustAnchors::iana_root();
+        let root = Name::root();
+        assert_eq!(
+            anchors.closest(&Name::from_str("www.example.").unwrap()),
+            Some(&root)
+        );
+        assert_eq!(
+            anchors.ds(&root).next().unwrap().digest().as_slice(),
+            base64::decode::<Vec<u8>>(
+                "4G1EuAuPHTmpXAsNfGXQhFjogECbvGg0VxBCN8f47I0="
+            )
+            .unwrap()
+            .as_slice()
+        );
+
+        let mut anchors = anchors;
+        let example = Name::from_str("example.").unwrap();
+        anchors.add_dnskey(example.clone(), TestKey::new(1, 257).dnskey);
+        assert_eq!(
+            anchors.closest(&Name::from_str("www.Example.").unwrap()),
+            Some(&example)
+        );
+        assert!(!anchors.contains(&Name::from_str("com.").unwrap()));
+    }
+
+    #[test]
+    fn negative_anchors() {
+        let mut tree = TestTree::new();
+        let mut anchors = tree.anchors();
+        let example = Name::from_str("example.").unwrap();
+        anchors.add_negative(example.clone(), 1600000.into());
+        assert!(anchors.is_negative(&example, 1500000.into()));
+        assert!(anchors.is_negative(
+            &Name::from_str("www.example.").unwrap(),
+            1500000.into()
+        ));
+        assert!(!anchors.is_negative(&tree.root, 1500000.into()));
+        assert!(!anchors.is_negative(&example, 1600000.into()));
+
+        // The chain becomes insecure at the delegation.
+        let mut chain = ChainValidator::new(&anchors, 1500000.into());
+        assert_eq!(
+            chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]),
+            SecurityStatus::Secure
+        );
+        assert_eq!(
+            chain.add_ds(&mut tree.ds, &[tree.ds_sig.clone()]),
+            SecurityStatus::Insecure(Reason::NegativeTrustAnchor)
+        );
+        assert_eq!(
+            chain.validate_rrset(&mut tree.a, &[tree.a_sig.clone()]),
+            SecurityStatus::Insecure(Reason::NegativeTrustAnchor)
+        );
+
+        // Expired anchors are ignored and can be removed.
+        let mut chain = ChainValidator::new(&anchors, 1700000.into());
+        chain.add_dnskeys(&mut tree.root_keys, &[tree.root_sig.clone()]);
+        assert_eq!(
+            chain.add_ds(&mut tree.ds, &[tree.ds_sig.clone()]),
+            SecurityStatus::Secure
+        );
+        anchors.remove_expired_negative(1700000.into());
+        assert_eq!(anchors.negative().count(), 0);
+
+        // A positive anchor below a negative one takes precedence.
+        anchors.add_negative(tree.root.clone(), 1600000.into());
+        anchors.add_dnskey(example.clone(), TestKey::new(3, 257).dnskey);
+        assert!(anchors
+            .is_negative(&Name::from_str("com.").unwrap(), 1500000.into()));
+        assert!(!anchors.is_negative(&example, 1500000.into()));
+        assert!(anchors.remove_negative(&tree.root));
+        assert!(!anchors.remove_negative(&tree.root));
+    }
+}
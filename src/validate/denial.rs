@@ -0,0 +1,1016 @@
+//! Authenticated denial of existence.
+//!
+//! Negative answers in signed zones are proven by NSEC or NSEC3 records.
+//! An NSEC record states that no names exist between its owner name and
+//! its next name in canonical order and which record types exist at its
+//! owner name. NSEC3 records do the same for hashed owner names.
+//!
+//! This module checks whether a set of such records indeed proves that a
+//! name doesn’t exist ([RFC 4035, section 5.4], [RFC 5155, section 8]).
+//! [`NsecDenial`] does this for NSEC records and [`Nsec3Denial`] for NSEC3
+//! records. Both assume that the records have already been validated, for
+//! instance via
+//! [`ChainValidator::validate_rrset`][super::chain::ChainValidator::validate_rrset].
+//!
+//! [RFC 4035, section 5.4]: https://tools.ietf.org/html/rfc4035#section-5.4
+//! [RFC 5155, section 8]: https://tools.ietf.org/html/rfc5155#section-8
+
+use crate::base::iana::Rtype;
+use crate::base::name::{Dname, ToDname};
+use crate::base::record::Record;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::crypto::{Backend, Nsec3HashError, Nsec3Hasher};
+use crate::rdata::dnssec::RtypeBitmap;
+use crate::rdata::{Nsec, Nsec3};
+use crate::utils::base32;
+use core::cmp::Ordering;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ NsecDenial ----------------------------------------------------
+
+/// Checks denial of existence via NSEC records.
+///
+/// All records are expected to be from the same zone and names passed to
+/// the methods to be within that zone.
+#[derive(Clone, Copy)]
+pub struct NsecDenial<'a, N, Octs, Name> {
+    records: &'a [Record<N, Nsec<Octs, Name>>],
+}
+
+impl<'a, N, Octs, Name> NsecDenial<'a, N, Octs, Name>
+where
+    N: ToDname,
+    Octs: AsRef<[u8]>,
+    Name: ToDname,
+{
+    /// Creates a new value using the given NSEC records.
+    pub fn new(records: &'a [Record<N, Nsec<Octs, Name>>]) -> Self {
+        NsecDenial { records }
+    }
+
+    /// Checks that the records prove that `qname` doesn’t exist.
+    ///
+    /// This requires an NSEC record covering `qname` and one covering the
+    /// wildcard at the closest encloser of `qname`.
+    pub fn nxdomain(
+        &self,
+        qname: &impl ToDname,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        if self.matching(&qname).is_some() {
+            return Err(DenialError::NameExists);
+        }
+        let closest = self.closest_encloser(&qname)?;
+        if closest.name_eq(&qname) {
+            // The next name is below qname, so it is an empty non-terminal.
+            return Err(DenialError::NameExists);
+        }
+        if self.covering(&wildcard(&closest))?.is_none() {
+            return Err(DenialError::WildcardNotDenied);
+        }
+        Ok(Denial::Proven)
+    }
+
+    /// Checks that the records prove that `qname` has no `qtype` records.
+    ///
+    /// This is the case if there is an NSEC record for `qname` that
+    /// doesn’t list `qtype` or CNAME. If `qname` is an empty non-terminal,
+    /// an NSEC record covering it is enough. Otherwise, the name could have
+    /// been synthesized from a wildcard, so there needs to be proof that
+    /// `qname` doesn’t exist and an NSEC record for the wildcard that
+    /// doesn’t list the type.
+    pub fn nodata(
+        &self,
+        qname: &impl ToDname,
+        qtype: Rtype,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        if let Some(record) = self.matching(&qname) {
+            return check_types(record.data().types(), qtype, &qname);
+        }
+        let covering = match self.covering(&qname)? {
+            Some(record) => record,
+            None => return Err(DenialError::NameNotDenied),
+        };
+
+        // An empty non-terminal: the next name is below qname.
+        if covering.data().next_name().ends_with(&qname) {
+            return Ok(Denial::Proven);
+        }
+
+        let closest = closest_encloser(
+            &qname,
+            covering.owner(),
+            covering.data().next_name(),
+        );
+        let wildcard = wildcard(&closest);
+        match self.matching(&wildcard) {
+            Some(record) => {
+                check_types(record.data().types(), qtype, &wildcard)
+            }
+            None => Err(DenialError::WildcardNotDenied),
+        }
+    }
+
+    /// Checks that a wildcard answer for `qname` is justified.
+    ///
+    /// An answer synthesized from a wildcard requires proof that `qname`
+    /// itself doesn’t exist. The `rrsig_labels` argument is the value of
+    /// the labels field of the answer’s RRSIG record.
+    pub fn wildcard_answer(
+        &self,
+        qname: &impl ToDname,
+        rrsig_labels: u8,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        if next_closer(&qname, rrsig_labels).is_none() {
+            return Err(DenialError::NotWildcard);
+        }
+        if self.covering(&qname)?.is_none() {
+            return Err(DenialError::NameNotDenied);
+        }
+        Ok(Denial::Proven)
+    }
+
+    /// Checks that the records prove an insecure delegation at `name`.
+    ///
+    /// This requires an NSEC record for `name` that lists NS but neither
+    /// DS nor SOA as per [RFC 4035, section 5.2].
+    ///
+    /// [RFC 4035, section 5.2]: https://tools.ietf.org/html/rfc4035#section-5.2
+    pub fn insecure_delegation(
+        &self,
+        name: &impl ToDname,
+    ) -> Result<Denial, DenialError> {
+        match self.matching(&name.to_vec()) {
+            Some(record) => check_delegation(record.data().types()),
+            None => Err(DenialError::NameNotDenied),
+        }
+    }
+
+    /// Returns the closest encloser of a name that doesn’t exist.
+    fn closest_encloser(
+        &self,
+        qname: &Dname<Vec<u8>>,
+    ) -> Result<Dname<Vec<u8>>, DenialError> {
+        let record =
+            self.covering(qname)?.ok_or(DenialError::NameNotDenied)?;
+        Ok(closest_encloser(
+            qname,
+            record.owner(),
+            record.data().next_name(),
+        ))
+    }
+
+    /// Returns the record owned by `name`.
+    fn matching(
+        &self,
+        name: &Dname<Vec<u8>>,
+    ) -> Option<&'a Record<N, Nsec<Octs, Name>>> {
+        self.records
+            .iter()
+            .find(|record| record.owner().name_eq(name))
+    }
+
+    /// Returns the record covering `name`.
+    ///
+    /// A record owned by a delegation or DNAME at an ancestor of `name` is
+    /// from the parent side of a zone cut and can’t deny anything below
+    /// it.
+    fn covering(
+        &self,
+        name: &Dname<Vec<u8>>,
+    ) -> Result<Option<&'a NsecRecord<N, Octs, Name>>, DenialError> {
+        let record = match self.records.iter().find(|record| {
+            covers(
+                record.owner().name_cmp(name),
+                name.name_cmp(record.data().next_name()),
+                record.owner().name_cmp(record.data().next_name()),
+            )
+        }) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        if name.ends_with(record.owner())
+            && !name.name_eq(record.owner())
+            && is_cut(record.data().types())
+        {
+            return Err(DenialError::WrongSideOfCut);
+        }
+        Ok(Some(record))
+    }
+}
+
+/// An NSEC record.
+type NsecRecord<N, Octs, Name> = Record<N, Nsec<Octs, Name>>;
+
+//------------ Nsec3Denial ---------------------------------------------------
+
+/// Checks denial of existence via NSEC3 records.
+///
+/// The records must be from the zone with the apex given upon creation.
+/// Hashes are calculated by an [`Nsec3Hasher`] using the crypto backend
+/// `B`.
+///
+/// As recommended by [RFC 9276], records with more additional iterations
+/// than the hasher’s maximum are not used and result in
+/// [`DenialError::TooManyIterations`]. Such answers should be treated as
+/// insecure.
+///
+/// [RFC 9276]: https://tools.ietf.org/html/rfc9276
+#[derive(Clone)]
+pub struct Nsec3Denial<'a, N, Octs, B> {
+    /// The apex of the zone.
+    zone: Dname<Vec<u8>>,
+
+    /// The NSEC3 records.
+    records: &'a [Record<N, Nsec3<Octs>>],
+
+    /// The hasher for owner names.
+    hasher: Nsec3Hasher<B>,
+}
+
+#[cfg(feature = "ring")]
+impl<'a, N, Octs> Nsec3Denial<'a, N, Octs, RingBackend>
+where
+    N: ToDname,
+    Octs: AsRef<[u8]>,
+{
+    /// Creates a new value for the given zone and NSEC3 records.
+    ///
+    /// Hashes are calculated using the [`RingBackend`].
+    pub fn new(
+        zone: &impl ToDname,
+        records: &'a [Record<N, Nsec3<Octs>>],
+    ) -> Self {
+        Self::with_backend(zone, records, RingBackend)
+    }
+}
+
+impl<'a, N, Octs, B> Nsec3Denial<'a, N, Octs, B>
+where
+    N: ToDname,
+    Octs: AsRef<[u8]>,
+    B: Backend,
+{
+    /// Creates a new value using the given backend.
+    pub fn with_backend(
+        zone: &impl ToDname,
+        records: &'a [Record<N, Nsec3<Octs>>],
+        backend: B,
+    ) -> Self {
+        Nsec3Denial {
+            zone: zone.to_vec(),
+            records,
+            hasher: Nsec3Hasher::with_backend(backend),
+        }
+    }
+
+    /// Sets the maximum number of additional hash iterations.
+    ///
+    /// The default is [`NSEC3_MAX_ITERATIONS`][crate::crypto::NSEC3_MAX_ITERATIONS].
+    pub fn set_max_iterations(&mut self, max_iterations: u16) {
+        self.hasher.set_max_iterations(max_iterations)
+    }
+
+    /// Checks that the records prove that `qname` doesn’t exist.
+    ///
+    /// This requires a closest encloser proof for `qname` and a record
+    /// covering the wildcard at the closest encloser. If the record
+    /// covering the next closer name has the opt-out flag set, the name may
+    /// exist as an unsigned delegation and the result is
+    /// [`Denial::OptOut`].
+    pub fn nxdomain(
+        &self,
+        qname: &impl ToDname,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        let proof = self.closest_encloser(&qname)?;
+        if self.covering(&wildcard(&proof.closest))?.is_none() {
+            return Err(DenialError::WildcardNotDenied);
+        }
+        Ok(proof.denial())
+    }
+
+    /// Checks that the records prove that `qname` has no `qtype` records.
+    ///
+    /// This is the case if there is an NSEC3 record matching `qname` that
+    /// doesn’t list `qtype` or CNAME. For DS queries, a closest encloser
+    /// proof where the next closer name is covered by an opt-out record
+    /// results in [`Denial::OptOut`]. Otherwise, the name could have been
+    /// synthesized from a wildcard, so there needs to be a closest encloser
+    /// proof and a record matching the wildcard that doesn’t list the type.
+    pub fn nodata(
+        &self,
+        qname: &impl ToDname,
+        qtype: Rtype,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        if let Some(record) = self.matching(&qname)? {
+            return check_types(record.data().types(), qtype, &qname);
+        }
+        let proof = self.closest_encloser(&qname)?;
+        if qtype == Rtype::Ds {
+            if proof.opt_out {
+                return Ok(Denial::OptOut);
+            }
+            return Err(DenialError::NameNotDenied);
+        }
+        let wildcard = wildcard(&proof.closest);
+        match self.matching(&wildcard)? {
+            Some(record) => {
+                check_types(record.data().types(), qtype, &wildcard)
+            }
+            None => Err(DenialError::WildcardNotDenied),
+        }
+    }
+
+    /// Checks that a wildcard answer for `qname` is justified.
+    ///
+    /// This requires a record covering the next closer name, i.e., the
+    /// name one label longer than the wildcard’s closest encloser. The
+    /// `rrsig_labels` argument is the value of the labels field of the
+    /// answer’s RRSIG record.
+    pub fn wildcard_answer(
+        &self,
+        qname: &impl ToDname,
+        rrsig_labels: u8,
+    ) -> Result<Denial, DenialError> {
+        let qname = qname.to_vec();
+        let next = next_closer(&qname, rrsig_labels)
+            .ok_or(DenialError::NotWildcard)?;
+        match self.covering(&next)? {
+            Some(record) if record.data().opt_out() => Ok(Denial::OptOut),
+            Some(_) => Ok(Denial::Proven),
+            None => Err(DenialError::NameNotDenied),
+        }
+    }
+
+    /// Checks that the records prove an insecure delegation at `name`.
+    ///
+    /// This requires an NSEC3 record matching `name` that lists NS but
+    /// neither DS nor SOA. If there is no such record, a closest encloser
+    /// proof where the next closer name is covered by an opt-out record
+    /// results in [`Denial::OptOut`] as per [RFC 5155, section 8.9].
+    ///
+    /// [RFC 5155, section 8.9]: https://tools.ietf.org/html/rfc5155#section-8.9
+    pub fn insecure_delegation(
+        &self,
+        name: &impl ToDname,
+    ) -> Result<Denial, DenialError> {
+        let name = name.to_vec();
+        if let Some(record) = self.matching(&name)? {
+            return check_delegation(record.data().types());
+        }
+        if self.closest_encloser(&name)?.opt_out {
+            Ok(Denial::OptOut)
+        } else {
+            Err(DenialError::NameNotDenied)
+        }
+    }
+
+    /// Performs the closest encloser proof for `qname`.
+    ///
+    /// See [RFC 5155, section 8.3].
+    ///
+    /// [RFC 5155, section 8.3]: https://tools.ietf.org/html/rfc5155#section-8.3
+    fn closest_encloser(
+        &self,
+        qname: &Dname<Vec<u8>>,
+    ) -> Result<EncloserProof, DenialError> {
+        if !qname.ends_with(&self.zone) {
+            return Err(DenialError::NotInZone);
+        }
+        let mut next = None;
+        for name in qname.iter_suffixes() {
+            let name = name.to_vec();
+            if let Some(record) = self.matching(&name)? {
+                let next = match next {
+                    Some(next) => next,
+                    None => return Err(DenialError::NameExists),
+                };
+                if is_cut(record.data().types()) {
+                    return Err(DenialError::WrongSideOfCut);
+                }
+                return match self.covering(&next)? {
+                    Some(record) => Ok(EncloserProof {
+                        closest: name,
+                        opt_out: record.data().opt_out(),
+                    }),
+                    None => Err(DenialError::NameNotDenied),
+                };
+            }
+            if name.name_eq(&self.zone) {
+                break;
+            }
+            next = Some(name);
+        }
+        Err(DenialError::NoClosestEncloser)
+    }
+
+    /// Returns the record matching `name`.
+    fn matching(
+        &self,
+        name: &Dname<Vec<u8>>,
+    ) -> Result<Option<&'a Record<N, Nsec3<Octs>>>, DenialError> {
+        for record in self.records {
+            let owner = match self.owner_hash(record.owner()) {
+                Some(owner) => owner,
+                None => continue,
+            };
+            if self.hash(name, record.data())? == owner {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the record covering `name`.
+    fn covering(
+        &self,
+        name: &Dname<Vec<u8>>,
+    ) -> Result<Option<&'a Record<N, Nsec3<Octs>>>, DenialError> {
+        for record in self.records {
+            let owner = match self.owner_hash(record.owner()) {
+                Some(owner) => owner,
+                None => continue,
+            };
+            let hash = self.hash(name, record.data())?;
+            let next = record.data().next_owner().as_ref();
+            if covers(
+                owner.as_slice().cmp(&hash),
+                hash.as_slice().cmp(next),
+                owner.as_slice().cmp(next),
+            ) {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the hash from the owner name of a record.
+    ///
+    /// Returns `None` if the owner name isn’t a hashed name directly below
+    /// the zone’s apex.
+    fn owner_hash(&self, owner: &N) -> Option<Vec<u8>> {
+        let owner = owner.to_vec();
+        let (label, parent) = owner.split_first()?;
+        if !parent.name_eq(&self.zone) {
+            return None;
+        }
+        let label = core::str::from_utf8(label.as_slice()).ok()?;
+        base32::decode_hex(label).ok()
+    }
+
+    /// Calculates the hash of `name` using the parameters of `nsec3`.
+    fn hash(
+        &self,
+        name: &Dname<Vec<u8>>,
+        nsec3: &Nsec3<Octs>,
+    ) -> Result<Vec<u8>, DenialError> {
+        self.hasher
+            .hash(
+                name,
+                nsec3.hash_algorithm(),
+                nsec3.iterations(),
+                nsec3.salt().as_slice(),
+            )
+            .map_err(|err| match err {
+                Nsec3HashError::UnsupportedAlgorithm => {
+                    DenialError::UnsupportedHash
+                }
+                Nsec3HashError::TooManyIterations => {
+                    DenialError::TooManyIterations
+                }
+            })
+    }
+}
+
+//------------ EncloserProof -------------------------------------------------
+
+/// The result of a successful closest encloser proof.
+struct EncloserProof {
+    /// The closest encloser.
+    closest: Dname<Vec<u8>>,
+
+    /// Whether the record covering the next closer name is opt-out.
+    opt_out: bool,
+}
+
+impl EncloserProof {
+    fn denial(&self) -> Denial {
+        if self.opt_out {
+            Denial::OptOut
+        } else {
+            Denial::Proven
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether a record covers a name.
+///
+/// The arguments are the orderings between the record’s owner and the
+/// name, the name and the record’s next name, and the owner and the next
+/// name. The last record in a zone has the first name as its next name,
+/// so in this case the name is covered if it is after the owner or before
+/// the next name.
+fn covers(
+    owner_name: Ordering,
+    name_next: Ordering,
+    owner_next: Ordering,
+) -> bool {
+    match owner_next {
+        Ordering::Less => {
+            owner_name == Ordering::Less && name_next == Ordering::Less
+        }
+        _ => owner_name == Ordering::Less || name_next == Ordering::Less,
+    }
+}
+
+/// Checks that the types of a record proves `qtype` doesn’t exist.
+fn check_types<Octs: AsRef<[u8]>>(
+    types: &RtypeBitmap<Octs>,
+    qtype: Rtype,
+    name: &Dname<Vec<u8>>,
+) -> Result<Denial, DenialError> {
+    if types.contains(qtype) || types.contains(Rtype::Cname) {
+        return Err(DenialError::TypeExists);
+    }
+    if qtype == Rtype::Ds {
+        // A record from the child side of a zone cut can’t prove the
+        // absence of the DS records kept by the parent.
+        if types.contains(Rtype::Soa) && !name.is_root() {
+            return Err(DenialError::WrongSideOfCut);
+        }
+    } else if types.contains(Rtype::Ns) && !types.contains(Rtype::Soa) {
+        // A record from the parent side of a zone cut can’t prove the
+        // absence of records in the child zone.
+        return Err(DenialError::WrongSideOfCut);
+    }
+    Ok(Denial::Proven)
+}
+
+/// Returns whether the types of a record show a zone cut below its owner.
+///
+/// This is the case for delegations, i.e., NS records without an SOA
+/// record, and for DNAME records.
+fn is_cut<Octs: AsRef<[u8]>>(types: &RtypeBitmap<Octs>) -> bool {
+    types.contains(Rtype::Dname)
+        || (types.contains(Rtype::Ns) && !types.contains(Rtype::Soa))
+}
+
+/// Checks that the types of a record prove an insecure delegation.
+fn check_delegation<Octs: AsRef<[u8]>>(
+    types: &RtypeBitmap<Octs>,
+) -> Result<Denial, DenialError> {
+    if !types.contains(Rtype::Ns) || types.contains(Rtype::Soa) {
+        return Err(DenialError::NotDelegation);
+    }
+    if types.contains(Rtype::Ds) {
+        return Err(DenialError::TypeExists);
+    }
+    Ok(Denial::Proven)
+}
+
+/// Returns the closest encloser of `qname` from a covering NSEC record.
+///
+/// This is the longer of the common ancestors of `qname` with the owner
+/// and the next name of the record.
+pub(crate) fn closest_encloser(
+    qname: &Dname<Vec<u8>>,
+    owner: &impl ToDname,
+    next: &impl ToDname,
+) -> Dname<Vec<u8>> {
+    let labels = common_labels(qname, owner).max(common_labels(qname, next));
+    qname
+        .iter_suffixes()
+        .nth(qname.label_count() - labels)
+        .expect("missing suffix")
+        .to_vec()
+}
+
+/// Returns the number of labels two names have in common at their end.
+///
+/// The root label is included in the count.
+fn common_labels(left: &impl ToDname, right: &impl ToDname) -> usize {
+    left.iter_labels()
+        .rev()
+        .zip(right.iter_labels().rev())
+        .take_while(|(left, right)| left == right)
+        .count()
+}
+
+/// Returns the next closer name for a wildcard expansion.
+///
+/// This is the suffix of `qname` with one more label than the closest
+/// encloser, i.e., one more than `rrsig_labels` non-root labels. Returns
+/// `None` if `qname` can’t have been expanded from a wildcard.
+fn next_closer(
+    qname: &Dname<Vec<u8>>,
+    rrsig_labels: u8,
+) -> Option<Dname<Vec<u8>>> {
+    let labels = usize::from(rrsig_labels) + 2;
+    let count = qname.label_count();
+    if count < labels {
+        return None;
+    }
+    qname
+        .iter_suffixes()
+        .nth(count - labels)
+        .map(|name| name.to_vec())
+}
+
+/// Returns the wildcard name directly below `name`.
+pub(crate) fn wildcard(name: &Dname<Vec<u8>>) -> Dname<Vec<u8>> {
+    let mut res = Vec::with_capacity(name.len() + 2);
+    res.extend_from_slice(b"\x01*");
+    res.extend_from_slice(name.as_slice());
+    Dname::from_octets(res).expect("long wildcard name")
+}
+
+//------------ Denial --------------------------------------------------------
+
+/// The outcome of a successful denial of existence check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Denial {
+    /// The name or type doesn’t exist.
+    Proven,
+
+    /// The name may exist as an unsigned delegation.
+    ///
+    /// The NSEC3 record covering the name has the opt-out flag set, so
+    /// there may be an insecure delegation for the name. Any answer for
+    /// the name is insecure.
+    OptOut,
+}
+
+//============ Error Types ===================================================
+
+//------------ DenialError ---------------------------------------------------
+
+/// The records don’t prove the denial of existence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DenialError {
+    /// There is a record for the name, so it exists.
+    NameExists,
+
+    /// No record proves that the name doesn’t exist.
+    NameNotDenied,
+
+    /// The record for the name says that the type exists.
+    TypeExists,
+
+    /// No record proves that the wildcard doesn’t exist.
+    WildcardNotDenied,
+
+    /// The closest encloser proof couldn’t be made.
+    NoClosestEncloser,
+
+    /// The record is from the wrong side of a zone cut.
+    WrongSideOfCut,
+
+    /// The name can’t have been expanded from a wildcard.
+    NotWildcard,
+
+    /// The name isn’t a delegation.
+    NotDelegation,
+
+    /// The name isn’t within the zone.
+    NotInZone,
+
+    /// The NSEC3 hash algorithm isn’t supported.
+    UnsupportedHash,
+
+    /// The NSEC3 records use too many hash iterations.
+    TooManyIterations,
+}
+
+//--- Display and Error
+
+impl fmt::Display for DenialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DenialError::NameExists => "name exists",
+            DenialError::NameNotDenied => "name not denied",
+            DenialError::TypeExists => "type exists",
+            DenialError::WildcardNotDenied => "wildcard not denied",
+            DenialError::NoClosestEncloser => "no closest encloser",
+            DenialError::WrongSideOfCut => "record from wrong side of cut",
+            DenialError::NotWildcard => "not a wildcard expansion",
+            DenialError::NotDelegation => "not a delegation",
+            DenialError::NotInZone => "name not in zone",
+            DenialError::UnsupportedHash => "unsupported NSEC3 hash",
+            DenialError::TooManyIterations => "too many NSEC3 iterations",
+        })
+    }
+}
+
+impl error::Error for DenialError {}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Nsec3HashAlg};
+    use crate::base::Ttl;
+    use crate::rdata::dnssec::RtypeBitmapBuilder;
+    use crate::rdata::nsec3::{Nsec3Salt, OwnerHash};
+    use std::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn types(types: &[Rtype]) -> RtypeBitmap<Vec<u8>> {
+        let mut builder = RtypeBitmapBuilder::new_vec();
+        for &rtype in types {
+            builder.add(rtype).unwrap()
+        }
+        builder.finalize()
+    }
+
+    /// The names and types of a test zone.
+    const ZONE: &[(&str, &[Rtype])] = &[
+        ("example.", &[Rtype::Soa, Rtype::Ns]),
+        ("a.example.", &[Rtype::A]),
+        ("w.example.", &[]),
+        ("*.w.example.", &[Rtype::Mx]),
+        ("x.example.", &[Rtype::A]),
+    ];
+
+    fn nsec_zone() -> Vec<Record<Name, Nsec<Vec<u8>, Name>>> {
+        // The empty non-terminal w.example. doesn’t get an NSEC record.
+        let names: Vec<_> = ZONE
+            .iter()
+            .filter(|(_, rtypes)| !rtypes.is_empty())
+            .collect();
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, (owner, rtypes))| {
+                let next = names[(i + 1) % names.len()].0;
+                Record::new(
+                    name(owner),
+                    Class::In,
+                    Ttl::from_secs(3600),
+                    Nsec::new(name(next), types(rtypes)),
+                )
+            })
+            .collect()
+    }
+
+    fn nsec3_zone(opt_out: bool) -> Vec<Record<Name, Nsec3<Vec<u8>>>> {
+        let apex = name("example.");
+        let salt =
+            Nsec3Salt::from_octets(vec![0xaa, 0xbb, 0xcc, 0xdd]).unwrap();
+        let params = Nsec3::new(
+            Nsec3HashAlg::Sha1,
+            0,
+            12,
+            salt.clone(),
+            OwnerHash::from_octets(Vec::new()).unwrap(),
+            types(&[]),
+        );
+        let empty: Vec<Record<Name, Nsec3<Vec<u8>>>> = Vec::new();
+        let denial = Nsec3Denial::new(&apex, &empty);
+        let mut hashes: Vec<_> = ZONE
+            .iter()
+            .map(|(owner, rtypes)| {
+                (denial.hash(&name(owner), &params).unwrap(), *rtypes)
+            })
+            .collect();
+        hashes.sort();
+        hashes
+            .iter()
+            .enumerate()
+            .map(|(i, (hash, rtypes))| {
+                let next = hashes[(i + 1) % hashes.len()].0.clone();
+                let owner =
+                    format!("{}.example.", base32::encode_string_hex(hash));
+                Record::new(
+                    name(&owner),
+                    Class::In,
+                    Ttl::from_secs(3600),
+                    Nsec3::new(
+                        Nsec3HashAlg::Sha1,
+                        u8::from(opt_out),
+                        12,
+                        salt.clone(),
+                        OwnerHash::from_octets(next).unwrap(),
+                        types(rtypes),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nsec3_hash() {
+        // From RFC 5155, appendix A.
+        let apex = name("example.");
+        let records = nsec3_zone(false);
+        let denial = Nsec3Denial::new(&apex, &records);
+        assert_eq!(
+            base32::encode_string_hex(
+                &denial.hash(&apex, records[0].data()).unwrap()
+            ),
+            "0P9MHAVEQVM6T7VBL5LOP2U3T2RP3TOM"
+        );
+        assert_eq!(
+            base32::encode_string_hex(
+                &denial.hash(&name("A.Example."), records[0].data()).unwrap()
+            ),
+            "35MTHGPGCU1QG68FAB165KLNSNK3DPVL"
+        );
+
+        // Records with more iterations than allowed are rejected.
+        let mut denial = denial;
+        denial.set_max_iterations(10);
+        assert_eq!(
+            denial.nxdomain(&name("b.example.")),
+            Err(DenialError::TooManyIterations)
+        );
+    }
+
+    #[test]
+    fn nsec() {
+        let records = nsec_zone();
+        let denial = NsecDenial::new(&records);
+
+        assert_eq!(denial.nxdomain(&name("b.example.")), Ok(Denial::Proven));
+        assert_eq!(
+            denial.nxdomain(&name("a.example.")),
+            Err(DenialError::NameExists)
+        );
+        assert_eq!(
+            denial.nxdomain(&name("w.example.")),
+            Err(DenialError::NameExists)
+        );
+        assert_eq!(
+            denial.nodata(&name("a.example."), Rtype::Mx),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("a.example."), Rtype::A),
+            Err(DenialError::TypeExists)
+        );
+
+        // Empty non-terminal.
+        assert_eq!(
+            denial.nodata(&name("w.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+
+        // Wildcards.
+        assert_eq!(
+            denial.wildcard_answer(&name("z.w.example."), 2),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.wildcard_answer(&name("z.w.example."), 3),
+            Err(DenialError::NotWildcard)
+        );
+        assert_eq!(
+            denial.nodata(&name("c.w.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("c.w.example."), Rtype::Mx),
+            Err(DenialError::TypeExists)
+        );
+
+        // Missing the record for the wildcard.
+        let records: Vec<_> = nsec_zone()
+            .into_iter()
+            .filter(|record| !record.owner().name_eq(&name("example.")))
+            .collect();
+        assert_eq!(
+            NsecDenial::new(&records).nxdomain(&name("b.example.")),
+            Err(DenialError::WildcardNotDenied)
+        );
+
+        // Delegations.
+        assert_eq!(
+            denial.insecure_delegation(&name("a.example.")),
+            Err(DenialError::NotDelegation)
+        );
+        assert_eq!(
+            denial.insecure_delegation(&name("example.")),
+            Err(DenialError::NotDelegation)
+        );
+        let records = [Record::new(
+            name("d.example."),
+            Class::In,
+            Ttl::from_secs(3600),
+            Nsec::new(
+                name("x.example."),
+                types(&[Rtype::Ns, Rtype::Rrsig, Rtype::Nsec]),
+            ),
+        )];
+        assert_eq!(
+            NsecDenial::new(&records)
+                .insecure_delegation(&name("d.example.")),
+            Ok(Denial::Proven)
+        );
+
+        // The parent-side record at a delegation can’t deny names or
+        // types below the cut.
+        let denial = NsecDenial::new(&records);
+        assert_eq!(
+            denial.nxdomain(&name("www.d.example.")),
+            Err(DenialError::WrongSideOfCut)
+        );
+        assert_eq!(
+            denial.nodata(&name("www.d.example."), Rtype::A),
+            Err(DenialError::WrongSideOfCut)
+        );
+        assert_eq!(
+            denial.wildcard_answer(&name("www.d.example."), 2),
+            Err(DenialError::WrongSideOfCut)
+        );
+        let records = [Record::new(
+            name("d.example."),
+            Class::In,
+            Ttl::from_secs(3600),
+            Nsec::new(
+                name("x.example."),
+                types(&[Rtype::Dname, Rtype::Rrsig, Rtype::Nsec]),
+            ),
+        )];
+        assert_eq!(
+            NsecDenial::new(&records).nxdomain(&name("www.d.example.")),
+            Err(DenialError::WrongSideOfCut)
+        );
+    }
+
+    #[test]
+    fn nsec3() {
+        let apex = name("example.");
+        let records = nsec3_zone(false);
+        let denial = Nsec3Denial::new(&apex, &records);
+
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.")),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nxdomain(&name("a.example.")),
+            Err(DenialError::NameExists)
+        );
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.org.")),
+            Err(DenialError::NotInZone)
+        );
+        assert_eq!(
+            denial.nodata(&name("a.example."), Rtype::Mx),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("a.example."), Rtype::A),
+            Err(DenialError::TypeExists)
+        );
+
+        // Empty non-terminal.
+        assert_eq!(
+            denial.nodata(&name("w.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+
+        // Wildcards.
+        assert_eq!(
+            denial.wildcard_answer(&name("z.w.example."), 2),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("c.w.example."), Rtype::A),
+            Ok(Denial::Proven)
+        );
+        assert_eq!(
+            denial.nodata(&name("c.w.example."), Rtype::Mx),
+            Err(DenialError::TypeExists)
+        );
+
+        // No DS without opt-out needs a matching record.
+        assert_eq!(
+            denial.nodata(&name("sub.example."), Rtype::Ds),
+            Err(DenialError::NameNotDenied)
+        );
+        let records = nsec3_zone(true);
+        let denial = Nsec3Denial::new(&apex, &records);
+        assert_eq!(
+            denial.nodata(&name("sub.example."), Rtype::Ds),
+            Ok(Denial::OptOut)
+        );
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.")),
+            Ok(Denial::OptOut)
+        );
+    }
+}
@@ -1,21 +1,59 @@
 //! DNSSEC validation.
 //!
 //! **This module is experimental and likely to change significantly.**
+//!
+//! The module provides extension traits for the DNSSEC record types. The
+//! [`RrsigExt`] trait implements the steps of verifying an RRSIG record
+//! over an RRset: constructing the signed data in canonical form,
+//! including the handling of expanded wildcards, checking the validity
+//! period of the signature, and matching the signature with a DNSKEY
+//! record. The complete process is available through
+//! [`RrsigExt::verify_rrset`].
+//!
+//! The cryptographic operations themselves are performed by a backend
+//! implementing [`Backend`][crate::crypto::Backend]. With the `ring`
+//! feature, convenience functions and constructors using the ring backend
+//! are available. Otherwise a backend has to be given explicitly.
+//!
+//! Validating an RRset requires following the chain of trust from a trust
+//! anchor down to the zone that signed it. This is done by the
+//! [`chain::ChainValidator`] which results in a [`chain::SecurityStatus`].
+//! Negative answers are checked via the NSEC and NSEC3 proofs provided by
+//! the [`denial`] module.
+//!
+//! Trust anchors can be kept up to date automatically following RFC 5011
+//! using the [`rollover::TrustAnchorManager`].
+//!
+//! A parent zone can update the DS records of a child based on the
+//! child’s CDS and CDNSKEY records via the [`cds::DsMaintainer`].
 #![cfg(feature = "validate")]
 #![cfg_attr(docsrs, doc(cfg(feature = "validate")))]
 
 use crate::base::cmp::CanonicalOrd;
-use crate::base::iana::{DigestAlg, SecAlg};
-use crate::base::name::ToDname;
+use crate::base::iana::DigestAlg;
+use crate::base::name::{Dname, ToDname};
 use crate::base::rdata::{ComposeRecordData, RecordData};
 use crate::base::record::Record;
+use crate::base::serial::Serial;
 use crate::base::wire::{Compose, Composer};
+use crate::crypto::Backend;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
 use crate::rdata::{Dnskey, Rrsig};
+use core::cmp::Ordering;
 use octseq::builder::with_infallible;
-use ring::{digest, signature};
+#[cfg(feature = "ring")]
+use ring::digest;
 use std::vec::Vec;
 use std::{error, fmt};
 
+pub use crate::crypto::AlgorithmError;
+
+pub mod cds;
+pub mod chain;
+pub mod denial;
+pub mod rollover;
+
 //------------ Dnskey --------------------------------------------------------
 
 /// Extensions for DNSKEY record type.
@@ -38,11 +76,23 @@ pub trait DnskeyExt {
     /// ```
     ///
     /// [RFC 4034, Section 5.1.4]: https://tools.ietf.org/html/rfc4034#section-5.1.4
+    #[cfg(feature = "ring")]
     fn digest<N: ToDname>(
         &self,
         dname: &N,
         algorithm: DigestAlg,
     ) -> Result<digest::Digest, AlgorithmError>;
+
+    /// Calculates a digest from DNSKEY using the given backend.
+    ///
+    /// This is the same as [`digest`][Self::digest] except that the
+    /// digest is calculated by `backend`.
+    fn digest_with<N: ToDname, B: Backend>(
+        &self,
+        backend: &B,
+        dname: &N,
+        algorithm: DigestAlg,
+    ) -> Result<Vec<u8>, AlgorithmError>;
 }
 
 impl<Octets> DnskeyExt for Dnskey<Octets>
@@ -67,6 +117,7 @@ where
     /// ```
     ///
     /// [RFC 4034, Section 5.1.4]: https://tools.ietf.org/html/rfc4034#section-5.1.4
+    #[cfg(feature = "ring")]
     fn digest<N: ToDname>(
         &self,
         dname: &N,
@@ -92,6 +143,20 @@ where
         ctx.update(&buf);
         Ok(ctx.finish())
     }
+
+    fn digest_with<N: ToDname, B: Backend>(
+        &self,
+        backend: &B,
+        dname: &N,
+        algorithm: DigestAlg,
+    ) -> Result<Vec<u8>, AlgorithmError> {
+        let mut buf: Vec<u8> = Vec::new();
+        with_infallible(|| {
+            dname.compose_canonical(&mut buf)?;
+            self.compose_canonical_rdata(&mut buf)
+        });
+        backend.digest(algorithm, &buf)
+    }
 }
 
 //------------ Rrsig ---------------------------------------------------------
@@ -109,13 +174,15 @@ pub trait RrsigExt {
     ///    the received RRset due to DNS name compression, decremented TTLs, or
     ///    wildcard expansion.
     /// ```
-    fn signed_data<N: ToDname, D: RecordData, B: Composer>(
+    fn signed_data<N, D, B>(
         &self,
         buf: &mut B,
         records: &mut [Record<N, D>],
     ) -> Result<(), B::AppendError>
     where
-        D: CanonicalOrd + ComposeRecordData + Sized;
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData + Sized,
+        B: Composer;
 
     /// Attempt to use the cryptographic signature to authenticate the signed data, and thus authenticate the RRSET.
     /// The signed data is expected to be calculated as per [RFC4035, Section 5.3.2](https://tools.ietf.org/html/rfc4035#section-5.3.2).
@@ -138,21 +205,98 @@ pub trait RrsigExt {
     ///    provides a list of algorithm types and provides pointers to the
     ///    documents that define each algorithm's use.
     /// ```
+    #[cfg(feature = "ring")]
     fn verify_signed_data(
         &self,
         dnskey: &Dnskey<impl AsRef<[u8]>>,
         signed_data: &impl AsRef<[u8]>,
     ) -> Result<(), AlgorithmError>;
+
+    /// Verifies the signed data using the given cryptographic backend.
+    ///
+    /// This is the same as [`verify_signed_data`][Self::verify_signed_data]
+    /// except that the cryptographic operations are performed by `backend`
+    /// instead of the default ring backend.
+    fn verify_signed_data_with<B: Backend>(
+        &self,
+        backend: &B,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+        signed_data: &impl AsRef<[u8]>,
+    ) -> Result<(), AlgorithmError>;
+
+    /// Checks that the signature is valid at the given time.
+    ///
+    /// As required by [RFC 4035, Section 5.3.1], the time must not be
+    /// before the inception or after the expiration time of the signature.
+    /// Both are compared using serial number arithmetic.
+    ///
+    /// [RFC 4035, Section 5.3.1]: https://tools.ietf.org/html/rfc4035#section-5.3.1
+    fn check_validity_period(
+        &self,
+        now: Serial,
+    ) -> Result<(), ValidationError>;
+
+    /// Returns whether a DNSKEY record may have created the signature.
+    ///
+    /// The key with the owner name `key_owner` matches if its owner is the
+    /// signer’s name, its algorithm and key tag are those of the signature,
+    /// and it is a zone key for the DNSSEC protocol that hasn’t been
+    /// revoked.
+    fn matches_key<N: ToDname>(
+        &self,
+        key_owner: &N,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+    ) -> bool;
+
+    /// Returns the wildcard name a record with the given owner was expanded
+    /// from.
+    ///
+    /// If the labels field of the signature is smaller than the number of
+    /// labels of `owner`, the record was synthesized from a wildcard and
+    /// the wildcard’s name is returned. Otherwise returns `None`.
+    fn expanded_wildcard<N: ToDname>(
+        &self,
+        owner: &N,
+    ) -> Option<Dname<Vec<u8>>>;
+
+    /// Verifies the signature over an RRset.
+    ///
+    /// This performs all the steps of [RFC 4035, Section 5.3]: it checks
+    /// that `records` form an RRset covered by the signature, that the
+    /// labels field is acceptable, that the DNSKEY record `dnskey` owned
+    /// by `key_owner` matches the signature, and that the signature is
+    /// valid at time `now`. It then constructs the signed data, including
+    /// the handling of expanded wildcards, and verifies the signature using
+    /// `backend`.
+    ///
+    /// The records are sorted into canonical order in the process.
+    ///
+    /// [RFC 4035, Section 5.3]: https://tools.ietf.org/html/rfc4035#section-5.3
+    fn verify_rrset<N, D, KN, B>(
+        &self,
+        backend: &B,
+        key_owner: &KN,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+        records: &mut [Record<N, D>],
+        now: Serial,
+    ) -> Result<(), ValidationError>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData,
+        KN: ToDname,
+        B: Backend;
 }
 
 impl<Octets: AsRef<[u8]>, Name: ToDname> RrsigExt for Rrsig<Octets, Name> {
-    fn signed_data<N: ToDname, D: RecordData, B: Composer>(
+    fn signed_data<N, D, B>(
         &self,
         buf: &mut B,
         records: &mut [Record<N, D>],
     ) -> Result<(), B::AppendError>
     where
-        D: CanonicalOrd + ComposeRecordData + Sized,
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData + Sized,
+        B: Composer,
     {
         // signed_data = RRSIG_RDATA | RR(1) | RR(2)...  where
         //    "|" denotes concatenation
@@ -168,12 +312,19 @@ impl<Octets: AsRef<[u8]>, Name: ToDname> RrsigExt for Rrsig<Octets, Name> {
         self.key_tag().compose(buf)?;
         self.signer_name().compose_canonical(buf)?;
 
-        // The set of all RR(i) is sorted into canonical order.
+        // The set of all RR(i) is sorted into canonical order and
+        // duplicate RRs are skipped.
         // See https://tools.ietf.org/html/rfc4034#section-6.3
         records.sort_by(|a, b| a.data().canonical_cmp(b.data()));
 
         // RR(i) = name | type | class | OrigTTL | RDATA length | RDATA
-        for rr in records {
+        let mut prev: Option<&Record<N, D>> = None;
+        for rr in records.iter() {
+            if let Some(prev) = prev.replace(rr) {
+                if prev.data().canonical_cmp(rr.data()).is_eq() {
+                    continue;
+                }
+            }
             // Handle expanded wildcards as per [RFC4035, Section 5.3.2]
             // (https://tools.ietf.org/html/rfc4035#section-5.3.2).
             let rrsig_labels = usize::from(self.labels());
@@ -204,146 +355,185 @@ impl<Octets: AsRef<[u8]>, Name: ToDname> RrsigExt for Rrsig<Octets, Name> {
         Ok(())
     }
 
+    #[cfg(feature = "ring")]
     fn verify_signed_data(
         &self,
         dnskey: &Dnskey<impl AsRef<[u8]>>,
         signed_data: &impl AsRef<[u8]>,
     ) -> Result<(), AlgorithmError> {
-        let signature = self.signature().as_ref();
-        let signed_data = signed_data.as_ref();
+        self.verify_signed_data_with(&RingBackend, dnskey, signed_data)
+    }
 
+    fn verify_signed_data_with<B: Backend>(
+        &self,
+        backend: &B,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+        signed_data: &impl AsRef<[u8]>,
+    ) -> Result<(), AlgorithmError> {
         // Caller needs to ensure that the signature matches the key, but enforce the algorithm match
         if self.algorithm() != dnskey.algorithm() {
             return Err(AlgorithmError::InvalidData);
         }
+        backend.verify(
+            self.algorithm(),
+            dnskey.public_key().as_ref(),
+            signed_data.as_ref(),
+            self.signature().as_ref(),
+        )
+    }
 
-        // Note: Canonicalize the algorithm, otherwise matching named variants against Int(_) is not going to work
-        let sec_alg = SecAlg::from_int(self.algorithm().to_int());
-        match sec_alg {
-            SecAlg::RsaSha1
-            | SecAlg::RsaSha1Nsec3Sha1
-            | SecAlg::RsaSha256
-            | SecAlg::RsaSha512 => {
-                let (algorithm, min_bytes) = match sec_alg {
-                    SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    SecAlg::RsaSha256 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    SecAlg::RsaSha512 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    _ => unreachable!(),
-                };
-
-                // The key isn't available in either PEM or DER, so use the
-                // direct RSA verifier.
-                let (e, n) = rsa_exponent_modulus(dnskey, min_bytes)?;
-                let public_key =
-                    signature::RsaPublicKeyComponents { n: &n, e: &e };
-                public_key
-                    .verify(algorithm, signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
-            }
-            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
-                let algorithm = match sec_alg {
-                    SecAlg::EcdsaP256Sha256 => {
-                        &signature::ECDSA_P256_SHA256_FIXED
-                    }
-                    SecAlg::EcdsaP384Sha384 => {
-                        &signature::ECDSA_P384_SHA384_FIXED
-                    }
-                    _ => unreachable!(),
-                };
-
-                // Add 0x4 identifier to the ECDSA pubkey as expected by ring.
-                let public_key = dnskey.public_key().as_ref();
-                let mut key = Vec::with_capacity(public_key.len() + 1);
-                key.push(0x4);
-                key.extend_from_slice(public_key);
-
-                signature::UnparsedPublicKey::new(algorithm, &key)
-                    .verify(signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
-            }
-            SecAlg::Ed25519 => {
-                let key = dnskey.public_key();
-                signature::UnparsedPublicKey::new(&signature::ED25519, &key)
-                    .verify(signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
+    fn check_validity_period(
+        &self,
+        now: Serial,
+    ) -> Result<(), ValidationError> {
+        match now.partial_cmp(&self.inception()) {
+            Some(Ordering::Less) | None => {
+                return Err(ValidationError::NotYetValid)
             }
-            _ => Err(AlgorithmError::Unsupported),
+            _ => {}
+        }
+        match now.partial_cmp(&self.expiration()) {
+            Some(Ordering::Greater) | None => Err(ValidationError::Expired),
+            _ => Ok(()),
         }
     }
-}
 
-/// Return the RSA exponent and modulus components from DNSKEY record data.
-fn rsa_exponent_modulus(
-    dnskey: &Dnskey<impl AsRef<[u8]>>,
-    min_len: usize,
-) -> Result<(&[u8], &[u8]), AlgorithmError> {
-    let public_key = dnskey.public_key().as_ref();
-    if public_key.len() <= 3 {
-        return Err(AlgorithmError::InvalidData);
+    fn matches_key<N: ToDname>(
+        &self,
+        key_owner: &N,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+    ) -> bool {
+        dnskey.is_zsk()
+            && !dnskey.is_revoked()
+            && dnskey.protocol() == 3
+            && dnskey.algorithm() == self.algorithm()
+            && dnskey.key_tag() == self.key_tag()
+            && self.signer_name().name_eq(key_owner)
     }
 
-    let (pos, exp_len) = match public_key[0] {
-        0 => (
-            3,
-            (usize::from(public_key[1]) << 8) | usize::from(public_key[2]),
-        ),
-        len => (1, usize::from(len)),
-    };
-
-    // Check if there's enough space for exponent and modulus.
-    if public_key[pos..].len() < pos + exp_len {
-        return Err(AlgorithmError::InvalidData);
-    };
-
-    // Check for minimum supported key size
-    if public_key[pos..].len() < min_len {
-        return Err(AlgorithmError::Unsupported);
+    fn expanded_wildcard<N: ToDname>(
+        &self,
+        owner: &N,
+    ) -> Option<Dname<Vec<u8>>> {
+        // Subtract the root label from count as the algorithm doesn't
+        // accomodate that.
+        let owner_labels = owner.iter_labels().count() - 1;
+        let rrsig_labels = usize::from(self.labels());
+        if rrsig_labels >= owner_labels {
+            return None;
+        }
+        let owner = owner.to_cow();
+        let suffix =
+            owner.iter_suffixes().nth(owner_labels - rrsig_labels)?;
+        let mut res = Vec::with_capacity(suffix.len() + 2);
+        res.extend_from_slice(b"\x01*");
+        res.extend_from_slice(suffix.as_slice());
+        Dname::from_octets(res).ok()
     }
 
-    Ok(public_key[pos..].split_at(exp_len))
+    fn verify_rrset<N, D, KN, B>(
+        &self,
+        backend: &B,
+        key_owner: &KN,
+        dnskey: &Dnskey<impl AsRef<[u8]>>,
+        records: &mut [Record<N, D>],
+        now: Serial,
+    ) -> Result<(), ValidationError>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + ComposeRecordData,
+        KN: ToDname,
+        B: Backend,
+    {
+        let first = match records.first() {
+            Some(first) => first,
+            None => return Err(ValidationError::RrsetMismatch),
+        };
+        if first.rtype() != self.type_covered()
+            || records.iter().any(|rr| {
+                rr.rtype() != first.rtype()
+                    || rr.class() != first.class()
+                    || !rr.owner().name_eq(first.owner())
+            })
+        {
+            return Err(ValidationError::RrsetMismatch);
+        }
+        if self.labels() > first.owner().rrsig_label_count() {
+            return Err(ValidationError::Labels);
+        }
+        if !self.matches_key(key_owner, dnskey) {
+            return Err(ValidationError::KeyMismatch);
+        }
+        self.check_validity_period(now)?;
+
+        let mut signed_data = Vec::new();
+        with_infallible(|| self.signed_data(&mut signed_data, records));
+        self.verify_signed_data_with(backend, dnskey, &signed_data)
+            .map_err(ValidationError::Algorithm)
+    }
 }
 
 //============ Error Types ===================================================
 
-//------------ AlgorithmError ------------------------------------------------
+//------------ ValidationError -----------------------------------------------
 
-/// An algorithm error during verification.
+/// A signature over an RRset failed to validate.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum AlgorithmError {
-    Unsupported,
-    BadSig,
-    InvalidData,
+pub enum ValidationError {
+    /// The records are not an RRset covered by the signature.
+    RrsetMismatch,
+
+    /// The labels field is larger than the number of labels of the owner.
+    Labels,
+
+    /// The key did not match the signature.
+    KeyMismatch,
+
+    /// The signature’s inception time is in the future.
+    NotYetValid,
+
+    /// The signature has expired.
+    Expired,
+
+    /// The cryptographic verification failed.
+    Algorithm(AlgorithmError),
+}
+
+//--- From
+
+impl From<AlgorithmError> for ValidationError {
+    fn from(err: AlgorithmError) -> Self {
+        ValidationError::Algorithm(err)
+    }
 }
 
 //--- Display and Error
 
-impl fmt::Display for AlgorithmError {
+impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            AlgorithmError::Unsupported => {
-                f.write_str("unsupported algorithm")
+            ValidationError::RrsetMismatch => {
+                f.write_str("records don't match signature")
+            }
+            ValidationError::Labels => f.write_str("invalid labels field"),
+            ValidationError::KeyMismatch => {
+                f.write_str("key doesn't match signature")
+            }
+            ValidationError::NotYetValid => {
+                f.write_str("signature not yet valid")
             }
-            AlgorithmError::BadSig => f.write_str("bad signature"),
-            AlgorithmError::InvalidData => f.write_str("invalid data"),
+            ValidationError::Expired => f.write_str("signature expired"),
+            ValidationError::Algorithm(err) => err.fmt(f),
         }
     }
 }
 
-impl error::Error for AlgorithmError {}
+impl error::Error for ValidationError {}
 
 //============ Test ==========================================================
 
 #[cfg(test)]
-#[cfg(feature = "std")]
+#[cfg(all(feature = "std", feature = "ring"))]
 mod test {
     use super::*;
     use crate::base::iana::{Class, Rtype, SecAlg};
@@ -703,4 +893,138 @@ mod test {
         // Test verifier
         assert_eq!(rrsig.verify_signed_data(&key, &signed_data), Ok(()));
     }
+
+    #[test]
+    fn rrsig_expanded_wildcard() {
+        let rrsig = Rrsig::new(
+            Rtype::Mx,
+            SecAlg::RsaSha1,
+            2,
+            Ttl::from_secs(3600),
+            0.into(),
+            0.into(),
+            38519,
+            Dname::from_str("example.").unwrap(),
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            rrsig.expanded_wildcard(
+                &Dname::from_str("a.z.w.example.").unwrap()
+            ),
+            Some(Dname::from_str("*.w.example.").unwrap())
+        );
+        assert_eq!(
+            rrsig.expanded_wildcard(&Dname::from_str("w.example.").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn rrsig_verify_rrset() {
+        let (ksk, zsk) = root_pubkey();
+        let rrsig = Rrsig::new(
+            Rtype::Dnskey,
+            SecAlg::RsaSha256,
+            0,
+            Ttl::from_secs(172800),
+            1560211200.into(),
+            1558396800.into(),
+            20326,
+            Dname::root(),
+            base64::decode::<Vec<u8>>(
+                "otBkINZAQu7AvPKjr/xWIEE7+SoZtKgF8bzVynX6bfJMJuPay8jPvNmwXkZ\
+                OdSoYlvFp0bk9JWJKCh8y5uoNfMFkN6OSrDkr3t0E+c8c0Mnmwkk5CETH3Gq\
+                xthi0yyRX5T4VlHU06/Ks4zI+XAgl3FBpOc554ivdzez8YCjAIGx7XgzzooE\
+                b7heMSlLc7S7/HNjw51TPRs4RxrAVcezieKCzPPpeWBhjE6R3oiSwrl0SBD4\
+                /yplrDlr7UHs/Atcm3MSgemdyr2sOoOUkVQCVpcj3SQQezoD2tCM7861CXEQ\
+                dg5fjeHDtz285xHt5HJpA5cOcctRo4ihybfow/+V7AQ==",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let mut records: Vec<_> = [&ksk, &zsk]
+            .iter()
+            .map(|&key| {
+                Record::new(
+                    Dname::root(),
+                    Class::In,
+                    Ttl::from_secs(0),
+                    key.clone(),
+                )
+            })
+            .collect();
+        let root = Dname::root();
+        let now = Serial::from(1559000000);
+
+        assert_eq!(
+            rrsig.verify_rrset(&RingBackend, &root, &ksk, &mut records, now),
+            Ok(())
+        );
+
+        // Duplicate records are only included once in the signed data.
+        let mut duplicated = records.clone();
+        duplicated.push(records[0].clone());
+        assert_eq!(
+            rrsig.verify_rrset(
+                &RingBackend,
+                &root,
+                &ksk,
+                &mut duplicated,
+                now
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            rrsig.verify_rrset(&RingBackend, &root, &zsk, &mut records, now),
+            Err(ValidationError::KeyMismatch)
+        );
+        assert_eq!(
+            rrsig.verify_rrset(
+                &RingBackend,
+                &Dname::from_str("net.").unwrap(),
+                &ksk,
+                &mut records,
+                now
+            ),
+            Err(ValidationError::KeyMismatch)
+        );
+        assert_eq!(
+            rrsig.verify_rrset(
+                &RingBackend,
+                &root,
+                &ksk,
+                &mut records,
+                1558000000.into()
+            ),
+            Err(ValidationError::NotYetValid)
+        );
+        assert_eq!(
+            rrsig.verify_rrset(
+                &RingBackend,
+                &root,
+                &ksk,
+                &mut records,
+                1561000000.into()
+            ),
+            Err(ValidationError::Expired)
+        );
+        assert_eq!(
+            rrsig.verify_rrset(
+                &RingBackend,
+                &root,
+                &ksk,
+                &mut records[..0],
+                now
+            ),
+            Err(ValidationError::RrsetMismatch)
+        );
+
+        // A modified RRset fails to verify.
+        records.pop();
+        assert_eq!(
+            rrsig.verify_rrset(&RingBackend, &root, &ksk, &mut records, now),
+            Err(ValidationError::Algorithm(AlgorithmError::BadSig))
+        );
+    }
 }
@@ -0,0 +1,824 @@
+//! Automated updates of trust anchors.
+//!
+//! [RFC 5011] describes how a validator can follow a rollover of the key
+//! signing keys of a zone it has a trust anchor for without manual
+//! intervention. New keys that appear in the zone’s DNSKEY RRset signed by
+//! an already trusted key are only accepted after they have been seen for
+//! an add hold-down time, and keys are dropped once the zone has
+//! published them with the REVOKE flag set and signed the RRset with the
+//! revoked key.
+//!
+//! The [`TrustAnchorManager`] implements this process. It is handed the
+//! DNSKEY RRsets of the managed zones whenever they have been fetched via
+//! [`observe`][TrustAnchorManager::observe] and updates the state of the
+//! tracked keys accordingly. The state is kept in an [`AnchorStore`]
+//! supplied by the user so it survives restarts. The keys that currently
+//! are trust anchors are available as [`TrustAnchors`] for use with the
+//! [`ChainValidator`] via
+//! [`trust_anchors`][TrustAnchorManager::trust_anchors].
+//!
+//! How often the DNSKEY RRsets should be fetched is given by
+//! [`query_interval`] and, after a failed attempt, [`retry_interval`].
+//!
+//! [RFC 5011]: https://tools.ietf.org/html/rfc5011
+
+use super::chain::{ChainValidator, Reason, SecurityStatus, TrustAnchors};
+use super::RrsigExt;
+use crate::base::cmp::CanonicalOrd;
+use crate::base::name::{Dname, ToDname};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::base::Ttl;
+use crate::crypto::Backend;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::rdata::{Dnskey, Rrsig};
+use core::cmp;
+use octseq::builder::with_infallible;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ TrustAnchorManager --------------------------------------------
+
+/// Manages trust anchors following the rules of RFC 5011.
+///
+/// The manager keeps a list of [`TrackedKey`]s for each managed zone. A
+/// zone becomes managed by adding an initial trust anchor via
+/// [`add_anchor`][Self::add_anchor]. Only keys with the secure entry point
+/// flag set are tracked.
+///
+/// Every change of state is written to the store before the method that
+/// caused it returns.
+#[derive(Clone, Debug)]
+pub struct TrustAnchorManager<S, B> {
+    /// The store to persist the keys to.
+    store: S,
+
+    /// The backend for cryptographic operations.
+    backend: B,
+
+    /// The tracked keys of all managed zones.
+    keys: Vec<TrackedKey>,
+
+    /// The minimum time a new key has to be seen before it is trusted.
+    add_hold_down: Ttl,
+
+    /// The time a revoked key is kept before it is removed.
+    remove_hold_down: Ttl,
+}
+
+#[cfg(feature = "ring")]
+impl<S: AnchorStore> TrustAnchorManager<S, RingBackend> {
+    /// Creates a new manager using the [`RingBackend`].
+    ///
+    /// The tracked keys are loaded from `store`.
+    pub fn new(store: S) -> Result<Self, S::Error> {
+        Self::with_backend(store, RingBackend)
+    }
+}
+
+impl<S: AnchorStore, B: Backend> TrustAnchorManager<S, B> {
+    /// The default add and remove hold-down time of 30 days.
+    pub const DEFAULT_HOLD_DOWN: Ttl = Ttl::from_days(30);
+
+    /// Creates a new manager using the given crypto backend.
+    ///
+    /// The tracked keys are loaded from `store`.
+    pub fn with_backend(mut store: S, backend: B) -> Result<Self, S::Error> {
+        let keys = store.load()?;
+        Ok(TrustAnchorManager {
+            store,
+            backend,
+            keys,
+            add_hold_down: Self::DEFAULT_HOLD_DOWN,
+            remove_hold_down: Self::DEFAULT_HOLD_DOWN,
+        })
+    }
+
+    /// Sets the add hold-down time.
+    ///
+    /// A new key is only trusted after it has been seen for the greater of
+    /// this time and the TTL of the DNSKEY RRset it first appeared in.
+    pub fn set_add_hold_down(&mut self, hold_down: Ttl) {
+        self.add_hold_down = hold_down
+    }
+
+    /// Sets the remove hold-down time.
+    ///
+    /// A revoked key is moved to the removed state after this time.
+    pub fn set_remove_hold_down(&mut self, hold_down: Ttl) {
+        self.remove_hold_down = hold_down
+    }
+
+    /// Returns the store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the tracked keys of all zones.
+    pub fn keys(&self) -> &[TrackedKey] {
+        &self.keys
+    }
+
+    /// Returns whether the given zone is managed.
+    pub fn is_managed<N: ToDname + ?Sized>(&self, zone: &N) -> bool {
+        self.keys.iter().any(|key| key.owner.name_eq(zone))
+    }
+
+    /// Adds a key as a trust anchor for a zone.
+    ///
+    /// The key is trusted right away. This is used to configure the
+    /// initial trust anchors of a zone. If the key is already tracked, its
+    /// state is left unchanged.
+    pub fn add_anchor(
+        &mut self,
+        owner: Dname<Vec<u8>>,
+        dnskey: Dnskey<Vec<u8>>,
+        now: Serial,
+    ) -> Result<(), S::Error> {
+        if self.find(&owner, &dnskey).is_some() {
+            return Ok(());
+        }
+        self.keys
+            .push(TrackedKey::new(owner, dnskey, KeyState::Valid, now));
+        self.store.save(&self.keys)
+    }
+
+    /// Returns the keys that currently are trust anchors.
+    ///
+    /// These are the keys in the valid and missing states.
+    pub fn trust_anchors(&self) -> TrustAnchors {
+        let mut res = TrustAnchors::new();
+        for key in &self.keys {
+            if key.state.is_trusted() {
+                res.add_dnskey(key.owner.clone(), key.dnskey.clone());
+            }
+        }
+        res
+    }
+
+    /// Processes a freshly fetched DNSKEY RRset of a managed zone.
+    ///
+    /// The RRset has to be validated by one of the zone’s current trust
+    /// anchors. If it isn’t, the state of the keys is left untouched and
+    /// the failed status is returned. The status for an RRset of a zone
+    /// that isn’t managed is indeterminate.
+    ///
+    /// If the RRset is valid, the tracked keys are updated as described in
+    /// section 4 of RFC 5011 and the new state is saved to the store.
+    pub fn observe<N, Octs, SigOcts, SigName>(
+        &mut self,
+        records: &mut [Record<N, Dnskey<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+        now: Serial,
+    ) -> Result<SecurityStatus, S::Error>
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        let (owner, ttl) = match records.first() {
+            Some(record) => (record.owner().to_vec(), record.ttl()),
+            None => return Ok(SecurityStatus::Bogus(Reason::NoMatchingKey)),
+        };
+        if !self.is_managed(&owner) {
+            return Ok(SecurityStatus::Indeterminate(Reason::NoTrustAnchor));
+        }
+        let anchors = self.trust_anchors();
+        let status =
+            ChainValidator::with_backend(&anchors, &self.backend, now)
+                .add_dnskeys(records, rrsigs);
+        if !status.is_secure() {
+            return Ok(status);
+        }
+
+        let mut changed = false;
+
+        // Keys that are present in the RRset.
+        let present: Vec<_> = records
+            .iter()
+            .map(|record| record.data())
+            .filter(|key| key.is_secure_entry_point())
+            .map(|key| to_vec_key(key, key.flags()))
+            .collect();
+        for key in present {
+            let revoked = key.is_revoked();
+            if revoked
+                && !self.self_signed(&owner, &key, records, rrsigs, now)
+            {
+                continue;
+            }
+            let key = to_vec_key(&key, key.flags() & !REVOKE);
+            let idx = match self.find(&owner, &key) {
+                Some(idx) => idx,
+                None => {
+                    if !revoked {
+                        let hold_down = cmp::max(self.add_hold_down, ttl);
+                        self.keys.push(TrackedKey::new(
+                            owner.clone(),
+                            key,
+                            KeyState::AddPend,
+                            now.add(hold_down.as_secs()),
+                        ));
+                        changed = true;
+                    }
+                    continue;
+                }
+            };
+            let tracked = &mut self.keys[idx];
+            let state = match (tracked.state, revoked) {
+                (KeyState::Removed, _) => continue,
+                (KeyState::Revoked, _) => continue,
+                (_, true) => KeyState::Revoked,
+                (KeyState::AddPend, false) if now >= tracked.since => {
+                    KeyState::Valid
+                }
+                (KeyState::Missing, false) => KeyState::Valid,
+                (_, false) => continue,
+            };
+            tracked.state = state;
+            tracked.since = match state {
+                KeyState::Revoked => now.add(self.remove_hold_down.as_secs()),
+                _ => now,
+            };
+            changed = true;
+        }
+
+        // Keys that are missing from the RRset.
+        let mut idx = 0;
+        while idx < self.keys.len() {
+            let tracked = &mut self.keys[idx];
+            if !tracked.owner.name_eq(&owner)
+                || records
+                    .iter()
+                    .any(|record| tracked.is_same_key(record.data()))
+            {
+                idx += 1;
+                continue;
+            }
+            match tracked.state {
+                KeyState::AddPend => {
+                    self.keys.remove(idx);
+                    changed = true;
+                    continue;
+                }
+                KeyState::Valid => {
+                    tracked.state = KeyState::Missing;
+                    tracked.since = now;
+                    changed = true;
+                }
+                _ => {}
+            }
+            idx += 1;
+        }
+
+        // Revoked keys whose remove hold-down time has passed.
+        for tracked in &mut self.keys {
+            if tracked.owner.name_eq(&owner)
+                && tracked.state == KeyState::Revoked
+                && now >= tracked.since
+            {
+                tracked.state = KeyState::Removed;
+                tracked.since = now;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.store.save(&self.keys)?;
+        }
+        Ok(status)
+    }
+
+    /// Returns the index of a tracked key.
+    fn find(
+        &self,
+        owner: &Dname<Vec<u8>>,
+        key: &Dnskey<Vec<u8>>,
+    ) -> Option<usize> {
+        self.keys.iter().position(|tracked| {
+            tracked.owner.name_eq(owner) && tracked.is_same_key(key)
+        })
+    }
+
+    /// Returns whether a revoked key has signed the RRset.
+    fn self_signed<N, Octs, SigOcts, SigName>(
+        &self,
+        owner: &Dname<Vec<u8>>,
+        key: &Dnskey<Vec<u8>>,
+        records: &mut [Record<N, Dnskey<Octs>>],
+        rrsigs: &[Rrsig<SigOcts, SigName>],
+        now: Serial,
+    ) -> bool
+    where
+        N: ToDname,
+        Octs: AsRef<[u8]>,
+        SigOcts: AsRef<[u8]>,
+        SigName: ToDname,
+    {
+        // We can’t use verify_rrset here since revoked keys never match a
+        // signature.
+        records.sort_by(|a, b| a.data().canonical_cmp(b.data()));
+        rrsigs.iter().any(|rrsig| {
+            if rrsig.algorithm() != key.algorithm()
+                || rrsig.key_tag() != key.key_tag()
+                || !rrsig.signer_name().name_eq(owner)
+                || rrsig.check_validity_period(now).is_err()
+            {
+                return false;
+            }
+            let mut signed_data = Vec::new();
+            with_infallible(|| rrsig.signed_data(&mut signed_data, records));
+            rrsig
+                .verify_signed_data_with(&self.backend, key, &signed_data)
+                .is_ok()
+        })
+    }
+}
+
+/// The REVOKE flag of a DNSKEY record.
+const REVOKE: u16 = 0b0000_0000_1000_0000;
+
+/// Copies DNSKEY record data into a vec using the given flags.
+fn to_vec_key(key: &Dnskey<impl AsRef<[u8]>>, flags: u16) -> Dnskey<Vec<u8>> {
+    // Safety: The data has come from valid record data.
+    unsafe {
+        Dnskey::new_unchecked(
+            flags,
+            key.protocol(),
+            key.algorithm(),
+            key.public_key().as_ref().into(),
+        )
+    }
+}
+
+//------------ query_interval and retry_interval -----------------------------
+
+/// Returns the time until the DNSKEY RRset of a zone should be refreshed.
+///
+/// This is the active refresh interval of section 2.3 of RFC 5011 based on
+/// the original TTL of the RRset and the expiration time of its
+/// signature. It is at least one hour and at most fifteen days.
+pub fn query_interval(
+    original_ttl: Ttl,
+    expiration: Serial,
+    now: Serial,
+) -> Ttl {
+    refresh_interval(
+        original_ttl / 2,
+        remaining(expiration, now) / 2,
+        Ttl::from_days(15),
+    )
+}
+
+/// Returns the time until a failed refresh should be retried.
+///
+/// This is the retry interval of section 2.3 of RFC 5011. It is at least
+/// one hour and at most a day.
+pub fn retry_interval(
+    original_ttl: Ttl,
+    expiration: Serial,
+    now: Serial,
+) -> Ttl {
+    refresh_interval(
+        original_ttl / 10,
+        remaining(expiration, now) / 10,
+        Ttl::DAY,
+    )
+}
+
+fn refresh_interval(ttl: Ttl, expiration: Ttl, max: Ttl) -> Ttl {
+    cmp::max(Ttl::HOUR, cmp::min(max, cmp::min(ttl, expiration)))
+}
+
+/// Returns the time left until `expiration`.
+fn remaining(expiration: Serial, now: Serial) -> Ttl {
+    if expiration > now {
+        Ttl::from_secs(u32::from(expiration).wrapping_sub(u32::from(now)))
+    } else {
+        Ttl::ZERO
+    }
+}
+
+//------------ TrackedKey ----------------------------------------------------
+
+/// A key tracked by the trust anchor manager.
+///
+/// The DNSKEY record data is kept without the REVOKE flag so the key can
+/// be identified before and after it has been revoked.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TrackedKey {
+    /// The zone the key belongs to.
+    owner: Dname<Vec<u8>>,
+
+    /// The key.
+    dnskey: Dnskey<Vec<u8>>,
+
+    /// The state of the key.
+    state: KeyState,
+
+    /// The time of the last state change or of the next pending one.
+    ///
+    /// For keys in the add pending state, this is the time the add
+    /// hold-down time ends. For revoked keys, it is the time the remove
+    /// hold-down time ends. For all other keys, it is the time they
+    /// entered their state.
+    since: Serial,
+}
+
+impl TrackedKey {
+    /// Creates a tracked key from its parts.
+    ///
+    /// This is mostly useful for implementing an [`AnchorStore`].
+    pub fn new(
+        owner: Dname<Vec<u8>>,
+        dnskey: Dnskey<Vec<u8>>,
+        state: KeyState,
+        since: Serial,
+    ) -> Self {
+        TrackedKey {
+            owner,
+            dnskey,
+            state,
+            since,
+        }
+    }
+
+    pub fn owner(&self) -> &Dname<Vec<u8>> {
+        &self.owner
+    }
+
+    pub fn dnskey(&self) -> &Dnskey<Vec<u8>> {
+        &self.dnskey
+    }
+
+    pub fn state(&self) -> KeyState {
+        self.state
+    }
+
+    /// Returns the time associated with the state.
+    ///
+    /// See [`new`][Self::new] for what this time means for each state.
+    pub fn since(&self) -> Serial {
+        self.since
+    }
+
+    /// Returns whether a DNSKEY is this key, ignoring the REVOKE flag.
+    fn is_same_key(&self, key: &Dnskey<impl AsRef<[u8]>>) -> bool {
+        self.dnskey.algorithm() == key.algorithm()
+            && self.dnskey.protocol() == key.protocol()
+            && self.dnskey.public_key().as_slice()
+                == key.public_key().as_ref()
+    }
+}
+
+//------------ KeyState ------------------------------------------------------
+
+/// The state of a tracked key as defined in section 4 of RFC 5011.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyState {
+    /// The key has been seen but the add hold-down time hasn’t passed yet.
+    AddPend,
+
+    /// The key is a trust anchor.
+    Valid,
+
+    /// The key is a trust anchor but has disappeared from the zone.
+    Missing,
+
+    /// The key has been revoked and is no longer a trust anchor.
+    Revoked,
+
+    /// The key has been revoked and the remove hold-down time has passed.
+    ///
+    /// Keys in this state can be dropped from the store.
+    Removed,
+}
+
+impl KeyState {
+    /// Returns whether a key in this state is a trust anchor.
+    pub fn is_trusted(self) -> bool {
+        matches!(self, KeyState::Valid | KeyState::Missing)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyState::AddPend => "AddPend",
+            KeyState::Valid => "Valid",
+            KeyState::Missing => "Missing",
+            KeyState::Revoked => "Revoked",
+            KeyState::Removed => "Removed",
+        })
+    }
+}
+
+//------------ AnchorStore ---------------------------------------------------
+
+/// Persistent storage for the state of the trust anchor manager.
+///
+/// The store receives the complete list of tracked keys whenever it has
+/// changed and has to return the last list saved when the manager is
+/// created.
+pub trait AnchorStore {
+    /// The error returned if loading or saving failed.
+    type Error;
+
+    /// Loads the tracked keys.
+    fn load(&mut self) -> Result<Vec<TrackedKey>, Self::Error>;
+
+    /// Saves the tracked keys.
+    fn save(&mut self, keys: &[TrackedKey]) -> Result<(), Self::Error>;
+}
+
+//------------ MemoryStore ---------------------------------------------------
+
+/// An anchor store that only keeps the keys in memory.
+///
+/// This store is mainly useful for testing or for wrapping in a store that
+/// persists the keys elsewhere.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    keys: Vec<TrackedKey>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keys(&self) -> &[TrackedKey] {
+        &self.keys
+    }
+}
+
+impl AnchorStore for MemoryStore {
+    type Error = StoreError;
+
+    fn load(&mut self) -> Result<Vec<TrackedKey>, Self::Error> {
+        Ok(self.keys.clone())
+    }
+
+    fn save(&mut self, keys: &[TrackedKey]) -> Result<(), Self::Error> {
+        self.keys = keys.into();
+        Ok(())
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ StoreError ----------------------------------------------------
+
+/// The memory store failed.
+///
+/// This can’t actually happen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StoreError;
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("anchor store failed")
+    }
+}
+
+impl error::Error for StoreError {}
+
+//============ Test ==========================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, SecAlg};
+    use crate::base::rdata::{ComposeRecordData, RecordData};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    type Name = Dname<Vec<u8>>;
+
+    const DAY: u32 = 86400;
+
+    struct TestKey {
+        pair: Ed25519KeyPair,
+        dnskey: Dnskey<Vec<u8>>,
+    }
+
+    impl TestKey {
+        fn new(seed: u8) -> Self {
+            let pair =
+                Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+            let dnskey = Dnskey::new(
+                257,
+                3,
+                SecAlg::Ed25519,
+                pair.public_key().as_ref().into(),
+            )
+            .unwrap();
+            TestKey { pair, dnskey }
+        }
+
+        fn revoked(&self) -> Dnskey<Vec<u8>> {
+            to_vec_key(&self.dnskey, self.dnskey.flags() | REVOKE)
+        }
+
+        fn sign<D>(
+            &self,
+            dnskey: &Dnskey<Vec<u8>>,
+            records: &mut [Record<Name, D>],
+            now: u32,
+        ) -> Rrsig<Vec<u8>, Name>
+        where
+            D: RecordData + CanonicalOrd + ComposeRecordData,
+        {
+            let mut rrsig = Rrsig::new(
+                records[0].rtype(),
+                SecAlg::Ed25519,
+                0,
+                Ttl::from_secs(3600),
+                (now + 10 * DAY).into(),
+                (now - DAY).into(),
+                dnskey.key_tag(),
+                Name::root(),
+                Vec::new(),
+            )
+            .unwrap();
+            let mut buf = Vec::new();
+            rrsig.signed_data(&mut buf, records).unwrap();
+            rrsig.set_signature(self.pair.sign(&buf).as_ref().into());
+            rrsig
+        }
+    }
+
+    fn rrset(
+        keys: Vec<Dnskey<Vec<u8>>>,
+    ) -> Vec<Record<Name, Dnskey<Vec<u8>>>> {
+        keys.into_iter()
+            .map(|key| {
+                Record::new(
+                    Name::root(),
+                    Class::In,
+                    Ttl::from_secs(3600),
+                    key,
+                )
+            })
+            .collect()
+    }
+
+    fn states(
+        manager: &TrustAnchorManager<MemoryStore, RingBackend>,
+    ) -> Vec<KeyState> {
+        manager.keys().iter().map(TrackedKey::state).collect()
+    }
+
+    #[test]
+    fn rollover() {
+        let old = TestKey::new(1);
+        let new = TestKey::new(2);
+        let mut now = 1_000_000_000;
+
+        let mut manager =
+            TrustAnchorManager::new(MemoryStore::new()).unwrap();
+        manager
+            .add_anchor(Name::root(), old.dnskey.clone(), now.into())
+            .unwrap();
+        assert!(manager.is_managed(&Name::root()));
+        assert_eq!(manager.store().keys().len(), 1);
+
+        // A new key appears, signed by the old key.
+        let mut records = rrset(vec![old.dnskey.clone(), new.dnskey.clone()]);
+        let sig = old.sign(&old.dnskey, &mut records, now);
+        assert_eq!(
+            manager.observe(&mut records, &[sig], now.into()).unwrap(),
+            SecurityStatus::Secure
+        );
+        assert_eq!(states(&manager), [KeyState::Valid, KeyState::AddPend]);
+        assert_eq!(manager.store().keys(), manager.keys());
+
+        // Before the hold-down time has passed, the key stays pending.
+        now += 29 * DAY;
+        let sig = old.sign(&old.dnskey, &mut records, now);
+        manager.observe(&mut records, &[sig], now.into()).unwrap();
+        assert_eq!(states(&manager), [KeyState::Valid, KeyState::AddPend]);
+
+        // Afterwards it becomes valid.
+        now += 2 * DAY;
+        let sig = old.sign(&old.dnskey, &mut records, now);
+        manager.observe(&mut records, &[sig], now.into()).unwrap();
+        assert_eq!(states(&manager), [KeyState::Valid, KeyState::Valid]);
+        let anchors = manager.trust_anchors();
+        assert_eq!(anchors.dnskeys(&Name::root()).count(), 2);
+
+        // The old key is revoked and signs the RRset with the new key.
+        let mut records = rrset(vec![old.revoked(), new.dnskey.clone()]);
+        let sigs = [
+            old.sign(&old.revoked(), &mut records, now),
+            new.sign(&new.dnskey, &mut records, now),
+        ];
+        assert_eq!(
+            manager.observe(&mut records, &sigs, now.into()).unwrap(),
+            SecurityStatus::Secure
+        );
+        assert_eq!(states(&manager), [KeyState::Revoked, KeyState::Valid]);
+        let anchors = manager.trust_anchors();
+        assert_eq!(
+            anchors.dnskeys(&Name::root()).collect::<Vec<_>>(),
+            [&new.dnskey]
+        );
+
+        // After the remove hold-down time, the key is removed.
+        now += 31 * DAY;
+        let mut records = rrset(vec![new.dnskey.clone()]);
+        let sig = new.sign(&new.dnskey, &mut records, now);
+        manager.observe(&mut records, &[sig], now.into()).unwrap();
+        assert_eq!(states(&manager), [KeyState::Removed, KeyState::Valid]);
+
+        // The state survives a restart.
+        let store = manager.store().clone();
+        let manager = TrustAnchorManager::new(store).unwrap();
+        assert_eq!(states(&manager), [KeyState::Removed, KeyState::Valid]);
+    }
+
+    #[test]
+    fn untrusted() {
+        let old = TestKey::new(1);
+        let new = TestKey::new(2);
+        let other = TestKey::new(3);
+        let now = 1_000_000_000;
+
+        let mut manager =
+            TrustAnchorManager::new(MemoryStore::new()).unwrap();
+        manager
+            .add_anchor(Name::root(), old.dnskey.clone(), now.into())
+            .unwrap();
+
+        // An RRset not signed by a trust anchor is ignored.
+        let mut records = rrset(vec![old.dnskey.clone(), new.dnskey.clone()]);
+        let sig = new.sign(&new.dnskey, &mut records, now);
+        assert!(manager
+            .observe(&mut records, &[sig], now.into())
+            .unwrap()
+            .is_bogus());
+        assert_eq!(states(&manager), [KeyState::Valid]);
+
+        // A revocation needs to be signed by the revoked key.
+        manager
+            .add_anchor(Name::root(), new.dnskey.clone(), now.into())
+            .unwrap();
+        let mut records = rrset(vec![old.revoked(), new.dnskey.clone()]);
+        let sigs = [
+            other.sign(&old.revoked(), &mut records, now),
+            new.sign(&new.dnskey, &mut records, now),
+        ];
+        assert_eq!(
+            manager.observe(&mut records, &sigs, now.into()).unwrap(),
+            SecurityStatus::Secure
+        );
+        assert_eq!(states(&manager), [KeyState::Valid, KeyState::Valid]);
+
+        // Unmanaged zones are indeterminate.
+        let mut records = rrset(vec![old.dnskey.clone()]);
+        records[0] = Record::new(
+            "example.".parse().unwrap(),
+            Class::In,
+            Ttl::from_secs(3600),
+            old.dnskey.clone(),
+        );
+        assert_eq!(
+            manager
+                .observe(
+                    &mut records,
+                    &[] as &[Rrsig<Vec<u8>, Name>],
+                    now.into()
+                )
+                .unwrap(),
+            SecurityStatus::Indeterminate(Reason::NoTrustAnchor)
+        );
+    }
+
+    #[test]
+    fn intervals() {
+        let now = Serial::from(1_000_000_000);
+        assert_eq!(
+            query_interval(Ttl::from_days(2), now.add(10 * DAY), now),
+            Ttl::from_days(1)
+        );
+        assert_eq!(
+            query_interval(Ttl::from_secs(60), now.add(10 * DAY), now),
+            Ttl::from_hours(1)
+        );
+        assert_eq!(
+            query_interval(Ttl::from_days(60), now.add(60 * DAY), now),
+            Ttl::from_days(15)
+        );
+        assert_eq!(
+            retry_interval(Ttl::from_days(2), now.add(10 * DAY), now),
+            Ttl::from_secs(2 * DAY / 10)
+        );
+        assert_eq!(
+            retry_interval(Ttl::from_days(20), now.add(20 * DAY), now),
+            Ttl::from_days(1)
+        );
+    }
+}
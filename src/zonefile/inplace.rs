@@ -14,7 +14,8 @@
 
 use crate::base::charstr::CharStr;
 use crate::base::iana::{Class, Rtype};
-use crate::base::name::{Chain, Dname, RelativeDname, ToDname};
+use crate::base::name::{Chain, Dname, RelativeDname, ToDname, ToLabelIter};
+use crate::base::policy::{self, ParsePolicy, Violation};
 use crate::base::record::Record;
 use crate::base::scan::{
     BadSymbol, ConvertSymbols, EntrySymbol, Scan, Scanner, ScannerError,
@@ -25,8 +26,9 @@ use crate::rdata::ZoneRecordData;
 use bytes::buf::UninitSlice;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use core::str::FromStr;
-use core::{fmt, str};
+use core::{fmt, mem, str};
 use octseq::str::Str;
+use std::vec::Vec;
 
 //------------ Type Aliases --------------------------------------------------
 
@@ -72,6 +74,15 @@ pub struct Zonefile {
 
     /// The last class.
     last_class: Option<Class>,
+
+    /// The policy for questionable records.
+    policy: ParsePolicy,
+
+    /// The owner of the first SOA record, if there was one.
+    apex: Option<ScannedDname>,
+
+    /// The warnings collected since they were last taken.
+    warnings: Vec<Error>,
 }
 
 impl Zonefile {
@@ -95,6 +106,9 @@ impl Zonefile {
             last_owner: None,
             last_ttl: None,
             last_class: None,
+            policy: ParsePolicy::new(),
+            apex: None,
+            warnings: Vec::new(),
         }
     }
 
@@ -166,6 +180,29 @@ impl Zonefile {
         self.origin = Some(origin)
     }
 
+    /// Sets the policy for questionable records.
+    ///
+    /// Records violating the policy are either rejected with an error or
+    /// accepted and reported as a warning that can be retrieved via
+    /// [`take_warnings`][Self::take_warnings]. See the
+    /// [`policy`][crate::base::policy] module for details. Records are
+    /// considered out of zone if their owner is not at or below the owner
+    /// of the first SOA record of this zonefile. Included files have to be
+    /// scanned with a zonefile of their own and don’t know about this
+    /// record.
+    ///
+    /// By default, all records are accepted silently.
+    pub fn set_policy(&mut self, policy: ParsePolicy) {
+        self.policy = policy
+    }
+
+    /// Returns the warnings collected so far.
+    ///
+    /// The warnings are removed from the zonefile.
+    pub fn take_warnings(&mut self) -> Vec<Error> {
+        mem::take(&mut self.warnings)
+    }
+
     /// Returns the next entry in the zonefile.
     ///
     /// Returns `Ok(None)` if the end of the file has been reached. Returns
@@ -363,12 +400,54 @@ impl<'a> EntryScanner<'a> {
         let data = ZoneRecordData::scan(rtype, self)?;
 
         self.zonefile.buf.require_line_feed()?;
+        self.apply_policy(&owner, rtype)?;
 
         Ok(ScannedEntry::Entry(Entry::Record(Record::new(
             owner, class, ttl, data,
         ))))
     }
 
+    /// Applies the policy to a record with the given owner and type.
+    fn apply_policy(
+        &mut self,
+        owner: &ScannedDname,
+        rtype: Rtype,
+    ) -> Result<(), EntryError> {
+        if policy::is_obsolete(rtype, true) {
+            self.check_policy(Violation::ObsoleteType(rtype))?;
+        }
+        match self.zonefile.apex.as_ref() {
+            Some(apex) => {
+                if !owner.ends_with(apex) {
+                    self.check_policy(Violation::OutOfZone)?;
+                }
+            }
+            None => {
+                if rtype == Rtype::Soa {
+                    self.zonefile.apex = Some(owner.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deals with a policy violation.
+    fn check_policy(
+        &mut self,
+        violation: Violation,
+    ) -> Result<(), EntryError> {
+        match self.zonefile.policy.check(violation) {
+            Ok(None) => Ok(()),
+            Ok(Some(violation)) => {
+                let warning =
+                    self.zonefile.buf.error(EntryError::policy(violation));
+                self.zonefile.warnings.push(warning);
+                Ok(())
+            }
+            Err(violation) => Err(EntryError::policy(violation)),
+        }
+    }
+
     /// Scans the TTL, class, and type portions of a regular record.
     fn scan_ctr(
         &mut self,
@@ -565,12 +644,16 @@ impl<'a> Scanner for EntryScanner<'a> {
         // The result will never be longer than the encoded form, so we can
         // trim off everything to the left already.
         self.zonefile.buf.trim_to(self.zonefile.buf.start);
+        let mut write = 0;
 
         // Skip over symbols that don’t need converting at the beginning.
-        while self.zonefile.buf.next_ascii_symbol()?.is_some() {}
+        // We can’t just use the start of the buffer afterwards since a
+        // closing quote has been skipped as well.
+        while self.zonefile.buf.next_ascii_symbol()?.is_some() {
+            write += 1;
+        }
 
         // If we aren’t done yet, we have escaped characters to replace.
-        let mut write = self.zonefile.buf.start;
         while let Some(sym) = self.zonefile.buf.next_symbol()? {
             self.zonefile.buf.buf[write] = sym.into_octet()?;
             write += 1;
@@ -644,9 +727,12 @@ impl<'a> Scanner for EntryScanner<'a> {
                             .chain(self.zonefile.get_origin()?)
                             .map_err(|_| EntryError::bad_dname());
                     } else {
+                        // The label conversion has already moved `write`
+                        // past the length octet of the next label which
+                        // doesn’t exist, so we have to stop at `start`.
                         return unsafe {
                             RelativeDname::from_octets_unchecked(
-                                self.zonefile.buf.split_to(write).freeze(),
+                                self.zonefile.buf.split_to(start).freeze(),
                             )
                             .chain(Dname::root())
                             .map_err(|_| EntryError::bad_dname())
@@ -654,7 +740,21 @@ impl<'a> Scanner for EntryScanner<'a> {
                     }
                 }
                 Some(true) => {
-                    // Last symbol was a dot: check length and continue.
+                    // Last symbol was a dot. If the label was empty, this
+                    // must be the root name which consists of only the dot.
+                    if write == start + 1 {
+                        if start != 0
+                            || self.zonefile.buf.next_symbol()?.is_some()
+                        {
+                            return Err(EntryError::bad_dname());
+                        }
+                        self.zonefile.buf.next_item()?;
+                        return RelativeDname::empty_bytes()
+                            .chain(Dname::root())
+                            .map_err(|_| EntryError::bad_dname());
+                    }
+
+                    // Otherwise check length and continue.
                     if write > 254 {
                         return Err(EntryError::bad_dname());
                     }
@@ -729,6 +829,7 @@ impl<'a> Scanner for EntryScanner<'a> {
         // to be 0, i.e., the start of the buffer. This also means that write
         // will contain the length of the domain name assembled so far, so we
         // can easily check if it has gotten too long.
+        self.zonefile.buf.require_token()?;
         assert!(self.zonefile.buf.start > 0, "missing token prefix space");
         self.zonefile.buf.trim_to(self.zonefile.buf.start - 1);
         let mut write = 0;
@@ -890,7 +991,7 @@ impl<'a> EntryScanner<'a> {
     ) -> Result<(), EntryError> {
         let start = *write;
         *write += 1;
-        let latest = *write + 255; // If write goes here, charstr is too long
+        let latest = *write + 256; // If write goes here, charstr is too long
         if *write == self.zonefile.buf.start {
             // Reading and writing position is equal, so we don’t need to
             // convert char symbols. Read char symbols until the end of label
@@ -1408,7 +1509,7 @@ enum ItemCat {
 //------------ EntryError ----------------------------------------------------
 
 /// An error returned by the entry scanner.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct EntryError(&'static str);
 
 impl EntryError {
@@ -1451,6 +1552,10 @@ impl EntryError {
     fn unknown_control() -> Self {
         EntryError("unknown control")
     }
+
+    fn policy(violation: Violation) -> Self {
+        EntryError(violation.as_str())
+    }
 }
 
 impl ScannerError for EntryError {
@@ -1494,7 +1599,7 @@ impl std::error::Error for EntryError {}
 
 //------------ Error ---------------------------------------------------------
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Error {
     err: EntryError,
     line: usize,
@@ -1589,4 +1694,105 @@ mod test {
             "../../test-data/zonefiles/unknown.yaml"
         ));
     }
+
+    /// Checks that absolute names contain nothing but their labels.
+    #[test]
+    fn absolute_dname() {
+        let mut zone = Zonefile::from("www.example. 3600 IN A 192.0.2.1\n");
+        let record = match zone.next_entry().unwrap() {
+            Some(Entry::Record(record)) => record,
+            _ => panic!(),
+        };
+        let (relative, root) = record.owner().clone().unwrap();
+        assert_eq!(relative.as_slice(), b"\x03www\x07example");
+        assert!(root.is_root());
+        assert_eq!(record.owner().iter_labels().rev().count(), 3);
+    }
+
+    /// Checks the generic syntax for classes and types of RFC 3597.
+    #[test]
+    fn generic_class_and_type() {
+        use std::string::ToString;
+
+        let mut zone = Zonefile::from(
+            "example. 3600 CLASS32 TYPE65280 \\# 2 abcd\n\
+             example. class32 3600 type1 \\# 4 0a000001\n",
+        );
+        zone.set_origin(Dname::root_bytes());
+        let record = match zone.next_entry().unwrap() {
+            Some(Entry::Record(record)) => record,
+            _ => panic!(),
+        };
+        assert_eq!(record.class(), Class::Int(32));
+        assert_eq!(record.rtype(), Rtype::Int(65280));
+        assert_eq!(
+            record.to_string(),
+            "example. 3600 CLASS32 TYPE65280 \\# 2 ab cd"
+        );
+        let record = match zone.next_entry().unwrap() {
+            Some(Entry::Record(record)) => record,
+            _ => panic!(),
+        };
+        assert_eq!(record.class(), Class::Int(32));
+        assert_eq!(record.rtype(), Rtype::A);
+    }
+
+    /// Checks that the policy is applied to records.
+    #[test]
+    fn policy() {
+        use crate::base::policy::Level;
+        use std::string::ToString;
+
+        let data = "example. 3600 IN SOA ns. host. 1 2 3 4 5\n\
+                    www.example. 3600 IN MD mail.example.\n\
+                    other. 3600 IN A 192.0.2.1\n";
+
+        let zone = Zonefile::from(data);
+        assert_eq!(zone.map(Result::unwrap).count(), 3);
+
+        let mut policy = ParsePolicy::new();
+        policy.set_obsolete_types(Level::Warn);
+        policy.set_out_of_zone(Level::Deny);
+        let mut zone = Zonefile::from(data);
+        zone.set_policy(policy);
+        assert!(zone.next_entry().unwrap().is_some());
+        assert!(zone.take_warnings().is_empty());
+        assert!(zone.next_entry().unwrap().is_some());
+        let warnings = zone.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].to_string().ends_with(": obsolete record type"));
+        assert!(zone
+            .next_entry()
+            .unwrap_err()
+            .to_string()
+            .ends_with(": record outside of zone"));
+    }
+
+    /// Checks that displayed records are scanned into the same records.
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn display_round_trip() {
+        use crate::test_support::Arbitrary;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use std::string::String;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..5000 {
+            let record = Record::<
+                Dname<Bytes>,
+                ZoneRecordData<Bytes, Dname<Bytes>>,
+            >::arbitrary(&mut rng);
+            let text = std::format!("{}\n", record);
+            let mut zone = Zonefile::new();
+            zone.extend_from_slice(text.as_bytes());
+            zone.set_origin(Dname::root_bytes());
+            match zone.next_entry() {
+                Ok(Some(Entry::Record(scanned))) => {
+                    assert_eq!(record, scanned, "{}", text)
+                }
+                other => panic!("{}: {:?}", String::from(text.trim()), other),
+            }
+        }
+    }
 }
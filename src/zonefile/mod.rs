@@ -3,3 +3,6 @@
 #![cfg_attr(docsrs, doc(cfg(feature = "zonefile")))]
 
 pub mod inplace;
+#[cfg(all(feature = "rand", feature = "tokio", feature = "tsig"))]
+pub mod secondary;
+pub mod transfer;
@@ -0,0 +1,522 @@
+//! Keeping a secondary copy of a zone up to date.
+//!
+//! A secondary name server receives its copy of a zone from one of the
+//! zone’s primaries via a zone transfer. A [`SecondaryZone`] holds the
+//! list of those primaries for a single zone together with the serial of
+//! the copy currently held.
+//!
+//! Each [`Primary`] has its own address and, optionally, its own TSIG key
+//! for signing the transfer. The primaries are kept in order of
+//! preference. [`SecondaryZone::refresh`] tries them in this order: if the
+//! transfer from a primary fails, whether because it can’t be reached,
+//! the TSIG signatures don’t check out, or the transfer is broken, the next
+//! primary is tried. The primary that last provided the zone is available
+//! via [`SecondaryZone::last_primary`].
+//!
+//! A transfer is only completed if the primary has a newer version of the
+//! zone than the one held. Its records are written as a zonefile via an
+//! [`AxfrToZonefile`] as they arrive.
+
+use super::transfer::{AxfrToZonefile, TransferError};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname};
+use crate::base::Serial;
+use crate::rdata::tsig::Time48;
+use crate::rdata::Soa;
+use crate::tsig::{ClientSequence, Key, ValidationError};
+use crate::utils::stream::MessageStream;
+use bytes::Bytes;
+use core::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec::Vec;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The default time to wait for a primary.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+//------------ Primary -------------------------------------------------------
+
+/// A primary to transfer a zone from.
+#[derive(Clone, Debug)]
+pub struct Primary {
+    /// The address to connect to.
+    addr: SocketAddr,
+
+    /// The TSIG key to sign the transfer with, if any.
+    key: Option<Arc<Key>>,
+}
+
+impl Primary {
+    /// Creates a primary transferring the zone without TSIG.
+    pub fn new(addr: SocketAddr) -> Self {
+        Primary { addr, key: None }
+    }
+
+    /// Creates a primary transferring the zone signed with `key`.
+    pub fn with_key(addr: SocketAddr, key: Arc<Key>) -> Self {
+        Primary {
+            addr,
+            key: Some(key),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn key(&self) -> Option<&Arc<Key>> {
+        self.key.as_ref()
+    }
+}
+
+//------------ SecondaryZone -------------------------------------------------
+
+/// A zone received from a list of primaries.
+#[derive(Clone, Debug)]
+pub struct SecondaryZone {
+    /// The apex of the zone.
+    apex: Dname<Bytes>,
+
+    /// The primaries in order of preference.
+    primaries: Vec<Primary>,
+
+    /// The time to wait for a primary when connecting or receiving.
+    timeout: Duration,
+
+    /// The serial of the copy held.
+    serial: Option<Serial>,
+
+    /// The index of the primary that last provided the zone.
+    last_primary: Option<usize>,
+}
+
+impl SecondaryZone {
+    /// Creates a new zone without primaries and without a copy.
+    pub fn new(apex: Dname<Bytes>) -> Self {
+        SecondaryZone {
+            apex,
+            primaries: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            serial: None,
+            last_primary: None,
+        }
+    }
+
+    pub fn apex(&self) -> &Dname<Bytes> {
+        &self.apex
+    }
+
+    /// Returns the primaries in order of preference.
+    pub fn primaries(&self) -> &[Primary] {
+        &self.primaries
+    }
+
+    /// Adds a primary less preferred than all the ones added before.
+    pub fn push_primary(&mut self, primary: Primary) {
+        self.primaries.push(primary)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the time to wait for a primary.
+    ///
+    /// The timeout applies to connecting as well as to each message of
+    /// the transfer separately.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout
+    }
+
+    /// Returns the serial of the copy held, if any.
+    pub fn serial(&self) -> Option<Serial> {
+        self.serial
+    }
+
+    /// Sets the serial of the copy held.
+    ///
+    /// This is useful when the zone has been loaded from a zonefile
+    /// written earlier.
+    pub fn set_serial(&mut self, serial: Option<Serial>) {
+        self.serial = serial
+    }
+
+    /// Returns the primary that last provided the zone.
+    pub fn last_primary(&self) -> Option<&Primary> {
+        self.last_primary.map(|index| &self.primaries[index])
+    }
+
+    /// Refreshes the zone from the first primary that can provide it.
+    ///
+    /// The primaries are tried in order of preference. For each transfer
+    /// attempted, `target` is called to produce the writer for the
+    /// zonefile. If a transfer fails, the content written so far should be
+    /// discarded. If the primary doesn’t have a newer serial than the copy
+    /// held, `target` isn’t called and [`Refresh::UpToDate`] is returned.
+    ///
+    /// If none of the primaries can provide the zone, returns the errors
+    /// encountered for each of them.
+    pub async fn refresh<W, F>(
+        &mut self,
+        mut target: F,
+    ) -> Result<Refresh<W>, RefreshError>
+    where
+        W: io::Write,
+        F: FnMut() -> W,
+    {
+        let mut errors = Vec::new();
+        for index in 0..self.primaries.len() {
+            let primary = &self.primaries[index];
+            match self.transfer(primary, &mut target).await {
+                Ok(Some((target, serial))) => {
+                    self.serial = Some(serial);
+                    self.last_primary = Some(index);
+                    return Ok(Refresh::Transferred(target));
+                }
+                Ok(None) => return Ok(Refresh::UpToDate),
+                Err(err) => errors.push((primary.addr, err)),
+            }
+        }
+        Err(RefreshError { errors })
+    }
+
+    /// Transfers the zone from a single primary.
+    ///
+    /// Returns `Ok(None)` if the primary doesn’t have a newer version.
+    async fn transfer<W, F>(
+        &self,
+        primary: &Primary,
+        target: F,
+    ) -> Result<Option<(W, Serial)>, PrimaryError>
+    where
+        W: io::Write,
+        F: FnOnce() -> W,
+    {
+        let mut request = MessageBuilder::new_stream_vec()
+            .request_axfr(&self.apex)
+            .map_err(TransferError::from)?
+            .additional();
+        let id = request.header().id();
+        let mut tsig = match primary.key.clone() {
+            Some(key) => Some(
+                ClientSequence::request(key, &mut request, Time48::now())
+                    .map_err(TransferError::from)?,
+            ),
+            None => None,
+        };
+
+        let mut sock =
+            timeout(self.timeout, TcpStream::connect(primary.addr))
+                .await
+                .map_err(|_| PrimaryError::Timeout)?
+                .map_err(TransferError::from)?;
+        sock.write_all(request.as_target().as_stream_slice())
+            .await
+            .map_err(TransferError::from)?;
+        let mut stream = MessageStream::new(sock);
+
+        let mut target = Some(target);
+        let mut writer = None;
+        loop {
+            let msg = timeout(self.timeout, stream.next_message())
+                .await
+                .map_err(|_| PrimaryError::Timeout)?
+                .map_err(TransferError::from)?
+                .ok_or_else(|| {
+                    TransferError::Io(io::ErrorKind::UnexpectedEof.into())
+                })?;
+            let mut msg = Message::from_octets(Vec::from(msg.as_ref()))
+                .map_err(|_| PrimaryError::BadResponse)?;
+            if !msg.header().qr() || msg.header().id() != id {
+                return Err(PrimaryError::BadResponse);
+            }
+            if let Some(tsig) = tsig.as_mut() {
+                tsig.answer(&mut msg, Time48::now())?;
+            }
+            let (writer, _) = match writer.as_mut() {
+                Some(writer) => writer,
+                None => {
+                    let serial = Self::first_serial(&msg)?;
+                    if let Some(current) = self.serial {
+                        if serial <= current {
+                            return Ok(None);
+                        }
+                    }
+                    // We only get here once, so the target is still there.
+                    let target = target.take().unwrap();
+                    writer.insert((AxfrToZonefile::new(target()), serial))
+                }
+            };
+            if writer.push_message(&msg)? {
+                break;
+            }
+        }
+        if let Some(tsig) = tsig {
+            tsig.done()?;
+        }
+        Ok(writer.map(|(writer, serial)| (writer.into_inner(), serial)))
+    }
+
+    /// Returns the serial from the first message of a transfer.
+    fn first_serial(msg: &Message<Vec<u8>>) -> Result<Serial, PrimaryError> {
+        if msg.header().rcode() != Rcode::NoError {
+            return Err(TransferError::Rcode(msg.header().rcode()).into());
+        }
+        let record = msg
+            .answer()
+            .map_err(TransferError::from)?
+            .next()
+            .ok_or(TransferError::MissingSoa)?
+            .map_err(TransferError::from)?;
+        if record.rtype() != Rtype::Soa {
+            return Err(TransferError::MissingSoa.into());
+        }
+        let record = record
+            .to_record::<Soa<ParsedDname<_>>>()
+            .map_err(TransferError::from)?
+            .ok_or(TransferError::MissingSoa)?;
+        Ok(record.data().serial())
+    }
+}
+
+//------------ Refresh -------------------------------------------------------
+
+/// The outcome of a successful refresh.
+#[derive(Clone, Debug)]
+pub enum Refresh<W> {
+    /// The primary didn’t have a newer version of the zone.
+    UpToDate,
+
+    /// The zone was transferred into the given writer.
+    Transferred(W),
+}
+
+//============ Error Types ===================================================
+
+//------------ PrimaryError --------------------------------------------------
+
+/// Transferring a zone from a primary failed.
+#[derive(Debug)]
+pub enum PrimaryError {
+    /// The primary didn’t respond in time.
+    Timeout,
+
+    /// The primary sent a message that isn’t a response to the request.
+    BadResponse,
+
+    /// The TSIG signatures of the transfer were invalid.
+    Tsig(ValidationError),
+
+    /// The transfer itself failed.
+    Transfer(TransferError),
+}
+
+impl From<ValidationError> for PrimaryError {
+    fn from(err: ValidationError) -> Self {
+        PrimaryError::Tsig(err)
+    }
+}
+
+impl From<TransferError> for PrimaryError {
+    fn from(err: TransferError) -> Self {
+        PrimaryError::Transfer(err)
+    }
+}
+
+impl fmt::Display for PrimaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PrimaryError::Timeout => f.write_str("primary timed out"),
+            PrimaryError::BadResponse => f.write_str("bad response"),
+            PrimaryError::Tsig(ref err) => err.fmt(f),
+            PrimaryError::Transfer(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PrimaryError {}
+
+//------------ RefreshError --------------------------------------------------
+
+/// None of the primaries could provide the zone.
+#[derive(Debug)]
+pub struct RefreshError {
+    /// The error for each primary tried.
+    errors: Vec<(SocketAddr, PrimaryError)>,
+}
+
+impl RefreshError {
+    /// Returns the errors for each primary in order of preference.
+    pub fn errors(&self) -> &[(SocketAddr, PrimaryError)] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.errors.is_empty() {
+            return f.write_str("no primaries");
+        }
+        f.write_str("all primaries failed")?;
+        for (addr, err) in &self.errors {
+            write!(f, "; {}: {}", addr, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RefreshError {}
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::{Record, Ttl};
+    use crate::rdata::A;
+    use crate::tsig::{Algorithm, ServerSequence};
+    use core::str::FromStr;
+    use std::string::String;
+    use tokio::net::TcpListener;
+
+    fn make_key(name: &str) -> Arc<Key> {
+        Arc::new(
+            Key::new(
+                Algorithm::Sha256,
+                b"secret",
+                FromStr::from_str(name).unwrap(),
+                None,
+                None,
+            )
+            .unwrap(),
+        )
+    }
+
+    /// Starts a primary serving the zone with the given serial.
+    ///
+    /// If `key` is given, requests must be signed with it.
+    async fn primary(serial: u32, key: Option<Arc<Key>>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                let key = key.clone();
+                tokio::spawn(async move {
+                    let mut stream = MessageStream::new(&mut sock);
+                    let msg = stream.next_message().await.unwrap().unwrap();
+                    let mut request =
+                        Message::from_octets(Vec::from(msg.as_ref()))
+                            .unwrap();
+                    let mut tsig = match key {
+                        Some(key) => {
+                            match ServerSequence::request(
+                                &key,
+                                &mut request,
+                                Time48::now(),
+                            ) {
+                                Ok(Some(tsig)) => Some(tsig),
+                                _ => return,
+                            }
+                        }
+                        None => None,
+                    };
+                    let apex =
+                        Dname::<Bytes>::from_str("example.com").unwrap();
+                    let soa = Record::new(
+                        apex.clone(),
+                        Class::In,
+                        Ttl::HOUR,
+                        Soa::new(
+                            apex.clone(),
+                            apex.clone(),
+                            Serial(serial),
+                            Ttl::HOUR,
+                            Ttl::HOUR,
+                            Ttl::HOUR,
+                            Ttl::HOUR,
+                        ),
+                    );
+                    let mut answer = MessageBuilder::new_stream_vec()
+                        .start_answer(&request, Rcode::NoError)
+                        .unwrap();
+                    answer.push(soa.clone()).unwrap();
+                    answer
+                        .push((
+                            apex.clone(),
+                            Class::In,
+                            3600,
+                            A::from_octets(192, 0, 2, 1),
+                        ))
+                        .unwrap();
+                    answer.push(soa).unwrap();
+                    let mut answer = answer.additional();
+                    if let Some(tsig) = tsig.as_mut() {
+                        tsig.answer(&mut answer, Time48::now()).unwrap();
+                    }
+                    sock.write_all(answer.as_target().as_stream_slice())
+                        .await
+                        .unwrap();
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn fall_back_to_next_primary() {
+        let key = make_key("key.example");
+        let wrong = make_key("other.example");
+        let unreachable = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut zone =
+            SecondaryZone::new(Dname::from_str("example.com").unwrap());
+        zone.set_timeout(Duration::from_secs(1));
+        zone.push_primary(Primary::new(unreachable));
+        zone.push_primary(Primary::with_key(
+            primary(1, Some(key.clone())).await,
+            wrong,
+        ));
+        zone.push_primary(Primary::with_key(
+            primary(2, Some(key.clone())).await,
+            key,
+        ));
+        zone.push_primary(Primary::new(primary(3, None).await));
+
+        let text = match zone.refresh(Vec::new).await.unwrap() {
+            Refresh::Transferred(text) => String::from_utf8(text).unwrap(),
+            Refresh::UpToDate => panic!("no transfer"),
+        };
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("192.0.2.1"));
+        assert_eq!(zone.serial(), Some(Serial(2)));
+        assert_eq!(
+            zone.last_primary().unwrap().addr(),
+            zone.primaries()[2].addr()
+        );
+        let res = zone.refresh(Vec::new).await;
+        assert!(matches!(res, Ok(Refresh::UpToDate)));
+
+        // All failing primaries are reported.
+        let mut zone =
+            SecondaryZone::new(Dname::from_str("example.com").unwrap());
+        zone.push_primary(Primary::new(unreachable));
+        let err = zone.refresh(Vec::new).await.unwrap_err();
+        assert_eq!(err.errors().len(), 1);
+        assert!(zone.last_primary().is_none());
+    }
+}
@@ -0,0 +1,438 @@
+//! Streaming conversion between zone transfers and zonefiles.
+//!
+//! This module provides two adapters that move a zone between the wire
+//! and a zonefile one record at a time. Neither collects the parsed
+//! records of the zone, which makes them suitable for very large zones
+//! on constrained hosts.
+//!
+//! [`AxfrToZonefile`] takes the response messages of an AXFR transfer as
+//! they arrive and writes their records in presentation format to a
+//! writer. [`ZonefileToAxfr`] goes the other way: it reads records from a
+//! [`Zonefile`] and produces the sequence of response messages answering
+//! an AXFR request.
+//!
+//! When receiving a transfer over TCP, the `MessageStream` type from the
+//! `utils::stream` module reads the response messages one by one as they
+//! arrive, ready to be pushed into an [`AxfrToZonefile`].
+
+use super::inplace::{self, Entry, ScannedRecord, Zonefile};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{
+    AnswerBuilder, MessageBuilder, PushError, StaticCompressor,
+};
+use crate::base::name::ParsedDname;
+use crate::base::wire::ParseError;
+use crate::rdata::AllRecordData;
+use bytes::{Bytes, BytesMut};
+use core::fmt;
+use octseq::Octets;
+use std::io;
+use std::vec::Vec;
+
+//------------ AxfrToZonefile ------------------------------------------------
+
+/// Writes the records of an AXFR transfer into a zonefile.
+///
+/// Feed the response messages of the transfer in order to
+/// [`push_message`][Self::push_message]. Each record is written on a line
+/// of its own in presentation format with an absolute owner name. The
+/// SOA record that closes the transfer is not written again.
+#[derive(Debug)]
+pub struct AxfrToZonefile<W> {
+    /// The writer the zonefile goes to.
+    target: W,
+
+    /// The number of records written so far.
+    records: usize,
+
+    /// Whether the closing SOA record has been seen.
+    complete: bool,
+}
+
+impl<W: io::Write> AxfrToZonefile<W> {
+    /// Creates a new converter writing to `target`.
+    pub fn new(target: W) -> Self {
+        AxfrToZonefile {
+            target,
+            records: 0,
+            complete: false,
+        }
+    }
+
+    /// Writes the records of the next response message of the transfer.
+    ///
+    /// Returns whether the transfer is complete, i.e., whether the
+    /// message contained the closing SOA record.
+    pub fn push_message<Octs: Octets>(
+        &mut self,
+        msg: &Message<Octs>,
+    ) -> Result<bool, TransferError> {
+        if msg.header().rcode() != Rcode::NoError {
+            return Err(TransferError::Rcode(msg.header().rcode()));
+        }
+        for record in msg.answer()? {
+            if self.complete {
+                return Err(TransferError::Malformed);
+            }
+            let record = record?;
+            let record = match record
+                .to_record::<AllRecordData<_, ParsedDname<_>>>()?
+            {
+                Some(record) => record,
+                None => continue,
+            };
+            if record.rtype() == Rtype::Soa {
+                if self.records > 0 {
+                    self.complete = true;
+                    continue;
+                }
+            } else if self.records == 0 {
+                return Err(TransferError::MissingSoa);
+            }
+            writeln!(self.target, "{}", record)?;
+            self.records += 1;
+        }
+        Ok(self.complete)
+    }
+
+    /// Returns the number of records written so far.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Returns whether the closing SOA record has been seen.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns a reference to the writer.
+    pub fn get_ref(&self) -> &W {
+        &self.target
+    }
+
+    /// Converts the value into the writer.
+    pub fn into_inner(self) -> W {
+        self.target
+    }
+}
+
+//------------ ZonefileToAxfr ------------------------------------------------
+
+/// Produces the response messages of an AXFR transfer from a zonefile.
+///
+/// The value is an iterator over the response messages answering
+/// `request`. The first record of the zonefile has to be the SOA record
+/// of the zone. It starts the first message and is repeated at the end of
+/// the last. Each message is filled with as many records as fit into
+/// `max_size` octets.
+///
+/// Include directives are not followed and result in an error.
+#[derive(Clone)]
+pub struct ZonefileToAxfr<Octs> {
+    /// The zonefile to read records from.
+    zonefile: Zonefile,
+
+    /// The request to answer.
+    request: Message<Octs>,
+
+    /// The maximum size of a response message.
+    max_size: usize,
+
+    /// The SOA record of the zone once it has been read.
+    soa: Option<ScannedRecord>,
+
+    /// A record that didn’t fit into the previous message.
+    pending: Option<ScannedRecord>,
+
+    /// Whether the end of the zonefile has been reached.
+    eof: bool,
+
+    /// Whether the closing SOA record has been added to a message.
+    done: bool,
+}
+
+impl<Octs: Octets> ZonefileToAxfr<Octs> {
+    /// Creates a new converter for the given zonefile and request.
+    pub fn new(
+        zonefile: Zonefile,
+        request: Message<Octs>,
+        max_size: usize,
+    ) -> Self {
+        ZonefileToAxfr {
+            zonefile,
+            request,
+            max_size,
+            soa: None,
+            pending: None,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Returns the next response message.
+    ///
+    /// Returns `Ok(None)` once all messages have been produced.
+    pub fn next_message(
+        &mut self,
+    ) -> Result<Option<Message<Bytes>>, TransferError> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut builder = MessageBuilder::from_target(StaticCompressor::new(
+            BytesMut::new(),
+        ))
+        .map_err(PushError::from)?;
+        builder.header_mut().set_aa(true);
+        let mut builder =
+            builder.start_answer(&self.request, Rcode::NoError)?;
+        if self.soa.is_none() {
+            let soa = match self.next_record()? {
+                Some(record) if record.rtype() == Rtype::Soa => record,
+                _ => return Err(TransferError::MissingSoa),
+            };
+            self.push(&mut builder, soa.clone())?;
+            self.soa = Some(soa);
+        }
+        loop {
+            let record = match self.pending.take() {
+                Some(record) => record,
+                None if self.eof => break,
+                None => match self.next_record()? {
+                    Some(record) => record,
+                    None => {
+                        self.eof = true;
+                        self.soa.clone().ok_or(TransferError::MissingSoa)?
+                    }
+                },
+            };
+            if !self.push(&mut builder, record)? {
+                return Ok(Some(finish(builder)));
+            }
+        }
+        self.done = true;
+        Ok(Some(finish(builder)))
+    }
+
+    /// Returns the next record of the zonefile.
+    fn next_record(
+        &mut self,
+    ) -> Result<Option<ScannedRecord>, TransferError> {
+        match self.zonefile.next_entry()? {
+            Some(Entry::Record(record)) => Ok(Some(record)),
+            Some(Entry::Include { .. }) => Err(TransferError::Include),
+            None => Ok(None),
+        }
+    }
+
+    /// Adds a record to the message if it still fits.
+    ///
+    /// If it doesn’t, keeps the record for the next message and returns
+    /// `Ok(false)`. The size is checked against the uncompressed length
+    /// of the record, so messages may end up a little smaller than
+    /// possible.
+    fn push(
+        &mut self,
+        builder: &mut AnswerBuilder<StaticCompressor<BytesMut>>,
+        record: ScannedRecord,
+    ) -> Result<bool, TransferError> {
+        let len = record.compose_len().unwrap_or_else(|| {
+            let mut buf = Vec::new();
+            let _ = record.compose(&mut buf);
+            buf.len()
+        });
+        if builder.as_slice().len() + len > self.max_size {
+            if builder.counts().ancount() == 0 {
+                return Err(TransferError::TooLarge);
+            }
+            self.pending = Some(record);
+            return Ok(false);
+        }
+        builder.push(&record)?;
+        Ok(true)
+    }
+}
+
+impl<Octs: Octets> Iterator for ZonefileToAxfr<Octs> {
+    type Item = Result<Message<Bytes>, TransferError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Converts the builder into the final message.
+fn finish(
+    builder: AnswerBuilder<StaticCompressor<BytesMut>>,
+) -> Message<Bytes> {
+    Message::from_octets(builder.finish().into_target().freeze())
+        .expect("message builder produced short message")
+}
+
+//============ Error Types ===================================================
+
+//------------ TransferError -------------------------------------------------
+
+/// Converting between a zone transfer and a zonefile failed.
+#[derive(Debug)]
+pub enum TransferError {
+    /// A response message of the transfer had an error rcode.
+    Rcode(Rcode),
+
+    /// The transfer or zonefile didn’t start with an SOA record.
+    MissingSoa,
+
+    /// There were records after the closing SOA record.
+    Malformed,
+
+    /// The zonefile contained an include directive.
+    Include,
+
+    /// A record didn’t fit into a message on its own.
+    TooLarge,
+
+    /// A response message couldn’t be parsed.
+    Parse(ParseError),
+
+    /// The zonefile couldn’t be parsed.
+    Zonefile(inplace::Error),
+
+    /// Writing the zonefile failed.
+    Io(io::Error),
+}
+
+impl From<ParseError> for TransferError {
+    fn from(err: ParseError) -> Self {
+        TransferError::Parse(err)
+    }
+}
+
+impl From<inplace::Error> for TransferError {
+    fn from(err: inplace::Error) -> Self {
+        TransferError::Zonefile(err)
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(err: io::Error) -> Self {
+        TransferError::Io(err)
+    }
+}
+
+impl From<PushError> for TransferError {
+    fn from(_: PushError) -> Self {
+        TransferError::TooLarge
+    }
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransferError::Rcode(rcode) => {
+                write!(f, "transfer failed with rcode {}", rcode)
+            }
+            TransferError::MissingSoa => f.write_str("missing SOA record"),
+            TransferError::Malformed => {
+                f.write_str("records after closing SOA record")
+            }
+            TransferError::Include => {
+                f.write_str("include directives not supported")
+            }
+            TransferError::TooLarge => f.write_str("record too large"),
+            TransferError::Parse(ref err) => err.fmt(f),
+            TransferError::Zonefile(ref err) => err.fmt(f),
+            TransferError::Io(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use core::str::FromStr;
+    use std::string::String;
+
+    const ZONE: &str = "\
+        $ORIGIN example.com.\n\
+        @ 3600 IN SOA ns1 hostmaster 1 3600 600 86400 300\n\
+        @ 3600 IN NS ns1\n\
+        ns1 3600 IN A 192.0.2.1\n\
+        www 3600 IN A 192.0.2.2\n\
+        www 3600 IN AAAA 2001:db8::2\n\
+        mail 3600 IN MX 10 www\n\
+    ";
+
+    fn request() -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::Axfr,
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    fn transfer(max_size: usize) -> (usize, String) {
+        let axfr =
+            ZonefileToAxfr::new(Zonefile::from(ZONE), request(), max_size);
+        let mut writer = AxfrToZonefile::new(Vec::new());
+        let mut messages = 0;
+        for msg in axfr {
+            let msg = msg.unwrap();
+            assert!(msg.as_slice().len() <= max_size);
+            assert!(!writer.is_complete());
+            writer.push_message(&msg).unwrap();
+            messages += 1;
+        }
+        assert!(writer.is_complete());
+        assert_eq!(writer.records(), 6);
+        (messages, String::from_utf8(writer.into_inner()).unwrap())
+    }
+
+    #[test]
+    fn round_trip() {
+        let (messages, text) = transfer(512);
+        assert_eq!(messages, 1);
+        let (messages, small) = transfer(150);
+        assert!(messages > 1);
+        assert_eq!(text, small);
+
+        let mut lines = text.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("example.com. 3600 IN SOA"));
+        assert_eq!(lines.count(), 5);
+        let records = Zonefile::from(text.as_str())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 6);
+    }
+
+    #[test]
+    fn errors() {
+        let zone = "example.com. 3600 IN A 192.0.2.1\n";
+        let mut axfr =
+            ZonefileToAxfr::new(Zonefile::from(zone), request(), 512);
+        assert!(matches!(axfr.next(), Some(Err(TransferError::MissingSoa))));
+
+        let mut axfr =
+            ZonefileToAxfr::new(Zonefile::from(ZONE), request(), 40);
+        assert!(matches!(axfr.next(), Some(Err(TransferError::TooLarge))));
+
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rcode(Rcode::NotAuth);
+        let mut writer = AxfrToZonefile::new(Vec::new());
+        assert!(matches!(
+            writer.push_message(&msg.into_message()),
+            Err(TransferError::Rcode(Rcode::NotAuth))
+        ));
+    }
+}
@@ -0,0 +1,1050 @@
+//! Answering queries from zone data.
+//!
+//! This module implements the algorithm for answering queries from
+//! authoritative data described in section 4.3.2 of RFC 1034 with the
+//! clarifications for wildcards of RFC 4592 and for negative answers of
+//! RFC 2308 and RFC 6604.
+
+use super::zone::{AnyPolicy, Node, Rrset, SharedRrset, StoredDname, Zone};
+use crate::base::charstr::CharStr;
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{
+    MessageBuilder, PushError, StaticCompressor,
+};
+use crate::base::name::{Label, ToDname};
+use crate::base::record::{Record, Ttl};
+use crate::rdata::{Hinfo, ZoneRecordData};
+use crate::utils::rotate::Rotator;
+use bytes::{Bytes, BytesMut};
+use octseq::Octets;
+use std::sync::Arc;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum number of CNAME records followed within a zone.
+///
+/// This protects against CNAME loops within the zone data.
+const MAX_CNAME_CHAIN: usize = 16;
+
+/// The maximum number of RRsets added to the additional section.
+///
+/// This limits the work done for answers with many NS, MX, or SRV records.
+const MAX_ADDITIONAL: usize = 32;
+
+//------------ Answer --------------------------------------------------------
+
+/// The answer to a query produced from the data of a zone.
+///
+/// The answer contains the RRsets for the answer, authority, and
+/// additional sections of a response. Since an RRset synthesized from a
+/// wildcard has the query name as its owner, each RRset is accompanied by
+/// its owner name.
+///
+/// Use [`to_message`][Self::to_message] to turn the answer into a
+/// response message.
+#[derive(Clone, Debug)]
+pub struct Answer {
+    /// The response code of the answer.
+    rcode: Rcode,
+
+    /// Whether the answer is authoritative.
+    ///
+    /// This is only `false` for referrals and queries outside the zone.
+    authoritative: bool,
+
+    /// The class of the zone the answer was produced from.
+    class: Class,
+
+    /// The content of the answer section.
+    answer: Vec<(StoredDname, SharedRrset)>,
+
+    /// The content of the authority section.
+    authority: Vec<(StoredDname, SharedRrset)>,
+
+    /// The content of the additional section.
+    ///
+    /// The RRsets are ordered by priority. If a response is too large,
+    /// they are left out from the end.
+    additional: Vec<(StoredDname, SharedRrset)>,
+
+    /// The number of RRsets at the start of `additional` that are required.
+    ///
+    /// These are the glue records for name servers within a delegated
+    /// zone. A response that can’t include them is truncated.
+    required: usize,
+
+    /// What needs to be proven if the answer is signed.
+    denials: Vec<Denial>,
+}
+
+impl Answer {
+    /// Creates a new, empty answer with the given rcode.
+    pub fn new(rcode: Rcode, class: Class) -> Self {
+        Answer {
+            rcode,
+            authoritative: true,
+            class,
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+            required: 0,
+            denials: Vec::new(),
+        }
+    }
+
+    /// Creates an answer for a query that isn’t for data in the zone.
+    pub fn refused(class: Class) -> Self {
+        let mut res = Self::new(Rcode::Refused, class);
+        res.authoritative = false;
+        res
+    }
+
+    /// Produces the answer to a query from the data of a zone.
+    pub(super) fn from_zone<N: ToDname + ?Sized>(
+        zone: &Zone,
+        qname: &N,
+        qtype: Rtype,
+    ) -> Self {
+        let mut qname: StoredDname = match qname.to_dname() {
+            Ok(qname) => qname,
+            Err(_) => return Self::refused(zone.class()),
+        };
+        let mut res = Self::new(Rcode::NoError, zone.class());
+        for _ in 0..MAX_CNAME_CHAIN {
+            let (node, closest) = match lookup(zone, &qname, qtype) {
+                Lookup::Node(node) => (node, None),
+                Lookup::Wildcard(node, closest) => (node, Some(closest)),
+                Lookup::Cut(owner, node) => {
+                    res.referral(zone, owner, node);
+                    return res;
+                }
+                Lookup::NxDomain(closest, node) => {
+                    res.negative(zone, Rcode::NXDomain);
+                    res.denials.push(Denial::NxDomain {
+                        qname,
+                        closest,
+                        closest_types: rtypes(node),
+                    });
+                    return res;
+                }
+                Lookup::OutOfZone => {
+                    if res.answer.is_empty() {
+                        return Self::refused(zone.class());
+                    }
+                    return res;
+                }
+            };
+            let target = res.answer_node(zone, node, &qname, qtype);
+            if let Some(closest) = closest {
+                res.push_wildcard_denial(zone, node, qname, closest);
+            }
+            match target {
+                Some(target) => qname = target,
+                None => return res,
+            }
+        }
+        res
+    }
+
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    pub fn is_authoritative(&self) -> bool {
+        self.authoritative
+    }
+
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    pub fn answer(&self) -> &[(StoredDname, SharedRrset)] {
+        &self.answer
+    }
+
+    /// Reorders the records of the RRsets in the answer section.
+    ///
+    /// This is done by [`Zone::query`] according to the zone’s rotation
+    /// already. RRsets shared with the zone are copied before they are
+    /// reordered.
+    pub fn rotate(&mut self, rotator: &Rotator) {
+        if rotator.is_fixed() {
+            return;
+        }
+        for (_, rrset) in &mut self.answer {
+            if rrset.data().len() > 1 {
+                Arc::make_mut(rrset).rotate(rotator)
+            }
+        }
+    }
+
+    pub fn authority(&self) -> &[(StoredDname, SharedRrset)] {
+        &self.authority
+    }
+
+    pub fn additional(&self) -> &[(StoredDname, SharedRrset)] {
+        &self.additional
+    }
+
+    /// Sets whether the answer is authoritative.
+    pub(super) fn set_authoritative(&mut self, authoritative: bool) {
+        self.authoritative = authoritative
+    }
+
+    /// Returns what needs to be proven if the answer is signed.
+    pub(super) fn denials(&self) -> &[Denial] {
+        &self.denials
+    }
+
+    /// Returns the answer, authority, and additional sections for changing.
+    pub(super) fn sections_mut(
+        &mut self,
+    ) -> [&mut Vec<(StoredDname, SharedRrset)>; 3] {
+        [&mut self.answer, &mut self.authority, &mut self.additional]
+    }
+
+    /// Creates a response message for the given request.
+    ///
+    /// The response copies ID, opcode, RD flag, and question from the
+    /// request. If the response would exceed `max_size`, RRsets are left
+    /// out of the additional section starting from the end. If even the
+    /// required glue doesn’t fit, the response has the TC flag set and
+    /// contains no records at all.
+    ///
+    /// The response doesn’t contain an OPT record even if the request
+    /// did.
+    pub fn to_message<Octs: Octets + ?Sized>(
+        &self,
+        request: &Message<Octs>,
+        max_size: usize,
+    ) -> Message<Bytes> {
+        (self.required..=self.additional.len())
+            .rev()
+            .find_map(|additional| {
+                self.compose(request, max_size, additional)
+            })
+            .unwrap_or_else(|| self.truncated(request))
+    }
+
+    /// Tries to compose a response within the size limit.
+    ///
+    /// Only the first `additional` RRsets of the additional section are
+    /// included.
+    fn compose<Octs: Octets + ?Sized>(
+        &self,
+        request: &Message<Octs>,
+        max_size: usize,
+        additional: usize,
+    ) -> Option<Message<Bytes>> {
+        let mut builder = MessageBuilder::from_target(StaticCompressor::new(
+            BytesMut::new(),
+        ))
+        .ok()?;
+        builder.header_mut().set_aa(self.authoritative);
+        let mut builder = builder.start_answer(request, self.rcode).ok()?;
+        push_rrsets(&self.answer, self.class, |record| builder.push(record))
+            .ok()?;
+        let mut builder = builder.authority();
+        push_rrsets(&self.authority, self.class, |record| {
+            builder.push(record)
+        })
+        .ok()?;
+        let mut builder = builder.additional();
+        push_rrsets(&self.additional[..additional], self.class, |record| {
+            builder.push(record)
+        })
+        .ok()?;
+        let octets = builder.finish().into_target().freeze();
+        if octets.len() > max_size {
+            return None;
+        }
+        Message::from_octets(octets).ok()
+    }
+
+    /// Creates a truncated response without any records.
+    fn truncated<Octs: Octets + ?Sized>(
+        &self,
+        request: &Message<Octs>,
+    ) -> Message<Bytes> {
+        let mut builder = MessageBuilder::new_bytes();
+        builder.header_mut().set_aa(self.authoritative);
+        builder.header_mut().set_tc(true);
+        match builder.start_answer(request, self.rcode) {
+            Ok(builder) => builder.into_message(),
+            Err(_) => {
+                let mut builder = MessageBuilder::new_bytes();
+                builder.header_mut().set_id(request.header().id());
+                builder.header_mut().set_qr(true);
+                builder.header_mut().set_tc(true);
+                builder.into_message()
+            }
+        }
+    }
+
+    /// Answers the query from the node for the query name.
+    ///
+    /// Returns the target of a CNAME to continue with if there is one.
+    fn answer_node(
+        &mut self,
+        zone: &Zone,
+        node: &Node,
+        qname: &StoredDname,
+        qtype: Rtype,
+    ) -> Option<StoredDname> {
+        if qtype == Rtype::Any {
+            self.answer_any(zone, node, qname);
+            return None;
+        }
+        if let Some(rrset) = node.rrset(qtype) {
+            self.answer.push((qname.clone(), rrset.clone()));
+            self.add_additional(zone, self.answer.len() - 1);
+            return None;
+        }
+        if let Some(rrset) = node.rrset(Rtype::Cname) {
+            self.answer.push((qname.clone(), rrset.clone()));
+            return match rrset.first() {
+                Some(ZoneRecordData::Cname(cname)) => {
+                    Some(cname.cname().clone())
+                }
+                _ => None,
+            };
+        }
+        self.nodata(zone, node, qname);
+        None
+    }
+
+    /// Answers a query for QTYPE ANY according to the zone’s policy.
+    fn answer_any(&mut self, zone: &Zone, node: &Node, qname: &StoredDname) {
+        let start = self.answer.len();
+        match zone.any_policy() {
+            AnyPolicy::All => {
+                self.answer.extend(
+                    node.rrsets().map(|rrset| (qname.clone(), rrset.clone())),
+                );
+            }
+            AnyPolicy::Hinfo => {
+                if node.rrsets().next().is_some() {
+                    self.answer.push((qname.clone(), rfc8482_hinfo()));
+                }
+            }
+            AnyPolicy::OneRrset => {
+                if let Some(rrset) =
+                    node.rrsets().min_by_key(|rrset| rrset.rtype())
+                {
+                    self.answer.push((qname.clone(), rrset.clone()));
+                }
+            }
+        }
+        if self.answer.len() == start {
+            self.nodata(zone, node, qname);
+        }
+        self.add_additional(zone, start);
+    }
+
+    /// Turns the answer into a NODATA answer for the given node.
+    fn nodata(&mut self, zone: &Zone, node: &Node, qname: &StoredDname) {
+        self.negative(zone, Rcode::NoError);
+        self.denials.push(Denial::NoData {
+            qname: qname.clone(),
+            types: rtypes(node),
+        });
+    }
+
+    /// Records the denial for an answer synthesized from a wildcard.
+    ///
+    /// If the answer for the wildcard was NODATA, the `NoData` denial
+    /// pushed for it is replaced.
+    fn push_wildcard_denial(
+        &mut self,
+        zone: &Zone,
+        node: &Node,
+        qname: StoredDname,
+        closest: StoredDname,
+    ) {
+        let closest_types =
+            find(zone, &closest, false).map(rtypes).unwrap_or_default();
+        let denial = match self.denials.last() {
+            Some(Denial::NoData { qname: name, .. }) if *name == qname => {
+                self.denials.pop();
+                Denial::WildcardNoData {
+                    qname,
+                    closest,
+                    closest_types,
+                    types: rtypes(node),
+                }
+            }
+            _ => Denial::Wildcard { qname, closest },
+        };
+        self.denials.push(denial);
+    }
+
+    /// Turns the answer into a referral to a delegated zone.
+    ///
+    /// The `node` is the node for the zone cut at `owner`.
+    fn referral(&mut self, zone: &Zone, owner: StoredDname, node: &Node) {
+        let ns = match node.rrset(Rtype::Ns) {
+            Some(ns) => ns,
+            None => return,
+        };
+        if self.answer.is_empty() {
+            self.authoritative = false;
+        }
+        for data in ns.data() {
+            if let ZoneRecordData::Ns(ns) = data {
+                let required = ns.nsdname().ends_with(&owner);
+                self.add_addresses(zone, ns.nsdname(), true, required);
+            }
+        }
+        self.authority.push((owner.clone(), ns.clone()));
+        self.denials.push(Denial::Referral {
+            cut: owner,
+            ds: node.rrset(Rtype::Ds).cloned(),
+            types: rtypes(node),
+        });
+    }
+
+    /// Turns the answer into a negative answer with the given rcode.
+    ///
+    /// Following RFC 2308, the SOA record of the zone is added to the
+    /// authority section with the lower of its TTL and minimum field as
+    /// its TTL.
+    fn negative(&mut self, zone: &Zone, rcode: Rcode) {
+        self.rcode = rcode;
+        let soa = match zone.soa() {
+            Some(soa) => soa,
+            None => return,
+        };
+        let ttl = match soa.first() {
+            Some(ZoneRecordData::Soa(data)) => soa.ttl().min(data.minimum()),
+            _ => soa.ttl(),
+        };
+        let soa = if ttl == soa.ttl() {
+            soa.clone()
+        } else {
+            let mut soa = Rrset::clone(soa);
+            soa.set_ttl(ttl);
+            Arc::new(soa)
+        };
+        self.authority.push((zone.apex().clone(), soa));
+    }
+
+    /// Adds addresses for the names in answer RRsets from `start` on.
+    ///
+    /// This is the additional section processing for NS, MX, and SRV
+    /// records. Each name is only looked up once and the addresses are
+    /// added in the order the names appear in the answer.
+    fn add_additional(&mut self, zone: &Zone, start: usize) {
+        let mut names: Vec<&StoredDname> = Vec::new();
+        for (_, rrset) in &self.answer[start..] {
+            for data in rrset.data() {
+                let name = match data {
+                    ZoneRecordData::Ns(ns) => ns.nsdname(),
+                    ZoneRecordData::Mx(mx) => mx.exchange(),
+                    ZoneRecordData::Srv(srv) => srv.target(),
+                    _ => continue,
+                };
+                if !names.iter().any(|item| item.name_eq(name)) {
+                    names.push(name)
+                }
+            }
+        }
+        let names: Vec<_> = names.into_iter().cloned().collect();
+        for name in names {
+            self.add_addresses(zone, &name, false, false)
+        }
+    }
+
+    /// Adds the A and AAAA RRsets of a name to the additional section.
+    ///
+    /// If `glue` is `true`, includes addresses below zone cuts. If
+    /// `required` is `true`, the RRsets are added behind the other
+    /// required RRsets and are exempt from the limit of
+    /// [`MAX_ADDITIONAL`] RRsets.
+    fn add_addresses(
+        &mut self,
+        zone: &Zone,
+        name: &StoredDname,
+        glue: bool,
+        required: bool,
+    ) {
+        let node = match find(zone, name, glue) {
+            Some(node) => node,
+            None => return,
+        };
+        for rtype in [Rtype::A, Rtype::Aaaa] {
+            if !required && self.additional.len() >= MAX_ADDITIONAL {
+                return;
+            }
+            if let Some(rrset) = node.rrset(rtype) {
+                let present = self.additional.iter().any(|(owner, item)| {
+                    item.rtype() == rtype && owner.name_eq(name)
+                });
+                if present {
+                    continue;
+                }
+                let item = (name.clone(), rrset.clone());
+                if required {
+                    self.additional.insert(self.required, item);
+                    self.required += 1;
+                } else {
+                    self.additional.push(item);
+                }
+            }
+        }
+    }
+}
+
+//------------ Lookup --------------------------------------------------------
+
+/// The result of looking up a name in a zone.
+enum Lookup<'a> {
+    /// The name exists.
+    Node(&'a Node),
+
+    /// The name is synthesized from the wildcard node.
+    ///
+    /// The name is that of the closest encloser.
+    Wildcard(&'a Node, StoredDname),
+
+    /// The name is at or below the zone cut with the given owner and node.
+    Cut(StoredDname, &'a Node),
+
+    /// The name doesn’t exist.
+    ///
+    /// The name and node are those of the closest encloser.
+    NxDomain(StoredDname, &'a Node),
+
+    /// The name isn’t within the zone.
+    OutOfZone,
+}
+
+//------------ Denial --------------------------------------------------------
+
+/// What a signed answer needs to prove in addition to its records.
+///
+/// The variants carry everything needed to synthesize NSEC or NSEC3
+/// records for the answer. The record types are those present at the
+/// respective node.
+#[derive(Clone, Debug)]
+pub(super) enum Denial {
+    /// The query name doesn’t exist.
+    NxDomain {
+        qname: StoredDname,
+        closest: StoredDname,
+        closest_types: Vec<Rtype>,
+    },
+
+    /// The query name exists but doesn’t have the query type.
+    NoData {
+        qname: StoredDname,
+        types: Vec<Rtype>,
+    },
+
+    /// The answer was synthesized from the wildcard below `closest`.
+    Wildcard {
+        qname: StoredDname,
+        closest: StoredDname,
+    },
+
+    /// The wildcard below `closest` doesn’t have the query type.
+    WildcardNoData {
+        qname: StoredDname,
+        closest: StoredDname,
+        closest_types: Vec<Rtype>,
+        types: Vec<Rtype>,
+    },
+
+    /// The answer is a referral to the zone cut at `cut`.
+    Referral {
+        cut: StoredDname,
+        ds: Option<SharedRrset>,
+        types: Vec<Rtype>,
+    },
+}
+
+//============ Helper Functions ==============================================
+
+/// Looks up the node for a query name.
+///
+/// Walks down the tree from the apex label by label. If a node with NS
+/// records is encountered on the way, the query is for a delegated zone.
+/// The exception is a query for DS records at the cut itself, which is
+/// answered from the parent side. If the name doesn’t exist, the
+/// wildcard at the closest encloser is used if present.
+fn lookup<'a>(
+    zone: &'a Zone,
+    qname: &StoredDname,
+    qtype: Rtype,
+) -> Lookup<'a> {
+    let mut labels = match zone.relative_labels(qname) {
+        Some(labels) => labels.peekable(),
+        None => return Lookup::OutOfZone,
+    };
+    let mut node = zone.root();
+    let mut depth = zone.apex().label_count();
+    while let Some(label) = labels.next() {
+        node = match node.child(label) {
+            Some(child) => child,
+            None => {
+                let closest = suffix(qname, depth);
+                return match node.child(Label::wildcard()) {
+                    Some(wildcard) => Lookup::Wildcard(wildcard, closest),
+                    None => Lookup::NxDomain(closest, node),
+                };
+            }
+        };
+        depth += 1;
+        if node.is_cut() && (labels.peek().is_some() || qtype != Rtype::Ds) {
+            return Lookup::Cut(suffix(qname, depth), node);
+        }
+    }
+    Lookup::Node(node)
+}
+
+/// Returns the suffix of a name with the given number of labels.
+fn suffix(name: &StoredDname, labels: usize) -> StoredDname {
+    name.iter_suffixes()
+        .nth(name.label_count() - labels)
+        .unwrap_or_else(|| name.clone())
+}
+
+/// Returns the record types present at a node.
+fn rtypes(node: &Node) -> Vec<Rtype> {
+    node.rrsets().map(|rrset| rrset.rtype()).collect()
+}
+
+/// Finds the node for a name without considering wildcards.
+///
+/// If `glue` is `false`, nodes at or below a zone cut are ignored.
+pub(super) fn find<'a>(
+    zone: &'a Zone,
+    name: &StoredDname,
+    glue: bool,
+) -> Option<&'a Node> {
+    let mut node = zone.root();
+    for label in zone.relative_labels(name)? {
+        node = node.child(label)?;
+        if !glue && node.is_cut() {
+            return None;
+        }
+    }
+    Some(node)
+}
+
+/// Returns the HINFO RRset answering ANY queries as per RFC 8482.
+fn rfc8482_hinfo() -> SharedRrset {
+    let mut rrset = Rrset::new(Rtype::Hinfo, Ttl::HOUR);
+    rrset.push_data(ZoneRecordData::Hinfo(Hinfo::new(
+        CharStr::from_octets(Bytes::from_static(b"RFC8482")).unwrap(),
+        CharStr::empty(),
+    )));
+    Arc::new(rrset)
+}
+
+/// Pushes the records of the RRsets via the given closure.
+fn push_rrsets(
+    rrsets: &[(StoredDname, SharedRrset)],
+    class: Class,
+    mut push: impl FnMut(
+        Record<&StoredDname, &ZoneRecordData<Bytes, StoredDname>>,
+    ) -> Result<(), PushError>,
+) -> Result<(), PushError> {
+    for (owner, rrset) in rrsets {
+        for data in rrset.data() {
+            push(Record::new(owner, class, rrset.ttl(), data))?;
+        }
+    }
+    Ok(())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::Serial;
+    use crate::rdata::{Aaaa, Cname, Mx, Ns, Soa, A};
+    use core::str::FromStr;
+    use std::string::{String, ToString};
+
+    fn name(s: &str) -> StoredDname {
+        StoredDname::from_str(s).unwrap()
+    }
+
+    fn zone() -> Zone {
+        let mut zone = Zone::new(name("example.com"), Class::In);
+        let mut add = |owner: &str, data: ZoneRecordData<_, _>| {
+            zone.insert(Record::new(name(owner), Class::In, Ttl::HOUR, data))
+                .unwrap()
+        };
+        add(
+            "example.com",
+            Soa::new(
+                name("ns1.example.com"),
+                name("hostmaster.example.com"),
+                Serial(1),
+                Ttl::HOUR,
+                Ttl::MINUTE,
+                Ttl::DAY,
+                Ttl::from_secs(300),
+            )
+            .into(),
+        );
+        add("example.com", Ns::new(name("ns1.example.com")).into());
+        add("example.com", Mx::new(10, name("mail.example.com")).into());
+        add("ns1.example.com", A::from_octets(192, 0, 2, 1).into());
+        add("mail.example.com", A::from_octets(192, 0, 2, 2).into());
+        add(
+            "mail.example.com",
+            Aaaa::from_str("2001:db8::2").unwrap().into(),
+        );
+        add(
+            "www.example.com",
+            Cname::new(name("web.example.com")).into(),
+        );
+        add("web.example.com", A::from_octets(192, 0, 2, 3).into());
+        add(
+            "out.example.com",
+            Cname::new(name("www.example.net")).into(),
+        );
+        add(
+            "gone.example.com",
+            Cname::new(name("nope.example.com")).into(),
+        );
+        add(
+            "loop1.example.com",
+            Cname::new(name("loop2.example.com")).into(),
+        );
+        add(
+            "loop2.example.com",
+            Cname::new(name("loop1.example.com")).into(),
+        );
+        add("*.wild.example.com", A::from_octets(192, 0, 2, 4).into());
+        add("a.b.example.com", A::from_octets(192, 0, 2, 5).into());
+        add(
+            "sub.example.com",
+            Ns::new(name("ns.sub.example.com")).into(),
+        );
+        add("ns.sub.example.com", A::from_octets(192, 0, 2, 6).into());
+        zone
+    }
+
+    fn owners(
+        section: &[(StoredDname, SharedRrset)],
+    ) -> Vec<(String, Rtype)> {
+        section
+            .iter()
+            .map(|(owner, rrset)| (owner.to_string(), rrset.rtype()))
+            .collect()
+    }
+
+    fn rrsets(items: &[(&str, Rtype)]) -> Vec<(String, Rtype)> {
+        items
+            .iter()
+            .map(|(owner, rtype)| (String::from(*owner), *rtype))
+            .collect()
+    }
+
+    #[test]
+    fn positive() {
+        let zone = zone();
+
+        let answer = zone.query(&name("www.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert!(answer.is_authoritative());
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[
+                ("www.example.com", Rtype::Cname),
+                ("web.example.com", Rtype::A)
+            ])
+        );
+        assert!(answer.authority().is_empty());
+
+        let answer = zone.query(&name("EXAMPLE.com"), Rtype::Mx);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("EXAMPLE.com", Rtype::Mx)])
+        );
+        let mut additional = owners(answer.additional());
+        additional.sort();
+        assert_eq!(
+            additional,
+            rrsets(&[
+                ("mail.example.com", Rtype::A),
+                ("mail.example.com", Rtype::Aaaa)
+            ])
+        );
+
+        let answer = zone.query(&name("example.com"), Rtype::Any);
+        assert_eq!(answer.answer().len(), 3);
+        assert_eq!(answer.additional().len(), 3);
+
+        // CNAMEs leaving the zone aren’t followed, loops are stopped.
+        let answer = zone.query(&name("out.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("out.example.com", Rtype::Cname)])
+        );
+        let answer = zone.query(&name("loop1.example.com"), Rtype::A);
+        assert_eq!(answer.answer().len(), MAX_CNAME_CHAIN);
+    }
+
+    #[test]
+    fn any_policy() {
+        let mut zone = zone();
+
+        zone.set_any_policy(AnyPolicy::Hinfo);
+        let answer = zone.query(&name("example.com"), Rtype::Any);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("example.com", Rtype::Hinfo)])
+        );
+        assert!(answer.additional().is_empty());
+        match answer.answer()[0].1.first() {
+            Some(ZoneRecordData::Hinfo(hinfo)) => {
+                assert_eq!(hinfo.cpu().as_slice(), b"RFC8482");
+                assert!(hinfo.os().is_empty());
+            }
+            _ => panic!("expected HINFO"),
+        }
+        let answer = zone.query(&name("b.example.com"), Rtype::Any);
+        assert!(answer.answer().is_empty());
+        assert_eq!(
+            owners(answer.authority()),
+            rrsets(&[("example.com", Rtype::Soa)])
+        );
+
+        zone.set_any_policy(AnyPolicy::OneRrset);
+        let answer = zone.query(&name("example.com"), Rtype::Any);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("example.com", Rtype::Ns)])
+        );
+        assert_eq!(
+            owners(answer.additional()),
+            rrsets(&[("ns1.example.com", Rtype::A)])
+        );
+    }
+
+    #[test]
+    fn rotation() {
+        use crate::utils::rotate::Rotation;
+
+        let mut zone = zone();
+        for i in 10..13 {
+            zone.insert(Record::new(
+                name("pool.example.com"),
+                Class::In,
+                Ttl::HOUR,
+                A::from_octets(192, 0, 2, i).into(),
+            ))
+            .unwrap();
+        }
+        let first = |zone: &Zone| {
+            let answer = zone.query(&name("pool.example.com"), Rtype::A);
+            match answer.answer()[0].1.first() {
+                Some(ZoneRecordData::A(a)) => a.addr().octets()[3],
+                _ => panic!("expected A"),
+            }
+        };
+        assert_eq!(first(&zone), 10);
+        assert_eq!(first(&zone), 10);
+
+        zone.set_rotation(Rotation::RoundRobin);
+        assert_eq!(first(&zone), 10);
+        assert_eq!(first(&zone), 11);
+        assert_eq!(first(&zone), 12);
+        assert_eq!(first(&zone), 10);
+
+        // The zone’s own data stays unchanged.
+        let node = zone.root().child(Label::from_static(b"pool")).unwrap();
+        match node.rrset(Rtype::A).unwrap().first() {
+            Some(ZoneRecordData::A(a)) => {
+                assert_eq!(a.addr().octets()[3], 10)
+            }
+            _ => panic!("expected A"),
+        }
+    }
+
+    #[test]
+    fn wildcard() {
+        let zone = zone();
+
+        let answer = zone.query(&name("foo.wild.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("foo.wild.example.com", Rtype::A)])
+        );
+        let answer = zone.query(&name("bar.foo.wild.example.com"), Rtype::A);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("bar.foo.wild.example.com", Rtype::A)])
+        );
+
+        // The wildcard doesn’t apply to existing names.
+        let answer = zone.query(&name("wild.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert!(answer.answer().is_empty());
+
+        // NODATA for a synthesized name.
+        let answer = zone.query(&name("foo.wild.example.com"), Rtype::Aaaa);
+        assert_eq!(answer.rcode(), Rcode::NoError);
+        assert!(answer.answer().is_empty());
+        assert_eq!(
+            owners(answer.authority()),
+            rrsets(&[("example.com", Rtype::Soa)])
+        );
+    }
+
+    #[test]
+    fn negative() {
+        let zone = zone();
+
+        // NODATA, also for empty non-terminals.
+        for qname in ["web.example.com", "b.example.com"] {
+            let answer = zone.query(&name(qname), Rtype::Aaaa);
+            assert_eq!(answer.rcode(), Rcode::NoError);
+            assert!(answer.is_authoritative());
+            assert!(answer.answer().is_empty());
+            assert_eq!(
+                owners(answer.authority()),
+                rrsets(&[("example.com", Rtype::Soa)])
+            );
+            assert_eq!(answer.authority()[0].1.ttl(), Ttl::from_secs(300));
+        }
+
+        let answer = zone.query(&name("nope.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NXDomain);
+        assert!(answer.is_authoritative());
+        assert!(answer.answer().is_empty());
+        assert_eq!(
+            owners(answer.authority()),
+            rrsets(&[("example.com", Rtype::Soa)])
+        );
+
+        // The rcode is that of the last name in a CNAME chain.
+        let answer = zone.query(&name("gone.example.com"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NXDomain);
+        assert_eq!(
+            owners(answer.answer()),
+            rrsets(&[("gone.example.com", Rtype::Cname)])
+        );
+
+        let answer = zone.query(&name("www.example.net"), Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::Refused);
+        assert!(!answer.is_authoritative());
+    }
+
+    #[test]
+    fn referral() {
+        let zone = zone();
+
+        for qname in ["sub.example.com", "www.sub.example.com"] {
+            let answer = zone.query(&name(qname), Rtype::A);
+            assert_eq!(answer.rcode(), Rcode::NoError);
+            assert!(!answer.is_authoritative());
+            assert!(answer.answer().is_empty());
+            assert_eq!(
+                owners(answer.authority()),
+                rrsets(&[("sub.example.com", Rtype::Ns)])
+            );
+            assert_eq!(
+                owners(answer.additional()),
+                rrsets(&[("ns.sub.example.com", Rtype::A)])
+            );
+        }
+
+        // DS queries at the cut are answered by the parent.
+        let answer = zone.query(&name("sub.example.com"), Rtype::Ds);
+        assert!(answer.is_authoritative());
+        assert_eq!(
+            owners(answer.authority()),
+            rrsets(&[("example.com", Rtype::Soa)])
+        );
+
+        // Glue is no authoritative data.
+        let answer = zone.query(&name("ns.sub.example.com"), Rtype::A);
+        assert!(!answer.is_authoritative());
+        assert!(answer.answer().is_empty());
+    }
+
+    #[test]
+    fn required_glue() {
+        let mut zone = zone();
+        zone.insert(Record::new(
+            name("sub.example.com"),
+            Class::In,
+            Ttl::HOUR,
+            Ns::new(name("ns1.example.com")).into(),
+        ))
+        .unwrap();
+        let mut request = MessageBuilder::new_vec().question();
+        request
+            .push((name("www.sub.example.com"), Rtype::A))
+            .unwrap();
+        let request = request.into_message();
+
+        // Glue within the delegated zone comes first.
+        let answer = zone.query(&name("www.sub.example.com"), Rtype::A);
+        assert_eq!(
+            owners(answer.additional()),
+            rrsets(&[
+                ("ns.sub.example.com", Rtype::A),
+                ("ns1.example.com", Rtype::A)
+            ])
+        );
+
+        let response = answer.to_message(&request, 512);
+        assert_eq!(response.header_counts().arcount(), 2);
+        let len = response.as_slice().len();
+        let response = answer.to_message(&request, len - 1);
+        assert!(!response.header().tc());
+        assert_eq!(response.header_counts().arcount(), 1);
+
+        // Without the required glue, the response is truncated.
+        let len = response.as_slice().len();
+        let response = answer.to_message(&request, len - 1);
+        assert!(response.header().tc());
+        assert_eq!(response.header_counts().nscount(), 0);
+    }
+
+    #[test]
+    fn to_message() {
+        let zone = zone();
+        let mut request = MessageBuilder::new_vec();
+        request.header_mut().set_id(12);
+        let mut request = request.question();
+        request.push((name("example.com"), Rtype::Mx)).unwrap();
+        let request = request.into_message();
+
+        let answer = zone.query(&name("example.com"), Rtype::Mx);
+        let response = answer.to_message(&request, 512);
+        assert_eq!(response.header().id(), 12);
+        assert!(response.header().aa());
+        assert!(!response.header().tc());
+        assert_eq!(response.header_counts().ancount(), 1);
+        assert_eq!(response.header_counts().arcount(), 2);
+        let first = response.answer().unwrap().next().unwrap().unwrap();
+        assert_eq!(first.rtype(), Rtype::Mx);
+
+        // Additional RRsets are left out from the end.
+        let len = response.as_slice().len();
+        let response = answer.to_message(&request, len - 1);
+        assert!(!response.header().tc());
+        assert_eq!(response.header_counts().ancount(), 1);
+        assert_eq!(response.header_counts().arcount(), 1);
+        let first = response.additional().unwrap().next().unwrap().unwrap();
+        assert_eq!(first.rtype(), Rtype::A);
+
+        let response = answer.to_message(&request, 40);
+        assert!(response.header().tc());
+        assert_eq!(response.header_counts().qdcount(), 1);
+        assert_eq!(response.header_counts().ancount(), 0);
+    }
+}
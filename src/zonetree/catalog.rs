@@ -0,0 +1,631 @@
+//! Catalog zones.
+//!
+//! A catalog zone is a zone whose records don’t describe data to be served
+//! but rather the list of zones a group of name servers should serve. The
+//! primary maintaining the list publishes it as a catalog zone and the
+//! secondaries learn about new and removed zones simply by transferring
+//! the catalog like any other zone. The format is defined in RFC 9432.
+//!
+//! The records of a catalog with the apex `$CATZ` are:
+//!
+//! * a TXT record `"2"` at `version.$CATZ` giving the schema version,
+//! * a single PTR record at `<id>.zones.$CATZ` for each member zone, with
+//!   the member zone’s name as its target and `<id>` an arbitrary label
+//!   unique within the catalog,
+//! * optionally, a PTR record at `coo.<id>.zones.$CATZ` pointing to
+//!   another catalog the member zone is about to move to (the “change of
+//!   ownership” property), and
+//! * optionally, TXT records at `group.<id>.zones.$CATZ` naming groups the
+//!   member zone belongs to. Groups allow the producer to tell consumers
+//!   how to treat a zone, e.g., with what settings or by which servers it
+//!   is to be served.
+//!
+//! On the consumer side, [`Catalog::from_zone`] reads the members from a
+//! transferred [`Zone`]. Member nodes without exactly one PTR record are
+//! ignored as are properties that can’t be understood. Since this crate
+//! doesn’t contain a zone maintainer that would act on a new catalog
+//! itself, [`Catalog::changes`] instead compares the previous and new
+//! version of a catalog and produces the list of [`CatalogChange`]s that
+//! need to be applied to a server’s configuration.
+//!
+//! On the producer side, a [`Catalog`] is created via [`Catalog::new`],
+//! filled with [`Member`]s via [`Catalog::insert`], and turned into a zone
+//! that can be served and transferred via [`Catalog::to_zone`].
+//!
+//! # Example
+//!
+//! ```
+//! use core::str::FromStr;
+//! use domain::base::iana::Class;
+//! use domain::base::{Dname, Serial, Ttl};
+//! use domain::zonetree::catalog::{Catalog, CatalogChange, Member};
+//!
+//! let name = |s| Dname::from_str(s).unwrap();
+//! let apex = name("catalog.invalid");
+//!
+//! // The producer publishes a catalog with one member.
+//! let mut catalog = Catalog::new(apex.clone(), Class::In);
+//! catalog.insert(Member::new(name("example.com")));
+//! let zone = catalog.to_zone(Serial(1), Ttl::ZERO).unwrap();
+//!
+//! // The consumer reads it after a transfer.
+//! let old = Catalog::new(apex, Class::In);
+//! let new = Catalog::from_zone(&zone).unwrap();
+//! let changes = old.changes(&new);
+//! assert_eq!(changes.len(), 1);
+//! assert!(matches!(changes[0], CatalogChange::Added(_)));
+//! assert_eq!(changes[0].zone(), &name("example.com"));
+//! ```
+
+use super::zone::{Node, StoredDname, StoredRecord, Zone};
+use crate::base::iana::{Class, Rtype};
+use crate::base::name::{Dname, DnameBuilder, Label, OwnedLabel};
+use crate::base::{Record, Serial, Ttl};
+use crate::rdata::{Ns, Ptr, Soa, Txt, ZoneRecordData};
+use bytes::Bytes;
+use core::str::FromStr;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The schema version of catalog zones supported.
+const VERSION: &[u8] = b"2";
+
+//------------ Catalog -------------------------------------------------------
+
+/// The member zones of a catalog zone.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Catalog {
+    /// The apex of the catalog zone.
+    apex: StoredDname,
+
+    /// The class of the catalog zone.
+    class: Class,
+
+    /// The members keyed by their zone name.
+    members: BTreeMap<StoredDname, Member>,
+}
+
+impl Catalog {
+    /// Creates a new, empty catalog.
+    pub fn new(apex: StoredDname, class: Class) -> Self {
+        Catalog {
+            apex,
+            class,
+            members: BTreeMap::new(),
+        }
+    }
+
+    /// Reads a catalog from the data of a catalog zone.
+    ///
+    /// Returns an error if the zone doesn’t declare a supported schema
+    /// version or if it lists a zone more than once. In both cases, RFC
+    /// 9432 asks consumers to keep using the previous version of the
+    /// catalog.
+    pub fn from_zone(zone: &Zone) -> Result<Self, CatalogError> {
+        Self::check_version(zone.root())?;
+        let mut res = Catalog::new(zone.apex().clone(), zone.class());
+        let zones = match zone.root().child(label(b"zones")) {
+            Some(zones) => zones,
+            None => return Ok(res),
+        };
+        for (id, node) in zones.children() {
+            let member = match Member::from_node(*id, node) {
+                Some(member) => member,
+                None => continue,
+            };
+            if let Some(member) = res.insert(member) {
+                return Err(CatalogError::DuplicateMember(member.zone));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Checks that the version property is present and supported.
+    fn check_version(root: &Node) -> Result<(), CatalogError> {
+        let rrset = root
+            .child(label(b"version"))
+            .and_then(|node| node.rrset(Rtype::Txt))
+            .ok_or(CatalogError::MissingVersion)?;
+        match rrset.data() {
+            [ZoneRecordData::Txt(txt)]
+                if txt.text::<Vec<u8>>() == VERSION =>
+            {
+                Ok(())
+            }
+            _ => Err(CatalogError::UnsupportedVersion),
+        }
+    }
+
+    pub fn apex(&self) -> &StoredDname {
+        &self.apex
+    }
+
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Returns the number of member zones.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns whether the catalog has no member zones.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Returns the member for the given zone if there is one.
+    pub fn get(&self, zone: &StoredDname) -> Option<&Member> {
+        self.members.get(zone)
+    }
+
+    /// Returns an iterator over the members ordered by zone name.
+    pub fn members(&self) -> impl Iterator<Item = &Member> {
+        self.members.values()
+    }
+
+    /// Adds a member to the catalog.
+    ///
+    /// If the catalog already contains the member’s zone, the old member
+    /// is replaced and returned.
+    pub fn insert(&mut self, member: Member) -> Option<Member> {
+        self.members.insert(member.zone.clone(), member)
+    }
+
+    /// Removes the member for the given zone from the catalog.
+    pub fn remove(&mut self, zone: &StoredDname) -> Option<Member> {
+        self.members.remove(zone)
+    }
+
+    /// Returns the changes necessary to get from this catalog to `new`.
+    ///
+    /// The changes are ordered by the name of their zone.
+    pub fn changes(&self, new: &Catalog) -> Vec<CatalogChange> {
+        let mut res = Vec::new();
+        for old in self.members() {
+            match new.get(&old.zone) {
+                None => res.push(CatalogChange::Removed(old.clone())),
+                Some(new) if new.id != old.id => {
+                    res.push(CatalogChange::Reset {
+                        old: old.clone(),
+                        new: new.clone(),
+                    })
+                }
+                Some(new) if new != old => res.push(CatalogChange::Updated {
+                    old: old.clone(),
+                    new: new.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for new in new.members() {
+            if !self.members.contains_key(&new.zone) {
+                res.push(CatalogChange::Added(new.clone()))
+            }
+        }
+        res.sort_by(|left, right| left.zone().cmp(right.zone()));
+        res
+    }
+
+    /// Produces the catalog zone for this catalog.
+    ///
+    /// The zone’s SOA record uses `serial` and all records use `ttl`. As
+    /// suggested by RFC 9432, the SOA and NS records point to `invalid.`
+    /// since the zone isn’t meant to be queried.
+    ///
+    /// Returns an error if an owner name would become too long.
+    pub fn to_zone(
+        &self,
+        serial: Serial,
+        ttl: Ttl,
+    ) -> Result<Zone, CatalogError> {
+        let invalid = StoredDname::from_str("invalid.").unwrap();
+        let mut zone = Zone::new(self.apex.clone(), self.class);
+        let mut insert = |owner, data| {
+            zone.insert(Record::new(owner, self.class, ttl, data))
+                .expect("record within catalog zone");
+        };
+        insert(
+            self.apex.clone(),
+            ZoneRecordData::Soa(Soa::new(
+                invalid.clone(),
+                invalid.clone(),
+                serial,
+                Ttl::HOUR,
+                Ttl::from_mins(10),
+                Ttl::from_days(7),
+                ttl,
+            )),
+        );
+        insert(self.apex.clone(), ZoneRecordData::Ns(Ns::new(invalid)));
+        insert(
+            self.owner(&[b"version"])?,
+            ZoneRecordData::Txt(txt(VERSION)),
+        );
+        for member in self.members() {
+            for record in member.records(self, ttl)? {
+                insert(record.owner().clone(), record.into_data());
+            }
+        }
+        Ok(zone)
+    }
+
+    /// Returns the name made of the given labels followed by the apex.
+    fn owner(&self, labels: &[&[u8]]) -> Result<StoredDname, CatalogError> {
+        let mut builder = DnameBuilder::new_bytes();
+        for label in labels {
+            builder
+                .append_label(label)
+                .map_err(|_| CatalogError::LongName)?;
+        }
+        builder
+            .append_origin(&self.apex)
+            .map_err(|_| CatalogError::LongName)
+    }
+}
+
+//------------ Member --------------------------------------------------------
+
+/// A member zone of a catalog.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Member {
+    /// The name of the member zone.
+    zone: StoredDname,
+
+    /// The label identifying the member within the catalog.
+    id: OwnedLabel,
+
+    /// The catalog the member zone is moving to.
+    coo: Option<StoredDname>,
+
+    /// The names of the groups the member belongs to, sorted.
+    groups: Vec<Bytes>,
+}
+
+impl Member {
+    /// Creates a new member for the given zone.
+    ///
+    /// The member’s ID is derived from a hash of the zone name, so the
+    /// same zone always gets the same ID.
+    pub fn new(zone: StoredDname) -> Self {
+        Member {
+            id: hash_id(&zone),
+            zone,
+            coo: None,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Reads a member from its node in the catalog zone.
+    fn from_node(id: OwnedLabel, node: &Node) -> Option<Self> {
+        let zone = match node.rrset(Rtype::Ptr)?.data() {
+            [ZoneRecordData::Ptr(ptr)] => ptr.ptrdname().clone(),
+            _ => return None,
+        };
+        let coo = node
+            .child(label(b"coo"))
+            .and_then(|node| node.rrset(Rtype::Ptr))
+            .and_then(|rrset| match rrset.data() {
+                [ZoneRecordData::Ptr(ptr)] => Some(ptr.ptrdname().clone()),
+                _ => None,
+            });
+        let mut groups: Vec<Bytes> = node
+            .child(label(b"group"))
+            .and_then(|node| node.rrset(Rtype::Txt))
+            .into_iter()
+            .flat_map(|rrset| rrset.data())
+            .filter_map(|data| match data {
+                ZoneRecordData::Txt(txt) => Some(txt.text()),
+                _ => None,
+            })
+            .collect();
+        groups.sort();
+        groups.dedup();
+        Some(Member {
+            zone,
+            id,
+            coo,
+            groups,
+        })
+    }
+
+    pub fn zone(&self) -> &StoredDname {
+        &self.zone
+    }
+
+    pub fn id(&self) -> &OwnedLabel {
+        &self.id
+    }
+
+    /// Sets the ID of the member.
+    ///
+    /// Changing the ID of a member tells consumers to reset all state they
+    /// keep for the zone, i.e., to transfer it anew.
+    pub fn set_id(&mut self, id: OwnedLabel) {
+        self.id = id
+    }
+
+    /// Returns the catalog the member zone is moving to, if any.
+    pub fn coo(&self) -> Option<&StoredDname> {
+        self.coo.as_ref()
+    }
+
+    pub fn set_coo(&mut self, coo: Option<StoredDname>) {
+        self.coo = coo
+    }
+
+    /// Returns the names of the groups the member belongs to.
+    pub fn groups(&self) -> &[Bytes] {
+        &self.groups
+    }
+
+    /// Adds the member to a group.
+    pub fn add_group(&mut self, group: impl Into<Bytes>) {
+        let group = group.into();
+        if let Err(pos) = self.groups.binary_search(&group) {
+            self.groups.insert(pos, group)
+        }
+    }
+
+    /// Returns the records for the member in the given catalog.
+    fn records(
+        &self,
+        catalog: &Catalog,
+        ttl: Ttl,
+    ) -> Result<Vec<StoredRecord>, CatalogError> {
+        let id = self.id.as_slice();
+        let class = catalog.class;
+        let mut res = vec![Record::new(
+            catalog.owner(&[id, b"zones"])?,
+            class,
+            ttl,
+            ZoneRecordData::Ptr(Ptr::new(self.zone.clone())),
+        )];
+        if let Some(coo) = self.coo.as_ref() {
+            res.push(Record::new(
+                catalog.owner(&[b"coo", id, b"zones"])?,
+                class,
+                ttl,
+                ZoneRecordData::Ptr(Ptr::new(coo.clone())),
+            ));
+        }
+        if !self.groups.is_empty() {
+            let owner = catalog.owner(&[b"group", id, b"zones"])?;
+            for group in &self.groups {
+                res.push(Record::new(
+                    owner.clone(),
+                    class,
+                    ttl,
+                    ZoneRecordData::Txt(txt(group)),
+                ));
+            }
+        }
+        Ok(res)
+    }
+}
+
+//------------ CatalogChange -------------------------------------------------
+
+/// A change between two versions of a catalog.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CatalogChange {
+    /// A zone was added to the catalog and should be provisioned.
+    Added(Member),
+
+    /// A zone was removed from the catalog and should be deprovisioned.
+    ///
+    /// If the member’s [`coo`][Member::coo] property names another
+    /// catalog, the zone may rather be in the process of moving to that
+    /// catalog. In this case, it should only be removed if it doesn’t
+    /// appear in the other catalog.
+    Removed(Member),
+
+    /// The ID of a zone changed.
+    ///
+    /// All state kept for the zone should be discarded and the zone
+    /// transferred anew.
+    Reset { old: Member, new: Member },
+
+    /// The properties of a zone changed.
+    Updated { old: Member, new: Member },
+}
+
+impl CatalogChange {
+    /// Returns the name of the zone the change is for.
+    pub fn zone(&self) -> &StoredDname {
+        match self {
+            CatalogChange::Added(member) | CatalogChange::Removed(member) => {
+                &member.zone
+            }
+            CatalogChange::Reset { new, .. }
+            | CatalogChange::Updated { new, .. } => &new.zone,
+        }
+    }
+}
+
+//------------ CatalogError --------------------------------------------------
+
+/// A catalog zone could not be read or produced.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CatalogError {
+    /// The zone doesn’t have a version property.
+    MissingVersion,
+
+    /// The version property is invalid or names an unsupported version.
+    UnsupportedVersion,
+
+    /// The zone lists the given zone more than once.
+    DuplicateMember(StoredDname),
+
+    /// An owner name would be too long.
+    LongName,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::MissingVersion => {
+                f.write_str("missing catalog zone version")
+            }
+            CatalogError::UnsupportedVersion => {
+                f.write_str("unsupported catalog zone version")
+            }
+            CatalogError::DuplicateMember(zone) => {
+                write!(f, "duplicate member zone {}", zone)
+            }
+            CatalogError::LongName => f.write_str("owner name too long"),
+        }
+    }
+}
+
+impl error::Error for CatalogError {}
+
+//============ Helper Functions ==============================================
+
+/// Returns a label known to be valid.
+fn label(slice: &[u8]) -> &Label {
+    Label::from_slice(slice).unwrap()
+}
+
+/// Returns TXT record data with a single character string.
+fn txt(text: &[u8]) -> Txt<Bytes> {
+    // Texts longer than a character string are split into several.
+    Txt::build_from_slice(text).unwrap()
+}
+
+/// Derives a member ID from a zone name.
+///
+/// This is the 64 bit FNV-1a hash of the lowercase wire format of the name
+/// in hexadecimal.
+fn hash_id(zone: &StoredDname) -> OwnedLabel {
+    let hash =
+        zone.as_slice()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, ch| {
+                (hash ^ u64::from(ch.to_ascii_lowercase()))
+                    .wrapping_mul(0x0100_0000_01b3)
+            });
+    let id = Dname::<Vec<u8>>::from_str(&format!("{:016x}", hash)).unwrap();
+    OwnedLabel::from_label(id.first())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(s: &str) -> StoredDname {
+        StoredDname::from_str(s).unwrap()
+    }
+
+    fn member(zone: &str, groups: &[&'static str]) -> Member {
+        let mut res = Member::new(name(zone));
+        for group in groups {
+            res.add_group(group.as_bytes())
+        }
+        res
+    }
+
+    fn catalog(members: Vec<Member>) -> Catalog {
+        let mut res = Catalog::new(name("catalog.invalid"), Class::In);
+        for member in members {
+            res.insert(member);
+        }
+        res
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut moving = member("example.net", &[]);
+        moving.set_coo(Some(name("other.invalid")));
+        let cat = catalog(vec![
+            member("example.com", &["signed", "primary"]),
+            moving,
+            member("example.org", &[]),
+        ]);
+        let zone = cat.to_zone(Serial(7), Ttl::ZERO).unwrap();
+        let read = Catalog::from_zone(&zone).unwrap();
+        assert_eq!(read, cat);
+        let com = read.get(&name("example.com")).unwrap();
+        assert_eq!(com.groups(), &["primary", "signed"]);
+        assert_eq!(
+            read.get(&name("example.net")).unwrap().coo(),
+            Some(&name("other.invalid"))
+        );
+        assert_eq!(Member::new(name("EXAMPLE.com")).id(), com.id());
+    }
+
+    #[test]
+    fn from_zone_errors() {
+        let insert = |zone: &mut Zone, owner: &str, data| {
+            zone.insert(Record::new(name(owner), Class::In, Ttl::ZERO, data))
+                .unwrap()
+        };
+        let ptr = |target| ZoneRecordData::Ptr(Ptr::new(name(target)));
+        let mut zone = Zone::new(name("catalog.invalid"), Class::In);
+        insert(&mut zone, "a.zones.catalog.invalid", ptr("example.com"));
+        assert_eq!(
+            Catalog::from_zone(&zone),
+            Err(CatalogError::MissingVersion)
+        );
+        let mut old = zone.clone();
+        insert(
+            &mut old,
+            "version.catalog.invalid",
+            ZoneRecordData::Txt(txt(b"1")),
+        );
+        assert_eq!(
+            Catalog::from_zone(&old),
+            Err(CatalogError::UnsupportedVersion)
+        );
+
+        insert(
+            &mut zone,
+            "version.catalog.invalid",
+            ZoneRecordData::Txt(txt(b"2")),
+        );
+        // Members with more than one PTR record are ignored.
+        insert(&mut zone, "b.zones.catalog.invalid", ptr("example.net"));
+        insert(&mut zone, "b.zones.catalog.invalid", ptr("example.org"));
+        assert_eq!(Catalog::from_zone(&zone).unwrap().len(), 1);
+
+        insert(&mut zone, "c.zones.catalog.invalid", ptr("Example.com"));
+        assert_eq!(
+            Catalog::from_zone(&zone),
+            Err(CatalogError::DuplicateMember(name("example.com")))
+        );
+    }
+
+    #[test]
+    fn changes() {
+        let mut reset = member("reset.example", &[]);
+        let old = catalog(vec![
+            member("removed.example", &[]),
+            reset.clone(),
+            member("updated.example", &["a"]),
+            member("kept.example", &["a"]),
+        ]);
+        reset.set_id(OwnedLabel::from_str("new").unwrap());
+        let new = catalog(vec![
+            member("added.example", &[]),
+            reset,
+            member("updated.example", &["a", "b"]),
+            member("kept.example", &["a"]),
+        ]);
+        let changes = old.changes(&new);
+        assert_eq!(changes.len(), 4);
+        assert!(matches!(changes[0], CatalogChange::Added(_)));
+        assert_eq!(changes[0].zone(), &name("added.example"));
+        assert!(matches!(changes[1], CatalogChange::Removed(_)));
+        assert_eq!(changes[1].zone(), &name("removed.example"));
+        assert!(matches!(changes[2], CatalogChange::Reset { .. }));
+        assert_eq!(changes[2].zone(), &name("reset.example"));
+        assert!(matches!(changes[3], CatalogChange::Updated { .. }));
+        assert_eq!(changes[3].zone(), &name("updated.example"));
+        assert!(new.changes(&new).is_empty());
+    }
+}
@@ -0,0 +1,409 @@
+//! Comparing the data of two zones.
+//!
+//! When a zone is migrated to a new server or a new signer, it is useful
+//! to check that the new copy carries the same data as the old one. The
+//! function [`compare`] walks two [`Zone`]s and reports each RRset that
+//! differs between them as an [`RrsetDiff`]. The differences are ordered by
+//! owner name, with parents before their children, and by record type.
+//!
+//! The zones can be loaded from anywhere. Records from a zonefile or a zone
+//! transfer are added via [`Zone::insert`] after turning their owner names
+//! into [`StoredDname`]s and their record data into
+//! [`StoredRecordData`][super::StoredRecordData], e.g., via
+//! `ZoneRecordData::flatten_into` for records parsed from a message.
+//!
+//! By default, RRsets are only considered equal if they have the same TTL
+//! and the records in the same order. [`CompareOptions`] allows relaxing
+//! this and ignoring the records added by DNSSEC signing.
+//!
+//! # Example
+//!
+//! ```
+//! use core::str::FromStr;
+//! use domain::base::iana::Class;
+//! use domain::base::{Dname, Record, Ttl};
+//! use domain::rdata::{ZoneRecordData, A};
+//! use domain::zonetree::compare::{compare, CompareOptions};
+//! use domain::zonetree::Zone;
+//!
+//! let zone = |addr| {
+//!     let apex = Dname::from_str("example.com").unwrap();
+//!     let mut zone = Zone::new(apex, Class::In);
+//!     zone.insert(Record::new(
+//!         Dname::from_str("www.example.com").unwrap(),
+//!         Class::In,
+//!         Ttl::HOUR,
+//!         ZoneRecordData::A(A::from_octets(192, 0, 2, addr)),
+//!     ))
+//!     .unwrap();
+//!     zone
+//! };
+//! let diffs = compare(&zone(1), &zone(2), CompareOptions::default());
+//! assert_eq!(diffs.len(), 1);
+//! assert_eq!(
+//!     diffs[0].to_string(),
+//!     "- www.example.com. 3600 IN A 192.0.2.1\n\
+//!      + www.example.com. 3600 IN A 192.0.2.2\n"
+//! );
+//! ```
+
+use super::zone::{Node, SharedRrset, StoredDname, Zone};
+use crate::base::iana::{Class, Rtype};
+use crate::base::name::{DnameBuilder, OwnedLabel};
+use std::fmt;
+use std::vec::Vec;
+
+//------------ compare -------------------------------------------------------
+
+/// Compares two zones RRset by RRset.
+///
+/// Returns the RRsets that differ between `left` and `right` according to
+/// `options`. If the apexes of the two zones differ, all of their RRsets
+/// differ.
+pub fn compare(
+    left: &Zone,
+    right: &Zone,
+    options: CompareOptions,
+) -> Vec<RrsetDiff> {
+    let mut walker = Walker {
+        options,
+        class: left.class(),
+        apex: left.apex(),
+        path: Vec::new(),
+        diffs: Vec::new(),
+    };
+    if left.apex() == right.apex() {
+        walker.walk(Some(left.root()), Some(right.root()));
+    } else {
+        walker.walk(Some(left.root()), None);
+        walker.class = right.class();
+        walker.apex = right.apex();
+        walker.walk(None, Some(right.root()));
+    }
+    walker.diffs
+}
+
+//------------ CompareOptions ------------------------------------------------
+
+/// Options for comparing zones.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompareOptions {
+    /// Ignore RRSIG, NSEC, and NSEC3 records.
+    ///
+    /// This allows comparing a signed zone with its unsigned version or
+    /// two copies signed with different keys.
+    pub ignore_dnssec: bool,
+
+    /// Ignore differences in the TTL of RRsets.
+    pub ignore_ttl: bool,
+
+    /// Ignore the order of records within an RRset.
+    pub ignore_order: bool,
+}
+
+impl CompareOptions {
+    /// Returns whether RRsets of the given type are ignored.
+    fn ignores(&self, rtype: Rtype) -> bool {
+        self.ignore_dnssec
+            && matches!(rtype, Rtype::Rrsig | Rtype::Nsec | Rtype::Nsec3)
+    }
+
+    /// Returns whether two RRsets are considered equal.
+    fn rrsets_eq(&self, left: &SharedRrset, right: &SharedRrset) -> bool {
+        if !self.ignore_ttl && left.ttl() != right.ttl() {
+            return false;
+        }
+        if self.ignore_order {
+            // RRsets don’t contain duplicates, so this is set equality.
+            left.data().len() == right.data().len()
+                && left.data().iter().all(|data| right.data().contains(data))
+        } else {
+            left.data() == right.data()
+        }
+    }
+}
+
+//------------ RrsetDiff -----------------------------------------------------
+
+/// An RRset that differs between two zones.
+///
+/// The RRset is either present in only one of the zones or has different
+/// content in both. The `Display` implementation shows the records of the
+/// left zone prefixed by `-` and those of the right zone prefixed by `+`.
+#[derive(Clone, Debug)]
+pub struct RrsetDiff {
+    /// The owner name of the RRset.
+    owner: StoredDname,
+
+    /// The class of the zone.
+    class: Class,
+
+    /// The record type of the RRset.
+    rtype: Rtype,
+
+    /// The RRset in the left zone, if present.
+    left: Option<SharedRrset>,
+
+    /// The RRset in the right zone, if present.
+    right: Option<SharedRrset>,
+}
+
+impl RrsetDiff {
+    pub fn owner(&self) -> &StoredDname {
+        &self.owner
+    }
+
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns the RRset in the left zone if it is present there.
+    pub fn left(&self) -> Option<&SharedRrset> {
+        self.left.as_ref()
+    }
+
+    /// Returns the RRset in the right zone if it is present there.
+    pub fn right(&self) -> Option<&SharedRrset> {
+        self.right.as_ref()
+    }
+
+    /// Writes the records of an RRset with the given prefix.
+    fn write_rrset(
+        &self,
+        f: &mut fmt::Formatter,
+        prefix: char,
+        rrset: Option<&SharedRrset>,
+    ) -> fmt::Result {
+        let rrset = match rrset {
+            Some(rrset) => rrset,
+            None => return Ok(()),
+        };
+        for data in rrset.data() {
+            writeln!(
+                f,
+                "{} {}. {} {} {} {}",
+                prefix,
+                self.owner,
+                rrset.ttl().as_secs(),
+                self.class,
+                self.rtype,
+                data
+            )?;
+        }
+        Ok(())
+    }
+}
+
+//--- Display
+
+impl fmt::Display for RrsetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_rrset(f, '-', self.left())?;
+        self.write_rrset(f, '+', self.right())
+    }
+}
+
+//------------ Walker --------------------------------------------------------
+
+/// Walks the trees of two zones.
+struct Walker<'a> {
+    /// The options for the comparison.
+    options: CompareOptions,
+
+    /// The class for the differences.
+    class: Class,
+
+    /// The apex the current path is relative to.
+    apex: &'a StoredDname,
+
+    /// The labels of the current node with the label closest to the apex
+    /// first.
+    path: Vec<OwnedLabel>,
+
+    /// The differences found so far.
+    diffs: Vec<RrsetDiff>,
+}
+
+impl<'a> Walker<'a> {
+    /// Compares two nodes and everything below them.
+    fn walk(&mut self, left: Option<&Node>, right: Option<&Node>) {
+        let mut rtypes: Vec<_> = left
+            .into_iter()
+            .chain(right)
+            .flat_map(|node| node.rrsets().map(|rrset| rrset.rtype()))
+            .filter(|rtype| !self.options.ignores(*rtype))
+            .collect();
+        rtypes.sort();
+        rtypes.dedup();
+        for rtype in rtypes {
+            let left = left.and_then(|node| node.rrset(rtype));
+            let right = right.and_then(|node| node.rrset(rtype));
+            if let (Some(left), Some(right)) = (left, right) {
+                if self.options.rrsets_eq(left, right) {
+                    continue;
+                }
+            }
+            self.diffs.push(RrsetDiff {
+                owner: self.owner(),
+                class: self.class,
+                rtype,
+                left: left.cloned(),
+                right: right.cloned(),
+            });
+        }
+
+        let mut labels: Vec<_> = left
+            .into_iter()
+            .chain(right)
+            .flat_map(|node| node.children().map(|(label, _)| *label))
+            .collect();
+        labels.sort();
+        labels.dedup();
+        for label in labels {
+            self.path.push(label);
+            self.walk(
+                left.and_then(|node| node.child(&label)),
+                right.and_then(|node| node.child(&label)),
+            );
+            self.path.pop();
+        }
+    }
+
+    /// Returns the owner name of the current node.
+    fn owner(&self) -> StoredDname {
+        let mut builder = DnameBuilder::new_bytes();
+        for label in self.path.iter().rev() {
+            // The name was valid when its records were added to the zone.
+            builder.append_label(label.as_slice()).unwrap();
+        }
+        builder.append_origin(self.apex).unwrap()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{Record, Ttl};
+    use crate::rdata::dnssec::RtypeBitmap;
+    use crate::rdata::{Nsec, Rrsig, ZoneRecordData, A};
+    use crate::zonetree::StoredRecordData;
+    use bytes::Bytes;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> StoredDname {
+        StoredDname::from_str(s).unwrap()
+    }
+
+    fn zone(records: &[(&str, u32, StoredRecordData)]) -> Zone {
+        let mut zone = Zone::new(name("example.com"), Class::In);
+        for (owner, ttl, data) in records {
+            zone.insert(Record::new(
+                name(owner),
+                Class::In,
+                Ttl::from_secs(*ttl),
+                data.clone(),
+            ))
+            .unwrap();
+        }
+        zone
+    }
+
+    fn a(addr: u8) -> StoredRecordData {
+        ZoneRecordData::A(A::from_octets(192, 0, 2, addr))
+    }
+
+    #[test]
+    fn compare_zones() {
+        let left = zone(&[
+            ("example.com", 3600, a(1)),
+            ("www.example.com", 3600, a(1)),
+            ("www.example.com", 3600, a(2)),
+            ("a.b.example.com", 3600, a(3)),
+        ]);
+        let right = zone(&[
+            ("example.com", 3600, a(1)),
+            ("www.example.com", 3600, a(2)),
+            ("www.example.com", 60, a(1)),
+            ("c.b.example.com", 3600, a(3)),
+        ]);
+
+        let diffs = compare(&left, &right, CompareOptions::default());
+        let diffs: Vec<_> = diffs
+            .iter()
+            .map(|diff| {
+                (
+                    diff.owner().to_string(),
+                    diff.left().is_some(),
+                    diff.right().is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            diffs,
+            [
+                ("a.b.example.com".into(), true, false),
+                ("c.b.example.com".into(), false, true),
+                ("www.example.com".into(), true, true),
+            ]
+        );
+
+        let diffs = compare(
+            &left,
+            &right,
+            CompareOptions {
+                ignore_ttl: true,
+                ignore_order: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(
+            diffs[0].to_string(),
+            "- a.b.example.com. 3600 IN A 192.0.2.3\n"
+        );
+    }
+
+    #[test]
+    fn ignore_dnssec() {
+        let rrsig = ZoneRecordData::Rrsig(
+            Rrsig::new(
+                Rtype::A,
+                13.into(),
+                3,
+                Ttl::HOUR,
+                1.into(),
+                0.into(),
+                1234,
+                name("example.com"),
+                Bytes::from_static(b"sig"),
+            )
+            .unwrap(),
+        );
+        let nsec = ZoneRecordData::Nsec(Nsec::new(
+            name("example.com"),
+            RtypeBitmap::<Bytes>::builder().finalize(),
+        ));
+        let unsigned = zone(&[("www.example.com", 3600, a(1))]);
+        let signed = zone(&[
+            ("www.example.com", 3600, a(1)),
+            ("www.example.com", 3600, rrsig),
+            ("www.example.com", 3600, nsec),
+        ]);
+        assert_eq!(
+            compare(&unsigned, &signed, CompareOptions::default()).len(),
+            2
+        );
+        assert!(compare(
+            &unsigned,
+            &signed,
+            CompareOptions {
+                ignore_dnssec: true,
+                ..Default::default()
+            }
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+//! Authoritative zone data and answering queries from it.
+//!
+//! This module provides the data structures for keeping the data of the
+//! zones a name server is authoritative for in memory and an
+//! implementation of the algorithm for answering queries from that data.
+//!
+//! A [`Zone`] holds the records of a single zone in a tree with a node for
+//! each name. Records are added one by one via [`Zone::insert`]. The zones
+//! are then collected in a [`ZoneTree`] which finds the zone a query is
+//! for.
+//!
+//! Queries are answered via [`ZoneTree::query`] or [`Zone::query`]. They
+//! follow the algorithm of section 4.3.2 of RFC 1034 and produce an
+//! [`Answer`] with the content of the answer, authority, and additional
+//! sections. The answer includes referrals with glue for names in
+//! delegated zones, follows CNAME chains within the zone, synthesizes
+//! records from wildcards, and distinguishes between names that don’t
+//! exist and names that don’t have records of the requested type.
+//! [`Answer::to_message`] finally produces the response message.
+//!
+//! With the `sign` feature, answers can be signed on the fly by an
+//! [`OnlineSigner`] which also makes up the NSEC or NSEC3 records needed
+//! for negative answers.
+//!
+//! The [`rpz`] module turns zones into response policies that rewrite the
+//! answers of a server or resolver. The [`compare`] module reports the
+//! differences between two zones. The [`catalog`] module reads and
+//! produces catalog zones listing the zones a group of servers provides.
+//!
+//! # Example
+//!
+//! ```
+//! use core::str::FromStr;
+//! use domain::base::iana::{Class, Rcode, Rtype};
+//! use domain::base::{Dname, MessageBuilder, Record, Ttl};
+//! use domain::rdata::{ZoneRecordData, A};
+//! use domain::zonetree::{Zone, ZoneTree};
+//!
+//! let apex = Dname::from_str("example.com").unwrap();
+//! let mut zone = Zone::new(apex, Class::In);
+//! zone.insert(Record::new(
+//!     Dname::from_str("www.example.com").unwrap(),
+//!     Class::In,
+//!     Ttl::HOUR,
+//!     ZoneRecordData::A(A::from_octets(192, 0, 2, 1)),
+//! ))
+//! .unwrap();
+//! let mut tree = ZoneTree::new();
+//! tree.insert_zone(zone);
+//!
+//! let qname = Dname::<Vec<u8>>::from_str("www.example.com").unwrap();
+//! let answer = tree.query(&qname, Class::In, Rtype::A).unwrap();
+//! assert_eq!(answer.rcode(), Rcode::NoError);
+//! assert_eq!(answer.answer().len(), 1);
+//! ```
+#![cfg(feature = "zonetree")]
+#![cfg_attr(docsrs, doc(cfg(feature = "zonetree")))]
+
+pub use self::answer::Answer;
+#[cfg(feature = "sign")]
+pub use self::online::{KeyProvider, OnlineSigner};
+pub use self::tree::ZoneTree;
+pub use self::zone::{
+    AnyPolicy, InsertError, Rrset, SharedRrset, StoredDname, StoredRecord,
+    StoredRecordData, Zone,
+};
+
+mod answer;
+pub mod catalog;
+pub mod compare;
+mod online;
+pub mod rpz;
+mod tree;
+mod zone;
@@ -0,0 +1,1020 @@
+//! Signing answers on the fly.
+//!
+//! Instead of signing a zone in advance, a name server can sign the
+//! records of an answer when producing it. The [`OnlineSigner`] adds the
+//! RRSIG records to an [`Answer`] and proves the non-existence of names
+//! and record types with NSEC or NSEC3 records made up for the answer.
+//!
+//! These records are the minimally covering records or “white lies” of
+//! [RFC 4470] and section 5.3 of [RFC 7129]: Rather than being part of a
+//! chain through all names of the zone, each of them only covers the one
+//! name that is denied. This way, there is no chain that needs to be kept
+//! up to date and the zone can’t be enumerated by walking the chain.
+//!
+//! The keys for signing are provided by a [`KeyProvider`]. The signer
+//! neither adds DNSKEY nor NSEC3PARAM records, so the zone needs to
+//! contain the DNSKEY RRset and, when using NSEC3, the NSEC3PARAM record
+//! matching the parameters of the signer.
+//!
+//! Signing should only happen if the request has the DO bit set.
+//!
+//! [RFC 4470]: https://tools.ietf.org/html/rfc4470
+//! [RFC 7129]: https://tools.ietf.org/html/rfc7129
+#![cfg(feature = "sign")]
+#![cfg_attr(docsrs, doc(cfg(feature = "sign")))]
+
+use super::answer::{find, Answer, Denial};
+use super::zone::{Rrset, SharedRrset, StoredDname, StoredRecordData, Zone};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Nsec3HashAlg, Rtype};
+use crate::base::name::{Dname, DnameBuilder, Label, ToDname};
+use crate::base::rdata::RecordData;
+use crate::base::record::{Record, Ttl};
+use crate::base::serial::Serial;
+use crate::crypto::Backend;
+#[cfg(feature = "ring")]
+use crate::crypto::RingBackend;
+use crate::rdata::dnssec::{ProtoRrsig, RtypeBitmap};
+use crate::rdata::nsec3::OwnerHash;
+use crate::rdata::{Nsec, Nsec3, ZoneRecordData};
+use crate::sign::key::SigningKey;
+use crate::sign::records::{DenialConfig, SignError};
+use crate::utils::base32;
+use bytes::{Bytes, BytesMut};
+use core::cmp;
+use std::sync::Arc;
+use std::vec::Vec;
+
+//------------ KeyProvider ---------------------------------------------------
+
+/// A type providing the keys for signing answers.
+///
+/// Any signing key is a key provider that signs everything with that one
+/// key.
+pub trait KeyProvider {
+    /// The type of the keys.
+    type Key: SigningKey;
+
+    /// Returns the key for signing an RRset of a zone.
+    ///
+    /// The zone is given by its apex. When using separate key and zone
+    /// signing keys, the key signing key should be returned for the
+    /// DNSKEY RRset and the zone signing key for everything else. If no
+    /// key is returned, the RRset stays unsigned.
+    fn signing_key(
+        &self,
+        apex: &StoredDname,
+        rtype: Rtype,
+    ) -> Option<&Self::Key>;
+}
+
+impl<K: SigningKey> KeyProvider for K {
+    type Key = K;
+
+    fn signing_key(&self, _apex: &StoredDname, _rtype: Rtype) -> Option<&K> {
+        Some(self)
+    }
+}
+
+//------------ OnlineSigner --------------------------------------------------
+
+/// Signs answers produced from a zone.
+///
+/// All authoritative RRsets of an answer are signed. This excludes the NS
+/// RRset and glue of referrals. Answers synthesized from a wildcard are
+/// signed as coming from the wildcard. For negative answers, referrals to
+/// unsigned zones, and wildcard answers, the NSEC or NSEC3 records
+/// required by [`DenialConfig`] are added along with their signatures.
+///
+/// The signatures are valid from an hour before until a day after the
+/// time of signing unless changed via
+/// [`set_validity`][Self::set_validity]. The crypto backend `B` is only
+/// used for hashing owner names for NSEC3.
+pub struct OnlineSigner<P, B> {
+    /// The provider for the keys.
+    provider: P,
+
+    /// How to prove non-existence.
+    denial: DenialConfig<Bytes>,
+
+    /// How long before signing the signatures become valid.
+    inception_offset: Ttl,
+
+    /// How long after signing the signatures stay valid.
+    validity: Ttl,
+
+    /// The crypto backend for NSEC3 hashing.
+    backend: B,
+}
+
+#[cfg(feature = "ring")]
+impl<P> OnlineSigner<P, RingBackend> {
+    /// Creates a new signer using the given key provider.
+    ///
+    /// The signer uses NSEC records and the [`RingBackend`].
+    pub fn new(provider: P) -> Self {
+        Self::with_backend(provider, RingBackend)
+    }
+}
+
+impl<P, B> OnlineSigner<P, B> {
+    /// Creates a new signer using the given crypto backend.
+    ///
+    /// The signer uses NSEC records.
+    pub fn with_backend(provider: P, backend: B) -> Self {
+        OnlineSigner {
+            provider,
+            denial: DenialConfig::Nsec,
+            inception_offset: Ttl::HOUR,
+            validity: Ttl::DAY,
+            backend,
+        }
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Sets the validity period of signatures relative to signing time.
+    ///
+    /// Signatures will be valid from `inception_offset` before until
+    /// `validity` after they were created. Both must be less than 2^31
+    /// seconds.
+    pub fn set_validity(&mut self, inception_offset: Ttl, validity: Ttl) {
+        self.inception_offset = inception_offset;
+        self.validity = validity;
+    }
+
+    pub fn set_denial(&mut self, denial: DenialConfig<Bytes>) {
+        self.denial = denial
+    }
+}
+
+impl<P: KeyProvider, B: Backend> OnlineSigner<P, B> {
+    /// Signs an answer produced from the given zone.
+    #[allow(clippy::type_complexity)]
+    pub fn sign(
+        &self,
+        zone: &Zone,
+        answer: &mut Answer,
+    ) -> Result<(), SignError<<P::Key as SigningKey>::Error>> {
+        self.sign_at(zone, answer, Serial::now())
+    }
+
+    /// Signs an answer as if the current time was `now`.
+    ///
+    /// Negative answers require the zone to have an SOA record: as with
+    /// signing complete zones, the TTL of the NSEC or NSEC3 records is the
+    /// lesser of the SOA record’s TTL and its minimum field.
+    #[allow(clippy::type_complexity)]
+    pub fn sign_at(
+        &self,
+        zone: &Zone,
+        answer: &mut Answer,
+        now: Serial,
+    ) -> Result<(), SignError<<P::Key as SigningKey>::Error>> {
+        let signer = Signer {
+            signer: self,
+            zone,
+            denials: answer.denials().to_vec(),
+            inception: Serial(
+                now.into_int().wrapping_sub(self.inception_offset.as_secs()),
+            ),
+            expiration: now.add(self.validity.as_secs()),
+        };
+        let [answer, authority, additional] = answer.sections_mut();
+        signer.sign_section(answer, |_, _| true)?;
+        signer.sign_section(authority, |owner, rrset| {
+            rrset.rtype() != Rtype::Ns || owner.name_eq(zone.apex())
+        })?;
+        signer.sign_section(additional, |owner, _| {
+            find(zone, owner, false).is_some()
+        })?;
+        signer.add_denials(authority)
+    }
+}
+
+//------------ Signer --------------------------------------------------------
+
+/// The state of signing a single answer.
+struct Signer<'a, P, B> {
+    /// The configuration of the signer.
+    signer: &'a OnlineSigner<P, B>,
+
+    /// The zone the answer was produced from.
+    zone: &'a Zone,
+
+    /// What needs to be proven for the answer.
+    denials: Vec<Denial>,
+
+    /// The inception time for the signatures.
+    inception: Serial,
+
+    /// The expiration time for the signatures.
+    expiration: Serial,
+}
+
+impl<'a, P: KeyProvider, B: Backend> Signer<'a, P, B> {
+    /// Adds signatures for the RRsets of a section selected by `filter`.
+    fn sign_section(
+        &self,
+        section: &mut Vec<(StoredDname, SharedRrset)>,
+        filter: impl Fn(&StoredDname, &Rrset) -> bool,
+    ) -> Result<(), SignError<<P::Key as SigningKey>::Error>> {
+        let mut res = Vec::with_capacity(section.len() * 2);
+        for (owner, rrset) in section.drain(..) {
+            let rrsig =
+                if rrset.rtype() != Rtype::Rrsig && filter(&owner, &rrset) {
+                    let source = self.wildcard_source(&owner);
+                    self.rrsig(source.as_ref().unwrap_or(&owner), &rrset)?
+                } else {
+                    None
+                };
+            res.push((owner.clone(), rrset));
+            if let Some(rrsig) = rrsig {
+                res.push((owner, rrsig));
+            }
+        }
+        *section = res;
+        Ok(())
+    }
+
+    /// Adds the signed records proving non-existence to the authority.
+    fn add_denials(
+        &self,
+        authority: &mut Vec<(StoredDname, SharedRrset)>,
+    ) -> Result<(), SignError<<P::Key as SigningKey>::Error>> {
+        let mut records = Vec::new();
+        for denial in &self.denials {
+            if let Denial::Referral {
+                cut, ds: Some(ds), ..
+            } = denial
+            {
+                authority.push((cut.clone(), ds.clone()));
+                if let Some(rrsig) = self.rrsig(cut, ds)? {
+                    authority.push((cut.clone(), rrsig));
+                }
+                continue;
+            }
+            match self.signer.denial {
+                DenialConfig::Nsec => nsec_records(denial, &mut records),
+                DenialConfig::Nsec3(ref params) => self
+                    .nsec3_records(denial, &mut records, |name| {
+                        params.hash(&self.signer.backend, name)
+                    })
+                    .map_err(SignError::Algorithm)?,
+            }
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let soa = self.zone.soa().ok_or(SignError::NoSoa)?;
+        let ttl = match soa.first() {
+            Some(ZoneRecordData::Soa(data)) => {
+                cmp::min(soa.ttl(), data.minimum())
+            }
+            _ => soa.ttl(),
+        };
+        for (owner, data) in records {
+            let mut rrset = Rrset::new(data.rtype(), ttl);
+            rrset.push_data(data);
+            let rrsig = self.rrsig(&owner, &rrset)?;
+            authority.push((owner.clone(), Arc::new(rrset)));
+            if let Some(rrsig) = rrsig {
+                authority.push((owner, rrsig));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the NSEC3 records for a denial.
+    fn nsec3_records<E>(
+        &self,
+        denial: &Denial,
+        records: &mut Vec<(StoredDname, StoredRecordData)>,
+        hash: impl Fn(&StoredDname) -> Result<Vec<u8>, E>,
+    ) -> Result<(), E> {
+        let params = match self.signer.denial {
+            DenialConfig::Nsec3(ref params) => params,
+            DenialConfig::Nsec => return Ok(()),
+        };
+        let apex = self.zone.apex();
+        let mut push = |hash: Vec<u8>, types: &[Rtype], matching: bool| {
+            let (owner, next) = if matching {
+                (hash_owner(&hash, apex), step(&hash, true))
+            } else {
+                (hash_owner(&step(&hash, false), apex), step(&hash, true))
+            };
+            let owner = match owner {
+                Some(owner) => owner,
+                None => return,
+            };
+            let mut bitmap = types.to_vec();
+            if is_signed(types) {
+                bitmap.push(Rtype::Rrsig)
+            }
+            push_record(
+                records,
+                owner,
+                ZoneRecordData::Nsec3(Nsec3::new(
+                    Nsec3HashAlg::Sha1,
+                    params.flags(),
+                    params.iterations(),
+                    params.salt().clone(),
+                    OwnerHash::from_octets(Bytes::from(next))
+                        .expect("long hash"),
+                    rtype_bitmap(bitmap),
+                )),
+            );
+        };
+
+        match denial {
+            Denial::NxDomain {
+                qname,
+                closest,
+                closest_types,
+            } => {
+                push(hash(closest)?, closest_types, true);
+                push(hash(&next_closer(qname, closest))?, &[], false);
+                if let Some(wildcard) = wildcard(closest) {
+                    push(hash(&wildcard)?, &[], false);
+                }
+            }
+            Denial::NoData { qname, types }
+            | Denial::Referral {
+                cut: qname,
+                ds: None,
+                types,
+            } => {
+                push(hash(qname)?, types, true);
+            }
+            Denial::Wildcard { qname, closest } => {
+                push(hash(&next_closer(qname, closest))?, &[], false);
+            }
+            Denial::WildcardNoData {
+                qname,
+                closest,
+                closest_types,
+                types,
+            } => {
+                push(hash(closest)?, closest_types, true);
+                push(hash(&next_closer(qname, closest))?, &[], false);
+                if let Some(wildcard) = wildcard(closest) {
+                    push(hash(&wildcard)?, types, true);
+                }
+            }
+            Denial::Referral { ds: Some(_), .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the wildcard a name in the answer was synthesized from.
+    fn wildcard_source(&self, owner: &StoredDname) -> Option<StoredDname> {
+        self.denials.iter().find_map(|denial| match denial {
+            Denial::Wildcard { qname, closest } if qname.name_eq(owner) => {
+                wildcard(closest)
+            }
+            _ => None,
+        })
+    }
+
+    /// Creates the RRSIG RRset for an RRset.
+    ///
+    /// The `owner` is the owner of the RRset as given in the zone, i.e.,
+    /// the wildcard for synthesized RRsets. Returns `None` if the key
+    /// provider doesn’t have a key for the RRset.
+    fn rrsig(
+        &self,
+        owner: &StoredDname,
+        rrset: &Rrset,
+    ) -> Result<Option<SharedRrset>, SignError<<P::Key as SigningKey>::Error>>
+    {
+        let apex = self.zone.apex();
+        let key = match self.signer.provider.signing_key(apex, rrset.rtype())
+        {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let rrsig = ProtoRrsig::new(
+            rrset.rtype(),
+            key.algorithm().map_err(SignError::Key)?,
+            owner.rrsig_label_count(),
+            rrset.ttl(),
+            self.expiration,
+            self.inception,
+            key.key_tag().map_err(SignError::Key)?,
+            apex.clone(),
+        );
+        let mut buf = Vec::new();
+        rrsig.compose_canonical(&mut buf).unwrap();
+        let mut data: Vec<_> = rrset.data().iter().collect();
+        data.sort_by(|left, right| left.canonical_cmp(right));
+        for data in data {
+            Record::new(owner, self.zone.class(), rrset.ttl(), data)
+                .compose_canonical(&mut buf)
+                .unwrap();
+        }
+        let signature = key.sign(&buf).map_err(SignError::Key)?;
+        let mut res = Rrset::new(Rtype::Rrsig, rrset.ttl());
+        res.push_data(ZoneRecordData::Rrsig(
+            rrsig
+                .into_rrsig(Bytes::copy_from_slice(signature.as_ref()))
+                .expect("long signature"),
+        ));
+        Ok(Some(Arc::new(res)))
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Creates the NSEC records for a denial.
+///
+/// A name that doesn’t exist is covered by a record from just before the
+/// name to just after it and all its descendants. A name that has no
+/// records of some type gets a record of its own listing its types.
+fn nsec_records(
+    denial: &Denial,
+    records: &mut Vec<(StoredDname, StoredRecordData)>,
+) {
+    match denial {
+        Denial::NxDomain {
+            qname,
+            closest,
+            closest_types,
+        } => {
+            nsec_cover(
+                &next_closer(qname, closest),
+                closest,
+                closest_types,
+                records,
+            );
+            if let Some(wildcard) = wildcard(closest) {
+                nsec_cover(&wildcard, closest, closest_types, records);
+            }
+        }
+        Denial::NoData { qname, types }
+        | Denial::Referral {
+            cut: qname,
+            ds: None,
+            types,
+        } => nsec_match(qname, types, records),
+        Denial::Wildcard { qname, closest } => {
+            nsec_cover(&next_closer(qname, closest), closest, &[], records);
+        }
+        Denial::WildcardNoData {
+            qname,
+            closest,
+            closest_types,
+            types,
+        } => {
+            nsec_cover(
+                &next_closer(qname, closest),
+                closest,
+                closest_types,
+                records,
+            );
+            if let Some(wildcard) = wildcard(closest) {
+                nsec_match(&wildcard, types, records);
+            }
+        }
+        Denial::Referral { ds: Some(_), .. } => {}
+    }
+}
+
+/// Adds an NSEC record covering a name that doesn’t exist.
+///
+/// If the record has to start at the closest encloser, it lists the
+/// types of the encloser.
+fn nsec_cover(
+    name: &StoredDname,
+    closest: &StoredDname,
+    closest_types: &[Rtype],
+    records: &mut Vec<(StoredDname, StoredRecordData)>,
+) {
+    let (owner, next) = match (predecessor(name), sibling_successor(name)) {
+        (Some(owner), Some(next)) => (owner, next),
+        _ => return,
+    };
+    let types = if owner.name_eq(closest) {
+        closest_types
+    } else {
+        &[]
+    };
+    nsec(owner, next, types, records)
+}
+
+/// Adds an NSEC record for a name that exists.
+fn nsec_match(
+    name: &StoredDname,
+    types: &[Rtype],
+    records: &mut Vec<(StoredDname, StoredRecordData)>,
+) {
+    if let Some(next) = successor(name) {
+        nsec(name.clone(), next, types, records)
+    }
+}
+
+/// Adds an NSEC record listing the types and NSEC and RRSIG.
+fn nsec(
+    owner: StoredDname,
+    next: StoredDname,
+    types: &[Rtype],
+    records: &mut Vec<(StoredDname, StoredRecordData)>,
+) {
+    let types = types.iter().copied().chain([Rtype::Nsec, Rtype::Rrsig]);
+    push_record(
+        records,
+        owner,
+        ZoneRecordData::Nsec(Nsec::new(next, rtype_bitmap(types))),
+    );
+}
+
+/// Adds a record unless there already is one for the owner and type.
+fn push_record(
+    records: &mut Vec<(StoredDname, StoredRecordData)>,
+    owner: StoredDname,
+    data: StoredRecordData,
+) {
+    let present = records.iter().any(|(item_owner, item)| {
+        item.rtype() == data.rtype() && item_owner.name_eq(&owner)
+    });
+    if !present {
+        records.push((owner, data))
+    }
+}
+
+/// Creates a record type bitmap.
+fn rtype_bitmap(
+    types: impl IntoIterator<Item = Rtype>,
+) -> RtypeBitmap<Bytes> {
+    let mut bitmap = RtypeBitmap::<Bytes>::builder();
+    for rtype in types {
+        bitmap.add(rtype).unwrap()
+    }
+    bitmap.finalize()
+}
+
+/// Returns whether a node with the given types has signed records.
+///
+/// This is not the case for empty non-terminals and insecure
+/// delegations.
+fn is_signed(types: &[Rtype]) -> bool {
+    let is_cut = types.contains(&Rtype::Ns) && !types.contains(&Rtype::Soa);
+    !types.is_empty() && (!is_cut || types.contains(&Rtype::Ds))
+}
+
+/// Returns the name one label below `closest` on the way to `qname`.
+fn next_closer(qname: &StoredDname, closest: &StoredDname) -> StoredDname {
+    qname
+        .iter_suffixes()
+        .nth(qname.label_count() - closest.label_count() - 1)
+        .unwrap_or_else(|| qname.clone())
+}
+
+/// Returns the wildcard directly below a name.
+fn wildcard(name: &StoredDname) -> Option<StoredDname> {
+    with_label(b"*", name)
+}
+
+/// Returns a name that sorts just before a name that doesn’t exist.
+///
+/// The result is smaller than the name in canonical order but greater
+/// than any name that may sensibly exist in the zone before it, so that
+/// the two names can be the boundaries of an NSEC record as suggested in
+/// RFC 4470: The last octet of the first label is decreased and the label
+/// filled up with the largest possible octets. A trailing zero octet is
+/// removed instead and a label of largest octets added to the front.
+fn predecessor(name: &StoredDname) -> Option<StoredDname> {
+    let parent = name.parent()?;
+    let mut label = name.first().as_slice().to_ascii_lowercase();
+    match label.pop()? {
+        0 if label.is_empty() => Some(parent),
+        0 => {
+            let base = with_label(&label, &parent)?;
+            let room = cmp::min(
+                Label::MAX_LEN,
+                Dname::MAX_LEN.saturating_sub(base.len() + 1),
+            );
+            if room == 0 {
+                return Some(base);
+            }
+            with_label(&[0xFF; Label::MAX_LEN][..room], &base)
+        }
+        octet => {
+            let octet = octet - 1;
+            // Upper case letters sort as lower case.
+            label.push(if octet.is_ascii_uppercase() {
+                b'@'
+            } else {
+                octet
+            });
+            let room = cmp::min(
+                Label::MAX_LEN - label.len(),
+                Dname::MAX_LEN.saturating_sub(label.len() + 1 + parent.len()),
+            );
+            label.resize(label.len() + room, 0xFF);
+            with_label(&label, &parent)
+        }
+    }
+}
+
+/// Returns a name that sorts just after a name and all its descendants.
+fn sibling_successor(name: &StoredDname) -> Option<StoredDname> {
+    let parent = name.parent()?;
+    let mut label = name.first().as_slice().to_ascii_lowercase();
+    if label.len() < Label::MAX_LEN && name.len() < Dname::MAX_LEN {
+        label.push(0);
+        return with_label(&label, &parent);
+    }
+    while label.last() == Some(&0xFF) {
+        label.pop();
+    }
+    let octet = label.pop()? + 1;
+    // Upper case letters sort as lower case.
+    label.push(if octet.is_ascii_uppercase() {
+        b'['
+    } else {
+        octet
+    });
+    with_label(&label, &parent)
+}
+
+/// Returns the name that immediately follows a name.
+///
+/// If the name is too long for that, returns a name following all its
+/// descendants instead.
+fn successor(name: &StoredDname) -> Option<StoredDname> {
+    with_label(b"\0", name).or_else(|| sibling_successor(name))
+}
+
+/// Returns the name with the given label prepended to `parent`.
+fn with_label(label: &[u8], parent: &impl ToDname) -> Option<StoredDname> {
+    let mut res = DnameBuilder::<BytesMut>::new();
+    res.append_label(label).ok()?;
+    res.append_origin(parent).ok()
+}
+
+/// Returns the owner name for an NSEC3 hash.
+fn hash_owner(hash: &[u8], apex: &StoredDname) -> Option<StoredDname> {
+    with_label(
+        base32::encode_string_hex(hash)
+            .to_ascii_lowercase()
+            .as_bytes(),
+        apex,
+    )
+}
+
+/// Returns the hash one larger or smaller than the given hash.
+fn step(hash: &[u8], up: bool) -> Vec<u8> {
+    let mut res = hash.to_vec();
+    for octet in res.iter_mut().rev() {
+        let (value, overflow) = if up {
+            octet.overflowing_add(1)
+        } else {
+            octet.overflowing_sub(1)
+        };
+        *octet = value;
+        if !overflow {
+            break;
+        }
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "validate", feature = "ring"))]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, DigestAlg, Rcode, SecAlg};
+    use crate::rdata::nsec3::Nsec3Salt;
+    use crate::rdata::{Ds, Ns, Soa, A};
+    use crate::sign::records::Nsec3Params;
+    use crate::sign::ring::Key;
+    use crate::validate::denial::{Denial as Proof, Nsec3Denial, NsecDenial};
+    use crate::validate::RrsigExt;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+
+    fn name(s: &str) -> StoredDname {
+        StoredDname::from_str(s).unwrap()
+    }
+
+    fn zone(key: &Key) -> Zone {
+        let mut zone = Zone::new(name("example.com"), Class::In);
+        let mut add = |owner: &str, data: StoredRecordData| {
+            zone.insert(Record::new(name(owner), Class::In, Ttl::HOUR, data))
+                .unwrap()
+        };
+        add(
+            "example.com",
+            Soa::new(
+                name("ns1.example.com"),
+                name("hostmaster.example.com"),
+                Serial(1),
+                Ttl::HOUR,
+                Ttl::MINUTE,
+                Ttl::DAY,
+                Ttl::from_secs(300),
+            )
+            .into(),
+        );
+        add("example.com", Ns::new(name("ns1.example.com")).into());
+        let dnskey = key.dnskey().unwrap();
+        add(
+            "example.com",
+            ZoneRecordData::Dnskey(
+                crate::rdata::Dnskey::new(
+                    dnskey.flags(),
+                    dnskey.protocol(),
+                    dnskey.algorithm(),
+                    Bytes::copy_from_slice(dnskey.public_key()),
+                )
+                .unwrap(),
+            ),
+        );
+        add("ns1.example.com", A::from_octets(192, 0, 2, 1).into());
+        add("www.example.com", A::from_octets(192, 0, 2, 2).into());
+        add("*.wild.example.com", A::from_octets(192, 0, 2, 3).into());
+        add(
+            "sub.example.com",
+            Ns::new(name("ns.sub.example.com")).into(),
+        );
+        add("ns.sub.example.com", A::from_octets(192, 0, 2, 4).into());
+        add("sec.example.com", Ns::new(name("ns1.example.com")).into());
+        add(
+            "sec.example.com",
+            Ds::new(
+                1,
+                SecAlg::EcdsaP256Sha256,
+                DigestAlg::Sha256,
+                Bytes::from(vec![0; 32]),
+            )
+            .unwrap()
+            .into(),
+        );
+        zone
+    }
+
+    /// Verifies all signatures in a section.
+    ///
+    /// Returns the owners and types of the signed RRsets.
+    fn verify(
+        key: &Key,
+        section: &[(StoredDname, SharedRrset)],
+    ) -> Vec<(StoredDname, Rtype)> {
+        let dnskey = key.dnskey().unwrap();
+        let mut res = Vec::new();
+        for (idx, (owner, rrset)) in section.iter().enumerate() {
+            let rrsig = match rrset.first() {
+                Some(ZoneRecordData::Rrsig(rrsig)) => rrsig,
+                _ => continue,
+            };
+            let (signed_owner, signed) = &section[idx - 1];
+            assert_eq!(signed_owner, owner);
+            assert_eq!(signed.rtype(), rrsig.type_covered());
+            let mut records: Vec<_> = signed
+                .data()
+                .iter()
+                .map(|data| {
+                    Record::new(
+                        owner.clone(),
+                        Class::In,
+                        signed.ttl(),
+                        data.clone(),
+                    )
+                })
+                .collect();
+            rrsig
+                .verify_rrset(
+                    &RingBackend,
+                    &name("example.com"),
+                    &dnskey,
+                    &mut records,
+                    Serial(1500),
+                )
+                .unwrap();
+            res.push((owner.clone(), signed.rtype()));
+        }
+        res
+    }
+
+    /// Returns the records of a type from a section.
+    fn records<T>(
+        section: &[(StoredDname, SharedRrset)],
+        data: impl Fn(&StoredRecordData) -> Option<&T>,
+    ) -> Vec<Record<StoredDname, T>>
+    where
+        T: Clone,
+    {
+        section
+            .iter()
+            .flat_map(|(owner, rrset)| {
+                rrset.data().iter().filter_map(|item| {
+                    data(item).map(|item| {
+                        Record::new(
+                            owner.clone(),
+                            Class::In,
+                            rrset.ttl(),
+                            item.clone(),
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn nsecs(
+        section: &[(StoredDname, SharedRrset)],
+    ) -> Vec<Record<StoredDname, Nsec<Bytes, StoredDname>>> {
+        records(section, |data| match data {
+            ZoneRecordData::Nsec(nsec) => Some(nsec),
+            _ => None,
+        })
+    }
+
+    fn nsec3s(
+        section: &[(StoredDname, SharedRrset)],
+    ) -> Vec<Record<StoredDname, Nsec3<Bytes>>> {
+        records(section, |data| match data {
+            ZoneRecordData::Nsec3(nsec3) => Some(nsec3),
+            _ => None,
+        })
+    }
+
+    fn query(
+        signer: &OnlineSigner<&Key, RingBackend>,
+        zone: &Zone,
+        qname: &str,
+        qtype: Rtype,
+    ) -> Answer {
+        let mut answer = zone.query(&name(qname), qtype);
+        signer.sign_at(zone, &mut answer, Serial(1000)).unwrap();
+        answer
+    }
+
+    #[test]
+    fn sign_nsec() {
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let zone = zone(&key);
+        let signer = OnlineSigner::new(&key);
+
+        let answer = query(&signer, &zone, "www.example.com", Rtype::A);
+        assert_eq!(
+            verify(&key, answer.answer()),
+            [(name("www.example.com"), Rtype::A)]
+        );
+        assert!(answer.authority().is_empty());
+
+        let answer = query(&signer, &zone, "example.com", Rtype::Dnskey);
+        assert_eq!(verify(&key, answer.answer()).len(), 1);
+
+        let answer = query(&signer, &zone, "nope.example.com", Rtype::A);
+        assert_eq!(answer.rcode(), Rcode::NXDomain);
+        let proof = nsecs(answer.authority());
+        assert_eq!(proof.len(), 2);
+        assert!(proof.iter().all(|nsec| nsec.ttl() == Ttl::from_secs(300)));
+        assert_eq!(verify(&key, answer.authority()).len(), 3);
+        let denial = NsecDenial::new(&proof);
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.com")),
+            Ok(Proof::Proven)
+        );
+        // White lies don’t deny any other names.
+        assert!(denial.nxdomain(&name("www.example.com")).is_err());
+        assert!(denial.nxdomain(&name("nopf.example.com")).is_err());
+
+        let answer = query(&signer, &zone, "www.example.com", Rtype::Aaaa);
+        assert_eq!(verify(&key, answer.authority()).len(), 2);
+        assert_eq!(
+            NsecDenial::new(&nsecs(answer.authority()))
+                .nodata(&name("www.example.com"), Rtype::Aaaa),
+            Ok(Proof::Proven)
+        );
+
+        let answer = query(&signer, &zone, "foo.wild.example.com", Rtype::A);
+        assert_eq!(
+            verify(&key, answer.answer()),
+            [(name("foo.wild.example.com"), Rtype::A)]
+        );
+        let labels = match answer.answer()[1].1.first() {
+            Some(ZoneRecordData::Rrsig(rrsig)) => rrsig.labels(),
+            _ => panic!("no RRSIG"),
+        };
+        assert_eq!(labels, 3);
+        assert_eq!(
+            NsecDenial::new(&nsecs(answer.authority()))
+                .wildcard_answer(&name("foo.wild.example.com"), labels),
+            Ok(Proof::Proven)
+        );
+
+        let answer =
+            query(&signer, &zone, "foo.wild.example.com", Rtype::Aaaa);
+        verify(&key, answer.authority());
+        assert_eq!(
+            NsecDenial::new(&nsecs(answer.authority()))
+                .nodata(&name("foo.wild.example.com"), Rtype::Aaaa),
+            Ok(Proof::Proven)
+        );
+
+        // Referrals leave NS and glue unsigned.
+        let answer = query(&signer, &zone, "www.sub.example.com", Rtype::A);
+        assert_eq!(
+            verify(&key, answer.authority()),
+            [(name("sub.example.com"), Rtype::Nsec)]
+        );
+        assert!(verify(&key, answer.additional()).is_empty());
+        assert_eq!(answer.additional().len(), 1);
+        assert_eq!(
+            NsecDenial::new(&nsecs(answer.authority()))
+                .insecure_delegation(&name("sub.example.com")),
+            Ok(Proof::Proven)
+        );
+
+        let answer = query(&signer, &zone, "sec.example.com", Rtype::A);
+        assert_eq!(
+            verify(&key, answer.authority()),
+            [(name("sec.example.com"), Rtype::Ds)]
+        );
+        assert!(nsecs(answer.authority()).is_empty());
+    }
+
+    #[test]
+    fn sign_nsec3() {
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let zone = zone(&key);
+        let mut signer = OnlineSigner::new(&key);
+        signer.set_denial(DenialConfig::Nsec3(Nsec3Params::new(
+            1,
+            Nsec3Salt::from_octets(Bytes::from_static(b"\xab\xcd")).unwrap(),
+            false,
+        )));
+        let apex = name("example.com");
+
+        let answer = query(&signer, &zone, "nope.example.com", Rtype::A);
+        let proof = nsec3s(answer.authority());
+        assert_eq!(proof.len(), 3);
+        assert_eq!(verify(&key, answer.authority()).len(), 4);
+        let denial = Nsec3Denial::new(&apex, &proof);
+        assert_eq!(
+            denial.nxdomain(&name("nope.example.com")),
+            Ok(Proof::Proven)
+        );
+        assert!(denial.nxdomain(&name("nopf.example.com")).is_err());
+
+        let answer = query(&signer, &zone, "www.example.com", Rtype::Aaaa);
+        assert_eq!(
+            Nsec3Denial::new(&apex, &nsec3s(answer.authority()))
+                .nodata(&name("www.example.com"), Rtype::Aaaa),
+            Ok(Proof::Proven)
+        );
+
+        let answer =
+            query(&signer, &zone, "foo.wild.example.com", Rtype::Aaaa);
+        verify(&key, answer.authority());
+        assert_eq!(
+            Nsec3Denial::new(&apex, &nsec3s(answer.authority()))
+                .nodata(&name("foo.wild.example.com"), Rtype::Aaaa),
+            Ok(Proof::Proven)
+        );
+
+        let answer = query(&signer, &zone, "foo.wild.example.com", Rtype::A);
+        assert_eq!(
+            Nsec3Denial::new(&apex, &nsec3s(answer.authority()))
+                .wildcard_answer(&name("foo.wild.example.com"), 3),
+            Ok(Proof::Proven)
+        );
+
+        let answer = query(&signer, &zone, "sub.example.com", Rtype::A);
+        assert_eq!(
+            Nsec3Denial::new(&apex, &nsec3s(answer.authority()))
+                .insecure_delegation(&name("sub.example.com")),
+            Ok(Proof::Proven)
+        );
+    }
+
+    #[test]
+    fn white_lies() {
+        let pred = |s: &str| predecessor(&name(s)).unwrap();
+        let succ = |s: &str| sibling_successor(&name(s)).unwrap();
+        let owner = pred("Nope.example.com");
+        assert_eq!(owner.first().len(), 63);
+        assert_eq!(&owner.first().as_slice()[..5], b"nopd\xff");
+        assert_eq!(owner.parent().unwrap(), name("example.com"));
+        assert!(owner < name("nope.example.com"));
+        assert!(owner > name("nopd.example.com"));
+        assert_eq!(pred("\\000.example.com"), name("example.com"));
+        assert_eq!(pred("[.example.com").first().as_slice()[0], b'@');
+        assert_eq!(succ("nope.example.com"), name("nope\\000.example.com"));
+        assert_eq!(
+            successor(&name("nope.example.com")).unwrap(),
+            name("\\000.nope.example.com")
+        );
+        assert_eq!(step(&[0x01, 0xff], true), [0x02, 0x00]);
+        assert_eq!(step(&[0x01, 0x00], false), [0x00, 0xff]);
+    }
+}
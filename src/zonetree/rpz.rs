@@ -0,0 +1,1116 @@
+//! Response policy zones.
+//!
+//! A response policy zone (RPZ) is a zone whose records don’t describe
+//! data to be served but rather a policy for rewriting answers. This is
+//! commonly used for blocking lists: the zone lists names or addresses
+//! that shouldn’t be resolved and what to answer instead. The format is
+//! described in draft-vixie-dnsop-dns-rpz.
+//!
+//! Each name in a policy zone is a trigger. Its position below the apex
+//! determines what it matches:
+//!
+//! * `<name>.<apex>` matches queries for `<name>`, `*.<name>.<apex>`
+//!   matches queries for all names below `<name>`,
+//! * `<prefix>.rpz-ip.<apex>` matches responses containing an address
+//!   within the prefix in their answer section,
+//! * `<name>.rpz-nsdname.<apex>` matches responses delegating to the name
+//!   server `<name>`, and
+//! * `<prefix>.rpz-nsip.<apex>` matches responses delegating to a name
+//!   server with an address within the prefix.
+//!
+//! A prefix is written as the prefix length followed by the address with
+//! its labels in reverse order. For IPv4, each octet is a label, so that
+//! `24.0.2.0.192` is `192.0.2.0/24`. For IPv6, each 16 bit group is a
+//! label in hexadecimal with `zz` standing in for the `::`, so that
+//! `48.zz.db8.2001` is `2001:db8::/48`.
+//!
+//! The records at the trigger determine the action taken when it
+//! matches. A CNAME record pointing to the root produces an NXDOMAIN
+//! response while one pointing to `*.` produces a NODATA response. A
+//! CNAME record pointing to `rpz-passthru.` lets the answer through
+//! unchanged, `rpz-drop.` drops the query, and `rpz-tcp-only.` forces
+//! clients to retry over TCP. All other records are local data that is
+//! served in place of the real answer.
+//!
+//! A policy zone is created from a regular [`Zone`] via
+//! [`PolicyZone::new`]. Triggers that can’t be understood are ignored.
+//! Multiple policy zones are collected in an [`Rpz`] which checks them in
+//! order. The first zone with a matching trigger decides. Within a zone,
+//! query name triggers are checked first, followed by address, name
+//! server name, and name server address triggers.
+//!
+//! Triggers for query names are checked before the query is answered, all
+//! other triggers once the response is available. A query matching a
+//! PASSTHRU trigger for its name is exempt from the checks of the
+//! response. Triggers for client addresses are not supported.
+//!
+//! With the `serve` feature, an [`RpzLayer`] applies a policy to the
+//! responses of a server. With the `resolv` feature, a policy can be
+//! attached to a stub resolver via
+//! [`StubResolver::with_rpz`][crate::resolv::StubResolver::with_rpz].
+//!
+//! # Example
+//!
+//! ```
+//! use core::str::FromStr;
+//! use domain::base::iana::Class;
+//! use domain::base::{Dname, Record, Ttl};
+//! use domain::rdata::{Cname, ZoneRecordData};
+//! use domain::zonetree::rpz::{Action, PolicyZone, Rpz};
+//! use domain::zonetree::Zone;
+//!
+//! let apex = Dname::from_str("rpz.local").unwrap();
+//! let mut zone = Zone::new(apex, Class::In);
+//! zone.insert(Record::new(
+//!     Dname::from_str("*.ads.example.rpz.local").unwrap(),
+//!     Class::In,
+//!     Ttl::HOUR,
+//!     ZoneRecordData::Cname(Cname::new(Dname::root_bytes())),
+//! ))
+//! .unwrap();
+//! let mut rpz = Rpz::new();
+//! rpz.push(PolicyZone::new(&zone));
+//!
+//! let qname = Dname::<Vec<u8>>::from_str("tracker.ads.example").unwrap();
+//! let hit = rpz.check_query(&qname).unwrap();
+//! assert!(matches!(hit.action(), Action::NxDomain));
+//! ```
+
+use super::answer::Answer;
+use super::zone::{Node, SharedRrset, StoredDname, Zone};
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::{Message, RecordSection};
+use crate::base::name::{DnameBuilder, OwnedLabel, ParsedDname, ToDname};
+use crate::rdata::{Aaaa, Ns, ZoneRecordData, A};
+use bytes::{Bytes, BytesMut};
+use octseq::octets::Octets;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str;
+use std::vec::Vec;
+#[cfg(feature = "serve")]
+use {
+    crate::serve::middleware::Layer,
+    crate::serve::{Request, Service, Transport},
+    core::future::Future,
+    core::pin::Pin,
+    std::boxed::Box,
+    std::sync::Arc,
+};
+
+//------------ Rpz -----------------------------------------------------------
+
+/// An ordered collection of response policy zones.
+#[derive(Clone, Debug, Default)]
+pub struct Rpz {
+    /// The policy zones in the order they are checked.
+    zones: Vec<PolicyZone>,
+}
+
+impl Rpz {
+    /// Creates a new collection without any policy zones.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a policy zone after all zones already present.
+    pub fn push(&mut self, zone: PolicyZone) {
+        self.zones.push(zone)
+    }
+
+    pub fn zones(&self) -> &[PolicyZone] {
+        &self.zones
+    }
+
+    /// Checks the query name triggers for a query name.
+    pub fn check_query<N: ToDname + ?Sized>(
+        &self,
+        qname: &N,
+    ) -> Option<Hit<'_>> {
+        let qname: StoredDname = qname.to_dname().ok()?;
+        self.zones.iter().find_map(|zone| {
+            zone.qname.find(&qname).map(|action| Hit {
+                zone,
+                trigger: Trigger::Qname,
+                action,
+            })
+        })
+    }
+
+    /// Checks the triggers that apply to the content of a response.
+    pub fn check_response<Octs: Octets>(
+        &self,
+        response: &Message<Octs>,
+    ) -> Option<Hit<'_>> {
+        self.zones.iter().find_map(|zone| {
+            zone.check_response(response).map(|(trigger, action)| Hit {
+                zone,
+                trigger,
+                action,
+            })
+        })
+    }
+}
+
+//------------ PolicyZone ----------------------------------------------------
+
+/// The triggers and actions of a single response policy zone.
+#[derive(Clone, Debug)]
+pub struct PolicyZone {
+    /// The apex of the zone.
+    apex: StoredDname,
+
+    /// The class of the zone.
+    class: Class,
+
+    /// The SOA RRset of the zone used in negative responses.
+    soa: Option<SharedRrset>,
+
+    /// The query name triggers.
+    qname: NameTriggers,
+
+    /// The response address triggers.
+    ip: IpTriggers,
+
+    /// The name server name triggers.
+    nsdname: NameTriggers,
+
+    /// The name server address triggers.
+    nsip: IpTriggers,
+}
+
+impl PolicyZone {
+    /// Creates a policy zone from the data of a zone.
+    pub fn new(zone: &Zone) -> Self {
+        let mut res = PolicyZone {
+            apex: zone.apex().clone(),
+            class: zone.class(),
+            soa: zone.soa().cloned(),
+            qname: NameTriggers::default(),
+            ip: IpTriggers::default(),
+            nsdname: NameTriggers::default(),
+            nsip: IpTriggers::default(),
+        };
+        res.add_children(zone.root(), &mut Vec::new());
+        res
+    }
+
+    pub fn apex(&self) -> &StoredDname {
+        &self.apex
+    }
+
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Adds the triggers for all nodes below the given one.
+    ///
+    /// The labels of the node relative to the apex are in `path` with the
+    /// label closest to the apex first.
+    fn add_children(&mut self, node: &Node, path: &mut Vec<OwnedLabel>) {
+        for (label, child) in node.children() {
+            path.push(*label);
+            self.add_trigger(child, path);
+            self.add_children(child, path);
+            path.pop();
+        }
+    }
+
+    /// Adds the trigger for a node if it has an action.
+    fn add_trigger(&mut self, node: &Node, path: &[OwnedLabel]) {
+        let action = match Action::from_node(node) {
+            Some(action) => action,
+            None => return,
+        };
+        let (kind, rest) = match path.split_first() {
+            Some(some) => some,
+            None => return,
+        };
+        let kind = kind.as_slice();
+        if kind.eq_ignore_ascii_case(b"rpz-ip") {
+            self.ip.insert(rest, action)
+        } else if kind.eq_ignore_ascii_case(b"rpz-nsip") {
+            self.nsip.insert(rest, action)
+        } else if kind.eq_ignore_ascii_case(b"rpz-nsdname") {
+            self.nsdname.insert(rest, action)
+        } else if !kind.eq_ignore_ascii_case(b"rpz-client-ip") {
+            self.qname.insert(path, action)
+        }
+    }
+
+    /// Checks the triggers that apply to the content of a response.
+    fn check_response<Octs: Octets>(
+        &self,
+        response: &Message<Octs>,
+    ) -> Option<(Trigger, &Action)> {
+        if !self.ip.is_empty() {
+            let answer = response.answer().ok()?;
+            if let Some(action) = addresses(answer)
+                .into_iter()
+                .find_map(|(_, addr)| self.ip.find(addr))
+            {
+                return Some((Trigger::ResponseIp, action));
+            }
+        }
+        if self.nsdname.is_empty() && self.nsip.is_empty() {
+            return None;
+        }
+        let servers = name_servers(response);
+        if let Some(action) =
+            servers.iter().find_map(|name| self.nsdname.find(name))
+        {
+            return Some((Trigger::NsDname, action));
+        }
+        if !self.nsip.is_empty() && !servers.is_empty() {
+            let additional = response.additional().ok()?;
+            if let Some(action) =
+                addresses(additional).into_iter().find_map(|(owner, addr)| {
+                    if servers.contains(&owner) {
+                        self.nsip.find(addr)
+                    } else {
+                        None
+                    }
+                })
+            {
+                return Some((Trigger::NsIp, action));
+            }
+        }
+        None
+    }
+
+    /// Creates a negative answer with the given rcode.
+    fn negative(&self, rcode: Rcode) -> Answer {
+        let mut res = Answer::new(rcode, self.class);
+        res.set_authoritative(false);
+        if let Some(soa) = self.soa.as_ref() {
+            let [_, authority, _] = res.sections_mut();
+            authority.push((self.apex.clone(), soa.clone()));
+        }
+        res
+    }
+
+    /// Creates an answer from local data.
+    ///
+    /// The RRsets matching the query type are owned by the query name. If
+    /// there are none, the answer is a NODATA answer.
+    fn local_data<Octs: Octets>(
+        &self,
+        request: &Message<Octs>,
+        rrsets: &[SharedRrset],
+    ) -> Answer {
+        let question = match request.first_question() {
+            Some(question) => question,
+            None => return self.negative(Rcode::NoError),
+        };
+        let qname: StoredDname = match question.qname().to_dname() {
+            Ok(qname) => qname,
+            Err(_) => return self.negative(Rcode::NoError),
+        };
+        let qtype = question.qtype();
+        let mut res = Answer::new(Rcode::NoError, self.class);
+        res.set_authoritative(false);
+        let [answer, _, _] = res.sections_mut();
+        for rrset in rrsets {
+            let rtype = rrset.rtype();
+            if rtype == qtype || rtype == Rtype::Cname || qtype == Rtype::Any
+            {
+                answer.push((qname.clone(), rrset.clone()));
+            }
+        }
+        if answer.is_empty() {
+            return self.negative(Rcode::NoError);
+        }
+        res
+    }
+}
+
+//------------ Trigger -------------------------------------------------------
+
+/// The kind of trigger that matched.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Trigger {
+    /// The name of the query.
+    Qname,
+
+    /// An address in the answer section of the response.
+    ResponseIp,
+
+    /// The name of a name server the response delegates to.
+    NsDname,
+
+    /// The address of a name server the response delegates to.
+    NsIp,
+}
+
+//------------ Action --------------------------------------------------------
+
+/// What to do with a query matching a trigger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// Respond with NXDOMAIN.
+    NxDomain,
+
+    /// Respond with NOERROR but without any records.
+    NoData,
+
+    /// Let the response through unchanged.
+    PassThru,
+
+    /// Don’t respond at all.
+    Drop,
+
+    /// Make UDP clients retry over TCP by responding with the TC flag.
+    TcpOnly,
+
+    /// Respond with the given records instead.
+    LocalData(Vec<SharedRrset>),
+}
+
+impl Action {
+    /// Determines the action from the records at a trigger.
+    fn from_node(node: &Node) -> Option<Self> {
+        if let Some(ZoneRecordData::Cname(cname)) =
+            node.rrset(Rtype::Cname).and_then(|rrset| rrset.first())
+        {
+            let target = cname.cname();
+            if target.is_root() {
+                return Some(Action::NxDomain);
+            }
+            if target.label_count() == 2 {
+                let label = target.first();
+                if label.is_wildcard() {
+                    return Some(Action::NoData);
+                }
+                let label = label.as_slice();
+                if label.eq_ignore_ascii_case(b"rpz-passthru") {
+                    return Some(Action::PassThru);
+                }
+                if label.eq_ignore_ascii_case(b"rpz-drop") {
+                    return Some(Action::Drop);
+                }
+                if label.eq_ignore_ascii_case(b"rpz-tcp-only") {
+                    return Some(Action::TcpOnly);
+                }
+            }
+        }
+        let data: Vec<_> = node
+            .rrsets()
+            .filter(|rrset| {
+                !matches!(
+                    rrset.rtype(),
+                    Rtype::Rrsig | Rtype::Nsec | Rtype::Nsec3
+                )
+            })
+            .cloned()
+            .collect();
+        if data.is_empty() {
+            None
+        } else {
+            Some(Action::LocalData(data))
+        }
+    }
+}
+
+//------------ Hit -----------------------------------------------------------
+
+/// A trigger of a policy zone that matched.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit<'a> {
+    /// The policy zone of the trigger.
+    zone: &'a PolicyZone,
+
+    /// The kind of trigger.
+    trigger: Trigger,
+
+    /// The action of the trigger.
+    action: &'a Action,
+}
+
+impl<'a> Hit<'a> {
+    pub fn zone(&self) -> &'a PolicyZone {
+        self.zone
+    }
+
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    pub fn action(&self) -> &'a Action {
+        self.action
+    }
+
+    /// Applies the action to a request.
+    ///
+    /// The argument `udp` states whether the request was received over
+    /// UDP. Responses are limited to `max_size` octets in the same way as
+    /// [`Answer::to_message`] does. Negative responses contain the SOA
+    /// record of the policy zone in their authority section. None of the
+    /// responses are authoritative.
+    pub fn respond<Octs: Octets>(
+        &self,
+        request: &Message<Octs>,
+        udp: bool,
+        max_size: usize,
+    ) -> Verdict {
+        let answer = match self.action {
+            Action::PassThru => return Verdict::Pass,
+            Action::Drop => return Verdict::Drop,
+            Action::TcpOnly => {
+                if !udp {
+                    return Verdict::Pass;
+                }
+                // A size limit of zero always produces a truncated
+                // response.
+                let mut answer = Answer::new(Rcode::NoError, self.zone.class);
+                answer.set_authoritative(false);
+                return Verdict::Respond(answer.to_message(request, 0));
+            }
+            Action::NxDomain => self.zone.negative(Rcode::NXDomain),
+            Action::NoData => self.zone.negative(Rcode::NoError),
+            Action::LocalData(rrsets) => {
+                self.zone.local_data(request, rrsets)
+            }
+        };
+        Verdict::Respond(answer.to_message(request, max_size))
+    }
+}
+
+//------------ Verdict -------------------------------------------------------
+
+/// The outcome of applying an action to a request.
+#[derive(Clone, Debug)]
+pub enum Verdict {
+    /// Answer the request normally.
+    Pass,
+
+    /// Don’t answer the request at all.
+    Drop,
+
+    /// Answer the request with the given response.
+    Respond(Message<Bytes>),
+}
+
+//------------ RpzService ----------------------------------------------------
+
+/// A service applying response policy zones to another service.
+///
+/// Requests matching a query name trigger are answered without calling
+/// the inner service. The responses of the inner service are checked
+/// against all other triggers.
+#[cfg(feature = "serve")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serve")))]
+#[derive(Clone, Debug)]
+pub struct RpzService<S> {
+    /// The inner service.
+    service: S,
+
+    /// The policy to apply.
+    rpz: Arc<Rpz>,
+}
+
+#[cfg(feature = "serve")]
+impl<S> RpzService<S> {
+    /// Creates a new service applying `rpz` to `service`.
+    pub fn new(service: S, rpz: Rpz) -> Self {
+        RpzService {
+            service,
+            rpz: Arc::new(rpz),
+        }
+    }
+}
+
+#[cfg(feature = "serve")]
+impl<S: Service> Service for RpzService<S> {
+    type Future =
+        Pin<Box<dyn Future<Output = Option<Message<Bytes>>> + Send>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let udp = request.transport() == Transport::Udp;
+        let max_size = request.max_response_size();
+        let hit = request
+            .message()
+            .first_question()
+            .and_then(|question| self.rpz.check_query(&question.qname()));
+        if let Some(hit) = hit {
+            return match hit.respond(request.message(), udp, max_size) {
+                Verdict::Pass => Box::pin(self.service.call(request)),
+                Verdict::Drop => Box::pin(async { None }),
+                Verdict::Respond(response) => {
+                    Box::pin(async move { Some(response) })
+                }
+            };
+        }
+        let rpz = self.rpz.clone();
+        let response = self.service.call(request.clone());
+        Box::pin(async move {
+            let response = response.await?;
+            let hit = match rpz.check_response(&response) {
+                Some(hit) => hit,
+                None => return Some(response),
+            };
+            match hit.respond(request.message(), udp, max_size) {
+                Verdict::Pass => Some(response),
+                Verdict::Drop => None,
+                Verdict::Respond(response) => Some(response),
+            }
+        })
+    }
+}
+
+//------------ RpzLayer ------------------------------------------------------
+
+/// A layer producing [`RpzService`] services.
+#[cfg(feature = "serve")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serve")))]
+#[derive(Clone, Debug)]
+pub struct RpzLayer {
+    /// The policy to apply.
+    rpz: Arc<Rpz>,
+}
+
+#[cfg(feature = "serve")]
+impl RpzLayer {
+    /// Creates a new layer applying the given policy.
+    pub fn new(rpz: Rpz) -> Self {
+        RpzLayer { rpz: Arc::new(rpz) }
+    }
+}
+
+#[cfg(feature = "serve")]
+impl<S> Layer<S> for RpzLayer {
+    type Service = RpzService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpzService {
+            service: inner,
+            rpz: self.rpz.clone(),
+        }
+    }
+}
+
+//------------ NameTriggers --------------------------------------------------
+
+/// The triggers for domain names of a policy zone.
+#[derive(Clone, Debug, Default)]
+struct NameTriggers {
+    /// The triggers for exactly the name.
+    exact: HashMap<StoredDname, Action>,
+
+    /// The triggers for all names below the name.
+    wildcard: HashMap<StoredDname, Action>,
+}
+
+impl NameTriggers {
+    fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.wildcard.is_empty()
+    }
+
+    /// Adds the trigger for a name given by its labels.
+    ///
+    /// The labels are in reverse order, starting with the one closest to
+    /// the root.
+    fn insert(&mut self, path: &[OwnedLabel], action: Action) {
+        let (path, wildcard) = match path.split_last() {
+            Some((label, rest)) if label.is_wildcard() => (rest, true),
+            _ => (path, false),
+        };
+        let name = match name_from_path(path) {
+            Some(name) => name,
+            None => return,
+        };
+        if wildcard {
+            self.wildcard.insert(name, action);
+        } else {
+            self.exact.insert(name, action);
+        }
+    }
+
+    /// Finds the action for a name.
+    ///
+    /// An exact trigger takes precedence over wildcards. Of the wildcards,
+    /// the one closest to the name wins.
+    fn find(&self, name: &StoredDname) -> Option<&Action> {
+        if let Some(action) = self.exact.get(name) {
+            return Some(action);
+        }
+        if self.wildcard.is_empty() {
+            return None;
+        }
+        name.iter_suffixes()
+            .skip(1)
+            .find_map(|suffix| self.wildcard.get(&suffix))
+    }
+}
+
+//------------ IpTriggers ----------------------------------------------------
+
+/// The triggers for address prefixes of a policy zone.
+#[derive(Clone, Debug, Default)]
+struct IpTriggers {
+    /// The prefixes and their prefix lengths.
+    prefixes: Vec<(IpAddr, u8, Action)>,
+}
+
+impl IpTriggers {
+    fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Adds the trigger for a prefix given by its labels.
+    ///
+    /// The labels are in reverse order, starting with the one closest to
+    /// the root.
+    fn insert(&mut self, path: &[OwnedLabel], action: Action) {
+        if let Some((addr, len)) = prefix_from_path(path) {
+            self.prefixes.push((addr, len, action))
+        }
+    }
+
+    /// Finds the action for the longest prefix covering an address.
+    fn find(&self, addr: IpAddr) -> Option<&Action> {
+        self.prefixes
+            .iter()
+            .filter(|(prefix, len, _)| prefix_covers(*prefix, *len, addr))
+            .max_by_key(|(_, len, _)| *len)
+            .map(|(_, _, action)| action)
+    }
+}
+
+//============ Helper Functions ==============================================
+
+/// Creates an absolute name from labels in reverse order.
+fn name_from_path(path: &[OwnedLabel]) -> Option<StoredDname> {
+    let mut res = DnameBuilder::<BytesMut>::new();
+    for label in path.iter().rev() {
+        res.append_label(label.as_slice()).ok()?;
+    }
+    res.into_dname().ok()
+}
+
+/// Parses an address prefix from labels in reverse order.
+///
+/// The last label is the prefix length. The labels before are either the
+/// four octets of an IPv4 address or the groups of an IPv6 address with
+/// at most one `zz` label for a run of zero groups.
+fn prefix_from_path(path: &[OwnedLabel]) -> Option<(IpAddr, u8)> {
+    let (len, path) = path.split_last()?;
+    let len: u8 = label_str(len)?.parse().ok()?;
+    if path.len() == 4 && len <= 32 {
+        let mut octets = [0u8; 4];
+        let parsed =
+            octets.iter_mut().zip(path).all(
+                |(octet, label)| match label_str(label)
+                    .and_then(|label| label.parse().ok())
+                {
+                    Some(value) => {
+                        *octet = value;
+                        true
+                    }
+                    None => false,
+                },
+            );
+        if parsed {
+            return Some((Ipv4Addr::from(octets).into(), len));
+        }
+    }
+    if len > 128 {
+        return None;
+    }
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    let mut compressed = false;
+    for label in path {
+        let label = label_str(label)?;
+        if label.eq_ignore_ascii_case("zz") {
+            if compressed {
+                return None;
+            }
+            compressed = true;
+            continue;
+        }
+        let group = u16::from_str_radix(label, 16).ok()?;
+        if compressed {
+            tail.push(group)
+        } else {
+            head.push(group)
+        }
+    }
+    let fill = 8usize.checked_sub(head.len() + tail.len())?;
+    if compressed == (fill == 0) {
+        return None;
+    }
+    let mut groups = [0u16; 8];
+    groups[..head.len()].copy_from_slice(&head);
+    groups[8 - tail.len()..].copy_from_slice(&tail);
+    Some((Ipv6Addr::from(groups).into(), len))
+}
+
+/// Returns the content of a label if it is a plain ASCII string.
+fn label_str(label: &OwnedLabel) -> Option<&str> {
+    str::from_utf8(label.as_slice()).ok()
+}
+
+/// Returns whether a prefix covers an address.
+fn prefix_covers(prefix: IpAddr, len: u8, addr: IpAddr) -> bool {
+    match (prefix, addr) {
+        (IpAddr::V4(prefix), IpAddr::V4(addr)) => {
+            (u32::from(prefix) ^ u32::from(addr))
+                .checked_shr(32 - u32::from(len))
+                .unwrap_or(0)
+                == 0
+        }
+        (IpAddr::V6(prefix), IpAddr::V6(addr)) => {
+            (u128::from(prefix) ^ u128::from(addr))
+                .checked_shr(128 - u32::from(len))
+                .unwrap_or(0)
+                == 0
+        }
+        _ => false,
+    }
+}
+
+/// Returns the owners and addresses of the A and AAAA records of a section.
+fn addresses<Octs: Octets>(
+    section: RecordSection<'_, Octs>,
+) -> Vec<(StoredDname, IpAddr)> {
+    let mut res = Vec::new();
+    for record in section.flatten() {
+        let addr = if let Ok(Some(record)) = record.to_record::<A>() {
+            IpAddr::from(record.data().addr())
+        } else if let Ok(Some(record)) = record.to_record::<Aaaa>() {
+            IpAddr::from(record.data().addr())
+        } else {
+            continue;
+        };
+        if let Ok(owner) = record.owner().to_dname() {
+            res.push((owner, addr))
+        }
+    }
+    res
+}
+
+/// Returns the names of the name servers in the authority section.
+fn name_servers<Octs: Octets>(response: &Message<Octs>) -> Vec<StoredDname> {
+    let mut res = Vec::new();
+    let authority = match response.authority() {
+        Ok(authority) => authority,
+        Err(_) => return res,
+    };
+    for record in authority.limit_to::<Ns<ParsedDname<_>>>().flatten() {
+        if let Ok(name) = record.data().nsdname().to_dname() {
+            res.push(name)
+        }
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{MessageBuilder, Record, Serial, Ttl};
+    use crate::rdata::{Cname, Soa};
+    use core::str::FromStr;
+    use std::format;
+
+    fn name(s: &str) -> StoredDname {
+        StoredDname::from_str(s).unwrap()
+    }
+
+    fn rpz() -> Rpz {
+        let mut zone = Zone::new(name("rpz.test"), Class::In);
+        let mut add = |owner: &str, data: ZoneRecordData<_, _>| {
+            zone.insert(Record::new(
+                name(&format!("{}rpz.test", owner)),
+                Class::In,
+                Ttl::HOUR,
+                data,
+            ))
+            .unwrap()
+        };
+        let cname = |target: &str| {
+            let target = match target {
+                "." => StoredDname::root_bytes(),
+                target => name(target),
+            };
+            Cname::new(target).into()
+        };
+        add(
+            "",
+            Soa::new(
+                name("rpz.test"),
+                name("hostmaster.rpz.test"),
+                Serial(1),
+                Ttl::HOUR,
+                Ttl::MINUTE,
+                Ttl::DAY,
+                Ttl::MINUTE,
+            )
+            .into(),
+        );
+        add("bad.example.", cname("."));
+        add("*.ads.example.", cname("*."));
+        add("ok.ads.example.", cname("rpz-passthru."));
+        add("slow.example.", cname("rpz-tcp-only."));
+        add("walled.example.", A::from_octets(192, 0, 2, 80).into());
+        add("32.1.2.0.192.rpz-ip.", cname("."));
+        add("24.0.100.51.198.rpz-ip.", cname("rpz-drop."));
+        add("25.128.100.51.198.rpz-ip.", cname("rpz-passthru."));
+        add("64.zz.db8.2001.rpz-ip.", cname("."));
+        add("ns.evil.example.rpz-nsdname.", cname("."));
+        add("32.2.113.0.203.rpz-nsip.", cname("*."));
+        add("99.1.2.0.192.rpz-ip.", cname("."));
+        add("32.2.2.0.192.rpz-client-ip.", cname("."));
+        let mut res = Rpz::new();
+        res.push(PolicyZone::new(&zone));
+        res
+    }
+
+    fn query(qname: &str, qtype: Rtype) -> Message<Bytes> {
+        let mut msg = MessageBuilder::new_bytes().question();
+        msg.push((name(qname), qtype)).unwrap();
+        msg.into_message()
+    }
+
+    fn response(
+        answer: &[(&str, ZoneRecordData<Bytes, StoredDname>)],
+        authority: &[(&str, ZoneRecordData<Bytes, StoredDname>)],
+        additional: &[(&str, ZoneRecordData<Bytes, StoredDname>)],
+    ) -> Message<Bytes> {
+        let request = query("www.example", Rtype::A);
+        let mut msg = MessageBuilder::new_bytes()
+            .start_answer(&request, Rcode::NoError)
+            .unwrap();
+        for (owner, data) in answer {
+            msg.push((name(owner), Ttl::HOUR, data)).unwrap();
+        }
+        let mut msg = msg.authority();
+        for (owner, data) in authority {
+            msg.push((name(owner), Ttl::HOUR, data)).unwrap();
+        }
+        let mut msg = msg.additional();
+        for (owner, data) in additional {
+            msg.push((name(owner), Ttl::HOUR, data)).unwrap();
+        }
+        msg.into_message()
+    }
+
+    fn action(hit: Option<Hit>) -> Option<(Trigger, Action)> {
+        hit.map(|hit| (hit.trigger(), hit.action().clone()))
+    }
+
+    #[test]
+    fn query_triggers() {
+        let rpz = rpz();
+        let check = |qname: &str| action(rpz.check_query(&name(qname)));
+        assert_eq!(
+            check("bad.example"),
+            Some((Trigger::Qname, Action::NxDomain))
+        );
+        assert_eq!(
+            check("BAD.Example"),
+            Some((Trigger::Qname, Action::NxDomain))
+        );
+        assert_eq!(check("www.bad.example"), None);
+        assert_eq!(check("ads.example"), None);
+        assert_eq!(
+            check("x.y.ads.example"),
+            Some((Trigger::Qname, Action::NoData))
+        );
+        assert_eq!(
+            check("ok.ads.example"),
+            Some((Trigger::Qname, Action::PassThru))
+        );
+        assert!(matches!(
+            check("walled.example"),
+            Some((Trigger::Qname, Action::LocalData(_)))
+        ));
+        assert_eq!(check("192.rpz-ip"), None);
+        assert_eq!(check("evil.example"), None);
+        assert_eq!(check("example"), None);
+    }
+
+    #[test]
+    fn response_triggers() {
+        let rpz = rpz();
+        let check = |answer: &[_], authority: &[_], additional: &[_]| {
+            action(
+                rpz.check_response(&response(answer, authority, additional)),
+            )
+        };
+        let a = |a, b, c, d| A::from_octets(a, b, c, d).into();
+        let aaaa = |s| Aaaa::from_str(s).unwrap().into();
+        let ns = |s| Ns::new(name(s)).into();
+
+        assert_eq!(
+            check(&[("www.example", a(192, 0, 2, 2))], &[], &[]),
+            None
+        );
+        assert_eq!(
+            check(&[("www.example", a(192, 0, 2, 1))], &[], &[]),
+            Some((Trigger::ResponseIp, Action::NxDomain))
+        );
+        assert_eq!(
+            check(&[("www.example", a(198, 51, 100, 7))], &[], &[]),
+            Some((Trigger::ResponseIp, Action::Drop))
+        );
+        assert_eq!(
+            check(&[("www.example", a(198, 51, 100, 200))], &[], &[]),
+            Some((Trigger::ResponseIp, Action::PassThru))
+        );
+        assert_eq!(
+            check(&[("www.example", aaaa("2001:db8::1"))], &[], &[]),
+            Some((Trigger::ResponseIp, Action::NxDomain))
+        );
+        assert_eq!(
+            check(&[("www.example", aaaa("2001:db8:1::1"))], &[], &[]),
+            None
+        );
+        assert_eq!(
+            check(&[], &[("example", ns("NS.evil.example"))], &[]),
+            Some((Trigger::NsDname, Action::NxDomain))
+        );
+        assert_eq!(
+            check(
+                &[],
+                &[("example", ns("ns.example"))],
+                &[
+                    ("ns.example", a(203, 0, 113, 1)),
+                    ("other.example", a(203, 0, 113, 2))
+                ]
+            ),
+            None
+        );
+        assert_eq!(
+            check(
+                &[],
+                &[("example", ns("ns.example"))],
+                &[("ns.example", a(203, 0, 113, 2))]
+            ),
+            Some((Trigger::NsIp, Action::NoData))
+        );
+    }
+
+    #[test]
+    fn prefixes() {
+        let path = |s: &str| {
+            s.split('.')
+                .rev()
+                .map(|label| OwnedLabel::from_str(label).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let prefix = |s: &str| prefix_from_path(&path(s));
+        assert_eq!(
+            prefix("24.0.2.0.192"),
+            Some((IpAddr::from([192, 0, 2, 0]), 24))
+        );
+        assert_eq!(
+            prefix("48.zz.db8.2001"),
+            Some((IpAddr::from_str("2001:db8::").unwrap(), 48))
+        );
+        assert_eq!(
+            prefix("128.1.zz.db8.2001"),
+            Some((IpAddr::from_str("2001:db8::1").unwrap(), 128))
+        );
+        assert_eq!(
+            prefix("128.8.7.6.5.4.3.2.1"),
+            Some((IpAddr::from_str("1:2:3:4:5:6:7:8").unwrap(), 128))
+        );
+        assert_eq!(prefix("33.1.2.0.192"), None);
+        assert_eq!(prefix("24.2.0.192"), None);
+        assert_eq!(prefix("64.zz.1.zz.2001"), None);
+        assert_eq!(prefix("64.zz.8.7.6.5.4.3.2.1"), None);
+        assert!(prefix_covers(IpAddr::from([0, 0, 0, 0]), 0, [1; 4].into()));
+    }
+
+    #[test]
+    fn respond() {
+        let rpz = rpz();
+        let request = query("bad.example", Rtype::A);
+        let response = match rpz
+            .check_query(&name("bad.example"))
+            .unwrap()
+            .respond(&request, true, 512)
+        {
+            Verdict::Respond(response) => response,
+            other => panic!("unexpected verdict {:?}", other),
+        };
+        assert_eq!(response.header().rcode(), Rcode::NXDomain);
+        assert!(!response.header().aa());
+        assert_eq!(response.header_counts().nscount(), 1);
+
+        let hit = rpz.check_query(&name("walled.example")).unwrap();
+        let request = query("walled.example", Rtype::A);
+        let response = match hit.respond(&request, true, 512) {
+            Verdict::Respond(response) => response,
+            other => panic!("unexpected verdict {:?}", other),
+        };
+        assert_eq!(response.header().rcode(), Rcode::NoError);
+        let answer = response.answer().unwrap().limit_to::<A>().next();
+        let answer = answer.unwrap().unwrap();
+        assert_eq!(
+            answer.owner().to_dname::<Bytes>().unwrap(),
+            name("walled.example")
+        );
+        assert_eq!(answer.data().addr(), Ipv4Addr::new(192, 0, 2, 80));
+        let request = query("walled.example", Rtype::Mx);
+        let response = match hit.respond(&request, true, 512) {
+            Verdict::Respond(response) => response,
+            other => panic!("unexpected verdict {:?}", other),
+        };
+        assert_eq!(response.header().rcode(), Rcode::NoError);
+        assert_eq!(response.header_counts().ancount(), 0);
+        assert_eq!(response.header_counts().nscount(), 1);
+
+        let hit = rpz.check_query(&name("slow.example")).unwrap();
+        let request = query("slow.example", Rtype::A);
+        assert!(matches!(hit.respond(&request, false, 512), Verdict::Pass));
+        match hit.respond(&request, true, 512) {
+            Verdict::Respond(response) => assert!(response.header().tc()),
+            other => panic!("unexpected verdict {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    #[tokio::test]
+    async fn service() {
+        use crate::serve::middleware::ServiceBuilder;
+
+        async fn answer(request: Request) -> Option<Message<Bytes>> {
+            let mut msg = MessageBuilder::new_bytes()
+                .start_answer(request.message(), Rcode::NoError)
+                .unwrap();
+            let addr = if request.message().first_question()?.qname()
+                == &name("blocked.example")
+            {
+                A::from_octets(192, 0, 2, 1)
+            } else {
+                A::from_octets(192, 0, 2, 2)
+            };
+            msg.push((name("www.example"), Ttl::HOUR, addr)).unwrap();
+            Some(msg.into_message())
+        }
+
+        let service = ServiceBuilder::new()
+            .layer(RpzLayer::new(rpz()))
+            .service(answer);
+        let call = |qname: &str| {
+            service.call(Request::new(
+                query(qname, Rtype::A),
+                "192.0.2.1:53".parse().unwrap(),
+                Transport::Udp,
+            ))
+        };
+        let rcode = |response: Option<Message<Bytes>>| {
+            response.map(|response| response.header().rcode())
+        };
+        assert_eq!(rcode(call("www.example").await), Some(Rcode::NoError));
+        assert_eq!(rcode(call("bad.example").await), Some(Rcode::NXDomain));
+        assert_eq!(
+            rcode(call("blocked.example").await),
+            Some(Rcode::NXDomain)
+        );
+        assert!(call("slow.example").await.unwrap().header().tc());
+    }
+}
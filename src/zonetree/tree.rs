@@ -0,0 +1,188 @@
+//! A collection of zones.
+
+use super::answer::Answer;
+use super::zone::Zone;
+use crate::base::iana::{Class, Rtype};
+use crate::base::name::{OwnedLabel, ToDname};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+//------------ ZoneTree ------------------------------------------------------
+
+/// A collection of zones.
+///
+/// The zones are kept in a tree per class with a node for every label of
+/// their apex so that the zone responsible for a name can be found
+/// quickly.
+#[derive(Clone, Debug, Default)]
+pub struct ZoneTree {
+    /// The root nodes for each class.
+    roots: HashMap<Class, TreeNode>,
+}
+
+impl ZoneTree {
+    /// Creates a new, empty zone tree.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a zone to the tree.
+    ///
+    /// Returns the zone previously added for the same apex and class, if
+    /// any.
+    pub fn insert_zone(
+        &mut self,
+        zone: impl Into<Arc<Zone>>,
+    ) -> Option<Arc<Zone>> {
+        let zone = zone.into();
+        let mut node = self.roots.entry(zone.class()).or_default();
+        for label in zone.apex().iter().rev() {
+            node = node.children.entry(label.into()).or_default();
+        }
+        node.zone.replace(zone)
+    }
+
+    /// Removes the zone with the given apex and class.
+    pub fn remove_zone<N: ToDname + ?Sized>(
+        &mut self,
+        apex: &N,
+        class: Class,
+    ) -> Option<Arc<Zone>> {
+        let mut node = self.roots.get_mut(&class)?;
+        for label in apex.iter_labels().rev() {
+            node = node.children.get_mut(label)?;
+        }
+        node.zone.take()
+    }
+
+    /// Returns the zone with exactly the given apex and class.
+    pub fn get_zone<N: ToDname + ?Sized>(
+        &self,
+        apex: &N,
+        class: Class,
+    ) -> Option<&Arc<Zone>> {
+        let mut node = self.roots.get(&class)?;
+        for label in apex.iter_labels().rev() {
+            node = node.children.get(label)?;
+        }
+        node.zone.as_ref()
+    }
+
+    /// Returns the zone a name belongs to.
+    ///
+    /// This is the zone with the longest apex that is a suffix of the
+    /// name.
+    pub fn find_zone<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+        class: Class,
+    ) -> Option<&Arc<Zone>> {
+        let mut node = self.roots.get(&class)?;
+        let mut res = node.zone.as_ref();
+        for label in name.iter_labels().rev() {
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => break,
+            };
+            if let Some(zone) = node.zone.as_ref() {
+                res = Some(zone)
+            }
+        }
+        res
+    }
+
+    /// Produces the answer to a query.
+    ///
+    /// Returns `None` if there is no zone for the query name.
+    pub fn query<N: ToDname + ?Sized>(
+        &self,
+        qname: &N,
+        qclass: Class,
+        qtype: Rtype,
+    ) -> Option<Answer> {
+        self.find_zone(qname, qclass)
+            .map(|zone| zone.query(qname, qtype))
+    }
+
+    /// Returns an iterator over all zones in the tree.
+    pub fn iter_zones(&self) -> impl Iterator<Item = &Arc<Zone>> {
+        let mut stack: std::vec::Vec<_> = self.roots.values().collect();
+        core::iter::from_fn(move || {
+            while let Some(node) = stack.pop() {
+                stack.extend(node.children.values());
+                if let Some(zone) = node.zone.as_ref() {
+                    return Some(zone);
+                }
+            }
+            None
+        })
+    }
+}
+
+//------------ TreeNode ------------------------------------------------------
+
+/// A node in the zone tree.
+#[derive(Clone, Debug, Default)]
+struct TreeNode {
+    /// The zone with this node’s name as its apex, if any.
+    zone: Option<Arc<Zone>>,
+
+    /// The nodes for the names one label below.
+    children: HashMap<OwnedLabel, TreeNode>,
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::name::Dname;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> Dname<bytes::Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn find_zone() {
+        let mut tree = ZoneTree::new();
+        assert!(tree
+            .insert_zone(Zone::new(name("example.com"), Class::In))
+            .is_none());
+        tree.insert_zone(Zone::new(name("sub.example.com"), Class::In));
+        tree.insert_zone(Zone::new(name("example.org"), Class::Ch));
+        assert!(tree
+            .insert_zone(Zone::new(name("Example.COM"), Class::In))
+            .is_some());
+        assert_eq!(tree.iter_zones().count(), 3);
+
+        let apex = |qname: &str, class| {
+            tree.find_zone(&name(qname), class)
+                .map(|zone| zone.apex().to_string())
+        };
+        assert_eq!(apex("example.com", Class::In).unwrap(), "Example.COM");
+        assert_eq!(
+            apex("www.example.com", Class::In).unwrap(),
+            "Example.COM"
+        );
+        assert_eq!(
+            apex("www.SUB.example.com", Class::In).unwrap(),
+            "sub.example.com"
+        );
+        assert!(apex("com", Class::In).is_none());
+        assert!(apex("example.org", Class::In).is_none());
+        assert!(apex("example.org", Class::Ch).is_some());
+
+        assert!(tree.get_zone(&name("www.example.com"), Class::In).is_none());
+        let answer =
+            tree.query(&name("www.example.com"), Class::In, Rtype::A);
+        assert_eq!(answer.unwrap().rcode(), Rcode::NXDomain);
+
+        assert!(tree.remove_zone(&name("example.com"), Class::In).is_some());
+        assert!(tree
+            .find_zone(&name("www.example.com"), Class::In)
+            .is_none());
+    }
+}
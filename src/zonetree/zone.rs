@@ -0,0 +1,351 @@
+//! The data of a single zone.
+
+use super::answer::Answer;
+use crate::base::iana::{Class, Rtype};
+use crate::base::name::{Dname, Label, OwnedLabel, ToDname};
+use crate::base::record::{Record, Ttl};
+use crate::rdata::ZoneRecordData;
+use crate::utils::rotate::{Rotation, Rotator};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Type Aliases --------------------------------------------------
+
+/// The type of domain names stored in a zone.
+pub type StoredDname = Dname<Bytes>;
+
+/// The type of record data stored in a zone.
+pub type StoredRecordData = ZoneRecordData<Bytes, StoredDname>;
+
+/// The type of records that can be added to a zone.
+pub type StoredRecord = Record<StoredDname, StoredRecordData>;
+
+/// An RRset shared between a zone and the answers created from it.
+pub type SharedRrset = Arc<Rrset>;
+
+//------------ Rrset ---------------------------------------------------------
+
+/// The record data of all records of a record type at a name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rrset {
+    /// The record type of the RRset.
+    rtype: Rtype,
+
+    /// The TTL shared by all records of the RRset.
+    ttl: Ttl,
+
+    /// The record data of the records.
+    data: Vec<StoredRecordData>,
+}
+
+impl Rrset {
+    /// Creates a new, empty RRset.
+    pub fn new(rtype: Rtype, ttl: Ttl) -> Self {
+        Rrset {
+            rtype,
+            ttl,
+            data: Vec::new(),
+        }
+    }
+
+    /// Returns the record type of the RRset.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns the TTL shared by all records of the RRset.
+    pub fn ttl(&self) -> Ttl {
+        self.ttl
+    }
+
+    /// Sets the TTL of all records of the RRset.
+    pub fn set_ttl(&mut self, ttl: Ttl) {
+        self.ttl = ttl
+    }
+
+    /// Returns the record data of the records.
+    pub fn data(&self) -> &[StoredRecordData] {
+        &self.data
+    }
+
+    /// Returns the record data of the first record if there is one.
+    pub fn first(&self) -> Option<&StoredRecordData> {
+        self.data.first()
+    }
+
+    /// Returns whether the RRset has no records.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Adds record data to the RRset.
+    ///
+    /// Since an RRset is a set, data that is already present is not added
+    /// again.
+    pub fn push_data(&mut self, data: StoredRecordData) {
+        if !self.data.contains(&data) {
+            self.data.push(data)
+        }
+    }
+
+    /// Reorders the record data of the RRset with the given rotator.
+    ///
+    /// The records of SRV RRsets are ordered by their priority and weight
+    /// if the rotator uses SRV-weighted rotation.
+    pub fn rotate(&mut self, rotator: &Rotator) {
+        if self.rtype == Rtype::Srv {
+            rotator.rotate_weighted(&mut self.data, |data| match data {
+                ZoneRecordData::Srv(srv) => (srv.priority(), srv.weight()),
+                _ => (0, 0),
+            })
+        } else {
+            rotator.rotate(&mut self.data)
+        }
+    }
+}
+
+//------------ Zone ----------------------------------------------------------
+
+/// The authoritative data of a zone.
+///
+/// A zone is built by adding records one by one via
+/// [`insert`][Self::insert]. The records are kept in a tree of nodes with
+/// one node for each label that can be queried efficiently via
+/// [`query`][Self::query].
+#[derive(Clone, Debug)]
+pub struct Zone {
+    /// The apex of the zone.
+    apex: StoredDname,
+
+    /// The class of the zone.
+    class: Class,
+
+    /// The node for the apex.
+    root: Node,
+
+    /// How queries for QTYPE ANY are answered.
+    any_policy: AnyPolicy,
+
+    /// How the records in the answer section are reordered.
+    rotator: Rotator,
+}
+
+impl Zone {
+    /// Creates a new, empty zone.
+    pub fn new(apex: StoredDname, class: Class) -> Self {
+        Zone {
+            apex,
+            class,
+            root: Node::default(),
+            any_policy: AnyPolicy::default(),
+            rotator: Rotator::default(),
+        }
+    }
+
+    /// Returns the apex of the zone.
+    pub fn apex(&self) -> &StoredDname {
+        &self.apex
+    }
+
+    /// Returns the class of the zone.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Returns how queries for QTYPE ANY are answered.
+    pub fn any_policy(&self) -> AnyPolicy {
+        self.any_policy
+    }
+
+    /// Sets how queries for QTYPE ANY are answered.
+    ///
+    /// The default is to answer with all RRsets of the query name.
+    pub fn set_any_policy(&mut self, policy: AnyPolicy) {
+        self.any_policy = policy
+    }
+
+    /// Returns how the records of the RRsets in answers are reordered.
+    pub fn rotation(&self) -> Rotation {
+        self.rotator.rotation()
+    }
+
+    /// Sets how the records of the RRsets in answers are reordered.
+    ///
+    /// The default is to leave the records in the order they were
+    /// inserted in.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotator = Rotator::new(rotation)
+    }
+
+    /// Returns the SOA RRset of the zone if it has one.
+    pub fn soa(&self) -> Option<&SharedRrset> {
+        self.root.rrset(Rtype::Soa)
+    }
+
+    /// Adds a record to the zone.
+    ///
+    /// The record is added to the RRset of its owner and type. If the
+    /// record’s TTL differs from that of the RRset, the RRset gets the
+    /// lower of the two as recommended by RFC 2181.
+    pub fn insert(
+        &mut self,
+        record: StoredRecord,
+    ) -> Result<(), InsertError> {
+        if record.class() != self.class {
+            return Err(InsertError::WrongClass);
+        }
+        let node = self.node_mut(record.owner())?;
+        let rtype = record.rtype();
+        let ttl = record.ttl();
+        let rrset = Arc::make_mut(
+            node.rrsets
+                .entry(rtype)
+                .or_insert_with(|| Arc::new(Rrset::new(rtype, ttl))),
+        );
+        if ttl < rrset.ttl() {
+            rrset.set_ttl(ttl)
+        }
+        rrset.push_data(record.into_data());
+        Ok(())
+    }
+
+    /// Produces the answer to a query for the given name and type.
+    pub fn query<N: ToDname + ?Sized>(
+        &self,
+        qname: &N,
+        qtype: Rtype,
+    ) -> Answer {
+        let mut answer = Answer::from_zone(self, qname, qtype);
+        answer.rotate(&self.rotator);
+        answer
+    }
+
+    /// Returns the node for the apex.
+    pub(super) fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Returns the labels of a name below the apex starting at the apex.
+    ///
+    /// Returns `None` if the name isn’t within the zone.
+    pub(super) fn relative_labels<'a, N: ToDname + ?Sized>(
+        &self,
+        name: &'a N,
+    ) -> Option<impl Iterator<Item = &'a Label>> {
+        if !name.ends_with(&self.apex) {
+            return None;
+        }
+        Some(name.iter_labels().rev().skip(self.apex.label_count()))
+    }
+
+    /// Returns the node for a name, creating it if necessary.
+    fn node_mut(
+        &mut self,
+        name: &StoredDname,
+    ) -> Result<&mut Node, InsertError> {
+        let labels = match self.relative_labels(name) {
+            Some(labels) => labels.map(OwnedLabel::from_label),
+            None => return Err(InsertError::OutOfZone),
+        };
+        let mut node = &mut self.root;
+        for label in labels {
+            node = node.children.entry(label).or_default();
+        }
+        Ok(node)
+    }
+}
+
+//------------ AnyPolicy -----------------------------------------------------
+
+/// How a zone answers queries for QTYPE ANY.
+///
+/// RFC 8482 allows servers to not answer ANY queries with all the RRsets
+/// of a name since these answers are large and popular for amplification
+/// attacks. Instead, a server may answer with a single synthesized HINFO
+/// record or with just one of the RRsets.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum AnyPolicy {
+    /// Answer with all RRsets of the query name.
+    #[default]
+    All,
+
+    /// Answer with one synthesized HINFO record.
+    ///
+    /// The record has a CPU field of `"RFC8482"` and an empty OS field as
+    /// suggested by section 4.2 of RFC 8482. Names without any records
+    /// still get a NODATA answer.
+    Hinfo,
+
+    /// Answer with one of the RRsets of the query name.
+    ///
+    /// The RRset with the lowest record type value is picked so that
+    /// answers are stable.
+    OneRrset,
+}
+
+//------------ Node ----------------------------------------------------------
+
+/// The node for a single name of a zone.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Node {
+    /// The RRsets of the name.
+    rrsets: HashMap<Rtype, SharedRrset>,
+
+    /// The nodes for the names one label below.
+    children: HashMap<OwnedLabel, Node>,
+}
+
+impl Node {
+    /// Returns the RRset of the given type if present.
+    pub fn rrset(&self, rtype: Rtype) -> Option<&SharedRrset> {
+        self.rrsets.get(&rtype)
+    }
+
+    /// Returns an iterator over all the RRsets of the node.
+    pub fn rrsets(&self) -> impl Iterator<Item = &SharedRrset> {
+        self.rrsets.values()
+    }
+
+    /// Returns the child node for the given label if present.
+    pub fn child(&self, label: &Label) -> Option<&Node> {
+        self.children.get(label)
+    }
+
+    /// Returns an iterator over the labels and nodes one label below.
+    pub fn children(&self) -> impl Iterator<Item = (&OwnedLabel, &Node)> {
+        self.children.iter()
+    }
+
+    /// Returns whether the node is the top of a delegated zone.
+    pub fn is_cut(&self) -> bool {
+        self.rrsets.contains_key(&Rtype::Ns)
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ InsertError ---------------------------------------------------
+
+/// A record couldn’t be added to a zone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InsertError {
+    /// The owner of the record isn’t within the zone.
+    OutOfZone,
+
+    /// The class of the record differs from that of the zone.
+    WrongClass,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InsertError::OutOfZone => f.write_str("record out of zone"),
+            InsertError::WrongClass => f.write_str("record of wrong class"),
+        }
+    }
+}
+
+impl error::Error for InsertError {}